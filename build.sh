@@ -10,16 +10,22 @@ if cargo --version &>/dev/null; then
   mkdir -p bin
   mv ./target/release/oxidate bin/
   mv ./target/release/ignite bin/
+  mv ./target/release/rstfmt bin/
+  mv ./target/release/rustscript bin/
   echo "Build complete. Executables are in the bin directory."
 
   echo "Adding temporary aliases for executables..."
   CWD=$(pwd)
   alias oxidate="$CWD/bin/oxidate"
   alias ignite="$CWD/bin/ignite"
+  alias rstfmt="$CWD/bin/rstfmt"
+  alias rustscript="$CWD/bin/rustscript"
 
   echo "To use the executables, run the following commands:"
+  echo "rustscript --help"
   echo "oxidate --help"
   echo "ignite --help"
+  echo "rstfmt --help"
 
 else
   echo "Rust is not installed. Please install Rust to proceed."
@@ -0,0 +1,58 @@
+use anyhow::Result;
+use parser::edition::Edition;
+use parser::Parser;
+
+/// Output format for `--emit-ast`
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum AstFormat {
+    /// Indented tree, for reading
+    Tree,
+    /// JSON, for external tooling
+    Json,
+}
+
+/// Parse the program and render its AST in the given format. Does not type check.
+pub fn dump_ast(inp: &str, format: AstFormat, edition: Edition) -> Result<String> {
+    let mut parser = Parser::new_from_string(inp);
+    parser.set_edition(edition);
+    let program = parser.parse()?;
+
+    let out = match format {
+        AstFormat::Tree => format!("{:#?}", program),
+        AstFormat::Json => serde_json::to_string_pretty(&program)?,
+    };
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dump_ast_tree() {
+        let out = dump_ast("let x = 2 + 3;", AstFormat::Tree, Edition::default()).unwrap();
+        assert!(out.contains("LetStmt"));
+        assert!(out.contains("BinOpExpr"));
+        assert!(out.contains("Integer(\n"));
+    }
+
+    #[test]
+    fn test_dump_ast_json() {
+        let out = dump_ast("let x : int = 2; x", AstFormat::Json, Edition::default()).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&out).unwrap();
+
+        let decl = &json["decls"][0]["LetStmt"];
+        assert_eq!(decl["ident"], "x");
+        assert_eq!(decl["type_ann"], "Int");
+        assert_eq!(decl["expr"]["Integer"], 2);
+        assert_eq!(json["last_expr"]["Symbol"], "x");
+        assert_eq!(json["symbols"][0], "x");
+    }
+
+    #[test]
+    fn test_dump_ast_parse_err() {
+        let err = dump_ast("let x = ;", AstFormat::Json, Edition::default()).unwrap_err();
+        assert!(err.to_string().contains("[ParseError]"));
+    }
+}
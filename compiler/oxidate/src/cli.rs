@@ -0,0 +1,410 @@
+//! The oxidate command line, also run by rustscript build and rustscript check.
+
+use anyhow::{Error, Result};
+use bytecode::{read_o2, write_o2, O2File, O2Header, SourceMap};
+use clap::{CommandFactory, FromArgMatches, Subcommand};
+use diagnostics::{Diagnostic, ToDiagnostics};
+use parser::edition::Edition;
+use serde_json::json;
+use std::{ffi::OsString, io::Read, path::Path};
+
+use crate::ast_dump::{dump_ast, AstFormat};
+use crate::compiler::{self, CompileError};
+use crate::linker::link;
+use crate::pipeline::{check, compile, compile_files, CompilationResult, Options};
+
+const RST: &str = "rst";
+const O2: &str = "o2";
+
+#[derive(clap::Parser, Debug)]
+#[command(name = "Oxidate")]
+#[command(version = "0.1.0")]
+#[command(about = "Compiler for RustScript", long_about = None)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// File containing RustScript code. Must have extension .rst
+    #[arg(required = true)]
+    file: Option<String>,
+
+    /// Output name (to be suffixed by .o2)
+    #[arg(short, long)]
+    out: Option<String>,
+
+    /// Don't type check the program. The .o2 file records this, and ignite warns when running it
+    #[arg(short = 'n', long = "no-typecheck")]
+    notype: bool,
+
+    /// Fail instead of writing the .o2 file if type checking gives any warnings
+    #[arg(long, conflicts_with = "notype")]
+    deny_warnings: bool,
+
+    /// Inline calls of small functions that don't call themselves, saving the cost of the call
+    #[arg(long)]
+    inline: bool,
+
+    /// Load builtin constants like PI and MAX_INT as literals instead of looking up their names,
+    /// wherever the program doesn't declare or assign the name itself
+    #[arg(long)]
+    fold_constants: bool,
+
+    /// Print the parsed AST instead of compiling: --emit-ast for a tree, --emit-ast=json for JSON
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    #[arg(num_args = 0..=1, require_equals = true, default_missing_value = "tree")]
+    emit_ast: Option<AstFormat>,
+
+    /// Print diagnostics and a success/failure summary as JSON lines instead of human-readable text
+    #[arg(long, conflicts_with = "emit_ast")]
+    json: bool,
+
+    /// Edition of the language the program is written in: 2026, or 2027 which also makes while,
+    /// for, in, struct, enum, match and continue keywords
+    #[arg(long, global = true, default_value_t = Edition::default())]
+    edition: Edition,
+
+    /// Don't warn about expression statements whose result is unused, like `x == 3;`
+    #[arg(long, global = true)]
+    allow_unused_results: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Parse and type check a .rst file and print its diagnostics, without compiling it. Exits with
+    /// an error if there are errors.
+    Check {
+        /// File containing RustScript code. Must have extension .rst
+        file: String,
+
+        /// Fail if type checking gives any warnings
+        #[arg(long)]
+        deny_warnings: bool,
+
+        /// Print diagnostics and a success/failure summary as JSON lines
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Link .rst or compiled .o2 files into one program that runs them in order. Each file can use
+    /// the top level functions and variables of the files before it.
+    Link {
+        /// Files to link, in the order they run. .rst files are compiled first, type checked as if
+        /// they followed the .rst files before them
+        #[arg(required = true)]
+        files: Vec<String>,
+
+        /// Output file, .o2 is added if missing
+        #[arg(short, long)]
+        out: String,
+
+        /// Don't type check the .rst files
+        #[arg(short = 'n', long = "no-typecheck")]
+        notype: bool,
+    },
+}
+
+/// Run the compiler with command line args, the first of which is the program name. Usage and
+/// help show bin_name as the program, so other binaries can run it as one of their subcommands.
+pub fn main<I, T>(bin_name: &str, args: I) -> Result<()>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    let matches = Args::command()
+        .bin_name(bin_name.to_owned())
+        .get_matches_from(args);
+    let args = Args::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+
+    match &args.command {
+        Some(Command::Link { files, out, notype }) => {
+            let options = Options {
+                typecheck: !notype,
+                edition: args.edition,
+                call_main: false,
+                warn_unused_results: !args.allow_unused_results,
+                ..Options::default()
+            };
+            return link_files(files, out, &options);
+        }
+        Some(Command::Check {
+            file,
+            deny_warnings,
+            json,
+        }) => return check_file(file, *deny_warnings, *json, &compile_options(&args)),
+        None => (),
+    }
+
+    if args.json {
+        return compile_json(&args);
+    }
+
+    let code = read_source(source_file(&args))?;
+
+    if let Some(format) = args.emit_ast {
+        let ast = dump_ast(&code, format, args.edition)
+            .map_err(|err| Error::msg(format!("\n{}", err)))?;
+        println!("{}", ast);
+        return Ok(());
+    }
+
+    let compiled = match compile(&code, &compile_options(&args)) {
+        Ok(res) if warnings_denied(&res, &args) => {
+            let e = format!(
+                "\n{}\nCompilation failed: {} warning(s) denied by --deny-warnings",
+                diagnostics::render(&res.warnings),
+                res.warnings.len()
+            );
+            return Err(Error::msg(e));
+        }
+        Ok(res) => {
+            if !res.warnings.is_empty() {
+                eprintln!("{}", diagnostics::render(&res.warnings));
+            }
+            res
+        }
+        Err(err) => {
+            let e = format!("\n{}", diagnostics::render(&compiler::diagnostics(&err)));
+            return Err(Error::msg(e));
+        }
+    };
+
+    let bc_name = write_output(&compiled, &args)?;
+    println!("Compiled successfully to {}", bc_name);
+
+    Ok(())
+}
+
+fn compile_options(args: &Args) -> Options {
+    Options {
+        typecheck: !args.notype,
+        inline: args.inline,
+        fold_constants: args.fold_constants,
+        edition: args.edition,
+        warn_unused_results: !args.allow_unused_results,
+        ..Options::default()
+    }
+}
+
+/// --deny-warnings: warnings stop compilation like errors
+fn warnings_denied(compiled: &CompilationResult, args: &Args) -> bool {
+    args.deny_warnings && !compiled.warnings.is_empty()
+}
+
+/// The .rst file to compile, which clap requires unless a subcommand is given
+fn source_file(args: &Args) -> &str {
+    args.file
+        .as_deref()
+        .expect("File is required without a subcommand")
+}
+
+/// Check the file is an existing .rst file and read it
+fn read_source(file: &str) -> Result<String, CompileError> {
+    let path = Path::new(file);
+
+    if !path.exists() {
+        let err = format!("File '{}' does not exist", file);
+        return Err(CompileError::new(&err));
+    }
+
+    if path.extension().is_none_or(|ext| ext != RST) {
+        let err = format!("File {} does not have extension .{RST}", file);
+        return Err(CompileError::new(&err));
+    }
+
+    let mut code: String = String::new();
+    std::fs::File::open(file)
+        .expect("File should exist")
+        .read_to_string(&mut code)
+        .map_err(|err| CompileError::new(&format!("Could not read {}: {}", file, err)))?;
+
+    Ok(code)
+}
+
+/// Write the bytecode, its header and source map to the .o2 file named by --out, or after the source file.
+/// Returns the file name.
+fn write_output(compiled: &CompilationResult, args: &Args) -> Result<String> {
+    let out_name = match &args.out {
+        Some(name) => name.to_owned(),
+        None => Path::new(source_file(args))
+            .file_stem()
+            .expect("File exists")
+            .to_owned()
+            .into_string()
+            .expect("File name should be valid string"),
+    };
+
+    let bc_name = format!("{}.o2", out_name);
+    let mut bc_file = std::fs::File::create(&bc_name)?;
+    let o2 = O2File {
+        header: Some(O2Header {
+            typechecked: !args.notype,
+        }),
+        bytecode: compiled.bytecode.clone(),
+        source_map: Some(SourceMap::new(source_file(args), compiled.lines.clone())),
+    };
+    write_o2(&o2, &mut bc_file)?;
+
+    Ok(bc_name)
+}
+
+/// link: compile the .rst files, read the compiled files, link them and write the program to out
+fn link_files(files: &[String], out: &str, options: &Options) -> Result<()> {
+    let mut sources = vec![];
+    for file in files {
+        if Path::new(file).extension().is_some_and(|ext| ext == RST) {
+            sources.push((file.to_owned(), read_source(file)?));
+        }
+    }
+    let mut compiled = compile_files(&sources, options)
+        .map_err(|(file, err)| {
+            let e = format!(
+                "\nIn {}:\n{}",
+                file,
+                diagnostics::render(&compiler::diagnostics(&err))
+            );
+            Error::msg(e)
+        })?
+        .into_iter();
+
+    let mut modules = Vec::with_capacity(files.len());
+    let mut typechecked = true;
+    for file in files {
+        let path = Path::new(file);
+        if path.extension().is_some_and(|ext| ext == RST) {
+            let res = compiled.next().expect("Every .rst file is compiled");
+            if !res.warnings.is_empty() {
+                eprintln!("{}", diagnostics::render(&res.warnings));
+            }
+            typechecked &= options.typecheck;
+            modules.push((file.to_owned(), res.bytecode));
+            continue;
+        }
+
+        if path.extension().is_none_or(|ext| ext != O2) {
+            let err = format!("File {} does not have extension .{RST} or .{O2}", file);
+            return Err(CompileError::new(&err).into());
+        }
+
+        let mut reader = std::fs::File::open(path)
+            .map_err(|err| CompileError::new(&format!("Could not read {}: {}", file, err)))?;
+        let o2 = read_o2(&mut reader)
+            .map_err(|err| CompileError::new(&format!("Could not read {}: {}", file, err)))?;
+        // unknown for files without a header, so only checked if every module says so
+        typechecked &= o2.header.is_some_and(|header| header.typechecked);
+        modules.push((file.to_owned(), o2.bytecode));
+    }
+
+    let linked = O2File {
+        header: Some(O2Header { typechecked }),
+        bytecode: link(&modules)?,
+        source_map: None,
+    };
+
+    let out_name = if Path::new(out).extension().is_some_and(|ext| ext == O2) {
+        out.to_owned()
+    } else {
+        format!("{}.{O2}", out)
+    };
+    let mut out_file = std::fs::File::create(&out_name)?;
+    write_o2(&linked, &mut out_file)?;
+    println!("Linked successfully to {}", out_name);
+
+    Ok(())
+}
+
+/// --json: print each diagnostic and then a summary as JSON lines on stdout, for CI and editors.
+/// Exits with code 1 if compilation failed.
+fn compile_json(args: &Args) -> Result<()> {
+    let compiled = read_source(source_file(args))
+        .map_err(|err| err.to_diagnostics())
+        .and_then(|code| {
+            compile(&code, &compile_options(args)).map_err(|err| compiler::diagnostics(&err))
+        });
+
+    let (diags, output) = match compiled {
+        Ok(res) if warnings_denied(&res, args) => (res.warnings, None),
+        Ok(res) => {
+            let bc_name = write_output(&res, args)?;
+            (res.warnings, Some(bc_name))
+        }
+        Err(diags) => (diags, None),
+    };
+
+    let success = output.is_some();
+    print_json(&diags, success, output);
+
+    Ok(())
+}
+
+/// check: print the diagnostics of the file, which is parsed and type checked but not compiled.
+/// Fails if there are errors, or warnings with deny_warnings.
+fn check_file(file: &str, deny_warnings: bool, json: bool, options: &Options) -> Result<()> {
+    let checked = read_source(file)
+        .map_err(|err| err.to_diagnostics())
+        .and_then(|code| check(&code, options).map_err(|err| compiler::diagnostics(&err)));
+
+    let (diags, success) = match checked {
+        Ok(res) => {
+            let success = !deny_warnings || res.warnings.is_empty();
+            (res.warnings, success)
+        }
+        Err(diags) => (diags, false),
+    };
+
+    if json {
+        print_json(&diags, success, None);
+        return Ok(());
+    }
+
+    let errors = diags.iter().filter(|d| d.is_error()).count();
+    match (success, errors) {
+        (true, _) => {
+            if !diags.is_empty() {
+                eprintln!("{}", diagnostics::render(&diags));
+            }
+            println!("No errors found in {}", file);
+            Ok(())
+        }
+        (false, 0) => Err(Error::msg(format!(
+            "\n{}\nCheck failed: {} warning(s) denied by --deny-warnings",
+            diagnostics::render(&diags),
+            diags.len()
+        ))),
+        (false, _) => Err(Error::msg(format!("\n{}", diagnostics::render(&diags)))),
+    }
+}
+
+/// Print each diagnostic and then a summary as JSON lines on stdout. Exits with code 1 unless
+/// success.
+fn print_json(diags: &[Diagnostic], success: bool, output: Option<String>) {
+    for diag in diags.iter() {
+        println!("{}", diagnostic_json(diag));
+    }
+
+    let errors = diags.iter().filter(|d| d.is_error()).count();
+    let summary = json!({
+        "type": "summary",
+        "success": success,
+        "errors": errors,
+        "warnings": diags.len() - errors,
+        "output": output,
+    });
+    println!("{}", summary);
+
+    if !success {
+        std::process::exit(1);
+    }
+}
+
+fn diagnostic_json(diag: &Diagnostic) -> serde_json::Value {
+    json!({
+        "type": "diagnostic",
+        "severity": diag.severity.to_string(),
+        "stage": diag.stage.to_string().to_lowercase(),
+        "code": diag.code,
+        "message": diag.message,
+        "span": diag.span.map(|span| json!({ "line": span.line, "col": span.col })),
+        "notes": diag.notes,
+    })
+}
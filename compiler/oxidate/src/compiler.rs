@@ -1,10 +1,18 @@
 use anyhow::Result;
+use diagnostics::{Diagnostic, Stage, ToDiagnostics, COMPILE_ERROR};
 use std::{fmt::Display, rc::Rc, vec};
-use types::type_checker::TypeChecker;
+use types::check_parallel::check_parallel_loops;
+use types::type_checker::TypeErrors;
 
-use bytecode::{BinOp, ByteCode, Value};
+use crate::parallel::lower_parallel_loop;
+use crate::pipeline::{compile, Options};
+#[cfg(any(test, feature = "debug-compiler"))]
+use crate::sanity;
+
+use bytecode::{builtin, BinOp, ByteCode, LineTable, SelectOp, UnOp, Value};
 use parser::structs::{
-    BinOpType, BlockSeq, Decl, Expr, FnCallData, FnDeclData, IfElseData, LoopData, UnOpType,
+    BinOpType, BlockSeq, Decl, Expr, FnCallData, FnDeclData, IfElseData, LetStmtData, LoopData,
+    ParallelLoopData, ParseError, QueueOp, SelectArm, SelectData, Type, UnOpType,
 };
 
 pub struct Compiler {
@@ -12,6 +20,18 @@ pub struct Compiler {
     // Tracks idx in bytecode for any nested break stmts compiled for that loop. Stack of vecs since we can have nested loops
     // and break should only break the closest enclosing loop
     loop_stack: Vec<Vec<usize>>,
+    // Number of enclosing function bodies, so a return outside any function is rejected even when type checking is off
+    fn_depth: usize,
+    // loop_stack length at each enclosing scope block in the current function, innermost last. A break
+    // or return can't leave a scope block, whose end joins the threads spawned in it
+    thread_scopes: Vec<usize>,
+    // Line of the instructions compiled so far
+    lines: LineTable,
+    // Line of the statement being compiled
+    line: Option<usize>,
+    // Names declared by the enclosing blocks and functions, and by the programs run before this one.
+    // A call is only compiled as a builtin's, e.g. spawn_limited, if its name isn't one of them
+    bound: Vec<String>,
 }
 
 #[derive(Debug, PartialEq)]
@@ -35,19 +55,99 @@ impl Display for CompileError {
 
 impl std::error::Error for CompileError {}
 
-// Workaround to ensure builtins that dont pop produce Unit when compiling fn call
-// Because user functions even if empty will produce unit (everything is value producing), so
-// this issue only applies to builtins with no value pushed
-const BUILTINS_WITH_NO_VAL: [&str; 3] = ["println", "print", "sem_set"];
+impl ToDiagnostics for CompileError {
+    fn to_diagnostics(&self) -> Vec<Diagnostic> {
+        vec![Diagnostic::error(Stage::Compile, COMPILE_ERROR, &self.msg)]
+    }
+}
+
+/// Diagnostics for an error returned by compile_from_string, whichever stage it came from
+pub fn diagnostics(err: &anyhow::Error) -> Vec<Diagnostic> {
+    if let Some(err) = err.downcast_ref::<ParseError>() {
+        err.to_diagnostics()
+    } else if let Some(err) = err.downcast_ref::<TypeErrors>() {
+        err.to_diagnostics()
+    } else if let Some(err) = err.downcast_ref::<CompileError>() {
+        err.to_diagnostics()
+    } else {
+        vec![Diagnostic::error(
+            Stage::Compile,
+            COMPILE_ERROR,
+            &err.to_string(),
+        )]
+    }
+}
+
+/// Target of a jump that has not been patched yet. No program is this long, so the VM fails on it
+/// instead of jumping somewhere plausible.
+pub(crate) const UNPATCHED: usize = usize::MAX;
+
+// spawn_limited(f, budget) is compiled like spawn f() with an instruction budget for the child
+const SPAWN_LIMITED: &str = "spawn_limited";
+
+// Names a select keeps the result of its SELECT in. They have spaces so they can't clash with the
+// program's names.
+const SELECT_ARM: &str = "select arm";
+const SELECT_VALUE: &str = "select value";
+
+/// Escape analysis for a block's environment, given the block's bytecode: it can outlive the block if
+/// a closure is created in it or a thread is spawned in it, including in nested blocks, since both
+/// keep the environment they were created in.
+pub(crate) fn scope_escapes(scope: &[ByteCode]) -> bool {
+    scope.iter().any(|instr| {
+        matches!(
+            instr,
+            ByteCode::LDF(_, _)
+                | ByteCode::SPAWN(_, _)
+                | ByteCode::SPAWNISOLATED(_, _)
+                | ByteCode::SPAWNLIMITED(_, _)
+        )
+    })
+}
+
+/// The body of a select arm, run as { let x = select value; body } if it binds the popped value
+fn select_arm_block(arm: &SelectArm) -> BlockSeq {
+    let Some(binding) = &arm.binding else {
+        return arm.body.clone();
+    };
+
+    BlockSeq {
+        decls: vec![Decl::LetStmt(LetStmtData {
+            ident: binding.clone(),
+            expr: Expr::Symbol(SELECT_VALUE.to_string()),
+            type_ann: None,
+        })],
+        last_expr: Some(Rc::new(Expr::BlockExpr(arm.body.clone()))),
+        symbols: vec![binding.clone()],
+        lines: vec![],
+    }
+}
 
 impl Compiler {
     pub fn new(program: BlockSeq) -> Compiler {
+        Compiler::with_outer(program, &[])
+    }
+
+    /// Compiler for a program run after others, like the lines of a REPL session, whose top level
+    /// names are outer
+    pub fn with_outer(program: BlockSeq, outer: &[String]) -> Compiler {
         Compiler {
             program,
             loop_stack: vec![],
+            fn_depth: 0,
+            thread_scopes: vec![],
+            lines: vec![],
+            line: None,
+            bound: outer.to_vec(),
         }
     }
 
+    /// Whether a call to name calls the builtin function, rather than something the program
+    /// declares with its name
+    fn calls_builtin(&self, name: &str, builtin: &str) -> bool {
+        name == builtin && !self.bound.iter().any(|bound| bound == name)
+    }
+
     fn compile_unop(
         &mut self,
         op: &UnOpType,
@@ -58,6 +158,8 @@ impl Compiler {
         match op {
             UnOpType::Negate => arr.push(ByteCode::UNOP(bytecode::UnOp::Neg)),
             UnOpType::Not => arr.push(ByteCode::UNOP(bytecode::UnOp::Not)),
+            // +x is x
+            UnOpType::Plus => (),
         }
         Ok(())
     }
@@ -78,12 +180,14 @@ impl Compiler {
                     decls: vec![],
                     last_expr: Some(Rc::new(rhs.clone())),
                     symbols: vec![],
+                    lines: vec![],
                 };
 
                 let else_blk = BlockSeq {
                     decls: vec![],
                     last_expr: Some(Rc::new(Expr::Bool(false))),
                     symbols: vec![],
+                    lines: vec![],
                 };
 
                 let stmt = IfElseData {
@@ -101,12 +205,14 @@ impl Compiler {
                     decls: vec![],
                     last_expr: Some(Rc::new(Expr::Bool(true))),
                     symbols: vec![],
+                    lines: vec![],
                 };
 
                 let else_blk = BlockSeq {
                     decls: vec![],
                     last_expr: Some(Rc::new(rhs.clone())),
                     symbols: vec![],
+                    lines: vec![],
                 };
 
                 let stmt = IfElseData {
@@ -146,6 +252,8 @@ impl Compiler {
             BinOpType::Sub => arr.push(ByteCode::BINOP(bytecode::BinOp::Sub)),
             BinOpType::Gt => arr.push(ByteCode::BINOP(BinOp::Gt)),
             BinOpType::Lt => arr.push(ByteCode::BINOP(BinOp::Lt)),
+            BinOpType::Ge => arr.push(ByteCode::BINOP(BinOp::Ge)),
+            BinOpType::Le => arr.push(ByteCode::BINOP(BinOp::Le)),
             BinOpType::LogicalEq => arr.push(ByteCode::BINOP(BinOp::Eq)),
             // Rest are and/or: handled above
             _ => unreachable!(),
@@ -179,7 +287,29 @@ impl Compiler {
             }
             Expr::IfElseExpr(if_else) => self.compile_if_else(if_else, arr)?,
             Expr::FnCallExpr(fn_call) => self.compile_fn_call(fn_call, arr)?,
-            Expr::SpawnExpr(fn_call) => self.compile_spawn(fn_call, arr)?,
+            Expr::CastExpr(expr, ty) => {
+                self.compile_expr(expr, arr)?;
+                let op = match ty {
+                    Type::Int => UnOp::ToInt,
+                    Type::Float => UnOp::ToFloat,
+                    Type::Bool => UnOp::ToBool,
+                    _ => {
+                        let e = format!("Can't cast to '{}'", ty);
+                        return Err(CompileError::new(&e));
+                    }
+                };
+                arr.push(ByteCode::UNOP(op));
+            }
+            // callee is loaded like any other value, then called
+            Expr::CallExpr(callee, args) => {
+                self.compile_expr(callee, arr)?;
+                for arg in args.iter() {
+                    self.compile_expr(arg, arr)?;
+                }
+                arr.push(ByteCode::CALL(args.len()));
+            }
+            Expr::SpawnExpr(fn_call) => self.compile_spawn(fn_call, false, arr)?,
+            Expr::SpawnIsolatedExpr(fn_call) => self.compile_spawn(fn_call, true, arr)?,
             Expr::JoinExpr(id) => {
                 arr.push(ByteCode::ld(id));
                 arr.push(ByteCode::JOIN);
@@ -192,25 +322,39 @@ impl Compiler {
     fn compile_spawn(
         &mut self,
         fn_call: &FnCallData,
+        isolated: bool,
         arr: &mut Vec<ByteCode>,
     ) -> Result<(), CompileError> {
-        // dbg!("SPAWN COMPILE:", _fn_call);
+        if self.calls_builtin(&fn_call.name, SPAWN_LIMITED) {
+            return Err(CompileError::new(
+                "spawn_limited can't be spawned, spawn a function that calls it",
+            ));
+        }
+
+        // the parent evaluates the function and its arguments, which SPAWN moves to the child
+        let arity = self.compile_call_operands(fn_call, arr)?;
+
         let spawn_idx = arr.len();
-        arr.push(ByteCode::SPAWN(0));
+        if isolated {
+            arr.push(ByteCode::SPAWNISOLATED(UNPATCHED, arity + 1));
+        } else {
+            arr.push(ByteCode::SPAWN(UNPATCHED, arity + 1));
+        }
 
         let goto_idx = arr.len();
-        arr.push(ByteCode::GOTO(0));
+        arr.push(ByteCode::GOTO(UNPATCHED));
 
         // spawn jumps to POP which is added after this
         let spawn_jmp = arr.len();
-        if let Some(ByteCode::SPAWN(jmp)) = arr.get_mut(spawn_idx) {
+        if let Some(ByteCode::SPAWN(jmp, _) | ByteCode::SPAWNISOLATED(jmp, _)) =
+            arr.get_mut(spawn_idx)
+        {
             *jmp = spawn_jmp;
         }
 
-        // child pops value on its stack
+        // child pops value on its stack, then calls the function with the arguments under it
         arr.push(ByteCode::POP);
-
-        self.compile_fn_call(fn_call, arr)?;
+        arr.push(ByteCode::CALL(arity));
         arr.push(ByteCode::DONE); // child thread finishes
 
         let goto_jmp = arr.len();
@@ -220,6 +364,52 @@ impl Compiler {
             *jmp = goto_jmp;
         }
 
+        #[cfg(any(test, feature = "debug-compiler"))]
+        sanity::check_patched(arr, &[spawn_idx, goto_idx], fn_call);
+
+        Ok(())
+    }
+
+    /// spawn_limited(f, budget): spawn a thread calling f(), which is terminated after executing budget instructions
+    fn compile_spawn_limited(
+        &mut self,
+        fn_call: &FnCallData,
+        arr: &mut Vec<ByteCode>,
+    ) -> Result<(), CompileError> {
+        let [func, budget] = fn_call.args.as_slice() else {
+            return Err(CompileError::new(
+                "spawn_limited expects a function and an instruction budget",
+            ));
+        };
+
+        // f is moved to the child, budget is popped by SPAWNLIMITED in the parent
+        self.compile_expr(func, arr)?;
+        self.compile_expr(budget, arr)?;
+
+        let spawn_idx = arr.len();
+        arr.push(ByteCode::SPAWNLIMITED(UNPATCHED, 1));
+
+        let goto_idx = arr.len();
+        arr.push(ByteCode::GOTO(UNPATCHED));
+
+        let spawn_jmp = arr.len();
+        if let Some(ByteCode::SPAWNLIMITED(jmp, _)) = arr.get_mut(spawn_idx) {
+            *jmp = spawn_jmp;
+        }
+
+        // child pops value on its stack, then calls f with no arguments
+        arr.push(ByteCode::POP);
+        arr.push(ByteCode::CALL(0));
+        arr.push(ByteCode::DONE);
+
+        let goto_jmp = arr.len();
+        if let Some(ByteCode::GOTO(jmp)) = arr.get_mut(goto_idx) {
+            *jmp = goto_jmp;
+        }
+
+        #[cfg(any(test, feature = "debug-compiler"))]
+        sanity::check_patched(arr, &[spawn_idx, goto_idx], fn_call);
+
         Ok(())
     }
 
@@ -248,13 +438,27 @@ impl Compiler {
         arr: &mut Vec<ByteCode>,
     ) -> Result<(), CompileError> {
         let decls = &blk.decls;
-        let syms = &blk.symbols;
 
+        // A name declared more than once in a block has one variable: a later let assigns the same
+        // variable, so functions declared in between see the new value. List each name once.
+        let mut syms: Vec<String> = Vec::with_capacity(blk.symbols.len());
+        for sym in blk.symbols.iter() {
+            if !syms.contains(sym) {
+                syms.push(sym.clone());
+            }
+        }
+
+        let scope_start = arr.len();
         if !syms.is_empty() {
             arr.push(ByteCode::ENTERSCOPE(syms.clone()));
         }
+        let outer_bound = self.bound.len();
+        self.bound.extend(syms.iter().cloned());
 
-        for decl in decls {
+        let outer_line = self.line;
+
+        for (idx, decl) in decls.iter().enumerate() {
+            self.mark_line(blk.lines.get(idx).copied(), arr.len());
             self.compile_decl(decl, arr)?;
             // pop result of statements - need to ensure all stmts produce something (either Unit or something else)
             arr.push(ByteCode::POP);
@@ -262,16 +466,44 @@ impl Compiler {
 
         // Handle expr
         if let Some(expr) = &blk.last_expr {
+            self.mark_line(blk.lines.get(decls.len()).copied(), arr.len());
             self.compile_expr(expr.as_ref(), arr)?;
         }
+        self.bound.truncate(outer_bound);
 
         if !syms.is_empty() {
-            arr.push(ByteCode::EXITSCOPE);
+            if scope_escapes(&arr[scope_start..]) {
+                arr.push(ByteCode::EXITSCOPE);
+            } else {
+                // the VM keeps a scope nothing can capture on the thread's stack
+                arr[scope_start] = ByteCode::ENTERSCOPELOCAL(syms);
+                arr.push(ByteCode::EXITSCOPELOCAL);
+            }
         }
 
+        #[cfg(any(test, feature = "debug-compiler"))]
+        sanity::check_scopes(&arr[scope_start..], blk);
+
+        // the rest of the statement the block is in
+        self.mark_line(outer_line, arr.len());
+
         Ok(())
     }
 
+    /// Record that the instructions from addr on are compiled from line, if it is known
+    fn mark_line(&mut self, line: Option<usize>, addr: usize) {
+        let Some(line) = line else {
+            return;
+        };
+        self.line = Some(line);
+
+        match self.lines.last_mut() {
+            Some((_, last_line)) if *last_line == line => (),
+            Some((last_addr, last_line)) if *last_addr == addr => *last_line = line,
+            _ => self.lines.push((addr, line)),
+        }
+    }
+
     /// Compile block appropriately based on whether it is none-like
     fn compile_block(
         &mut self,
@@ -307,16 +539,36 @@ impl Compiler {
             }
             Decl::IfOnlyStmt(if_else) => self.compile_if_else(if_else, arr)?,
             Decl::LoopStmt(lp) => self.compile_loop(lp, arr)?,
+            Decl::ParallelLoopStmt(lp) => self.compile_parallel_loop(lp, arr)?,
+            Decl::ScopeStmt(blk) => self.compile_scope(blk, arr)?,
+            Decl::SelectStmt(select) => self.compile_select(select, arr)?,
             // push GOTO, push idx of this break in arr onto loop stack
             Decl::BreakStmt => {
                 let break_idx = arr.len();
-                arr.push(ByteCode::GOTO(0));
-                if let Some(breaks) = self.loop_stack.last_mut() {
-                    breaks.push(break_idx);
+                arr.push(ByteCode::GOTO(UNPATCHED));
+                let loops = self.loop_stack.len();
+                let Some(breaks) = self.loop_stack.last_mut() else {
+                    return Err(CompileError::new("'break' outside of a loop"));
+                };
+                // the innermost scope block was entered in the loop being broken out of
+                if self.thread_scopes.last() == Some(&loops) {
+                    return Err(CompileError::new(
+                        "Can't break out of a scope block: it must run to its end to join the threads spawned in it",
+                    ));
                 }
+                breaks.push(break_idx);
             }
             Decl::FnDeclStmt(fn_decl) => self.compile_fn_decl(fn_decl, arr)?,
             Decl::ReturnStmt(ret_stmt) => {
+                if self.fn_depth == 0 {
+                    return Err(CompileError::new("'return' outside of a function"));
+                }
+                if !self.thread_scopes.is_empty() {
+                    return Err(CompileError::new(
+                        "Can't return from a scope block: it must run to its end to join the threads spawned in it",
+                    ));
+                }
+
                 // compile expr. if not there, push Unit
                 if let Some(expr) = ret_stmt {
                     self.compile_expr(expr, arr)?;
@@ -329,12 +581,12 @@ impl Compiler {
             }
             // These don't return anything, so push unit after as well
             Decl::WaitStmt(sem) => {
-                arr.push(ByteCode::ld(sem));
+                self.compile_expr(sem, arr)?;
                 arr.push(ByteCode::WAIT);
                 arr.push(ByteCode::ldc(Value::Unit));
             }
             Decl::PostStmt(sem) => {
-                arr.push(ByteCode::ld(sem));
+                self.compile_expr(sem, arr)?;
                 arr.push(ByteCode::POST);
                 arr.push(ByteCode::ldc(Value::Unit));
             }
@@ -361,7 +613,7 @@ impl Compiler {
 
         // push GOTO for skipping fn compile
         let goto_idx = arr.len();
-        arr.push(ByteCode::GOTO(0));
+        arr.push(ByteCode::GOTO(UNPATCHED));
 
         // add params to fn blk
         // let mut fn_blk = fn_decl.body.clone();
@@ -370,7 +622,19 @@ impl Compiler {
 
         // compile the augmented blk
 
-        self.compile_block(&fn_decl.body, arr)?;
+        // a break in the body can't target a loop enclosing the declaration
+        let outer_loops = std::mem::take(&mut self.loop_stack);
+        let outer_scopes = std::mem::take(&mut self.thread_scopes);
+        let outer_bound = self.bound.len();
+        self.bound
+            .extend(fn_decl.params.iter().map(|param| param.name.clone()));
+        self.fn_depth += 1;
+        let res = self.compile_block(&fn_decl.body, arr);
+        self.fn_depth -= 1;
+        self.bound.truncate(outer_bound);
+        self.loop_stack = outer_loops;
+        self.thread_scopes = outer_scopes;
+        res?;
         // self.compile_block(&fn_blk, arr)?;
 
         // push reset to return last value produced by blk, in case no return was there
@@ -386,6 +650,9 @@ impl Compiler {
             *idx = goto_addr;
         }
 
+        #[cfg(any(test, feature = "debug-compiler"))]
+        sanity::check_patched(arr, &[goto_idx], fn_decl);
+
         Ok(())
     }
 
@@ -395,21 +662,44 @@ impl Compiler {
         fn_call: &FnCallData,
         arr: &mut Vec<ByteCode>,
     ) -> Result<(), CompileError> {
-        // TODO: change to accept arbitary expr for fn
+        if self.calls_builtin(&fn_call.name, SPAWN_LIMITED) {
+            return self.compile_spawn_limited(fn_call, arr);
+        }
+
+        let arity = self.compile_call_operands(fn_call, arr)?;
+        arr.push(ByteCode::CALL(arity));
+
+        Ok(())
+    }
+
+    /// Push the function fn_call calls and its arguments, returning how many arguments CALL takes
+    fn compile_call_operands(
+        &mut self,
+        fn_call: &FnCallData,
+        arr: &mut Vec<ByteCode>,
+    ) -> Result<usize, CompileError> {
+        if !fn_call.named_args.is_empty() {
+            let e = format!(
+                "Named arguments in call to '{}' must be resolved before compiling",
+                fn_call.name
+            );
+            return Err(CompileError::new(&e));
+        }
+
+        // calls on other expressions are Expr::CallExpr
         self.compile_expr(&Expr::Symbol(fn_call.name.clone()), arr)?;
 
         for arg in fn_call.args.iter() {
             self.compile_expr(arg, arr)?;
         }
 
-        arr.push(ByteCode::CALL(fn_call.args.len()));
-
-        // push unit for builtin that produces no value
-        if BUILTINS_WITH_NO_VAL.contains(&fn_call.name.as_str()) {
-            arr.push(ByteCode::ldc(Value::Unit));
+        // dbg(expr) also gets the source text of expr to print
+        if self.calls_builtin(&fn_call.name, builtin::DBG_SYM) && fn_call.args.len() == 1 {
+            arr.push(ByteCode::ldc(fn_call.args[0].to_string()));
+            Ok(2)
+        } else {
+            Ok(fn_call.args.len())
         }
-
-        Ok(())
     }
 
     /// Compile if_else as statement or as expr - changes how blocks are compiled
@@ -420,12 +710,12 @@ impl Compiler {
     ) -> Result<(), CompileError> {
         self.compile_expr(&if_else.cond, arr)?;
         let jof_idx = arr.len();
-        arr.push(ByteCode::JOF(0));
+        arr.push(ByteCode::JOF(UNPATCHED));
 
         self.compile_block(&if_else.if_blk, arr)?;
 
         let goto_idx = arr.len();
-        arr.push(ByteCode::GOTO(0));
+        arr.push(ByteCode::GOTO(UNPATCHED));
 
         // set JOF arg to after GOTO (either else_blk start, or LDC Unit for if-only)
         let len = arr.len();
@@ -446,6 +736,9 @@ impl Compiler {
             *idx = len;
         }
 
+        #[cfg(any(test, feature = "debug-compiler"))]
+        sanity::check_patched(arr, &[jof_idx, goto_idx], if_else);
+
         Ok(())
     }
 
@@ -467,7 +760,7 @@ impl Compiler {
         if let Some(expr) = &loop_data.cond {
             self.compile_expr(expr, arr)?;
             jof_idx.replace(arr.len());
-            arr.push(ByteCode::JOF(0));
+            arr.push(ByteCode::JOF(UNPATCHED));
         }
 
         // loop body
@@ -483,6 +776,9 @@ impl Compiler {
             if let Some(ByteCode::JOF(jmp_idx)) = arr.get_mut(idx) {
                 *jmp_idx = loop_end_idx;
             }
+
+            #[cfg(any(test, feature = "debug-compiler"))]
+            sanity::check_patched(arr, &[idx], loop_data);
         }
 
         Ok(loop_end_idx)
@@ -505,11 +801,6 @@ impl Compiler {
             .last()
             .expect("Loop stack should be present since pushed earlier");
 
-        // Later: can use this to detect infinite loops
-        // if breaks.len() == 0 && loop_data.cond.is_none() {
-        //     dbg!("[WARNING] Breaks was empty: loop has no break");
-        // }
-
         for idx in breaks.iter() {
             let idx = idx.to_owned();
 
@@ -518,29 +809,136 @@ impl Compiler {
             }
         }
 
+        #[cfg(any(test, feature = "debug-compiler"))]
+        sanity::check_patched(arr, breaks, loop_data);
+
         self.loop_stack.pop();
         Ok(())
     }
 
-    pub fn compile(mut self) -> anyhow::Result<Vec<ByteCode>, CompileError> {
+    // scope { ... } => ENTERTHREADSCOPE, block, POP, JOINTHREADSCOPE, LDC Unit
+    // Threads spawned while the block runs, also by functions it calls, are joined before the next statement
+    fn compile_scope(
+        &mut self,
+        blk: &BlockSeq,
+        arr: &mut Vec<ByteCode>,
+    ) -> Result<(), CompileError> {
+        arr.push(ByteCode::ENTERTHREADSCOPE);
+
+        self.thread_scopes.push(self.loop_stack.len());
+        let res = self.compile_block(blk, arr);
+        self.thread_scopes.pop();
+        res?;
+
+        arr.push(ByteCode::POP); // scope is a statement, its block's value is unused
+        arr.push(ByteCode::JOINTHREADSCOPE);
+        arr.push(ByteCode::ldc(Value::Unit));
+        Ok(())
+    }
+
+    // @parallel loop var in start..end { body } => the block parallel::lower_parallel_loop lowers it to
+    fn compile_parallel_loop(
+        &mut self,
+        lp: &ParallelLoopData,
+        arr: &mut Vec<ByteCode>,
+    ) -> Result<(), CompileError> {
+        self.compile_block(&lower_parallel_loop(lp), arr)
+    }
+
+    // select { arms } => ENTERSCOPE [select arm, select value], operands of each arm, SELECT, ASSIGN select arm,
+    // ASSIGN select value, if select arm == 0 { .. } else if .. else { default }, POP, EXITSCOPE, LDC Unit
+    // The SELECT does the first arm's operation that can be done, and the if runs that arm's body
+    fn compile_select(
+        &mut self,
+        select: &SelectData,
+        arr: &mut Vec<ByteCode>,
+    ) -> Result<(), CompileError> {
+        let syms = vec![SELECT_ARM.to_string(), SELECT_VALUE.to_string()];
+        let scope_start = arr.len();
+        arr.push(ByteCode::ENTERSCOPE(syms.clone()));
+
+        let mut ops = vec![];
+        for arm in select.arms.iter() {
+            for operand in arm.op.operands() {
+                self.compile_expr(operand, arr)?;
+            }
+            ops.push(match arm.op {
+                QueueOp::Pop(_) => SelectOp::Pop,
+                QueueOp::Push(..) => SelectOp::Push,
+            });
+        }
+        arr.push(ByteCode::SELECT(ops, select.default.is_some()));
+        arr.push(ByteCode::assign(SELECT_ARM));
+        arr.push(ByteCode::assign(SELECT_VALUE));
+
+        // built from the last arm back, so each arm is the else of the one before
+        let mut dispatch = select.default.clone();
+        for (idx, arm) in select.arms.iter().enumerate().rev() {
+            let if_else = IfElseData {
+                cond: Expr::BinOpExpr(
+                    BinOpType::LogicalEq,
+                    Box::new(Expr::Symbol(SELECT_ARM.to_string())),
+                    Box::new(Expr::Integer(idx as i64)),
+                ),
+                if_blk: select_arm_block(arm),
+                else_blk: dispatch,
+            };
+            dispatch = Some(BlockSeq {
+                decls: vec![],
+                last_expr: Some(Rc::new(Expr::IfElseExpr(Box::new(if_else)))),
+                symbols: vec![],
+                lines: vec![],
+            });
+        }
+        if let Some(dispatch) = dispatch {
+            self.compile_block(&dispatch, arr)?;
+            arr.push(ByteCode::POP); // select is a statement, its arm's value is unused
+        }
+
+        if scope_escapes(&arr[scope_start..]) {
+            arr.push(ByteCode::EXITSCOPE);
+        } else {
+            arr[scope_start] = ByteCode::ENTERSCOPELOCAL(syms);
+            arr.push(ByteCode::EXITSCOPELOCAL);
+        }
+        arr.push(ByteCode::ldc(Value::Unit));
+        Ok(())
+    }
+
+    pub fn compile(self) -> anyhow::Result<Vec<ByteCode>, CompileError> {
+        Ok(self.compile_with_lines()?.0)
+    }
+
+    /// Compile the program, also returning the line of the source each instruction came from
+    pub fn compile_with_lines(
+        mut self,
+    ) -> anyhow::Result<(Vec<ByteCode>, LineTable), CompileError> {
+        // @parallel loops are checked again here, since the program may not have been type checked
+        if let Some((_, e)) = check_parallel_loops(&self.program).into_iter().next() {
+            return Err(CompileError::new(&e));
+        }
+
         let mut bytecode: Vec<ByteCode> = vec![];
         let prog = self.program.clone();
         self.compile_block_body(&prog, &mut bytecode)?;
         bytecode.push(ByteCode::DONE);
 
-        Ok(bytecode)
+        #[cfg(any(test, feature = "debug-compiler"))]
+        {
+            sanity::check_done(&self.loop_stack, self.fn_depth);
+            sanity::check_program(&bytecode, "compile");
+        }
+
+        Ok((bytecode, self.lines))
     }
 }
 
-/// Takes in a string and returns compiled bytecode or errors
+/// Takes in a string and returns compiled bytecode or errors.
+/// Shorthand for pipeline::compile with the default options and type checking on or off.
 pub fn compile_from_string(inp: &str, type_check: bool) -> Result<Vec<ByteCode>> {
-    let parser = parser::Parser::new_from_string(inp);
-    let program = parser.parse()?;
-
-    if type_check {
-        TypeChecker::new(&program).type_check()?;
-    }
-
-    let compiler = Compiler::new(program);
-    Ok(compiler.compile()?)
+    let options = Options {
+        typecheck: type_check,
+        ..Options::default()
+    };
+    Ok(compile(inp, &options)?.bytecode)
 }
@@ -0,0 +1,222 @@
+use std::{
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
+
+use bytecode::{builtin, Value};
+use parser::structs::{BlockSeq, Decl, Expr, QueueOp};
+
+use crate::inline::assigned_names_block;
+
+/// Replace uses of the builtin math and environment constants, like PI and MAX_INT, with their
+/// values, so they compile to LDC instead of an LD that looks the name up through every
+/// environment up to the global one.
+///
+/// A constant is only replaced where its name still means the constant:
+/// - not in a block or function that declares the name, including a parameter
+/// - not anywhere if the program assigns to the name
+/// - not anywhere if it is in outer, the names declared before the program runs, e.g. by the files
+///   linked before it
+pub fn fold_constants(program: &mut BlockSeq, outer: &[String]) {
+    let mut assigned = HashSet::new();
+    assigned_names_block(program, &mut assigned);
+
+    let constants = builtin::constants()
+        .into_iter()
+        .filter(|(sym, _)| !assigned.contains(*sym) && !outer.iter().any(|name| name == sym))
+        .filter_map(|(sym, val)| Some((sym.to_string(), literal(val)?)))
+        .collect();
+
+    let mut folder = Folder {
+        constants,
+        bound: vec![],
+    };
+    folder.block(program);
+}
+
+/// The literal a constant's value is written as
+fn literal(val: Value) -> Option<Expr> {
+    match val {
+        Value::Int(val) => Some(Expr::Integer(val)),
+        Value::Float(val) => Some(Expr::Float(val)),
+        _ => None,
+    }
+}
+
+struct Folder {
+    constants: HashMap<String, Expr>,
+    // names declared by the enclosing blocks and functions
+    bound: Vec<String>,
+}
+
+impl Folder {
+    fn block(&mut self, blk: &mut BlockSeq) {
+        let outer = self.bound.len();
+        self.bound.extend(blk.symbols.iter().cloned());
+
+        for decl in blk.decls.iter_mut() {
+            self.decl(decl);
+        }
+        if let Some(expr) = blk.last_expr.as_mut() {
+            self.expr(Rc::make_mut(expr));
+        }
+
+        self.bound.truncate(outer);
+    }
+
+    fn decl(&mut self, decl: &mut Decl) {
+        match decl {
+            Decl::LetStmt(stmt) => self.expr(&mut stmt.expr),
+            Decl::AssignStmt(stmt) => self.expr(&mut stmt.expr),
+            Decl::ExprStmt(expr)
+            | Decl::ReturnStmt(Some(expr))
+            | Decl::WaitStmt(expr)
+            | Decl::PostStmt(expr) => self.expr(expr),
+            Decl::IfOnlyStmt(if_else) => {
+                self.expr(&mut if_else.cond);
+                self.block(&mut if_else.if_blk);
+            }
+            Decl::LoopStmt(lp) => {
+                if let Some(cond) = lp.cond.as_mut() {
+                    self.expr(cond);
+                }
+                self.block(&mut lp.body);
+            }
+            Decl::ParallelLoopStmt(lp) => {
+                self.expr(&mut lp.start);
+                self.expr(&mut lp.end);
+                let outer = self.bound.len();
+                self.bound.push(lp.var.clone());
+                self.block(&mut lp.body);
+                self.bound.truncate(outer);
+            }
+            Decl::ScopeStmt(blk) => self.block(blk),
+            Decl::SelectStmt(select) => {
+                for arm in select.arms.iter_mut() {
+                    match &mut arm.op {
+                        QueueOp::Pop(q) => self.expr(q),
+                        QueueOp::Push(q, v) => {
+                            self.expr(q);
+                            self.expr(v);
+                        }
+                    }
+                    let outer = self.bound.len();
+                    self.bound.extend(arm.binding.iter().cloned());
+                    self.block(&mut arm.body);
+                    self.bound.truncate(outer);
+                }
+                if let Some(default) = select.default.as_mut() {
+                    self.block(default);
+                }
+            }
+            Decl::FnDeclStmt(fn_decl) => {
+                let outer = self.bound.len();
+                self.bound
+                    .extend(fn_decl.params.iter().map(|p| p.name.clone()));
+                self.block(&mut fn_decl.body);
+                self.bound.truncate(outer);
+            }
+            Decl::ReturnStmt(None) | Decl::BreakStmt | Decl::YieldStmt => (),
+        }
+    }
+
+    fn expr(&mut self, expr: &mut Expr) {
+        match expr {
+            Expr::Symbol(sym) => {
+                if self.bound.contains(sym) {
+                    return;
+                }
+                if let Some(val) = self.constants.get(sym) {
+                    *expr = val.clone();
+                }
+            }
+            Expr::UnOpExpr(_, e) | Expr::CastExpr(e, _) => self.expr(e),
+            Expr::BinOpExpr(_, lhs, rhs) => {
+                self.expr(lhs);
+                self.expr(rhs);
+            }
+            Expr::BlockExpr(blk) => self.block(blk),
+            Expr::IfElseExpr(if_else) => {
+                self.expr(&mut if_else.cond);
+                self.block(&mut if_else.if_blk);
+                if let Some(else_blk) = if_else.else_blk.as_mut() {
+                    self.block(else_blk);
+                }
+            }
+            Expr::CallExpr(callee, args) => {
+                self.expr(callee);
+                args.iter_mut().for_each(|arg| self.expr(arg));
+            }
+            Expr::FnCallExpr(call) | Expr::SpawnExpr(call) | Expr::SpawnIsolatedExpr(call) => {
+                call.args.iter_mut().for_each(|arg| self.expr(arg));
+                call.named_args
+                    .iter_mut()
+                    .for_each(|(_, arg)| self.expr(arg));
+            }
+            Expr::Integer(_)
+            | Expr::Float(_)
+            | Expr::Bool(_)
+            | Expr::StringLiteral(_)
+            | Expr::JoinExpr(_) => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytecode::ByteCode;
+    use parser::Parser;
+
+    use super::fold_constants;
+    use crate::compiler::Compiler;
+
+    fn expect_fold(inp: &str, outer: &[&str], exp: &str) {
+        let mut prog = Parser::new_from_string(inp).parse().expect("Should parse");
+        let outer: Vec<String> = outer.iter().map(|s| s.to_string()).collect();
+        fold_constants(&mut prog, &outer);
+        assert_eq!(prog.to_string(), exp);
+    }
+
+    #[test]
+    fn test_fold_constants() {
+        expect_fold(
+            "let r = 2.0; PI * r * r",
+            &[],
+            "let r = 2.0;((3.141592653589793*r)*r)",
+        );
+        expect_fold(
+            "fn f(x: int) -> bool { x < MAX_INT } f(MIN_INT)",
+            &[],
+            "fn f (x:int) -> bool { (x<9223372036854775807) };f(-9223372036854775808)",
+        );
+
+        let prog = Parser::new_from_string("E").parse().unwrap();
+        let mut folded = prog.clone();
+        fold_constants(&mut folded, &[]);
+        let instrs = Compiler::new(folded).compile().unwrap();
+        assert_eq!(
+            instrs,
+            vec![ByteCode::ldc(std::f64::consts::E), ByteCode::DONE]
+        );
+    }
+
+    #[test]
+    fn test_fold_constants_shadowed() {
+        // declared in a block or function, only there
+        expect_fold(
+            "let x = { let PI = 3; PI }; fn f(E: int) -> int { E } PI",
+            &[],
+            "let x = { let PI = 3;PI };fn f (E:int) -> int { E };3.141592653589793",
+        );
+        // declared at the top level, anywhere in it
+        expect_fold(
+            "let y = PI; let PI = 3; PI",
+            &[],
+            "let y = PI;let PI = 3;PI",
+        );
+        // assigned anywhere
+        expect_fold("fn f() { PI = 3.0; } PI", &[], "fn f () { PI = 3.0; };PI");
+        // declared by a file linked before
+        expect_fold("PI + E", &["PI"], "(PI+2.718281828459045)");
+    }
+}
@@ -0,0 +1,616 @@
+use std::{
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
+
+use parser::structs::{BlockSeq, Decl, Expr, FnCallData, FnDeclData, LetStmtData, QueueOp};
+
+use crate::compiler::Compiler;
+
+/// Largest function body, in instructions, that is inlined.
+pub const INLINE_MAX_INSTRS: usize = 16;
+
+/// A function that can be inlined, declared in the scope at depth
+struct Candidate {
+    params: Vec<String>,
+    body: BlockSeq,
+    // names the body uses that it doesn't declare
+    free: HashSet<String>,
+    depth: usize,
+}
+
+/// Names declared in a block, or a function's parameters, and the functions declared in the block so
+/// far that can be inlined
+#[derive(Default)]
+struct Scope {
+    names: HashSet<String>,
+    fns: HashMap<String, Rc<Candidate>>,
+}
+
+/// Inline calls of small user functions: a direct call f(a, b) of a function declared as
+/// fn f(x, y) { body } becomes the block { let x = a; let y = b; { body } }, which runs the same
+/// as the call without the CALL and RESET.
+///
+/// To keep the program's behaviour, a function is only inlined if:
+/// - its body compiles to at most INLINE_MAX_INSTRS instructions, has no return and declares no
+///   functions
+/// - it doesn't call itself
+/// - its name is declared once in its block and never assigned, so the call is always to it
+/// - the call comes after the declaration in the same block, or in a block or function inside it
+/// - no name the body uses from outside it is declared again between the declaration and the call,
+///   so the body sees the same variables
+/// - no argument uses a name of one of the parameters, which the block would shadow
+///
+/// Calls in an inlined body are not inlined again.
+pub fn inline_calls(program: &mut BlockSeq) {
+    let mut assigned = HashSet::new();
+    assigned_names_block(program, &mut assigned);
+
+    let mut inliner = Inliner {
+        scopes: vec![],
+        assigned,
+    };
+    inliner.block(program);
+}
+
+struct Inliner {
+    scopes: Vec<Scope>,
+    // names assigned anywhere in the program, which can't be inlined functions
+    assigned: HashSet<String>,
+}
+
+impl Inliner {
+    fn block(&mut self, blk: &mut BlockSeq) {
+        self.scopes.push(Scope {
+            names: blk.symbols.iter().cloned().collect(),
+            fns: HashMap::new(),
+        });
+
+        for idx in 0..blk.decls.len() {
+            self.decl(&mut blk.decls[idx]);
+
+            if let Decl::FnDeclStmt(fn_decl) = &blk.decls[idx] {
+                if let Some(candidate) = self.candidate(fn_decl, &blk.decls) {
+                    let scope = self.scopes.last_mut().expect("Block has a scope");
+                    scope.fns.insert(fn_decl.name.clone(), Rc::new(candidate));
+                }
+            }
+        }
+
+        if let Some(expr) = blk.last_expr.as_mut() {
+            self.expr(Rc::make_mut(expr));
+        }
+
+        self.scopes.pop();
+    }
+
+    fn decl(&mut self, decl: &mut Decl) {
+        match decl {
+            Decl::LetStmt(stmt) => self.expr(&mut stmt.expr),
+            Decl::AssignStmt(stmt) => self.expr(&mut stmt.expr),
+            Decl::ExprStmt(expr) => self.expr(expr),
+            Decl::IfOnlyStmt(if_else) => {
+                self.expr(&mut if_else.cond);
+                self.block(&mut if_else.if_blk);
+            }
+            Decl::LoopStmt(lp) => {
+                if let Some(cond) = lp.cond.as_mut() {
+                    self.expr(cond);
+                }
+                self.block(&mut lp.body);
+            }
+            Decl::ParallelLoopStmt(lp) => {
+                self.expr(&mut lp.start);
+                self.expr(&mut lp.end);
+                self.scopes.push(Scope {
+                    names: HashSet::from([lp.var.clone()]),
+                    fns: HashMap::new(),
+                });
+                self.block(&mut lp.body);
+                self.scopes.pop();
+            }
+            Decl::ScopeStmt(blk) => self.block(blk),
+            Decl::SelectStmt(select) => {
+                for arm in select.arms.iter_mut() {
+                    match &mut arm.op {
+                        QueueOp::Pop(q) => self.expr(q),
+                        QueueOp::Push(q, v) => {
+                            self.expr(q);
+                            self.expr(v);
+                        }
+                    }
+                    self.scopes.push(Scope {
+                        names: arm.binding.iter().cloned().collect(),
+                        fns: HashMap::new(),
+                    });
+                    self.block(&mut arm.body);
+                    self.scopes.pop();
+                }
+                if let Some(default) = select.default.as_mut() {
+                    self.block(default);
+                }
+            }
+            Decl::FnDeclStmt(fn_decl) => {
+                self.scopes.push(Scope {
+                    names: fn_decl.params.iter().map(|p| p.name.clone()).collect(),
+                    fns: HashMap::new(),
+                });
+                self.block(&mut fn_decl.body);
+                self.scopes.pop();
+            }
+            Decl::ReturnStmt(Some(expr)) | Decl::WaitStmt(expr) | Decl::PostStmt(expr) => {
+                self.expr(expr)
+            }
+            Decl::ReturnStmt(None) | Decl::BreakStmt | Decl::YieldStmt => (),
+        }
+    }
+
+    fn expr(&mut self, expr: &mut Expr) {
+        match expr {
+            Expr::UnOpExpr(_, e) | Expr::CastExpr(e, _) => self.expr(e),
+            Expr::BinOpExpr(_, lhs, rhs) => {
+                self.expr(lhs);
+                self.expr(rhs);
+            }
+            Expr::BlockExpr(blk) => self.block(blk),
+            Expr::IfElseExpr(if_else) => {
+                self.expr(&mut if_else.cond);
+                self.block(&mut if_else.if_blk);
+                if let Some(else_blk) = if_else.else_blk.as_mut() {
+                    self.block(else_blk);
+                }
+            }
+            Expr::CallExpr(callee, args) => {
+                self.expr(callee);
+                args.iter_mut().for_each(|arg| self.expr(arg));
+            }
+            Expr::FnCallExpr(call) | Expr::SpawnExpr(call) | Expr::SpawnIsolatedExpr(call) => {
+                call.args.iter_mut().for_each(|arg| self.expr(arg));
+                call.named_args
+                    .iter_mut()
+                    .for_each(|(_, arg)| self.expr(arg));
+            }
+            Expr::Symbol(_)
+            | Expr::Integer(_)
+            | Expr::Float(_)
+            | Expr::Bool(_)
+            | Expr::StringLiteral(_)
+            | Expr::JoinExpr(_) => (),
+        }
+
+        if let Expr::FnCallExpr(call) = expr {
+            if let Some(inlined) = self.inline(call) {
+                *expr = inlined;
+            }
+        }
+    }
+
+    /// The function declared by fn_decl, if it can be inlined. decls are the declarations of the
+    /// block it is declared in.
+    fn candidate(&self, fn_decl: &FnDeclData, decls: &[Decl]) -> Option<Candidate> {
+        let declared = decls
+            .iter()
+            .filter(|decl| match decl {
+                Decl::LetStmt(stmt) => stmt.ident == fn_decl.name,
+                Decl::FnDeclStmt(other) => other.name == fn_decl.name,
+                _ => false,
+            })
+            .count();
+        if declared > 1 || self.assigned.contains(&fn_decl.name) || !inlinable_block(&fn_decl.body)
+        {
+            return None;
+        }
+
+        let params: Vec<String> = fn_decl.params.iter().map(|p| p.name.clone()).collect();
+        let mut free = HashSet::new();
+        free_names_block(&fn_decl.body, &mut params.clone(), &mut free);
+        if free.contains(&fn_decl.name) {
+            return None;
+        }
+
+        let size = Compiler::new(fn_decl.body.clone()).compile().ok()?.len() - 1;
+        if size > INLINE_MAX_INSTRS {
+            return None;
+        }
+
+        Some(Candidate {
+            params,
+            body: fn_decl.body.clone(),
+            free,
+            depth: self.scopes.len() - 1,
+        })
+    }
+
+    /// The block replacing call, if it calls a function that can be inlined here
+    fn inline(&self, call: &FnCallData) -> Option<Expr> {
+        // the innermost declaration of the name must be the function
+        let depth = self
+            .scopes
+            .iter()
+            .rposition(|scope| scope.names.contains(&call.name))?;
+        let candidate = self.scopes[depth].fns.get(&call.name)?;
+
+        if !call.named_args.is_empty() || call.args.len() != candidate.params.len() {
+            return None;
+        }
+
+        // names the body uses must not be declared again since the function was
+        let shadowed = self.scopes[candidate.depth + 1..]
+            .iter()
+            .any(|scope| candidate.free.iter().any(|name| scope.names.contains(name)));
+        if shadowed {
+            return None;
+        }
+
+        let mut arg_names = HashSet::new();
+        for arg in call.args.iter() {
+            free_names_expr(arg, &mut vec![], &mut arg_names);
+        }
+        if candidate.params.iter().any(|p| arg_names.contains(p)) {
+            return None;
+        }
+
+        let body = Expr::BlockExpr(candidate.body.clone());
+        if candidate.params.is_empty() {
+            return Some(body);
+        }
+
+        let decls = candidate
+            .params
+            .iter()
+            .zip(call.args.iter())
+            .map(|(param, arg)| {
+                Decl::LetStmt(LetStmtData {
+                    ident: param.clone(),
+                    expr: arg.clone(),
+                    type_ann: None,
+                })
+            })
+            .collect();
+
+        Some(Expr::BlockExpr(BlockSeq {
+            decls,
+            last_expr: Some(Rc::new(body)),
+            symbols: candidate.params.clone(),
+            lines: vec![],
+        }))
+    }
+}
+
+/// Whether a function body can be inlined as a block: it has no return, which would leave the
+/// caller, and declares no functions, which would need their own environment
+fn inlinable_block(blk: &BlockSeq) -> bool {
+    blk.decls.iter().all(inlinable_decl) && blk.last_expr.as_deref().is_none_or(inlinable_expr)
+}
+
+fn inlinable_decl(decl: &Decl) -> bool {
+    match decl {
+        // lowered to a block declaring the function its workers run
+        Decl::ReturnStmt(_) | Decl::FnDeclStmt(_) | Decl::ParallelLoopStmt(_) => false,
+        Decl::LetStmt(stmt) => inlinable_expr(&stmt.expr),
+        Decl::AssignStmt(stmt) => inlinable_expr(&stmt.expr),
+        Decl::ExprStmt(expr) => inlinable_expr(expr),
+        Decl::IfOnlyStmt(if_else) => {
+            inlinable_expr(&if_else.cond) && inlinable_block(&if_else.if_blk)
+        }
+        Decl::LoopStmt(lp) => {
+            lp.cond.as_ref().is_none_or(inlinable_expr) && inlinable_block(&lp.body)
+        }
+        Decl::ScopeStmt(blk) => inlinable_block(blk),
+        Decl::SelectStmt(select) => {
+            select.arms.iter().all(|arm| {
+                arm.op.operands().into_iter().all(inlinable_expr) && inlinable_block(&arm.body)
+            }) && select.default.as_ref().is_none_or(inlinable_block)
+        }
+        Decl::WaitStmt(expr) | Decl::PostStmt(expr) => inlinable_expr(expr),
+        Decl::BreakStmt | Decl::YieldStmt => true,
+    }
+}
+
+fn inlinable_expr(expr: &Expr) -> bool {
+    match expr {
+        Expr::UnOpExpr(_, e) | Expr::CastExpr(e, _) => inlinable_expr(e),
+        Expr::BinOpExpr(_, lhs, rhs) => inlinable_expr(lhs) && inlinable_expr(rhs),
+        Expr::BlockExpr(blk) => inlinable_block(blk),
+        Expr::IfElseExpr(if_else) => {
+            inlinable_expr(&if_else.cond)
+                && inlinable_block(&if_else.if_blk)
+                && if_else.else_blk.as_ref().is_none_or(inlinable_block)
+        }
+        Expr::CallExpr(callee, args) => inlinable_expr(callee) && args.iter().all(inlinable_expr),
+        Expr::FnCallExpr(call) | Expr::SpawnExpr(call) | Expr::SpawnIsolatedExpr(call) => {
+            call.args.iter().all(inlinable_expr)
+                && call.named_args.iter().all(|(_, arg)| inlinable_expr(arg))
+        }
+        Expr::Symbol(_)
+        | Expr::Integer(_)
+        | Expr::Float(_)
+        | Expr::Bool(_)
+        | Expr::StringLiteral(_)
+        | Expr::JoinExpr(_) => true,
+    }
+}
+
+/// Add the names blk uses that aren't declared in it or in bound to free
+fn free_names_block(blk: &BlockSeq, bound: &mut Vec<String>, free: &mut HashSet<String>) {
+    let outer = bound.len();
+    bound.extend(blk.symbols.iter().cloned());
+
+    for decl in blk.decls.iter() {
+        free_names_decl(decl, bound, free);
+    }
+    if let Some(expr) = blk.last_expr.as_deref() {
+        free_names_expr(expr, bound, free);
+    }
+
+    bound.truncate(outer);
+}
+
+fn free_names_decl(decl: &Decl, bound: &mut Vec<String>, free: &mut HashSet<String>) {
+    let mut use_name = |name: &String, bound: &Vec<String>| {
+        if !bound.contains(name) {
+            free.insert(name.clone());
+        }
+    };
+
+    match decl {
+        Decl::LetStmt(stmt) => free_names_expr(&stmt.expr, bound, free),
+        Decl::AssignStmt(stmt) => {
+            use_name(&stmt.ident, bound);
+            free_names_expr(&stmt.expr, bound, free);
+        }
+        Decl::ExprStmt(expr)
+        | Decl::ReturnStmt(Some(expr))
+        | Decl::WaitStmt(expr)
+        | Decl::PostStmt(expr) => free_names_expr(expr, bound, free),
+        Decl::IfOnlyStmt(if_else) => {
+            free_names_expr(&if_else.cond, bound, free);
+            free_names_block(&if_else.if_blk, bound, free);
+        }
+        Decl::LoopStmt(lp) => {
+            if let Some(cond) = lp.cond.as_ref() {
+                free_names_expr(cond, bound, free);
+            }
+            free_names_block(&lp.body, bound, free);
+        }
+        Decl::ParallelLoopStmt(lp) => {
+            free_names_expr(&lp.start, bound, free);
+            free_names_expr(&lp.end, bound, free);
+            bound.push(lp.var.clone());
+            free_names_block(&lp.body, bound, free);
+            bound.pop();
+        }
+        Decl::ScopeStmt(blk) => free_names_block(blk, bound, free),
+        Decl::SelectStmt(select) => {
+            for arm in select.arms.iter() {
+                arm.op
+                    .operands()
+                    .into_iter()
+                    .for_each(|e| free_names_expr(e, bound, free));
+                let outer = bound.len();
+                bound.extend(arm.binding.iter().cloned());
+                free_names_block(&arm.body, bound, free);
+                bound.truncate(outer);
+            }
+            if let Some(default) = select.default.as_ref() {
+                free_names_block(default, bound, free);
+            }
+        }
+        Decl::FnDeclStmt(fn_decl) => {
+            let outer = bound.len();
+            bound.extend(fn_decl.params.iter().map(|p| p.name.clone()));
+            free_names_block(&fn_decl.body, bound, free);
+            bound.truncate(outer);
+        }
+        Decl::ReturnStmt(None) | Decl::BreakStmt | Decl::YieldStmt => (),
+    }
+}
+
+fn free_names_expr(expr: &Expr, bound: &mut Vec<String>, free: &mut HashSet<String>) {
+    let mut use_name = |name: &String, bound: &Vec<String>| {
+        if !bound.contains(name) {
+            free.insert(name.clone());
+        }
+    };
+
+    match expr {
+        Expr::Symbol(sym) | Expr::JoinExpr(sym) => use_name(sym, bound),
+        Expr::UnOpExpr(_, e) | Expr::CastExpr(e, _) => free_names_expr(e, bound, free),
+        Expr::BinOpExpr(_, lhs, rhs) => {
+            free_names_expr(lhs, bound, free);
+            free_names_expr(rhs, bound, free);
+        }
+        Expr::BlockExpr(blk) => free_names_block(blk, bound, free),
+        Expr::IfElseExpr(if_else) => {
+            free_names_expr(&if_else.cond, bound, free);
+            free_names_block(&if_else.if_blk, bound, free);
+            if let Some(else_blk) = if_else.else_blk.as_ref() {
+                free_names_block(else_blk, bound, free);
+            }
+        }
+        Expr::CallExpr(callee, args) => {
+            free_names_expr(callee, bound, free);
+            args.iter()
+                .for_each(|arg| free_names_expr(arg, bound, free));
+        }
+        Expr::FnCallExpr(call) | Expr::SpawnExpr(call) | Expr::SpawnIsolatedExpr(call) => {
+            use_name(&call.name, bound);
+            call.args
+                .iter()
+                .for_each(|arg| free_names_expr(arg, bound, free));
+            call.named_args
+                .iter()
+                .for_each(|(_, arg)| free_names_expr(arg, bound, free));
+        }
+        Expr::Integer(_) | Expr::Float(_) | Expr::Bool(_) | Expr::StringLiteral(_) => (),
+    }
+}
+
+/// Add the names assigned to anywhere in blk to assigned
+pub(crate) fn assigned_names_block(blk: &BlockSeq, assigned: &mut HashSet<String>) {
+    for decl in blk.decls.iter() {
+        assigned_names_decl(decl, assigned);
+    }
+    if let Some(expr) = blk.last_expr.as_deref() {
+        assigned_names_expr(expr, assigned);
+    }
+}
+
+fn assigned_names_decl(decl: &Decl, assigned: &mut HashSet<String>) {
+    match decl {
+        Decl::AssignStmt(stmt) => {
+            assigned.insert(stmt.ident.clone());
+            assigned_names_expr(&stmt.expr, assigned);
+        }
+        Decl::LetStmt(stmt) => assigned_names_expr(&stmt.expr, assigned),
+        Decl::ExprStmt(expr)
+        | Decl::ReturnStmt(Some(expr))
+        | Decl::WaitStmt(expr)
+        | Decl::PostStmt(expr) => assigned_names_expr(expr, assigned),
+        Decl::IfOnlyStmt(if_else) => {
+            assigned_names_expr(&if_else.cond, assigned);
+            assigned_names_block(&if_else.if_blk, assigned);
+        }
+        Decl::LoopStmt(lp) => {
+            if let Some(cond) = lp.cond.as_ref() {
+                assigned_names_expr(cond, assigned);
+            }
+            assigned_names_block(&lp.body, assigned);
+        }
+        Decl::ParallelLoopStmt(lp) => {
+            assigned_names_expr(&lp.start, assigned);
+            assigned_names_expr(&lp.end, assigned);
+            assigned_names_block(&lp.body, assigned);
+        }
+        Decl::ScopeStmt(blk) => assigned_names_block(blk, assigned),
+        Decl::SelectStmt(select) => {
+            for arm in select.arms.iter() {
+                arm.op
+                    .operands()
+                    .into_iter()
+                    .for_each(|e| assigned_names_expr(e, assigned));
+                assigned_names_block(&arm.body, assigned);
+            }
+            if let Some(default) = select.default.as_ref() {
+                assigned_names_block(default, assigned);
+            }
+        }
+        Decl::FnDeclStmt(fn_decl) => assigned_names_block(&fn_decl.body, assigned),
+        Decl::ReturnStmt(None) | Decl::BreakStmt | Decl::YieldStmt => (),
+    }
+}
+
+fn assigned_names_expr(expr: &Expr, assigned: &mut HashSet<String>) {
+    match expr {
+        Expr::UnOpExpr(_, e) | Expr::CastExpr(e, _) => assigned_names_expr(e, assigned),
+        Expr::BinOpExpr(_, lhs, rhs) => {
+            assigned_names_expr(lhs, assigned);
+            assigned_names_expr(rhs, assigned);
+        }
+        Expr::BlockExpr(blk) => assigned_names_block(blk, assigned),
+        Expr::IfElseExpr(if_else) => {
+            assigned_names_expr(&if_else.cond, assigned);
+            assigned_names_block(&if_else.if_blk, assigned);
+            if let Some(else_blk) = if_else.else_blk.as_ref() {
+                assigned_names_block(else_blk, assigned);
+            }
+        }
+        Expr::CallExpr(callee, args) => {
+            assigned_names_expr(callee, assigned);
+            args.iter()
+                .for_each(|arg| assigned_names_expr(arg, assigned));
+        }
+        Expr::FnCallExpr(call) | Expr::SpawnExpr(call) | Expr::SpawnIsolatedExpr(call) => {
+            call.args
+                .iter()
+                .for_each(|arg| assigned_names_expr(arg, assigned));
+            call.named_args
+                .iter()
+                .for_each(|(_, arg)| assigned_names_expr(arg, assigned));
+        }
+        Expr::Symbol(_)
+        | Expr::Integer(_)
+        | Expr::Float(_)
+        | Expr::Bool(_)
+        | Expr::StringLiteral(_)
+        | Expr::JoinExpr(_) => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::Parser;
+
+    use super::inline_calls;
+
+    fn expect_inline(inp: &str, exp: &str) {
+        let mut prog = Parser::new_from_string(inp).parse().expect("Should parse");
+        inline_calls(&mut prog);
+        assert_eq!(prog.to_string(), exp);
+    }
+
+    /// The program is the same after inlining
+    fn expect_no_inline(inp: &str) {
+        let mut prog = Parser::new_from_string(inp).parse().expect("Should parse");
+        let before = prog.to_string();
+        inline_calls(&mut prog);
+        assert_eq!(prog.to_string(), before);
+    }
+
+    #[test]
+    fn test_inline() {
+        expect_inline(
+            "fn sq(x: int) -> int { x * x } sq(3)",
+            "fn sq (x:int) -> int { (x*x) };{ let x = 3;{ (x*x) } }",
+        );
+        // no parameters, a unit body, calls in arguments and in nested functions
+        expect_inline(
+            "let n = 0; fn incr() { n = n + 1; } incr(); fn g() { incr(); }",
+            "let n = 0;fn incr () { n = (n+1); };{ n = (n+1); };fn g () { { n = (n+1); }; };",
+        );
+        expect_inline(
+            "fn add(x: int, y: int) -> int { x + y } add(add(1, 2), 3)",
+            "fn add (x:int, y:int) -> int { (x+y) };{ let x = { let x = 1;let y = 2;{ (x+y) } };let y = 3;{ (x+y) } }",
+        );
+        // a name the body uses, declared again after the function but not between it and the call
+        expect_inline(
+            "let k = 2; fn f(x: int) -> int { x * k } let y = f(1); { let k = 5; } y",
+            "let k = 2;fn f (x:int) -> int { (x*k) };let y = { let x = 1;{ (x*k) } };{ let k = 5; };y",
+        );
+    }
+
+    #[test]
+    fn test_no_inline() {
+        // recursive
+        expect_no_inline("fn f(n: int) -> int { if n < 1 { 0 } else { f(n - 1) } } f(3)");
+        // returns, declares a function
+        expect_no_inline("fn f(n: int) -> int { return n; } f(3)");
+        // calls of g in f are inlined, but f declares g so calls of f are not
+        expect_inline(
+            "fn f() -> int { fn g() -> int { 1 } g() } f()",
+            "fn f () -> int { fn g () -> int { 1 };{ 1 } };f()",
+        );
+        // too big
+        expect_no_inline(
+            "fn f(x: int) -> int { let a = x + 1; let b = a * 2; let c = b - 3; let d = c * a; a + b + c + d } f(1)",
+        );
+        // the name may refer to something else at the call
+        expect_no_inline("fn f() -> int { 1 } f = 2; f()");
+        expect_no_inline("fn f() -> int { 1 } { let f = 2; f }");
+        expect_no_inline("fn f() -> int { 1 } let f = 2; f");
+        // f is called in g before it is declared, only the call of g is inlined
+        expect_inline(
+            "fn g() -> int { f() } fn f() -> int { 1 } g()",
+            "fn g () -> int { f() };fn f () -> int { 1 };{ f() }",
+        );
+        // the body would see a different k
+        expect_no_inline("let k = 2; fn f() -> int { k } { let k = 3; f() }");
+        // in g, f would see g's k. g can be inlined: f is still called and sees the outer k
+        expect_inline(
+            "let k = 2; fn f() -> int { k } fn g(k: int) -> int { f() } g(1)",
+            "let k = 2;fn f () -> int { k };fn g (k:int) -> int { f() };{ let k = 1;{ f() } }",
+        );
+        // the argument would see the parameter
+        expect_no_inline("let x = 2; fn f(x: int) -> int { x } f(x + 1)");
+    }
+}
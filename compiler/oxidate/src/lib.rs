@@ -1,2 +1,12 @@
+pub mod ast_dump;
+pub mod cli;
 pub mod compiler;
+pub mod constants;
+pub mod inline;
+pub mod linker;
+pub mod parallel;
+pub mod peephole;
+pub mod pipeline;
+#[cfg(any(test, feature = "debug-compiler"))]
+mod sanity;
 pub mod tests;
@@ -0,0 +1,305 @@
+use std::collections::HashMap;
+
+use bytecode::ByteCode;
+
+use crate::compiler::{scope_escapes, CompileError};
+
+/// Link compiled programs into one program that runs them in the order given.
+///
+/// The top level names of every module are declared in one shared scope, so a module can use the
+/// functions and variables of the modules before it by name. A name declared at the top level of
+/// two modules is an error. Jump and function addresses are moved to where each module ends up, and
+/// the value of every module but the last is discarded, so the linked program's value is the last
+/// module's.
+///
+/// Constants are stored inline in LDC instructions rather than in a table, so there is nothing to
+/// de-duplicate between modules.
+pub fn link(modules: &[(String, Vec<ByteCode>)]) -> Result<Vec<ByteCode>, CompileError> {
+    let split: Vec<Module> = modules
+        .iter()
+        .map(|(name, bytecode)| Module::split(name, bytecode))
+        .collect::<Result<_, _>>()?;
+
+    let mut syms: Vec<String> = vec![];
+    let mut declared_in: HashMap<&str, &str> = HashMap::new();
+    for module in split.iter() {
+        for sym in module.syms.iter() {
+            if let Some(other) = declared_in.insert(sym, module.name) {
+                let e = format!(
+                    "'{}' is declared at the top level of both {} and {}",
+                    sym, other, module.name
+                );
+                return Err(CompileError::new(&e));
+            }
+            syms.push(sym.clone());
+        }
+    }
+
+    let mut body: Vec<ByteCode> = vec![];
+    // the shared scope, if any, is entered before the first module
+    let start = usize::from(!syms.is_empty());
+    for (idx, module) in split.iter().enumerate() {
+        let base = start + body.len();
+        for instr in module.body.iter() {
+            body.push(module.relocate(instr, base)?);
+        }
+
+        if idx + 1 < split.len() && module.leaves_value() {
+            body.push(ByteCode::POP);
+        }
+    }
+
+    let mut linked = Vec::with_capacity(body.len() + 3);
+    if syms.is_empty() {
+        linked.extend(body);
+    } else {
+        let (enter, exit) = if scope_escapes(&body) {
+            (ByteCode::ENTERSCOPE(syms), ByteCode::EXITSCOPE)
+        } else {
+            (ByteCode::ENTERSCOPELOCAL(syms), ByteCode::EXITSCOPELOCAL)
+        };
+        linked.push(enter);
+        linked.extend(body);
+        linked.push(exit);
+    }
+    linked.push(ByteCode::DONE);
+
+    Ok(linked)
+}
+
+/// A compiled program without its top level scope, to run after other programs in one VM with its
+/// top level names declared in a scope they share, like the lines of a REPL session. Returns the
+/// top level names, and the instructions moved to start at base and ending with DONE.
+pub fn unscoped(
+    name: &str,
+    bytecode: &[ByteCode],
+    base: usize,
+) -> Result<(Vec<String>, Vec<ByteCode>), CompileError> {
+    let module = Module::split(name, bytecode)?;
+    let mut instrs = module
+        .body
+        .iter()
+        .map(|instr| module.relocate(instr, base))
+        .collect::<Result<Vec<_>, _>>()?;
+    instrs.push(ByteCode::DONE);
+
+    Ok((module.syms.to_vec(), instrs))
+}
+
+/// A compiled program split into its top level names and the instructions that run in their scope
+struct Module<'a> {
+    name: &'a str,
+    syms: &'a [String],
+    body: &'a [ByteCode],
+    // index in the original program of the first instruction of body
+    offset: usize,
+}
+
+impl<'a> Module<'a> {
+    fn split(name: &'a str, bytecode: &'a [ByteCode]) -> Result<Module<'a>, CompileError> {
+        let Some((ByteCode::DONE, program)) = bytecode.split_last() else {
+            let e = format!(
+                "{} is not a compiled program: it doesn't end with DONE",
+                name
+            );
+            return Err(CompileError::new(&e));
+        };
+
+        // The top level scope starts the program and its exit is the last instruction before DONE.
+        // A block at the start of a program without top level names also starts with ENTERSCOPE, but
+        // its scope is exited earlier.
+        if let Some(ByteCode::ENTERSCOPE(syms) | ByteCode::ENTERSCOPELOCAL(syms)) = program.first()
+        {
+            if Module::scope_end(program) == Some(program.len() - 1) {
+                return Ok(Module {
+                    name,
+                    syms,
+                    body: &program[1..program.len() - 1],
+                    offset: 1,
+                });
+            }
+        }
+
+        Ok(Module {
+            name,
+            syms: &[],
+            body: program,
+            offset: 0,
+        })
+    }
+
+    /// Index of the instruction exiting the scope entered at the start of the program.
+    /// Scopes are entered and exited once each in the bytecode, so they nest like brackets.
+    fn scope_end(program: &[ByteCode]) -> Option<usize> {
+        let mut depth = 0;
+        for (idx, instr) in program.iter().enumerate() {
+            match instr {
+                ByteCode::ENTERSCOPE(_) | ByteCode::ENTERSCOPELOCAL(_) => depth += 1,
+                ByteCode::EXITSCOPE | ByteCode::EXITSCOPELOCAL => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Some(idx);
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        None
+    }
+
+    /// Every statement's value is popped, so only a last expression leaves a value behind
+    fn leaves_value(&self) -> bool {
+        self.body
+            .last()
+            .is_some_and(|instr| !instr.eq(&ByteCode::POP))
+    }
+
+    /// The instruction with its address moved for the body starting at base in the linked program.
+    /// Addresses may point one past the body, where the module ends.
+    fn relocate(&self, instr: &ByteCode, base: usize) -> Result<ByteCode, CompileError> {
+        let mut instr = instr.clone();
+        match &mut instr {
+            ByteCode::JOF(addr)
+            | ByteCode::GOTO(addr)
+            | ByteCode::LDF(addr, _)
+            | ByteCode::SPAWN(addr, _)
+            | ByteCode::SPAWNISOLATED(addr, _)
+            | ByteCode::SPAWNLIMITED(addr, _) => {
+                if *addr < self.offset || *addr > self.offset + self.body.len() {
+                    let e = format!(
+                        "{} jumps to {}, outside of the program's top level",
+                        self.name, addr
+                    );
+                    return Err(CompileError::new(&e));
+                }
+                *addr = base + *addr - self.offset;
+            }
+            _ => (),
+        }
+
+        Ok(instr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytecode::ByteCode::*;
+    use bytecode::{BinOp, ByteCode, Value};
+
+    use super::{link, unscoped};
+    use crate::compiler::compile_from_string;
+
+    fn module(name: &str, src: &str) -> (String, Vec<ByteCode>) {
+        (
+            name.to_string(),
+            compile_from_string(src, false).expect("Should compile"),
+        )
+    }
+
+    #[test]
+    fn test_link() {
+        let linked = link(&[
+            module("a.o2", "fn f(x: int) -> int { x + 1 }"),
+            module("b.o2", "let y = f(2); y"),
+        ])
+        .expect("Should link");
+
+        assert_eq!(
+            linked,
+            vec![
+                ByteCode::enterscope(vec!["f", "y"]),
+                // a.o2, jumps moved by the shared ENTERSCOPE
+                ByteCode::ldf(3, vec!["x"]),
+                GOTO(5),
+                ByteCode::ldldcbinop("x", 1, BinOp::Add),
+                RESET(bytecode::FrameType::CallFrame),
+                ByteCode::assign("f"),
+                LDC(Value::Unit),
+                POP,
+                // b.o2
+                ByteCode::ld("f"),
+                ByteCode::ldc(2),
+                CALL(1),
+                ByteCode::assign("y"),
+                LDC(Value::Unit),
+                POP,
+                ByteCode::ld("y"),
+                EXITSCOPE,
+                DONE,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_link_module_values() {
+        // the value of a module before the last is popped, jumps to its end land on the POP
+        let linked = link(&[
+            module("a.o2", "if true { 1 } else { 2 }"),
+            module("b.o2", "3"),
+        ])
+        .expect("Should link");
+
+        assert_eq!(
+            linked,
+            vec![
+                ByteCode::ldc(true),
+                JOF(4),
+                ByteCode::ldc(1),
+                GOTO(5),
+                ByteCode::ldc(2),
+                POP,
+                ByteCode::ldc(3),
+                DONE,
+            ]
+        );
+
+        // a leading block isn't the top level scope
+        let linked = link(&[
+            module("a.o2", "{ let x = 1; } { let y = 2; y }"),
+            module("b.o2", ""),
+        ])
+        .expect("Should link");
+        assert_eq!(linked.first(), Some(&ByteCode::enterscopelocal(vec!["x"])));
+        assert_eq!(linked.last(), Some(&DONE));
+    }
+
+    #[test]
+    fn test_unscoped() {
+        let (_, line) = module("line", "let x = 1; if x > 0 { x } else { 0 }");
+        let (syms, instrs) = unscoped("line", &line, 10).expect("Should split");
+
+        assert_eq!(syms, vec!["x".to_string()]);
+        assert_eq!(
+            instrs,
+            vec![
+                ByteCode::ldc(1),
+                ByteCode::assign("x"),
+                LDC(Value::Unit),
+                POP,
+                ByteCode::ldldcbinop("x", 0, BinOp::Gt),
+                JOF(18),
+                ByteCode::ld("x"),
+                GOTO(19),
+                ByteCode::ldc(0),
+                DONE,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_link_errs() {
+        let err = link(&[module("a.o2", "let x = 1;"), module("b.o2", "let x = 2;")])
+            .expect_err("Should err");
+        assert_eq!(
+            err.to_string(),
+            "[CompileError] -  'x' is declared at the top level of both a.o2 and b.o2"
+        );
+
+        let err = link(&[("c.o2".to_string(), vec![ByteCode::ldc(1)])]).expect_err("Should err");
+        assert_eq!(
+            err.to_string(),
+            "[CompileError] -  c.o2 is not a compiled program: it doesn't end with DONE"
+        );
+    }
+}
@@ -0,0 +1,147 @@
+use parser::structs::{
+    AssignStmtData, BinOpType, BlockSeq, Decl, Expr, FnCallData, FnDeclData, FnParam, LetStmtData,
+    LoopData, ParallelLoopData, Type,
+};
+
+/// Number of threads the iterations of a @parallel loop are split between
+pub const PARALLEL_WORKERS: usize = 4;
+
+// Names of what a @parallel loop is lowered to. They have spaces so they can't clash with the
+// program's names.
+const RANGE_START: &str = "range start";
+const RANGE_END: &str = "range end";
+const RANGE_INDEX: &str = "range index";
+const WORKER_FN: &str = "parallel worker";
+const WORKER_ID: &str = "worker id";
+const WORKER_THREAD: &str = "worker thread";
+
+/// The block a @parallel loop runs as: worker k of PARALLEL_WORKERS runs the iterations
+/// start + k, start + k + PARALLEL_WORKERS, ... in a thread of its own, and the block ends once
+/// every worker has been joined. The lowered statements have no lines of their own, so they keep
+/// the line of the loop.
+///
+/// {
+///     let range start: int = start;
+///     let range end: int = end;
+///     fn parallel worker(worker id: int) {
+///         let range index = range start + worker id;
+///         loop range index < range end {
+///             let var = range index;
+///             { body };
+///             range index = range index + PARALLEL_WORKERS;
+///         }
+///     }
+///     let worker thread 0 = spawn parallel worker(0);
+///     ...
+///     join worker thread 0;
+///     ...
+/// }
+pub fn lower_parallel_loop(lp: &ParallelLoopData) -> BlockSeq {
+    let sym = |name: &str| Expr::Symbol(name.to_string());
+    let add = |lhs: Expr, rhs: Expr| Expr::BinOpExpr(BinOpType::Add, Box::new(lhs), Box::new(rhs));
+    let let_stmt = |ident: &str, expr: Expr, type_ann: Option<Type>| {
+        Decl::LetStmt(LetStmtData {
+            ident: ident.to_string(),
+            expr,
+            type_ann,
+        })
+    };
+
+    let iteration = BlockSeq {
+        decls: vec![
+            let_stmt(&lp.var, sym(RANGE_INDEX), None),
+            Decl::ExprStmt(Expr::BlockExpr(lp.body.clone())),
+            Decl::AssignStmt(AssignStmtData {
+                ident: RANGE_INDEX.to_string(),
+                expr: add(sym(RANGE_INDEX), Expr::Integer(PARALLEL_WORKERS as i64)),
+            }),
+        ],
+        last_expr: None,
+        symbols: vec![lp.var.clone()],
+        lines: vec![],
+    };
+
+    let worker = FnDeclData {
+        name: WORKER_FN.to_string(),
+        params: vec![FnParam {
+            name: WORKER_ID.to_string(),
+            type_ann: Some(Type::Int),
+            default: None,
+        }],
+        ret_type: Type::Unit,
+        body: BlockSeq {
+            decls: vec![
+                let_stmt(RANGE_INDEX, add(sym(RANGE_START), sym(WORKER_ID)), None),
+                Decl::LoopStmt(LoopData {
+                    cond: Some(Expr::BinOpExpr(
+                        BinOpType::Lt,
+                        Box::new(sym(RANGE_INDEX)),
+                        Box::new(sym(RANGE_END)),
+                    )),
+                    body: iteration,
+                }),
+            ],
+            last_expr: None,
+            symbols: vec![RANGE_INDEX.to_string()],
+            lines: vec![],
+        },
+    };
+
+    let threads: Vec<String> = (0..PARALLEL_WORKERS)
+        .map(|k| format!("{} {}", WORKER_THREAD, k))
+        .collect();
+
+    let mut decls = vec![
+        let_stmt(RANGE_START, lp.start.clone(), Some(Type::Int)),
+        let_stmt(RANGE_END, lp.end.clone(), Some(Type::Int)),
+        Decl::FnDeclStmt(worker),
+    ];
+    for (k, thread) in threads.iter().enumerate() {
+        let spawn = Expr::SpawnExpr(FnCallData {
+            name: WORKER_FN.to_string(),
+            args: vec![Expr::Integer(k as i64)],
+            named_args: vec![],
+        });
+        decls.push(let_stmt(thread, spawn, None));
+    }
+    for thread in threads.iter() {
+        decls.push(Decl::ExprStmt(Expr::JoinExpr(thread.clone())));
+    }
+
+    let mut symbols = vec![
+        RANGE_START.to_string(),
+        RANGE_END.to_string(),
+        WORKER_FN.to_string(),
+    ];
+    symbols.extend(threads);
+
+    BlockSeq {
+        decls,
+        last_expr: None,
+        symbols,
+        lines: vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::structs::Decl;
+    use parser::Parser;
+
+    use super::lower_parallel_loop;
+
+    #[test]
+    fn test_lower_parallel_loop() {
+        let prog = Parser::new_from_string("@parallel loop i in 0..n { f(i); }")
+            .parse()
+            .expect("Should parse");
+        let Decl::ParallelLoopStmt(lp) = &prog.decls[0] else {
+            panic!("Should be a @parallel loop");
+        };
+
+        assert_eq!(
+            lower_parallel_loop(lp).to_string(),
+            "let range start : int = 0;let range end : int = n;fn parallel worker (worker id:int) { let range index = (range start+worker id);loop (range index<range end) { let i = range index;{ f(i); };range index = (range index+4); }; };let worker thread 0 = spawn parallel worker(0);let worker thread 1 = spawn parallel worker(1);let worker thread 2 = spawn parallel worker(2);let worker thread 3 = spawn parallel worker(3);join worker thread 0;join worker thread 1;join worker thread 2;join worker thread 3;"
+        );
+    }
+}
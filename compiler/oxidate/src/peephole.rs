@@ -0,0 +1,242 @@
+use std::collections::HashSet;
+
+use bytecode::{BinOp, ByteCode, LineTable};
+
+/// Peephole pass replacing common instruction sequences with fused super-instructions:
+/// - LD x; LDC v; BINOP Add; ASSIGN x => INC(x, v)
+/// - LD x; LDC v; BINOP op           => LDLDCBINOP(x, v, op)
+///
+/// A sequence is only fused if no jump lands inside it. Jump targets (JOF, GOTO, LDF and the SPAWN variants) are
+/// rewritten to the new instruction indices afterwards.
+pub fn fuse(instrs: Vec<ByteCode>) -> Vec<ByteCode> {
+    fuse_with_lines(instrs, &mut vec![])
+}
+
+/// fuse, also moving the addresses of the compiler's line table to the fused output.
+/// A fused instruction keeps the line of the first instruction it replaces.
+pub fn fuse_with_lines(instrs: Vec<ByteCode>, lines: &mut LineTable) -> Vec<ByteCode> {
+    let targets = jump_targets(&instrs);
+
+    // new_idx[i] is the index of old instruction i in the fused output. Has one extra slot
+    // for the end of the program in case a jump goes there.
+    let mut new_idx: Vec<usize> = Vec::with_capacity(instrs.len() + 1);
+    let mut out: Vec<ByteCode> = Vec::with_capacity(instrs.len());
+
+    let mut i = 0;
+    while i < instrs.len() {
+        let (fused, len) = match_fused(&instrs[i..])
+            .filter(|(_, len)| (i + 1..i + len).all(|idx| !targets.contains(&idx)))
+            .unwrap_or_else(|| (instrs[i].clone(), 1));
+
+        for _ in 0..len {
+            new_idx.push(out.len());
+        }
+        out.push(fused);
+        i += len;
+    }
+    new_idx.push(out.len());
+
+    for instr in out.iter_mut() {
+        match instr {
+            ByteCode::JOF(addr)
+            | ByteCode::GOTO(addr)
+            | ByteCode::LDF(addr, _)
+            | ByteCode::SPAWN(addr, _)
+            | ByteCode::SPAWNISOLATED(addr, _)
+            | ByteCode::SPAWNLIMITED(addr, _) => *addr = new_idx[*addr],
+            _ => (),
+        }
+    }
+
+    for (addr, _) in lines.iter_mut() {
+        *addr = new_idx[*addr];
+    }
+    lines.dedup_by_key(|(addr, _)| *addr);
+
+    out
+}
+
+/// Addresses that can be jumped to
+fn jump_targets(instrs: &[ByteCode]) -> HashSet<usize> {
+    instrs
+        .iter()
+        .filter_map(|instr| match instr {
+            ByteCode::JOF(addr)
+            | ByteCode::GOTO(addr)
+            | ByteCode::LDF(addr, _)
+            | ByteCode::SPAWN(addr, _)
+            | ByteCode::SPAWNISOLATED(addr, _)
+            | ByteCode::SPAWNLIMITED(addr, _) => Some(*addr),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Try to match a fusable sequence at the start of instrs, returning the fused instruction
+/// and the number of instructions it replaces. Longest match wins.
+fn match_fused(instrs: &[ByteCode]) -> Option<(ByteCode, usize)> {
+    match instrs {
+        [ByteCode::LD(sym), ByteCode::LDC(val), ByteCode::BINOP(BinOp::Add), ByteCode::ASSIGN(target), ..]
+            if sym.eq(target) =>
+        {
+            Some((ByteCode::INC(sym.clone(), val.clone()), 4))
+        }
+        [ByteCode::LD(sym), ByteCode::LDC(val), ByteCode::BINOP(op), ..] => Some((
+            ByteCode::LDLDCBINOP(sym.clone(), val.clone(), op.clone()),
+            3,
+        )),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytecode::ByteCode::*;
+    use bytecode::{BinOp, ByteCode, FrameType, Value};
+
+    use super::fuse;
+    use crate::compiler::compile_from_string;
+
+    #[test]
+    fn test_fuse_inc() {
+        let instrs = vec![
+            ByteCode::ld("x"),
+            ByteCode::ldc(1),
+            BINOP(BinOp::Add),
+            ByteCode::assign("x"),
+            ByteCode::ldc(Value::Unit),
+            POP,
+            DONE,
+        ];
+        assert_eq!(
+            fuse(instrs),
+            vec![ByteCode::inc("x", 1), ByteCode::ldc(Value::Unit), POP, DONE]
+        );
+
+        // different symbol: only the binop part is fused
+        let instrs = vec![
+            ByteCode::ld("x"),
+            ByteCode::ldc(1),
+            BINOP(BinOp::Add),
+            ByteCode::assign("y"),
+            DONE,
+        ];
+        assert_eq!(
+            fuse(instrs),
+            vec![
+                ByteCode::ldldcbinop("x", 1, BinOp::Add),
+                ByteCode::assign("y"),
+                DONE
+            ]
+        );
+
+        // not Add
+        let instrs = vec![
+            ByteCode::ld("x"),
+            ByteCode::ldc(2),
+            BINOP(BinOp::Mul),
+            ByteCode::assign("x"),
+            DONE,
+        ];
+        assert_eq!(
+            fuse(instrs),
+            vec![
+                ByteCode::ldldcbinop("x", 2, BinOp::Mul),
+                ByteCode::assign("x"),
+                DONE
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fuse_patches_jumps() {
+        // let x = 0; loop x < 10 { x = x + 1; } x
+        let instrs = vec![
+            ByteCode::enterscope(vec!["x"]), // 0
+            ByteCode::ldc(0),
+            ByteCode::assign("x"),
+            ByteCode::ldc(Value::Unit),
+            POP,
+            ByteCode::ld("x"), // 5: loop start
+            ByteCode::ldc(10),
+            BINOP(BinOp::Lt),
+            JOF(17),
+            ByteCode::ld("x"),
+            ByteCode::ldc(1),
+            BINOP(BinOp::Add),
+            ByteCode::assign("x"),
+            ByteCode::ldc(Value::Unit),
+            POP,
+            POP,
+            GOTO(5),
+            ByteCode::ldc(Value::Unit), // 17: loop end
+            POP,
+            ByteCode::ld("x"),
+            EXITSCOPE,
+            DONE,
+        ];
+
+        assert_eq!(
+            fuse(instrs),
+            vec![
+                ByteCode::enterscope(vec!["x"]), // 0
+                ByteCode::ldc(0),
+                ByteCode::assign("x"),
+                ByteCode::ldc(Value::Unit),
+                POP,
+                ByteCode::ldldcbinop("x", 10, BinOp::Lt), // 5: loop start
+                JOF(12),
+                ByteCode::inc("x", 1),
+                ByteCode::ldc(Value::Unit),
+                POP,
+                POP,
+                GOTO(5),
+                ByteCode::ldc(Value::Unit), // 12: loop end
+                POP,
+                ByteCode::ld("x"),
+                EXITSCOPE,
+                DONE,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fuse_jump_into_sequence() {
+        // jump lands on the LDC: can't fuse
+        let instrs = vec![
+            ByteCode::ld("x"),
+            ByteCode::ldc(1),
+            BINOP(BinOp::Add),
+            GOTO(1),
+            DONE,
+        ];
+        assert_eq!(fuse(instrs.clone()), instrs);
+
+        // function and spawn addresses are patched too
+        let instrs = vec![
+            ByteCode::ld("x"),
+            ByteCode::ldc(1),
+            BINOP(BinOp::Add),
+            ByteCode::ldf(5, vec!["y"]),
+            SPAWN(6, 0),
+            ByteCode::reset(FrameType::CallFrame),
+            DONE,
+        ];
+        assert_eq!(
+            fuse(instrs),
+            vec![
+                ByteCode::ldldcbinop("x", 1, BinOp::Add),
+                ByteCode::ldf(3, vec!["y"]),
+                SPAWN(4, 0),
+                ByteCode::reset(FrameType::CallFrame),
+                DONE,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compile_from_string_fuses() {
+        let res = compile_from_string("let x = 0; x = x + 2; x", true).unwrap();
+        assert!(res.contains(&ByteCode::inc("x", 2)));
+    }
+}
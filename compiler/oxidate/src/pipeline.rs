@@ -0,0 +1,522 @@
+use anyhow::Result;
+use bytecode::{ByteCode, LineTable, Symbol};
+use diagnostics::Diagnostic;
+use parser::edition::Edition;
+use parser::structs::{BlockSeq, Type};
+use types::call_args::resolve_call_args;
+use types::entry_point::call_main;
+use types::type_checker::{TypeChecker, TypeErrors};
+
+use crate::{compiler::Compiler, constants::fold_constants, inline::inline_calls, peephole};
+
+/// Options for compile.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Options {
+    /// Type check the program before compiling it.
+    pub typecheck: bool,
+    /// Run the peephole pass, fusing common instruction sequences.
+    pub optimize: bool,
+    /// Inline calls of small user functions, see inline::inline_calls.
+    pub inline: bool,
+    /// Load builtin constants like PI as literals, see constants::fold_constants.
+    pub fold_constants: bool,
+    /// Build a symbol table of the compiled program.
+    pub debug_symbols: bool,
+    /// Edition of the language the program is written in.
+    pub edition: Edition,
+    /// Call a top level fn main after the rest of the program, see types::entry_point.
+    pub call_main: bool,
+    /// Warn about expression statements whose result is unused, like `x == 3;`.
+    pub warn_unused_results: bool,
+}
+
+impl Default for Options {
+    /// Type checked and optimized, without debug symbols: what oxidate and ignite use.
+    fn default() -> Self {
+        Options {
+            typecheck: true,
+            optimize: true,
+            inline: false,
+            fold_constants: false,
+            debug_symbols: false,
+            edition: Edition::default(),
+            call_main: true,
+            warn_unused_results: true,
+        }
+    }
+}
+
+/// Everything produced by compiling a program.
+#[derive(Debug, Clone)]
+pub struct CompilationResult {
+    /// The parsed program.
+    pub ast: BlockSeq,
+    /// Type of the program's result, if it was type checked.
+    pub ty: Option<Type>,
+    /// The compiled program, ending in DONE.
+    pub bytecode: Vec<ByteCode>,
+    /// Symbols of the compiled program, if debug symbols were asked for.
+    pub symbols: Option<SymbolTable>,
+    /// The line of the source each instruction came from.
+    pub lines: LineTable,
+    /// Warnings from type checking, which did not stop compilation.
+    pub warnings: Vec<Diagnostic>,
+}
+
+/// Everything produced by checking a program without compiling it.
+#[derive(Debug, Clone)]
+pub struct CheckResult {
+    /// The parsed program.
+    pub ast: BlockSeq,
+    /// Type of the program's result.
+    pub ty: Type,
+    /// Warnings from type checking.
+    pub warnings: Vec<Diagnostic>,
+}
+
+/// Names defined by a compiled program and where their code is.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SymbolTable {
+    /// Names declared at the top level of the program.
+    pub globals: Vec<Symbol>,
+    /// Every function declared in the program, including nested ones, in bytecode order.
+    pub functions: Vec<FnSymbol>,
+}
+
+/// A function declared in the program.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FnSymbol {
+    pub name: Symbol,
+    pub params: Vec<Symbol>,
+    /// Address of the first instruction of the function's body.
+    pub addr: usize,
+}
+
+impl SymbolTable {
+    /// Build the symbol table of a compiled program. Functions are found from the bytecode so
+    /// addresses are correct after optimization: a declaration compiles to LDF(addr, params) and
+    /// GOTO(end) over the body, where end is the ASSIGN of the function's name.
+    pub fn new(ast: &BlockSeq, bytecode: &[ByteCode]) -> SymbolTable {
+        let functions = bytecode
+            .windows(2)
+            .filter_map(|pair| match pair {
+                [ByteCode::LDF(addr, params), ByteCode::GOTO(end)] => match bytecode.get(*end) {
+                    Some(ByteCode::ASSIGN(name)) => Some(FnSymbol {
+                        name: name.clone(),
+                        params: params.clone(),
+                        addr: *addr,
+                    }),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect();
+
+        SymbolTable {
+            globals: ast.symbols.clone(),
+            functions,
+        }
+    }
+
+    /// Look up a function by name. With nested functions of the same name, the first one declared.
+    pub fn function(&self, name: &str) -> Option<&FnSymbol> {
+        self.functions.iter().find(|f| f.name == name)
+    }
+}
+
+/// Parse, type check and compile a program: the pipeline shared by oxidate, ignite and the REPL.
+///
+/// # Errors
+///
+/// ParseError, TypeErrors or CompileError from the stage that failed. compiler::diagnostics turns
+/// these into diagnostics.
+pub fn compile(src: &str, options: &Options) -> Result<CompilationResult> {
+    let mut ast = parse(src, options.edition)?;
+    if options.call_main {
+        call_main(&mut ast)?;
+    }
+
+    let (ty, warnings) = if options.typecheck {
+        let (ty, warnings) = type_check(&ast, options);
+        (Some(ty?), warnings)
+    } else {
+        (None, vec![])
+    };
+
+    compile_checked(ast, ty, warnings, options, &[])
+}
+
+/// Parse, type check and compile the files of a program that oxidate link runs in order, given as
+/// (name, source). Each file is compiled after the files before it, see Incremental.
+///
+/// # Errors
+///
+/// The name of the first file that failed, with the error compile would give for it.
+pub fn compile_files(
+    files: &[(String, String)],
+    options: &Options,
+) -> Result<Vec<CompilationResult>, (String, anyhow::Error)> {
+    let mut program = Incremental::default();
+    let mut results = Vec::with_capacity(files.len());
+    for (name, src) in files {
+        let res = program
+            .compile(src, options)
+            .map_err(|err| (name.clone(), err))?;
+        program.push(&res);
+        results.push(res);
+    }
+
+    Ok(results)
+}
+
+/// Compiles programs that run one after another in the same top level scope, like the files oxidate
+/// link joins or the lines of a REPL session. Each program is type checked as if it followed the
+/// top level of the programs pushed before it, so it can use their functions and variables, and
+/// only reports its own warnings. main isn't called: whoever runs the programs decides their order.
+#[derive(Debug, Clone, Default)]
+pub struct Incremental {
+    before: Vec<BlockSeq>,
+    // warnings of the programs pushed so far
+    seen: Vec<Diagnostic>,
+}
+
+impl Incremental {
+    /// Parse, type check and compile src after the programs pushed so far, without pushing it.
+    ///
+    /// # Errors
+    ///
+    /// ParseError, TypeErrors or CompileError, like compile.
+    pub fn compile(&self, src: &str, options: &Options) -> Result<CompilationResult> {
+        let ast = parse(src, options.edition)?;
+
+        let (ty, warnings) = if options.typecheck {
+            let program = preceded_by(&ast, &self.before);
+            let (ty, mut warnings) = type_check(&program, options);
+            for warning in self.seen.iter() {
+                if let Some(idx) = warnings.iter().position(|w| w == warning) {
+                    warnings.remove(idx);
+                }
+            }
+            (Some(ty?), warnings)
+        } else {
+            (None, vec![])
+        };
+
+        let outer: Vec<String> = self
+            .before
+            .iter()
+            .flat_map(|ast| ast.symbols.clone())
+            .collect();
+        compile_checked(ast, ty, warnings, options, &outer)
+    }
+
+    /// Add a program compiled by compile, so the programs compiled after it follow it
+    pub fn push(&mut self, compiled: &CompilationResult) {
+        self.before.push(compiled.ast.clone());
+        self.seen.extend(compiled.warnings.iter().cloned());
+    }
+}
+
+/// The program of ast run after the top level of each program in before, as linking runs them,
+/// to type check ast in. Their results are discarded, and were checked with their own file, so
+/// they are left out rather than reported as unused.
+fn preceded_by(ast: &BlockSeq, before: &[BlockSeq]) -> BlockSeq {
+    let mut program = BlockSeq {
+        decls: vec![],
+        last_expr: ast.last_expr.clone(),
+        symbols: vec![],
+        lines: vec![],
+    };
+    for prog in before.iter() {
+        program.decls.extend(prog.decls.iter().cloned());
+        program.symbols.extend(prog.symbols.iter().cloned());
+    }
+    program.decls.extend(ast.decls.iter().cloned());
+    program.symbols.extend(ast.symbols.iter().cloned());
+
+    program
+}
+
+/// Compile a parsed program, type checked already if options say so, with the type and warnings
+/// type checking gave. outer are the names declared by the programs that run before it.
+fn compile_checked(
+    ast: BlockSeq,
+    ty: Option<Type>,
+    warnings: Vec<Diagnostic>,
+    options: &Options,
+    outer: &[String],
+) -> Result<CompilationResult> {
+    let mut program = ast.clone();
+    if options.inline {
+        inline_calls(&mut program);
+    }
+    if options.fold_constants {
+        fold_constants(&mut program, outer);
+    }
+
+    let (mut bytecode, mut lines) = Compiler::with_outer(program, outer).compile_with_lines()?;
+    if options.optimize {
+        bytecode = peephole::fuse_with_lines(bytecode, &mut lines);
+        #[cfg(any(test, feature = "debug-compiler"))]
+        crate::sanity::check_program(&bytecode, "peephole");
+    }
+
+    let symbols = options
+        .debug_symbols
+        .then(|| SymbolTable::new(&ast, &bytecode));
+
+    Ok(CompilationResult {
+        ast,
+        ty,
+        bytecode,
+        symbols,
+        lines,
+        warnings,
+    })
+}
+
+/// Parse and type check a program without compiling it: what oxidate check runs. Of the options,
+/// only the edition and the warnings to give are used.
+///
+/// # Errors
+///
+/// ParseError or TypeErrors, like compile.
+pub fn check(src: &str, options: &Options) -> Result<CheckResult> {
+    let mut ast = parse(src, options.edition)?;
+    call_main(&mut ast)?;
+    let (ty, warnings) = type_check(&ast, options);
+
+    Ok(CheckResult {
+        ty: ty?,
+        warnings,
+        ast,
+    })
+}
+
+fn type_check(ast: &BlockSeq, options: &Options) -> (Result<Type, TypeErrors>, Vec<Diagnostic>) {
+    let mut checker = TypeChecker::new(ast);
+    checker.set_warn_unused_results(options.warn_unused_results);
+    checker.type_check_with_warnings()
+}
+
+fn parse(src: &str, edition: Edition) -> Result<BlockSeq> {
+    let mut parser = parser::Parser::new_from_string(src);
+    parser.set_edition(edition);
+    let mut ast = parser.parse()?;
+    // named arguments and defaults become positional arguments, even if type checking is off
+    resolve_call_args(&mut ast)?;
+    Ok(ast)
+}
+
+#[cfg(test)]
+mod tests {
+    use bytecode::ByteCode;
+    use parser::edition::Edition;
+    use parser::structs::Type;
+
+    use super::{check, compile, compile_files, FnSymbol, Options};
+    use crate::compiler::diagnostics;
+
+    #[test]
+    fn test_compile_options() {
+        let src = "let x = 0; x = x + 1; x";
+
+        let res = compile(src, &Options::default()).unwrap();
+        assert_eq!(res.ty, Some(Type::Int));
+        assert!(res.bytecode.contains(&ByteCode::inc("x", 1)));
+        assert_eq!(res.symbols, None);
+        assert_eq!(res.ast.symbols, vec!["x".to_string()]);
+        assert!(res.warnings.is_empty());
+
+        let opts = Options {
+            typecheck: false,
+            optimize: false,
+            inline: false,
+            fold_constants: false,
+            debug_symbols: false,
+            edition: Edition::default(),
+            call_main: true,
+            warn_unused_results: true,
+        };
+        let res = compile(src, &opts).unwrap();
+        assert_eq!(res.ty, None);
+        assert!(!res.bytecode.contains(&ByteCode::inc("x", 1)));
+        assert_eq!(res.bytecode.last(), Some(&ByteCode::DONE));
+
+        // type errors are only found when type checking
+        let src = "let x : int = true; 2";
+        assert!(compile(src, &opts).is_ok());
+        let err = compile(src, &Options::default()).unwrap_err();
+        assert_eq!(diagnostics(&err)[0].code, diagnostics::TYPE_ERROR);
+    }
+
+    #[test]
+    fn test_compile_debug_symbols() {
+        let src = r"
+        let g = 2;
+        fn add(x: int, y: int) -> int {
+            fn twice(z: int) -> int {
+                z * 2
+            }
+            twice(x) + y + g
+        }
+        add(1, 2)
+        ";
+        let opts = Options {
+            debug_symbols: true,
+            ..Options::default()
+        };
+        let res = compile(src, &opts).unwrap();
+        let symbols = res.symbols.unwrap();
+
+        assert_eq!(symbols.globals, vec!["g".to_string(), "add".to_string()]);
+        assert_eq!(symbols.functions.len(), 2);
+
+        let add = symbols.function("add").unwrap();
+        assert_eq!(add.params, vec!["x".to_string(), "y".to_string()]);
+        assert!(matches!(res.bytecode[add.addr - 2], ByteCode::LDF(_, _)));
+
+        let twice = symbols.function("twice").unwrap();
+        assert_eq!(
+            twice,
+            &FnSymbol {
+                name: "twice".to_string(),
+                params: vec!["z".to_string()],
+                addr: twice.addr,
+            }
+        );
+        assert!(twice.addr > add.addr);
+        assert!(symbols.function("g").is_none());
+    }
+
+    #[test]
+    fn test_compile_lines() {
+        let src = "let x = 0;\nx = x + 1;\n{\n  let y = x;\n  y\n};\nx";
+        let res = compile(src, &Options::default()).unwrap();
+        // x = x + 1 is fused to one INC, the block's exit is back on line 3
+        assert_eq!(
+            res.lines,
+            vec![(1, 1), (5, 2), (8, 3), (9, 4), (13, 5), (15, 3), (16, 7)]
+        );
+        assert_eq!(res.bytecode[5], ByteCode::inc("x", 1));
+    }
+
+    #[test]
+    fn test_compile_warnings() {
+        let res = compile("let x = 1; let x = 2; x", &Options::default()).unwrap();
+        assert_eq!(res.warnings.len(), 1);
+        assert!(!res.warnings[0].is_error());
+        assert_eq!(res.warnings[0].code, diagnostics::SHADOWED_BINDING);
+
+        // unused results are only warned about if options say so
+        let src = "let x = 1; x == 2; x";
+        assert_eq!(compile(src, &Options::default()).unwrap().warnings.len(), 1);
+        let opts = Options {
+            warn_unused_results: false,
+            ..Options::default()
+        };
+        assert!(compile(src, &opts).unwrap().warnings.is_empty());
+        assert!(check(src, &opts).unwrap().warnings.is_empty());
+    }
+
+    #[test]
+    fn test_compile_errors() {
+        let err = compile("let x = ;", &Options::default()).unwrap_err();
+        assert_eq!(diagnostics(&err)[0].code, diagnostics::PARSE_ERROR);
+    }
+
+    #[test]
+    fn test_check() {
+        let res = check(
+            "fn f(x: int, y: int = 2) -> int { x + y } f(1)",
+            &Options::default(),
+        )
+        .unwrap();
+        assert_eq!(res.ty, Type::Int);
+        assert!(res.warnings.is_empty());
+
+        let res = check("let x = 1; let x = 2; x", &Options::default()).unwrap();
+        assert_eq!(res.warnings.len(), 1);
+
+        // every type error is reported, not just the first
+        let err = check("x; y;", &Options::default()).unwrap_err();
+        assert_eq!(diagnostics(&err).len(), 2);
+        let err = check("let x = ;", &Options::default()).unwrap_err();
+        assert_eq!(diagnostics(&err)[0].code, diagnostics::PARSE_ERROR);
+    }
+
+    #[test]
+    fn test_compile_files() {
+        let files = [
+            ("a.rst", "let x = 1; let x = 2; fn f() -> int { x }"),
+            ("b.rst", "let y = f(); y"),
+            ("c.rst", "let z : int = y; let z = 3; z"),
+        ]
+        .map(|(name, src)| (name.to_string(), src.to_string()));
+
+        let res = compile_files(&files, &Options::default()).unwrap();
+        assert_eq!(res.len(), 3);
+        assert_eq!(res[2].ty, Some(Type::Int));
+        // each file only reports its own warnings
+        let warnings: Vec<usize> = res.iter().map(|res| res.warnings.len()).collect();
+        assert_eq!(warnings, vec![1, 0, 1]);
+
+        let (name, err) = compile_files(&files[1..], &Options::default()).unwrap_err();
+        assert_eq!(name, "b.rst");
+        assert_eq!(diagnostics(&err)[0].code, diagnostics::UNDECLARED_NAME);
+
+        let opts = Options {
+            typecheck: false,
+            ..Options::default()
+        };
+        assert!(compile_files(&files[1..], &opts).is_ok());
+    }
+
+    #[test]
+    fn test_compile_call_main() {
+        let src = "fn main() -> int { 2 }";
+        let res = compile(src, &Options::default()).unwrap();
+        assert_eq!(res.ty, Some(Type::Int));
+        assert_eq!(check(src, &Options::default()).unwrap().ty, Type::Int);
+
+        let opts = Options {
+            call_main: false,
+            ..Options::default()
+        };
+        let res = compile(src, &opts).unwrap();
+        assert_eq!(res.ty, Some(Type::Unit));
+        assert!(res.ast.last_expr.is_none());
+    }
+
+    #[test]
+    fn test_compile_edition() {
+        let src = "let match = 1; match";
+        assert!(compile(src, &Options::default()).is_ok());
+
+        let opts = Options {
+            edition: Edition::E2027,
+            ..Options::default()
+        };
+        let err = compile(src, &opts).unwrap_err();
+        assert_eq!(diagnostics(&err)[0].code, diagnostics::PARSE_ERROR);
+        assert!(check(src, &opts).is_err());
+    }
+
+    #[test]
+    fn test_compile_max_nesting() {
+        // the deepest program the parser accepts also type checks and compiles without overflowing
+        // the stack of a test thread
+        let depth = parser::MAX_NESTING_DEPTH / 2 - 1;
+        let src = format!("{}1{}", "{ ".repeat(depth), " }".repeat(depth));
+        assert_eq!(
+            compile(&src, &Options::default()).unwrap().ty,
+            Some(Type::Int)
+        );
+
+        let src = format!("{}1", "-".repeat(parser::MAX_NESTING_DEPTH - 2));
+        assert_eq!(
+            compile(&src, &Options::default()).unwrap().ty,
+            Some(Type::Int)
+        );
+    }
+}
@@ -0,0 +1,197 @@
+//! Checks of the compiler's own invariants, on with the debug-compiler feature and in tests.
+//!
+//! These catch bugs in the compiler, not in the program being compiled: every failure panics with an
+//! internal compiler error naming the AST node whose code broke the invariant.
+
+use std::fmt::Display;
+
+use bytecode::ByteCode;
+
+use crate::compiler::UNPATCHED;
+
+/// Address an instruction jumps to or starts a function or thread at
+fn jump_target(instr: &ByteCode) -> Option<usize> {
+    match instr {
+        ByteCode::JOF(addr)
+        | ByteCode::GOTO(addr)
+        | ByteCode::LDF(addr, _)
+        | ByteCode::SPAWN(addr, _)
+        | ByteCode::SPAWNISOLATED(addr, _)
+        | ByteCode::SPAWNLIMITED(addr, _) => Some(*addr),
+        _ => None,
+    }
+}
+
+fn ice(msg: &str, node: &dyn Display) -> ! {
+    panic!(
+        "internal compiler error: {}\nwhile compiling: {}",
+        msg, node
+    )
+}
+
+/// The jumps at addresses jumps in code, which the compiler emitted for node, have been patched
+/// with their targets.
+pub(crate) fn check_patched(code: &[ByteCode], jumps: &[usize], node: &dyn Display) {
+    for addr in jumps {
+        match code.get(*addr) {
+            Some(instr) if jump_target(instr) == Some(UNPATCHED) => {
+                ice(&format!("{:?} at {} was never patched", instr, addr), node)
+            }
+            Some(instr) if jump_target(instr).is_some() => (),
+            instr => ice(
+                &format!("expected a jump at {}, found {:?}", addr, instr),
+                node,
+            ),
+        }
+    }
+}
+
+/// Every ENTERSCOPE in code, the code compiled for block, is closed by an EXITSCOPE in the same
+/// block, and nothing exits a scope the block did not enter.
+pub(crate) fn check_scopes(code: &[ByteCode], block: &dyn Display) {
+    let mut depth: usize = 0;
+    for (idx, instr) in code.iter().enumerate() {
+        match instr {
+            ByteCode::ENTERSCOPE(_) | ByteCode::ENTERSCOPELOCAL(_) => depth += 1,
+            ByteCode::EXITSCOPE | ByteCode::EXITSCOPELOCAL => {
+                depth = depth.checked_sub(1).unwrap_or_else(|| {
+                    ice(
+                        &format!("{:?} at offset {} has no ENTERSCOPE", instr, idx),
+                        block,
+                    )
+                });
+            }
+            _ => (),
+        }
+    }
+
+    if depth > 0 {
+        ice(
+            &format!("{} ENTERSCOPE left without EXITSCOPE", depth),
+            block,
+        );
+    }
+}
+
+/// The whole program after stage: it ends in DONE and every jump is inside it.
+pub(crate) fn check_program(code: &[ByteCode], stage: &str) {
+    let stage = format!("the program, after {}", stage);
+
+    if !matches!(code.last(), Some(ByteCode::DONE)) {
+        ice("the program does not end in DONE", &stage);
+    }
+
+    for (addr, instr) in code.iter().enumerate() {
+        if jump_target(instr) == Some(UNPATCHED) {
+            ice(
+                &format!("{:?} at {} was never patched", instr, addr),
+                &stage,
+            );
+        }
+
+        if jump_target(instr).is_some_and(|target| target >= code.len()) {
+            ice(
+                &format!(
+                    "{:?} at {} jumps outside the program of {} instructions",
+                    instr,
+                    addr,
+                    code.len()
+                ),
+                &stage,
+            );
+        }
+    }
+}
+
+/// Nothing is left over from compiling nested loops and functions.
+pub(crate) fn check_done(loop_stack: &[Vec<usize>], fn_depth: usize) {
+    if !loop_stack.is_empty() {
+        ice(
+            &format!("{} loops still open", loop_stack.len()),
+            &"the program",
+        );
+    }
+
+    if fn_depth != 0 {
+        ice(
+            &format!("{} functions still open", fn_depth),
+            &"the program",
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytecode::ByteCode::*;
+    use bytecode::{ByteCode, Value};
+
+    use super::*;
+
+    fn panic_msg(f: impl FnOnce() + std::panic::UnwindSafe) -> String {
+        let err = std::panic::catch_unwind(f).expect_err("Should panic");
+        err.downcast_ref::<String>().cloned().unwrap_or_default()
+    }
+
+    #[test]
+    fn test_check_patched() {
+        check_patched(
+            &[GOTO(UNPATCHED), GOTO(3), LDC(Value::Unit)],
+            &[1],
+            &"loop { }",
+        );
+
+        let msg = panic_msg(|| check_patched(&[POP, JOF(UNPATCHED)], &[1], &"if x { }"));
+        assert_eq!(
+            msg,
+            format!(
+                "internal compiler error: JOF({}) at 1 was never patched\nwhile compiling: if x {{ }}",
+                UNPATCHED
+            )
+        );
+
+        let msg = panic_msg(|| check_patched(&[POP], &[0], &"if x { }"));
+        assert!(msg.contains("expected a jump at 0, found Some(POP)"));
+    }
+
+    #[test]
+    fn test_check_scopes() {
+        check_scopes(
+            &[
+                ByteCode::enterscope(vec!["x"]),
+                ByteCode::enterscope(vec!["y"]),
+                EXITSCOPELOCAL,
+                EXITSCOPE,
+            ],
+            &"{ }",
+        );
+
+        let msg = panic_msg(|| check_scopes(&[ByteCode::enterscope(vec!["x"])], &"{ let x = 1; }"));
+        assert_eq!(
+            msg,
+            "internal compiler error: 1 ENTERSCOPE left without EXITSCOPE\nwhile compiling: { let x = 1; }"
+        );
+
+        let msg = panic_msg(|| check_scopes(&[EXITSCOPE], &"{ }"));
+        assert!(msg.contains("EXITSCOPE at offset 0 has no ENTERSCOPE"));
+    }
+
+    #[test]
+    fn test_check_program() {
+        check_program(&[GOTO(1), DONE], "compile");
+
+        let msg = panic_msg(|| check_program(&[GOTO(2), DONE], "peephole"));
+        assert_eq!(
+            msg,
+            "internal compiler error: GOTO(2) at 0 jumps outside the program of 2 instructions\nwhile compiling: the program, after peephole"
+        );
+
+        let msg = panic_msg(|| check_program(&[GOTO(UNPATCHED), DONE], "compile"));
+        assert!(msg.contains("at 0 was never patched"));
+
+        let msg = panic_msg(|| check_program(&[POP], "compile"));
+        assert!(msg.contains("does not end in DONE"));
+
+        let msg = panic_msg(|| check_done(&[vec![]], 0));
+        assert!(msg.contains("1 loops still open"));
+    }
+}
@@ -1,14 +1,15 @@
+#![allow(clippy::module_inception)]
 #[cfg(test)]
 mod tests {
 
     use std::vec;
 
-    use bytecode::ByteCode;
     use bytecode::ByteCode::*;
     use bytecode::Value::*;
+    use bytecode::{ByteCode, SelectOp};
     use parser::Parser;
 
-    use crate::compiler::Compiler;
+    use crate::compiler::{compile_from_string, diagnostics, Compiler};
 
     fn exp_compile_str(inp: &str) -> Vec<ByteCode> {
         let parser = Parser::new_from_string(inp);
@@ -150,16 +151,68 @@ mod tests {
         test_comp("(4 < 6) == (false == (3 > 3))", exp);
     }
 
+    #[test]
+    fn test_compile_let_same_name() {
+        // one variable per name in a block
+        let exp = vec![
+            ENTERSCOPELOCAL(vec!["x".to_string(), "y".to_string()]),
+            LDC(Int(1)),
+            ASSIGN("x".to_string()),
+            LDC(Unit),
+            POP,
+            LDC(Int(2)),
+            ASSIGN("y".to_string()),
+            LDC(Unit),
+            POP,
+            LDC(Bool(true)),
+            ASSIGN("x".to_string()),
+            LDC(Unit),
+            POP,
+            LD("x".to_string()),
+            EXITSCOPELOCAL,
+            DONE,
+        ];
+        test_comp("let x = 1; let y = 2; let x = true; x", exp);
+
+        // nested blocks still get their own
+        let res = exp_compile_str("let x = 1; { let x = 2; x }");
+        assert_eq!(res[0], ENTERSCOPELOCAL(vec!["x".to_string()]));
+        assert!(res[1..].contains(&ENTERSCOPELOCAL(vec!["x".to_string()])));
+    }
+
+    #[test]
+    fn test_compile_scope_escape() {
+        // a closure or thread keeps the block's environment, and every enclosing one
+        let res = exp_compile_str("let x = 1; { let y = 2; fn f() -> int { y } f() }");
+        assert_eq!(res.iter().filter(|i| **i == EXITSCOPE).count(), 2);
+        assert!(!res.contains(&EXITSCOPELOCAL));
+        assert!(!res.iter().any(|i| matches!(i, ENTERSCOPELOCAL(_))));
+
+        let res = exp_compile_str("fn f() {} let x = 1; { let y = 2; spawn f(); } x");
+        assert_eq!(res.iter().filter(|i| **i == EXITSCOPE).count(), 2);
+
+        // only the block without one is local
+        let res = exp_compile_str("fn f() {} { let y = 2; y } { let z = 3; spawn f(); z }");
+        assert_eq!(res.iter().filter(|i| **i == EXITSCOPE).count(), 2);
+        assert_eq!(res.iter().filter(|i| **i == EXITSCOPELOCAL).count(), 1);
+        assert_eq!(
+            res.iter()
+                .filter(|i| matches!(i, ENTERSCOPELOCAL(_)))
+                .collect::<Vec<_>>(),
+            vec![&ENTERSCOPELOCAL(vec!["y".to_string()])]
+        );
+    }
+
     #[test]
     fn test_compile_let() {
         let res = exp_compile_str("let x = 2;");
         let exp = vec![
-            ENTERSCOPE(vec!["x".to_string()]),
+            ENTERSCOPELOCAL(vec!["x".to_string()]),
             LDC(Int(2)),
             ASSIGN("x".to_string()),
             LDC(Unit),
             POP,
-            EXITSCOPE,
+            EXITSCOPELOCAL,
             DONE,
         ];
 
@@ -168,7 +221,7 @@ mod tests {
         // stmt last
         let res = exp_compile_str("let x = 2; let y = 3; ");
         let exp = vec![
-            ENTERSCOPE(vec!["x".to_string(), "y".to_string()]),
+            ENTERSCOPELOCAL(vec!["x".to_string(), "y".to_string()]),
             LDC(Int(2)),
             ASSIGN("x".to_string()),
             LDC(Unit),
@@ -177,7 +230,7 @@ mod tests {
             ASSIGN("y".to_string()),
             LDC(Unit),
             POP,
-            EXITSCOPE,
+            EXITSCOPELOCAL,
             DONE,
         ];
 
@@ -186,7 +239,7 @@ mod tests {
         // many
         let res = exp_compile_str("let x = 2; let y = 3; 40");
         let exp = vec![
-            ENTERSCOPE(vec!["x".to_string(), "y".to_string()]),
+            ENTERSCOPELOCAL(vec!["x".to_string(), "y".to_string()]),
             LDC(Int(2)),
             ASSIGN("x".to_string()),
             LDC(Unit),
@@ -196,7 +249,7 @@ mod tests {
             LDC(Unit),
             POP,
             LDC(Int(40)),
-            EXITSCOPE,
+            EXITSCOPELOCAL,
             DONE,
         ];
 
@@ -207,7 +260,7 @@ mod tests {
     fn test_compile_sym() {
         let res = exp_compile_str("let x = 2; -x+2;");
         let exp = vec![
-            ENTERSCOPE(vec!["x".to_string()]),
+            ENTERSCOPELOCAL(vec!["x".to_string()]),
             LDC(Int(2)),
             ASSIGN("x".to_string()),
             LDC(Unit),
@@ -217,14 +270,14 @@ mod tests {
             LDC(Int(2)),
             BINOP(bytecode::BinOp::Add),
             POP,
-            EXITSCOPE,
+            EXITSCOPELOCAL,
             DONE,
         ];
         assert_eq!(res, exp);
 
         let res = exp_compile_str("let x = 2; let y = x; x*5+2");
         let exp = vec![
-            ENTERSCOPE(vec!["x".to_string(), "y".to_string()]),
+            ENTERSCOPELOCAL(vec!["x".to_string(), "y".to_string()]),
             LDC(Int(2)),
             ASSIGN("x".to_string()),
             LDC(Unit),
@@ -238,7 +291,7 @@ mod tests {
             BINOP(bytecode::BinOp::Mul),
             LDC(Int(2)),
             BINOP(bytecode::BinOp::Add),
-            EXITSCOPE,
+            EXITSCOPELOCAL,
             DONE,
         ];
 
@@ -276,7 +329,7 @@ mod tests {
     fn test_compile_assign() {
         let res = exp_compile_str("let x = 2; x = 3;");
         let exp = vec![
-            ENTERSCOPE(vec!["x".to_string()]),
+            ENTERSCOPELOCAL(vec!["x".to_string()]),
             LDC(Int(2)),
             ASSIGN("x".to_string()),
             LDC(Unit),
@@ -285,7 +338,7 @@ mod tests {
             ASSIGN("x".to_string()),
             LDC(Unit),
             POP,
-            EXITSCOPE,
+            EXITSCOPELOCAL,
             DONE,
         ];
         assert_eq!(res, exp);
@@ -293,7 +346,7 @@ mod tests {
         // diff types
         let res = exp_compile_str("let x = 2; x = true;");
         let exp = vec![
-            ENTERSCOPE(vec!["x".to_string()]),
+            ENTERSCOPELOCAL(vec!["x".to_string()]),
             LDC(Int(2)),
             ASSIGN("x".to_string()),
             LDC(Unit),
@@ -302,7 +355,7 @@ mod tests {
             ASSIGN("x".to_string()),
             LDC(Unit),
             POP,
-            EXITSCOPE,
+            EXITSCOPELOCAL,
             DONE,
         ];
         assert_eq!(res, exp);
@@ -315,15 +368,15 @@ mod tests {
         test_comp(t, exp);
 
         let t = "{ 2; 3 }";
-        let exp = vec![ByteCode::ldc(2), ByteCode::POP, ByteCode::ldc(3), DONE];
+        let exp = vec![ByteCode::ldc(2), POP, ByteCode::ldc(3), DONE];
         test_comp(t, exp);
 
         let t = "{ 2; 3; }";
         let exp = vec![
             ByteCode::ldc(2),
-            ByteCode::POP,
+            POP,
             ByteCode::ldc(3),
-            ByteCode::POP,
+            POP,
             LDC(Unit),
             DONE,
         ];
@@ -332,9 +385,9 @@ mod tests {
         let t = "{ 2; 3; 4 }";
         let exp = vec![
             ByteCode::ldc(2),
-            ByteCode::POP,
+            POP,
             ByteCode::ldc(3),
-            ByteCode::POP,
+            POP,
             ByteCode::ldc(4),
             DONE,
         ];
@@ -344,11 +397,11 @@ mod tests {
         let t = "{ 2; 3; 4 };";
         let exp = vec![
             ByteCode::ldc(2),
-            ByteCode::POP,
+            POP,
             ByteCode::ldc(3),
-            ByteCode::POP,
+            POP,
             ByteCode::ldc(4),
-            ByteCode::POP,
+            POP,
             DONE,
         ];
         test_comp(t, exp);
@@ -356,13 +409,13 @@ mod tests {
         let t = "{ 2; 3; 4; };";
         let exp = vec![
             ByteCode::ldc(2),
-            ByteCode::POP,
+            POP,
             ByteCode::ldc(3),
-            ByteCode::POP,
+            POP,
             ByteCode::ldc(4),
-            ByteCode::POP,
+            POP,
             ByteCode::ldc(Unit),
-            ByteCode::POP,
+            POP,
             DONE,
         ];
         test_comp(t, exp);
@@ -444,12 +497,12 @@ mod tests {
         test_comp(
             t,
             vec![
-                ENTERSCOPE(vec!["x".to_string()]),
+                ENTERSCOPELOCAL(vec!["x".to_string()]),
                 LDC(Unit),
                 ASSIGN("x".to_string()),
                 LDC(Unit),
                 POP,
-                EXITSCOPE,
+                EXITSCOPELOCAL,
                 DONE,
             ],
         );
@@ -464,12 +517,12 @@ mod tests {
         test_comp(
             t,
             vec![
-                ENTERSCOPE(vec!["x".to_string()]),
+                ENTERSCOPELOCAL(vec!["x".to_string()]),
                 ByteCode::ldc(2),
                 ASSIGN("x".to_string()),
                 ByteCode::ldc(Unit),
                 POP,
-                ENTERSCOPE(vec!["y".to_string()]),
+                ENTERSCOPELOCAL(vec!["y".to_string()]),
                 LDC(Int(3)),
                 ASSIGN("y".to_string()),
                 LDC(Unit),
@@ -477,8 +530,8 @@ mod tests {
                 LD("x".to_string()),
                 LD("y".to_string()),
                 ByteCode::binop("+"),
-                EXITSCOPE,
-                EXITSCOPE,
+                EXITSCOPELOCAL,
+                EXITSCOPELOCAL,
                 DONE,
             ],
         );
@@ -490,7 +543,7 @@ mod tests {
         test_comp(
             t,
             vec![
-                ENTERSCOPE(vec!["x".to_string()]),
+                ENTERSCOPELOCAL(vec!["x".to_string()]),
                 ByteCode::ldc(2),
                 ASSIGN("x".to_string()),
                 LDC(Unit),
@@ -501,7 +554,7 @@ mod tests {
                 POP,
                 LDC(Unit),
                 POP,
-                EXITSCOPE,
+                EXITSCOPELOCAL,
                 DONE,
             ],
         );
@@ -522,7 +575,7 @@ mod tests {
         test_comp(
             t,
             vec![
-                ENTERSCOPE(vec!["x".to_string()]),
+                ENTERSCOPELOCAL(vec!["x".to_string()]),
                 ByteCode::ldc(2),
                 ASSIGN("x".to_string()),
                 LDC(Unit),
@@ -533,7 +586,7 @@ mod tests {
                 POP,
                 LDC(Unit),
                 POP,
-                EXITSCOPE,
+                EXITSCOPELOCAL,
                 DONE,
             ],
         );
@@ -629,7 +682,7 @@ mod tests {
         ";
 
         let exp = vec![
-            ENTERSCOPE(vec!["y".to_string()]),
+            ENTERSCOPELOCAL(vec!["y".to_string()]),
             LDC(Bool(true)),
             ByteCode::ASSIGN("y".to_string()),
             LDC(Unit),
@@ -653,7 +706,7 @@ mod tests {
             LDC(Unit),
             POP,
             ByteCode::ld("y"),
-            EXITSCOPE,
+            EXITSCOPELOCAL,
             DONE,
         ];
 
@@ -734,7 +787,7 @@ mod tests {
         test_comp(
             t,
             vec![
-                ENTERSCOPE(vec!["y".to_string(), "x".to_string()]),
+                ENTERSCOPELOCAL(vec!["y".to_string(), "x".to_string()]),
                 LDC(Bool(true)),
                 ByteCode::ASSIGN("y".to_string()),
                 LDC(Unit),
@@ -752,7 +805,7 @@ mod tests {
                 LDC(Unit),
                 POP,
                 ByteCode::ld("x".to_string()),
-                EXITSCOPE,
+                EXITSCOPELOCAL,
                 DONE,
             ],
         );
@@ -771,7 +824,7 @@ mod tests {
         test_comp(
             t,
             vec![
-                ENTERSCOPE(vec!["x".to_string()]),
+                ENTERSCOPELOCAL(vec!["x".to_string()]),
                 LDC(Bool(true)),
                 JOF(7),
                 LDC(Int(2)),
@@ -785,7 +838,7 @@ mod tests {
                 LDC(Unit),
                 POP,
                 ByteCode::ld("x".to_string()),
-                EXITSCOPE,
+                EXITSCOPELOCAL,
                 DONE,
             ],
         );
@@ -949,7 +1002,7 @@ mod tests {
         test_comp(
             t,
             vec![
-                ENTERSCOPE(vec!["x".to_string()]),
+                ENTERSCOPELOCAL(vec!["x".to_string()]),
                 LDC(Int(0)),
                 ByteCode::assign("x"),
                 LDC(Unit),
@@ -970,7 +1023,7 @@ mod tests {
                 LDC(Unit), // 18 - loop end (load unit as value)
                 POP,
                 ByteCode::ld("x"),
-                EXITSCOPE,
+                EXITSCOPELOCAL,
                 DONE,
             ],
         );
@@ -991,7 +1044,7 @@ mod tests {
         test_comp(
             t,
             vec![
-                ENTERSCOPE(vec!["x".to_string()]),
+                ENTERSCOPELOCAL(vec!["x".to_string()]),
                 LDC(Int(0)),
                 ByteCode::assign("x"),
                 LDC(Unit),
@@ -1022,7 +1075,7 @@ mod tests {
                 LDC(Unit),
                 POP,
                 LD("x".to_string()),
-                EXITSCOPE,
+                EXITSCOPELOCAL,
                 DONE,
             ],
         );
@@ -1030,6 +1083,7 @@ mod tests {
 
     #[test]
     fn test_compile_fn_call() {
+        // builtins with no result push Unit themselves
         let t = "print(2, 3)";
         test_comp(
             t,
@@ -1038,7 +1092,6 @@ mod tests {
                 LDC(Int(2)),
                 LDC(Int(3)),
                 CALL(2),
-                LDC(Unit),
                 DONE,
             ],
         );
@@ -1051,11 +1104,58 @@ mod tests {
                 LDC(Int(2)),
                 LDC(Int(3)),
                 CALL(2),
-                LDC(Unit),
                 POP,
                 DONE,
             ],
         );
+
+        // callee that isn't a name is compiled as an expression
+        let t = "get_fn()(2, 3)";
+        test_comp(
+            t,
+            vec![
+                ByteCode::ld("get_fn"),
+                CALL(0),
+                LDC(Int(2)),
+                LDC(Int(3)),
+                CALL(2),
+                DONE,
+            ],
+        );
+
+        // dbg gets source text of its argument
+        let t = "dbg(x + 1)";
+        test_comp(
+            t,
+            vec![
+                ByteCode::ld("dbg"),
+                ByteCode::ld("x"),
+                LDC(Int(1)),
+                BINOP(bytecode::BinOp::Add),
+                LDC(String("(x+1)".to_string())),
+                CALL(2),
+                DONE,
+            ],
+        );
+
+        // but not a dbg the program declares
+        let t = "fn dbg(x: int) -> int { x * 2 } dbg(3)";
+        let compiled = compile_from_string(t, true).unwrap();
+        let call = [ByteCode::ld("dbg"), LDC(Int(3)), CALL(1)];
+        assert!(compiled.windows(3).any(|instrs| instrs == call));
+        let t = "fn f(dbg: int) -> int { dbg(dbg) }";
+        let compiled = compile_from_string(t, false).unwrap();
+        assert!(!compiled.contains(&LDC(String("dbg".to_string()))));
+
+        // sem_create() is called without a count, the builtin defaults it
+        test_comp(
+            "sem_create()",
+            vec![ByteCode::ld("sem_create"), CALL(0), DONE],
+        );
+        test_comp(
+            "sem_create(0)",
+            vec![ByteCode::ld("sem_create"), LDC(Int(0)), CALL(1), DONE],
+        );
     }
 
     #[test]
@@ -1149,15 +1249,48 @@ mod tests {
             vec![
                 ByteCode::ldc(2),
                 POP,
-                SPAWN(4),
+                // the parent evaluates the function and argument
+                LD("func".to_string()),
+                ByteCode::ldc(1),
+                SPAWN(6, 2),
                 GOTO(9),
                 POP,
+                CALL(1),
+                DONE,
+                POP,
+                ByteCode::ldc(3),
+                POP,
+                DONE,
+            ],
+        );
+
+        let t = "spawn isolated func(1);";
+        test_comp(
+            t,
+            vec![
                 LD("func".to_string()),
                 ByteCode::ldc(1),
+                SPAWNISOLATED(4, 2),
+                GOTO(7),
+                POP,
                 CALL(1),
                 DONE,
                 POP,
-                ByteCode::ldc(3),
+                DONE,
+            ],
+        );
+
+        let t = "spawn_limited(func, 100);";
+        test_comp(
+            t,
+            vec![
+                LD("func".to_string()),
+                ByteCode::ldc(100),
+                SPAWNLIMITED(4, 1),
+                GOTO(7),
+                POP,
+                CALL(0),
+                DONE,
                 POP,
                 DONE,
             ],
@@ -1188,4 +1321,204 @@ mod tests {
             ],
         );
     }
+
+    #[test]
+    fn test_compile_diagnostics() {
+        use diagnostics::{Severity, Stage};
+
+        let err = compile_from_string("let x = ;", true).unwrap_err();
+        let diags = diagnostics(&err);
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0].stage, Stage::Parse);
+        assert_eq!(diags[0].severity, Severity::Error);
+
+        // one diagnostic per type error
+        let err = compile_from_string("x; y;", true).unwrap_err();
+        let diags = diagnostics(&err);
+        assert_eq!(diags.len(), 2);
+        assert_eq!(diags[0].stage, Stage::Type);
+        assert_eq!(diags[0].message, "Identifier 'x' not declared");
+        assert_eq!(
+            diagnostics::render(&diags),
+            "[TypeError]: Identifier 'x' not declared\n[TypeError]: Identifier 'y' not declared"
+        );
+
+        let err = compile_from_string("spawn_limited(f)", false).unwrap_err();
+        let diags = diagnostics(&err);
+        assert_eq!(diags[0].stage, Stage::Compile);
+    }
+
+    #[test]
+    fn test_compile_break_return_placement() {
+        // without type checking, the compiler still rejects these
+        let err = compile_from_string("break;", false).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "[CompileError] -  'break' outside of a loop"
+        );
+
+        let err = compile_from_string("loop { fn f() { break; } break; }", false).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "[CompileError] -  'break' outside of a loop"
+        );
+
+        let err = compile_from_string("{ return 2; }", false).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "[CompileError] -  'return' outside of a function"
+        );
+
+        // type checker reports them first
+        let err = compile_from_string("return;", true).unwrap_err();
+        let diags = diagnostics(&err);
+        assert_eq!(diags[0].message, "'return' outside of a function");
+
+        compile_from_string("loop { fn f() { loop { break; } return; } break; }", false).unwrap();
+    }
+
+    #[test]
+    fn test_compile_scope() {
+        test_comp(
+            "scope { yield; } 2",
+            vec![
+                ENTERTHREADSCOPE,
+                YIELD,
+                LDC(Unit),
+                POP,
+                LDC(Unit),
+                POP,
+                JOINTHREADSCOPE,
+                LDC(Unit),
+                POP,
+                ByteCode::ldc(2),
+                DONE,
+            ],
+        );
+
+        // break and return can't leave a scope block, only loops and functions in it. Without type
+        // checking, the compiler still rejects these
+        let err = compile_from_string("loop { scope { break; } }", false).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "[CompileError] -  Can't break out of a scope block: it must run to its end to join the threads spawned in it"
+        );
+        let err = compile_from_string("fn f() { scope { return; } }", false).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "[CompileError] -  Can't return from a scope block: it must run to its end to join the threads spawned in it"
+        );
+        // type checker reports them first
+        let err = compile_from_string("loop { scope { break; } }", true).unwrap_err();
+        let diags = diagnostics(&err);
+        assert_eq!(
+            diags[0].message,
+            "Can't break out of a scope block: it must run to its end to join the threads spawned in it"
+        );
+        compile_from_string(
+            "scope { loop { break; } fn f() -> int { return 2; } } loop { scope { } break; }",
+            true,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_compile_parallel_loop() {
+        compile_from_string(
+            "fn f(x: int) {} let n = 10; @parallel loop i in 0..n { let x = i; x = x + 1; f(x); } n",
+            true,
+        )
+        .unwrap();
+
+        // without type checking, the compiler still rejects iterations that can't run at the same time
+        let err = compile_from_string(
+            "let sum = 0; @parallel loop i in 0..10 { sum = sum + i; }",
+            false,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "[CompileError] -  A @parallel loop can't assign to 'sum', which is declared outside it: its iterations run at the same time and would race on it"
+        );
+        let err = compile_from_string("loop { @parallel loop i in 0..10 { break; } }", false)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "[CompileError] -  Can't break out of a @parallel loop: its iterations run in threads of their own"
+        );
+        let err = compile_from_string("fn f() { @parallel loop i in 0..10 { return; } }", false)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "[CompileError] -  Can't return from a @parallel loop: its iterations run in threads of their own"
+        );
+        let err = compile_from_string(
+            "let s = 0; fn inc() { s = s + 1; } @parallel loop i in 0..100 { inc(); }",
+            false,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "[CompileError] -  A @parallel loop can't call 'inc', which assigns to 's' declared outside the loop: its iterations run at the same time and would race on it"
+        );
+    }
+
+    #[test]
+    fn test_compile_select() {
+        let arm = "select arm".to_string();
+        let value = "select value".to_string();
+        test_comp(
+            "select { let x = queue_pop(q) => { x } default => { 1 } } 2",
+            vec![
+                ENTERSCOPELOCAL(vec![arm.clone(), value.clone()]),
+                ByteCode::ld("q"),
+                SELECT(vec![SelectOp::Pop], true),
+                ASSIGN(arm.clone()),
+                ASSIGN(value.clone()),
+                LD(arm),
+                LDC(Int(0)),
+                BINOP(bytecode::BinOp::Eq),
+                JOF(17),
+                ENTERSCOPELOCAL(vec!["x".to_string()]),
+                LD(value),
+                ASSIGN("x".to_string()),
+                LDC(Unit),
+                POP,
+                ByteCode::ld("x"),
+                EXITSCOPELOCAL,
+                GOTO(18),
+                ByteCode::ldc(1),
+                POP,
+                EXITSCOPELOCAL,
+                LDC(Unit),
+                POP,
+                ByteCode::ldc(2),
+                DONE,
+            ],
+        );
+
+        // break and return in an arm leave the select like any block
+        compile_from_string(
+            "let q = queue_create(); fn f() -> int { loop { select { queue_push(q, 1) => { break; } } } select { queue_pop(q) => { return 2; } } } f()",
+            true,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_compile_cast() {
+        test_comp(
+            "x as float + 1.5",
+            vec![
+                ByteCode::ld("x"),
+                UNOP(bytecode::UnOp::ToFloat),
+                LDC(Float(1.5)),
+                BINOP(bytecode::BinOp::Add),
+                DONE,
+            ],
+        );
+
+        let err = compile_from_string("2 as str", false).unwrap_err();
+        assert_eq!(err.to_string(), "[CompileError] -  Can't cast to 'str'");
+    }
 }
@@ -1,3 +1,5 @@
+use crate::Value;
+
 pub const PI_SYM: &str = "PI";
 pub const E_SYM: &str = "E";
 pub const MAX_INT_SYM: &str = "MAX_INT";
@@ -5,5 +7,23 @@ pub const MIN_INT_SYM: &str = "MIN_INT";
 pub const MAX_FLOAT_SYM: &str = "MAX_FLOAT";
 pub const MIN_FLOAT_SYM: &str = "MIN_FLOAT";
 pub const EPSILON_SYM: &str = "EPSILON";
+pub const NAN_SYM: &str = "NAN";
+pub const INFINITY_SYM: &str = "INFINITY";
 pub const TRUE_SYM: &str = "true";
 pub const FALSE_SYM: &str = "false";
+
+/// The math and environment constants with their values, which the global environment starts with.
+/// The compiler can load them as literals instead, see oxidate's fold_constants.
+pub fn constants() -> [(&'static str, Value); 9] {
+    [
+        (PI_SYM, Value::Float(std::f64::consts::PI)),
+        (E_SYM, Value::Float(std::f64::consts::E)),
+        (MAX_INT_SYM, Value::Int(i64::MAX)),
+        (MIN_INT_SYM, Value::Int(i64::MIN)),
+        (MAX_FLOAT_SYM, Value::Float(f64::MAX)),
+        (MIN_FLOAT_SYM, Value::Float(f64::MIN)),
+        (EPSILON_SYM, Value::Float(f64::EPSILON)),
+        (NAN_SYM, Value::Float(f64::NAN)),
+        (INFINITY_SYM, Value::Float(f64::INFINITY)),
+    ]
+}
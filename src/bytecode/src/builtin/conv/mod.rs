@@ -2,8 +2,16 @@ pub use atoi::*;
 pub use float_to_int::*;
 pub use int_to_float::*;
 pub use itoa::*;
+pub use parse_failed::*;
+pub use parse_float::*;
+pub use parse_int::*;
+pub use parse_int_radix::*;
 
 mod atoi;
 mod float_to_int;
 mod int_to_float;
 mod itoa;
+mod parse_failed;
+mod parse_float;
+mod parse_int;
+mod parse_int_radix;
@@ -0,0 +1,17 @@
+use std::rc::Weak;
+
+use crate::{FnType, Value, W};
+
+pub const PARSE_FAILED_SYM: &str = "parse_failed";
+
+/// True if the last parse_int, parse_int_radix, parse_float or json_get call in the current thread got
+/// invalid input and returned its sentinel (0, 0.0 or "") instead of a parsed value.
+pub fn parse_failed() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: PARSE_FAILED_SYM.into(),
+        prms: vec![],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
@@ -0,0 +1,24 @@
+use std::rc::Weak;
+
+use anyhow::Result;
+
+use crate::{FnType, Value, W};
+
+pub const PARSE_FLOAT_SYM: &str = "parse_float";
+
+pub fn parse_float() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: PARSE_FLOAT_SYM.into(),
+        prms: vec!["s".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
+
+/// Parse a float, ignoring surrounding whitespace. Integers, exponents, inf and NaN are accepted as in
+/// Rust. None if the string is not a valid float.
+pub fn parse_float_impl(s: &Value) -> Result<Option<Value>> {
+    let s: String = s.clone().try_into()?;
+    Ok(s.trim().parse::<f64>().ok().map(Value::Float))
+}
@@ -0,0 +1,23 @@
+use std::rc::Weak;
+
+use anyhow::Result;
+
+use crate::{FnType, Value, W};
+
+pub const PARSE_INT_SYM: &str = "parse_int";
+
+pub fn parse_int() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: PARSE_INT_SYM.into(),
+        prms: vec!["s".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
+
+/// Parse a base 10 integer, ignoring surrounding whitespace. None if the string is not a valid integer.
+pub fn parse_int_impl(s: &Value) -> Result<Option<Value>> {
+    let s: String = s.clone().try_into()?;
+    Ok(s.trim().parse::<i64>().ok().map(Value::Int))
+}
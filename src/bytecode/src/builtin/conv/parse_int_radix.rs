@@ -0,0 +1,37 @@
+use std::rc::Weak;
+
+use anyhow::Result;
+
+use crate::{ByteCodeError, FnType, Value, W};
+
+pub const PARSE_INT_RADIX_SYM: &str = "parse_int_radix";
+
+pub fn parse_int_radix() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: PARSE_INT_RADIX_SYM.into(),
+        prms: vec!["s".into(), "base".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
+
+/// Parse an integer in the given base (2 to 36), ignoring surrounding whitespace. Letters for digits above
+/// 9 can be upper or lowercase, so this reads what format_int writes. None if the string is not a valid
+/// integer in that base.
+pub fn parse_int_radix_impl(s: &Value, base: &Value) -> Result<Option<Value>> {
+    let s: String = s.clone().try_into()?;
+    let base: i64 = base.clone().try_into()?;
+
+    if !(2..=36).contains(&base) {
+        return Err(ByteCodeError::IllegalArgument(format!(
+            "parse_int_radix base must be between 2 and 36, got {}",
+            base
+        ))
+        .into());
+    }
+
+    Ok(i64::from_str_radix(s.trim(), base as u32)
+        .ok()
+        .map(Value::Int))
+}
@@ -0,0 +1,56 @@
+use std::rc::Weak;
+
+use anyhow::Result;
+
+use crate::{ByteCodeError, FnType, Value, W};
+
+pub const FORMAT_INT_SYM: &str = "format_int";
+
+const DIGITS: &[u8; 36] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+pub fn format_int() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: FORMAT_INT_SYM.into(),
+        prms: vec!["n".into(), "base".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
+
+/// Format an integer in the given base (2 to 36), using lowercase letters for digits above 9.
+/// Negative numbers are written with a leading '-', e.g format_int(-255, 16) is "-ff".
+pub fn format_int_impl(n: &Value, base: &Value) -> Result<Value> {
+    let n: i64 = n.clone().try_into()?;
+    let base: i64 = base.clone().try_into()?;
+
+    if !(2..=36).contains(&base) {
+        return Err(ByteCodeError::IllegalArgument(format!(
+            "format_int base must be between 2 and 36, got {}",
+            base
+        ))
+        .into());
+    }
+
+    // unsigned_abs so i64::MIN does not overflow
+    let mut rem = n.unsigned_abs();
+    let base = base as u64;
+    let mut digits: Vec<u8> = vec![];
+
+    loop {
+        digits.push(DIGITS[(rem % base) as usize]);
+        rem /= base;
+        if rem == 0 {
+            break;
+        }
+    }
+
+    if n < 0 {
+        digits.push(b'-');
+    }
+
+    digits.reverse();
+    let s = String::from_utf8(digits).expect("Digits are ascii");
+
+    Ok(Value::String(s))
+}
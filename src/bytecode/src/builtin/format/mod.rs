@@ -0,0 +1,7 @@
+pub use format_int::*;
+pub use set_float_precision::*;
+pub use to_fixed::*;
+
+mod format_int;
+mod set_float_precision;
+mod to_fixed;
@@ -0,0 +1,34 @@
+use std::rc::Weak;
+
+use anyhow::Result;
+
+use super::MAX_FIXED_DIGITS;
+use crate::{ByteCodeError, FnType, Value, W};
+
+pub const SET_FLOAT_PRECISION_SYM: &str = "set_float_precision";
+
+pub fn set_float_precision() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: SET_FLOAT_PRECISION_SYM.into(),
+        prms: vec!["digits".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
+
+/// The precision floats are printed with from now on: digits after the decimal point, or the
+/// shortest representation that reads back as the same float for -1.
+pub fn set_float_precision_impl(digits: &Value) -> Result<Option<usize>> {
+    let digits: i64 = digits.clone().try_into()?;
+
+    match digits {
+        -1 => Ok(None),
+        0..=MAX_FIXED_DIGITS => Ok(Some(digits as usize)),
+        _ => Err(ByteCodeError::IllegalArgument(format!(
+            "set_float_precision digits must be -1 or between 0 and {}, got {}",
+            MAX_FIXED_DIGITS, digits
+        ))
+        .into()),
+    }
+}
@@ -0,0 +1,37 @@
+use std::rc::Weak;
+
+use anyhow::Result;
+
+use crate::{ByteCodeError, FnType, Value, W};
+
+pub const TO_FIXED_SYM: &str = "to_fixed";
+
+/// Same limit as JavaScript's Number.prototype.toFixed
+pub const MAX_FIXED_DIGITS: i64 = 100;
+
+pub fn to_fixed() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: TO_FIXED_SYM.into(),
+        prms: vec!["f".into(), "digits".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
+
+/// Format a float with exactly `digits` digits after the decimal point, rounding half to even.
+/// Output does not depend on the locale: '.' is always the decimal separator.
+pub fn to_fixed_impl(f: &Value, digits: &Value) -> Result<Value> {
+    let f: f64 = f.clone().try_into()?;
+    let digits: i64 = digits.clone().try_into()?;
+
+    if !(0..=MAX_FIXED_DIGITS).contains(&digits) {
+        return Err(ByteCodeError::IllegalArgument(format!(
+            "to_fixed digits must be between 0 and {}, got {}",
+            MAX_FIXED_DIGITS, digits
+        ))
+        .into());
+    }
+
+    Ok(Value::String(format!("{:.*}", digits as usize, f)))
+}
@@ -0,0 +1,85 @@
+use std::rc::Weak;
+
+use anyhow::Result;
+
+use crate::{FnType, Value, W};
+
+pub const JSON_GET_SYM: &str = "json_get";
+
+pub fn json_get() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: JSON_GET_SYM.into(),
+        prms: vec!["s".into(), "path".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
+
+/// The value at path in the JSON text s. path is object keys and array indices separated by '.',
+/// e.g "servers.0.port", and the empty path is the whole document. A string is given without its
+/// quotes, anything else as JSON text, so numbers can be read with parse_int and parse_float.
+/// None if s is not valid JSON or nothing is at path.
+pub fn json_get_impl(s: &Value, path: &Value) -> Result<Option<Value>> {
+    let s: String = s.clone().try_into()?;
+    let path: String = path.clone().try_into()?;
+
+    let Ok(doc) = serde_json::from_str::<serde_json::Value>(&s) else {
+        return Ok(None);
+    };
+
+    let keys: Vec<&str> = if path.is_empty() {
+        vec![]
+    } else {
+        path.split('.').collect()
+    };
+
+    let mut value = &doc;
+    for key in keys {
+        let next = match value {
+            serde_json::Value::Array(items) => key.parse::<usize>().ok().and_then(|i| items.get(i)),
+            _ => value.get(key),
+        };
+        match next {
+            Some(next) => value = next,
+            None => return Ok(None),
+        }
+    }
+
+    let res = match value {
+        serde_json::Value::String(s) => s.clone(),
+        _ => value.to_string(),
+    };
+    Ok(Some(Value::String(res)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::json_get_impl;
+    use crate::Value;
+
+    const DOC: &str =
+        r#"{"name": "web", "servers": [{"port": 80}, {"port": 8080, "tls": true}], "ratio": 0.5}"#;
+
+    fn get(s: &str, path: &str) -> Option<String> {
+        json_get_impl(&Value::String(s.into()), &Value::String(path.into()))
+            .expect("Should take strings")
+            .map(|v| v.try_into().unwrap())
+    }
+
+    #[test]
+    fn test_json_get() {
+        assert_eq!(get(DOC, "name"), Some("web".into()));
+        assert_eq!(get(DOC, "servers.1.port"), Some("8080".into()));
+        assert_eq!(get(DOC, "servers.1.tls"), Some("true".into()));
+        assert_eq!(get(DOC, "ratio"), Some("0.5".into()));
+        assert_eq!(get(DOC, "servers.0"), Some(r#"{"port":80}"#.into()));
+        assert_eq!(get("[1, 2]", ""), Some("[1,2]".into()));
+        assert_eq!(get(r#"{"": 1}"#, ""), Some(r#"{"":1}"#.into()));
+
+        assert_eq!(get(DOC, "servers.2.port"), None);
+        assert_eq!(get(DOC, "servers.x"), None);
+        assert_eq!(get(DOC, "name.first"), None);
+        assert_eq!(get("{", "name"), None);
+    }
+}
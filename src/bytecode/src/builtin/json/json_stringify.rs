@@ -0,0 +1,91 @@
+use std::rc::Weak;
+
+use anyhow::Result;
+use serde_json::Number;
+
+use crate::{type_of, ByteCodeError, FnType, Value, W};
+
+pub const JSON_STRINGIFY_SYM: &str = "json_stringify";
+
+pub fn json_stringify() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: JSON_STRINGIFY_SYM.into(),
+        prms: vec!["v".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
+
+/// The value as JSON text: unit is null and a matrix is an array of its rows.
+///
+/// # Errors
+///
+/// If the value has no JSON form, e.g a semaphore, or is a float JSON can't represent: NaN or infinity.
+pub fn json_stringify_impl(v: &Value) -> Result<Value> {
+    Ok(Value::String(to_json(v)?.to_string()))
+}
+
+fn to_json(v: &Value) -> Result<serde_json::Value> {
+    let json = match v {
+        Value::Unit => serde_json::Value::Null,
+        Value::Int(n) => (*n).into(),
+        Value::Float(f) => float_to_json(*f)?,
+        Value::Bool(b) => (*b).into(),
+        Value::String(s) => s.as_str().into(),
+        Value::Matrix(m) => {
+            let m = m.0.borrow();
+            let rows = (0..m.rows)
+                .map(|r| m.data[r * m.cols..(r + 1) * m.cols].iter())
+                .map(|row| row.map(|f| float_to_json(*f)).collect())
+                .collect::<Result<Vec<_>>>()?;
+            serde_json::Value::Array(rows)
+        }
+        _ => {
+            let e = format!("json_stringify can't encode a {}", type_of(v));
+            return Err(ByteCodeError::IllegalArgument(e).into());
+        }
+    };
+
+    Ok(json)
+}
+
+fn float_to_json(f: f64) -> Result<serde_json::Value> {
+    let n = Number::from_f64(f).ok_or_else(|| {
+        ByteCodeError::IllegalArgument(format!("json_stringify can't encode {}", f))
+    })?;
+    Ok(serde_json::Value::Number(n))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::json_stringify_impl;
+    use crate::{MatrixData, Value};
+
+    fn stringify(v: Value) -> String {
+        json_stringify_impl(&v)
+            .expect("Should encode")
+            .try_into()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_json_stringify() {
+        assert_eq!(stringify(Value::Int(-3)), "-3");
+        assert_eq!(stringify(Value::Float(2.5)), "2.5");
+        assert_eq!(stringify(Value::Bool(true)), "true");
+        assert_eq!(stringify(Value::Unit), "null");
+        assert_eq!(
+            stringify(Value::String("say \"hi\"\n".into())),
+            r#""say \"hi\"\n""#
+        );
+
+        let m: Value = MatrixData::new(2, 2, 1.5).unwrap().into();
+        assert_eq!(stringify(m), "[[1.5,1.5],[1.5,1.5]]");
+        let empty: Value = MatrixData::new(2, 0, 0.0).unwrap().into();
+        assert_eq!(stringify(empty), "[[],[]]");
+
+        assert!(json_stringify_impl(&Value::Float(f64::NAN)).is_err());
+        assert!(json_stringify_impl(&Value::Unitialized).is_err());
+    }
+}
@@ -0,0 +1,5 @@
+pub use json_get::*;
+pub use json_stringify::*;
+
+mod json_get;
+mod json_stringify;
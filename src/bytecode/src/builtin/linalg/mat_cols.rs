@@ -0,0 +1,24 @@
+use std::rc::Weak;
+
+use anyhow::Result;
+
+use crate::{FnType, Matrix, Value, W};
+
+pub const MAT_COLS_SYM: &str = "mat_cols";
+
+pub fn mat_cols() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: MAT_COLS_SYM.into(),
+        prms: vec!["m".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
+
+/// The number of columns of m.
+pub fn mat_cols_impl(m: &Value) -> Result<Value> {
+    let m: Matrix = m.clone().try_into()?;
+    let cols = m.borrow().cols;
+    Ok(Value::Int(cols as i64))
+}
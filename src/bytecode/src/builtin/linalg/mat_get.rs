@@ -0,0 +1,28 @@
+use std::rc::Weak;
+
+use anyhow::Result;
+
+use crate::{FnType, Matrix, Value, W};
+
+pub const MAT_GET_SYM: &str = "mat_get";
+
+pub fn mat_get() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: MAT_GET_SYM.into(),
+        prms: vec!["m".into(), "i".into(), "j".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
+
+/// The element of m in row i and column j, counting from 0.
+pub fn mat_get_impl(m: &Value, i: &Value, j: &Value) -> Result<Value> {
+    let m: Matrix = m.clone().try_into()?;
+    let i: i64 = i.clone().try_into()?;
+    let j: i64 = j.clone().try_into()?;
+
+    let m = m.borrow();
+    let idx = m.index(i, j)?;
+    Ok(Value::Float(m.data[idx]))
+}
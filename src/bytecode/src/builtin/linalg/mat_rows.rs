@@ -0,0 +1,24 @@
+use std::rc::Weak;
+
+use anyhow::Result;
+
+use crate::{FnType, Matrix, Value, W};
+
+pub const MAT_ROWS_SYM: &str = "mat_rows";
+
+pub fn mat_rows() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: MAT_ROWS_SYM.into(),
+        prms: vec!["m".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
+
+/// The number of rows of m.
+pub fn mat_rows_impl(m: &Value) -> Result<Value> {
+    let m: Matrix = m.clone().try_into()?;
+    let rows = m.borrow().rows;
+    Ok(Value::Int(rows as i64))
+}
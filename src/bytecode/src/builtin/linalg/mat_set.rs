@@ -0,0 +1,30 @@
+use std::rc::Weak;
+
+use anyhow::Result;
+
+use crate::{FnType, Matrix, Value, W};
+
+pub const MAT_SET_SYM: &str = "mat_set";
+
+pub fn mat_set() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: MAT_SET_SYM.into(),
+        prms: vec!["m".into(), "i".into(), "j".into(), "x".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
+
+/// Set the element of m in row i and column j to x. Every holder of m sees the change.
+pub fn mat_set_impl(m: &Value, i: &Value, j: &Value, x: &Value) -> Result<()> {
+    let m: Matrix = m.clone().try_into()?;
+    let i: i64 = i.clone().try_into()?;
+    let j: i64 = j.clone().try_into()?;
+    let x: f64 = x.clone().try_into()?;
+
+    let mut m = m.borrow_mut();
+    let idx = m.index(i, j)?;
+    m.data[idx] = x;
+    Ok(())
+}
@@ -0,0 +1,25 @@
+use std::rc::Weak;
+
+use anyhow::Result;
+
+use crate::{FnType, Matrix, Value, W};
+
+pub const MATMUL_SYM: &str = "matmul";
+
+pub fn matmul() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: MATMUL_SYM.into(),
+        prms: vec!["a".into(), "b".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
+
+/// The product a * b as a new matrix. a must have as many columns as b has rows.
+pub fn matmul_impl(a: &Value, b: &Value) -> Result<Value> {
+    let a: Matrix = a.clone().try_into()?;
+    let b: Matrix = b.clone().try_into()?;
+    let product = a.borrow().matmul(&b.borrow())?;
+    Ok(product.into())
+}
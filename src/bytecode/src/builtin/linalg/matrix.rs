@@ -0,0 +1,25 @@
+use std::rc::Weak;
+
+use anyhow::Result;
+
+use crate::{FnType, MatrixData, Value, W};
+
+pub const MATRIX_SYM: &str = "matrix";
+
+pub fn matrix() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: MATRIX_SYM.into(),
+        prms: vec!["rows".into(), "cols".into(), "init".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
+
+/// A new rows x cols matrix with every element set to init.
+pub fn matrix_impl(rows: &Value, cols: &Value, init: &Value) -> Result<Value> {
+    let rows: i64 = rows.clone().try_into()?;
+    let cols: i64 = cols.clone().try_into()?;
+    let init: f64 = init.clone().try_into()?;
+    Ok(MatrixData::new(rows, cols, init)?.into())
+}
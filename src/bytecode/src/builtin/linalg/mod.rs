@@ -0,0 +1,13 @@
+pub use mat_cols::*;
+pub use mat_get::*;
+pub use mat_rows::*;
+pub use mat_set::*;
+pub use matmul::*;
+pub use matrix::*;
+
+mod mat_cols;
+mod mat_get;
+mod mat_rows;
+mod mat_set;
+mod matmul;
+mod matrix;
@@ -18,7 +18,10 @@ pub fn abs() -> Value {
 
 pub fn abs_impl(x: &Value) -> Result<Value> {
     match x.clone() {
-        Value::Int(x) => Ok(Value::Int(x.abs())),
+        // only MIN_INT overflows
+        Value::Int(x) => Ok(Value::Int(x.checked_abs().ok_or(
+            ByteCodeError::IllegalArgument(format!("abs({}) overflows", x)),
+        )?)),
         Value::Float(x) => Ok(Value::Float(x.abs())),
         _ => Err(ByteCodeError::BadType {
             expected: "Integer or Float".to_string(),
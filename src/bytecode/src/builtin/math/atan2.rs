@@ -0,0 +1,24 @@
+use std::rc::Weak;
+
+use anyhow::Result;
+
+use crate::{FnType, Value, W};
+
+pub const ATAN2_SYM: &str = "atan2";
+
+pub fn atan2() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: ATAN2_SYM.into(),
+        prms: vec!["y".into(), "x".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
+
+/// Four quadrant arctangent of y / x in radians
+pub fn atan2_impl(y: &Value, x: &Value) -> Result<Value> {
+    let y: f64 = y.clone().try_into()?;
+    let x: f64 = x.clone().try_into()?;
+    Ok(Value::Float(y.atan2(x)))
+}
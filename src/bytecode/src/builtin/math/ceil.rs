@@ -0,0 +1,23 @@
+use std::rc::Weak;
+
+use anyhow::Result;
+
+use crate::{FnType, Value, W};
+
+pub const CEIL_SYM: &str = "ceil";
+
+pub fn ceil() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: CEIL_SYM.into(),
+        prms: vec!["x".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
+
+/// Smallest integer value greater than or equal to x, as a float
+pub fn ceil_impl(x: &Value) -> Result<Value> {
+    let x: f64 = x.clone().try_into()?;
+    Ok(Value::Float(x.ceil()))
+}
@@ -0,0 +1,59 @@
+use std::rc::Weak;
+
+use anyhow::Result;
+
+use crate::{ByteCodeError, FnType, Value, W};
+
+pub const CLAMP_SYM: &str = "clamp";
+
+pub fn clamp() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: CLAMP_SYM.into(),
+        prms: vec!["x".into(), "lo".into(), "hi".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
+
+/// Restrict x to the range [lo, hi]. All three must be ints or all floats.
+/// A NaN x gives NaN, but lo > hi or a NaN bound is an error instead of a panic.
+pub fn clamp_impl(x: &Value, lo: &Value, hi: &Value) -> Result<Value> {
+    match (x.clone(), lo.clone(), hi.clone()) {
+        (Value::Int(x), Value::Int(lo), Value::Int(hi)) => {
+            if lo > hi {
+                return Err(bad_bounds(&Value::Int(lo), &Value::Int(hi)));
+            }
+            Ok(Value::Int(x.clamp(lo, hi)))
+        }
+        (Value::Float(x), Value::Float(lo), Value::Float(hi)) => {
+            // NaN bounds are rejected too
+            if lo.is_nan() || hi.is_nan() || lo > hi {
+                return Err(bad_bounds(&Value::Float(lo), &Value::Float(hi)));
+            }
+            Ok(Value::Float(x.clamp(lo, hi)))
+        }
+        _ => Err(ByteCodeError::TypeMismatch {
+            expected: crate::type_of(x).to_string(),
+            found: format!("{}, {}", crate::type_of(lo), crate::type_of(hi)),
+        }
+        .into()),
+    }
+}
+
+fn bad_bounds(lo: &Value, hi: &Value) -> anyhow::Error {
+    ByteCodeError::IllegalArgument(format!(
+        "clamp bounds must satisfy lo <= hi, got {} and {}",
+        describe(lo),
+        describe(hi)
+    ))
+    .into()
+}
+
+/// A bound with its type, printing floats with their decimal point so 2.0 isn't shown as 2
+fn describe(bound: &Value) -> String {
+    match bound {
+        Value::Float(f) => format!("{} {:?}", crate::type_of(bound), f),
+        _ => format!("{} {}", crate::type_of(bound), bound),
+    }
+}
@@ -0,0 +1,42 @@
+use std::rc::Weak;
+
+use anyhow::Result;
+
+use crate::{ByteCodeError, FnType, Value, W};
+
+pub const DIV_SYM: &str = "div";
+
+pub fn div() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: DIV_SYM.into(),
+        prms: vec!["a".into(), "b".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
+
+/// Integer division rounding towards negative infinity, e.g div(-7, 2) is -4.
+/// The / operator on ints truncates towards zero instead (-7 / 2 is -3).
+pub fn div_impl(a: &Value, b: &Value) -> Result<Value> {
+    let a: i64 = a.clone().try_into()?;
+    let b: i64 = b.clone().try_into()?;
+
+    if b == 0 {
+        return Err(ByteCodeError::DivisionByZero.into());
+    }
+
+    // only MIN_INT / -1 overflows
+    let q = a
+        .checked_div(b)
+        .ok_or(ByteCodeError::IllegalArgument(format!(
+            "div({}, {}) overflows",
+            a, b
+        )))?;
+
+    if a % b != 0 && ((a < 0) != (b < 0)) {
+        Ok(Value::Int(q - 1))
+    } else {
+        Ok(Value::Int(q))
+    }
+}
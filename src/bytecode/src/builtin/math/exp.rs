@@ -0,0 +1,23 @@
+use std::rc::Weak;
+
+use anyhow::Result;
+
+use crate::{FnType, Value, W};
+
+pub const EXP_SYM: &str = "exp";
+
+pub fn exp() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: EXP_SYM.into(),
+        prms: vec!["x".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
+
+/// e^x
+pub fn exp_impl(x: &Value) -> Result<Value> {
+    let x: f64 = x.clone().try_into()?;
+    Ok(Value::Float(x.exp()))
+}
@@ -0,0 +1,24 @@
+use std::rc::Weak;
+
+use anyhow::Result;
+
+use crate::{FnType, Value, W};
+
+pub const FDIV_SYM: &str = "fdiv";
+
+pub fn fdiv() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: FDIV_SYM.into(),
+        prms: vec!["a".into(), "b".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
+
+/// Divide two ints as floats, e.g fdiv(4, 5) is 0.8. Dividing by zero follows float semantics.
+pub fn fdiv_impl(a: &Value, b: &Value) -> Result<Value> {
+    let a: i64 = a.clone().try_into()?;
+    let b: i64 = b.clone().try_into()?;
+    Ok(Value::Float(a as f64 / b as f64))
+}
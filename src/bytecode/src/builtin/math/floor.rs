@@ -0,0 +1,23 @@
+use std::rc::Weak;
+
+use anyhow::Result;
+
+use crate::{FnType, Value, W};
+
+pub const FLOOR_SYM: &str = "floor";
+
+pub fn floor() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: FLOOR_SYM.into(),
+        prms: vec!["x".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
+
+/// Largest integer value less than or equal to x, as a float
+pub fn floor_impl(x: &Value) -> Result<Value> {
+    let x: f64 = x.clone().try_into()?;
+    Ok(Value::Float(x.floor()))
+}
@@ -0,0 +1,24 @@
+use std::rc::Weak;
+
+use anyhow::Result;
+
+use crate::{FnType, Value, W};
+
+pub const HYPOT_SYM: &str = "hypot";
+
+pub fn hypot() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: HYPOT_SYM.into(),
+        prms: vec!["x".into(), "y".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
+
+/// Length of the hypotenuse, sqrt(x^2 + y^2) without intermediate overflow
+pub fn hypot_impl(x: &Value, y: &Value) -> Result<Value> {
+    let x: f64 = x.clone().try_into()?;
+    let y: f64 = y.clone().try_into()?;
+    Ok(Value::Float(x.hypot(y)))
+}
@@ -0,0 +1,23 @@
+use std::rc::Weak;
+
+use anyhow::Result;
+
+use crate::{FnType, Value, W};
+
+pub const IS_FINITE_SYM: &str = "is_finite";
+
+pub fn is_finite() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: IS_FINITE_SYM.into(),
+        prms: vec!["x".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
+
+/// True if x is neither infinite nor NaN
+pub fn is_finite_impl(x: &Value) -> Result<Value> {
+    let x: f64 = x.clone().try_into()?;
+    Ok(Value::Bool(x.is_finite()))
+}
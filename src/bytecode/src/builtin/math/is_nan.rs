@@ -0,0 +1,23 @@
+use std::rc::Weak;
+
+use anyhow::Result;
+
+use crate::{FnType, Value, W};
+
+pub const IS_NAN_SYM: &str = "is_nan";
+
+pub fn is_nan() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: IS_NAN_SYM.into(),
+        prms: vec!["x".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
+
+/// True if x is NaN. Use this instead of x == NAN, which is always false
+pub fn is_nan_impl(x: &Value) -> Result<Value> {
+    let x: f64 = x.clone().try_into()?;
+    Ok(Value::Bool(x.is_nan()))
+}
@@ -0,0 +1,23 @@
+use std::rc::Weak;
+
+use anyhow::Result;
+
+use crate::{FnType, Value, W};
+
+pub const LOG10_SYM: &str = "log10";
+
+pub fn log10() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: LOG10_SYM.into(),
+        prms: vec!["x".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
+
+/// Base 10 logarithm. log10(0.0) is -INFINITY and negative x gives NaN
+pub fn log10_impl(x: &Value) -> Result<Value> {
+    let x: f64 = x.clone().try_into()?;
+    Ok(Value::Float(x.log10()))
+}
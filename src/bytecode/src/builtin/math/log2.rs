@@ -0,0 +1,23 @@
+use std::rc::Weak;
+
+use anyhow::Result;
+
+use crate::{FnType, Value, W};
+
+pub const LOG2_SYM: &str = "log2";
+
+pub fn log2() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: LOG2_SYM.into(),
+        prms: vec!["x".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
+
+/// Base 2 logarithm. log2(0.0) is -INFINITY and negative x gives NaN
+pub fn log2_impl(x: &Value) -> Result<Value> {
+    let x: f64 = x.clone().try_into()?;
+    Ok(Value::Float(x.log2()))
+}
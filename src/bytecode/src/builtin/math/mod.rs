@@ -1,19 +1,47 @@
 pub use abs::*;
+pub use atan2::*;
+pub use ceil::*;
+pub use clamp::*;
 pub use cos::*;
+pub use div::*;
+pub use exp::*;
+pub use fdiv::*;
+pub use floor::*;
+pub use hypot::*;
+pub use is_finite::*;
+pub use is_nan::*;
 pub use log::*;
+pub use log10::*;
+pub use log2::*;
 pub use max::*;
 pub use min::*;
 pub use pow::*;
+pub use round::*;
 pub use sin::*;
 pub use sqrt::*;
 pub use tan::*;
+pub use trunc::*;
 
 mod abs;
+mod atan2;
+mod ceil;
+mod clamp;
 mod cos;
+mod div;
+mod exp;
+mod fdiv;
+mod floor;
+mod hypot;
+mod is_finite;
+mod is_nan;
 mod log;
+mod log10;
+mod log2;
 mod max;
 mod min;
 mod pow;
+mod round;
 mod sin;
 mod sqrt;
 mod tan;
+mod trunc;
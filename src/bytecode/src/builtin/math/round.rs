@@ -0,0 +1,23 @@
+use std::rc::Weak;
+
+use anyhow::Result;
+
+use crate::{FnType, Value, W};
+
+pub const ROUND_SYM: &str = "round";
+
+pub fn round() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: ROUND_SYM.into(),
+        prms: vec!["x".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
+
+/// Nearest integer value to x as a float, rounding half-way cases away from zero
+pub fn round_impl(x: &Value) -> Result<Value> {
+    let x: f64 = x.clone().try_into()?;
+    Ok(Value::Float(x.round()))
+}
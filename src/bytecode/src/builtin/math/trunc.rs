@@ -0,0 +1,23 @@
+use std::rc::Weak;
+
+use anyhow::Result;
+
+use crate::{FnType, Value, W};
+
+pub const TRUNC_SYM: &str = "trunc";
+
+pub fn trunc() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: TRUNC_SYM.into(),
+        prms: vec!["x".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
+
+/// Integer part of x as a float, rounding towards zero
+pub fn trunc_impl(x: &Value) -> Result<Value> {
+    let x: f64 = x.clone().try_into()?;
+    Ok(Value::Float(x.trunc()))
+}
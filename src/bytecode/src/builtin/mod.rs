@@ -1,17 +1,33 @@
 pub use constants::*;
 pub use conv::*;
+pub use format::*;
+pub use json::*;
+pub use linalg::*;
 pub use math::*;
+pub use process::*;
+pub use queue::*;
 pub use semaphore::*;
+pub use stderr::*;
 pub use stdin::*;
 pub use stdout::*;
 pub use string::*;
+pub use thread::*;
+pub use value::*;
 
 mod constants;
 mod conv;
+mod format;
+mod json;
+mod linalg;
 mod math;
+mod process;
+mod queue;
 mod semaphore;
+mod stderr;
 mod stdin;
 mod stdout;
 mod string;
+mod thread;
+mod value;
 
 pub const BUILTIN_SYM: &str = "BUILTIN";
@@ -0,0 +1,218 @@
+use std::io::Read;
+use std::process::{Child, Command, Stdio};
+use std::rc::Weak;
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+
+use crate::{ByteCodeError, FnType, Value, W};
+
+pub const EXEC_SYM: &str = "exec";
+pub const EXEC_STDOUT_SYM: &str = "exec_stdout";
+pub const EXEC_STDERR_SYM: &str = "exec_stderr";
+
+/// How often exec checks whether a command with a timeout has finished
+const EXEC_POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// How long exec waits for the output of a command it killed
+const EXEC_KILL_GRACE: Duration = Duration::from_millis(100);
+
+/// exec(cmd, timeout_ms) runs cmd with the shell and returns its exit code. The VM keeps its output
+/// for exec_stdout and exec_stderr.
+pub fn exec() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: EXEC_SYM.into(),
+        prms: vec!["cmd".into(), "timeout_ms".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
+
+/// exec_stdout() is what the last exec call in the current thread printed to stdout.
+pub fn exec_stdout() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: EXEC_STDOUT_SYM.into(),
+        prms: vec![],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
+
+/// exec_stderr() is what the last exec call in the current thread printed to stderr.
+pub fn exec_stderr() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: EXEC_STDERR_SYM.into(),
+        prms: vec![],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
+
+/// What a command run by exec did.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ExecOutput {
+    /// The command's exit code, or -1 if it was killed by a signal or for running out of time.
+    pub code: i64,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// Run cmd with sh -c (cmd /C on Windows), without stdin, and wait for it to finish. If it runs
+/// for longer than timeout_ms milliseconds, it is killed. A timeout of 0 or less waits for as long
+/// as the command runs.
+///
+/// The command runs to completion before exec returns, so no other thread runs in the meantime.
+///
+/// # Errors
+///
+/// If the shell couldn't be started.
+pub fn exec_impl(cmd: &Value, timeout_ms: &Value) -> Result<ExecOutput> {
+    let cmd: String = cmd.clone().try_into()?;
+    let timeout_ms: i64 = timeout_ms.clone().try_into()?;
+
+    let mut command = if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.arg("/C").arg(&cmd);
+        command
+    } else {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(&cmd);
+        command
+    };
+
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| {
+            ByteCodeError::IllegalArgument(format!("exec couldn't run '{}': {}", cmd, err))
+        })?;
+
+    // read both pipes while waiting, so a command filling one of them doesn't block
+    let stdout = Pipe::read(child.stdout.take());
+    let stderr = Pipe::read(child.stderr.take());
+
+    let timeout = u64::try_from(timeout_ms)
+        .ok()
+        .filter(|ms| *ms > 0)
+        .map(Duration::from_millis);
+    let code = wait(&mut child, timeout)?;
+
+    // a command killed for running out of time may have left processes behind holding its pipes
+    // open, so only wait a little for the rest of its output
+    let grace = timeout.map(|_| Instant::now() + EXEC_KILL_GRACE);
+    Ok(ExecOutput {
+        code,
+        stdout: stdout.output(grace),
+        stderr: stderr.output(grace),
+    })
+}
+
+/// Wait for the child to exit, killing it after timeout if there is one. Its exit code, or -1.
+fn wait(child: &mut Child, timeout: Option<Duration>) -> Result<i64> {
+    let status = match timeout {
+        None => child.wait()?,
+        Some(timeout) => {
+            let deadline = Instant::now() + timeout;
+            loop {
+                if let Some(status) = child.try_wait()? {
+                    break status;
+                }
+
+                let now = Instant::now();
+                if now >= deadline {
+                    child.kill()?;
+                    child.wait()?;
+                    return Ok(-1);
+                }
+                std::thread::sleep(EXEC_POLL_INTERVAL.min(deadline - now));
+            }
+        }
+    };
+
+    Ok(status.code().map_or(-1, i64::from))
+}
+
+/// One of the child's output pipes, read to the end on a thread of its own.
+struct Pipe {
+    out: Arc<Mutex<Vec<u8>>>,
+    reader: JoinHandle<()>,
+}
+
+impl Pipe {
+    fn read<R: Read + Send + 'static>(pipe: Option<R>) -> Pipe {
+        let out = Arc::new(Mutex::new(vec![]));
+        let buf = Arc::clone(&out);
+        let reader = std::thread::spawn(move || {
+            let Some(mut pipe) = pipe else {
+                return;
+            };
+            let mut chunk = [0; 4096];
+            // whatever was read before an error is still the command's output
+            while let Ok(n @ 1..) = pipe.read(&mut chunk) {
+                buf.lock()
+                    .expect("Reader doesn't panic")
+                    .extend(&chunk[..n]);
+            }
+        });
+
+        Pipe { out, reader }
+    }
+
+    /// Everything read from the pipe, waiting until it is closed or, if given, until deadline.
+    fn output(self, deadline: Option<Instant>) -> String {
+        match deadline {
+            None => {
+                let _ = self.reader.join();
+            }
+            Some(deadline) => {
+                while !self.reader.is_finished() && Instant::now() < deadline {
+                    std::thread::sleep(EXEC_POLL_INTERVAL);
+                }
+            }
+        }
+
+        let out = self.out.lock().expect("Reader doesn't panic");
+        String::from_utf8_lossy(&out).into_owned()
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::{exec_impl, ExecOutput};
+    use crate::Value;
+
+    fn run(cmd: &str, timeout_ms: i64) -> ExecOutput {
+        exec_impl(&Value::String(cmd.into()), &Value::Int(timeout_ms)).expect("Should run sh")
+    }
+
+    #[test]
+    fn test_exec() {
+        let out = run("echo out; echo err >&2; exit 3", 0);
+        assert_eq!(
+            out,
+            ExecOutput {
+                code: 3,
+                stdout: "out\n".into(),
+                stderr: "err\n".into(),
+            }
+        );
+
+        // output bigger than a pipe's buffer doesn't block the command
+        let out = run("head -c 200000 /dev/zero", 10_000);
+        assert_eq!((out.code, out.stdout.len()), (0, 200000));
+
+        // killed when it runs out of time, without waiting for the sleep it started
+        let start = std::time::Instant::now();
+        let out = run("echo started; sleep 5", 100);
+        assert_eq!(out.code, -1);
+        assert_eq!(out.stdout, "started\n");
+        assert!(start.elapsed().as_secs() < 2);
+    }
+}
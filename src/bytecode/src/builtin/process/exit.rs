@@ -0,0 +1,26 @@
+use std::rc::Weak;
+
+use anyhow::Result;
+
+use crate::{ByteCodeError, FnType, Value, W};
+
+pub const EXIT_SYM: &str = "exit";
+
+pub fn exit() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: EXIT_SYM.into(),
+        prms: vec!["code".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
+
+/// Get the process exit code from the argument. The VM is responsible for stopping the program.
+pub fn exit_impl(code: &Value) -> Result<i32> {
+    let code: i64 = code.clone().try_into()?;
+    let code = i32::try_from(code).map_err(|_| {
+        ByteCodeError::IllegalArgument(format!("exit code {} is out of range", code))
+    })?;
+    Ok(code)
+}
@@ -0,0 +1,7 @@
+#[cfg(feature = "exec")]
+pub use exec::*;
+pub use exit::*;
+
+#[cfg(feature = "exec")]
+mod exec;
+mod exit;
@@ -0,0 +1,17 @@
+use std::rc::Weak;
+
+use crate::{FnType, Value, W};
+
+pub const FILTER_SYM: &str = "filter";
+
+/// filter(q, pred) returns a new queue with the values of queue q for which pred returns true, in
+/// order. q is not changed. The VM implements it.
+pub fn filter() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: FILTER_SYM.into(),
+        prms: vec!["q".into(), "pred".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
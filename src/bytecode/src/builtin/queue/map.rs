@@ -0,0 +1,17 @@
+use std::rc::Weak;
+
+use crate::{FnType, Value, W};
+
+pub const MAP_SYM: &str = "map";
+
+/// map(q, f) returns a new queue with f applied to each value of queue q, in order. q is not changed.
+/// The VM implements it, since f is called like any other function.
+pub fn map() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: MAP_SYM.into(),
+        prms: vec!["q".into(), "f".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
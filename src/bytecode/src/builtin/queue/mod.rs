@@ -0,0 +1,19 @@
+pub use filter::*;
+pub use map::*;
+pub use queue_create::*;
+pub use queue_create_bounded::*;
+pub use queue_pop::*;
+pub use queue_push::*;
+pub use queue_try_pop::*;
+pub use queue_try_push::*;
+pub use reduce::*;
+
+mod filter;
+mod map;
+mod queue_create;
+mod queue_create_bounded;
+mod queue_pop;
+mod queue_push;
+mod queue_try_pop;
+mod queue_try_push;
+mod reduce;
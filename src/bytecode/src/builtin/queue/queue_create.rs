@@ -0,0 +1,19 @@
+use std::rc::Weak;
+
+use crate::{FnType, Queue, Value, W};
+
+pub const QUEUE_CREATE_SYM: &str = "queue_create";
+
+pub fn queue_create() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: QUEUE_CREATE_SYM.into(),
+        prms: vec![],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
+
+pub fn queue_create_impl() -> Value {
+    Queue::new().into()
+}
@@ -0,0 +1,32 @@
+use std::rc::Weak;
+
+use anyhow::Result;
+
+use crate::{ByteCodeError, FnType, Queue, Value, W};
+
+pub const QUEUE_CREATE_BOUNDED_SYM: &str = "queue_create_bounded";
+
+pub fn queue_create_bounded() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: QUEUE_CREATE_BOUNDED_SYM.into(),
+        prms: vec!["capacity".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
+
+/// Create a queue holding at most capacity values, which must be at least 1.
+pub fn queue_create_bounded_impl(capacity: &Value) -> Result<Value> {
+    let capacity: i64 = capacity.clone().try_into()?;
+    let cap = usize::try_from(capacity)
+        .ok()
+        .filter(|cap| *cap > 0)
+        .ok_or_else(|| {
+            ByteCodeError::IllegalArgument(format!(
+                "queue capacity must be at least 1, got {}",
+                capacity
+            ))
+        })?;
+    Ok(Queue::bounded(cap).into())
+}
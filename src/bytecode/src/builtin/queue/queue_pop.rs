@@ -0,0 +1,24 @@
+use std::rc::Weak;
+
+use anyhow::Result;
+
+use crate::{FnType, Queue, Value, W};
+
+pub const QUEUE_POP_SYM: &str = "queue_pop";
+
+pub fn queue_pop() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: QUEUE_POP_SYM.into(),
+        prms: vec!["q".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
+
+/// Remove the value at the front of the queue. None if the queue is empty, the VM is responsible for blocking the thread.
+pub fn queue_pop_impl(q: &Value) -> Result<Option<Value>> {
+    let q: Queue = q.clone().try_into()?;
+    let front = q.borrow_mut().values.pop_front();
+    Ok(front)
+}
@@ -0,0 +1,38 @@
+use std::rc::Weak;
+
+use anyhow::Result;
+
+use crate::{type_of, ByteCodeError, FnType, Queue, Value, W};
+
+pub const QUEUE_PUSH_SYM: &str = "queue_push";
+
+pub fn queue_push() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: QUEUE_PUSH_SYM.into(),
+        prms: vec!["q".into(), "v".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
+
+/// Add the value to the back of the queue. False if the queue is full, the VM is responsible for blocking the thread.
+/// Closures are rejected: the garbage collector does not look inside queues, so their environments could be reclaimed.
+pub fn queue_push_impl(q: &Value, v: &Value) -> Result<bool> {
+    let q: Queue = q.clone().try_into()?;
+
+    if let Value::Closure { .. } = v {
+        return Err(ByteCodeError::IllegalArgument(format!(
+            "can't push a {} to a queue",
+            type_of(v)
+        ))
+        .into());
+    }
+
+    let mut q = q.borrow_mut();
+    if q.is_full() {
+        return Ok(false);
+    }
+    q.values.push_back(v.clone());
+    Ok(true)
+}
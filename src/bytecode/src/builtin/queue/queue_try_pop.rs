@@ -0,0 +1,25 @@
+use std::rc::Weak;
+
+use anyhow::Result;
+
+use crate::{FnType, Value, W};
+
+use super::queue_pop_impl;
+
+pub const QUEUE_TRY_POP_SYM: &str = "queue_try_pop";
+
+pub fn queue_try_pop() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: QUEUE_TRY_POP_SYM.into(),
+        prms: vec!["q".into(), "default".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
+
+/// Remove the value at the front of the queue, or return default if the queue is empty. Never
+/// blocks.
+pub fn queue_try_pop_impl(q: &Value, default: &Value) -> Result<Value> {
+    Ok(queue_pop_impl(q)?.unwrap_or_else(|| default.clone()))
+}
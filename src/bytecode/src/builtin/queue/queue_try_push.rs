@@ -0,0 +1,25 @@
+use std::rc::Weak;
+
+use anyhow::Result;
+
+use crate::{FnType, Value, W};
+
+use super::queue_push_impl;
+
+pub const QUEUE_TRY_PUSH_SYM: &str = "queue_try_push";
+
+pub fn queue_try_push() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: QUEUE_TRY_PUSH_SYM.into(),
+        prms: vec!["q".into(), "v".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
+
+/// Add the value to the back of the queue if it is not full. Never blocks, whether the value was
+/// pushed is returned.
+pub fn queue_try_push_impl(q: &Value, v: &Value) -> Result<Value> {
+    Ok(Value::Bool(queue_push_impl(q, v)?))
+}
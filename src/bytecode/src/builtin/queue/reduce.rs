@@ -0,0 +1,17 @@
+use std::rc::Weak;
+
+use crate::{FnType, Value, W};
+
+pub const REDUCE_SYM: &str = "reduce";
+
+/// reduce(q, init, f) folds the values of queue q from the front: f(f(init, q0), q1) and so on.
+/// Returns init if q is empty. q is not changed. The VM implements it.
+pub fn reduce() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: REDUCE_SYM.into(),
+        prms: vec!["q".into(), "init".into(), "f".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
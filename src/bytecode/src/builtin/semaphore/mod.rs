@@ -1,5 +1,7 @@
 pub use sem_create::*;
 pub use sem_set::*;
+pub use sem_value::*;
 
 mod sem_create;
 mod sem_set;
+mod sem_value;
@@ -1,6 +1,8 @@
 use std::rc::Weak;
 
-use crate::{FnType, Semaphore, Value, W};
+use anyhow::Result;
+
+use crate::{ByteCodeError, FnType, Semaphore, Value, W};
 
 pub const SEM_CREATE_SYM: &str = "sem_create";
 
@@ -8,12 +10,25 @@ pub fn sem_create() -> Value {
     Value::Closure {
         fn_type: FnType::Builtin,
         sym: SEM_CREATE_SYM.into(),
-        prms: vec![],
+        prms: vec!["count".into()],
         addr: 0,
         env: W(Weak::new()),
     }
 }
 
-pub fn sem_create_impl() -> Value {
-    Semaphore::default().into()
+/// Initial count of a semaphore when sem_create is called without one.
+pub const SEM_CREATE_DEFAULT_COUNT: i64 = 1;
+
+/// Create a semaphore with the given initial count.
+pub fn sem_create_impl(initial: &Value) -> Result<Value> {
+    Ok(Semaphore::new(sem_count(initial)?).into())
+}
+
+/// Convert the value to a semaphore count, which can't be negative.
+pub(super) fn sem_count(val: &Value) -> Result<u64> {
+    let val: i64 = val.clone().try_into()?;
+    let count = u64::try_from(val).map_err(|_| {
+        ByteCodeError::IllegalArgument(format!("semaphore count {} is negative", val))
+    })?;
+    Ok(count)
 }
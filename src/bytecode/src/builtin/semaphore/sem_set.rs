@@ -2,6 +2,7 @@ use std::rc::Weak;
 
 use anyhow::Result;
 
+use super::sem_create::sem_count;
 use crate::{FnType, Semaphore, Value, W};
 
 pub const SEM_SET_SYM: &str = "sem_set";
@@ -10,18 +11,18 @@ pub fn sem_set() -> Value {
     Value::Closure {
         fn_type: FnType::Builtin,
         sym: SEM_SET_SYM.into(),
-        prms: vec![],
-        addr: 2,
+        prms: vec!["sem".into(), "count".into()],
+        addr: 0,
         env: W(Weak::new()),
     }
 }
 
 pub fn sem_set_impl(sem: &Value, val: &Value) -> Result<()> {
     let sem: Semaphore = sem.clone().try_into()?;
-    let val: i64 = val.clone().try_into()?;
+    let val = sem_count(val)?;
 
     let mut sem_guard = sem.lock().unwrap();
-    *sem_guard = val as u64;
+    *sem_guard = val;
 
     Ok(())
 }
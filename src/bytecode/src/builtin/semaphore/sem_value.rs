@@ -0,0 +1,24 @@
+use std::rc::Weak;
+
+use anyhow::Result;
+
+use crate::{FnType, Semaphore, Value, W};
+
+pub const SEM_VALUE_SYM: &str = "sem_value";
+
+pub fn sem_value() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: SEM_VALUE_SYM.into(),
+        prms: vec!["sem".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
+
+/// Current count of the semaphore, for debugging. Another thread may change it right after.
+pub fn sem_value_impl(sem: &Value) -> Result<Value> {
+    let sem: Semaphore = sem.clone().try_into()?;
+    let count = *sem.lock().unwrap();
+    Ok(Value::Int(count as i64))
+}
@@ -0,0 +1,31 @@
+use std::rc::Weak;
+
+use crate::{format_float, FnType, Value, W};
+
+pub const EPRINT_SYM: &str = "eprint";
+
+pub fn eprint() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: EPRINT_SYM.into(),
+        prms: vec!["s".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
+
+pub fn eprint_impl(v: &Value, float_precision: Option<usize>) {
+    match v {
+        Value::Unitialized => eprint!("uninitialized"),
+        Value::Unit => eprint!("()"),
+        Value::String(s) => eprint!("{}", s),
+        Value::Bool(b) => eprint!("{}", b),
+        Value::Int(i) | Value::ThreadId(i) => eprint!("{}", i),
+        Value::Float(f) => eprint!("{}", format_float(*f, float_precision)),
+        Value::Semaphore(_) => eprint!("semaphore"),
+        Value::Queue(_) => eprint!("queue"),
+        Value::Matrix(mat) => eprint!("{}", mat.borrow().display(float_precision)),
+        Value::Closure { .. } => eprint!("closure"),
+        Value::Cancelled => eprint!("cancelled"),
+    }
+}
@@ -0,0 +1,19 @@
+use std::rc::Weak;
+
+use crate::{FnType, Value, W};
+
+pub const EPRINTLN_SYM: &str = "eprintln";
+
+pub fn eprintln() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: EPRINTLN_SYM.into(),
+        prms: vec!["s".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
+
+pub fn eprintln_impl(v: &Value, float_precision: Option<usize>) {
+    eprintln!("{}", v.display(float_precision));
+}
@@ -0,0 +1,5 @@
+pub use eprint::*;
+pub use eprintln::*;
+
+mod eprint;
+mod eprintln;
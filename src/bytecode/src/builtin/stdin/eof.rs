@@ -0,0 +1,22 @@
+use std::{io::BufRead, rc::Weak};
+
+use anyhow::Result;
+
+use crate::{FnType, Value, W};
+
+pub const EOF_SYM: &str = "eof";
+
+pub fn eof() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: EOF_SYM.into(),
+        prms: vec![],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
+
+/// True if stdin has no more input. Waits for input if none is buffered, but does not consume it.
+pub fn eof_impl() -> Result<bool> {
+    Ok(std::io::stdin().lock().fill_buf()?.is_empty())
+}
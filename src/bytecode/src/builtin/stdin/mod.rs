@@ -1,3 +1,7 @@
+pub use eof::*;
+pub use read_all::*;
 pub use read_line::*;
 
+mod eof;
+mod read_all;
 mod read_line;
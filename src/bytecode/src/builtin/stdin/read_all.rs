@@ -0,0 +1,24 @@
+use std::{io::Read, rc::Weak};
+
+use anyhow::Result;
+
+use crate::{FnType, Value, W};
+
+pub const READ_ALL_SYM: &str = "read_all";
+
+pub fn read_all() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: READ_ALL_SYM.into(),
+        prms: vec![],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
+
+/// Read the rest of stdin until end of input.
+pub fn read_all_impl() -> Result<String> {
+    let mut input = String::new();
+    std::io::stdin().read_to_string(&mut input)?;
+    Ok(input)
+}
@@ -16,8 +16,34 @@ pub fn read_line() -> Value {
     }
 }
 
+/// Read a line from stdin without its line ending ("\n" or "\r\n"), like lines typed into the REPL.
+/// At the end of input this returns "", so use eof to tell an empty line from the end of input.
 pub fn read_line_impl() -> Result<String> {
     let mut input = String::new();
     std::io::stdin().read_line(&mut input)?;
-    Ok(input)
+    Ok(trim_line_ending(input))
+}
+
+fn trim_line_ending(mut line: String) -> String {
+    if line.ends_with('\n') {
+        line.pop();
+        if line.ends_with('\r') {
+            line.pop();
+        }
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::trim_line_ending;
+
+    #[test]
+    fn test_trim_line_ending() {
+        assert_eq!(trim_line_ending("abc\n".to_string()), "abc");
+        assert_eq!(trim_line_ending("abc\r\n".to_string()), "abc");
+        assert_eq!(trim_line_ending("\n".to_string()), "");
+        assert_eq!(trim_line_ending("abc".to_string()), "abc");
+        assert_eq!(trim_line_ending(" abc \n\n".to_string()), " abc \n");
+    }
 }
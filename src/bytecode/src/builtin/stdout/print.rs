@@ -1,6 +1,6 @@
 use std::rc::Weak;
 
-use crate::{FnType, Value, W};
+use crate::{format_float, FnType, Value, W};
 
 pub const PRINT_SYM: &str = "print";
 
@@ -14,15 +14,18 @@ pub fn print() -> Value {
     }
 }
 
-pub fn print_impl(v: &Value) {
+pub fn print_impl(v: &Value, float_precision: Option<usize>) {
     match v {
         Value::Unitialized => print!("uninitialized"),
         Value::Unit => print!("()"),
         Value::String(s) => print!("{}", s),
         Value::Bool(b) => print!("{}", b),
-        Value::Int(i) => print!("{}", i),
-        Value::Float(f) => print!("{}", f),
+        Value::Int(i) | Value::ThreadId(i) => print!("{}", i),
+        Value::Float(f) => print!("{}", format_float(*f, float_precision)),
         Value::Semaphore(_) => print!("semaphore"),
+        Value::Queue(_) => print!("queue"),
+        Value::Matrix(mat) => print!("{}", mat.borrow().display(float_precision)),
         Value::Closure { .. } => print!("closure"),
+        Value::Cancelled => print!("cancelled"),
     }
 }
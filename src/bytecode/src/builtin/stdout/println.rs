@@ -14,6 +14,6 @@ pub fn println() -> Value {
     }
 }
 
-pub fn println_impl(v: &Value) {
-    println!("{v}");
+pub fn println_impl(v: &Value, float_precision: Option<usize>) {
+    println!("{}", v.display(float_precision));
 }
@@ -0,0 +1,17 @@
+use std::rc::Weak;
+
+use crate::{FnType, Value, W};
+
+pub const CANCEL_SYM: &str = "cancel";
+
+/// cancel(t) asks thread t to stop: it ends before its next instruction, and joining it gives
+/// cancelled instead of its result. The VM implements it.
+pub fn cancel() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: CANCEL_SYM.into(),
+        prms: vec!["t".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
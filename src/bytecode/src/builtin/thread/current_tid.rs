@@ -0,0 +1,16 @@
+use std::rc::Weak;
+
+use crate::{FnType, Value, W};
+
+pub const CURRENT_TID_SYM: &str = "current_tid";
+
+/// current_tid() is the ID of the thread calling it. The VM implements it.
+pub fn current_tid() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: CURRENT_TID_SYM.into(),
+        prms: vec![],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
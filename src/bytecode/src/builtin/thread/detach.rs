@@ -0,0 +1,17 @@
+use std::rc::Weak;
+
+use crate::{FnType, Value, W};
+
+pub const DETACH_SYM: &str = "detach";
+
+/// detach(t) means thread t will never be joined: its result is dropped when it finishes and the
+/// program does not wait for it to exit. The VM implements it.
+pub fn detach() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: DETACH_SYM.into(),
+        prms: vec!["t".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
@@ -0,0 +1,16 @@
+use std::rc::Weak;
+
+use crate::{FnType, Value, W};
+
+pub const IS_ALIVE_SYM: &str = "is_alive";
+
+/// is_alive(t) is true while thread t is running, ready or blocked. The VM implements it.
+pub fn is_alive() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: IS_ALIVE_SYM.into(),
+        prms: vec!["t".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
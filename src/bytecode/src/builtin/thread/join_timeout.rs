@@ -0,0 +1,17 @@
+use std::rc::Weak;
+
+use crate::{FnType, Value, W};
+
+pub const JOIN_TIMEOUT_SYM: &str = "join_timeout";
+
+/// join_timeout(t, ms) joins thread t like join, but gives up after ms milliseconds and returns ().
+/// The VM implements it.
+pub fn join_timeout() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: JOIN_TIMEOUT_SYM.into(),
+        prms: vec!["t".into(), "ms".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
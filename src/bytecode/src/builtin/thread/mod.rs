@@ -0,0 +1,15 @@
+pub use cancel::*;
+pub use current_tid::*;
+pub use detach::*;
+pub use is_alive::*;
+pub use join_timeout::*;
+pub use sched_stats::*;
+pub use thread_count::*;
+
+mod cancel;
+mod current_tid;
+mod detach;
+mod is_alive;
+mod join_timeout;
+mod sched_stats;
+mod thread_count;
@@ -0,0 +1,17 @@
+use std::rc::Weak;
+
+use crate::{FnType, Value, W};
+
+pub const SCHED_STATS_SYM: &str = "sched_stats";
+
+/// sched_stats() is a JSON string of the scheduler's counters so far: context switches, the average
+/// ready queue wait and each thread's run time. The VM implements it.
+pub fn sched_stats() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: SCHED_STATS_SYM.into(),
+        prms: vec![],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
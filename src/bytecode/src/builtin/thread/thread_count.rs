@@ -0,0 +1,16 @@
+use std::rc::Weak;
+
+use crate::{FnType, Value, W};
+
+pub const THREAD_COUNT_SYM: &str = "thread_count";
+
+/// thread_count() is the number of live threads, including the caller. The VM implements it.
+pub fn thread_count() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: THREAD_COUNT_SYM.into(),
+        prms: vec![],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
@@ -0,0 +1,20 @@
+use std::rc::Weak;
+
+use crate::{FnType, Value, W};
+
+pub const CLONE_SYM: &str = "clone";
+
+pub fn clone() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: CLONE_SYM.into(),
+        prms: vec!["v".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
+
+/// clone(v): a copy of v that changes to v don't show in, see Value::deep_clone
+pub fn clone_impl(v: &Value) -> Value {
+    v.deep_clone()
+}
@@ -0,0 +1,42 @@
+use std::rc::Weak;
+
+use anyhow::Result;
+
+use crate::{FnType, Value, W};
+
+pub const DBG_SYM: &str = "dbg";
+
+/// dbg(v) takes one argument in source. A call by name also gets the source text of the argument
+/// expression from the compiler, as a second, hidden argument.
+pub fn dbg() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: DBG_SYM.into(),
+        prms: vec!["v".into(), "expr".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
+
+/// Print the value to stderr, after the expression text if there is one, and return the value.
+/// at is where dbg was called, e.g main.rst:3, if it is known.
+pub fn dbg_impl(v: &Value, expr: Option<&Value>, at: Option<&str>) -> Result<Value> {
+    let shown = match v {
+        Value::String(s) => format!("{:?}", s),
+        _ => v.to_string(),
+    };
+    let tag = match at {
+        Some(at) => format!("[dbg {}]", at),
+        None => "[dbg]".to_string(),
+    };
+
+    match expr {
+        Some(expr) => {
+            let expr: String = expr.clone().try_into()?;
+            eprintln!("{} {} = {}", tag, expr, shown);
+        }
+        None => eprintln!("{} {}", tag, shown),
+    }
+
+    Ok(v.clone())
+}
@@ -0,0 +1,9 @@
+pub use clone::*;
+pub use dbg::*;
+pub use type_of::*;
+pub use vars::*;
+
+mod clone;
+mod dbg;
+mod type_of;
+mod vars;
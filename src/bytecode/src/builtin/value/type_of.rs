@@ -0,0 +1,20 @@
+use std::rc::Weak;
+
+use crate::{type_of, FnType, Value, W};
+
+pub const TYPE_OF_SYM: &str = "type_of";
+
+pub fn type_of_() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: TYPE_OF_SYM.into(),
+        prms: vec!["v".into()],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
+
+/// Runtime type name of the value, e.g "Int"
+pub fn type_of_impl(v: &Value) -> Value {
+    Value::String(type_of(v).to_string())
+}
@@ -0,0 +1,104 @@
+use std::{cell::RefCell, collections::HashSet, rc::Weak};
+
+use crate::{type_of, Environment, FnType, Symbol, Value, W};
+
+pub const VARS_SYM: &str = "vars";
+
+/// vars() lists the names in scope where it is called and their types. The VM implements it,
+/// since it needs the caller's environment.
+pub fn vars() -> Value {
+    Value::Closure {
+        fn_type: FnType::Builtin,
+        sym: VARS_SYM.into(),
+        prms: vec![],
+        addr: 0,
+        env: W(Weak::new()),
+    }
+}
+
+/// Names bound in env and the scopes around it with the runtime type of their values, innermost
+/// scope first and sorted by name within a scope.
+///
+/// Names shadowed by an inner scope and names declared but not yet assigned are left out, as is
+/// the global scope, which only holds builtins and constants.
+pub fn bindings(env: &Weak<RefCell<Environment>>) -> Vec<(Symbol, &'static str)> {
+    let mut out = vec![];
+    let mut seen: HashSet<Symbol> = HashSet::new();
+    let mut env = Some(env.clone());
+
+    while let Some(scope) = env.and_then(|env| env.upgrade()) {
+        let scope = scope.borrow();
+        if scope.parent.is_none() {
+            break;
+        }
+
+        let mut syms: Vec<_> = scope
+            .env
+            .iter()
+            .filter(|(_, val)| !matches!(val, Value::Unitialized))
+            .collect();
+        syms.sort_by_key(|(sym, _)| *sym);
+        for (sym, val) in syms {
+            if seen.insert(sym.clone()) {
+                out.push((sym.clone(), type_of(val)));
+            }
+        }
+
+        env = scope.parent.clone();
+    }
+
+    out
+}
+
+/// One "name: Type" line per binding in scope of env
+pub fn vars_impl(env: &Weak<RefCell<Environment>>) -> Value {
+    let lines: Vec<String> = bindings(env)
+        .into_iter()
+        .map(|(sym, ty)| format!("{}: {}", sym, ty))
+        .collect();
+    Value::String(lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use crate::{weak_clone, Environment, Value};
+
+    use super::{bindings, vars_impl};
+
+    #[test]
+    fn test_vars() {
+        let global = Environment::new_global_wrapped();
+
+        let outer = Environment::new_wrapped();
+        outer.borrow_mut().set_parent(weak_clone(&global));
+        outer.borrow_mut().set("x", 1);
+        outer.borrow_mut().set("s", "hi");
+        outer.borrow_mut().set("later", Value::Unitialized);
+
+        let inner = Environment::new_wrapped();
+        inner.borrow_mut().set_parent(weak_clone(&outer));
+        inner.borrow_mut().set("x", 1.5);
+        inner.borrow_mut().set("b", true);
+
+        assert_eq!(
+            bindings(&Rc::downgrade(&inner)),
+            vec![
+                ("b".to_string(), "Bool"),
+                ("x".to_string(), "Float"),
+                ("s".to_string(), "String"),
+            ]
+        );
+        assert_eq!(
+            vars_impl(&Rc::downgrade(&outer)),
+            Value::String("s: String\nx: Int".into())
+        );
+
+        // the global scope is never listed
+        assert_eq!(
+            vars_impl(&Rc::downgrade(&global)),
+            Value::String(String::new())
+        );
+    }
+}
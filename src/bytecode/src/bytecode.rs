@@ -37,14 +37,27 @@ pub enum ByteCode {
     RESET(FrameType),
     /// Create a new scope with the given symbols.
     ENTERSCOPE(Vec<Symbol>),
+    /// Create a new scope with the given symbols, which the compiler found no closure or thread can
+    /// capture. Its environment belongs to the scope's stack frame instead of the environment
+    /// registry, and is exited with EXITSCOPELOCAL.
+    ENTERSCOPELOCAL(Vec<Symbol>),
     /// Exit the current scope.
     EXITSCOPE,
+    /// Exit the current scope, which the compiler found no closure or thread can capture, so its
+    /// environment is freed now instead of by the garbage collector.
+    EXITSCOPELOCAL,
     /// Load the function with the given number of arguments and the function address onto the operant stack.
     LDF(usize, Vec<Symbol>),
     /// Call a function with the given number of arguments.
     CALL(usize),
-    /// Spawn a new thread with the address of the instruction for the child to execute.
-    SPAWN(Address),
+    /// Spawn a new thread with the address of the instruction for the child to execute. The given
+    /// number of values, the function to call and its arguments, are moved from the top of the
+    /// parent's operand stack to the child's.
+    SPAWN(Address, usize),
+    /// Like SPAWN, but the child runs on a copy of the parent's environment so its writes are not shared.
+    SPAWNISOLATED(Address, usize),
+    /// Like SPAWN, but first pops an instruction budget for the child. The child is terminated if it executes more instructions.
+    SPAWNLIMITED(Address, usize),
     /// Join a thread.
     JOIN,
     /// Yield the current thread.
@@ -55,6 +68,40 @@ pub enum ByteCode {
     WAIT,
     /// Post the semaphore.
     POST,
+    /// Fused LD(sym); LDC(val); BINOP(Add); ASSIGN(sym). Emitted by the compiler's peephole pass.
+    INC(Symbol, Value),
+    /// Fused LD(sym); LDC(val); BINOP(op). Emitted by the compiler's peephole pass.
+    LDLDCBINOP(Symbol, Value, BinOp),
+    /// Start a thread scope: the threads the current thread spawns from now on are joined by the
+    /// matching JOINTHREADSCOPE.
+    ENTERTHREADSCOPE,
+    /// Wait for every thread spawned since the matching ENTERTHREADSCOPE to finish, then end the
+    /// thread scope.
+    JOINTHREADSCOPE,
+    /// Do the first of the queue operations that can be done straight away, popping the operands of
+    /// all of them from the operand stack, in order. Pushes the popped value, or Unit for a push,
+    /// then the index of the operation done. If none can be done and the flag is set, pushes Unit
+    /// and the number of operations instead, otherwise the thread waits until one can be done.
+    SELECT(Vec<SelectOp>, bool),
+}
+
+/// A queue operation a SELECT can do
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SelectOp {
+    /// Pop from the queue, its operand
+    Pop,
+    /// Push to the queue, its first operand, the value, its second
+    Push,
+}
+
+impl SelectOp {
+    /// Number of values the operation takes from the operand stack
+    pub fn operands(&self) -> usize {
+        match self {
+            SelectOp::Pop => 1,
+            SelectOp::Push => 2,
+        }
+    }
 }
 
 /// For creating ByteCode instructions in a more ergonomic way.
@@ -87,9 +134,21 @@ impl ByteCode {
         ByteCode::RESET(t.into())
     }
 
+    pub fn inc(sym: impl Into<Symbol>, v: impl Into<Value>) -> Self {
+        ByteCode::INC(sym.into(), v.into())
+    }
+
+    pub fn ldldcbinop(sym: impl Into<Symbol>, v: impl Into<Value>, op: impl Into<BinOp>) -> Self {
+        ByteCode::LDLDCBINOP(sym.into(), v.into(), op.into())
+    }
+
     pub fn enterscope<T: Into<Symbol>>(syms: Vec<T>) -> Self {
         ByteCode::ENTERSCOPE(syms.into_iter().map(Into::into).collect())
     }
+
+    pub fn enterscopelocal<T: Into<Symbol>>(syms: Vec<T>) -> Self {
+        ByteCode::ENTERSCOPELOCAL(syms.into_iter().map(Into::into).collect())
+    }
 }
 
 #[cfg(test)]
@@ -1,6 +1,6 @@
 use std::{
     cell::RefCell,
-    collections::{hash_map::Entry, HashMap},
+    collections::HashMap,
     fmt::Debug,
     rc::{Rc, Weak},
 };
@@ -35,13 +35,24 @@ impl Environment {
     /// Constants are added to the global environment.
     /// - Logical constants: true, false
     /// - Math constants: PI, E
-    /// - Environment constants: MAX_INT, MIN_INT, MAX_FLOAT, MIN_FLOAT, EPSILON
+    /// - Environment constants: MAX_INT, MIN_INT, MAX_FLOAT, MIN_FLOAT, EPSILON, NAN, INFINITY
     ///
     /// Built in functions are added to the global environment.
-    /// - Math functions: abs, ceil, floor, round, sqrt, sin, cos, tan, log10, pow
+    /// - Math functions: abs, floor, ceil, round, trunc, sqrt, exp, log, log2, log10, pow, sin, cos,
+    ///   tan, atan2, hypot, clamp, is_nan, is_finite, div, fdiv
     /// - String functions: len
-    /// - Type conversion functions: int_to_float, float_to_int, atoi, atoi
+    /// - Type conversion functions: int_to_float, float_to_int, atoi, itoa, parse_int, parse_int_radix,
+    ///   parse_float, parse_failed
+    /// - Value functions: clone, type_of, vars, dbg
+    /// - JSON functions: json_stringify, json_get
+    /// - Formatting functions: to_fixed, format_int, set_float_precision
     /// - Comparison functions: min, max
+    /// - I/O functions: read_line, read_all, eof, print, println, eprint, eprintln
+    /// - Queue functions: queue_create, queue_create_bounded, queue_push, queue_pop, queue_try_push,
+    ///   queue_try_pop, map, filter, reduce
+    /// - Matrix functions: matrix, mat_get, mat_set, mat_rows, mat_cols, matmul
+    /// - Thread functions: join_timeout, is_alive, detach, cancel, current_tid, thread_count, sched_stats
+    /// - Process functions: exit, and with the exec feature exec, exec_stdout, exec_stderr
     ///
     /// # Returns
     ///
@@ -54,17 +65,10 @@ impl Environment {
         env.borrow_mut().set(builtin::TRUE_SYM, true);
         env.borrow_mut().set(builtin::FALSE_SYM, false);
 
-        // Math constants
-        env.borrow_mut().set(builtin::PI_SYM, std::f64::consts::PI);
-        env.borrow_mut().set(builtin::E_SYM, std::f64::consts::E);
-
-        //Environment constants
-        env.borrow_mut().set(builtin::MAX_INT_SYM, std::i64::MAX);
-        env.borrow_mut().set(builtin::MIN_INT_SYM, std::i64::MIN);
-        env.borrow_mut().set(builtin::MAX_FLOAT_SYM, std::f64::MAX);
-        env.borrow_mut().set(builtin::MIN_FLOAT_SYM, std::f64::MIN);
-        env.borrow_mut()
-            .set(builtin::EPSILON_SYM, std::f64::EPSILON);
+        // Math and environment constants
+        for (sym, val) in builtin::constants() {
+            env.borrow_mut().set(sym, val);
+        }
 
         // Built in functions
         // Math functions
@@ -77,6 +81,21 @@ impl Environment {
         env.borrow_mut().set(builtin::SQRT_SYM, builtin::sqrt());
         env.borrow_mut().set(builtin::MAX_SYM, builtin::max());
         env.borrow_mut().set(builtin::MIN_SYM, builtin::min());
+        env.borrow_mut().set(builtin::FLOOR_SYM, builtin::floor());
+        env.borrow_mut().set(builtin::CEIL_SYM, builtin::ceil());
+        env.borrow_mut().set(builtin::ROUND_SYM, builtin::round());
+        env.borrow_mut().set(builtin::TRUNC_SYM, builtin::trunc());
+        env.borrow_mut().set(builtin::EXP_SYM, builtin::exp());
+        env.borrow_mut().set(builtin::LOG2_SYM, builtin::log2());
+        env.borrow_mut().set(builtin::LOG10_SYM, builtin::log10());
+        env.borrow_mut().set(builtin::ATAN2_SYM, builtin::atan2());
+        env.borrow_mut().set(builtin::HYPOT_SYM, builtin::hypot());
+        env.borrow_mut().set(builtin::CLAMP_SYM, builtin::clamp());
+        env.borrow_mut().set(builtin::DIV_SYM, builtin::div());
+        env.borrow_mut().set(builtin::FDIV_SYM, builtin::fdiv());
+        env.borrow_mut().set(builtin::IS_NAN_SYM, builtin::is_nan());
+        env.borrow_mut()
+            .set(builtin::IS_FINITE_SYM, builtin::is_finite());
 
         // String functions
         env.borrow_mut()
@@ -89,19 +108,114 @@ impl Environment {
             .set(builtin::FLOAT_TO_INT_SYM, builtin::float_to_int());
         env.borrow_mut().set(builtin::ATOI_SYM, builtin::atoi());
         env.borrow_mut().set(builtin::ITOA_SYM, builtin::itoa());
+        env.borrow_mut()
+            .set(builtin::PARSE_INT_SYM, builtin::parse_int());
+        env.borrow_mut()
+            .set(builtin::PARSE_INT_RADIX_SYM, builtin::parse_int_radix());
+        env.borrow_mut()
+            .set(builtin::PARSE_FLOAT_SYM, builtin::parse_float());
+        env.borrow_mut()
+            .set(builtin::PARSE_FAILED_SYM, builtin::parse_failed());
+
+        // Value functions
+        env.borrow_mut().set(builtin::CLONE_SYM, builtin::clone());
+        env.borrow_mut()
+            .set(builtin::TYPE_OF_SYM, builtin::type_of_());
+        env.borrow_mut().set(builtin::VARS_SYM, builtin::vars());
+        env.borrow_mut().set(builtin::DBG_SYM, builtin::dbg());
 
-        // stdin, stdout
+        // JSON functions
+        env.borrow_mut()
+            .set(builtin::JSON_STRINGIFY_SYM, builtin::json_stringify());
+        env.borrow_mut()
+            .set(builtin::JSON_GET_SYM, builtin::json_get());
+
+        // Formatting functions
+        env.borrow_mut()
+            .set(builtin::TO_FIXED_SYM, builtin::to_fixed());
+        env.borrow_mut()
+            .set(builtin::FORMAT_INT_SYM, builtin::format_int());
+        env.borrow_mut().set(
+            builtin::SET_FLOAT_PRECISION_SYM,
+            builtin::set_float_precision(),
+        );
+
+        // stdin, stdout, stderr
         env.borrow_mut()
             .set(builtin::READ_LINE_SYM, builtin::read_line());
+        env.borrow_mut()
+            .set(builtin::READ_ALL_SYM, builtin::read_all());
+        env.borrow_mut().set(builtin::EOF_SYM, builtin::eof());
         env.borrow_mut().set(builtin::PRINT_SYM, builtin::print());
         env.borrow_mut()
             .set(builtin::PRINTLN_SYM, builtin::println());
+        env.borrow_mut().set(builtin::EPRINT_SYM, builtin::eprint());
+        env.borrow_mut()
+            .set(builtin::EPRINTLN_SYM, builtin::eprintln());
 
         // Semaphore functions
         env.borrow_mut()
             .set(builtin::SEM_CREATE_SYM, builtin::sem_create());
         env.borrow_mut()
             .set(builtin::SEM_SET_SYM, builtin::sem_set());
+        env.borrow_mut()
+            .set(builtin::SEM_VALUE_SYM, builtin::sem_value());
+
+        // Queue functions
+        env.borrow_mut()
+            .set(builtin::QUEUE_CREATE_SYM, builtin::queue_create());
+        env.borrow_mut()
+            .set(builtin::QUEUE_PUSH_SYM, builtin::queue_push());
+        env.borrow_mut()
+            .set(builtin::QUEUE_POP_SYM, builtin::queue_pop());
+        env.borrow_mut().set(
+            builtin::QUEUE_CREATE_BOUNDED_SYM,
+            builtin::queue_create_bounded(),
+        );
+        env.borrow_mut()
+            .set(builtin::QUEUE_TRY_PUSH_SYM, builtin::queue_try_push());
+        env.borrow_mut()
+            .set(builtin::QUEUE_TRY_POP_SYM, builtin::queue_try_pop());
+        env.borrow_mut().set(builtin::MAP_SYM, builtin::map());
+        env.borrow_mut().set(builtin::FILTER_SYM, builtin::filter());
+        env.borrow_mut().set(builtin::REDUCE_SYM, builtin::reduce());
+
+        // Matrix functions
+        env.borrow_mut().set(builtin::MATRIX_SYM, builtin::matrix());
+        env.borrow_mut()
+            .set(builtin::MAT_GET_SYM, builtin::mat_get());
+        env.borrow_mut()
+            .set(builtin::MAT_SET_SYM, builtin::mat_set());
+        env.borrow_mut()
+            .set(builtin::MAT_ROWS_SYM, builtin::mat_rows());
+        env.borrow_mut()
+            .set(builtin::MAT_COLS_SYM, builtin::mat_cols());
+        env.borrow_mut().set(builtin::MATMUL_SYM, builtin::matmul());
+
+        // Thread functions
+        env.borrow_mut()
+            .set(builtin::JOIN_TIMEOUT_SYM, builtin::join_timeout());
+        env.borrow_mut()
+            .set(builtin::IS_ALIVE_SYM, builtin::is_alive());
+        env.borrow_mut().set(builtin::DETACH_SYM, builtin::detach());
+        env.borrow_mut().set(builtin::CANCEL_SYM, builtin::cancel());
+        env.borrow_mut()
+            .set(builtin::CURRENT_TID_SYM, builtin::current_tid());
+        env.borrow_mut()
+            .set(builtin::THREAD_COUNT_SYM, builtin::thread_count());
+        env.borrow_mut()
+            .set(builtin::SCHED_STATS_SYM, builtin::sched_stats());
+
+        // Process functions
+        env.borrow_mut().set(builtin::EXIT_SYM, builtin::exit());
+        #[cfg(feature = "exec")]
+        {
+            env.borrow_mut().set(builtin::EXEC_SYM, builtin::exec());
+            env.borrow_mut()
+                .set(builtin::EXEC_STDOUT_SYM, builtin::exec_stdout());
+            env.borrow_mut()
+                .set(builtin::EXEC_STDERR_SYM, builtin::exec_stderr());
+        }
 
         env
     }
@@ -171,8 +285,8 @@ impl Environment {
         let sym = sym.into();
 
         // If the symbol is found in the current environment, update the value.
-        if let Entry::Occupied(mut entry) = self.env.entry(sym.clone()) {
-            entry.insert(val.into());
+        if let Some(entry) = self.env.get_mut(&sym) {
+            *entry = val.into();
             return Ok(());
         }
 
@@ -248,6 +362,6 @@ mod tests {
             child_env.borrow().get(&"y".to_string()).unwrap(),
             Value::Int(43)
         );
-        assert!(!child_env.borrow().env.contains_key(&"x".to_string()));
+        assert!(!child_env.borrow().env.contains_key("x"));
     }
 }
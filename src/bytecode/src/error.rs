@@ -11,6 +11,12 @@ pub enum ByteCodeError {
     #[error("Unbounded name: {name}")]
     UnboundedName { name: String },
 
+    #[error("Division by zero")]
+    DivisionByZero,
+
+    #[error("Illegal argument: {0}")]
+    IllegalArgument(String),
+
     #[error("Environment access after drop")]
     EnvironmentDroppedError,
 }
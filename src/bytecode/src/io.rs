@@ -1,8 +1,30 @@
 use std::io::{Read, Write};
 
 use anyhow::Result;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
-use crate::ByteCode;
+use crate::{ByteCode, SourceMap};
+
+/// Start of a .o2 file with a header. Read as the length of a bytecode section it would be over 2^56
+/// bytes, so a file without a header is never mistaken for one with a header.
+const O2_MAGIC: [u8; 8] = *b"RSTO2\0\0\x01";
+
+/// How a .o2 file was compiled, written before its bytecode.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct O2Header {
+    /// Whether the program was type checked before it was compiled.
+    pub typechecked: bool,
+}
+
+/// Everything in a .o2 file.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct O2File {
+    /// None for files written without a header, such as by write_bytecode.
+    pub header: Option<O2Header>,
+    pub bytecode: Vec<ByteCode>,
+    /// Where in the source the bytecode came from, if known.
+    pub source_map: Option<SourceMap>,
+}
 
 /// Serialize the bytecode to the writer.
 /// The serialized format is:
@@ -16,14 +38,10 @@ use crate::ByteCode;
 /// # Returns
 /// - `Result<()>`: The result of the serialization
 pub fn write_bytecode<W: Write>(bytecode: &[ByteCode], writer: &mut W) -> Result<()> {
-    let serialized = bincode::serialize(bytecode)?;
-    let len = serialized.len() as u64;
-    writer.write_all(&len.to_le_bytes())?;
-    writer.write_all(&serialized)?;
-    Ok(())
+    write_section(bytecode, writer)
 }
 
-/// Deserialize the bytecode from the reader.
+/// Deserialize the bytecode from the reader, skipping the header if there is one.
 /// The serialized format is:
 /// - 8 bytes for the length of the serialized bytecode
 /// - The serialized bytecode
@@ -34,13 +52,97 @@ pub fn write_bytecode<W: Write>(bytecode: &[ByteCode], writer: &mut W) -> Result
 /// # Returns
 /// - `Result<Vec<ByteCode>>`: The result of the deserialization
 pub fn read_bytecode<R: Read>(reader: &mut R) -> Result<Vec<ByteCode>> {
+    Ok(read_header_and_bytecode(reader)?.1)
+}
+
+/// Serialize a whole .o2 file to the writer.
+/// The serialized format is:
+/// - If there is a header, the 8 bytes of O2_MAGIC and the header in the same format as the bytecode
+/// - The bytecode, as written by write_bytecode
+/// - If there is a source map, the source map in the same format as the bytecode
+///
+/// A reader that only knows about bytecode sections stops after the bytecode, so files without a
+/// header can still be read by older readers.
+///
+/// # Arguments
+/// - `o2`: The file to serialize
+/// - `writer`: The writer to write it to
+pub fn write_o2<W: Write>(o2: &O2File, writer: &mut W) -> Result<()> {
+    if let Some(header) = &o2.header {
+        writer.write_all(&O2_MAGIC)?;
+        write_section(header, writer)?;
+    }
+
+    write_bytecode(&o2.bytecode, writer)?;
+
+    if let Some(source_map) = &o2.source_map {
+        write_section(source_map, writer)?;
+    }
+
+    Ok(())
+}
+
+/// Deserialize a whole .o2 file from the reader. The header and source map are None if the file
+/// doesn't have them.
+pub fn read_o2<R: Read>(reader: &mut R) -> Result<O2File> {
+    let (header, bytecode) = read_header_and_bytecode(reader)?;
+
+    let mut rest = vec![];
+    reader.read_to_end(&mut rest)?;
+    let source_map = if rest.is_empty() {
+        None
+    } else {
+        Some(read_section(&mut rest.as_slice())?)
+    };
+
+    Ok(O2File {
+        header,
+        bytecode,
+        source_map,
+    })
+}
+
+fn read_header_and_bytecode<R: Read>(reader: &mut R) -> Result<(Option<O2Header>, Vec<ByteCode>)> {
     let mut len_bytes = [0; 8];
     reader.read_exact(&mut len_bytes)?;
+
+    let header = if len_bytes == O2_MAGIC {
+        let header = read_section(reader)?;
+        reader.read_exact(&mut len_bytes)?;
+        Some(header)
+    } else {
+        None
+    };
+
+    Ok((header, read_section_after_len(len_bytes, reader)?))
+}
+
+/// Write the value's length in 8 bytes, followed by the value
+fn write_section<W: Write, T: Serialize + ?Sized>(value: &T, writer: &mut W) -> Result<()> {
+    let serialized = bincode::serialize(value)?;
+    let len = serialized.len() as u64;
+    writer.write_all(&len.to_le_bytes())?;
+    writer.write_all(&serialized)?;
+    Ok(())
+}
+
+fn read_section<R: Read, T: DeserializeOwned>(reader: &mut R) -> Result<T> {
+    let mut len_bytes = [0; 8];
+    reader.read_exact(&mut len_bytes)?;
+    read_section_after_len(len_bytes, reader)
+}
+
+fn read_section_after_len<R: Read, T: DeserializeOwned>(
+    len_bytes: [u8; 8],
+    reader: &mut R,
+) -> Result<T> {
     let len = u64::from_le_bytes(len_bytes) as usize;
-    let mut serialized = vec![0; len];
-    reader.read_exact(&mut serialized)?;
-    let bytecode = bincode::deserialize(&serialized)?;
-    Ok(bytecode)
+    let mut serialized = vec![];
+    reader.take(len as u64).read_to_end(&mut serialized)?;
+    if serialized.len() < len {
+        anyhow::bail!("Truncated .o2 file");
+    }
+    Ok(bincode::deserialize(&serialized)?)
 }
 
 #[cfg(test)]
@@ -85,4 +187,35 @@ mod tests {
         // remove file
         std::fs::remove_file("test.o2").unwrap();
     }
+
+    #[test]
+    fn test_o2_serialization() {
+        let bc = vec![ByteCode::ldc(1), ByteCode::DONE];
+        let o2 = O2File {
+            header: Some(O2Header { typechecked: false }),
+            bytecode: bc.clone(),
+            source_map: Some(SourceMap::new("x.rst", vec![(0, 1), (1, 2)])),
+        };
+
+        let mut serialized = Vec::new();
+        write_o2(&o2, &mut serialized).unwrap();
+        assert_eq!(read_o2(&mut serialized.as_slice()).unwrap(), o2);
+
+        // read_bytecode skips the header and ignores the source map
+        assert_eq!(read_bytecode(&mut serialized.as_slice()).unwrap(), bc);
+
+        // and files from write_bytecode have neither
+        let mut serialized = Vec::new();
+        write_bytecode(&bc, &mut serialized).unwrap();
+        let read = read_o2(&mut serialized.as_slice()).unwrap();
+        assert_eq!(read.header, None);
+        assert_eq!(read.source_map, None);
+        assert_eq!(read.bytecode, bc);
+
+        // a truncated file is an error, not a huge allocation
+        let mut serialized = Vec::new();
+        write_o2(&o2, &mut serialized).unwrap();
+        serialized.truncate(serialized.len() - 1);
+        assert!(read_o2(&mut serialized.as_slice()).is_err());
+    }
 }
@@ -2,9 +2,12 @@ pub use bytecode::*;
 pub use environment::*;
 pub use error::*;
 pub use io::*;
+pub use matrix::*;
 pub use operator::*;
 pub use prelude::*;
+pub use queue::*;
 pub use semaphore::*;
+pub use source_map::*;
 pub use stack_frame::*;
 pub use value::*;
 
@@ -13,8 +16,11 @@ mod bytecode;
 mod environment;
 mod error;
 mod io;
+mod matrix;
 mod operator;
 mod prelude;
+mod queue;
 mod semaphore;
+mod source_map;
 mod stack_frame;
 mod value;
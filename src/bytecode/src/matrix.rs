@@ -0,0 +1,189 @@
+use std::{cell::RefCell, fmt::Debug, rc::Rc};
+
+use anyhow::Result;
+
+use crate::{format_float, ByteCodeError, W};
+
+/// A matrix of floats. Like a queue it is a handle, so threads given the same matrix see each
+/// other's writes, e.g. workers filling in rows of a shared result. clone copies the elements.
+pub type Matrix = W<Rc<RefCell<MatrixData>>>;
+
+/// The elements of a matrix with rows rows and cols columns, stored row by row.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatrixData {
+    pub rows: usize,
+    pub cols: usize,
+    pub data: Vec<f64>,
+}
+
+impl MatrixData {
+    /// A rows x cols matrix with every element set to init.
+    ///
+    /// # Errors
+    ///
+    /// If rows or cols is negative, or the matrix has more elements than a Vec can hold.
+    pub fn new(rows: i64, cols: i64, init: f64) -> Result<Self> {
+        let (Ok(r), Ok(c)) = (usize::try_from(rows), usize::try_from(cols)) else {
+            return Err(ByteCodeError::IllegalArgument(format!(
+                "matrix dimensions must not be negative, got {}x{}",
+                rows, cols
+            ))
+            .into());
+        };
+
+        let len = r
+            .checked_mul(c)
+            .filter(|len| *len <= isize::MAX as usize / std::mem::size_of::<f64>())
+            .ok_or_else(|| {
+                ByteCodeError::IllegalArgument(format!("matrix of {}x{} is too large", rows, cols))
+            })?;
+
+        Ok(MatrixData {
+            rows: r,
+            cols: c,
+            data: vec![init; len],
+        })
+    }
+
+    /// Index into data of the element in row i and column j.
+    ///
+    /// # Errors
+    ///
+    /// If the element is outside the matrix.
+    pub fn index(&self, i: i64, j: i64) -> Result<usize> {
+        match (usize::try_from(i), usize::try_from(j)) {
+            (Ok(r), Ok(c)) if r < self.rows && c < self.cols => Ok(r * self.cols + c),
+            _ => Err(ByteCodeError::IllegalArgument(format!(
+                "index ({}, {}) is out of bounds for a {}x{} matrix",
+                i, j, self.rows, self.cols
+            ))
+            .into()),
+        }
+    }
+
+    /// The product self * rhs.
+    ///
+    /// # Errors
+    ///
+    /// If self doesn't have as many columns as rhs has rows.
+    pub fn matmul(&self, rhs: &MatrixData) -> Result<MatrixData> {
+        if self.cols != rhs.rows {
+            return Err(ByteCodeError::IllegalArgument(format!(
+                "can't multiply a {}x{} matrix by a {}x{} matrix",
+                self.rows, self.cols, rhs.rows, rhs.cols
+            ))
+            .into());
+        }
+
+        let mut data = vec![0.0; self.rows * rhs.cols];
+        // i-k-j order walks both rhs and the result row by row, which is much faster than i-j-k
+        for (lhs_row, out_row) in self
+            .data
+            .chunks_exact(self.cols.max(1))
+            .zip(data.chunks_exact_mut(rhs.cols.max(1)))
+        {
+            for (a, rhs_row) in lhs_row.iter().zip(rhs.data.chunks_exact(rhs.cols.max(1))) {
+                for (out, b) in out_row.iter_mut().zip(rhs_row) {
+                    *out += a * b;
+                }
+            }
+        }
+
+        Ok(MatrixData {
+            rows: self.rows,
+            cols: rhs.cols,
+            data,
+        })
+    }
+
+    /// The elements row by row, e.g. [[1.0, 2.0], [3.0, 4.0]], with floats formatted like print.
+    pub fn display(&self, float_precision: Option<usize>) -> String {
+        let rows: Vec<String> = (0..self.rows)
+            .map(|r| {
+                let row = &self.data[r * self.cols..(r + 1) * self.cols];
+                let elems: Vec<String> = row
+                    .iter()
+                    .map(|x| format_float(*x, float_precision))
+                    .collect();
+                format!("[{}]", elems.join(", "))
+            })
+            .collect();
+
+        format!("[{}]", rows.join(", "))
+    }
+}
+
+impl Matrix {
+    pub fn new(data: MatrixData) -> Self {
+        Self(Rc::new(RefCell::new(data)))
+    }
+}
+
+impl PartialEq for Matrix {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Clone for Matrix {
+    fn clone(&self) -> Self {
+        Self(Rc::clone(&self.0))
+    }
+}
+
+impl Debug for Matrix {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Matrix({})", self.borrow().display(None))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from_rows(rows: &[&[f64]]) -> MatrixData {
+        MatrixData {
+            rows: rows.len(),
+            cols: rows.first().map_or(0, |row| row.len()),
+            data: rows.iter().flat_map(|row| row.iter().copied()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_matrix_new_index() {
+        let m = MatrixData::new(2, 3, 1.5).unwrap();
+        assert_eq!(m.data, vec![1.5; 6]);
+        assert_eq!(m.index(1, 2).unwrap(), 5);
+        assert_eq!(
+            m.index(2, 0).unwrap_err().to_string(),
+            "Illegal argument: index (2, 0) is out of bounds for a 2x3 matrix"
+        );
+        assert!(m.index(0, -1).is_err());
+
+        assert!(MatrixData::new(-1, 2, 0.0).is_err());
+        assert!(MatrixData::new(i64::MAX, i64::MAX, 0.0).is_err());
+        assert_eq!(MatrixData::new(0, 3, 0.0).unwrap().display(None), "[]");
+    }
+
+    #[test]
+    fn test_matmul() {
+        let a = from_rows(&[&[1.0, 2.0, 3.0], &[4.0, 5.0, 6.0]]);
+        let b = from_rows(&[&[7.0, 8.0], &[9.0, 10.0], &[11.0, 12.0]]);
+        let c = a.matmul(&b).unwrap();
+        assert_eq!(c, from_rows(&[&[58.0, 64.0], &[139.0, 154.0]]));
+        assert_eq!(c.display(None), "[[58.0, 64.0], [139.0, 154.0]]");
+        assert_eq!(c.display(Some(1)), "[[58.0, 64.0], [139.0, 154.0]]");
+
+        // empty inner dimension gives zeros
+        let c = MatrixData::new(2, 0, 0.0)
+            .unwrap()
+            .matmul(&MatrixData::new(0, 2, 0.0).unwrap())
+            .unwrap();
+        assert_eq!(c.data, vec![0.0; 4]);
+
+        assert_eq!(
+            a.matmul(&a).unwrap_err().to_string(),
+            "Illegal argument: can't multiply a 2x3 matrix by a 2x3 matrix"
+        );
+    }
+}
@@ -12,9 +12,9 @@ pub enum BinOp {
     Div,
     /// Modulo of two values of the same type (int)
     Mod,
-    /// Greater than comparison of two values of the same type (int or float)
+    /// Greater than comparison of two values of the same type (int or float or string)
     Gt,
-    /// Less than comparison of two values of the same type (int or float)
+    /// Less than comparison of two values of the same type (int or float or string)
     Lt,
     /// Equality comparison of two values of the same type (bool or int or float or string)
     Eq,
@@ -22,6 +22,10 @@ pub enum BinOp {
     And,
     /// Logical OR of two values of the same type (bool)
     Or,
+    /// Greater than or equal comparison of two values of the same type (int or float or string)
+    Ge,
+    /// Less than or equal comparison of two values of the same type (int or float or string)
+    Le,
 }
 
 impl From<&str> for BinOp {
@@ -34,6 +38,8 @@ impl From<&str> for BinOp {
             "%" => BinOp::Mod,
             ">" => BinOp::Gt,
             "<" => BinOp::Lt,
+            ">=" => BinOp::Ge,
+            "<=" => BinOp::Le,
             "==" => BinOp::Eq,
             "&&" => BinOp::And,
             "||" => BinOp::Or,
@@ -52,6 +58,8 @@ impl From<BinOp> for String {
             BinOp::Mod => "%".to_string(),
             BinOp::Gt => ">".to_string(),
             BinOp::Lt => "<".to_string(),
+            BinOp::Ge => ">=".to_string(),
+            BinOp::Le => "<=".to_string(),
             BinOp::Eq => "==".to_string(),
             BinOp::And => "&&".to_string(),
             BinOp::Or => "||".to_string(),
@@ -65,6 +73,13 @@ pub enum UnOp {
     Neg,
     /// Logical negation of a value of the same type (bool)
     Not,
+    /// Cast to int (x as int). Floats are truncated towards zero and saturate at the bounds of int,
+    /// NaN becomes 0. Bools become 0 or 1
+    ToInt,
+    /// Cast to float (x as float). Ints are rounded to the nearest float
+    ToFloat,
+    /// Cast to bool (x as bool), only from bool
+    ToBool,
 }
 
 impl From<&str> for UnOp {
@@ -72,6 +87,9 @@ impl From<&str> for UnOp {
         match s {
             "-" => UnOp::Neg,
             "!" => UnOp::Not,
+            "as int" => UnOp::ToInt,
+            "as float" => UnOp::ToFloat,
+            "as bool" => UnOp::ToBool,
             _ => panic!("Invalid unary operator: {}", s),
         }
     }
@@ -82,6 +100,9 @@ impl From<UnOp> for String {
         match op {
             UnOp::Neg => "-".to_string(),
             UnOp::Not => "!".to_string(),
+            UnOp::ToInt => "as int".to_string(),
+            UnOp::ToFloat => "as float".to_string(),
+            UnOp::ToBool => "as bool".to_string(),
         }
     }
 }
@@ -0,0 +1,61 @@
+use std::{cell::RefCell, collections::VecDeque, fmt::Debug, rc::Rc};
+
+use crate::{Value, W};
+
+/// A first-in first-out queue of values shared between threads, for producer/consumer patterns.
+/// Threads are scheduled on one OS thread by the VM, so no lock is needed.
+pub type Queue = W<Rc<RefCell<QueueData>>>;
+
+/// The values in a queue, front first, and how many it can hold at once.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QueueData {
+    pub values: VecDeque<Value>,
+    /// None for a queue that grows as needed
+    pub capacity: Option<usize>,
+}
+
+impl QueueData {
+    /// Whether the queue holds as many values as it can, so a push has to wait for a pop.
+    pub fn is_full(&self) -> bool {
+        self.capacity.is_some_and(|cap| self.values.len() >= cap)
+    }
+}
+
+impl Queue {
+    pub fn new() -> Self {
+        Self(Rc::new(RefCell::new(QueueData::default())))
+    }
+
+    /// A queue holding at most capacity values. Pushing to it when full blocks until a value is
+    /// popped, so a producer can't run ahead of its consumers.
+    pub fn bounded(capacity: usize) -> Self {
+        Self(Rc::new(RefCell::new(QueueData {
+            values: VecDeque::new(),
+            capacity: Some(capacity),
+        })))
+    }
+}
+
+impl Default for Queue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PartialEq for Queue {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Clone for Queue {
+    fn clone(&self) -> Self {
+        Self(Rc::clone(&self.0))
+    }
+}
+
+impl Debug for Queue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Queue({:?})", self.borrow().values)
+    }
+}
@@ -0,0 +1,51 @@
+use serde::{Deserialize, Serialize};
+
+use crate::Address;
+
+/// (address, line) pairs sorted by address: the instructions from each address up to the next
+/// pair's were compiled from that line. Lines start from 1.
+pub type LineTable = Vec<(Address, usize)>;
+
+/// Which line of which source file each instruction of a compiled program came from, so runtime
+/// errors can point at the line that failed.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct SourceMap {
+    /// The source file, as it was given to the compiler.
+    pub file: String,
+    /// The line of each instruction.
+    pub lines: LineTable,
+}
+
+impl SourceMap {
+    pub fn new(file: &str, lines: LineTable) -> SourceMap {
+        SourceMap {
+            file: file.to_string(),
+            lines,
+        }
+    }
+
+    /// The line the instruction at addr was compiled from, if known.
+    pub fn line(&self, addr: usize) -> Option<usize> {
+        let after = self.lines.partition_point(|(start, _)| *start <= addr);
+        after.checked_sub(1).map(|idx| self.lines[idx].1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SourceMap;
+
+    #[test]
+    fn test_source_map_line() {
+        let map = SourceMap::new("x.rst", vec![(0, 1), (3, 2), (5, 4)]);
+        assert_eq!(map.line(0), Some(1));
+        assert_eq!(map.line(2), Some(1));
+        assert_eq!(map.line(3), Some(2));
+        assert_eq!(map.line(4), Some(2));
+        assert_eq!(map.line(100), Some(4));
+
+        let map = SourceMap::new("x.rst", vec![(2, 1)]);
+        assert_eq!(map.line(1), None);
+        assert_eq!(SourceMap::default().line(0), None);
+    }
+}
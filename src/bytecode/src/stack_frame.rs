@@ -1,6 +1,8 @@
+use std::{cell::RefCell, rc::Rc};
+
 use serde::{Deserialize, Serialize};
 
-use crate::EnvWeak;
+use crate::{EnvWeak, Environment};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub enum FrameType {
@@ -13,6 +15,9 @@ pub struct StackFrame {
     pub frame_type: FrameType,
     pub address: Option<usize>,
     pub env: EnvWeak,
+    /// The environment of a scope entered with ENTERSCOPELOCAL. The frame owns it, so it is freed
+    /// when the frame is popped rather than by the garbage collector.
+    pub scope: Option<Rc<RefCell<Environment>>>,
 }
 
 impl StackFrame {
@@ -21,6 +26,18 @@ impl StackFrame {
             frame_type,
             address: None,
             env,
+            scope: None,
+        }
+    }
+
+    /// A block frame for a local scope: it restores env when the scope exits and owns scope, the
+    /// scope's environment.
+    pub fn new_local(env: EnvWeak, scope: Rc<RefCell<Environment>>) -> Self {
+        StackFrame {
+            frame_type: FrameType::BlockFrame,
+            address: None,
+            env,
+            scope: Some(scope),
         }
     }
 
@@ -29,6 +46,7 @@ impl StackFrame {
             frame_type,
             address: Some(address),
             env,
+            scope: None,
         }
     }
 }
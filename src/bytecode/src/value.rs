@@ -2,7 +2,7 @@ use std::fmt::{Debug, Display};
 
 use serde::{Deserialize, Serialize};
 
-use crate::{ByteCodeError, EnvWeak, Semaphore, Symbol};
+use crate::{ByteCodeError, EnvWeak, Matrix, MatrixData, Queue, Semaphore, Symbol};
 
 /// The values that can be stored on the operant stack.
 #[derive(Serialize, Deserialize, Clone, PartialEq)]
@@ -10,12 +10,18 @@ pub enum Value {
     Unitialized,
     Unit,
     Int(i64),
+    /// ID of a thread, from spawn or current_tid. Only thread IDs can be joined, not plain ints.
+    ThreadId(i64),
     Float(f64),
     Bool(bool),
     String(String),
     #[serde(skip_serializing, skip_deserializing)]
     Semaphore(Semaphore),
     #[serde(skip_serializing, skip_deserializing)]
+    Queue(Queue),
+    #[serde(skip_serializing, skip_deserializing)]
+    Matrix(Matrix),
+    #[serde(skip_serializing, skip_deserializing)]
     Closure {
         fn_type: FnType,
         sym: Symbol,
@@ -23,6 +29,8 @@ pub enum Value {
         addr: usize,
         env: EnvWeak,
     },
+    /// Result of joining a thread that was cancelled before it finished.
+    Cancelled,
 }
 
 #[derive(Clone, Debug, PartialEq, Default)]
@@ -37,11 +45,82 @@ pub fn type_of(value: &Value) -> &'static str {
         Value::Unitialized => "Unitialized",
         Value::Unit => "Unit",
         Value::Int(_) => "Int",
+        Value::ThreadId(_) => "ThreadId",
         Value::Float(_) => "Float",
         Value::Bool(_) => "Bool",
         Value::String(_) => "String",
         Value::Semaphore(_) => "Semaphore",
+        Value::Queue(_) => "Queue",
+        Value::Matrix(_) => "Matrix",
         Value::Closure { .. } => "Closure",
+        Value::Cancelled => "Cancelled",
+    }
+}
+
+impl Value {
+    /// Copy of the value that shares no mutable state with the original.
+    ///
+    /// Most values are immutable, so this is the same as clone. It is the single place where
+    /// compound values are copied: a matrix copy has its own elements, and is == to the original
+    /// until either is changed. Semaphores, queues and closures are handles: the copy refers to the
+    /// same semaphore / queue / environment, since threads share them to communicate.
+    pub fn deep_clone(&self) -> Value {
+        match self {
+            Value::Matrix(m) => Matrix::new(m.borrow().clone()).into(),
+            Value::Unitialized
+            | Value::Unit
+            | Value::Int(_)
+            | Value::ThreadId(_)
+            | Value::Float(_)
+            | Value::Bool(_)
+            | Value::String(_)
+            | Value::Semaphore(_)
+            | Value::Queue(_)
+            | Value::Closure { .. }
+            | Value::Cancelled => self.clone(),
+        }
+    }
+}
+
+impl Value {
+    /// Display the value, with floats at float_precision digits after the decimal point if given
+    pub fn display(&self, float_precision: Option<usize>) -> String {
+        match self {
+            Value::Float(f) => format_float(*f, float_precision),
+            Value::Matrix(m) => m.borrow().display(float_precision),
+            _ => self.to_string(),
+        }
+    }
+}
+
+/// Format a float with precision digits after the decimal point, or if None, the shortest decimal
+/// that reads back as the same float, like ryu: 1.0, 0.1, 1e300, 1.5e-7. Scientific notation is
+/// used for exponents below -5 or above 15, where the decimal form gets long.
+pub fn format_float(f: f64, precision: Option<usize>) -> String {
+    if let Some(digits) = precision {
+        return format!("{:.*}", digits, f);
+    }
+
+    // NaN, inf and -inf
+    if !f.is_finite() {
+        return f.to_string();
+    }
+
+    let sci = format!("{:e}", f);
+    let exp: i32 = sci
+        .split_once('e')
+        .and_then(|(_, exp)| exp.parse().ok())
+        .unwrap_or(0);
+
+    if !(-5..16).contains(&exp) {
+        return sci;
+    }
+
+    let dec = f.to_string();
+    if dec.contains('.') {
+        dec
+    } else {
+        format!("{}.0", dec)
     }
 }
 
@@ -52,10 +131,13 @@ impl Display for Value {
             Value::Unit => "()".to_string(),
             Value::String(s) => s.to_string(),
             Value::Bool(b) => b.to_string(),
-            Value::Int(i) => i.to_string(),
-            Value::Float(f) => f.to_string(),
+            Value::Int(i) | Value::ThreadId(i) => i.to_string(),
+            Value::Float(f) => format_float(*f, None),
             Value::Semaphore(_) => "semaphore".to_string(),
+            Value::Queue(_) => "queue".to_string(),
+            Value::Matrix(m) => m.borrow().display(None),
             Value::Closure { .. } => "closure".to_string(),
+            Value::Cancelled => "cancelled".to_string(),
         };
 
         write!(f, "{}", res)
@@ -70,8 +152,11 @@ impl Debug for Value {
             Value::String(s) => s.to_string(),
             Value::Bool(b) => b.to_string(),
             Value::Int(i) => i.to_string(),
-            Value::Float(f) => f.to_string(),
+            Value::ThreadId(tid) => format!("ThreadId({})", tid),
+            Value::Float(f) => format_float(*f, None),
             Value::Semaphore(_) => "semaphore".to_string(),
+            Value::Queue(_) => "queue".to_string(),
+            Value::Matrix(m) => m.borrow().display(None),
             Value::Closure {
                 sym,
                 fn_type,
@@ -82,6 +167,7 @@ impl Debug for Value {
                 "Closure {{ sym: {}, fn_type: {:?}, prms: {:?}, addr: {} }}",
                 sym, fn_type, prms, addr
             ),
+            Value::Cancelled => "cancelled".to_string(),
         };
 
         write!(f, "{}", res)
@@ -130,6 +216,24 @@ impl From<Semaphore> for Value {
     }
 }
 
+impl From<Queue> for Value {
+    fn from(v: Queue) -> Self {
+        Value::Queue(v)
+    }
+}
+
+impl From<Matrix> for Value {
+    fn from(v: Matrix) -> Self {
+        Value::Matrix(v)
+    }
+}
+
+impl From<MatrixData> for Value {
+    fn from(v: MatrixData) -> Self {
+        Value::Matrix(Matrix::new(v))
+    }
+}
+
 impl TryFrom<Value> for () {
     type Error = ByteCodeError;
 
@@ -214,6 +318,34 @@ impl TryFrom<Value> for Semaphore {
     }
 }
 
+impl TryFrom<Value> for Queue {
+    type Error = ByteCodeError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Queue(q) => Ok(q),
+            _ => Err(ByteCodeError::TypeMismatch {
+                expected: "Queue".to_string(),
+                found: format!("{:?}", value),
+            }),
+        }
+    }
+}
+
+impl TryFrom<Value> for Matrix {
+    type Error = ByteCodeError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Matrix(m) => Ok(m),
+            _ => Err(ByteCodeError::TypeMismatch {
+                expected: "Matrix".to_string(),
+                found: format!("{:?}", value),
+            }),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -236,6 +368,26 @@ mod tests {
         assert_eq!(v, Value::Float(0.0));
     }
 
+    #[test]
+    fn test_format_float() {
+        assert_eq!(format_float(1.0, None), "1.0");
+        assert_eq!(format_float(-0.0, None), "-0.0");
+        assert_eq!(format_float(0.1 + 0.2, None), "0.30000000000000004");
+        assert_eq!(format_float(123456.5, None), "123456.5");
+        assert_eq!(format_float(1e15, None), "1000000000000000.0");
+        assert_eq!(format_float(1e16, None), "1e16");
+        assert_eq!(format_float(1e300, None), "1e300");
+        assert_eq!(format_float(0.00001, None), "0.00001");
+        assert_eq!(format_float(1.5e-7, None), "1.5e-7");
+        assert_eq!(format_float(f64::NAN, None), "NaN");
+        assert_eq!(format_float(f64::NEG_INFINITY, None), "-inf");
+
+        assert_eq!(format_float(2.0 / 3.0, Some(3)), "0.667");
+        assert_eq!(format_float(2.5, Some(0)), "2");
+        assert_eq!(Value::Float(1.0).display(Some(2)), "1.00");
+        assert_eq!(Value::Int(1).display(Some(2)), "1");
+    }
+
     #[test]
     fn test_unequal() {
         let val_int: Value = 42.into();
@@ -266,6 +418,28 @@ mod tests {
         assert_eq!(value, Value::Unit);
     }
 
+    #[test]
+    fn test_deep_clone() {
+        let vals: [Value; 5] = [42.into(), 4.2.into(), true.into(), "s".into(), ().into()];
+        for val in vals {
+            assert_eq!(val.deep_clone(), val);
+        }
+
+        // semaphore is a handle: the copy is the same semaphore
+        let sem = Semaphore::new(2);
+        let val: Value = sem.clone().into();
+        let copy: Semaphore = val.deep_clone().try_into().unwrap();
+        *copy.lock().unwrap() = 0;
+        assert_eq!(*sem.lock().unwrap(), 0);
+
+        // a matrix copy has its own elements
+        let val: Value = MatrixData::new(1, 1, 1.0).unwrap().into();
+        let copy: Matrix = val.deep_clone().try_into().unwrap();
+        copy.borrow_mut().data[0] = 2.0;
+        assert_eq!(val.to_string(), "[[1.0]]");
+        assert_eq!(Value::Matrix(copy).to_string(), "[[2.0]]");
+    }
+
     #[test]
     fn test_from_string() {
         let string_value: String = "Hello, World!".to_string();
@@ -0,0 +1,172 @@
+use std::fmt::Display;
+
+/// Codes for the errors of a stage that don't have a more specific code below. For the type checker
+/// that is values of the wrong type.
+pub const PARSE_ERROR: &str = "E0001";
+pub const TYPE_ERROR: &str = "E0002";
+pub const COMPILE_ERROR: &str = "E0003";
+pub const RUNTIME_ERROR: &str = "E0004";
+
+/// Type errors other than values of the wrong type. Codes are E02xx, after the type stage's E0002.
+pub const UNDECLARED_NAME: &str = "E0201";
+pub const MISSING_TYPE_ANNOTATION: &str = "E0202";
+/// Calls with too many, too few, unknown or repeated arguments
+pub const WRONG_ARGUMENTS: &str = "E0203";
+/// break or return where it can't jump to, like break outside of a loop
+pub const MISPLACED_JUMP: &str = "E0204";
+pub const INVALID_MAIN: &str = "E0205";
+/// A @parallel loop assigning to a variable declared outside it
+pub const SHARED_ASSIGNMENT: &str = "E0206";
+
+/// Every kind of warning has a code of its own, so tools can tell them apart and suppress some
+pub const SHADOWED_BINDING: &str = "W0201";
+pub const ENDLESS_LOOP: &str = "W0202";
+pub const UNUSED_RESULT: &str = "W0203";
+pub const UNCHECKED_PROGRAM: &str = "W0401";
+pub const BUSY_WAIT: &str = "W0402";
+pub const LOST_THREAD_ERROR: &str = "W0403";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// The stage of the toolchain that produced a diagnostic
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Parse,
+    Type,
+    Compile,
+    Runtime,
+}
+
+impl Display for Stage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Stage::Parse => write!(f, "Parse"),
+            Stage::Type => write!(f, "Type"),
+            Stage::Compile => write!(f, "Compile"),
+            Stage::Runtime => write!(f, "Runtime"),
+        }
+    }
+}
+
+/// Position in the source, both 1-based
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub line: usize,
+    pub col: usize,
+}
+
+/// An error or warning from any stage, so the CLI, REPL and editor tooling can render them the same way.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub stage: Stage,
+    pub code: String,
+    /// None until the stage tracks source positions
+    pub span: Option<Span>,
+    pub message: String,
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn error(stage: Stage, code: &str, message: &str) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Error,
+            stage,
+            code: code.to_string(),
+            span: None,
+            message: message.to_string(),
+            notes: vec![],
+        }
+    }
+
+    pub fn warning(stage: Stage, code: &str, message: &str) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Warning,
+            ..Diagnostic::error(stage, code, message)
+        }
+    }
+
+    pub fn with_span(mut self, span: Span) -> Diagnostic {
+        self.span = Some(span);
+        self
+    }
+
+    pub fn with_note(mut self, note: &str) -> Diagnostic {
+        self.notes.push(note.to_string());
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+/// Rendered like the stages' own errors, e.g "[TypeError]: Identifier 'x' not declared"
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = match self.severity {
+            Severity::Error => "Error",
+            Severity::Warning => "Warning",
+        };
+        write!(f, "[{}{}]: {}", self.stage, kind, self.message)?;
+
+        if let Some(span) = self.span {
+            write!(f, " at {}:{}", span.line, span.col)?;
+        }
+
+        for note in self.notes.iter() {
+            write!(f, "\n  note: {}", note)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Errors that can be reported as diagnostics
+pub trait ToDiagnostics {
+    fn to_diagnostics(&self) -> Vec<Diagnostic>;
+}
+
+/// Render diagnostics one after another, one or more lines each
+pub fn render(diags: &[Diagnostic]) -> String {
+    diags
+        .iter()
+        .map(|d| d.to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let diags = vec![
+            Diagnostic::error(Stage::Type, TYPE_ERROR, "Identifier 'x' not declared"),
+            Diagnostic::warning(Stage::Compile, COMPILE_ERROR, "unused value")
+                .with_span(Span { line: 2, col: 5 })
+                .with_note("assign it with let"),
+        ];
+
+        assert_eq!(
+            render(&diags),
+            "[TypeError]: Identifier 'x' not declared\n[CompileWarning]: unused value at 2:5\n  note: assign it with let"
+        );
+        assert!(diags[0].is_error());
+        assert!(!diags[1].is_error());
+    }
+}
@@ -1,4 +1,4 @@
-use logos::{Lexer, Logos, Skip};
+use logos::{FilterResult, Lexer, Logos, Skip};
 
 /// Update the line count and the char index.
 fn newline_callback(lex: &mut Lexer<Token>) -> Skip {
@@ -12,8 +12,70 @@ fn comment_callback(_lex: &mut Lexer<Token>) -> Skip {
     Skip
 }
 
+// Skip a shebang line like #!/usr/bin/env ignite, so scripts can be executable on Unix.
+// It is only allowed at the very start of the input.
+fn shebang_callback(lex: &mut Lexer<Token>) -> FilterResult<(), LexError> {
+    if lex.span().start == 0 {
+        FilterResult::Skip
+    } else {
+        FilterResult::Error(LexError::Unrecognised)
+    }
+}
+
+fn integer_callback(lex: &mut Lexer<Token>) -> Result<i64, LexError> {
+    lex.slice().parse().map_err(|_| LexError::IntegerOutOfRange)
+}
+
+fn integer_range_callback(lex: &mut Lexer<Token>) -> Result<i64, LexError> {
+    let slice = lex.slice();
+    slice[..slice.len() - 2]
+        .parse()
+        .map_err(|_| LexError::IntegerOutOfRange)
+}
+
+// 5.5, .5, 5., 5e3 and 5.5e-3. Parsing can't fail, but is infinite if too large
+fn float_callback(lex: &mut Lexer<Token>) -> Result<f64, LexError> {
+    match lex.slice().parse::<f64>() {
+        Ok(val) if val.is_finite() => Ok(val),
+        _ => Err(LexError::FloatOutOfRange),
+    }
+}
+
+// A number with letters right after it, like 1.0f or 10i, which Rust and C programmers may write
+// out of habit. Caught here so the error is about the suffix rather than an unexpected name.
+fn suffix_callback<T>(_lex: &mut Lexer<Token>) -> Result<T, LexError> {
+    Err(LexError::LiteralSuffix)
+}
+
+/// Why the input could not be lexed
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum LexError {
+    /// The input doesn't start any token, e.g. an unterminated string
+    #[default]
+    Unrecognised,
+    IntegerOutOfRange,
+    FloatOutOfRange,
+    /// A number followed by letters, e.g. 1.0f
+    LiteralSuffix,
+}
+
+impl LexError {
+    /// Message for the error, given the input it was found in
+    pub fn message(&self, slice: &str) -> String {
+        match self {
+            LexError::Unrecognised => format!("Unrecognised token '{}'", slice),
+            LexError::IntegerOutOfRange => format!("Integer literal '{}' out of range", slice),
+            LexError::FloatOutOfRange => format!("Float literal '{}' out of range", slice),
+            LexError::LiteralSuffix => format!(
+                "Number literal '{}' has a suffix, but literals don't take one: write 1 for an int and 1.0 for a float, or convert with 'as int' or 'as float'",
+                slice
+            ),
+        }
+    }
+}
+
 #[derive(Debug, Logos, PartialEq, Clone)]
-#[logos(skip r"[ \t\r\f]+", extras=(usize, usize))]
+#[logos(skip r"[ \t\r\f]+", extras=(usize, usize), error = LexError)]
 // #[logos(extras = (usize, usize))]
 pub enum Token {
     #[regex(r"\n", newline_callback)]
@@ -28,6 +90,9 @@ pub enum Token {
     #[token(".")]
     Dot,
 
+    #[token("..")]
+    DotDot,
+
     #[token(",")]
     Comma,
 
@@ -79,6 +144,12 @@ pub enum Token {
     #[token(">")]
     Gt,
 
+    #[token("<=")]
+    Le,
+
+    #[token(">=")]
+    Ge,
+
     #[token("-")]
     Minus,
 
@@ -124,6 +195,10 @@ pub enum Token {
     #[token("->")]
     FnDeclReturn,
 
+    // separates a select arm from its body
+    #[token("=>")]
+    FatArrow,
+
     #[token("return")]
     Return,
 
@@ -133,6 +208,9 @@ pub enum Token {
     #[regex(r#"//[^\n]*"#, comment_callback)]
     Comment,
 
+    #[regex(r#"#![^\n]*"#, shebang_callback)]
+    Shebang,
+
     #[token("loop")]
     Loop,
 
@@ -154,6 +232,9 @@ pub enum Token {
     #[token("yield")]
     Yield,
 
+    #[token("as")]
+    As,
+
     #[token("false", |_| false)]
     #[token("true", |_| true)]
     Bool(bool),
@@ -161,12 +242,21 @@ pub enum Token {
     // issue: negative numbers should be dealt with at parser level instead of lexer level (causes issue with minus operator)
     // https://stackoverflow.com/questions/58910659/how-to-properly-lex-negative-numbers
     // so we don't put -? at the front
-    #[regex(r"\d+", |lex| lex.slice().parse::<i64>().unwrap())]
+    #[regex(r"\d+", integer_callback)]
+    #[regex(r"\d+[a-zA-Z_][a-zA-Z0-9_]*", suffix_callback, priority = 1)]
     Integer(i64),
 
-    #[regex(r"\d*\.\d+", |lex| lex.slice().parse::<f64>().unwrap())]
+    #[regex(r"\d+\.\d*([eE][+-]?\d+)?", float_callback)]
+    #[regex(r"\.\d+([eE][+-]?\d+)?", float_callback)]
+    #[regex(r"\d+[eE][+-]?\d+", float_callback)]
+    #[regex(r"\d+\.\d*[a-zA-Z_][a-zA-Z0-9_]*", suffix_callback, priority = 1)]
     Float(f64),
 
+    // An integer right before '..', like the 0 in 0..n, which would otherwise lex as the float 0.
+    // followed by '.'. The parser splits it into Integer and DotDot.
+    #[regex(r"\d+\.\.", integer_range_callback)]
+    IntegerDotDot(i64),
+
     #[regex(r#""([^"\\]|\\["\\bnfrt]|u[a-fA-F0-9]{4})*""#, |lex| {
       let slice = lex.slice();
       let stripped = &slice[1..slice.len() - 1];
@@ -181,7 +271,33 @@ impl std::fmt::Display for Token {
     }
 }
 
+/// Words that are not keywords yet but will be, e.g. for while loops. They lex as identifiers, and
+/// editions of the language that have them as keywords reject them as names.
+pub const RESERVED_KEYWORDS: [&str; 7] =
+    ["while", "for", "in", "struct", "enum", "match", "continue"];
+
 impl Token {
+    /// Whether the token is a keyword, which can't be used as a name
+    pub fn is_keyword(&self) -> bool {
+        matches!(
+            self,
+            Self::Let
+                | Self::If
+                | Self::Else
+                | Self::Fn
+                | Self::Return
+                | Self::Loop
+                | Self::Break
+                | Self::Spawn
+                | Self::Join
+                | Self::Wait
+                | Self::Post
+                | Self::Yield
+                | Self::As
+                | Self::Bool(_)
+        )
+    }
+
     pub fn repr(&self) -> String {
         match self {
             Self::Ident(id) => id.to_string(),
@@ -189,6 +305,7 @@ impl Token {
             Self::Semi => ";".to_string(),
             Self::Colon => ":".to_string(),
             Self::Dot => ".".to_string(),
+            Self::DotDot => "..".to_string(),
             Self::Comma => ",".to_string(),
             Self::OpenParen => "(".to_string(),
             Self::CloseParen => ")".to_string(),
@@ -205,6 +322,8 @@ impl Token {
             Self::Bang => "!".to_string(),
             Self::Lt => "<".to_string(),
             Self::Gt => ">".to_string(),
+            Self::Le => "<=".to_string(),
+            Self::Ge => ">=".to_string(),
             Self::Minus => "-".to_string(),
             Self::And => "&".to_string(),
             Self::Or => "|".to_string(),
@@ -216,7 +335,9 @@ impl Token {
             Self::Let => "let".to_string(),
             Self::Bool(val) => val.to_string(),
             Self::Integer(val) => val.to_string(),
-            Self::Float(val) => val.to_string(),
+            // Debug keeps the point, so 1.0 is not printed as the int 1
+            Self::Float(val) => format!("{:?}", val),
+            Self::IntegerDotDot(val) => format!("{}..", val),
             Self::If => "if".to_string(),
             Self::Else => "else".to_string(),
             Self::LogEq => "==".to_string(),
@@ -225,15 +346,18 @@ impl Token {
             Self::Loop => "loop".to_string(),
             Self::Break => "break".to_string(),
             Self::Comment => "//".to_string(),
+            Self::Shebang => "#!".to_string(),
             Self::Newline => "\n".to_string(),
             Self::Fn => "fn".to_string(),
             Self::Return => "return".to_string(),
             Self::FnDeclReturn => "->".to_string(),
+            Self::FatArrow => "=>".to_string(),
             Self::Spawn => "spawn".to_string(),
             Self::Join => "join".to_string(),
             Self::Wait => "wait".to_string(),
             Self::Post => "post".to_string(),
             Self::Yield => "yield".to_string(),
+            Self::As => "as".to_string(),
         }
     }
 }
@@ -245,8 +369,6 @@ pub fn lex(input: &str) -> Lexer<'_, Token> {
 #[cfg(test)]
 mod test {
     use super::*;
-    use std::f64;
-    use std::i64;
 
     #[test]
     fn test_bool() {
@@ -366,6 +488,99 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_float_forms() {
+        let input = "5. 5e3 5E+3 .5e1 1.2e-4 -1.2e-4 2.e2 0e0";
+        let tokens: Vec<Token> = Token::lexer(input).map(|tok| tok.unwrap()).collect();
+
+        let expected = vec![
+            Token::Float(5.0),
+            Token::Float(5000.0),
+            Token::Float(5000.0),
+            Token::Float(5.0),
+            Token::Float(1.2e-4),
+            Token::Minus,
+            Token::Float(1.2e-4),
+            Token::Float(200.0),
+            Token::Float(0.0),
+        ];
+        assert_eq!(tokens, expected);
+
+        // no digits after e: a suffix, not an exponent
+        let tokens: Vec<_> = Token::lexer("5e").collect();
+        assert_eq!(tokens, vec![Err(LexError::LiteralSuffix)]);
+
+        assert_eq!(Token::Float(1.0).to_string(), "1.0");
+        assert_eq!(Token::Float(1e300).to_string(), "1e300");
+    }
+
+    #[test]
+    fn test_ranges() {
+        let tokens: Vec<Token> = Token::lexer("0..n lo..10 1.5..2")
+            .map(|tok| tok.unwrap())
+            .collect();
+        let expected = vec![
+            Token::IntegerDotDot(0),
+            Token::Ident("n".to_string()),
+            Token::Ident("lo".to_string()),
+            Token::DotDot,
+            Token::Integer(10),
+            Token::Float(1.5),
+            Token::DotDot,
+            Token::Integer(2),
+        ];
+        assert_eq!(tokens, expected);
+    }
+
+    #[test]
+    fn test_keywords() {
+        let tokens: Vec<Token> = Token::lexer("loop true while looping")
+            .map(|tok| tok.unwrap())
+            .collect();
+        assert!(tokens[0].is_keyword());
+        assert!(tokens[1].is_keyword());
+        // reserved words are still names
+        assert_eq!(tokens[2], Token::Ident("while".to_string()));
+        assert!(!tokens[2].is_keyword());
+        assert!(!tokens[3].is_keyword());
+    }
+
+    #[test]
+    fn test_lex_errors() {
+        let mut lexer = Token::lexer("9223372036854775808 1e309 `");
+
+        assert_eq!(lexer.next(), Some(Err(LexError::IntegerOutOfRange)));
+        assert_eq!(
+            LexError::IntegerOutOfRange.message(lexer.slice()),
+            "Integer literal '9223372036854775808' out of range"
+        );
+        assert_eq!(lexer.next(), Some(Err(LexError::FloatOutOfRange)));
+        assert_eq!(lexer.next(), Some(Err(LexError::Unrecognised)));
+        assert_eq!(
+            LexError::Unrecognised.message(lexer.slice()),
+            "Unrecognised token '`'"
+        );
+
+        // suffixes, but exponents are part of a float
+        let tokens: Vec<_> = Token::lexer("1.0f 10i 2.5e3 4e2x 7 as").collect();
+        assert_eq!(
+            tokens,
+            vec![
+                Err(LexError::LiteralSuffix),
+                Err(LexError::LiteralSuffix),
+                Ok(Token::Float(2500.0)),
+                Err(LexError::LiteralSuffix),
+                Ok(Token::Integer(7)),
+                Ok(Token::As),
+            ]
+        );
+        let mut lexer = Token::lexer("1.0f");
+        lexer.next();
+        assert!(LexError::LiteralSuffix
+            .message(lexer.slice())
+            .starts_with("Number literal '1.0f' has a suffix"));
+    }
+
     #[test]
     fn test_string() {
         let mut lexer = Token::lexer(r#""hello" "world""#);
@@ -535,14 +750,16 @@ mod test {
 
     #[test]
     fn test_lex_comp_ops() {
-        // ==, <, >, &&, ||
-        let t = "== = < > && ||";
+        // ==, <, >, <=, >=, &&, ||
+        let t = "== = < > <= >= && ||";
         let mut lexer = Token::lexer(t);
         let exp: Vec<Token> = vec![
             Token::LogEq,
             Token::Eq,
             Token::Lt,
             Token::Gt,
+            Token::Le,
+            Token::Ge,
             Token::LogAnd,
             Token::LogOr,
         ];
@@ -638,6 +855,23 @@ mod test {
         assert_eq!(lexer.next(), None);
     }
 
+    #[test]
+    fn test_lex_shebang() {
+        let t = "#!/usr/bin/env ignite\n1;";
+        let mut lexer = Token::lexer(t);
+        assert_eq!(lexer.next().unwrap().unwrap(), Token::Integer(1));
+        assert_eq!(lexer.extras.0, 1);
+        assert_eq!(lexer.next().unwrap().unwrap(), Token::Semi);
+        assert_eq!(lexer.next(), None);
+
+        // only at the start
+        let t = "1;\n#!/usr/bin/env ignite";
+        let mut lexer = Token::lexer(t);
+        assert_eq!(lexer.next().unwrap().unwrap(), Token::Integer(1));
+        assert_eq!(lexer.next().unwrap().unwrap(), Token::Semi);
+        assert!(lexer.next().unwrap().is_err());
+    }
+
     #[test]
     fn test_lex_spawn_join() {
         let t = r"
@@ -660,4 +894,37 @@ mod test {
         assert_eq!(lexer.next().unwrap().unwrap(), Token::Post);
         assert_eq!(lexer.next().unwrap().unwrap(), Token::Yield);
     }
+
+    #[test]
+    fn test_lex_as() {
+        let mut lexer = Token::lexer("x as float; ask");
+
+        assert_eq!(
+            lexer.next().unwrap().unwrap(),
+            Token::Ident("x".to_string())
+        );
+        assert_eq!(lexer.next().unwrap().unwrap(), Token::As);
+        assert_eq!(
+            lexer.next().unwrap().unwrap(),
+            Token::Ident("float".to_string())
+        );
+        assert_eq!(lexer.next().unwrap().unwrap(), Token::Semi);
+        assert_eq!(
+            lexer.next().unwrap().unwrap(),
+            Token::Ident("ask".to_string())
+        );
+    }
+
+    #[test]
+    fn test_lex_fat_arrow() {
+        let tokens: Vec<Token> = Token::lexer("x => y = >").map(|tok| tok.unwrap()).collect();
+        let expected = vec![
+            Token::Ident("x".to_string()),
+            Token::FatArrow,
+            Token::Ident("y".to_string()),
+            Token::Eq,
+            Token::Gt,
+        ];
+        assert_eq!(tokens, expected);
+    }
 }
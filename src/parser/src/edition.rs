@@ -0,0 +1,118 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use lexer::RESERVED_KEYWORDS;
+
+/// Version of the language a program is written in. New keywords are only added in a new edition,
+/// so programs using them as names keep working in the edition they were written for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Edition {
+    /// The language as first released
+    #[default]
+    E2026,
+    /// Also makes the reserved words while, for, in, struct, enum, match and continue keywords
+    E2027,
+}
+
+impl Edition {
+    /// The newest edition
+    pub const LATEST: Edition = Edition::E2027;
+
+    /// Whether the identifier name is a keyword in this edition
+    pub fn is_keyword(&self, name: &str) -> bool {
+        match self {
+            Edition::E2026 => false,
+            Edition::E2027 => RESERVED_KEYWORDS.contains(&name),
+        }
+    }
+}
+
+impl Display for Edition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Edition::E2026 => write!(f, "2026"),
+            Edition::E2027 => write!(f, "2027"),
+        }
+    }
+}
+
+impl FromStr for Edition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "2026" => Ok(Edition::E2026),
+            "2027" => Ok(Edition::E2027),
+            _ => Err(format!("Unknown edition '{}', expected 2026 or 2027", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::*;
+    use crate::Parser;
+
+    fn parse_edition(inp: &str, edition: Edition) -> Result<String, String> {
+        let mut parser = Parser::new_from_string(inp);
+        parser.set_edition(edition);
+        parser
+            .parse()
+            .map(|prog| prog.to_string())
+            .map_err(|err| err.to_string())
+    }
+
+    #[test]
+    fn test_keyword_names() {
+        test_parse_err(
+            "let loop = 3;",
+            "'loop' is a keyword and can't be used as a name",
+            true,
+        );
+        test_parse_err(
+            "fn spawn() {}",
+            "'spawn' is a keyword and can't be used as a name",
+            true,
+        );
+        test_parse_err(
+            "fn f(x: int, true: bool) {}",
+            "'true' is a keyword and can't be used as a name",
+            true,
+        );
+        test_parse_err("let 3 = 3;", "Expected identifier", true);
+    }
+
+    #[test]
+    fn test_edition_keywords() {
+        let src = "let match = 1; fn while(for: int) -> int { for } while(match)";
+        assert_eq!(
+            parse_edition(src, Edition::E2026).unwrap(),
+            "let match = 1;fn while (for:int) -> int { for };while(match)"
+        );
+
+        let err = parse_edition(src, Edition::E2027).unwrap_err();
+        assert_eq!(
+            err,
+            "[ParseError]: 'match' is a keyword in edition 2027 and can't be used as a name"
+        );
+
+        // using a name is an error too, not only declaring it
+        let err = parse_edition("continue", Edition::E2027).unwrap_err();
+        assert!(err.contains("'continue' is a keyword in edition 2027"));
+        assert_eq!(
+            parse_edition("let r = 1; r", Edition::LATEST).unwrap(),
+            "let r = 1;r"
+        );
+    }
+
+    #[test]
+    fn test_edition_from_str() {
+        assert_eq!("2027".parse(), Ok(Edition::E2027));
+        assert_eq!(Edition::default().to_string(), "2026");
+        assert_eq!(
+            "2025".parse::<Edition>(),
+            Err("Unknown edition '2025', expected 2026 or 2027".to_string())
+        );
+    }
+}
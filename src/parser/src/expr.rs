@@ -11,8 +11,12 @@ impl<'inp> Parser<'inp> {
     // Return as Decl for consistency
     // Invariant: prev_tok should contain the start of the expr before call
     pub(crate) fn parse_expr(&mut self, min_bp: u8) -> Result<Decl, ParseError> {
+        self.nested(|parser| parser.parse_expr_inner(min_bp))
+    }
+
+    fn parse_expr_inner(&mut self, min_bp: u8) -> Result<Decl, ParseError> {
         let prev_tok = self.expect_prev_tok()?;
-        let mut lhs = match prev_tok {
+        let lhs = match prev_tok {
             Token::OpenParen => {
                 self.advance();
                 let lhs = self.parse_expr(0)?;
@@ -31,6 +35,13 @@ impl<'inp> Parser<'inp> {
                 let res = Expr::UnOpExpr(UnOpType::Negate, Box::new(rhs.to_expr()?));
                 Ok(ExprStmt(res))
             }
+            Token::Plus => {
+                let ((), r_bp) = Parser::get_prefix_bp(&UnOpType::Plus);
+                self.advance();
+                let rhs = self.parse_expr(r_bp)?;
+                let res = Expr::UnOpExpr(UnOpType::Plus, Box::new(rhs.to_expr()?));
+                Ok(ExprStmt(res))
+            }
             Token::Bang => {
                 let ((), r_bp) = Parser::get_prefix_bp(&UnOpType::Not);
                 self.advance();
@@ -42,6 +53,7 @@ impl<'inp> Parser<'inp> {
                 // Three cases: id, id = ..., id() => load var, assignment, func call
                 // Handle just id first
                 // dbg!(&self.lexer.peek());
+                self.check_name(id)?;
                 self.parse_ident(id.to_string(), min_bp)
             }
             Token::OpenBrace => self.parse_blk(),
@@ -52,6 +64,12 @@ impl<'inp> Parser<'inp> {
             ))),
         }?;
 
+        self.parse_infix(lhs, min_bp)
+    }
+
+    /// Parse the operators, calls and casts that follow lhs, which was already parsed, binding at
+    /// least as tightly as min_bp
+    pub(crate) fn parse_infix(&mut self, mut lhs: Decl, min_bp: u8) -> Result<Decl, ParseError> {
         // dbg!("LHS:", &lhs);
         loop {
             if self.lexer.peek().is_none()
@@ -62,6 +80,12 @@ impl<'inp> Parser<'inp> {
                 || self.is_peek_token_type(Token::OpenBrace)
                 // to deal with comma in func call e.g print(2,3);
                 || self.is_peek_token_type(Token::Comma)
+                // to end the body of an if without braces e.g if (c) 1 else 2
+                || self.is_peek_token_type(Token::Else)
+                // to end the start of a range e.g @parallel loop i in 0..n
+                || self.is_peek_token_type(Token::DotDot)
+                // to end the operation of a select arm e.g queue_pop(q) => { .. }
+                || self.is_peek_token_type(Token::FatArrow)
             {
                 break;
             }
@@ -73,6 +97,30 @@ impl<'inp> Parser<'inp> {
                 .clone()
                 .expect("Lexer should not fail");
 
+            // Call on the result of an expression e.g get_fn()(3), (if c { f } else { g })(2). Binds tighter
+            // than any operator. A block followed by ( is a separate statement, as before.
+            if tok.eq(&Token::OpenParen) && !matches!(self.prev_tok, Some(Token::CloseBrace)) {
+                let (args, named_args) = self.parse_call_args()?;
+                if !named_args.is_empty() {
+                    return Err(ParseError::new(
+                        "Named arguments need a call to a function by name",
+                    ));
+                }
+                lhs = ExprStmt(Expr::CallExpr(Box::new(lhs.to_expr()?), args));
+                continue;
+            }
+
+            // Cast e.g x as float. Peek is at the first token of the type after advancing past as
+            if tok.eq(&Token::As) {
+                if Parser::CAST_BP < min_bp {
+                    break;
+                }
+                self.advance();
+                let ty = self.parse_type_annotation()?;
+                lhs = ExprStmt(Expr::CastExpr(Box::new(lhs.to_expr()?), ty));
+                continue;
+            }
+
             // dbg!("Prev_tok before from_token:", &self.prev_tok);
             let binop = BinOpType::from_token(&tok);
 
@@ -151,6 +199,29 @@ mod tests {
 
         // no type checking yet - leave type checking to one distinct phase
         test_parse("let x = -true+false;", "let x = ((-true)+false);");
+
+        // unary plus
+        test_parse("+2;", "(+2);");
+        test_parse("3*+2", "(3*(+2))");
+        test_parse("3++2", "(3+(+2))");
+        test_parse("let x = +-1.5;", "let x = (+(-1.5));");
+    }
+
+    #[test]
+    fn test_parse_cast() {
+        test_parse("x as float", "(x as float)");
+        test_parse("-x as int", "((-x) as int)");
+        test_parse("a * b as float + 1", "((a*(b as float))+1)");
+        test_parse("x as int as float", "((x as int) as float)");
+        test_parse("(a + b) as float", "((a+b) as float)");
+        test_parse("f(2) as int < 3", "((f(2) as int)<3)");
+        test_parse("let y = 2 as float;", "let y = (2 as float);");
+
+        test_parse_err(
+            "x as 2",
+            "Expected identifier or '(' for type annotation, got '2'",
+            true,
+        );
     }
 
     #[test]
@@ -1,36 +1,21 @@
 use std::collections::HashSet;
 
 use crate::Decl;
+use crate::Expr;
 use crate::FnDeclData;
 use crate::FnParam;
 use crate::ParseError;
 use crate::Parser;
 use crate::Type;
+use crate::UnOpType;
 use lexer::Token;
 
 // FnDecl is only statement, not expression
 // return stmt is only allowed inside a function
 impl<'inp> Parser<'inp> {
     pub(crate) fn parse_fn_decl(&mut self) -> Result<Decl, ParseError> {
-        let prev_is_loop = self.is_loop;
-        let prev_is_fn = self.is_fn;
-
-        // turn it off because break is not automatically allowed in fn
-        self.is_loop = false;
-        self.is_fn = true;
-        let res = self.parse_fn_decl_inner();
-
-        // restore
-        self.is_loop = prev_is_loop;
-        self.is_fn = prev_is_fn;
-        res
-    }
-
-    pub(crate) fn parse_fn_decl_inner(&mut self) -> Result<Decl, ParseError> {
         // Get name
-        crate::expect_token_body!(self.lexer.peek(), Ident, "identifier")?;
-        let fn_name = Parser::string_from_ident(self.lexer.peek());
-        self.advance();
+        let fn_name = self.consume_name()?;
 
         self.consume_token_type(
             Token::OpenParen,
@@ -50,11 +35,9 @@ impl<'inp> Parser<'inp> {
             }
 
             // Invariant: at start peek is a param identifier
-            let param_name = Parser::string_from_ident(self.lexer.peek());
+            let param_name = self.consume_name()?;
             let mut param_ty: Option<Type> = None;
 
-            self.advance(); // go past ident
-
             if self.is_peek_token_type(Token::Colon) {
                 // Parse type annotation if any
                 self.advance(); // put colon in advance so at type_ann first tok = first token for type
@@ -65,6 +48,27 @@ impl<'inp> Parser<'inp> {
                 // self.advance();
             }
 
+            // Default value: only literals, so it doesn't matter where it's evaluated
+            let mut default: Option<Expr> = None;
+            if self.consume_opt_token_type(Token::Eq) {
+                self.advance(); // first token of the default goes into prev_tok
+                let expr = self.parse_expr(0)?.to_expr()?;
+                if !Parser::is_literal(&expr) {
+                    let e = format!(
+                        "Default value for parameter '{}' must be a literal, got '{}'",
+                        param_name, expr
+                    );
+                    return Err(ParseError::new(&e));
+                }
+                default.replace(expr);
+            } else if params.iter().any(|p: &FnParam| p.default.is_some()) {
+                let e = format!(
+                    "Parameter '{}' without a default value can't follow parameters with defaults",
+                    param_name
+                );
+                return Err(ParseError::new(&e));
+            }
+
             // Comma or CloseParen
             if !self.lexer.peek().eq(&Some(&Ok(Token::CloseParen))) {
                 self.consume_token_type(
@@ -86,6 +90,7 @@ impl<'inp> Parser<'inp> {
             params.push(FnParam {
                 name: param_name,
                 type_ann: param_ty,
+                default,
             })
         }
 
@@ -120,6 +125,17 @@ impl<'inp> Parser<'inp> {
 
         Ok(Decl::FnDeclStmt(fn_decl))
     }
+
+    /// Literal value, including negative numbers
+    fn is_literal(expr: &Expr) -> bool {
+        match expr {
+            Expr::Integer(_) | Expr::Float(_) | Expr::Bool(_) | Expr::StringLiteral(_) => true,
+            Expr::UnOpExpr(UnOpType::Negate | UnOpType::Plus, expr) => {
+                matches!(**expr, Expr::Integer(_) | Expr::Float(_))
+            }
+            _ => false,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -195,6 +211,36 @@ mod tests {
         test_parse(t, "fn f (x, y:int, z:bool, g) { let y = 2; };");
     }
 
+    #[test]
+    fn test_parse_fn_decl_defaults() {
+        let t = r#"
+        fn greet(name: str, greeting: str = "hello", n: int = -1) {
+        }
+        "#;
+        test_parse(
+            t,
+            "fn greet (name:str, greeting:str = hello, n:int = (-1)) {  };",
+        );
+
+        let t = r"
+        fn f(x: int = 2 + 3) {}
+        ";
+        test_parse_err(
+            t,
+            "Default value for parameter 'x' must be a literal, got '(2+3)'",
+            true,
+        );
+
+        let t = r"
+        fn f(x: int = 2, y: int) {}
+        ";
+        test_parse_err(
+            t,
+            "Parameter 'y' without a default value can't follow parameters with defaults",
+            true,
+        );
+    }
+
     #[test]
     fn test_parse_fn_decl_with_retype() {
         let t = r"
@@ -317,23 +363,6 @@ mod tests {
 
     #[test]
     fn test_parse_fn_decl_edges2() {
-        let t = r"
-        fn f() {
-            break;
-        }
-        ";
-        test_parse_err(t, "break outside of loop", true);
-
-        let t = r"
-        fn f() {
-            loop {
-                break;
-            }
-            break;
-        }
-        ";
-        test_parse_err(t, "break outside of loop", true);
-
         let t = r"
         fn f() {
             loop {
@@ -344,16 +373,6 @@ mod tests {
         ";
         test_parse(t, "fn f () { loop  { break;return; }; };");
 
-        // fn in loop
-        let t = r"
-        loop {
-            fn f() {
-                break;
-            }
-        }
-        ";
-        test_parse_err(t, "break outside of loop", true);
-
         // fn in loop with return
         let t = r"
         loop {
@@ -368,18 +387,6 @@ mod tests {
             t,
             "loop  { fn f () -> int { loop  { break; };return; };break; };",
         );
-
-        // cant return outside fn
-        let t = r"
-        loop {
-            fn f() {
-                loop { break; }
-                return;
-            }
-            return;
-        }
-        ";
-        test_parse_err(t, "return outside of fn", true);
     }
 
     #[test]
@@ -2,6 +2,7 @@ use crate::AssignStmtData;
 use crate::Decl;
 use crate::Expr;
 use crate::FnCallData;
+use crate::NamedArg;
 use crate::ParseError;
 use crate::Parser;
 use lexer::Token;
@@ -27,47 +28,72 @@ impl<'inp> Parser<'inp> {
                 return Ok(Decl::AssignStmt(assign));
             } else if tok.eq(&Token::OpenParen) {
                 // Fn call
-                self.consume_token_type(Token::OpenParen, "Expected '('")?;
-                // dbg!("tok after:", &self.lexer.peek());
+                let (args, named_args) = self.parse_call_args()?;
+                let data = FnCallData {
+                    name: ident,
+                    args,
+                    named_args,
+                };
 
-                let mut args: Vec<Expr> = vec![];
-
-                while let Some(tok) = self.lexer.peek() {
-                    let tok = tok.clone();
-                    // stop at )
-                    if tok.clone().unwrap().eq(&Token::CloseParen) {
-                        break;
-                    }
-
-                    self.advance(); // put next tok into prev_tok so parse_expr can use it
-
-                    // let expr = self.parse_expr(min_bp)?.to_expr()?;
-                    // need to reset min_bp when parsing each expr, shouldnt depend on prev
-                    let expr = self.parse_expr(0)?.to_expr()?;
+                let fn_call = Expr::FnCallExpr(data);
 
-                    // dbg!("Peek after parsing:", &self.lexer.peek(), &expr);
+                return Ok(Decl::ExprStmt(fn_call));
+            }
+        }
 
-                    args.push(expr);
+        Ok(Decl::ExprStmt(sym))
+    }
 
-                    if !self.lexer.peek().eq(&Some(&Ok(Token::CloseParen))) {
-                        self.consume_token_type(
-                            Token::Comma,
-                            "Expected ',' to separate function arguments",
-                        )?;
-                    }
-                }
+    /// Parse the arguments of a call: positional ones, then named ones (name: expr). Expect lexer.peek() to be
+    /// at the opening paren, ends with peek after the closing paren
+    pub(crate) fn parse_call_args(&mut self) -> Result<(Vec<Expr>, Vec<NamedArg>), ParseError> {
+        self.consume_token_type(Token::OpenParen, "Expected '('")?;
+        // dbg!("tok after:", &self.lexer.peek());
 
-                self.consume_token_type(Token::CloseParen, "Expected ')'")?;
+        let mut args: Vec<Expr> = vec![];
+        let mut named_args: Vec<NamedArg> = vec![];
 
-                let data = FnCallData { name: ident, args };
+        while let Some(tok) = self.lexer.peek() {
+            let tok = tok.clone();
+            // stop at )
+            if tok.clone().unwrap().eq(&Token::CloseParen) {
+                break;
+            }
 
-                let fn_call = Expr::FnCallExpr(data);
+            self.advance(); // put next tok into prev_tok so parse_expr can use it
+
+            // name: expr
+            if let (Some(Token::Ident(name)), true) =
+                (self.prev_tok.clone(), self.is_peek_token_type(Token::Colon))
+            {
+                self.advance(); // colon into prev_tok
+                self.advance(); // first token of the expr into prev_tok
+                let expr = self.parse_expr(0)?.to_expr()?;
+                named_args.push((name, expr));
+            } else if !named_args.is_empty() {
+                return Err(ParseError::new(
+                    "Positional arguments can't follow named arguments",
+                ));
+            } else {
+                // need to reset min_bp when parsing each expr, shouldnt depend on prev
+                let expr = self.parse_expr(0)?.to_expr()?;
+
+                // dbg!("Peek after parsing:", &self.lexer.peek(), &expr);
+
+                args.push(expr);
+            }
 
-                return Ok(Decl::ExprStmt(fn_call));
+            if !self.lexer.peek().eq(&Some(&Ok(Token::CloseParen))) {
+                self.consume_token_type(
+                    Token::Comma,
+                    "Expected ',' to separate function arguments",
+                )?;
             }
         }
 
-        Ok(Decl::ExprStmt(sym))
+        self.consume_token_type(Token::CloseParen, "Expected ')'")?;
+
+        Ok((args, named_args))
     }
 }
 
@@ -145,4 +171,37 @@ mod tests {
         test_parse_err("print(}", "Unexpected token - not an expression", true);
         test_parse_err("print(,)", "Unexpected token - not an expression", true);
     }
+
+    #[test]
+    fn test_parse_named_args() {
+        test_parse("f(name: 2)", "f(name: 2)");
+        test_parse("f(1, b: 2 + 3, c: g(x: 1))", "f(1,b: (2+3),c: g(x: 1))");
+
+        test_parse_err(
+            "f(a: 1, 2)",
+            "Positional arguments can't follow named arguments",
+            true,
+        );
+        test_parse_err(
+            "f()(a: 1)",
+            "Named arguments need a call to a function by name",
+            true,
+        );
+    }
+
+    #[test]
+    fn test_parse_call_expr() {
+        test_parse("get_fn()(3)", "(get_fn())(3)");
+        test_parse("get_fn(1)(2, 3)(4);", "((get_fn(1))(2,3))(4);");
+        test_parse("(f)(2)", "(f)(2)");
+        test_parse("(if c { f } else { g })(2)", "(if c { f } else { g })(2)");
+        test_parse("1 + f()(2) * 3", "(1+((f())(2)*3))");
+        test_parse("-f()(2)", "(-(f())(2))");
+        test_parse("let x = f()(2);", "let x = (f())(2);");
+
+        // block followed by ( is still two statements
+        test_parse("{ f } (2)", "{ f };2");
+
+        test_parse_err("f()(2", "Expected ',' to separate function arguments", true);
+    }
 }
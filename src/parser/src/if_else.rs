@@ -1,44 +1,61 @@
+use std::rc::Rc;
+
+use crate::BinOpType;
 use crate::BlockSeq;
 use crate::Decl;
-// use crate::Decl::*;
 use crate::Expr;
 use crate::IfElseData;
 use crate::ParseError;
 use crate::Parser;
-// use crate::{BinOpType, UnOpType};
 use lexer::Token;
 
 impl<'inp> Parser<'inp> {
+    // if cond { .. } else { .. }, or without braces if the condition is in parentheses:
+    // if (cond) expr else expr
     pub(crate) fn parse_if_else(&mut self, min_bp: u8) -> Result<Decl, ParseError> {
-        // condition - in parens
-        // self.consume_token_type(Token::OpenParen, "Expected open parenthesis")?;
+        self.advance(); // first token of the condition into prev_tok
 
-        // If token not consumed (no open paren), advance so first token of expr goes into prev_tok
-        if !self.consume_opt_token_type(Token::OpenParen) {
+        // (cond) is only the whole condition if no operator continues it, e.g. not (a) + 1 > 2
+        let mut braceless = false;
+        let cond = if let Some(Token::OpenParen) = self.prev_tok {
             self.advance();
-        }
-
-        let cond = self.parse_expr(min_bp)?.to_expr()?;
-
-        // go past OpenBrace, put in prev_tok
-        self.consume_token_type(
-            Token::OpenBrace,
-            &format!("Expected {} for if block", Token::OpenBrace),
-        )?;
+            let group = self.parse_expr(0)?;
+            self.consume_token_type(Token::CloseParen, "Expected closing parenthesis")?;
 
-        let if_blk = self.parse_blk()?.to_block()?;
-
-        // check else
-        let mut else_blk: Option<BlockSeq> = None;
+            if self.is_peek_token_type(Token::Minus) {
+                self.parse_cond_or_negative_body(group, min_bp, &mut braceless)?
+            } else if self.continues_expr() {
+                self.parse_infix(group, min_bp)?
+            } else {
+                braceless = !self.is_peek_token_type(Token::OpenBrace);
+                group
+            }
+        } else {
+            self.parse_expr(min_bp)?
+        }
+        .to_expr()?;
 
-        if self.expect_token_type(Token::Else, "").is_ok() {
-            self.consume_token_type(Token::Else, "Expected 'else' for if")?;
+        let if_blk = if braceless {
+            self.parse_braceless_body()?
+        } else {
+            // go past OpenBrace, put in prev_tok
             self.consume_token_type(
                 Token::OpenBrace,
-                &format!("Expected {} for else block", Token::OpenBrace),
+                &format!("Expected {} for if block", Token::OpenBrace),
             )?;
+            self.parse_blk()?.to_block()?
+        };
+
+        // check else. It belongs to the nearest if, so in if (a) if (b) x else y it is b's
+        let mut else_blk: Option<BlockSeq> = None;
 
-            let blk = self.parse_blk()?.to_block()?;
+        if self.consume_opt_token_type(Token::Else) {
+            // else { .. }, or any expression including another if: else if (c) x else y
+            let blk = if self.consume_opt_token_type(Token::OpenBrace) {
+                self.parse_blk()?.to_block()?
+            } else {
+                self.parse_braceless_body()?
+            };
 
             else_blk.replace(blk);
         }
@@ -59,6 +76,67 @@ impl<'inp> Parser<'inp> {
             Ok(Decl::IfOnlyStmt(stmt))
         }
     }
+
+    /// Whether peek continues the expression before it: a binary operator or a cast
+    fn continues_expr(&mut self) -> bool {
+        match self.lexer.peek() {
+            Some(Ok(tok)) => tok.eq(&Token::As) || BinOpType::from_token(tok).is_ok(),
+            _ => false,
+        }
+    }
+
+    /// A minus after (cond) continues the condition if that ends at a block, as in
+    /// if (a) - 1 > 0 { .. }. Otherwise it starts the body, as in if (a) -1 else 1, and braceless
+    /// is set.
+    fn parse_cond_or_negative_body(
+        &mut self,
+        group: Decl,
+        min_bp: u8,
+        braceless: &mut bool,
+    ) -> Result<Decl, ParseError> {
+        let before = (self.lexer.clone(), self.prev_tok.clone());
+        match self.parse_infix(group.clone(), min_bp) {
+            Ok(cond) if self.is_peek_token_type(Token::OpenBrace) => Ok(cond),
+            _ => {
+                (self.lexer, self.prev_tok) = before;
+                *braceless = true;
+                Ok(group)
+            }
+        }
+    }
+
+    /// The body of an if without braces: one statement, which extends as far as it can, so
+    /// if (c) 1 else 2 + 3 is if (c) { 1 } else { 2 + 3 }. It is parsed to the block it would be with
+    /// braces.
+    fn parse_braceless_body(&mut self) -> Result<BlockSeq, ParseError> {
+        let line = self.lexer.line();
+        self.advance(); // first token of the body into prev_tok
+
+        let body = self.parse_decl()?;
+        if let Decl::LetStmt(_) | Decl::FnDeclStmt(_) = body {
+            return Err(ParseError::new(&format!(
+                "'{}' needs braces around it to be the body of an if",
+                body
+            )));
+        }
+
+        let blk = match body.to_expr() {
+            Ok(expr) => BlockSeq {
+                decls: vec![],
+                last_expr: Some(Rc::new(expr)),
+                symbols: vec![],
+                lines: vec![line],
+            },
+            Err(_) => BlockSeq {
+                decls: vec![body],
+                last_expr: None,
+                symbols: vec![],
+                lines: vec![line],
+            },
+        };
+
+        Ok(blk)
+    }
 }
 
 #[cfg(test)]
@@ -269,4 +347,74 @@ mod tests {
         ";
         test_parse(t, "let x = { if false { 20; };if true { 2 } else { 3 } };");
     }
+
+    #[test]
+    fn test_parse_if_braceless() {
+        // same AST as with braces
+        test_parse("if (x > 1) 2 else 3", "if (x>1) { 2 } else { 3 }");
+        test_parse(
+            "let y = if (c) f(1) else -1; y",
+            "let y = if c { f(1) } else { (-1) };y",
+        );
+        test_parse("if (c) { 2 } else 3", "if c { 2 } else { 3 }");
+        test_parse("if c { 2 } else 3", "if c { 2 } else { 3 }");
+
+        // statements, which need a semicolon before the next one like any other statement
+        test_parse(
+            "loop { if (x > 3) break; x = x + 1; }",
+            "loop  { if (x>3) { break; };x = (x+1); };",
+        );
+        test_parse("if (c) x = 1; x", "if c { x = 1; };x");
+        test_parse_err("if (c) x = 1 y", "Expected infix operator but got: y", true);
+
+        // the condition goes on if an operator follows the parentheses
+        test_parse("if (a) + 1 > 2 { 3 }", "if ((a+1)>2) { 3 };");
+        test_parse("if (a) as int > 2 { 3 }", "if ((a as int)>2) { 3 };");
+
+        // else if
+        test_parse(
+            "if (a) 1 else if (b) 2 else 3",
+            "if a { 1 } else { if b { 2 } else { 3 } }",
+        );
+        test_parse(
+            "if a { 1 } else if b { 2 } else { 3 }",
+            "if a { 1 } else { if b { 2 } else { 3 } }",
+        );
+    }
+
+    #[test]
+    fn test_parse_if_braceless_precedence() {
+        // the body goes as far as it can
+        test_parse("if (c) 1 else 2 + 3", "if c { 1 } else { (2+3) }");
+        test_parse(
+            "let x = 1 + if (c) 2 else 3;",
+            "let x = (1+if c { 2 } else { 3 });",
+        );
+
+        // a dangling else belongs to the nearest if
+        test_parse("if (a) if (b) 1 else 2;", "if a { if b { 1 } else { 2 } };");
+        test_parse("if (a) if (b) x = 1;", "if a { if b { x = 1; }; };");
+
+        // - continues the condition only if a block follows
+        test_parse("if (c) -1 else 1", "if c { (-1) } else { 1 }");
+        test_parse("if (c) - 1 > 0 { 2 }", "if ((c-1)>0) { 2 };");
+        test_parse("if (c) - 1 else 1", "if c { (-1) } else { 1 }");
+        // without parentheses the condition runs into the body
+        test_parse_err(
+            "if c 1 else 2",
+            "[ParseError]: Expected infix operator but got: 1",
+            false,
+        );
+
+        test_parse_err(
+            "if (c) let x = 1;",
+            "'let x = 1' needs braces around it to be the body of an if",
+            true,
+        );
+        test_parse_err(
+            "if (c) 1 else",
+            "[ParseError]: Unexpected token: 'else'",
+            false,
+        );
+    }
 }
@@ -10,9 +10,7 @@ impl<'inp> Parser<'inp> {
     // Parse let statement
     // let x = 2;
     pub(crate) fn parse_let(&mut self) -> Result<Decl, ParseError> {
-        crate::expect_token_body!(self.lexer.peek(), Ident, "identifier")?;
-        let ident = Parser::string_from_ident(self.lexer.peek());
-        self.advance();
+        let ident = self.consume_name()?;
 
         let mut type_ann: Option<Type> = None;
 
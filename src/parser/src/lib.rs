@@ -1,79 +1,123 @@
+use edition::Edition;
 use lexer::{lex, Token};
 use logos::Lexer;
-use std::iter::Peekable;
 use structs::*;
+use tokens::Tokens;
 
 pub mod blk;
+pub mod edition;
 pub mod expr;
 pub mod fn_decl;
 pub mod ident;
 pub mod if_else;
 pub mod let_stmt;
 pub mod parse_loop;
+pub mod parse_parallel;
+pub mod parse_scope;
+pub mod parse_select;
 pub mod parse_type_ann;
+#[cfg(test)]
+mod round_trip;
 pub mod seq;
 pub mod structs;
+mod tokens;
 
-// To expect token types that have a value inside (for Ident and primitives)
-macro_rules! expect_token_body {
-    ($peek:expr, $token:ident, $expected:expr) => {{
-        let err = Err(ParseError::new(concat!("Expected ", $expected)));
-        let pk = $peek;
-
-        if pk.is_none() {
-            err
-        } else {
-            let pk = pk
-                .expect("Peek has something")
-                .as_ref()
-                .expect("Expect lexer to succeed");
-            match pk {
-                Token::$token(_) => Ok(()),
-                _ => err,
-            }
-        }
-    }};
-}
-
-pub(crate) use expect_token_body;
+/// How deeply expressions, blocks and type annotations can be nested by default. Parsing recurses
+/// for each level, and so do the type checker and compiler, so without a limit a few thousand '('
+/// would overflow the stack instead of failing with an error.
+pub const MAX_NESTING_DEPTH: usize = 100;
 
 pub struct Parser<'inp> {
     prev_tok: Option<Token>,
-    lexer: Peekable<Lexer<'inp, Token>>,
-    pub is_loop: bool,
-    pub is_fn: bool,
+    lexer: Tokens<'inp>,
+    // Number of expressions, blocks and type annotations being parsed inside each other
+    depth: usize,
+    max_depth: usize,
+    edition: Edition,
 }
 
 impl<'inp> Parser<'inp> {
     pub fn new(lexer: Lexer<'_, Token>) -> Parser<'_> {
         Parser {
             prev_tok: None,
-            lexer: lexer.peekable(),
-            is_loop: false,
-            is_fn: false,
+            lexer: Tokens::new(lexer),
+            depth: 0,
+            max_depth: MAX_NESTING_DEPTH,
+            edition: Edition::default(),
         }
     }
 
     pub fn new_from_string(inp: &str) -> Parser<'_> {
         Parser {
             prev_tok: None,
-            lexer: lex(inp).peekable(),
-            is_loop: false,
-            is_fn: false,
+            lexer: Tokens::new(lex(inp)),
+            depth: 0,
+            max_depth: MAX_NESTING_DEPTH,
+            edition: Edition::default(),
+        }
+    }
+
+    /// Fail with an error instead of parsing anything nested more than max_depth levels deep.
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.max_depth = max_depth;
+    }
+
+    /// Parse the program as written for edition, which decides what the keywords are.
+    pub fn set_edition(&mut self, edition: Edition) {
+        self.edition = edition;
+    }
+
+    /// Expect peek to be the name of something being declared, e.g. a variable, and advance past it
+    fn consume_name(&mut self) -> Result<String, ParseError> {
+        match self.lexer.peek() {
+            Some(Ok(Token::Ident(name))) => {
+                let name = name.to_string();
+                self.check_name(&name)?;
+                self.advance();
+                Ok(name)
+            }
+            Some(Ok(tok)) if tok.is_keyword() => Err(ParseError::new(&format!(
+                "'{}' is a keyword and can't be used as a name",
+                tok
+            ))),
+            _ => Err(ParseError::new("Expected identifier")),
+        }
+    }
+
+    /// A name that is a keyword in the edition being parsed is an error
+    fn check_name(&self, name: &str) -> Result<(), ParseError> {
+        if self.edition.is_keyword(name) {
+            return Err(ParseError::new(&format!(
+                "'{}' is a keyword in edition {} and can't be used as a name",
+                name, self.edition
+            )));
         }
+        Ok(())
+    }
+
+    /// Parse one level of nesting deeper with f, failing if that is deeper than max_depth.
+    fn nested<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<T, ParseError>,
+    ) -> Result<T, ParseError> {
+        if self.depth >= self.max_depth {
+            return Err(ParseError::new(&format!(
+                "Expression too deeply nested, the limit is {} levels",
+                self.max_depth
+            )));
+        }
+
+        self.depth += 1;
+        let res = f(self);
+        self.depth -= 1;
+        res
     }
 
     // Check if peek is a specific token type
     fn is_peek_token_type(&mut self, token: Token) -> bool {
-        let pk = self.lexer.peek();
-        if pk.is_none() {
-            false
-        } else {
-            let pk = pk.unwrap();
-            match pk {
-                Ok(prev) => prev.eq(&token),
-                _ => false,
-            }
+        match self.lexer.peek() {
+            Some(Ok(prev)) => prev.eq(&token),
+            _ => false,
         }
     }
 
@@ -124,14 +168,6 @@ impl<'inp> Parser<'inp> {
         }
     }
 
-    // Pass in self.lexer.peek() => get String out for Ident, String in quotes
-    pub(crate) fn string_from_ident(token: Option<&Result<Token, ()>>) -> String {
-        // dbg!("string from ident token:", &token);
-        let tok = token.unwrap();
-        let tok = tok.clone().unwrap();
-        tok.to_string()
-    }
-
     /// Expect one of Ident, (, or fn to start type annotation
     fn expect_token_for_type_ann(token: Option<&Result<Token, ()>>) -> Result<(), ParseError> {
         if let Some(Ok(tok)) = token {
@@ -161,28 +197,45 @@ impl<'inp> Parser<'inp> {
             BinOpType::Mul | BinOpType::Div => (8, 9),
             BinOpType::Add | BinOpType::Sub => (6, 7),
             // no associativity for comparison ops
-            BinOpType::LogicalEq | BinOpType::Gt | BinOpType::Lt => (5, 5),
+            BinOpType::LogicalEq
+            | BinOpType::Gt
+            | BinOpType::Lt
+            | BinOpType::Ge
+            | BinOpType::Le => (5, 5),
             BinOpType::LogicalAnd => (3, 4),
             BinOpType::LogicalOr => (1, 2),
         }
     }
 
-    // Unary negation must have a higher precedence than binops
+    // Unary negation must have a higher precedence than binops and casts: -x as float is (-x) as float
     fn get_prefix_bp(unop: &UnOpType) -> ((), u8) {
         match unop {
-            UnOpType::Negate | UnOpType::Not => ((), 10),
+            UnOpType::Negate | UnOpType::Not | UnOpType::Plus => ((), 11),
         }
     }
 
+    // Postfix cast binds tighter than any binop: a * b as float is a * (b as float)
+    const CAST_BP: u8 = 10;
+
     // Parses and returns a declaration. At this stage "declaration" includes values, let assignments, fn declarations, etc
     // Because treatment of something as an expression can vary based on whether it is last value or not, whether semicolon comes after, etc.
     fn parse_decl(&mut self) -> Result<Decl, ParseError> {
+        // scope { ... } - scope is only a keyword before a block
+        if self.at_scope() {
+            return self.parse_scope();
+        }
+        // select { ... } - likewise
+        if self.at_select() {
+            return self.parse_select();
+        }
+
         let prev_tok = self.expect_prev_tok()?;
         match prev_tok {
             Token::Integer(_)
             | Token::Float(_)
             | Token::Bool(_)
             | Token::Minus
+            | Token::Plus
             | Token::Ident(_)
             | Token::OpenParen
             | Token::Bang
@@ -191,9 +244,21 @@ impl<'inp> Parser<'inp> {
             | Token::String(_) => self.parse_expr(0),
             Token::Spawn => {
                 self.advance();
+
+                // spawn isolated f() - isolated is only a keyword when followed by the function name
+                let isolated = matches!(&self.prev_tok, Some(Token::Ident(id)) if id == "isolated")
+                    && matches!(self.lexer.peek(), Some(Ok(Token::Ident(_))));
+                if isolated {
+                    self.advance();
+                }
+
                 let fn_call = self.parse_expr(0)?.to_expr()?;
                 if let Expr::FnCallExpr(fn_data) = fn_call {
-                    let sp = Expr::SpawnExpr(fn_data);
+                    let sp = if isolated {
+                        Expr::SpawnIsolatedExpr(fn_data)
+                    } else {
+                        Expr::SpawnExpr(fn_data)
+                    };
                     Ok(Decl::ExprStmt(sp))
                 } else {
                     Err(ParseError::new("spawn expected function call"))
@@ -210,39 +275,21 @@ impl<'inp> Parser<'inp> {
                     Err(ParseError::new("join expected variable for thread to join"))
                 }
             }
-            // wait sem;
+            // wait sem; where sem is any expression, the type checker checks it is a semaphore
             Token::Wait => {
                 self.advance();
                 let sem = self.parse_expr(0)?.to_expr()?;
-                if let Expr::Symbol(sem_sym) = sem {
-                    Ok(Decl::WaitStmt(sem_sym))
-                } else {
-                    Err(ParseError::new("wait expected semaphore variable"))
-                }
+                Ok(Decl::WaitStmt(sem))
             }
             Token::Post => {
                 self.advance();
                 let sem = self.parse_expr(0)?.to_expr()?;
-                if let Expr::Symbol(sem_sym) = sem {
-                    Ok(Decl::PostStmt(sem_sym))
-                } else {
-                    Err(ParseError::new("post expected semaphore variable"))
-                }
-            }
-            // if not is_loop, error
-            Token::Break => {
-                if !self.is_loop {
-                    return Err(ParseError::new("break outside of loop"));
-                }
-                Ok(Decl::BreakStmt)
+                Ok(Decl::PostStmt(sem))
             }
+            // placement of break and return is checked by the type checker and compiler
+            Token::Break => Ok(Decl::BreakStmt),
             Token::Yield => Ok(Decl::YieldStmt),
-            // if not is_fn, err
             Token::Return => {
-                if !self.is_fn {
-                    return Err(ParseError::new("return outside of fn"));
-                }
-
                 // parse expr if not semicolon
                 let mut ret_expr: Option<Expr> = None;
                 if !self.is_peek_token_type(Token::Semi) {
@@ -255,6 +302,7 @@ impl<'inp> Parser<'inp> {
             }
             Token::Let => self.parse_let(),
             Token::Loop => self.parse_loop(),
+            Token::At => self.parse_annotated(),
             Token::Fn => self.parse_fn_decl(),
             _ => Err(ParseError::new(&format!(
                 "Unexpected token: '{}'",
@@ -265,7 +313,12 @@ impl<'inp> Parser<'inp> {
 
     // Implicit block
     pub fn parse(mut self) -> Result<BlockSeq, ParseError> {
-        self.parse_seq()
+        let res = self.parse_seq();
+        // input the lexer couldn't lex ends the tokens early, so what was parsed is not the program
+        match self.lexer.take_error() {
+            Some(err) => Err(err),
+            None => res,
+        }
     }
 }
 
@@ -306,6 +359,18 @@ mod tests {
         test_parse(" 20 ;30; \n40 \n ", "20;30;40"); // two exprstmt + expr
     }
 
+    #[test]
+    fn test_parse_lines() {
+        let inp = "let x = 1;\n\n  x = 2; { 3; }\nx";
+        let res = Parser::new_from_string(inp).parse().expect("Should parse");
+        assert_eq!(res.lines, vec![1, 3, 3, 4]);
+
+        let Decl::ExprStmt(Expr::BlockExpr(blk)) = &res.decls[2] else {
+            panic!("Should be a block");
+        };
+        assert_eq!(blk.lines, vec![3]);
+    }
+
     #[test]
     fn test_parse_floats() {
         test_parse(" 2.2\n ", "2.2");
@@ -366,6 +431,22 @@ mod tests {
         ";
         test_parse_err(t, "spawn expected function call", true);
 
+        // isolated
+        let t = r"
+        let t = spawn isolated func(2);
+        spawn isolated();
+        spawn isolated(isolated)
+        ";
+        test_parse(
+            t,
+            "let t = spawn isolated func(2);spawn isolated();spawn isolated(isolated)",
+        );
+
+        let t = r"
+        spawn isolated x;
+        ";
+        test_parse_err(t, "spawn expected function call", true);
+
         // join
         let t = r"
         let t = spawn func();
@@ -381,15 +462,17 @@ mod tests {
         ";
         test_parse(t, "let sem = sem_create();wait sem;post sem;");
 
+        // any expression, the type checker checks it is a semaphore
         let t = r"
-        wait 2+2;
+        wait get_lock();
+        post if first { a } else { b };
         ";
-        test_parse_err(t, "expected semaphore variable", true);
+        test_parse(t, "wait get_lock();post if first { a } else { b };");
 
         let t = r"
-        post 2+2;
+        wait;
         ";
-        test_parse_err(t, "expected semaphore variable", true);
+        test_parse_err(t, "not an expression", true);
 
         // can't assign wait/post
         let t = r"
@@ -423,4 +506,73 @@ mod tests {
         let t = r#"let t = "hello world"; println(t);"#;
         test_parse(t, "let t = hello world;println(t);");
     }
+
+    #[test]
+    fn test_parse_nesting_limit() {
+        let parens = |n: usize| format!("{}1{}", "(".repeat(n), ")".repeat(n));
+        let err = "[ParseError]: Expression too deeply nested, the limit is 100 levels";
+
+        // the program's block is the first level
+        test_parse(&parens(MAX_NESTING_DEPTH - 2), "1");
+        test_parse_err(&parens(MAX_NESTING_DEPTH), err, false);
+        // no stack overflow however deep
+        test_parse_err(&parens(100_000), err, false);
+        test_parse_err(&format!("{}1", "-".repeat(100_000)), err, false);
+        test_parse_err(&"{ ".repeat(100_000), err, false);
+        test_parse_err(
+            &format!("let f : {}int = 1;", "fn() -> ".repeat(100_000)),
+            err,
+            false,
+        );
+
+        // the program, each block and the expression inside it are a level each
+        let mut parser = Parser::new_from_string("{ { 1 } }");
+        parser.set_max_depth(5);
+        assert_eq!(
+            parser.parse().unwrap_err().to_string(),
+            "[ParseError]: Expression too deeply nested, the limit is 5 levels"
+        );
+        let mut parser = Parser::new_from_string("{ { 1 } }");
+        parser.set_max_depth(6);
+        assert!(parser.parse().is_ok());
+    }
+
+    #[test]
+    fn test_parse_float_forms() {
+        test_parse("5. + 5e3 * -1.2e-4", "(5.0+(5000.0*(-0.00012)))");
+        test_parse("let x = 1.0; x", "let x = 1.0;x");
+        // a float is printed so it parses back as a float
+        test_parse("1e300", "1e300");
+    }
+
+    #[test]
+    fn test_parse_lex_errors() {
+        test_parse_err(
+            "let x = 1;\nlet y = 99999999999999999999;",
+            "[ParseError]: Integer literal '99999999999999999999' out of range on line 2",
+            false,
+        );
+        test_parse_err(
+            "1e999",
+            "[ParseError]: Float literal '1e999' out of range on line 1",
+            false,
+        );
+        test_parse_err(
+            "let x = 1.0f;",
+            "[ParseError]: Number literal '1.0f' has a suffix, but literals don't take one: write 1 for an int and 1.0 for a float, or convert with 'as int' or 'as float' on line 1",
+            false,
+        );
+        test_parse_err(
+            "let s = \"abc;",
+            "[ParseError]: Unrecognised token '\"abc;' on line 1",
+            false,
+        );
+        // the input ends at the error, so what comes before parsing fine doesn't make it a program
+        test_parse_err(
+            "1 + 2 `",
+            "[ParseError]: Unrecognised token '`' on line 1",
+            false,
+        );
+        test_parse_err("#!/bin/ignite\n1 #!", "Unrecognised token '#!'", true);
+    }
 }
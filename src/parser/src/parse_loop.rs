@@ -34,15 +34,7 @@ impl<'inp> Parser<'inp> {
     /*
 
     */
-    // Ensure is_loop flag is saved and restored as long as valid return. Error crashes the whole parser so it's fine
     pub(crate) fn parse_loop(&mut self) -> Result<Decl, ParseError> {
-        let prev_is_loop = self.is_loop;
-        let lp = self.parse_loop_inner()?;
-        self.is_loop = prev_is_loop;
-        Ok(lp)
-    }
-
-    fn parse_loop_inner(&mut self) -> Result<Decl, ParseError> {
         // If token not consumed (no open paren), advance so first token of expr goes into prev_tok
         // allows loop (x < 3) - condition in brackets
         if !self.consume_opt_token_type(Token::OpenParen) {
@@ -51,10 +43,6 @@ impl<'inp> Parser<'inp> {
 
         // dbg!("prev_tok after loop:", &self.prev_tok);
 
-        // save and restore prev is_loop e.g when coming from global
-        let prev_is_loop = self.is_loop;
-        self.is_loop = true;
-
         let cond = self.parse_expr(0)?.to_expr()?;
 
         // If the thing we parsed is a block, this is a loop with just a body and no cond
@@ -83,8 +71,6 @@ impl<'inp> Parser<'inp> {
             body: loop_blk,
         };
 
-        self.is_loop = prev_is_loop;
-
         Ok(Decl::LoopStmt(lp))
     }
 }
@@ -225,51 +211,9 @@ mod tests {
         ";
         test_parse_err(t, "Expected semicolon", true);
 
-        // break not allowed outside  loop
-        let t = r"
-        break;
-        ";
-        test_parse_err(t, "break outside of loop", true);
-
-        let t = r"
-        {
-            break;
-        }
-        ";
-        test_parse_err(t, "break outside of loop", true);
-
-        let t = r"
-        if true {
-            break;
-        }
-        ";
-        test_parse_err(t, "break outside of loop", true);
-
-        let t = r"
-        if true {
-            2;
-        } else {
-            break;
-        }
-        ";
-        test_parse_err(t, "break outside of loop", true);
-
-        let t = r"
-        loop {
-            let x = 0;
-            loop {
-                break;
-            }
-            break;
-        }
-        {
-            loop {
-                break;
-            }
-            break;
-        };
-        ";
-        test_parse_err(t, "break outside of loop", true);
+        // placement is checked after parsing
+        test_parse("break;", "break;");
+        test_parse("if true { break; }", "if true { break; };");
     }
 
     #[test]
@@ -292,14 +236,6 @@ mod tests {
         ";
         test_parse(t, "loop (x<5) { if (x==3) { break; } else { 30; } };");
 
-        let t = r"
-        loop {
-            break;
-        }
-        break;
-        ";
-        test_parse_err(t, "break outside of loop", true);
-
         // nested
         let t = r"
         loop {
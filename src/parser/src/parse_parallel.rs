@@ -0,0 +1,112 @@
+use lexer::Token;
+
+use crate::structs::ParallelLoopData;
+use crate::Decl;
+use crate::ParseError;
+use crate::Parser;
+
+/*
+@parallel loop i in 0..n {
+    matrix_set(m, i, 0, f(i));
+}
+*/
+impl<'inp> Parser<'inp> {
+    /// Parse an annotated statement, after the '@'. The only annotation is @parallel, on a loop
+    /// over a range of ints, whose iterations run in threads of their own. The type checker and
+    /// compiler check its iterations can run at the same time, and the compiler lowers it to
+    /// spawns and joins.
+    pub(crate) fn parse_annotated(&mut self) -> Result<Decl, ParseError> {
+        match self.lexer.peek() {
+            Some(Ok(Token::Ident(name))) if name == "parallel" => self.advance(),
+            Some(Ok(tok)) => {
+                let e = format!("Unknown annotation '@{}', the only one is @parallel", tok);
+                return Err(ParseError::new(&e));
+            }
+            _ => return Err(ParseError::new("Expected annotation after '@'")),
+        }
+
+        self.consume_token_type(Token::Loop, "Expected loop after @parallel")?;
+        let var = self.consume_name()?;
+        match self.lexer.peek() {
+            Some(Ok(Token::Ident(kw))) if kw == "in" => self.advance(),
+            _ => {
+                return Err(ParseError::new(
+                    "Expected 'in' after the loop variable, as in '@parallel loop i in 0..n { }'",
+                ))
+            }
+        }
+
+        self.advance();
+        let start = self.parse_expr(0)?.to_expr()?;
+        self.consume_token_type(
+            Token::DotDot,
+            &format!(
+                "Expected {} between the start and end of the range",
+                Token::DotDot
+            ),
+        )?;
+        self.advance();
+        let end = self.parse_expr(0)?.to_expr()?;
+
+        self.consume_token_type(
+            Token::OpenBrace,
+            &format!("Expected {} for loop block", Token::OpenBrace),
+        )?;
+        let body = self.parse_blk()?.to_block()?;
+
+        Ok(Decl::ParallelLoopStmt(ParallelLoopData {
+            var,
+            start,
+            end,
+            body,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::{test_parse, test_parse_err};
+
+    #[test]
+    fn test_parse_parallel_loop() {
+        test_parse(
+            "@parallel loop i in 0..n { f(i); }",
+            "@parallel loop i in 0..n { f(i); };",
+        );
+
+        // variables, loops and functions of the body's own, and a statement after the loop
+        let t = r"
+        @parallel loop i in lo + 1..2 * hi {
+            let x = 0;
+            loop x < i {
+                x = x + 1;
+                if x == 3 { break; }
+            }
+            fn g(y: int) -> int { y = y + 1; return y; }
+            i = g(x);
+        }
+        2
+        ";
+        test_parse(t, "@parallel loop i in (lo+1)..(2*hi) { let x = 0;loop (x<i) { x = (x+1);if (x==3) { break; }; };fn g (y:int) -> int { y = (y+1);return y; };i = g(x); };2");
+    }
+
+    #[test]
+    fn test_parse_parallel_loop_err() {
+        test_parse_err(
+            "@fast loop i in 0..10 {}",
+            "Unknown annotation '@fast', the only one is @parallel",
+            true,
+        );
+        test_parse_err("@parallel { 2; }", "Expected loop after @parallel", true);
+        test_parse_err(
+            "@parallel loop i < 10 {}",
+            "Expected 'in' after the loop variable",
+            true,
+        );
+        test_parse_err(
+            "@parallel loop i in 10 {}",
+            "Expected .. between the start and end of the range",
+            true,
+        );
+    }
+}
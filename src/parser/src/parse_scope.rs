@@ -0,0 +1,56 @@
+use lexer::Token;
+
+use crate::Decl;
+use crate::ParseError;
+use crate::Parser;
+
+/// Word that starts a scope block. It is only a keyword when followed by the block, so it can still
+/// be used as a name.
+pub const SCOPE_KEYWORD: &str = "scope";
+
+/*
+scope {
+    spawn f(1);
+    spawn f(2);
+}
+*/
+impl<'inp> Parser<'inp> {
+    /// Whether the previous token starts a scope block: scope followed by '{'
+    pub(crate) fn at_scope(&mut self) -> bool {
+        matches!(&self.prev_tok, Some(Token::Ident(id)) if id == SCOPE_KEYWORD)
+            && self.is_peek_token_type(Token::OpenBrace)
+    }
+
+    /// Parse a scope block, after 'scope'. Every thread spawned while the block runs is joined at
+    /// its end, so none outlives it. Like a loop it is only a statement.
+    pub(crate) fn parse_scope(&mut self) -> Result<Decl, ParseError> {
+        self.consume_token_type(
+            Token::OpenBrace,
+            &format!("Expected {} for scope block", Token::OpenBrace),
+        )?;
+        let body = self.parse_blk()?.to_block()?;
+        Ok(Decl::ScopeStmt(body))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::{test_parse, test_parse_err};
+
+    #[test]
+    fn test_parse_scope() {
+        test_parse(
+            "scope { spawn f(1); spawn f(2); } 3",
+            "scope { spawn f(1);spawn f(2); };3",
+        );
+        test_parse(
+            "fn g() { scope { let t = spawn f(); join t; } }",
+            "fn g () { scope { let t = spawn f();join t; }; };",
+        );
+
+        // scope is a name when no block follows
+        test_parse("let scope = 2; scope + 1", "let scope = 2;(scope+1)");
+
+        test_parse_err("let x = scope { 2 };", "scope is not an expression", true);
+    }
+}
@@ -0,0 +1,170 @@
+use lexer::Token;
+
+use crate::structs::{QueueOp, SelectArm, SelectData};
+use crate::BlockSeq;
+use crate::Decl;
+use crate::Expr;
+use crate::ParseError;
+use crate::Parser;
+
+/// Word that starts a select. Like scope it is only a keyword when followed by the block.
+pub const SELECT_KEYWORD: &str = "select";
+
+/// Word that starts the arm a select runs when no other arm is ready
+pub const DEFAULT_ARM: &str = "default";
+
+/*
+select {
+    let x = queue_pop(q1) => { println(x); }
+    queue_push(q2, 1) => { println("sent"); }
+    default => { println("nothing ready"); }
+}
+*/
+impl<'inp> Parser<'inp> {
+    /// Whether the previous token starts a select: select followed by '{'
+    pub(crate) fn at_select(&mut self) -> bool {
+        matches!(&self.prev_tok, Some(Token::Ident(id)) if id == SELECT_KEYWORD)
+            && self.is_peek_token_type(Token::OpenBrace)
+    }
+
+    /// Parse a select, after 'select'. Each arm is a queue_pop or queue_push, and the select runs
+    /// the body of the first arm whose operation can be done, waiting until one can unless there
+    /// is a default arm, which must come last. Like a loop it is only a statement.
+    pub(crate) fn parse_select(&mut self) -> Result<Decl, ParseError> {
+        self.consume_token_type(
+            Token::OpenBrace,
+            &format!("Expected {} for select block", Token::OpenBrace),
+        )?;
+
+        let mut arms = vec![];
+        let mut default = None;
+        while !self.consume_opt_token_type(Token::CloseBrace) {
+            if self.lexer.peek().is_none() {
+                let e = format!("Expected '{}' to close select", Token::CloseBrace);
+                return Err(ParseError::new(&e));
+            }
+            if default.is_some() {
+                return Err(ParseError::new(
+                    "The default arm must be the last arm of a select",
+                ));
+            }
+
+            if matches!(self.lexer.peek(), Some(Ok(Token::Ident(id))) if id == DEFAULT_ARM) {
+                self.advance();
+                default = Some(self.parse_select_body()?);
+                continue;
+            }
+
+            let binding = if self.consume_opt_token_type(Token::Let) {
+                let name = self.consume_name()?;
+                self.consume_token_type(Token::Eq, "Expected '='")?;
+                Some(name)
+            } else {
+                None
+            };
+
+            self.advance();
+            let op = queue_op(self.parse_expr(0)?.to_expr()?)?;
+            if binding.is_some() {
+                if let QueueOp::Push(..) = op {
+                    return Err(ParseError::new(
+                        "Only a queue_pop arm of a select can bind a value with let",
+                    ));
+                }
+            }
+
+            let body = self.parse_select_body()?;
+            arms.push(SelectArm { op, binding, body });
+        }
+
+        if arms.is_empty() {
+            return Err(ParseError::new(
+                "A select needs at least one queue_pop or queue_push arm",
+            ));
+        }
+
+        Ok(Decl::SelectStmt(SelectData { arms, default }))
+    }
+
+    /// Parse '=> { .. }', the body of a select arm
+    fn parse_select_body(&mut self) -> Result<BlockSeq, ParseError> {
+        self.consume_token_type(
+            Token::FatArrow,
+            &format!(
+                "Expected {} before the body of a select arm",
+                Token::FatArrow
+            ),
+        )?;
+        self.consume_token_type(
+            Token::OpenBrace,
+            &format!("Expected {} for select arm block", Token::OpenBrace),
+        )?;
+        self.parse_blk()?.to_block()
+    }
+}
+
+/// The queue operation a select arm waits on, from the call written for it
+fn queue_op(call: Expr) -> Result<QueueOp, ParseError> {
+    if let Expr::FnCallExpr(data) = &call {
+        if data.named_args.is_empty() {
+            match (data.name.as_str(), data.args.as_slice()) {
+                ("queue_pop", [q]) => return Ok(QueueOp::Pop(q.clone())),
+                ("queue_push", [q, v]) => return Ok(QueueOp::Push(q.clone(), v.clone())),
+                _ => (),
+            }
+        }
+    }
+
+    let e = format!(
+        "A select arm must be queue_pop(q) or queue_push(q, v), got '{}'",
+        call
+    );
+    Err(ParseError::new(&e))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::{test_parse, test_parse_err};
+
+    #[test]
+    fn test_parse_select() {
+        test_parse(
+            "select { let x = queue_pop(q) => { x } queue_push(r, 1) => { 2; } } 3",
+            "select { let x = queue_pop(q) => { x } queue_push(r,1) => { 2; } };3",
+        );
+        test_parse(
+            "fn f() { select { queue_pop(q) => {} default => { yield; } } }",
+            "fn f () { select { queue_pop(q) => {  } default => { yield; } }; };",
+        );
+
+        // select and default are names when not used for a select
+        test_parse(
+            "let select = 2; let default = select; default",
+            "let select = 2;let default = select;default",
+        );
+
+        test_parse_err(
+            "let x = select { queue_pop(q) => {} };",
+            "select is not an expression",
+            true,
+        );
+        test_parse_err(
+            "select { f(q) => {} }",
+            "must be queue_pop(q) or queue_push(q, v), got 'f(q)'",
+            true,
+        );
+        test_parse_err(
+            "select { let x = queue_push(q, 1) => {} }",
+            "Only a queue_pop arm",
+            true,
+        );
+        test_parse_err(
+            "select { default => {} queue_pop(q) => {} }",
+            "The default arm must be the last arm",
+            true,
+        );
+        test_parse_err("select { default => {} }", "needs at least one", true);
+        test_parse_err("select { queue_pop(q) {} }", "Expected => before", true);
+        test_parse_err("select { queue_pop(q) => {}", "to close select", true);
+    }
+}
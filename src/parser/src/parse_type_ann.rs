@@ -9,6 +9,10 @@ impl<'inp> Parser<'inp> {
     // Should only consume tokens belonging to the annotation, starting peek at first token and ending
     // peek at token AFTER the last token of type annotation
     pub(crate) fn parse_type_annotation(&mut self) -> Result<Type, ParseError> {
+        self.nested(|parser| parser.parse_type_annotation_inner())
+    }
+
+    fn parse_type_annotation_inner(&mut self) -> Result<Type, ParseError> {
         // self.consume_token_type(Token::Colon, "Expected a colon")?;
         // expect_token_body!(self.lexer.peek(), Ident, "identifier")?;
         Parser::expect_token_for_type_ann(self.lexer.peek())?;
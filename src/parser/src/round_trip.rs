@@ -0,0 +1,181 @@
+//! Property tests for the parser on generated programs.
+//!
+//! Expressions print fully parenthesised, so an expression printed with only the parentheses that
+//! precedence and associativity need must parse back to the tree it was printed from. Whole programs
+//! printed through Display must parse back to a program that prints the same.
+
+use proptest::prelude::*;
+
+use crate::structs::{BinOpType, Expr, UnOpType};
+use crate::Parser;
+
+fn sym(names: &'static [&'static str]) -> impl Strategy<Value = Expr> {
+    prop::sample::select(names).prop_map(|name| Expr::Symbol(name.to_string()))
+}
+
+fn bin(op: BinOpType, lhs: Expr, rhs: Expr) -> Expr {
+    Expr::BinOpExpr(op, Box::new(lhs), Box::new(rhs))
+}
+
+fn int_expr() -> impl Strategy<Value = Expr> {
+    let leaf = prop_oneof![(0..100i64).prop_map(Expr::Integer), sym(&["x", "y"])];
+    let ops = [
+        BinOpType::Add,
+        BinOpType::Sub,
+        BinOpType::Mul,
+        BinOpType::Div,
+    ];
+
+    leaf.prop_recursive(4, 32, 2, move |inner| {
+        prop_oneof![
+            inner
+                .clone()
+                .prop_map(|e| Expr::UnOpExpr(UnOpType::Negate, Box::new(e))),
+            inner
+                .clone()
+                .prop_map(|e| Expr::UnOpExpr(UnOpType::Plus, Box::new(e))),
+            (prop::sample::select(ops.to_vec()), inner.clone(), inner)
+                .prop_map(|(op, lhs, rhs)| bin(op, lhs, rhs)),
+        ]
+    })
+}
+
+fn bool_expr() -> impl Strategy<Value = Expr> {
+    let cmp_ops = [
+        BinOpType::Lt,
+        BinOpType::Gt,
+        BinOpType::Le,
+        BinOpType::Ge,
+        BinOpType::LogicalEq,
+    ];
+    let leaf = prop_oneof![
+        any::<bool>().prop_map(Expr::Bool),
+        sym(&["b"]),
+        (
+            prop::sample::select(cmp_ops.to_vec()),
+            int_expr(),
+            int_expr()
+        )
+            .prop_map(|(op, lhs, rhs)| bin(op, lhs, rhs)),
+    ];
+    let ops = [BinOpType::LogicalAnd, BinOpType::LogicalOr];
+
+    leaf.prop_recursive(3, 16, 2, move |inner| {
+        prop_oneof![
+            inner
+                .clone()
+                .prop_map(|e| Expr::UnOpExpr(UnOpType::Not, Box::new(e))),
+            (prop::sample::select(ops.to_vec()), inner.clone(), inner)
+                .prop_map(|(op, lhs, rhs)| bin(op, lhs, rhs)),
+        ]
+    })
+}
+
+/// How tightly an expression binds when printed without parentheses, higher binds tighter
+fn prec(expr: &Expr) -> u8 {
+    match expr {
+        Expr::BinOpExpr(op, _, _) => match op {
+            BinOpType::LogicalOr => 1,
+            BinOpType::LogicalAnd => 2,
+            BinOpType::Gt
+            | BinOpType::Lt
+            | BinOpType::Ge
+            | BinOpType::Le
+            | BinOpType::LogicalEq => 3,
+            BinOpType::Add | BinOpType::Sub => 4,
+            BinOpType::Mul | BinOpType::Div => 5,
+        },
+        Expr::UnOpExpr(_, _) => 6,
+        _ => 7,
+    }
+}
+
+/// Print with the fewest parentheses that keep the meaning: binary operators are left associative
+fn print_min(expr: &Expr) -> String {
+    let wrap = |child: &Expr, parens: bool| {
+        if parens {
+            format!("({})", print_min(child))
+        } else {
+            print_min(child)
+        }
+    };
+
+    match expr {
+        Expr::UnOpExpr(op, operand) => format!("{}{}", op, wrap(operand, prec(operand) < 6)),
+        Expr::BinOpExpr(op, lhs, rhs) => {
+            let p = prec(expr);
+            format!(
+                "{} {} {}",
+                wrap(lhs, prec(lhs) < p),
+                op,
+                wrap(rhs, prec(rhs) <= p)
+            )
+        }
+        _ => expr.to_string(),
+    }
+}
+
+/// Source for a statement using the generated expressions
+fn stmt() -> impl Strategy<Value = String> {
+    let simple = prop_oneof![
+        int_expr().prop_map(|e| format!("let x = {};", print_min(&e))),
+        bool_expr().prop_map(|e| format!("let b : bool = {};", print_min(&e))),
+        int_expr().prop_map(|e| format!("y = {};", print_min(&e))),
+        int_expr().prop_map(|e| format!("f({}, x);", print_min(&e))),
+    ];
+
+    simple.prop_recursive(2, 12, 3, |inner| {
+        let body = prop::collection::vec(inner, 0..3).prop_map(|stmts| stmts.join(" "));
+        prop_oneof![
+            (bool_expr(), body.clone()).prop_map(|(cond, body)| format!(
+                "if {} {{ {} }}",
+                print_min(&cond),
+                body
+            )),
+            (bool_expr(), body.clone(), body.clone()).prop_map(|(cond, if_blk, else_blk)| {
+                format!(
+                    "if {} {{ {} }} else {{ {} }}",
+                    print_min(&cond),
+                    if_blk,
+                    else_blk
+                )
+            }),
+            (bool_expr(), body.clone()).prop_map(|(cond, body)| format!(
+                "loop {} {{ {} break; }}",
+                print_min(&cond),
+                body
+            )),
+            body.prop_map(|body| format!("{{ {} }}", body)),
+        ]
+    })
+}
+
+fn parse(src: &str) -> String {
+    Parser::new_from_string(src)
+        .parse()
+        .unwrap_or_else(|err| panic!("'{}' should parse: {}", src, err))
+        .to_string()
+}
+
+proptest! {
+    #[test]
+    fn prop_parse_respects_precedence(expr in prop_oneof![int_expr(), bool_expr()]) {
+        prop_assert_eq!(parse(&print_min(&expr)), expr.to_string());
+    }
+
+    #[test]
+    fn prop_parse_display_round_trip(
+        stmts in prop::collection::vec(stmt(), 0..5),
+        last in prop::option::of(int_expr())
+    ) {
+        let mut src = stmts.join(" ");
+        // starts with a name: after an if or a block, a leading - would be read as subtraction
+        if let Some(last) = last {
+            let last = bin(BinOpType::Add, Expr::Symbol("x".to_string()), last);
+            src.push_str(&format!(" {}", print_min(&last)));
+        }
+
+        let printed = parse(&src);
+        prop_assert_eq!(parse(&printed), printed);
+    }
+}
@@ -8,9 +8,14 @@ use std::rc::Rc;
 
 impl<'inp> Parser<'inp> {
     pub(crate) fn parse_seq(&mut self) -> Result<BlockSeq, ParseError> {
+        self.nested(|parser| parser.parse_seq_inner())
+    }
+
+    fn parse_seq_inner(&mut self) -> Result<BlockSeq, ParseError> {
         let mut decls: Vec<Decl> = vec![];
         let mut symbols: Vec<String> = vec![];
         let mut last_expr: Option<Expr> = None;
+        let mut lines: Vec<usize> = vec![];
 
         while self.lexer.peek().is_some() {
             // parsing a block: break so parse_blk can consume CloseBrace
@@ -18,6 +23,7 @@ impl<'inp> Parser<'inp> {
                 break;
             }
 
+            let line = self.lexer.line();
             self.advance();
             // dbg!("prev_tok:", &self.prev_tok);
 
@@ -38,6 +44,7 @@ impl<'inp> Parser<'inp> {
                 }
 
                 decls.push(expr);
+                lines.push(line);
 
                 self.advance();
                 continue;
@@ -48,6 +55,7 @@ impl<'inp> Parser<'inp> {
                 let to_expr = expr.to_expr();
                 if to_expr.is_ok() {
                     last_expr.replace(to_expr?);
+                    lines.push(line);
                     break;
                 }
             }
@@ -61,6 +69,7 @@ impl<'inp> Parser<'inp> {
                 .unwrap_or(false)
             {
                 decls.push(expr);
+                lines.push(line);
             }
             // Syntax error
             else {
@@ -72,6 +81,7 @@ impl<'inp> Parser<'inp> {
             decls,
             last_expr: last_expr.map(Rc::new),
             symbols,
+            lines,
         })
     }
 }
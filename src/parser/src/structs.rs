@@ -1,9 +1,11 @@
 use std::fmt::{Display, Formatter};
 use std::rc::Rc;
 
+use diagnostics::{Diagnostic, Stage, ToDiagnostics, PARSE_ERROR};
 use lexer::Token;
+use serde::Serialize;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum BinOpType {
     Add,
     Sub,
@@ -11,6 +13,8 @@ pub enum BinOpType {
     Div,
     Gt,
     Lt,
+    Ge,
+    Le,
     LogicalEq,
     LogicalAnd,
     LogicalOr,
@@ -25,6 +29,8 @@ impl BinOpType {
             Token::Slash => Ok(Self::Div),
             Token::Gt => Ok(Self::Gt),
             Token::Lt => Ok(Self::Lt),
+            Token::Ge => Ok(Self::Ge),
+            Token::Le => Ok(Self::Le),
             Token::LogEq => Ok(Self::LogicalEq),
             Token::LogAnd => Ok(Self::LogicalAnd),
             Token::LogOr => Ok(Self::LogicalOr),
@@ -45,6 +51,8 @@ impl Display for BinOpType {
             BinOpType::Div => "/",
             BinOpType::Lt => "<",
             BinOpType::Gt => ">",
+            BinOpType::Le => "<=",
+            BinOpType::Ge => ">=",
             BinOpType::LogicalEq => "==",
             BinOpType::LogicalAnd => "&&",
             BinOpType::LogicalOr => "||",
@@ -53,10 +61,12 @@ impl Display for BinOpType {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum UnOpType {
     Negate,
     Not,
+    /// +x, which is x: only there for symmetry with -x
+    Plus,
 }
 
 impl Display for UnOpType {
@@ -64,22 +74,31 @@ impl Display for UnOpType {
         let chr = match self {
             Self::Negate => "-",
             Self::Not => "!",
+            Self::Plus => "+",
         };
 
         write!(f, "{}", chr)
     }
 }
 
+// name: expr argument in a call
+pub type NamedArg = (String, Expr);
+
 // Function call
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct FnCallData {
     pub name: String,
     pub args: Vec<Expr>,
+    // name: expr arguments after the positional ones, mapped to parameters before type checking
+    pub named_args: Vec<NamedArg>,
 }
 
 impl Display for FnCallData {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let args: Vec<String> = self.args.iter().map(|x| x.to_string()).collect();
+        let mut args: Vec<String> = self.args.iter().map(|x| x.to_string()).collect();
+        for (name, arg) in self.named_args.iter() {
+            args.push(format!("{}: {}", name, arg));
+        }
         let args = args.join(",");
 
         let s = format!("{}({})", self.name, args);
@@ -89,7 +108,7 @@ impl Display for FnCallData {
 }
 
 // Different from bytecode Value because values on op stack might be different (e.g fn call)
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum Expr {
     Symbol(String),
     Integer(i64),
@@ -101,7 +120,13 @@ pub enum Expr {
     BlockExpr(BlockSeq), // expr can be a block
     IfElseExpr(Box<IfElseData>),
     FnCallExpr(FnCallData),
+    // Call of a callee that isn't a plain name e.g get_fn()(3)
+    CallExpr(Box<Expr>, Vec<Expr>),
+    // expr as ty
+    CastExpr(Box<Expr>, Type),
     SpawnExpr(FnCallData),
+    // spawn isolated f(): child runs on a snapshot of the parent's environment
+    SpawnIsolatedExpr(FnCallData),
     // Because join can return something so must be able to assign to it
     // String is the symbol of the thread id to join
     JoinExpr(String),
@@ -111,7 +136,7 @@ impl Display for Expr {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let string = match self {
             Expr::Integer(val) => val.to_string(),
-            Expr::Float(val) => val.to_string(),
+            Expr::Float(val) => format!("{:?}", val),
             Expr::Bool(val) => val.to_string(),
             Expr::UnOpExpr(op, expr) => {
                 format!("({}{})", op, expr)
@@ -124,7 +149,13 @@ impl Display for Expr {
             // Expr::BlockExpr(seq) => seq.to_string(),
             Expr::IfElseExpr(expr) => expr.to_string(),
             Expr::FnCallExpr(expr) => expr.to_string(),
+            Expr::CallExpr(callee, args) => {
+                let args: Vec<String> = args.iter().map(|x| x.to_string()).collect();
+                format!("({})({})", callee, args.join(","))
+            }
+            Expr::CastExpr(expr, ty) => format!("({} as {})", expr, ty),
             Expr::SpawnExpr(expr) => format!("spawn {}", expr),
+            Expr::SpawnIsolatedExpr(expr) => format!("spawn isolated {}", expr),
             Expr::JoinExpr(sym) => format!("join {}", sym),
             Expr::StringLiteral(str) => str.to_string(),
         };
@@ -133,14 +164,14 @@ impl Display for Expr {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct LetStmtData {
     pub ident: String,
     pub expr: Expr,
     pub type_ann: Option<Type>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct AssignStmtData {
     pub ident: String,
     pub expr: Expr,
@@ -164,7 +195,7 @@ impl Display for AssignStmtData {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct IfElseData {
     pub cond: Expr,
     pub if_blk: BlockSeq,
@@ -183,7 +214,7 @@ impl Display for IfElseData {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct LoopData {
     pub cond: Option<Expr>,
     pub body: BlockSeq,
@@ -201,27 +232,112 @@ impl Display for LoopData {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+// @parallel loop var in start..end { body }
+#[derive(Debug, Clone, Serialize)]
+pub struct ParallelLoopData {
+    pub var: String,
+    pub start: Expr,
+    pub end: Expr,
+    pub body: BlockSeq,
+}
+
+impl Display for ParallelLoopData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "@parallel loop {} in {}..{} {{ {} }}",
+            self.var, self.start, self.end, self.body
+        )
+    }
+}
+
+// The queue operations a select arm can wait on
+#[derive(Debug, Clone, Serialize)]
+pub enum QueueOp {
+    Pop(Expr),        // queue_pop(q)
+    Push(Expr, Expr), // queue_push(q, v)
+}
+
+impl QueueOp {
+    /// The queue, and for a push the value, in the order they are evaluated
+    pub fn operands(&self) -> Vec<&Expr> {
+        match self {
+            QueueOp::Pop(q) => vec![q],
+            QueueOp::Push(q, v) => vec![q, v],
+        }
+    }
+}
+
+impl Display for QueueOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QueueOp::Pop(q) => write!(f, "queue_pop({})", q),
+            QueueOp::Push(q, v) => write!(f, "queue_push({},{})", q, v),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SelectArm {
+    pub op: QueueOp,
+    // let x = queue_pop(q) => { .. }: the popped value is bound to x in the body
+    pub binding: Option<String>,
+    pub body: BlockSeq,
+}
+
+impl Display for SelectArm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(binding) = &self.binding {
+            write!(f, "let {} = ", binding)?;
+        }
+        write!(f, "{} => {{ {} }}", self.op, self.body)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SelectData {
+    pub arms: Vec<SelectArm>,
+    // run when no arm's operation can be done straight away, instead of waiting
+    pub default: Option<BlockSeq>,
+}
+
+impl Display for SelectData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut arms: Vec<String> = self.arms.iter().map(|arm| arm.to_string()).collect();
+        if let Some(default) = &self.default {
+            arms.push(format!("default => {{ {} }}", default));
+        }
+        write!(f, "select {{ {} }}", arms.join(" "))
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
 // function parameter
 pub struct FnParam {
     pub name: String,
     pub type_ann: Option<Type>,
+    // Literal used when a call leaves the parameter out
+    pub default: Option<Expr>,
 }
 
 impl Display for FnParam {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let param_str = if let Some(ty) = &self.type_ann {
+        let mut param_str = if let Some(ty) = &self.type_ann {
             format!("{}:{}", self.name, ty)
         } else {
             self.name.to_string()
         };
 
+        if let Some(default) = &self.default {
+            param_str.push_str(&format!(" = {}", default));
+        }
+
         write!(f, "{}", param_str)
     }
 }
 
 // Fn Decl
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct FnDeclData {
     pub name: String,
     pub params: Vec<FnParam>,
@@ -249,7 +365,7 @@ impl Display for FnDeclData {
 }
 
 // Later: LetStmt, IfStmt, FnDef, etc.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum Decl {
     LetStmt(LetStmtData),
     AssignStmt(AssignStmtData),
@@ -258,17 +374,23 @@ pub enum Decl {
     IfOnlyStmt(IfElseData),
     // loop is always a stmt (for now)
     LoopStmt(LoopData),
+    // @parallel loop i in 0..n { .. } - stmt only, its iterations run in threads of their own
+    ParallelLoopStmt(ParallelLoopData),
     FnDeclStmt(FnDeclData),
     // only inside loop
     BreakStmt,
     // only inside fn
     ReturnStmt(Option<Expr>),
-    // wait sem; - stmt only
-    WaitStmt(String),
+    // wait sem; - stmt only, sem is any expression evaluating to a semaphore
+    WaitStmt(Expr),
     // post sem; - stmt only
-    PostStmt(String),
+    PostStmt(Expr),
     // yield; - no args
     YieldStmt,
+    // scope { ... } - stmt only, joins the threads spawned in the block at its end
+    ScopeStmt(BlockSeq),
+    // select { queue_pop(q) => { .. } default => { .. } } - stmt only
+    SelectStmt(SelectData),
 }
 
 impl Decl {
@@ -290,11 +412,16 @@ impl Decl {
                 Err(ParseError::new("Function declaration is not an expression"))
             }
             Self::LoopStmt(_) => Err(ParseError::new("loop is not an expression")),
+            Self::ParallelLoopStmt(_) => {
+                Err(ParseError::new("@parallel loop is not an expression"))
+            }
             Self::BreakStmt => Err(ParseError::new("break is not an expression")),
             Self::ReturnStmt(_) => Err(ParseError::new("return is not an expression")),
             Self::WaitStmt(_) => Err(ParseError::new("wait is not an expression")),
             Self::PostStmt(_) => Err(ParseError::new("post is not an expression")),
             Self::YieldStmt => Err(ParseError::new("yield is not an expression")),
+            Self::ScopeStmt(_) => Err(ParseError::new("scope is not an expression")),
+            Self::SelectStmt(_) => Err(ParseError::new("select is not an expression")),
             Self::ExprStmt(expr) => Ok(expr.clone()),
         }
     }
@@ -323,6 +450,7 @@ impl Display for Decl {
             Decl::AssignStmt(stmt) => stmt.to_string(),
             Decl::IfOnlyStmt(expr) => expr.to_string(),
             Decl::LoopStmt(lp) => lp.to_string(),
+            Decl::ParallelLoopStmt(lp) => lp.to_string(),
             Decl::BreakStmt => Token::Break.to_string(),
             Decl::FnDeclStmt(fn_decl) => fn_decl.to_string(),
             Decl::ReturnStmt(expr) => {
@@ -340,6 +468,8 @@ impl Display for Decl {
             Decl::WaitStmt(sym) => format!("wait {}", sym),
             Decl::PostStmt(sym) => format!("post {}", sym),
             Decl::YieldStmt => "yield".to_string(),
+            Decl::ScopeStmt(blk) => format!("scope {{ {} }}", blk),
+            Decl::SelectStmt(select) => select.to_string(),
         };
 
         write!(f, "{}", string)
@@ -348,12 +478,15 @@ impl Display for Decl {
 
 // Last expression is value of program semantics (else Unit type)
 // Program is either one declaration or a sequence of declarations with optional last expression
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct BlockSeq {
     pub decls: Vec<Decl>,
     pub last_expr: Option<Rc<Expr>>,
     // List of top level uninitialised symbols (variable/func declarations)
     pub symbols: Vec<String>,
+    // Line each decl starts on, followed by the line of last_expr if there is one
+    #[serde(skip)]
+    pub lines: Vec<usize>,
 }
 
 impl Display for BlockSeq {
@@ -372,7 +505,7 @@ impl Display for BlockSeq {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct ParseError {
     msg: String,
 }
@@ -394,9 +527,15 @@ impl Display for ParseError {
 // automatic due to Display
 impl std::error::Error for ParseError {}
 
+impl ToDiagnostics for ParseError {
+    fn to_diagnostics(&self) -> Vec<Diagnostic> {
+        vec![Diagnostic::error(Stage::Parse, PARSE_ERROR, &self.msg)]
+    }
+}
+
 // Type of a function value - subset of FnDeclData
 // Params: care only about types not names
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct FnTypeData {
     pub params: Vec<Type>,
     pub ret_type: Type,
@@ -430,7 +569,7 @@ impl Display for FnTypeData {
 }
 
 // Type annotation corresponding to compile time types
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub enum Type {
     Int,
     Float,
@@ -440,6 +579,8 @@ pub enum Type {
     BuiltInFn, // type checking done separately since it can be polymorphic unlike user fn
     ThreadId,  // result of spawn
     Semaphore,
+    Queue,       // queue of ints shared between threads
+    Matrix,      // matrix of floats, shared like a queue
     Unit,        // void type like Rust
     Unitialised, // Type for variables that exist in a block but not yet declared - only used for TyEnv
 }
@@ -463,6 +604,9 @@ impl Type {
             "float" => Ok(Self::Float),
             "str" => Ok(Self::String),
             "sem" => Ok(Self::Semaphore),
+            "tid" => Ok(Self::ThreadId),
+            "queue" => Ok(Self::Queue),
+            "matrix" => Ok(Self::Matrix),
             _ => Err(ParseError::new(&format!(
                 "Unknown primitive type: {}",
                 input
@@ -484,6 +628,8 @@ impl Display for Type {
             Self::UserFn(fn_ty) => fn_ty.to_string(),
             Self::ThreadId => "tid".to_string(),
             Self::Semaphore => "sem".to_string(),
+            Self::Queue => "queue".to_string(),
+            Self::Matrix => "matrix".to_string(),
         };
 
         write!(f, "{}", string)
@@ -0,0 +1,88 @@
+use lexer::{LexError, Token};
+use logos::Lexer;
+
+use crate::ParseError;
+
+/// The lexer with one token of lookahead, like Peekable, that also knows which line the next token
+/// is on. The lexer counts the newlines it skips, so the count is read right after lexing a token.
+///
+/// Input the lexer can't make a token of ends the tokens as if the input ended there, and the error
+/// is kept for the parser to report.
+///
+/// An integer lexed together with the '..' after it, as in 0..n, is split back into the two tokens.
+#[derive(Clone)]
+pub(crate) struct Tokens<'inp> {
+    lexer: Lexer<'inp, Token>,
+    peeked: Option<Option<Result<Token, ()>>>,
+    peeked_line: usize,
+    // DotDot split off an IntegerDotDot, the next token to return
+    split: Option<Token>,
+    error: Option<ParseError>,
+}
+
+impl<'inp> Tokens<'inp> {
+    pub(crate) fn new(lexer: Lexer<'inp, Token>) -> Tokens<'inp> {
+        Tokens {
+            lexer,
+            peeked: None,
+            peeked_line: 1,
+            split: None,
+            error: None,
+        }
+    }
+
+    pub(crate) fn peek(&mut self) -> Option<&Result<Token, ()>> {
+        if self.peeked.is_none() {
+            let next = self.lex();
+            self.peeked_line = self.lexer.extras.0 + 1;
+            self.peeked = Some(next);
+        }
+
+        self.peeked.as_ref().and_then(|tok| tok.as_ref())
+    }
+
+    pub(crate) fn next(&mut self) -> Option<Result<Token, ()>> {
+        match self.peeked.take() {
+            Some(tok) => tok,
+            None => self.lex(),
+        }
+    }
+
+    fn lex(&mut self) -> Option<Result<Token, ()>> {
+        if let Some(tok) = self.split.take() {
+            return Some(Ok(tok));
+        }
+        if self.error.is_some() {
+            return None;
+        }
+
+        match self.lexer.next()? {
+            Ok(Token::IntegerDotDot(val)) => {
+                self.split = Some(Token::DotDot);
+                Some(Ok(Token::Integer(val)))
+            }
+            Ok(tok) => Some(Ok(tok)),
+            Err(err) => {
+                self.error = Some(self.lex_error(err));
+                None
+            }
+        }
+    }
+
+    fn lex_error(&self, err: LexError) -> ParseError {
+        let line = self.lexer.extras.0 + 1;
+        let msg = format!("{} on line {}", err.message(self.lexer.slice()), line);
+        ParseError::new(&msg)
+    }
+
+    /// The error for the first input the lexer couldn't make a token of, if any
+    pub(crate) fn take_error(&mut self) -> Option<ParseError> {
+        self.error.take()
+    }
+
+    /// Line of the next token, starting from 1. At the end of input, the last line.
+    pub(crate) fn line(&mut self) -> usize {
+        self.peek();
+        self.peeked_line
+    }
+}
@@ -0,0 +1,394 @@
+use std::{collections::HashMap, rc::Rc};
+
+use parser::structs::{BlockSeq, Decl, Expr, FnCallData, FnParam, LetStmtData, QueueOp};
+
+use crate::type_checker::TypeErrors;
+use diagnostics::WRONG_ARGUMENTS;
+
+/// Rewrites calls using named arguments or parameter defaults into plain positional calls, so the type
+/// checker and compiler only ever see one argument per parameter in declaration order.
+///
+/// Only calls to a function declared by name in an enclosing block can be resolved, since the parameter
+/// names and defaults come from its declaration. The arguments given are evaluated in the order they
+/// are written: when that isn't parameter order, the call becomes a block that first binds them to
+/// temporaries, e.g. f(b: g(), a: h()) to { let arg 0 = g(); let arg 1 = h(); f(arg 1, arg 0) }.
+pub fn resolve_call_args(program: &mut BlockSeq) -> Result<(), TypeErrors> {
+    CallArgs::default().resolve_block(program, &[])
+}
+
+// Name of the temporary the nth argument given is bound to, with a space so it can't clash with the
+// program's names
+const ARG_TEMP: &str = "arg";
+
+/// Stack of blocks mapping names to the parameters of the function they refer to, or None if the name
+/// is bound to something else (shadowing an outer function)
+#[derive(Default)]
+struct CallArgs {
+    scopes: Vec<HashMap<String, Option<Vec<FnParam>>>>,
+}
+
+impl CallArgs {
+    fn lookup(&self, name: &str) -> Option<&Vec<FnParam>> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .and_then(|params| params.as_ref())
+    }
+
+    fn bind(&mut self, name: &str, params: Option<Vec<FnParam>>) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), params);
+        }
+    }
+
+    fn resolve_block(&mut self, blk: &mut BlockSeq, params: &[FnParam]) -> Result<(), TypeErrors> {
+        self.scopes.push(HashMap::new());
+        for param in params {
+            self.bind(&param.name, None);
+        }
+
+        let res = self.resolve_block_inner(blk);
+        self.scopes.pop();
+        res
+    }
+
+    fn resolve_block_inner(&mut self, blk: &mut BlockSeq) -> Result<(), TypeErrors> {
+        for decl in blk.decls.iter_mut() {
+            self.resolve_decl(decl)?;
+        }
+
+        if let Some(expr) = blk.last_expr.as_mut() {
+            self.resolve_expr(Rc::make_mut(expr))?;
+        }
+
+        Ok(())
+    }
+
+    fn resolve_decl(&mut self, decl: &mut Decl) -> Result<(), TypeErrors> {
+        match decl {
+            Decl::LetStmt(stmt) => {
+                self.resolve_expr(&mut stmt.expr)?;
+                self.bind(&stmt.ident, None);
+            }
+            Decl::AssignStmt(stmt) => {
+                self.resolve_expr(&mut stmt.expr)?;
+                // no longer known to be the declared function
+                if let Some(scope) = self
+                    .scopes
+                    .iter_mut()
+                    .rev()
+                    .find(|scope| scope.contains_key(&stmt.ident))
+                {
+                    scope.insert(stmt.ident.clone(), None);
+                }
+            }
+            Decl::ExprStmt(expr) => self.resolve_expr(expr)?,
+            Decl::IfOnlyStmt(if_else) => {
+                self.resolve_expr(&mut if_else.cond)?;
+                self.resolve_block(&mut if_else.if_blk, &[])?;
+            }
+            Decl::LoopStmt(lp) => {
+                if let Some(cond) = lp.cond.as_mut() {
+                    self.resolve_expr(cond)?;
+                }
+                self.resolve_block(&mut lp.body, &[])?;
+            }
+            Decl::ParallelLoopStmt(lp) => {
+                self.resolve_expr(&mut lp.start)?;
+                self.resolve_expr(&mut lp.end)?;
+                let var = FnParam {
+                    name: lp.var.clone(),
+                    type_ann: None,
+                    default: None,
+                };
+                self.resolve_block(&mut lp.body, &[var])?;
+            }
+            Decl::ScopeStmt(blk) => self.resolve_block(blk, &[])?,
+            Decl::SelectStmt(select) => {
+                for arm in select.arms.iter_mut() {
+                    match &mut arm.op {
+                        QueueOp::Pop(q) => self.resolve_expr(q)?,
+                        QueueOp::Push(q, v) => {
+                            self.resolve_expr(q)?;
+                            self.resolve_expr(v)?;
+                        }
+                    }
+                    // the popped value is bound like a parameter of the body
+                    let binding: Vec<FnParam> = arm
+                        .binding
+                        .iter()
+                        .map(|name| FnParam {
+                            name: name.clone(),
+                            type_ann: None,
+                            default: None,
+                        })
+                        .collect();
+                    self.resolve_block(&mut arm.body, &binding)?;
+                }
+                if let Some(default) = select.default.as_mut() {
+                    self.resolve_block(default, &[])?;
+                }
+            }
+            // bound before the body so recursive calls resolve too
+            Decl::FnDeclStmt(fn_decl) => {
+                self.bind(&fn_decl.name, Some(fn_decl.params.clone()));
+                self.resolve_block(&mut fn_decl.body, &fn_decl.params)?;
+            }
+            Decl::ReturnStmt(Some(expr)) | Decl::WaitStmt(expr) | Decl::PostStmt(expr) => {
+                self.resolve_expr(expr)?
+            }
+            Decl::ReturnStmt(None) | Decl::BreakStmt | Decl::YieldStmt => (),
+        }
+
+        Ok(())
+    }
+
+    fn resolve_expr(&mut self, expr: &mut Expr) -> Result<(), TypeErrors> {
+        if let Expr::FnCallExpr(call) | Expr::SpawnExpr(call) | Expr::SpawnIsolatedExpr(call) = expr
+        {
+            let temps = self.resolve_call(call)?;
+            if !temps.is_empty() {
+                let symbols = temps
+                    .iter()
+                    .filter_map(|decl| match decl {
+                        Decl::LetStmt(stmt) => Some(stmt.ident.clone()),
+                        _ => None,
+                    })
+                    .collect();
+                let call = std::mem::replace(expr, Expr::Bool(false));
+                *expr = Expr::BlockExpr(BlockSeq {
+                    decls: temps,
+                    last_expr: Some(Rc::new(call)),
+                    symbols,
+                    lines: vec![],
+                });
+            }
+            return Ok(());
+        }
+
+        match expr {
+            Expr::UnOpExpr(_, expr) | Expr::CastExpr(expr, _) => self.resolve_expr(expr)?,
+            Expr::BinOpExpr(_, lhs, rhs) => {
+                self.resolve_expr(lhs)?;
+                self.resolve_expr(rhs)?;
+            }
+            Expr::BlockExpr(blk) => self.resolve_block(blk, &[])?,
+            Expr::IfElseExpr(if_else) => {
+                self.resolve_expr(&mut if_else.cond)?;
+                self.resolve_block(&mut if_else.if_blk, &[])?;
+                if let Some(else_blk) = if_else.else_blk.as_mut() {
+                    self.resolve_block(else_blk, &[])?;
+                }
+            }
+            Expr::FnCallExpr(_) | Expr::SpawnExpr(_) | Expr::SpawnIsolatedExpr(_) => (),
+            Expr::CallExpr(callee, args) => {
+                self.resolve_expr(callee)?;
+                for arg in args.iter_mut() {
+                    self.resolve_expr(arg)?;
+                }
+            }
+            Expr::Symbol(_)
+            | Expr::Integer(_)
+            | Expr::Float(_)
+            | Expr::Bool(_)
+            | Expr::StringLiteral(_)
+            | Expr::JoinExpr(_) => (),
+        }
+
+        Ok(())
+    }
+
+    /// Resolve the arguments of call into one per parameter, returning the lets binding the
+    /// arguments given to temporaries if they have to be evaluated before the call.
+    fn resolve_call(&mut self, call: &mut FnCallData) -> Result<Vec<Decl>, TypeErrors> {
+        for arg in call.args.iter_mut() {
+            self.resolve_expr(arg)?;
+        }
+        for (_, arg) in call.named_args.iter_mut() {
+            self.resolve_expr(arg)?;
+        }
+
+        let Some(params) = self.lookup(&call.name) else {
+            if !call.named_args.is_empty() {
+                let e = format!(
+                    "Named arguments need a call to a declared function, '{}' isn't one",
+                    call.name
+                );
+                return Err(TypeErrors::with_code(WRONG_ARGUMENTS, &e));
+            }
+            return Ok(vec![]);
+        };
+
+        // positional calls that defaults can't complete, or with too many arguments, get the type
+        // checker's arity error instead
+        let completed_by_defaults = params
+            .iter()
+            .skip(call.args.len())
+            .all(|param| param.default.is_some());
+        if call.args.len() > params.len()
+            || (call.named_args.is_empty()
+                && (call.args.len() == params.len() || !completed_by_defaults))
+        {
+            return Ok(vec![]);
+        }
+
+        // each slot holds the argument given for the parameter and its index in the call
+        let mut slots: Vec<Option<(usize, Expr)>> = vec![None; params.len()];
+        let given = call.args.drain(..).map(|arg| (None, arg));
+        let named = call
+            .named_args
+            .drain(..)
+            .map(|(name, arg)| (Some(name), arg));
+        for (pos, (name, arg)) in given.chain(named).enumerate() {
+            let Some(name) = name else {
+                slots[pos] = Some((pos, arg));
+                continue;
+            };
+
+            let Some(idx) = params.iter().position(|param| param.name.eq(&name)) else {
+                let e = format!("Function '{}' has no parameter named '{}'", call.name, name);
+                return Err(TypeErrors::with_code(WRONG_ARGUMENTS, &e));
+            };
+
+            if slots[idx].is_some() {
+                let e = format!(
+                    "Argument for parameter '{}' given more than once in call to '{}'",
+                    name, call.name
+                );
+                return Err(TypeErrors::with_code(WRONG_ARGUMENTS, &e));
+            }
+            slots[idx] = Some((pos, arg));
+        }
+
+        // in order if the arguments given are in parameter order as written
+        let given: Vec<usize> = slots.iter().flatten().map(|(pos, _)| *pos).collect();
+        let in_order = given.is_sorted();
+        let mut temps = vec![];
+        for (slot, param) in slots.into_iter().zip(params.iter()) {
+            match slot {
+                Some((_, arg)) if in_order => call.args.push(arg),
+                Some((pos, arg)) => {
+                    let ident = format!("{} {}", ARG_TEMP, pos);
+                    call.args.push(Expr::Symbol(ident.clone()));
+                    temps.push((
+                        pos,
+                        Decl::LetStmt(LetStmtData {
+                            ident,
+                            expr: arg,
+                            type_ann: None,
+                        }),
+                    ));
+                }
+                None if param.default.is_some() => {
+                    call.args.extend(param.default.clone());
+                }
+                None => {
+                    let e = format!(
+                        "Missing argument for parameter '{}' in call to '{}'",
+                        param.name, call.name
+                    );
+                    return Err(TypeErrors::with_code(WRONG_ARGUMENTS, &e));
+                }
+            }
+        }
+
+        temps.sort_by_key(|(pos, _)| *pos);
+        Ok(temps.into_iter().map(|(_, decl)| decl).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::Parser;
+
+    use super::resolve_call_args;
+
+    fn expect_resolved(inp: &str, exp: &str) {
+        let mut prog = Parser::new_from_string(inp).parse().expect("Should parse");
+        resolve_call_args(&mut prog).expect("Should resolve");
+        assert_eq!(prog.to_string(), exp);
+    }
+
+    fn expect_resolve_err(inp: &str, exp_err: &str) {
+        let mut prog = Parser::new_from_string(inp).parse().expect("Should parse");
+        let err = resolve_call_args(&mut prog).expect_err("Should err");
+        assert_eq!(err.to_string(), format!("[TypeError]: {}", exp_err));
+    }
+
+    #[test]
+    fn test_resolve_call_args() {
+        let decl = "fn f (a:int, b:int = 2, c:bool = true) { a }";
+
+        expect_resolved(
+            "fn f(a: int, b: int = 2, c: bool = true) { a } f(1)",
+            &format!("{};f(1,2,true)", decl),
+        );
+        expect_resolved(
+            "fn f(a: int, b: int = 2, c: bool = true) { a } f(a: 3, b: 4)",
+            &format!("{};f(3,4,true)", decl),
+        );
+
+        // arguments out of parameter order are evaluated first, in the order written
+        expect_resolved(
+            "fn f(a: int, b: int = 2, c: bool = true) { a } f(c: false, a: 3)",
+            &format!(
+                "{};{{ let arg 0 = false;let arg 1 = 3;f(arg 1,2,arg 0) }}",
+                decl
+            ),
+        );
+        expect_resolved(
+            "fn f(a: int, b: int = 2, c: bool = true) { a } f(b: 1, c: false, a: 0)",
+            &format!(
+                "{};{{ let arg 0 = 1;let arg 1 = false;let arg 2 = 0;f(arg 2,arg 0,arg 1) }}",
+                decl
+            ),
+        );
+        expect_resolved(
+            "fn f(a: int, b: int = 2, c: bool = true) { a } f(1, c: false)",
+            &format!("{};f(1,2,false)", decl),
+        );
+
+        // nested calls and blocks
+        expect_resolved(
+            "fn f(a: int, b: int = 2, c: bool = true) { a } { f(f(b: 1, a: 0)) }",
+            &format!(
+                "{};{{ f({{ let arg 0 = 1;let arg 1 = 0;f(arg 1,arg 0,true) }},2,true) }}",
+                decl
+            ),
+        );
+
+        // calls to functions without the declaration at hand are left alone
+        expect_resolved("g(1)", "g(1)");
+        expect_resolved(
+            "fn f(a: int, b: int = 2) { a } let f = g; f(1)",
+            "fn f (a:int, b:int = 2) { a };let f = g;f(1)",
+        );
+    }
+
+    #[test]
+    fn test_resolve_call_args_errs() {
+        let decl = "fn f(a: int, b: int = 2) { a } ";
+
+        expect_resolve_err(
+            &format!("{}f(b: 3)", decl),
+            "Missing argument for parameter 'a' in call to 'f'",
+        );
+        expect_resolve_err(
+            &format!("{}f(1, a: 3)", decl),
+            "Argument for parameter 'a' given more than once in call to 'f'",
+        );
+        expect_resolve_err(
+            &format!("{}f(1, d: 3)", decl),
+            "Function 'f' has no parameter named 'd'",
+        );
+        expect_resolve_err(
+            "println(x: 2)",
+            "Named arguments need a call to a declared function, 'println' isn't one",
+        );
+        expect_resolve_err(
+            &format!("{}{{ let f = 2; f(a: 1) }}", decl),
+            "Named arguments need a call to a declared function, 'f' isn't one",
+        );
+    }
+}
@@ -1,10 +1,15 @@
 use crate::type_checker::{CheckResult, TypeChecker, TypeErrors};
-use parser::structs::{FnCallData, Type};
+use diagnostics::{UNDECLARED_NAME, WRONG_ARGUMENTS};
+use parser::structs::{Expr, FnCallData, FnTypeData, Type};
 
 // Ideally these constants should be shared across type checker and VM but I don't want to waste time refactoring
 const READ_LINE: &str = "read_line";
+const READ_ALL: &str = "read_all";
+const EOF: &str = "eof";
 const PRINT: &str = "print";
 const PRINTLN: &str = "println";
+const EPRINT: &str = "eprint";
+const EPRINTLN: &str = "eprintln";
 const STRING_LEN: &str = "string_len";
 const MIN: &str = "min";
 const MAX: &str = "max";
@@ -15,17 +20,78 @@ const TAN: &str = "tan";
 const SQRT: &str = "sqrt";
 const LOG: &str = "log";
 const POW: &str = "pow";
+const FLOOR: &str = "floor";
+const CEIL: &str = "ceil";
+const ROUND: &str = "round";
+const TRUNC: &str = "trunc";
+const EXP: &str = "exp";
+const LOG2: &str = "log2";
+const LOG10: &str = "log10";
+const ATAN2: &str = "atan2";
+const HYPOT: &str = "hypot";
+const CLAMP: &str = "clamp";
+const IS_NAN: &str = "is_nan";
+const IS_FINITE: &str = "is_finite";
+const DIV: &str = "div";
+const FDIV: &str = "fdiv";
 const ITOA: &str = "itoa";
 const ATOI: &str = "atoi";
+const PARSE_INT: &str = "parse_int";
+const PARSE_INT_RADIX: &str = "parse_int_radix";
+const PARSE_FLOAT: &str = "parse_float";
+const PARSE_FAILED: &str = "parse_failed";
 const FLOAT_TO_INT: &str = "float_to_int";
 const INT_TO_FLOAT: &str = "int_to_float";
+const CLONE: &str = "clone";
+const TYPE_OF: &str = "type_of";
+const VARS: &str = "vars";
+const DBG: &str = "dbg";
+const JSON_STRINGIFY: &str = "json_stringify";
+const JSON_GET: &str = "json_get";
+const TO_FIXED: &str = "to_fixed";
+const FORMAT_INT: &str = "format_int";
+const SET_FLOAT_PRECISION: &str = "set_float_precision";
 const SEM_CREATE: &str = "sem_create";
 const SEM_SET: &str = "sem_set";
+const SEM_VALUE: &str = "sem_value";
+const QUEUE_CREATE: &str = "queue_create";
+const QUEUE_PUSH: &str = "queue_push";
+const QUEUE_POP: &str = "queue_pop";
+const QUEUE_CREATE_BOUNDED: &str = "queue_create_bounded";
+const QUEUE_TRY_PUSH: &str = "queue_try_push";
+const QUEUE_TRY_POP: &str = "queue_try_pop";
+const MAP: &str = "map";
+const FILTER: &str = "filter";
+const REDUCE: &str = "reduce";
+const MATRIX: &str = "matrix";
+const MAT_GET: &str = "mat_get";
+const MAT_SET: &str = "mat_set";
+const MAT_ROWS: &str = "mat_rows";
+const MAT_COLS: &str = "mat_cols";
+const MATMUL: &str = "matmul";
+const JOIN_TIMEOUT: &str = "join_timeout";
+const IS_ALIVE: &str = "is_alive";
+const DETACH: &str = "detach";
+const CANCEL: &str = "cancel";
+const CURRENT_TID: &str = "current_tid";
+const THREAD_COUNT: &str = "thread_count";
+const SCHED_STATS: &str = "sched_stats";
+pub(crate) const EXIT: &str = "exit";
+const EXEC: &str = "exec";
+const EXEC_STDOUT: &str = "exec_stdout";
+const EXEC_STDERR: &str = "exec_stderr";
 
-const BUILTINS: [&str; 19] = [
+// Not a builtin closure: the compiler turns spawn_limited(f, budget) into a spawn with an instruction budget
+pub(crate) const SPAWN_LIMITED: &str = "spawn_limited";
+
+const BUILTINS: [&str; 74] = [
     READ_LINE,
+    READ_ALL,
+    EOF,
     PRINT,
     PRINTLN,
+    EPRINT,
+    EPRINTLN,
     STRING_LEN,
     MIN,
     MAX,
@@ -36,18 +102,80 @@ const BUILTINS: [&str; 19] = [
     SQRT,
     LOG,
     POW,
+    FLOOR,
+    CEIL,
+    ROUND,
+    TRUNC,
+    EXP,
+    LOG2,
+    LOG10,
+    ATAN2,
+    HYPOT,
+    CLAMP,
+    IS_NAN,
+    IS_FINITE,
+    DIV,
+    FDIV,
     ITOA,
     ATOI,
+    PARSE_INT,
+    PARSE_INT_RADIX,
+    PARSE_FLOAT,
+    PARSE_FAILED,
     FLOAT_TO_INT,
     INT_TO_FLOAT,
+    CLONE,
+    TYPE_OF,
+    VARS,
+    DBG,
+    JSON_STRINGIFY,
+    JSON_GET,
+    TO_FIXED,
+    FORMAT_INT,
+    SET_FLOAT_PRECISION,
     SEM_CREATE,
     SEM_SET,
+    SEM_VALUE,
+    QUEUE_CREATE,
+    QUEUE_PUSH,
+    QUEUE_POP,
+    QUEUE_CREATE_BOUNDED,
+    QUEUE_TRY_PUSH,
+    QUEUE_TRY_POP,
+    MAP,
+    FILTER,
+    REDUCE,
+    MATRIX,
+    MAT_GET,
+    MAT_SET,
+    MAT_ROWS,
+    MAT_COLS,
+    MATMUL,
+    JOIN_TIMEOUT,
+    IS_ALIVE,
+    DETACH,
+    CANCEL,
+    CURRENT_TID,
+    THREAD_COUNT,
+    SCHED_STATS,
+    EXIT,
 ];
 
+// Running commands is only possible with the exec feature
+#[cfg(feature = "exec")]
+const EXEC_BUILTINS: [&str; 3] = [EXEC, EXEC_STDOUT, EXEC_STDERR];
+#[cfg(not(feature = "exec"))]
+const EXEC_BUILTINS: [&str; 0] = [];
+
+/// Type of a function with the given parameter and return types, for builtins taking functions
+fn fn_type(params: Vec<Type>, ret_type: Type) -> Type {
+    Type::UserFn(Box::new(FnTypeData { params, ret_type }))
+}
+
 impl<'prog> TypeChecker<'prog> {
     /// Check if name is a builtin function
     pub(crate) fn is_builtin_fn(name: &str) -> bool {
-        BUILTINS.contains(&name)
+        BUILTINS.contains(&name) || EXEC_BUILTINS.contains(&name)
     }
 
     fn get_type_string(arg_types: &[Type]) -> String {
@@ -70,7 +198,7 @@ impl<'prog> TypeChecker<'prog> {
                 "Function '{}' takes {} arguments but {} were supplied",
                 fn_name, exp_len, arg_len
             );
-            return Err(TypeErrors::new_err(&e));
+            return Err(TypeErrors::with_code(WRONG_ARGUMENTS, &e));
         }
 
         Ok(())
@@ -119,6 +247,16 @@ impl<'prog> TypeChecker<'prog> {
                 TypeChecker::check_arg_params_match(name, &arg_types, &[])?;
                 Type::String
             }
+            // () -> string
+            READ_ALL => {
+                TypeChecker::check_arg_params_match(name, &arg_types, &[])?;
+                Type::String
+            }
+            // () -> bool
+            EOF => {
+                TypeChecker::check_arg_params_match(name, &arg_types, &[])?;
+                Type::Bool
+            }
             // (any) -> ()
             PRINT => {
                 TypeChecker::check_arg_params_len(name, arg_types.len(), 1)?;
@@ -129,6 +267,16 @@ impl<'prog> TypeChecker<'prog> {
                 TypeChecker::check_arg_params_len(name, arg_types.len(), 1)?;
                 Type::Unit
             }
+            // (any) -> (), same as print but to stderr
+            EPRINT => {
+                TypeChecker::check_arg_params_len(name, arg_types.len(), 1)?;
+                Type::Unit
+            }
+            // (any) -> ()
+            EPRINTLN => {
+                TypeChecker::check_arg_params_len(name, arg_types.len(), 1)?;
+                Type::Unit
+            }
             // (string) => int
             STRING_LEN => {
                 TypeChecker::check_arg_params_match(name, &arg_types, &[Type::String])?;
@@ -263,6 +411,46 @@ impl<'prog> TypeChecker<'prog> {
                     }
                 }
             }
+            // float -> float
+            FLOOR | CEIL | ROUND | TRUNC | EXP | LOG2 | LOG10 => {
+                TypeChecker::check_arg_params_match(name, &arg_types, &[Type::Float])?;
+                Type::Float
+            }
+            // (float, float) -> float
+            ATAN2 | HYPOT => {
+                TypeChecker::check_arg_params_match(name, &arg_types, &[Type::Float, Type::Float])?;
+                Type::Float
+            }
+            // (int, int, int) -> int or (float, float, float) -> float
+            CLAMP => {
+                TypeChecker::check_arg_params_len(name, arg_types.len(), 3)?;
+                match (&arg_types[0], &arg_types[1], &arg_types[2]) {
+                    (Type::Int, Type::Int, Type::Int) => Type::Int,
+                    (Type::Float, Type::Float, Type::Float) => Type::Float,
+                    _ => {
+                        let e = format!(
+                            "Expected (int, int, int) or (float, float, float) but got {}",
+                            TypeChecker::get_type_string(&arg_types)
+                        );
+                        return Err(TypeErrors::new_err(&e));
+                    }
+                }
+            }
+            // float -> bool
+            IS_NAN | IS_FINITE => {
+                TypeChecker::check_arg_params_match(name, &arg_types, &[Type::Float])?;
+                Type::Bool
+            }
+            // (int, int) -> int
+            DIV => {
+                TypeChecker::check_arg_params_match(name, &arg_types, &[Type::Int, Type::Int])?;
+                Type::Int
+            }
+            // (int, int) -> float
+            FDIV => {
+                TypeChecker::check_arg_params_match(name, &arg_types, &[Type::Int, Type::Int])?;
+                Type::Float
+            }
             // int -> string
             ITOA => {
                 TypeChecker::check_arg_params_len(name, arg_types.len(), 1)?;
@@ -291,6 +479,26 @@ impl<'prog> TypeChecker<'prog> {
                     }
                 }
             }
+            // string -> int, 0 if invalid
+            PARSE_INT => {
+                TypeChecker::check_arg_params_match(name, &arg_types, &[Type::String])?;
+                Type::Int
+            }
+            // (string, int) -> int, 0 if invalid
+            PARSE_INT_RADIX => {
+                TypeChecker::check_arg_params_match(name, &arg_types, &[Type::String, Type::Int])?;
+                Type::Int
+            }
+            // string -> float, 0.0 if invalid
+            PARSE_FLOAT => {
+                TypeChecker::check_arg_params_match(name, &arg_types, &[Type::String])?;
+                Type::Float
+            }
+            // () -> bool
+            PARSE_FAILED => {
+                TypeChecker::check_arg_params_len(name, arg_types.len(), 0)?;
+                Type::Bool
+            }
             // float -> int
             FLOAT_TO_INT => {
                 TypeChecker::check_arg_params_len(name, arg_types.len(), 1)?;
@@ -319,27 +527,262 @@ impl<'prog> TypeChecker<'prog> {
                     }
                 }
             }
-            // () -> semaphore
-            SEM_CREATE => {
-                // Fill out this block
+            // any -> string
+            TYPE_OF => {
+                TypeChecker::check_arg_params_len(name, arg_types.len(), 1)?;
+                Type::String
+            }
+            // () -> string
+            VARS => {
                 TypeChecker::check_arg_params_len(name, arg_types.len(), 0)?;
+                Type::String
+            }
+            // T -> T
+            CLONE | DBG => {
+                TypeChecker::check_arg_params_len(name, arg_types.len(), 1)?;
+                arg_types[0].to_owned()
+            }
+            // T -> string, for types with a JSON form
+            JSON_STRINGIFY => {
+                TypeChecker::check_arg_params_len(name, arg_types.len(), 1)?;
+                match arg_types[0] {
+                    Type::Int
+                    | Type::Float
+                    | Type::Bool
+                    | Type::String
+                    | Type::Unit
+                    | Type::Matrix => Type::String,
+                    _ => {
+                        let e = format!("json_stringify can't encode a {}", arg_types[0]);
+                        return Err(TypeErrors::new_err(&e));
+                    }
+                }
+            }
+            // (string, string) -> string, "" if invalid
+            JSON_GET => {
+                TypeChecker::check_arg_params_match(
+                    name,
+                    &arg_types,
+                    &[Type::String, Type::String],
+                )?;
+                Type::String
+            }
+            // (float, int) -> string
+            TO_FIXED => {
+                TypeChecker::check_arg_params_match(name, &arg_types, &[Type::Float, Type::Int])?;
+                Type::String
+            }
+            // (int, int) -> string
+            FORMAT_INT => {
+                TypeChecker::check_arg_params_match(name, &arg_types, &[Type::Int, Type::Int])?;
+                Type::String
+            }
+            // int -> ()
+            SET_FLOAT_PRECISION => {
+                TypeChecker::check_arg_params_match(name, &arg_types, &[Type::Int])?;
+                Type::Unit
+            }
+            // () -> semaphore, int -> semaphore
+            SEM_CREATE => {
+                if !arg_types.is_empty() {
+                    TypeChecker::check_arg_params_match(name, &arg_types, &[Type::Int])?;
+                }
                 Type::Semaphore
             }
+            // (semaphore, int) -> ()
             SEM_SET => {
-                // Fill out this block
-                todo!()
+                TypeChecker::check_arg_params_match(
+                    name,
+                    &arg_types,
+                    &[Type::Semaphore, Type::Int],
+                )?;
+                Type::Unit
+            }
+            // semaphore -> int
+            SEM_VALUE => {
+                TypeChecker::check_arg_params_match(name, &arg_types, &[Type::Semaphore])?;
+                Type::Int
+            }
+            // () -> queue
+            QUEUE_CREATE => {
+                TypeChecker::check_arg_params_len(name, arg_types.len(), 0)?;
+                Type::Queue
+            }
+            // (queue, int) -> ()
+            QUEUE_PUSH => {
+                TypeChecker::check_arg_params_match(name, &arg_types, &[Type::Queue, Type::Int])?;
+                Type::Unit
+            }
+            // queue -> int
+            QUEUE_POP => {
+                TypeChecker::check_arg_params_match(name, &arg_types, &[Type::Queue])?;
+                Type::Int
+            }
+            // int -> queue
+            QUEUE_CREATE_BOUNDED => {
+                TypeChecker::check_arg_params_match(name, &arg_types, &[Type::Int])?;
+                Type::Queue
+            }
+            // (queue, int) -> bool
+            QUEUE_TRY_PUSH => {
+                TypeChecker::check_arg_params_match(name, &arg_types, &[Type::Queue, Type::Int])?;
+                Type::Bool
+            }
+            // (queue, int) -> int
+            QUEUE_TRY_POP => {
+                TypeChecker::check_arg_params_match(name, &arg_types, &[Type::Queue, Type::Int])?;
+                Type::Int
+            }
+            // (queue, fn(int) -> int) -> queue
+            MAP => {
+                let f = fn_type(vec![Type::Int], Type::Int);
+                TypeChecker::check_arg_params_match(name, &arg_types, &[Type::Queue, f])?;
+                Type::Queue
+            }
+            // (queue, fn(int) -> bool) -> queue
+            FILTER => {
+                let pred = fn_type(vec![Type::Int], Type::Bool);
+                TypeChecker::check_arg_params_match(name, &arg_types, &[Type::Queue, pred])?;
+                Type::Queue
+            }
+            // (queue, T, fn(T, int) -> T) -> T
+            REDUCE => {
+                TypeChecker::check_arg_params_len(name, arg_types.len(), 3)?;
+                let acc = arg_types[1].clone();
+                let f = fn_type(vec![acc.clone(), Type::Int], acc.clone());
+                TypeChecker::check_arg_params_match(
+                    name,
+                    &arg_types,
+                    &[Type::Queue, acc.clone(), f],
+                )?;
+                acc
+            }
+            // (int, int, float) -> matrix
+            MATRIX => {
+                TypeChecker::check_arg_params_match(
+                    name,
+                    &arg_types,
+                    &[Type::Int, Type::Int, Type::Float],
+                )?;
+                Type::Matrix
+            }
+            // (matrix, int, int) -> float
+            MAT_GET => {
+                TypeChecker::check_arg_params_match(
+                    name,
+                    &arg_types,
+                    &[Type::Matrix, Type::Int, Type::Int],
+                )?;
+                Type::Float
+            }
+            // (matrix, int, int, float) -> ()
+            MAT_SET => {
+                TypeChecker::check_arg_params_match(
+                    name,
+                    &arg_types,
+                    &[Type::Matrix, Type::Int, Type::Int, Type::Float],
+                )?;
+                Type::Unit
+            }
+            // matrix -> int
+            MAT_ROWS | MAT_COLS => {
+                TypeChecker::check_arg_params_match(name, &arg_types, &[Type::Matrix])?;
+                Type::Int
+            }
+            // (matrix, matrix) -> matrix
+            MATMUL => {
+                TypeChecker::check_arg_params_match(
+                    name,
+                    &arg_types,
+                    &[Type::Matrix, Type::Matrix],
+                )?;
+                Type::Matrix
             }
-            _ => todo!(),
+            // (tid, int) -> (), like join the result type of the thread is not tracked
+            JOIN_TIMEOUT => {
+                TypeChecker::check_arg_params_match(
+                    name,
+                    &arg_types,
+                    &[Type::ThreadId, Type::Int],
+                )?;
+                Type::Unit
+            }
+            // tid -> bool
+            IS_ALIVE => {
+                TypeChecker::check_arg_params_match(name, &arg_types, &[Type::ThreadId])?;
+                Type::Bool
+            }
+            // tid -> ()
+            DETACH | CANCEL => {
+                TypeChecker::check_arg_params_match(name, &arg_types, &[Type::ThreadId])?;
+                Type::Unit
+            }
+            // () -> tid
+            CURRENT_TID => {
+                TypeChecker::check_arg_params_len(name, arg_types.len(), 0)?;
+                Type::ThreadId
+            }
+            // () -> int
+            THREAD_COUNT => {
+                TypeChecker::check_arg_params_len(name, arg_types.len(), 0)?;
+                Type::Int
+            }
+            // () -> str
+            SCHED_STATS => {
+                TypeChecker::check_arg_params_len(name, arg_types.len(), 0)?;
+                Type::String
+            }
+            // int -> ()
+            EXIT => {
+                TypeChecker::check_arg_params_match(name, &arg_types, &[Type::Int])?;
+                Type::Unit
+            }
+            // (string, int) -> int
+            EXEC => {
+                TypeChecker::check_arg_params_match(name, &arg_types, &[Type::String, Type::Int])?;
+                Type::Int
+            }
+            // () -> string
+            EXEC_STDOUT | EXEC_STDERR => {
+                TypeChecker::check_arg_params_len(name, arg_types.len(), 0)?;
+                Type::String
+            }
+            _ => unreachable!("Builtin '{}' has no type check", name),
         };
 
         Ok(check_res)
     }
 
+    /// spawn_limited(f, budget) takes a function with no parameters and an int budget
+    fn check_spawn_limited(arg_types: &[Type]) -> Result<(), TypeErrors> {
+        TypeChecker::check_arg_params_len(SPAWN_LIMITED, arg_types.len(), 2)?;
+
+        let takes_no_args = matches!(&arg_types[0], Type::UserFn(fn_ty) if fn_ty.params.is_empty());
+        if !takes_no_args || arg_types[1] != Type::Int {
+            let e = format!(
+                "Function '{}' expects a function with no parameters and an int budget, got {}",
+                SPAWN_LIMITED,
+                TypeChecker::get_type_string(arg_types)
+            );
+            return Err(TypeErrors::new_err(&e));
+        }
+
+        Ok(())
+    }
+
     // Accumulate errors from the expressions. Propagate must_break, must_return
     pub(crate) fn check_fn_call(
         &mut self,
         fn_call: &FnCallData,
     ) -> Result<CheckResult, TypeErrors> {
+        if !fn_call.named_args.is_empty() {
+            let e = format!(
+                "Named arguments in call to '{}' must be resolved before type checking",
+                fn_call.name
+            );
+            return Err(TypeErrors::new_err(&e));
+        }
+
         let mut ty_errs = TypeErrors::new();
 
         let mut check_res = CheckResult {
@@ -371,24 +814,124 @@ impl<'prog> TypeChecker<'prog> {
             return Err(ty_errs);
         }
 
-        if TypeChecker::is_builtin_fn(&fn_call.name) {
-            return self.check_builtin_fn_call(&fn_call.name, arg_types, check_res);
+        // unless the program declares a spawn_limited of its own
+        if fn_call.name.eq(SPAWN_LIMITED) && self.lookup(SPAWN_LIMITED).is_none() {
+            TypeChecker::check_spawn_limited(&arg_types)?;
+            check_res.ty = Type::ThreadId;
+            return Ok(check_res);
+        }
+
+        let name = &fn_call.name;
+        let builtin = TypeChecker::is_builtin_fn(name);
+        let Some((env_idx, ty)) = self.lookup(name) else {
+            if builtin {
+                return self.check_builtin_fn_call(name, arg_types, check_res);
+            }
+            let e = format!("Identifier '{}' not declared", name);
+            return Err(TypeErrors::with_code(UNDECLARED_NAME, &e));
+        };
+
+        // User fn, or a variable shadowing a builtin of the same name
+        match ty {
+            Type::UserFn(fn_ty) => {
+                TypeChecker::check_arg_params_match(name, &arg_types, &fn_ty.params)?;
+                check_res.ty = fn_ty.ret_type.clone();
+            }
+            // builtins called through another name aren't checked
+            Type::BuiltInFn => (),
+            // declared later: fine from a function declared before it, which can only be called
+            // once it is, but an error in the block itself, which declares the name from its start
+            Type::Unitialised => {
+                let fn_env = self.fn_env_stack.last().copied().unwrap_or_default();
+                if builtin && env_idx >= fn_env {
+                    let e = format!(
+                        "'{}' is called before its declaration in the same block, which shadows the builtin function '{}' in the whole block. Call it after the declaration, or rename the declaration to call the builtin",
+                        name, name
+                    );
+                    return Err(TypeErrors::new_err(&e));
+                }
+            }
+            ty => {
+                let hint = if builtin {
+                    format!(
+                        ". The variable shadows the builtin function '{}', rename it to call the builtin",
+                        name
+                    )
+                } else {
+                    String::new()
+                };
+                let e = format!(
+                    "Can't call '{}' of type '{}', expected a function{}",
+                    name, ty, hint
+                );
+                return Err(TypeErrors::new_err(&e));
+            }
         }
 
-        // User fn
+        Ok(check_res)
+    }
 
-        // Check arg and params match
+    /// Call whose callee is an expression rather than a name e.g get_fn()(3). The callee must be a function.
+    pub(crate) fn check_call_expr(
+        &mut self,
+        callee: &Expr,
+        args: &[Expr],
+    ) -> Result<CheckResult, TypeErrors> {
+        let mut ty_errs = TypeErrors::new();
 
-        // TODO: lookup type of the user fn in env, cast to function type and use its return type
-        let fn_ty = self.get_type(&fn_call.name)?.to_fn_type();
-        if let Some(ty) = fn_ty {
-            let param_types: Vec<Type> = ty.params.iter().map(|x| x.to_owned()).collect();
+        let mut check_res = CheckResult {
+            ty: Type::Unit,
+            must_break: false,
+            must_return: false,
+        };
 
-            TypeChecker::check_arg_params_match(&fn_call.name, &arg_types, &param_types)?;
-            check_res.ty = ty.ret_type;
+        let mut callee_ty = Type::Unit;
+        match self.check_expr(callee) {
+            Ok(callee_res) => {
+                callee_ty = callee_res.ty.clone();
+                check_res = callee_res;
+            }
+            Err(mut errs) => {
+                ty_errs.append(&mut errs);
+            }
+        }
+
+        let mut arg_types: Vec<Type> = vec![];
+        for arg in args.iter() {
+            match self.check_expr(arg) {
+                Ok(arg_res) => {
+                    check_res = CheckResult::combine(&check_res, &arg_res);
+                    arg_types.push(arg_res.ty);
+                }
+                Err(mut errs) => {
+                    ty_errs.append(&mut errs);
+                }
+            }
+        }
+
+        if !ty_errs.is_ok() {
+            return Err(ty_errs);
+        }
+
+        match callee_ty {
+            Type::UserFn(fn_ty) => {
+                TypeChecker::check_arg_params_match(
+                    &callee.to_string(),
+                    &arg_types,
+                    &fn_ty.params,
+                )?;
+                check_res.ty = fn_ty.ret_type;
+            }
+            // builtins called through another name aren't checked, same as for symbols
+            Type::BuiltInFn => check_res.ty = Type::Unit,
+            ty => {
+                let e = format!(
+                    "Can't call '{}' of type '{}', expected a function",
+                    callee, ty
+                );
+                return Err(TypeErrors::new_err(&e));
+            }
         }
-        // dbg!("fn_ty", fn_ty);
-        // check_res.ty = fn_ty;
 
         Ok(check_res)
     }
@@ -452,6 +995,98 @@ mod tests {
         expect_err(t, "Mismatched types in function call:", true);
     }
 
+    #[test]
+    fn test_type_check_call_expr() {
+        let t = r"
+        fn mk() -> fn(int) -> bool {
+            fn h(x: int) -> bool {
+                x < 2
+            }
+            h
+        }
+        mk()(3)
+        ";
+        expect_pass(t, Type::Bool);
+
+        let t = r"
+        fn f(x: int) -> int { x }
+        fn g(x: int) -> int { x }
+        (if true { f } else { g })(2)
+        ";
+        expect_pass(t, Type::Int);
+
+        let t = r"
+        fn mk() -> fn(int) -> bool {
+            fn h(x: int) -> bool {
+                x < 2
+            }
+            h
+        }
+        mk()(true)
+        ";
+        expect_err(
+            t,
+            "Mismatched types in function call: got ((bool)) but expected ((int))",
+            true,
+        );
+
+        let t = r"
+        fn f() -> int { 2 }
+        f()(3)
+        ";
+        expect_err(
+            t,
+            "Can't call 'f()' of type 'int', expected a function",
+            true,
+        );
+
+        // errors in callee and args are both reported
+        let t = r"
+        x()(y)
+        ";
+        expect_err(t, "Identifier 'x' not declared", true);
+        expect_err(t, "Identifier 'y' not declared", true);
+    }
+
+    #[test]
+    fn test_type_check_spawn_limited() {
+        let t = r"
+        fn f() {
+            loop {}
+        }
+        spawn_limited(f, 100)
+        ";
+        expect_pass(t, Type::ThreadId);
+
+        let t = r"
+        fn f(x: int) {
+        }
+        spawn_limited(f, 100)
+        ";
+        expect_err(
+            t,
+            "expects a function with no parameters and an int budget",
+            true,
+        );
+
+        let t = r"
+        fn f() {
+        }
+        spawn_limited(f, true)
+        ";
+        expect_err(
+            t,
+            "expects a function with no parameters and an int budget",
+            true,
+        );
+
+        expect_err(
+            "fn f() {} spawn_limited(f)",
+            "takes 2 arguments but 1 were supplied",
+            true,
+        );
+    }
+
     #[test]
     fn test_type_check_builtin_sym() {
         for &builtin in BUILTINS.iter() {
@@ -462,6 +1097,12 @@ mod tests {
     #[test]
     fn test_type_check_builtin_functions() {
         expect_pass("let x : () = print(2); x", Type::Unit);
+        expect_pass("let x : () = eprintln(2); x", Type::Unit);
+        expect_err(
+            "eprint(2, 3)",
+            "takes 1 arguments but 2 were supplied",
+            true,
+        );
 
         // Test min
         expect_pass("let x : int = min(2, 3); x", Type::Int);
@@ -493,6 +1134,36 @@ mod tests {
         // Test pow
         expect_pass("let x : float = pow(2.0, 3.0); x", Type::Float);
 
+        // Test rounding, exp and logs
+        for f in ["floor", "ceil", "round", "trunc", "exp", "log2", "log10"] {
+            expect_pass(&format!("let x : float = {}(2.5); x", f), Type::Float);
+            expect_err(&format!("{}(2)", f), "Mismatched types", true);
+        }
+
+        // Test atan2, hypot
+        expect_pass("let x : float = atan2(1.0, 2.0); x", Type::Float);
+        expect_pass("let x : float = hypot(3.0, 4.0); x", Type::Float);
+        expect_err("hypot(3.0)", "takes 2 arguments but 1 were supplied", true);
+
+        // Test clamp
+        expect_pass("let x : int = clamp(5, 0, 3); x", Type::Int);
+        expect_pass("let x : float = clamp(5.0, 0.0, 3.0); x", Type::Float);
+        expect_err(
+            "clamp(5, 0.0, 3)",
+            "Expected (int, int, int) or (float, float, float) but got (int, float, int)",
+            true,
+        );
+
+        // Test float classification
+        expect_pass("let x : bool = is_nan(NAN); x", Type::Bool);
+        expect_pass("let x : bool = is_finite(INFINITY); x", Type::Bool);
+        expect_err("is_nan(2)", "Mismatched types", true);
+
+        // Test int division
+        expect_pass("let x : int = div(7, 2); x", Type::Int);
+        expect_pass("let x : float = fdiv(4, 5); x", Type::Float);
+        expect_err("div(7.0, 2.0)", "Mismatched types", true);
+
         // Test itoa
         // expect_pass("let x : string = itoa(123); x", Type::String);
 
@@ -505,7 +1176,280 @@ mod tests {
         // Test int_to_float
         expect_pass("let x : float = int_to_float(3); x", Type::Float);
 
+        // Test clone
+        expect_pass("let x : str = clone(\"s\"); x", Type::String);
+        expect_pass("let x = sem_create(); clone(x)", Type::Semaphore);
+        expect_err("clone(1, 2)", "takes 1 arguments but 2 were supplied", true);
+
+        // Test type_of, dbg
+        expect_pass("let x : str = type_of(2); x", Type::String);
+        expect_pass("let x : float = dbg(2.0 + 3.0); x", Type::Float);
+        expect_err("dbg()", "takes 1 arguments but 0 were supplied", true);
+
+        // Test vars
+        expect_pass("let x : str = vars(); x", Type::String);
+        expect_err("vars(1)", "takes 0 arguments but 1 were supplied", true);
+
+        // Test json
+        expect_pass("let x : str = json_stringify(2.5); x", Type::String);
+        expect_pass("json_stringify(matrix(1, 1, 0.0))", Type::String);
+        expect_err(
+            "json_stringify(sem_create())",
+            "json_stringify can't encode a sem",
+            true,
+        );
+        expect_pass(r#"let x : str = json_get("[1]", "0"); x"#, Type::String);
+        expect_err(
+            r#"json_get("[1]", 0)"#,
+            "Mismatched types in function call",
+            true,
+        );
+
+        // Test exec, only a builtin with the exec feature
+        if cfg!(feature = "exec") {
+            expect_pass(r#"let x : int = exec("ls", 0); x"#, Type::Int);
+            expect_pass("let x : str = exec_stdout(); x", Type::String);
+            expect_err(
+                r#"exec("ls")"#,
+                "takes 2 arguments but 1 were supplied",
+                true,
+            );
+        } else {
+            expect_err(r#"exec("ls", 0)"#, "Identifier 'exec' not declared", true);
+        }
+
+        // Test parsing
+        expect_pass(r#"let x : int = parse_int("12"); x"#, Type::Int);
+        expect_pass(r#"let x : int = parse_int_radix("ff", 16); x"#, Type::Int);
+        expect_pass(r#"let x : float = parse_float("1.5"); x"#, Type::Float);
+        expect_pass("let x : bool = parse_failed(); x", Type::Bool);
+        expect_err("parse_int(12)", "Mismatched types in function call", true);
+        expect_err(
+            r#"parse_int_radix("ff")"#,
+            "takes 2 arguments but 1 were supplied",
+            true,
+        );
+
+        // Test formatting
+        expect_pass("let x : str = to_fixed(2.5, 2); x", Type::String);
+        expect_pass("let x : str = format_int(255, 16); x", Type::String);
+        expect_err("to_fixed(2, 2)", "Mismatched types in function call", true);
+        expect_pass("set_float_precision(2)", Type::Unit);
+        expect_err(
+            "set_float_precision(2.0)",
+            "Mismatched types in function call",
+            true,
+        );
+        expect_err(
+            "format_int(2.0, 2)",
+            "Mismatched types in function call",
+            true,
+        );
+
         // Test sem
         expect_pass("let x = sem_create(); x", Type::Semaphore);
+        expect_pass("let x = sem_create(3); x", Type::Semaphore);
+        expect_err(
+            "sem_create(true)",
+            "Mismatched types in function call",
+            true,
+        );
+        expect_err(
+            "sem_create(1, 2)",
+            "takes 1 arguments but 2 were supplied",
+            true,
+        );
+        expect_pass(
+            "let x = sem_create(); let y : () = sem_set(x, 2); y",
+            Type::Unit,
+        );
+        expect_err("sem_set(2, 2)", "Mismatched types in function call", true);
+        expect_pass(
+            "let x = sem_create(); let y : int = sem_value(x); y",
+            Type::Int,
+        );
+        expect_err("sem_value()", "takes 1 arguments but 0 were supplied", true);
+
+        // Test stdin
+        expect_pass("let s : str = read_all(); s", Type::String);
+        expect_pass("let done : bool = eof(); done", Type::Bool);
+        expect_err("eof(1)", "takes 0 arguments but 1 were supplied", true);
+
+        // Test queue
+        expect_pass("let q : queue = queue_create(); q", Type::Queue);
+        expect_pass(
+            "let q = queue_create(); let x : () = queue_push(q, 1); x",
+            Type::Unit,
+        );
+        expect_pass(
+            "let q = queue_create(); let x : int = queue_pop(q); x",
+            Type::Int,
+        );
+        expect_err(
+            "let q = queue_create(); queue_push(q, 1.0)",
+            "Mismatched types in function call",
+            true,
+        );
+        expect_err("queue_pop(2)", "Mismatched types in function call", true);
+        expect_pass("let q : queue = queue_create_bounded(4); q", Type::Queue);
+        expect_pass(
+            "let q = queue_create_bounded(1); let x : bool = queue_try_push(q, 1); x",
+            Type::Bool,
+        );
+        expect_pass(
+            "let q = queue_create(); let x : int = queue_try_pop(q, -1); x",
+            Type::Int,
+        );
+        expect_err(
+            "queue_create_bounded(true)",
+            "Mismatched types in function call",
+            true,
+        );
+        expect_err(
+            "queue_try_pop(queue_create())",
+            "takes 2 arguments but 1 were supplied",
+            true,
+        );
+
+        // Test map, filter, reduce
+        let t = r"
+        fn double(x: int) -> int { x * 2 }
+        fn big(x: int) -> bool { x > 2 }
+        fn add(acc: float, x: int) -> float { acc + int_to_float(x) }
+        let q = queue_create();
+        let doubled : queue = map(q, double);
+        let bigs : queue = filter(doubled, big);
+        reduce(bigs, 0.0, add)
+        ";
+        expect_pass(t, Type::Float);
+        expect_err(
+            "fn even(x: int) -> bool { true } map(queue_create(), even)",
+            "got ((queue, fn(int) -> bool)) but expected ((queue, fn(int) -> int))",
+            true,
+        );
+        expect_err(
+            "fn add(acc: int, x: int) -> int { acc + x } reduce(queue_create(), 0.0, add)",
+            "expected ((queue, float, fn(float, int) -> float))",
+            true,
+        );
+        expect_err(
+            "filter(queue_create(), abs)",
+            "Mismatched types in function call",
+            true,
+        );
+
+        // Test matrix
+        let t = r"
+        let a : matrix = matrix(2, 3, 0.0);
+        mat_set(a, 0, 1, 2.5);
+        let n : int = mat_rows(a) * mat_cols(a);
+        let c = matmul(a, matrix(3, 1, 1.0));
+        mat_get(c, 0, 0)
+        ";
+        expect_pass(t, Type::Float);
+        expect_err("matrix(2, 2, 0)", "Mismatched types in function call", true);
+        expect_err(
+            "mat_set(matrix(1, 1, 0.0), 0, 0, 1)",
+            "Mismatched types in function call",
+            true,
+        );
+
+        // Test join_timeout, is_alive
+        let t = r"
+        fn f() {}
+        let t = spawn f();
+        let alive : bool = is_alive(t);
+        join_timeout(t, 100)
+        ";
+        expect_pass(t, Type::Unit);
+        expect_err("is_alive(1)", "Mismatched types in function call", true);
+        expect_err(
+            "fn f() {} let t = spawn f(); join_timeout(t)",
+            "takes 2 arguments but 1 were supplied",
+            true,
+        );
+
+        // Test detach
+        expect_pass(
+            "fn f() {} let t = spawn f(); let x : () = detach(t); x",
+            Type::Unit,
+        );
+        expect_err("detach(1)", "Mismatched types in function call", true);
+
+        // Test cancel
+        expect_pass(
+            "fn f() {} let t = spawn f(); let x : () = cancel(t); x",
+            Type::Unit,
+        );
+        expect_err("cancel(1)", "Mismatched types in function call", true);
+
+        // Test current_tid, thread_count, sched_stats
+        expect_pass("current_tid()", Type::ThreadId);
+        expect_pass("let n : int = thread_count(); n", Type::Int);
+        expect_err(
+            "thread_count(1)",
+            "takes 0 arguments but 1 were supplied",
+            true,
+        );
+        expect_pass("let s : str = sched_stats(); s", Type::String);
+        expect_err(
+            "sched_stats(1)",
+            "takes 0 arguments but 1 were supplied",
+            true,
+        );
+
+        // Test exit
+        expect_pass("let x : () = exit(1); x", Type::Unit);
+        expect_err("exit(1.0)", "Mismatched types in function call", true);
+    }
+
+    #[test]
+    fn test_type_check_shadowed_builtin() {
+        // names the program declares shadow builtins
+        expect_pass("let print = 3; print + 1", Type::Int);
+        expect_pass("fn println(x: int) -> int { x + 1 } println(2)", Type::Int);
+        expect_err(
+            "fn println(x: int) -> int { x } println(true)",
+            "Mismatched types in function call",
+            true,
+        );
+        // only in their scope
+        expect_pass("{ let print = 3; } print(1); 2", Type::Int);
+        expect_err(
+            "let print = 3; print(2);",
+            "Can't call 'print' of type 'int', expected a function. The variable shadows the builtin function 'print', rename it to call the builtin",
+            true,
+        );
+        expect_err(
+            "fn f(abs: float) -> float { abs(abs) } f(1.0)",
+            "Can't call 'abs' of type 'float'",
+            true,
+        );
+        expect_err(
+            "let x = 3; x(1)",
+            "Can't call 'x' of type 'int', expected a function",
+            true,
+        );
+        expect_err("PI(1)", "Can't call 'PI' of type 'float'", true);
+
+        // builtins the compiler calls in a special way too
+        expect_pass("fn dbg(x: int) -> int { x * 2 } dbg(3)", Type::Int);
+        expect_pass("fn sem_create() -> int { 5 } sem_create()", Type::Int);
+        expect_pass(
+            "fn spawn_limited(x: int, y: int) -> int { x + y } spawn_limited(1, 2)",
+            Type::Int,
+        );
+
+        // a declaration shadows from the start of its block
+        expect_err(
+            "{ print(1); let print = 3; }",
+            "'print' is called before its declaration in the same block, which shadows the builtin function 'print' in the whole block",
+            true,
+        );
+        // a function declared before can only be called once it is declared
+        expect_pass(
+            "fn a() { println(1); } fn println(x: int) {} a(); 1",
+            Type::Int,
+        );
     }
 }
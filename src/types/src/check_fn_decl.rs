@@ -1,6 +1,7 @@
 use parser::structs::{FnDeclData, FnTypeData, Type};
 
-use crate::type_checker::{CheckResult, TypeChecker, TypeErrors};
+use crate::type_checker::{CheckResult, JumpCtx, TypeChecker, TypeErrors};
+use diagnostics::MISSING_TYPE_ANNOTATION;
 
 impl<'prog> TypeChecker<'prog> {
     pub(crate) fn check_fn_decl(
@@ -8,7 +9,11 @@ impl<'prog> TypeChecker<'prog> {
         fn_decl: &FnDeclData,
     ) -> Result<CheckResult, TypeErrors> {
         self.fn_type_stack.push(fn_decl.ret_type.clone());
+        self.fn_env_stack.push(self.envs.len());
+        self.loop_ctx_stack.push(JumpCtx::Fn);
         let res = self.check_fn_decl_inner(fn_decl);
+        self.loop_ctx_stack.pop();
+        self.fn_env_stack.pop();
         self.fn_type_stack.pop();
         res
     }
@@ -26,10 +31,20 @@ impl<'prog> TypeChecker<'prog> {
 
         for param in fn_decl.params.iter() {
             if let Some(ty) = &param.type_ann {
+                if let Some(default) = &param.default {
+                    let default_ty = self.check_expr(default)?.ty;
+                    if !default_ty.eq(ty) {
+                        let e = format!(
+                            "Default value for parameter '{}' has type '{}' but the parameter has type '{}'",
+                            param.name, default_ty, ty
+                        );
+                        return Err(TypeErrors::new_err(&e));
+                    }
+                }
                 param_types.push(ty.to_owned());
             } else {
                 let e = format!("Parameter '{}' has no type annotation", param.name);
-                return Err(TypeErrors::new_err(&e));
+                return Err(TypeErrors::with_code(MISSING_TYPE_ANNOTATION, &e));
             }
         }
 
@@ -95,6 +110,33 @@ mod tests {
 
     use crate::type_checker::{expect_err, expect_pass, expect_pass_str};
 
+    #[test]
+    fn test_type_check_return_outside_fn() {
+        let t = r"
+        loop {
+            fn f() {
+                loop { break; }
+                return;
+            }
+            return;
+        }
+        ";
+        expect_err(t, "'return' outside of a function", true);
+
+        let t = r"
+        return 2;
+        ";
+        expect_err(t, "'return' outside of a function", true);
+
+        let t = r"
+        fn f() -> int {
+            { return 2; }
+        }
+        f()
+        ";
+        expect_pass(t, Type::Int);
+    }
+
     #[test]
     fn test_type_check_fn_decl_simple() {
         let t = r"
@@ -130,6 +172,40 @@ mod tests {
         expect_pass_str(t, "fn(int)");
     }
 
+    #[test]
+    fn test_type_check_fn_defaults() {
+        let t = r#"
+        fn greet(name: str, greeting: str = "hello") -> str {
+            greeting
+        }
+        greet("x");
+        greet(greeting: "hi", name: "y")
+        "#;
+        expect_pass(t, Type::String);
+
+        let t = r"
+        fn f(x: int = true) {}
+        ";
+        expect_err(
+            t,
+            "Default value for parameter 'x' has type 'bool' but the parameter has type 'int'",
+            true,
+        );
+
+        // defaults are filled in before the call is checked
+        let t = r"
+        fn f(x: int, y: float = 1.5) -> float {
+            y
+        }
+        f(true)
+        ";
+        expect_err(
+            t,
+            "Mismatched types in function call: got ((bool, float)) but expected ((int, float))",
+            true,
+        );
+    }
+
     #[test]
     fn test_type_check_fn_decl_fails() {
         // param has no ty ann
@@ -195,8 +271,7 @@ mod tests {
         ";
         expect_pass(t, Type::Unit);
 
-        // // if only, loop are not must_ret
-        //     // although inf loop that would definitely return here, we are conservative
+        // if only is not must_ret, but a loop without cond that can't break only ends by returning
         let t = r"
         fn f() -> int {
             if true {
@@ -208,6 +283,42 @@ mod tests {
             }
         }
         ";
+        expect_pass(t, Type::Unit);
+
+        let t = r"
+        fn f() -> int {
+            loop {
+                if true {
+                    break;
+                }
+                return 30;
+            }
+        }
+        ";
+        expect_err(t, "might not return", true);
+
+        // a break in a nested loop only exits that loop
+        let t = r"
+        fn f(x: int) -> int {
+            loop {
+                loop {
+                    break;
+                }
+                let y = { if x < 2 { return x; } x * 2 };
+                x = y;
+            }
+        }
+        ";
+        expect_pass(t, Type::Unit);
+
+        // a conditional loop may not run
+        let t = r"
+        fn f() -> int {
+            loop true {
+                return 30;
+            }
+        }
+        ";
         expect_err(t, "might not return", true);
 
         // unit - don't have to must_return
@@ -1,10 +1,25 @@
 use crate::type_checker::{CheckResult, TypeChecker, TypeErrors};
-use parser::structs::LetStmtData;
+use diagnostics::SHADOWED_BINDING;
+use parser::structs::{LetStmtData, Type};
 
 impl<'prog> TypeChecker<'prog> {
     pub(crate) fn check_let(&mut self, stmt: &LetStmtData) -> Result<CheckResult, TypeErrors> {
         let mut ty_errs = TypeErrors::new();
 
+        // The block's symbols start uninitialised, so an initialised one was bound earlier in the block
+        let shadowed = self
+            .envs
+            .last()
+            .and_then(|env| env.get(&stmt.ident))
+            .is_some_and(|ty| !ty.eq(&Type::Unitialised));
+        if shadowed {
+            let warning = format!(
+                "'{}' shadows an earlier binding of '{}' in the same block",
+                stmt.ident, stmt.ident
+            );
+            self.warn(SHADOWED_BINDING, &warning);
+        }
+
         let mut expr_type: Option<CheckResult> = None;
         match self.check_expr(&stmt.expr) {
             Ok(res) => {
@@ -74,9 +89,37 @@ impl<'prog> TypeChecker<'prog> {
 
 #[cfg(test)]
 mod tests {
+    use diagnostics::SHADOWED_BINDING;
     use parser::structs::Type;
 
-    use crate::type_checker::{expect_err, expect_pass};
+    use crate::type_checker::{expect_err, expect_pass, expect_warnings};
+
+    #[test]
+    fn test_type_check_let_shadow_warnings() {
+        expect_warnings(
+            "let x = 2; let x = true; x",
+            &[(
+                SHADOWED_BINDING,
+                "'x' shadows an earlier binding of 'x' in the same block",
+            )],
+        );
+
+        // a nested block is a new scope
+        expect_warnings("let x = 2; { let x = true; x }", &[]);
+        expect_warnings("let x = 2; let y = 3; x + y", &[]);
+
+        // parameters are bound in the function's block
+        expect_warnings(
+            "fn f(x: int) -> int { let x = 2; x } f(1)",
+            &[(
+                SHADOWED_BINDING,
+                "'x' shadows an earlier binding of 'x' in the same block",
+            )],
+        );
+
+        // shadowing may change the type
+        expect_pass("let x = 2; let x = true; x", Type::Bool);
+    }
 
     #[test]
     fn test_type_check_sym_advanced() {
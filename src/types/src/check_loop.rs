@@ -1,5 +1,116 @@
-use crate::type_checker::{CheckResult, TypeChecker, TypeErrors};
-use parser::structs::{LoopData, Type};
+use crate::check_fn_call::{EXIT, SPAWN_LIMITED};
+use crate::type_checker::{CheckResult, JumpCtx, TypeChecker, TypeErrors};
+use diagnostics::ENDLESS_LOOP;
+use parser::structs::{BlockSeq, Decl, Expr, LoopData, Type};
+
+/// Whether a break in the block could exit the loop the block is the body of. Breaks in nested loops
+/// and functions don't count.
+fn may_break(blk: &BlockSeq) -> bool {
+    blk.decls.iter().any(decl_may_break) || blk.last_expr.as_deref().is_some_and(expr_may_break)
+}
+
+fn decl_may_break(decl: &Decl) -> bool {
+    match decl {
+        Decl::BreakStmt => true,
+        Decl::LetStmt(stmt) => expr_may_break(&stmt.expr),
+        Decl::AssignStmt(stmt) => expr_may_break(&stmt.expr),
+        Decl::ExprStmt(expr)
+        | Decl::ReturnStmt(Some(expr))
+        | Decl::WaitStmt(expr)
+        | Decl::PostStmt(expr) => expr_may_break(expr),
+        Decl::IfOnlyStmt(if_else) => {
+            expr_may_break(&if_else.cond)
+                || may_break(&if_else.if_blk)
+                || if_else.else_blk.as_ref().is_some_and(may_break)
+        }
+        Decl::LoopStmt(lp) => lp.cond.as_ref().is_some_and(expr_may_break),
+        // its body can't break, see check_parallel_loops
+        Decl::ParallelLoopStmt(lp) => expr_may_break(&lp.start) || expr_may_break(&lp.end),
+        Decl::ScopeStmt(blk) => may_break(blk),
+        Decl::SelectStmt(select) => {
+            select.arms.iter().any(|arm| {
+                arm.op.operands().into_iter().any(expr_may_break) || may_break(&arm.body)
+            }) || select.default.as_ref().is_some_and(may_break)
+        }
+        Decl::FnDeclStmt(_) | Decl::ReturnStmt(None) | Decl::YieldStmt => false,
+    }
+}
+
+fn expr_may_break(expr: &Expr) -> bool {
+    match expr {
+        Expr::BlockExpr(blk) => may_break(blk),
+        Expr::IfElseExpr(if_else) => {
+            expr_may_break(&if_else.cond)
+                || may_break(&if_else.if_blk)
+                || if_else.else_blk.as_ref().is_some_and(may_break)
+        }
+        Expr::UnOpExpr(_, expr) | Expr::CastExpr(expr, _) => expr_may_break(expr),
+        Expr::BinOpExpr(_, lhs, rhs) => expr_may_break(lhs) || expr_may_break(rhs),
+        Expr::FnCallExpr(call) | Expr::SpawnExpr(call) | Expr::SpawnIsolatedExpr(call) => {
+            call.args.iter().any(expr_may_break)
+        }
+        Expr::CallExpr(callee, args) => expr_may_break(callee) || args.iter().any(expr_may_break),
+        Expr::Symbol(_)
+        | Expr::Integer(_)
+        | Expr::Float(_)
+        | Expr::Bool(_)
+        | Expr::StringLiteral(_)
+        | Expr::JoinExpr(_) => false,
+    }
+}
+
+/// Whether the block returns, exits the program or spawns a thread, including in nested loops but not
+/// in nested functions. A loop without a break that does any of these is not obviously stuck.
+fn may_escape(blk: &BlockSeq) -> bool {
+    blk.decls.iter().any(decl_may_escape) || blk.last_expr.as_deref().is_some_and(expr_may_escape)
+}
+
+fn decl_may_escape(decl: &Decl) -> bool {
+    match decl {
+        Decl::ReturnStmt(_) => true,
+        Decl::LetStmt(stmt) => expr_may_escape(&stmt.expr),
+        Decl::AssignStmt(stmt) => expr_may_escape(&stmt.expr),
+        Decl::ExprStmt(expr) | Decl::WaitStmt(expr) | Decl::PostStmt(expr) => expr_may_escape(expr),
+        Decl::IfOnlyStmt(if_else) => {
+            expr_may_escape(&if_else.cond)
+                || may_escape(&if_else.if_blk)
+                || if_else.else_blk.as_ref().is_some_and(may_escape)
+        }
+        Decl::LoopStmt(lp) => lp.cond.as_ref().is_some_and(expr_may_escape) || may_escape(&lp.body),
+        // it spawns its workers
+        Decl::ParallelLoopStmt(_) => true,
+        Decl::ScopeStmt(blk) => may_escape(blk),
+        Decl::SelectStmt(select) => {
+            select.arms.iter().any(|arm| {
+                arm.op.operands().into_iter().any(expr_may_escape) || may_escape(&arm.body)
+            }) || select.default.as_ref().is_some_and(may_escape)
+        }
+        Decl::FnDeclStmt(_) | Decl::BreakStmt | Decl::YieldStmt => false,
+    }
+}
+
+fn expr_may_escape(expr: &Expr) -> bool {
+    match expr {
+        Expr::SpawnExpr(_) | Expr::SpawnIsolatedExpr(_) => true,
+        Expr::FnCallExpr(call) if call.name == EXIT || call.name == SPAWN_LIMITED => true,
+        Expr::FnCallExpr(call) => call.args.iter().any(expr_may_escape),
+        Expr::BlockExpr(blk) => may_escape(blk),
+        Expr::IfElseExpr(if_else) => {
+            expr_may_escape(&if_else.cond)
+                || may_escape(&if_else.if_blk)
+                || if_else.else_blk.as_ref().is_some_and(may_escape)
+        }
+        Expr::UnOpExpr(_, expr) | Expr::CastExpr(expr, _) => expr_may_escape(expr),
+        Expr::BinOpExpr(_, lhs, rhs) => expr_may_escape(lhs) || expr_may_escape(rhs),
+        Expr::CallExpr(callee, args) => expr_may_escape(callee) || args.iter().any(expr_may_escape),
+        Expr::Symbol(_)
+        | Expr::Integer(_)
+        | Expr::Float(_)
+        | Expr::Bool(_)
+        | Expr::StringLiteral(_)
+        | Expr::JoinExpr(_) => false,
+    }
+}
 
 impl<'prog> TypeChecker<'prog> {
     // if loop cond present, must be bool. else just check blks.
@@ -9,37 +120,33 @@ impl<'prog> TypeChecker<'prog> {
 
         // if condition: check has type bool. add errs if any
         if let Some(expr) = &loop_data.cond {
-            let check_cond = self.check_expr(expr);
-
-            match check_cond {
-                Ok(CheckResult {
-                    ty: Type::Bool,
-                    must_break: _,
-                    must_return: _,
-                }) => (),
-                Ok(ty) => {
-                    let e = format!(
-                        "Expected type '{}' for loop predicate but got '{}'",
-                        Type::Bool,
-                        ty.ty
-                    );
-                    ty_errs.add(&e);
-                }
-                Err(mut errs) => ty_errs.append(&mut errs),
+            if let Err(mut errs) = self.check_cond(expr, "loop") {
+                ty_errs.append(&mut errs);
             }
         }
 
+        self.loop_ctx_stack.push(JumpCtx::Loop);
         let mut check_blk = self.check_block(&loop_data.body, vec![]);
+        self.loop_ctx_stack.pop();
         if let Err(ref mut errs) = check_blk {
             ty_errs.append(errs);
         }
 
-        // TODO: a loop with no cond and no must_break in its block has must_return = true
+        // A loop with no cond that can't break only ends by returning, so code after it never runs
+        let must_return = loop_data.cond.is_none() && !may_break(&loop_data.body);
+
+        if must_return && !may_escape(&loop_data.body) {
+            self.warn(
+                ENDLESS_LOOP,
+                "Loop with no condition and no break never ends",
+            );
+        }
+
         if ty_errs.is_ok() {
             Ok(CheckResult {
                 ty: Type::Unit,
                 must_break: false, // loop never contributes to must_break of outer
-                must_return: false,
+                must_return,
             })
         } else {
             Err(ty_errs)
@@ -49,9 +156,10 @@ impl<'prog> TypeChecker<'prog> {
 
 #[cfg(test)]
 mod tests {
+    use diagnostics::ENDLESS_LOOP;
     use parser::structs::Type;
 
-    use crate::type_checker::{expect_err, expect_pass};
+    use crate::type_checker::{expect_err, expect_pass, expect_warnings};
 
     #[test]
     fn test_type_check_loop() {
@@ -99,6 +207,148 @@ mod tests {
         expect_pass(t, Type::Unit);
     }
 
+    #[test]
+    fn test_type_check_break_outside_loop() {
+        let t = r"
+        fn f() {
+            break;
+        }
+        ";
+        expect_err(t, "'break' outside of a loop", true);
+
+        let t = r"
+        fn f() {
+            loop {
+                break;
+            }
+            break;
+        }
+        ";
+        expect_err(t, "'break' outside of a loop", true);
+
+        let t = r"
+        loop {
+            fn f() {
+                break;
+            }
+        }
+        ";
+        expect_err(t, "'break' outside of a loop", true);
+
+        let t = r"
+        break;
+        ";
+        expect_err(t, "'break' outside of a loop", true);
+
+        let t = r"
+        {
+            break;
+        }
+        ";
+        expect_err(t, "'break' outside of a loop", true);
+
+        let t = r"
+        if true {
+            break;
+        }
+        ";
+        expect_err(t, "'break' outside of a loop", true);
+
+        let t = r"
+        if true {
+            2;
+        } else {
+            break;
+        }
+        ";
+        expect_err(t, "'break' outside of a loop", true);
+
+        let t = r"
+        loop {
+            let x = 0;
+            loop {
+                break;
+            }
+            break;
+        }
+        {
+            loop {
+                break;
+            }
+            break;
+        };
+        ";
+        expect_err(t, "'break' outside of a loop", true);
+
+        let t = r"
+        loop {
+            break;
+        }
+        break;
+        ";
+        expect_err(t, "'break' outside of a loop", true);
+
+        let t = r"
+        fn f() {
+            loop {
+                break;
+                return;
+            }
+        }
+        loop {
+            fn g() -> int {
+                loop { break; }
+                return 2;
+            }
+            break;
+        }
+        ";
+        expect_pass(t, Type::Unit);
+    }
+
+    #[test]
+    fn test_type_check_jump_out_of_scope() {
+        // a scope block must run to its end to join its threads
+        expect_err(
+            "loop { scope { break; } }",
+            "Can't break out of a scope block: it must run to its end to join the threads spawned in it",
+            true,
+        );
+        expect_err(
+            "loop { scope { if true { break; } } }",
+            "Can't break out of a scope block",
+            true,
+        );
+        expect_err(
+            "fn f() { scope { return; } }",
+            "Can't return from a scope block: it must run to its end to join the threads spawned in it",
+            true,
+        );
+        expect_err(
+            "fn f() { scope { loop { return; } } }",
+            "Can't return from a scope block",
+            true,
+        );
+        expect_err("scope { break; }", "'break' outside of a loop", true);
+
+        // loops and functions in the block can still be left
+        let t = r"
+        scope {
+            loop { break; }
+            fn f() -> int { return 2; }
+        }
+        loop {
+            scope { }
+            break;
+        }
+        fn g() -> int {
+            scope { }
+            return 2;
+        }
+        ";
+        expect_pass(t, Type::Unit);
+    }
+
     #[test]
     fn test_type_check_errs() {
         // cond has errs
@@ -117,7 +367,17 @@ mod tests {
         ";
         expect_err(
             t,
-            "Expected type 'bool' for loop predicate but got 'int'",
+            "Expected type 'bool' for loop condition '((2+2)-3)', got 'int'",
+            true,
+        );
+
+        let t = r"
+        let s = 3;
+        loop s { }
+        ";
+        expect_err(
+            t,
+            "Expected type 'bool' for loop condition 's', got 'int'",
             true,
         );
 
@@ -135,7 +395,7 @@ mod tests {
             2+false;
         }
         ";
-        expect_err(t,  "[TypeError]: Expected type 'bool' for loop predicate but got 'float'\n[TypeError]: Can't apply '+' to types 'int' and 'bool'", false);
+        expect_err(t,  "[TypeError]: Expected type 'bool' for loop condition '(2.2-3.6)', got 'float'\n[TypeError]: Can't apply '+' to types 'int' and 'bool'", false);
     }
 
     #[test]
@@ -193,4 +453,27 @@ mod tests {
             true,
         );
     }
+
+    #[test]
+    fn test_type_check_infinite_loop_warning() {
+        let never_ends = (
+            ENDLESS_LOOP,
+            "Loop with no condition and no break never ends",
+        );
+        expect_warnings("let x = 0; loop { x = x + 1; }", &[never_ends]);
+        // a break in a nested loop only ends that loop
+        expect_warnings("loop { loop { break; } }", &[never_ends]);
+        expect_warnings(
+            "fn f() { loop { fn g() { return; } yield; } }",
+            &[never_ends],
+        );
+
+        expect_warnings("loop { break; }", &[]);
+        expect_warnings("let x = 0; loop x < 3 { x = x + 1; }", &[]);
+        expect_warnings("fn f() -> int { loop { return 2; } } f()", &[]);
+        expect_warnings("fn f() { loop { loop { return; } } }", &[]);
+        expect_warnings("loop { exit(0); }", &[]);
+        expect_warnings("fn f() {} loop { spawn f(); }", &[]);
+        expect_warnings("fn f() {} loop { spawn_limited(f, 10); }", &[]);
+    }
 }
@@ -0,0 +1,455 @@
+use std::collections::HashMap;
+
+use crate::type_checker::{CheckResult, JumpCtx, TypeChecker, TypeErrors};
+use diagnostics::{MISPLACED_JUMP, SHARED_ASSIGNMENT};
+use parser::structs::{BlockSeq, Decl, Expr, FnDeclData, FnParam, ParallelLoopData, Type};
+
+/// Why a @parallel loop's iterations can't run at the same time: a diagnostic code and message
+pub type ParallelError = (&'static str, String);
+
+impl<'prog> TypeChecker<'prog> {
+    /*
+    0. The iterations were checked to be able to run at the same time before type checking the
+       program, see check_parallel_loops
+    1. Check the range's start and end are ints
+    2. Check the body with the loop variable bound to an int. Each iteration runs in a function of
+       its own, so the body can't break or return out of the loop
+    */
+    pub(crate) fn check_parallel_loop(
+        &mut self,
+        lp: &ParallelLoopData,
+    ) -> Result<CheckResult, TypeErrors> {
+        let mut ty_errs = TypeErrors::new();
+        for bound in [&lp.start, &lp.end] {
+            if let Err(mut errs) = self.check_operand(bound, "@parallel loop range", Type::Int) {
+                ty_errs.append(&mut errs);
+            }
+        }
+
+        let var = FnParam {
+            name: lp.var.clone(),
+            type_ann: Some(Type::Int),
+            default: None,
+        };
+        self.fn_type_stack.push(Type::Unit);
+        self.fn_env_stack.push(self.envs.len());
+        self.loop_ctx_stack.push(JumpCtx::Fn);
+        let check_blk = self.check_block(&lp.body, vec![var]);
+        self.loop_ctx_stack.pop();
+        self.fn_env_stack.pop();
+        self.fn_type_stack.pop();
+        if let Err(mut errs) = check_blk {
+            ty_errs.append(&mut errs);
+        }
+
+        if ty_errs.is_ok() {
+            Ok(CheckResult {
+                ty: Type::Unit,
+                must_break: false,
+                must_return: false,
+            })
+        } else {
+            Err(ty_errs)
+        }
+    }
+}
+
+/// Check the iterations of each @parallel loop in program can run at the same time: the body may
+/// only assign to variables it declares, including in functions it declares, and can't break or
+/// return out of the loop. Functions declared outside the body that it calls or refers to by name
+/// are followed, along with the functions they refer to, and may only assign to their own
+/// variables. A function the body gets some other way, e.g. as an argument of the function the
+/// loop is in, can't be followed and isn't checked. Returns the first error of each loop that
+/// fails.
+///
+/// The compiler checks this too, since it compiles programs that weren't type checked.
+pub fn check_parallel_loops(program: &BlockSeq) -> Vec<ParallelError> {
+    let mut check = ParallelCheck::default();
+    // outside a @parallel loop nothing fails, each loop's error is added to errs
+    let _ = check.block(program, vec![]);
+    check.errs
+}
+
+/// Walks a program keeping the names each enclosing block declares, checking the body of each
+/// @parallel loop it comes to
+#[derive(Default)]
+struct ParallelCheck<'a> {
+    // names declared by each enclosing block, with the declarations of those that are functions
+    scopes: Vec<HashMap<&'a str, Option<&'a FnDeclData>>>,
+    // the @parallel loop body being checked, if any
+    body: Option<Body<'a>>,
+    // functions declared outside a body that were found to only assign to their own variables, or
+    // are being checked, so recursion ends
+    followed: Vec<&'a FnDeclData>,
+    errs: Vec<ParallelError>,
+}
+
+/// Where in the body of a @parallel loop the check is
+#[derive(Clone, Copy)]
+struct Body<'a> {
+    // index of the first scope declared in the body, or in the function followed from it
+    base: usize,
+    // whether in a loop or function declared in the body
+    in_loop: bool,
+    in_fn: bool,
+    // the function declared outside the body that the body refers to, if following it
+    callee: Option<&'a str>,
+}
+
+impl<'a> ParallelCheck<'a> {
+    /// The index of the innermost scope declaring name, and the declaration if it is a function
+    fn lookup(&self, name: &str) -> Option<(usize, Option<&'a FnDeclData>)> {
+        self.scopes
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(idx, scope)| scope.get(name).map(|fn_decl| (idx, *fn_decl)))
+    }
+
+    /// Check blk in a scope declaring names, e.g. a function's parameters, and the block's own
+    fn block(&mut self, blk: &'a BlockSeq, names: Vec<&'a str>) -> Result<(), ParallelError> {
+        let mut scope: HashMap<&'a str, Option<&'a FnDeclData>> = names
+            .into_iter()
+            .chain(blk.symbols.iter().map(String::as_str))
+            .map(|name| (name, None))
+            .collect();
+        // a block declares its names from its start, so a function can be referred to before it
+        for decl in blk.decls.iter() {
+            if let Decl::FnDeclStmt(fn_decl) = decl {
+                scope.insert(&fn_decl.name, Some(fn_decl));
+            }
+        }
+
+        self.scopes.push(scope);
+        let res = blk
+            .decls
+            .iter()
+            .try_for_each(|decl| self.decl(decl))
+            .and_then(|_| match blk.last_expr.as_deref() {
+                Some(expr) => self.expr(expr),
+                None => Ok(()),
+            });
+        self.scopes.pop();
+        res
+    }
+
+    /// Run check with the body, if in one, changed by change
+    fn within(
+        &mut self,
+        change: fn(&mut Body<'a>),
+        check: impl FnOnce(&mut Self) -> Result<(), ParallelError>,
+    ) -> Result<(), ParallelError> {
+        let saved = self.body;
+        if let Some(body) = self.body.as_mut() {
+            change(body);
+        }
+        let res = check(self);
+        self.body = saved;
+        res
+    }
+
+    fn decl(&mut self, decl: &'a Decl) -> Result<(), ParallelError> {
+        let body = self.body;
+        match decl {
+            Decl::AssignStmt(stmt) => {
+                if let Some(body) = body {
+                    let in_body = self
+                        .lookup(&stmt.ident)
+                        .is_some_and(|(idx, _)| idx >= body.base);
+                    if !in_body {
+                        return Err(shared_assignment(&stmt.ident, body.callee));
+                    }
+                }
+                self.expr(&stmt.expr)
+            }
+            Decl::BreakStmt if body.is_some_and(|body| !body.in_loop && !body.in_fn) => Err((
+                MISPLACED_JUMP,
+                "Can't break out of a @parallel loop: its iterations run in threads of their own"
+                    .to_string(),
+            )),
+            Decl::ReturnStmt(_) if body.is_some_and(|body| !body.in_fn) => Err((
+                MISPLACED_JUMP,
+                "Can't return from a @parallel loop: its iterations run in threads of their own"
+                    .to_string(),
+            )),
+            Decl::LetStmt(stmt) => self.expr(&stmt.expr),
+            Decl::ExprStmt(expr)
+            | Decl::ReturnStmt(Some(expr))
+            | Decl::WaitStmt(expr)
+            | Decl::PostStmt(expr) => self.expr(expr),
+            Decl::IfOnlyStmt(if_else) => {
+                self.expr(&if_else.cond)?;
+                self.block(&if_else.if_blk, vec![])
+            }
+            Decl::ScopeStmt(blk) => self.block(blk, vec![]),
+            Decl::SelectStmt(select) => {
+                for arm in select.arms.iter() {
+                    for operand in arm.op.operands() {
+                        self.expr(operand)?;
+                    }
+                    // the popped value is bound like a parameter of the body
+                    let binding = arm.binding.iter().map(String::as_str).collect();
+                    self.block(&arm.body, binding)?;
+                }
+                match select.default.as_ref() {
+                    Some(default) => self.block(default, vec![]),
+                    None => Ok(()),
+                }
+            }
+            Decl::LoopStmt(lp) => {
+                if let Some(cond) = lp.cond.as_ref() {
+                    self.expr(cond)?;
+                }
+                self.within(
+                    |body| body.in_loop = true,
+                    |check| check.block(&lp.body, vec![]),
+                )
+            }
+            Decl::ParallelLoopStmt(lp) => self.parallel_loop(lp),
+            Decl::FnDeclStmt(fn_decl) => {
+                let params = fn_decl.params.iter().map(|p| p.name.as_str()).collect();
+                self.within(
+                    |body| {
+                        body.in_loop = false;
+                        body.in_fn = true;
+                    },
+                    |check| check.block(&fn_decl.body, params),
+                )
+            }
+            Decl::ReturnStmt(None) | Decl::BreakStmt | Decl::YieldStmt => Ok(()),
+        }
+    }
+
+    fn expr(&mut self, expr: &'a Expr) -> Result<(), ParallelError> {
+        match expr {
+            Expr::UnOpExpr(_, e) | Expr::CastExpr(e, _) => self.expr(e),
+            Expr::BinOpExpr(_, lhs, rhs) => {
+                self.expr(lhs)?;
+                self.expr(rhs)
+            }
+            Expr::BlockExpr(blk) => self.block(blk, vec![]),
+            Expr::IfElseExpr(if_else) => {
+                self.expr(&if_else.cond)?;
+                self.block(&if_else.if_blk, vec![])?;
+                match if_else.else_blk.as_ref() {
+                    Some(else_blk) => self.block(else_blk, vec![]),
+                    None => Ok(()),
+                }
+            }
+            Expr::CallExpr(callee, args) => {
+                self.expr(callee)?;
+                args.iter().try_for_each(|arg| self.expr(arg))
+            }
+            Expr::FnCallExpr(call) | Expr::SpawnExpr(call) | Expr::SpawnIsolatedExpr(call) => {
+                call.args
+                    .iter()
+                    .chain(call.named_args.iter().map(|(_, arg)| arg))
+                    .try_for_each(|arg| self.expr(arg))?;
+                self.follow(&call.name)
+            }
+            // a function can be called through a variable or by a builtin like map
+            Expr::Symbol(name) => self.follow(name),
+            Expr::Integer(_)
+            | Expr::Float(_)
+            | Expr::Bool(_)
+            | Expr::StringLiteral(_)
+            | Expr::JoinExpr(_) => Ok(()),
+        }
+    }
+
+    /// Check the body of a @parallel loop, adding its error if it has one. Its range is evaluated
+    /// before the iterations start.
+    fn parallel_loop(&mut self, lp: &'a ParallelLoopData) -> Result<(), ParallelError> {
+        self.expr(&lp.start)?;
+        self.expr(&lp.end)?;
+        // a loop in a function followed from another loop's body is checked where it's declared
+        if self.body.is_some_and(|body| body.callee.is_some()) {
+            return Ok(());
+        }
+
+        let saved = self.body.replace(Body {
+            base: self.scopes.len(),
+            in_loop: false,
+            in_fn: false,
+            callee: None,
+        });
+        let res = self.block(&lp.body, vec![&lp.var]);
+        self.body = saved;
+        if let Err(err) = res {
+            self.errs.push(err);
+        }
+        Ok(())
+    }
+
+    /// Check the function name refers to, if the body being checked refers to it and it is
+    /// declared outside the body: it runs in each iteration, so may only assign to its own
+    /// variables.
+    fn follow(&mut self, name: &'a str) -> Result<(), ParallelError> {
+        let Some(body) = self.body else {
+            return Ok(());
+        };
+        let Some((idx, Some(fn_decl))) = self.lookup(name) else {
+            return Ok(());
+        };
+        let followed = self.followed.iter().any(|f| std::ptr::eq(*f, fn_decl));
+        if idx >= body.base || followed {
+            return Ok(());
+        }
+
+        self.followed.push(fn_decl);
+        // the function sees the names declared where it is, not those of the blocks in between
+        let inner = self.scopes.split_off(idx + 1);
+        let saved = self.body.replace(Body {
+            base: self.scopes.len(),
+            in_loop: false,
+            in_fn: true,
+            callee: body.callee.or(Some(name)),
+        });
+        let params = fn_decl.params.iter().map(|p| p.name.as_str()).collect();
+        let res = self.block(&fn_decl.body, params);
+        self.body = saved;
+        self.scopes.extend(inner);
+
+        if res.is_err() {
+            self.followed.retain(|f| !std::ptr::eq(*f, fn_decl));
+        }
+        res
+    }
+}
+
+fn shared_assignment(ident: &str, callee: Option<&str>) -> ParallelError {
+    let e = match callee {
+        Some(callee) => format!(
+            "A @parallel loop can't call '{}', which assigns to '{}' declared outside the loop: its iterations run at the same time and would race on it",
+            callee, ident
+        ),
+        None => format!(
+            "A @parallel loop can't assign to '{}', which is declared outside it: its iterations run at the same time and would race on it",
+            ident
+        ),
+    };
+    (SHARED_ASSIGNMENT, e)
+}
+
+#[cfg(test)]
+mod tests {
+    use diagnostics::{ToDiagnostics, MISPLACED_JUMP, SHARED_ASSIGNMENT, TYPE_ERROR};
+    use parser::structs::Type;
+
+    use crate::type_checker::{expect_err, expect_pass, parse_resolved, TypeChecker};
+
+    fn err_code(inp: &str) -> String {
+        let prog = parse_resolved(inp);
+        let err = TypeChecker::new(&prog)
+            .type_check()
+            .expect_err("Should err");
+        err.to_diagnostics()[0].code.clone()
+    }
+
+    #[test]
+    fn test_type_check_parallel_loop() {
+        expect_pass(
+            "let n = 10; @parallel loop i in 0..n { let x = i * 2; x = x + 1; } n",
+            Type::Int,
+        );
+        // variables, loops and functions of the body's own
+        let t = r"
+        @parallel loop i in 1..2 * 3 {
+            let x = 0;
+            loop x < i {
+                x = x + 1;
+                if x == 3 { break; }
+            }
+            fn g(y: int) -> int { y = y + 1; return y; }
+            i = g(x);
+        }
+        ";
+        expect_pass(t, Type::Unit);
+
+        // functions it calls that only assign to their own variables, recursive ones too
+        let t = r"
+        let total = 0;
+        fn fact(n: int) -> int {
+            let res = 1;
+            if n > 1 { res = n * fact(n - 1); }
+            res
+        }
+        fn sq(x: int) -> int { x = x * x; x }
+        @parallel loop i in 0..5 { let x = sq(fact(i)); }
+        total = fact(3);
+        total
+        ";
+        expect_pass(t, Type::Int);
+
+        expect_err(
+            "@parallel loop i in 0..2.5 {}",
+            "Expected type 'int' for @parallel loop range operand '2.5', got 'float'",
+            true,
+        );
+        expect_err(
+            "@parallel loop i in 0..2 { let b : bool = i; }",
+            "'b' has declared type bool but assigned type int",
+            true,
+        );
+        assert_eq!(err_code("@parallel loop i in 0..2.5 {}"), TYPE_ERROR);
+    }
+
+    #[test]
+    fn test_type_check_parallel_loop_shared() {
+        expect_err(
+            "let sum = 0; @parallel loop i in 0..10 { sum = sum + i; }",
+            "A @parallel loop can't assign to 'sum', which is declared outside it: its iterations run at the same time and would race on it",
+            true,
+        );
+        // also in a function declared in the body
+        expect_err(
+            "let sum = 0; @parallel loop i in 0..10 { fn f() { sum = 1; } f(); }",
+            "can't assign to 'sum'",
+            true,
+        );
+        // and in functions declared outside it that it calls, directly or not
+        expect_err(
+            "let s = 0; fn inc() { s = s + 1; } @parallel loop i in 0..100 { inc(); }",
+            "A @parallel loop can't call 'inc', which assigns to 's' declared outside the loop: its iterations run at the same time and would race on it",
+            true,
+        );
+        expect_err(
+            "let s = 0; fn g() { inc(); } fn inc() { s = 1; } @parallel loop i in 0..2 { g(); }",
+            "can't call 'g', which assigns to 's'",
+            true,
+        );
+        expect_err(
+            "let s = 0; fn inc() { s = 1; } @parallel loop i in 0..2 { let f = inc; f(); }",
+            "can't call 'inc', which assigns to 's'",
+            true,
+        );
+        expect_err(
+            "let s = 0; fn inc() { s = 1; } @parallel loop i in 0..2 { let t = spawn inc(); join t; }",
+            "can't call 'inc', which assigns to 's'",
+            true,
+        );
+        expect_err(
+            "@parallel loop i in 0..10 { if i == 2 { break; } }",
+            "Can't break out of a @parallel loop: its iterations run in threads of their own",
+            true,
+        );
+        expect_err(
+            "fn f() { @parallel loop i in 0..10 { return; } }",
+            "Can't return from a @parallel loop",
+            true,
+        );
+
+        assert_eq!(
+            err_code("let sum = 0; @parallel loop i in 0..10 { sum = i; }"),
+            SHARED_ASSIGNMENT
+        );
+        assert_eq!(
+            err_code("let s = 0; fn f() { s = 1; } @parallel loop i in 0..2 { f(); }"),
+            SHARED_ASSIGNMENT
+        );
+        assert_eq!(
+            err_code("loop { @parallel loop i in 0..10 { break; } }"),
+            MISPLACED_JUMP
+        );
+    }
+}
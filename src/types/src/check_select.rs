@@ -0,0 +1,122 @@
+use crate::type_checker::{CheckResult, TypeChecker, TypeErrors};
+use parser::structs::{FnParam, QueueOp, SelectData, Type};
+
+impl<'prog> TypeChecker<'prog> {
+    /*
+    0. Check each arm's queue is a queue, and the value it pushes an int
+    1. Check each arm's body, with the popped int bound if the arm has a let, and the default arm's
+    2. A select is a statement, so it is always Unit. Exactly one arm runs, so it must break or
+       return only if every arm, including the default, does
+    */
+    pub(crate) fn check_select(&mut self, select: &SelectData) -> Result<CheckResult, TypeErrors> {
+        let mut ty_errs = TypeErrors::new();
+        let mut must_break = true;
+        let mut must_return = true;
+
+        for arm in select.arms.iter() {
+            let operands = match &arm.op {
+                QueueOp::Pop(q) => vec![(q, Type::Queue)],
+                QueueOp::Push(q, v) => vec![(q, Type::Queue), (v, Type::Int)],
+            };
+            for (operand, exp) in operands {
+                if let Err(mut errs) = self.check_operand(operand, "select", exp) {
+                    ty_errs.append(&mut errs);
+                }
+            }
+
+            let binding = arm
+                .binding
+                .iter()
+                .map(|name| FnParam {
+                    name: name.clone(),
+                    type_ann: Some(Type::Int),
+                    default: None,
+                })
+                .collect();
+            match self.check_block(&arm.body, binding) {
+                Ok(res) => {
+                    must_break = must_break && res.must_break;
+                    must_return = must_return && res.must_return;
+                }
+                Err(mut errs) => ty_errs.append(&mut errs),
+            }
+        }
+
+        if let Some(default) = &select.default {
+            match self.check_block(default, vec![]) {
+                Ok(res) => {
+                    must_break = must_break && res.must_break;
+                    must_return = must_return && res.must_return;
+                }
+                Err(mut errs) => ty_errs.append(&mut errs),
+            }
+        }
+
+        if ty_errs.is_ok() {
+            Ok(CheckResult {
+                ty: Type::Unit,
+                must_break,
+                must_return,
+            })
+        } else {
+            Err(ty_errs)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::structs::Type;
+
+    use crate::type_checker::{expect_err, expect_pass};
+
+    #[test]
+    fn test_type_check_select() {
+        let t = r"
+        let q = queue_create();
+        let r = queue_create_bounded(1);
+        let n = 0;
+        select {
+            let x = queue_pop(q) => { n = x + 1; }
+            queue_push(r, n) => { n = 2; }
+            default => { n = 3; }
+        }
+        n
+        ";
+        expect_pass(t, Type::Int);
+
+        // every arm returns, so the function does
+        let t = r"
+        fn f(q: queue) -> int {
+            select {
+                let x = queue_pop(q) => { return x; }
+                default => { return 0; }
+            }
+        }
+        f(queue_create())
+        ";
+        expect_pass(t, Type::Int);
+
+        // the binding is only in its arm
+        expect_err(
+            "let q = queue_create(); select { let x = queue_pop(q) => {} } x",
+            "Identifier 'x' not declared",
+            true,
+        );
+        expect_err(
+            "select { queue_pop(2) => {} }",
+            "Expected type 'queue' for select operand '2', got 'int'",
+            true,
+        );
+        expect_err(
+            "let q = queue_create(); select { queue_push(q, true) => {} }",
+            "Expected type 'int' for select operand 'true', got 'bool'",
+            true,
+        );
+        expect_err(
+            "let q = queue_create(); select { let x = queue_pop(q) => { let y: bool = x; } }",
+            "'y' has declared type bool but assigned type int",
+            true,
+        );
+    }
+}
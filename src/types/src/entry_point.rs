@@ -0,0 +1,110 @@
+use std::rc::Rc;
+
+use parser::structs::{BlockSeq, Decl, Expr, FnCallData, Type};
+
+use crate::type_checker::TypeErrors;
+use diagnostics::INVALID_MAIN;
+
+/// Name of the function a program starts at, if it declares one
+pub const MAIN_FN: &str = "main";
+
+/// If the program declares fn main at the top level, call it after the top level declarations
+/// have run: main's result, if it has one, becomes the result of the program.
+///
+/// main takes no parameters, and since its result is the program's, the program can't end in an
+/// expression of its own.
+pub fn call_main(program: &mut BlockSeq) -> Result<(), TypeErrors> {
+    let main = program
+        .decls
+        .iter()
+        .enumerate()
+        .find_map(|(idx, decl)| match decl {
+            Decl::FnDeclStmt(fn_decl) if fn_decl.name == MAIN_FN => Some((idx, fn_decl)),
+            _ => None,
+        });
+
+    let Some((idx, main)) = main else {
+        return Ok(());
+    };
+
+    if !main.params.is_empty() {
+        let e = format!("fn {} can't take parameters", MAIN_FN);
+        return Err(TypeErrors::with_code(INVALID_MAIN, &e));
+    }
+
+    if let Some(expr) = &program.last_expr {
+        let e = format!(
+            "The program declares fn {} so its result is the result of {}, it can't end in the expression '{}'",
+            MAIN_FN, MAIN_FN, expr
+        );
+        return Err(TypeErrors::with_code(INVALID_MAIN, &e));
+    }
+
+    let call = Expr::FnCallExpr(FnCallData {
+        name: MAIN_FN.to_string(),
+        args: vec![],
+        named_args: vec![],
+    });
+    // a main without a result is a statement, so the program has no result to print either
+    if main.ret_type == Type::Unit {
+        program.decls.push(Decl::ExprStmt(call));
+    } else {
+        program.last_expr = Some(Rc::new(call));
+    }
+
+    // errors calling main are reported on the line it is declared on
+    if let Some(line) = program.lines.get(idx).copied() {
+        program.lines.push(line);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::Parser;
+
+    use super::call_main;
+
+    fn expect_main(inp: &str, exp: &str) {
+        let mut prog = Parser::new_from_string(inp).parse().expect("Should parse");
+        call_main(&mut prog).expect("Should add the call to main");
+        assert_eq!(prog.to_string(), exp);
+    }
+
+    fn expect_main_err(inp: &str, exp_err: &str) {
+        let mut prog = Parser::new_from_string(inp).parse().expect("Should parse");
+        let err = call_main(&mut prog).expect_err("Should err");
+        assert_eq!(err.to_string(), format!("[TypeError]: {}", exp_err));
+    }
+
+    #[test]
+    fn test_call_main() {
+        expect_main(
+            "let x = 2; fn main() -> int { x }",
+            "let x = 2;fn main () -> int { x };main()",
+        );
+        // only a top level main is called
+        expect_main(
+            "fn f() { fn main() {} } 1",
+            "fn f () { fn main () {  }; };1",
+        );
+        expect_main("let main = 2; main", "let main = 2;main");
+        expect_main("fn main() {}", "fn main () {  };main();");
+
+        let mut prog = Parser::new_from_string("let x = 1;\n\nfn main() {\n}")
+            .parse()
+            .unwrap();
+        call_main(&mut prog).unwrap();
+        assert_eq!(prog.lines, vec![1, 3, 3]);
+    }
+
+    #[test]
+    fn test_call_main_err() {
+        expect_main_err("fn main(x: int) {}", "fn main can't take parameters");
+        expect_main_err(
+            "fn main() {} main()",
+            "The program declares fn main so its result is the result of main, it can't end in the expression 'main()'",
+        );
+    }
+}
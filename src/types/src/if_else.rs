@@ -13,22 +13,8 @@ impl<'prog> TypeChecker<'prog> {
         if_else: &IfElseData,
     ) -> Result<CheckResult, TypeErrors> {
         let mut ty_errs = TypeErrors::new();
-        let check_cond = self.check_expr(&if_else.cond);
-
-        // check predicate for errs and add. no errs -> check type is bool
-        if let Err(mut errs) = check_cond {
+        if let Err(mut errs) = self.check_cond(&if_else.cond, "if") {
             ty_errs.append(&mut errs);
-        } else {
-            let check_cond = check_cond.unwrap();
-            if !check_cond.ty.eq(&Type::Bool) {
-                // add cond is not bool err
-                let e = format!(
-                    "Expected type '{}' for if condition, got '{}'",
-                    Type::Bool,
-                    check_cond.ty
-                );
-                ty_errs.add(&e);
-            }
         }
 
         // add if blk errs
@@ -182,7 +168,18 @@ mod tests {
             20;
         }
         ";
-        expect_err(t, "Expected type 'bool' for if condition, got 'int'", true);
+        expect_err(
+            t,
+            "Expected type 'bool' for if condition '(2+2)', got 'int'",
+            true,
+        );
+
+        let t = r"if 3 { 20 } else { 30 }";
+        expect_err(
+            t,
+            "Expected type 'bool' for if condition '3', got 'int'",
+            true,
+        );
 
         // cond has err when if-else types match
         let t = r"let x = 2; let y = 3; if !x == y { 20 } else { 30 }";
@@ -199,7 +196,7 @@ mod tests {
             30+false;
         }
         ";
-        expect_err(t,  "[TypeError]: Expected type 'bool' for if condition, got 'int'\n[TypeError]: 'x' has declared type bool but assigned type float\n[TypeError]: Can't apply '+' to types 'int' and 'bool'", false);
+        expect_err(t,  "[TypeError]: Expected type 'bool' for if condition '(2+2)', got 'int'\n[TypeError]: 'x' has declared type bool but assigned type float\n[TypeError]: Can't apply '+' to types 'int' and 'bool'", false);
 
         // multiple errs in blks
         let t = r"
@@ -211,7 +208,7 @@ mod tests {
             2.56+2;
         }
         ";
-        expect_err(t,  "[TypeError]: Expected type 'bool' for if condition, got 'int'\n[TypeError]: 'x' has declared type bool but assigned type float\n[TypeError]: Can't apply '+' to types 'int' and 'bool'\n[TypeError]: Can't apply '+' to types 'int' and 'bool'\n[TypeError]: Can't apply '+' to types 'float' and 'int'", false);
+        expect_err(t,  "[TypeError]: Expected type 'bool' for if condition '(2+2)', got 'int'\n[TypeError]: 'x' has declared type bool but assigned type float\n[TypeError]: Can't apply '+' to types 'int' and 'bool'\n[TypeError]: Can't apply '+' to types 'int' and 'bool'\n[TypeError]: Can't apply '+' to types 'float' and 'int'", false);
 
         // cond + else err
         let t = r"
@@ -221,7 +218,7 @@ mod tests {
             30+false;
         }
         ";
-        expect_err(t, "[TypeError]: Expected type 'bool' for if condition, got 'int'\n[TypeError]: Can't apply '+' to types 'int' and 'bool'", false);
+        expect_err(t, "[TypeError]: Expected type 'bool' for if condition '(2+2)', got 'int'\n[TypeError]: Can't apply '+' to types 'int' and 'bool'", false);
 
         // cond + if err
         let t = r"
@@ -231,7 +228,7 @@ mod tests {
              300;
          }
          ";
-        expect_err(t, "[TypeError]: Expected type 'bool' for if condition, got 'int'\n[TypeError]: Can't apply '+' to types 'int' and 'float'", false);
+        expect_err(t, "[TypeError]: Expected type 'bool' for if condition '(2+2)', got 'int'\n[TypeError]: Can't apply '+' to types 'int' and 'float'", false);
     }
 
     #[test]
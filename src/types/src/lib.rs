@@ -1,7 +1,12 @@
 pub mod blk;
+pub mod call_args;
 pub mod check_fn_call;
 pub mod check_fn_decl;
 pub mod check_let;
 pub mod check_loop;
+pub mod check_parallel;
+pub mod check_select;
+pub mod entry_point;
 pub mod if_else;
 pub mod type_checker;
+mod unused;
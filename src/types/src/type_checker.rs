@@ -1,11 +1,19 @@
+use diagnostics::{
+    Diagnostic, Stage, ToDiagnostics, MISPLACED_JUMP, MISSING_TYPE_ANNOTATION, TYPE_ERROR,
+    UNDECLARED_NAME,
+};
 use parser::{structs::*, Parser};
 use std::{collections::HashMap, fmt::Display};
 
 use parser::structs::{BlockSeq, Decl, Expr, Type};
 
+use crate::call_args::resolve_call_args;
+use crate::check_parallel::check_parallel_loops;
+
 #[derive(Debug, PartialEq)]
 pub struct TypeErrors {
-    pub(crate) errs: Vec<String>,
+    // diagnostic code and message of each error
+    pub(crate) errs: Vec<(&'static str, String)>,
     pub(crate) cont: bool,
 }
 
@@ -17,9 +25,15 @@ impl TypeErrors {
         }
     }
 
+    /// An error about a value of the wrong type
     pub fn new_err(err: &str) -> TypeErrors {
+        TypeErrors::with_code(TYPE_ERROR, err)
+    }
+
+    /// An error of another kind, with its diagnostic code
+    pub fn with_code(code: &'static str, err: &str) -> TypeErrors {
         TypeErrors {
-            errs: vec![err.to_string()],
+            errs: vec![(code, err.to_string())],
             cont: true,
         }
     }
@@ -29,7 +43,7 @@ impl TypeErrors {
     }
 
     pub fn add(&mut self, err: &str) {
-        self.errs.push(err.to_string());
+        self.errs.push((TYPE_ERROR, err.to_string()));
     }
 
     /// Move errors from the other into this one, leaving the other empty
@@ -47,7 +61,7 @@ impl Display for TypeErrors {
         let string = self
             .errs
             .iter()
-            .map(|x| format!("[TypeError]: {}", x))
+            .map(|(_, x)| format!("[TypeError]: {}", x))
             .collect::<Vec<String>>()
             .join("\n");
         write!(f, "{}", string)
@@ -56,8 +70,41 @@ impl Display for TypeErrors {
 
 impl std::error::Error for TypeErrors {}
 
+impl ToDiagnostics for TypeErrors {
+    fn to_diagnostics(&self) -> Vec<Diagnostic> {
+        self.errs
+            .iter()
+            .map(|(code, err)| Diagnostic::error(Stage::Type, code, err))
+            .collect()
+    }
+}
+
 type Env = HashMap<String, Type>;
 
+/// Added to the error for comparing an int with a float. The VM gives the same advice.
+const INT_FLOAT_CMP_HINT: &str = ". Convert one side with 'as float' or 'as int' to compare them";
+
+// Constants set in the global environment by the VM (see bytecode::Environment::new_global_wrapped)
+const BUILTIN_CONSTANTS: [(&str, Type); 9] = [
+    ("PI", Type::Float),
+    ("E", Type::Float),
+    ("MAX_INT", Type::Int),
+    ("MIN_INT", Type::Int),
+    ("MAX_FLOAT", Type::Float),
+    ("MIN_FLOAT", Type::Float),
+    ("EPSILON", Type::Float),
+    ("NAN", Type::Float),
+    ("INFINITY", Type::Float),
+];
+
+/// Outermost env, enclosing the program, that holds the builtin constants
+fn new_builtin_constants_env() -> Env {
+    BUILTIN_CONSTANTS
+        .iter()
+        .map(|(sym, ty)| (sym.to_string(), ty.to_owned()))
+        .collect()
+}
+
 pub fn new_env_with_syms(syms: Vec<String>) -> Env {
     let mut env: Env = HashMap::new();
     for sym in syms.iter() {
@@ -87,38 +134,72 @@ impl CheckResult {
     }
 }
 
+/// What a break or return is nested in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum JumpCtx {
+    Loop,
+    Fn,
+    // a scope block, which must run to its end to join the threads spawned in it
+    Scope,
+}
+
 /// Struct to enable type checking by encapsulating type environment.
 pub struct TypeChecker<'prog> {
     program: &'prog BlockSeq,
     pub(crate) envs: Vec<Env>,
     // stores type of function currently being checked at top (empty if not checking function)
     pub(crate) fn_type_stack: Vec<Type>,
+    // index in envs of the body of each enclosing function, innermost at top
+    pub(crate) fn_env_stack: Vec<usize>,
+    // enclosing loop, function and scope block bodies, innermost at top. break is only allowed in a
+    // loop of the current function, and neither break nor return can leave a scope block
+    pub(crate) loop_ctx_stack: Vec<JumpCtx>,
+    // non-fatal problems found so far, e.g a let shadowing a binding in the same block
+    pub(crate) warnings: Vec<Diagnostic>,
+    // warn about expression statements that do nothing, see check_unused_result
+    pub(crate) warn_unused_results: bool,
 }
 
 impl<'prog> TypeChecker<'prog> {
     pub fn new(program: &BlockSeq) -> TypeChecker<'_> {
         TypeChecker {
             program,
-            envs: vec![],
+            envs: vec![new_builtin_constants_env()],
             fn_type_stack: vec![],
+            fn_env_stack: vec![],
+            loop_ctx_stack: vec![],
+            warnings: vec![],
+            warn_unused_results: true,
         }
     }
 
+    /// Turn the warning for expression statements whose result is unused, like `x == 3;`, on or off
+    pub fn set_warn_unused_results(&mut self, on: bool) {
+        self.warn_unused_results = on;
+    }
+
     /// Return type of identifier by looking up nested scopes, or error if not there.
+    /// Names the program declares shadow the builtin functions.
     pub(crate) fn get_type(&self, ident: &str) -> Result<Type, TypeErrors> {
-        if TypeChecker::is_builtin_fn(ident) {
-            return Ok(Type::BuiltInFn);
+        if let Some((_, ty)) = self.lookup(ident) {
+            return Ok(ty.to_owned());
         }
 
-        for env in self.envs.iter().rev() {
-            let ty = env.get(ident);
-            if let Some(ty) = ty {
-                return Ok(ty.to_owned());
-            }
+        if TypeChecker::is_builtin_fn(ident) {
+            return Ok(Type::BuiltInFn);
         }
 
         let e = format!("Identifier '{}' not declared", ident);
-        Err(TypeErrors::new_err(&e))
+        Err(TypeErrors::with_code(UNDECLARED_NAME, &e))
+    }
+
+    /// The index in envs of the innermost scope declaring ident, and its type there
+    pub(crate) fn lookup(&self, ident: &str) -> Option<(usize, &Type)> {
+        self.envs
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(idx, env)| env.get(ident).map(|ty| (idx, ty)))
     }
 
     /// Returns type of identifier if initialised. If identifier doesn't exist or still uninit, returns Error.
@@ -127,7 +208,7 @@ impl<'prog> TypeChecker<'prog> {
         let ty = self.get_type(ident)?;
         if ty.eq(&Type::Unitialised) {
             let e = format!("Identifier '{}' assigned before declaration", ident);
-            Err(TypeErrors::new_err(&e))
+            Err(TypeErrors::with_code(UNDECLARED_NAME, &e))
         } else {
             Ok(ty)
         }
@@ -146,6 +227,55 @@ impl<'prog> TypeChecker<'prog> {
 
     /// Put param string and type into last env without checking if it's there
     // For use in fn_decl
+    /// Check the condition of an if or loop, named by construct, is a bool.
+    /// The error names the condition, since there are no source locations yet.
+    pub(crate) fn check_cond(&mut self, cond: &Expr, construct: &str) -> Result<(), TypeErrors> {
+        let cond_res = self.check_expr(cond)?;
+
+        if !cond_res.ty.eq(&Type::Bool) {
+            let e = format!(
+                "Expected type '{}' for {} condition '{}', got '{}'",
+                Type::Bool,
+                construct,
+                cond,
+                cond_res.ty
+            );
+            return Err(TypeErrors::new_err(&e));
+        }
+
+        Ok(())
+    }
+
+    /// Check the operand of a wait, post, join, select arm or @parallel loop range, named by
+    /// construct, has type exp.
+    pub(crate) fn check_operand(
+        &mut self,
+        operand: &Expr,
+        construct: &str,
+        exp: Type,
+    ) -> Result<CheckResult, TypeErrors> {
+        let ty = self.check_expr(operand)?.ty;
+
+        if !ty.eq(&exp) {
+            // say where a value of the expected type comes from
+            let hint = match exp {
+                Type::Semaphore => ". Create a semaphore with sem_create()",
+                _ => "",
+            };
+            let e = format!(
+                "Expected type '{}' for {} operand '{}', got '{}'{}",
+                exp, construct, operand, ty, hint
+            );
+            return Err(TypeErrors::new_err(&e));
+        }
+
+        Ok(CheckResult {
+            ty: Type::Unit,
+            must_break: false,
+            must_return: false,
+        })
+    }
+
     pub(crate) fn assign_param_types(&mut self, params: Vec<FnParam>) -> Result<(), TypeErrors> {
         let mut ty_errs = TypeErrors::new();
 
@@ -157,7 +287,7 @@ impl<'prog> TypeChecker<'prog> {
                     }
                     None => {
                         let e = format!("Parameter '{}' has no type annotation", param.name);
-                        ty_errs.add(&e);
+                        ty_errs.append(&mut TypeErrors::with_code(MISSING_TYPE_ANNOTATION, &e));
                     }
                 };
             }
@@ -176,7 +306,7 @@ impl<'prog> TypeChecker<'prog> {
         expr: &Expr,
     ) -> Result<CheckResult, TypeErrors> {
         match op {
-            UnOpType::Negate => {
+            UnOpType::Negate | UnOpType::Plus => {
                 // Return err imm if operand itself is not well typed
                 let check_res = self.check_expr(expr)?;
                 match check_res.ty {
@@ -189,6 +319,10 @@ impl<'prog> TypeChecker<'prog> {
 
                         Ok(res)
                     }
+                    _ if matches!(op, UnOpType::Plus) => {
+                        let e = format!("Can't apply unary plus to type {}", check_res.ty);
+                        Err(TypeErrors::new_err(&e))
+                    }
                     _ => {
                         let e = format!("Can't negate type {}", check_res.ty);
                         Err(TypeErrors::new_err(&e))
@@ -216,6 +350,31 @@ impl<'prog> TypeChecker<'prog> {
         }
     }
 
+    /// Numeric casts between int and float, bool to int, and casts of a primitive to its own type
+    pub(crate) fn check_cast(&mut self, expr: &Expr, ty: &Type) -> Result<CheckResult, TypeErrors> {
+        let check_res = self.check_expr(expr)?;
+        let allowed = matches!(
+            (&check_res.ty, ty),
+            (Type::Int | Type::Float, Type::Int | Type::Float)
+                | (Type::Bool, Type::Int | Type::Bool)
+                | (Type::ThreadId, Type::Int)
+        );
+
+        if !allowed {
+            let e = format!(
+                "Can't cast '{}' of type '{}' to '{}'",
+                expr, check_res.ty, ty
+            );
+            return Err(TypeErrors::new_err(&e));
+        }
+
+        Ok(CheckResult {
+            ty: ty.clone(),
+            must_break: check_res.must_break,
+            must_return: check_res.must_return,
+        })
+    }
+
     // Add, Sub, Mul, Div where allowed are (int, int) and (float, float)
     fn check_math_ops(
         op: &BinOpType,
@@ -283,22 +442,43 @@ impl<'prog> TypeChecker<'prog> {
         let l_type = l_type?;
         let r_type = r_type?;
 
-        let err = format!(
+        let mut err = format!(
             "Can't apply '{}' to types '{}' and '{}'",
             op, l_type.ty, r_type.ty
         );
 
+        // ints and floats are never converted implicitly, so 2 == 2.0 doesn't compare numbers
+        let is_cmp = !matches!(
+            op,
+            BinOpType::Add
+                | BinOpType::Sub
+                | BinOpType::Div
+                | BinOpType::Mul
+                | BinOpType::LogicalOr
+                | BinOpType::LogicalAnd
+        );
+        if is_cmp
+            && matches!(
+                (&l_type.ty, &r_type.ty),
+                (Type::Int, Type::Float) | (Type::Float, Type::Int)
+            )
+        {
+            err.push_str(INT_FLOAT_CMP_HINT);
+        }
+
         let err: Result<_, TypeErrors> = Err(TypeErrors::new_err(&err));
 
         match op {
             BinOpType::Add | BinOpType::Sub | BinOpType::Div | BinOpType::Mul => {
                 TypeChecker::check_math_ops(op, &l_type, &r_type)
             }
-            // (num, num) => bool
-            BinOpType::Gt | BinOpType::Lt => {
+            // (num, num) => bool, (str, str) => bool
+            BinOpType::Gt | BinOpType::Lt | BinOpType::Ge | BinOpType::Le => {
                 if matches!(
                     (l_type.ty, r_type.ty),
-                    (Type::Int, Type::Int) | (Type::Float, Type::Float)
+                    (Type::Int, Type::Int)
+                        | (Type::Float, Type::Float)
+                        | (Type::String, Type::String)
                 ) {
                     // Ok(Type::Bool)
                     let res = CheckResult {
@@ -327,8 +507,17 @@ impl<'prog> TypeChecker<'prog> {
                     err
                 }
             }
-            // (t, t) => bool
+            // (t, t) => bool, except functions, which the VM can't compare either
             BinOpType::LogicalEq => {
+                let is_fn = |ty: &Type| matches!(ty, Type::UserFn(_) | Type::BuiltInFn);
+                if is_fn(&l_type.ty) || is_fn(&r_type.ty) {
+                    let e = format!(
+                        "Can't compare functions: '{} {} {}' has types '{}' and '{}'. Compare what they return instead",
+                        lhs, op, rhs, l_type.ty, r_type.ty
+                    );
+                    return Err(TypeErrors::new_err(&e));
+                }
+
                 if l_type.ty.eq(&r_type.ty) {
                     let res = CheckResult {
                         ty: Type::Bool,
@@ -388,7 +577,9 @@ impl<'prog> TypeChecker<'prog> {
             Expr::BlockExpr(blk) => return self.check_block(blk, vec![]),
             Expr::IfElseExpr(if_else) => return self.check_if_else(if_else),
             Expr::FnCallExpr(fn_call) => return self.check_fn_call(fn_call),
-            Expr::SpawnExpr(fn_call) => {
+            Expr::CallExpr(callee, args) => return self.check_call_expr(callee, args),
+            Expr::CastExpr(expr, ty) => return self.check_cast(expr, ty),
+            Expr::SpawnExpr(fn_call) | Expr::SpawnIsolatedExpr(fn_call) => {
                 self.check_fn_call(fn_call)?;
                 CheckResult {
                     ty: Type::ThreadId,
@@ -398,11 +589,9 @@ impl<'prog> TypeChecker<'prog> {
             }
             // TODO: return join type based on function that was called
             // Need to track spawn / join calls at compile time
-            Expr::JoinExpr(_) => CheckResult {
-                ty: Type::Unit,
-                must_break: false,
-                must_return: false,
-            },
+            Expr::JoinExpr(sym) => {
+                return self.check_operand(&Expr::Symbol(sym.to_owned()), "join", Type::ThreadId)
+            }
         };
 
         if local_errs.is_ok() {
@@ -418,7 +607,11 @@ impl<'prog> TypeChecker<'prog> {
         match decl {
             Decl::LetStmt(stmt) => self.check_let(stmt),
             // Type check the expr and return any errors
-            Decl::ExprStmt(expr) => self.check_expr(expr),
+            Decl::ExprStmt(expr) => {
+                let res = self.check_expr(expr)?;
+                self.check_unused_result(expr);
+                Ok(res)
+            }
             // Check if sym is declared already. Then check expr matches type at decl
             Decl::AssignStmt(stmt) => {
                 let sym_ty = self.get_type_if_init(&stmt.ident.to_owned())?;
@@ -442,7 +635,38 @@ impl<'prog> TypeChecker<'prog> {
             }
             Decl::IfOnlyStmt(if_else) => self.check_if_else(if_else),
             Decl::LoopStmt(lp) => self.check_loop(lp),
+            Decl::ParallelLoopStmt(lp) => self.check_parallel_loop(lp),
+            // a scope block is a statement, whatever its last expression
+            Decl::ScopeStmt(blk) => {
+                self.loop_ctx_stack.push(JumpCtx::Scope);
+                let res = self.check_block(blk, vec![]);
+                self.loop_ctx_stack.pop();
+                let res = res?;
+                Ok(CheckResult {
+                    ty: Type::Unit,
+                    ..res
+                })
+            }
+            Decl::SelectStmt(select) => self.check_select(select),
             Decl::BreakStmt => {
+                let mut ctxs = self.loop_ctx_stack.iter().rev();
+                let in_scope = ctxs.clone().next() == Some(&JumpCtx::Scope);
+                match ctxs.find(|ctx| **ctx != JumpCtx::Scope) {
+                    Some(JumpCtx::Loop) if in_scope => {
+                        return Err(TypeErrors::with_code(
+                            MISPLACED_JUMP,
+                            "Can't break out of a scope block: it must run to its end to join the threads spawned in it",
+                        ));
+                    }
+                    Some(JumpCtx::Loop) => (),
+                    _ => {
+                        return Err(TypeErrors::with_code(
+                            MISPLACED_JUMP,
+                            "'break' outside of a loop",
+                        ));
+                    }
+                }
+
                 // must_break base case
                 Ok(CheckResult {
                     ty: Type::Unit,
@@ -451,7 +675,6 @@ impl<'prog> TypeChecker<'prog> {
                 })
             }
             Decl::FnDeclStmt(fn_decl) => self.check_fn_decl(fn_decl),
-            // TODO: check nested returns with fn stack
             Decl::ReturnStmt(ret_expr) => {
                 // dbg!("fn_stack at return:", &self.fn_type_stack);
                 let mut res = CheckResult {
@@ -469,11 +692,24 @@ impl<'prog> TypeChecker<'prog> {
                 // now it's either unit or the type of the ret_expr
                 // return type must match fn annotated
 
-                // expect because parser rejects return outside function
-                let fn_ty = self
-                    .fn_type_stack
-                    .last()
-                    .expect("Should have type in fn_stack");
+                let Some(fn_ty) = self.fn_type_stack.last() else {
+                    return Err(TypeErrors::with_code(
+                        MISPLACED_JUMP,
+                        "'return' outside of a function",
+                    ));
+                };
+                let in_scope = self
+                    .loop_ctx_stack
+                    .iter()
+                    .rev()
+                    .take_while(|ctx| **ctx != JumpCtx::Fn)
+                    .any(|ctx| *ctx == JumpCtx::Scope);
+                if in_scope {
+                    return Err(TypeErrors::with_code(
+                        MISPLACED_JUMP,
+                        "Can't return from a scope block: it must run to its end to join the threads spawned in it",
+                    ));
+                }
                 if !res.ty.eq(fn_ty) {
                     let e = format!(
                         "Expected function return type '{}' but return statement has type '{}'",
@@ -484,16 +720,8 @@ impl<'prog> TypeChecker<'prog> {
 
                 Ok(res)
             }
-            Decl::WaitStmt(_) => Ok(CheckResult {
-                ty: Type::Unit,
-                must_break: false,
-                must_return: false,
-            }),
-            Decl::PostStmt(_) => Ok(CheckResult {
-                ty: Type::Unit,
-                must_break: false,
-                must_return: false,
-            }),
+            Decl::WaitStmt(sem) => self.check_operand(sem, "wait", Type::Semaphore),
+            Decl::PostStmt(sem) => self.check_operand(sem, "post", Type::Semaphore),
             Decl::YieldStmt => Ok(CheckResult {
                 ty: Type::Unit,
                 must_break: false,
@@ -504,10 +732,31 @@ impl<'prog> TypeChecker<'prog> {
         // Ok(())
     }
 
-    pub fn type_check(mut self) -> Result<Type, TypeErrors> {
-        let ty = self.check_block(self.program, vec![])?;
-        // dbg!(&ty);
-        Ok(ty.ty)
+    pub fn type_check(self) -> Result<Type, TypeErrors> {
+        self.type_check_with_warnings().0
+    }
+
+    /// Add a warning with its diagnostic code
+    pub(crate) fn warn(&mut self, code: &str, warning: &str) {
+        self.warnings
+            .push(Diagnostic::warning(Stage::Type, code, warning));
+    }
+
+    /// Type check like type_check, also returning the warnings found, even if there were errors.
+    /// @parallel loops whose iterations can't run at the same time fail before anything is
+    /// type checked.
+    pub fn type_check_with_warnings(mut self) -> (Result<Type, TypeErrors>, Vec<Diagnostic>) {
+        let parallel = check_parallel_loops(self.program);
+        if !parallel.is_empty() {
+            let errs = TypeErrors {
+                errs: parallel,
+                cont: true,
+            };
+            return (Err(errs), self.warnings);
+        }
+
+        let res = self.check_block(self.program, vec![]).map(|res| res.ty);
+        (res, self.warnings)
     }
 }
 
@@ -517,16 +766,36 @@ impl Default for TypeErrors {
     }
 }
 
+/// Parse with call arguments resolved, as the compile pipeline does
+pub(crate) fn parse_resolved(inp: &str) -> BlockSeq {
+    let mut prog = Parser::new_from_string(inp).parse().expect("Should parse");
+    resolve_call_args(&mut prog).expect("Should resolve call arguments");
+    prog
+}
+
 pub fn expect_pass(inp: &str, exp_type: Type) {
-    let prog = Parser::new_from_string(inp).parse().expect("Should parse");
+    let prog = parse_resolved(inp);
     let ty = TypeChecker::new(&prog).type_check();
     dbg!(&ty);
     assert_eq!(Ok(exp_type), ty)
 }
 
+/// Expect type check to pass with exactly these warnings, each a code and message
+pub fn expect_warnings(inp: &str, exp_warnings: &[(&str, &str)]) {
+    let prog = parse_resolved(inp);
+    let (ty, warnings) = TypeChecker::new(&prog).type_check_with_warnings();
+    dbg!(&ty, &warnings);
+    assert!(ty.is_ok());
+    let warnings: Vec<_> = warnings
+        .iter()
+        .map(|warning| (warning.code.as_str(), warning.message.as_str()))
+        .collect();
+    assert_eq!(warnings, exp_warnings)
+}
+
 /// To expect type str
 pub fn expect_pass_str(inp: &str, exp_type_str: &str) {
-    let prog = Parser::new_from_string(inp).parse().expect("Should parse");
+    let prog = parse_resolved(inp);
     let ty = TypeChecker::new(&prog)
         .type_check()
         .expect("Type check should pass");
@@ -536,7 +805,7 @@ pub fn expect_pass_str(inp: &str, exp_type_str: &str) {
 
 // contains true means check if input contains exp_err. else check full equals
 pub fn expect_err(inp: &str, exp_err: &str, contains: bool) {
-    let prog = Parser::new_from_string(inp).parse().expect("Should parse");
+    let prog = parse_resolved(inp);
     dbg!(&prog);
     let ty_err = TypeChecker::new(&prog)
         .type_check()
@@ -553,9 +822,44 @@ pub fn expect_err(inp: &str, exp_err: &str, contains: bool) {
 
 #[cfg(test)]
 mod tests {
-    use super::{expect_err, expect_pass};
+    use super::{expect_err, expect_pass, parse_resolved, TypeChecker};
+    use crate::entry_point::call_main;
+    use diagnostics::*;
     use parser::structs::Type;
 
+    #[test]
+    fn test_type_error_codes() {
+        let code = |inp: &str| {
+            let mut prog = parse_resolved(inp);
+            let err = match call_main(&mut prog) {
+                Ok(()) => TypeChecker::new(&prog)
+                    .type_check()
+                    .expect_err("Should err"),
+                Err(err) => err,
+            };
+            err.to_diagnostics()[0].code.clone()
+        };
+
+        assert_eq!(code("let x : int = true;"), TYPE_ERROR);
+        assert_eq!(code("x + 1"), UNDECLARED_NAME);
+        assert_eq!(code("x = 1; let x = 2;"), UNDECLARED_NAME);
+        assert_eq!(code("fn f(x) {}"), MISSING_TYPE_ANNOTATION);
+        assert_eq!(code("fn f(x: int) {} f(1, 2);"), WRONG_ARGUMENTS);
+        assert_eq!(code("break;"), MISPLACED_JUMP);
+        assert_eq!(code("return;"), MISPLACED_JUMP);
+        assert_eq!(code("fn main(x: int) {}"), INVALID_MAIN);
+    }
+
+    #[test]
+    fn test_type_check_builtin_constants() {
+        expect_pass("let x : float = PI * E; x", Type::Float);
+        expect_pass("MAX_INT", Type::Int);
+        expect_pass("let y : float = NAN + INFINITY; y", Type::Float);
+        // can shadow
+        expect_pass("let PI = 3; PI", Type::Int);
+        expect_err("let x : int = EPSILON;", "[TypeError]", true);
+    }
+
     #[test]
     fn test_type_check_basic() {
         // Primitives
@@ -607,6 +911,11 @@ mod tests {
         expect_pass("let x : float = 2.33; -x", Type::Float);
         expect_pass("let x : float = 2.33; -x;", Type::Unit);
 
+        // Unary plus
+        expect_pass("let x : int = 20; +x", Type::Int);
+        expect_pass("+2.5 * 2.0", Type::Float);
+        expect_err("+true", "Can't apply unary plus to type bool", true);
+
         // Not
         expect_pass("let x : bool = true; !x", Type::Bool);
         expect_err("let x : int = 20; !x", "NOT to type int", true);
@@ -684,7 +993,7 @@ mod tests {
 
     #[test]
     fn test_type_check_binops_cmp() {
-        // ==, >, <
+        // ==, >, <, >=, <=
 
         // eq
         expect_pass("true == false", Type::Bool);
@@ -714,6 +1023,57 @@ mod tests {
             true,
         );
 
+        // >=, <=
+        expect_pass("2 >= 3", Type::Bool);
+        expect_pass("2.5 <= 3.2", Type::Bool);
+        expect_err(
+            "true >= false",
+            "Can't apply '>=' to types 'bool' and 'bool'",
+            true,
+        );
+        expect_err(
+            "2 <= 3.0",
+            "Can't apply '<=' to types 'int' and 'float'",
+            true,
+        );
+
+        // int and float are compared after an explicit conversion
+        expect_err(
+            "2 == 2.0",
+            "[TypeError]: Can't apply '==' to types 'int' and 'float'. Convert one side with 'as float' or 'as int' to compare them",
+            false,
+        );
+        expect_err(
+            "let x = 1.5; x > 1",
+            "Can't apply '>' to types 'float' and 'int'. Convert one side",
+            true,
+        );
+        expect_pass("2 as float == 2.0", Type::Bool);
+        expect_pass("let x = 1.5; x as int > 1", Type::Bool);
+
+        // functions have no equality
+        expect_err(
+            "fn f() {} fn g() {} f == g",
+            "[TypeError]: Can't compare functions: 'f == g' has types 'fn()' and 'fn()'. Compare what they return instead",
+            false,
+        );
+        expect_err(
+            "fn f(x: int) -> int { x } f == 1",
+            "Can't compare functions: 'f == 1'",
+            true,
+        );
+        expect_err("println == print", "Can't compare functions", true);
+        expect_pass("fn f(x: int) -> int { x } f(1) == 1", Type::Bool);
+
+        // strings are ordered
+        expect_pass(r#""ab" < "b""#, Type::Bool);
+        expect_pass(r#""ab" >= "b""#, Type::Bool);
+        expect_err(
+            r#""2" < 3"#,
+            "Can't apply '<' to types 'str' and 'int'",
+            true,
+        );
+
         // mix
         expect_pass("false == (3 > 5)", Type::Bool);
         expect_err(
@@ -758,4 +1118,87 @@ mod tests {
         let t = r"let t = sem_create(); t";
         expect_pass(t, Type::Semaphore);
     }
+
+    #[test]
+    fn test_type_check_wait_post() {
+        expect_pass("let s = sem_create(); post s; wait s;", Type::Unit);
+        expect_pass(
+            "fn f(s: sem) -> sem { wait s; post s; s } let s : sem = f(sem_create()); s",
+            Type::Semaphore,
+        );
+
+        expect_err(
+            "let s = 2; wait s;",
+            "Expected type 'sem' for wait operand 's', got 'int'. Create a semaphore with sem_create()",
+            true,
+        );
+        expect_err(
+            "fn s() {} post s;",
+            "Expected type 'sem' for post operand 's', got 'fn()'. Create a semaphore with sem_create()",
+            true,
+        );
+        expect_pass(
+            "let locks = sem_create(); fn lock() -> sem { locks } wait lock(); post { lock() };",
+            Type::Unit,
+        );
+        expect_err(
+            "wait 2 + 2;",
+            "Expected type 'sem' for wait operand '(2+2)', got 'int'. Create a semaphore with sem_create()",
+            true,
+        );
+        expect_err("wait s;", "Identifier 's' not declared", true);
+        expect_err(
+            "let q = queue_create(); post q;",
+            "Expected type 'sem' for post operand 'q', got 'queue'. Create a semaphore with sem_create()",
+            true,
+        );
+    }
+
+    #[test]
+    fn test_type_check_join() {
+        expect_pass("fn f() {} let t = spawn f(); join t; t as int", Type::Int);
+        expect_pass(
+            "fn f(t: tid) { join t; } let t = spawn f(current_tid()); t",
+            Type::ThreadId,
+        );
+
+        expect_err(
+            "let t = 2; join t;",
+            "Expected type 'tid' for join operand 't', got 'int'",
+            true,
+        );
+        expect_err(
+            "fn f() {} let t = spawn f(); t + 1",
+            "Can't apply '+' to types 'tid' and 'int'",
+            true,
+        );
+    }
+
+    #[test]
+    fn test_type_check_cast() {
+        expect_pass("2 as float", Type::Float);
+        expect_pass("2.5 as int", Type::Int);
+        expect_pass("true as int", Type::Int);
+        expect_pass("let x = 2; x as int", Type::Int);
+        expect_pass("-2.5 as int * 2", Type::Int);
+        expect_pass("1.5 + 2 as float", Type::Float);
+
+        expect_err(
+            "true as float",
+            "Can't cast 'true' of type 'bool' to 'float'",
+            true,
+        );
+        expect_err("2 as bool", "Can't cast '2' of type 'int' to 'bool'", true);
+        expect_err(
+            r#""2" as int"#,
+            "Can't cast '2' of type 'str' to 'int'",
+            true,
+        );
+        expect_err("2 as str", "Can't cast '2' of type 'int' to 'str'", true);
+        expect_err(
+            "(1 + 2.5) as int",
+            "Can't apply '+' to types 'int' and 'float'",
+            true,
+        );
+    }
 }
@@ -0,0 +1,87 @@
+use diagnostics::UNUSED_RESULT;
+use parser::structs::{BinOpType, Expr};
+
+use crate::type_checker::TypeChecker;
+
+impl<'prog> TypeChecker<'prog> {
+    /// Warn about an expression statement whose value is computed and thrown away without doing
+    /// anything else, like `2 + 2;`, or `x == 3;` where `x = 3;` was meant.
+    /// Binding the value with `let _ = ...` says it is unused on purpose.
+    pub(crate) fn check_unused_result(&mut self, expr: &Expr) {
+        if !self.warn_unused_results || !is_pure(expr) {
+            return;
+        }
+
+        let warning = match expr {
+            Expr::BinOpExpr(BinOpType::LogicalEq, lhs, rhs) if matches!(**lhs, Expr::Symbol(_)) => {
+                format!(
+                    "The result of '{}' is unused. Did you mean to assign with '{} = {}'?",
+                    expr, lhs, rhs
+                )
+            }
+            _ => format!(
+                "The result of '{}' is unused, so the statement does nothing. Use 'let _ = ...' if this is intended",
+                expr
+            ),
+        };
+        self.warn(UNUSED_RESULT, &warning);
+    }
+}
+
+/// Whether evaluating expr has no effect besides giving its value: literals, names and operators on
+/// them. Calls, blocks, ifs, spawn and join may do something else, so they aren't pure.
+fn is_pure(expr: &Expr) -> bool {
+    match expr {
+        Expr::Integer(_)
+        | Expr::Float(_)
+        | Expr::Bool(_)
+        | Expr::StringLiteral(_)
+        | Expr::Symbol(_) => true,
+        Expr::UnOpExpr(_, expr) | Expr::CastExpr(expr, _) => is_pure(expr),
+        Expr::BinOpExpr(_, lhs, rhs) => is_pure(lhs) && is_pure(rhs),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::type_checker::{expect_warnings, parse_resolved, TypeChecker};
+    use diagnostics::UNUSED_RESULT;
+
+    #[test]
+    fn test_type_check_unused_result() {
+        expect_warnings(
+            "let x = 2; x == 3; x",
+            &[(
+                UNUSED_RESULT,
+                "The result of '(x==3)' is unused. Did you mean to assign with 'x = 3'?",
+            )],
+        );
+        expect_warnings(
+            "2 + 2; 1",
+            &[(
+                UNUSED_RESULT,
+                "The result of '(2+2)' is unused, so the statement does nothing. Use 'let _ = ...' if this is intended",
+            )],
+        );
+        expect_warnings(
+            "let x = 1.5; -x as int; 1",
+            &[(
+                UNUSED_RESULT,
+                "The result of '((-x) as int)' is unused, so the statement does nothing. Use 'let _ = ...' if this is intended",
+            )],
+        );
+
+        // statements that may do something, or whose value is used
+        expect_warnings("fn f() -> int { 2 } f(); 1", &[]);
+        expect_warnings("let _ = 2 + 2; let x = 1; x = 2; x == 2", &[]);
+        expect_warnings("{ 2 }; if true { 1 } else { 2 }; 3", &[]);
+
+        let prog = parse_resolved("2 + 2; 1");
+        let mut checker = TypeChecker::new(&prog);
+        checker.set_warn_unused_results(false);
+        let (ty, warnings) = checker.type_check_with_warnings();
+        assert!(ty.is_ok());
+        assert!(warnings.is_empty());
+    }
+}
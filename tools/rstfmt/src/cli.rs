@@ -0,0 +1,89 @@
+//! The rstfmt command line, also run by rustscript fmt.
+
+use anyhow::{Error, Result};
+use clap::{CommandFactory, FromArgMatches};
+use std::{ffi::OsString, path::Path};
+
+use crate::{format_source, FormatError};
+
+const RST: &str = "rst";
+
+#[derive(clap::Parser, Debug)]
+#[command(name = "rstfmt")]
+#[command(version = "0.1.0")]
+#[command(about = "Formatter for RustScript", long_about = None)]
+struct Args {
+    /// Files containing RustScript code. Must have extension .rst
+    #[arg(required = true)]
+    files: Vec<String>,
+
+    /// Overwrite the files with the formatted code instead of printing it
+    #[arg(short, long)]
+    write: bool,
+
+    /// Do not change anything, exit with an error if any file is not formatted
+    #[arg(short, long, conflicts_with = "write")]
+    check: bool,
+}
+
+fn read_rst(file: &str) -> Result<String> {
+    let path = Path::new(file);
+
+    if !path.exists() {
+        let err = format!("File '{}' does not exist", file);
+        return Err(FormatError::new(&err).into());
+    }
+
+    if path.extension().is_none_or(|ext| ext != RST) {
+        let err = format!("File {} does not have extension .{RST}", file);
+        return Err(FormatError::new(&err).into());
+    }
+
+    Ok(std::fs::read_to_string(path)?)
+}
+
+/// Run the formatter with command line args, the first of which is the program name. Usage and
+/// help show bin_name as the program.
+pub fn main<I, T>(bin_name: &str, args: I) -> Result<()>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    let matches = Args::command()
+        .bin_name(bin_name.to_owned())
+        .get_matches_from(args);
+    let args = Args::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+    let mut unformatted: Vec<&str> = vec![];
+
+    for file in args.files.iter() {
+        let code = read_rst(file)?;
+
+        let formatted = match format_source(&code) {
+            Ok(formatted) => formatted,
+            Err(err) => {
+                let e = format!("\n{}: {}", file, err);
+                return Err(Error::msg(e));
+            }
+        };
+
+        if args.check {
+            if formatted != code {
+                unformatted.push(file);
+            }
+        } else if args.write {
+            if formatted != code {
+                std::fs::write(file, formatted)?;
+                println!("Formatted {}", file);
+            }
+        } else {
+            print!("{}", formatted);
+        }
+    }
+
+    if !unformatted.is_empty() {
+        let e = format!("\nNot formatted:\n{}", unformatted.join("\n"));
+        return Err(Error::msg(e));
+    }
+
+    Ok(())
+}
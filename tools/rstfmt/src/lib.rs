@@ -0,0 +1,446 @@
+use anyhow::Result;
+use std::fmt::Display;
+use std::ops::Range;
+
+use lexer::{lex, Token};
+use parser::Parser;
+
+pub mod cli;
+pub mod trivia;
+
+use trivia::{Trivia, TriviaItem};
+
+const INDENT: &str = "    ";
+
+#[derive(Debug, PartialEq)]
+pub struct FormatError {
+    msg: String,
+}
+
+impl FormatError {
+    pub fn new(err: &str) -> FormatError {
+        FormatError {
+            msg: err.to_owned(),
+        }
+    }
+}
+
+impl Display for FormatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[FormatError]: {}", self.msg)
+    }
+}
+
+impl std::error::Error for FormatError {}
+
+/// Format RustScript source into its canonical form.
+///
+/// The program is parsed first so only valid programs are formatted. Formatting works on the token
+/// stream rather than the AST so that comments and literals are kept exactly as written: only
+/// whitespace changes. As a safety net, the output is parsed again and must produce the same
+/// program (compared through the parser's Display impls) as the input.
+pub fn format_source(src: &str) -> Result<String> {
+    let tokens = tokenize(src)?;
+    let original = Parser::new_from_string(src).parse()?;
+
+    let formatted = Printer::new(src, &tokens).print();
+
+    let reparsed = Parser::new_from_string(&formatted)
+        .parse()
+        .map_err(|e| FormatError::new(&format!("formatted output does not parse: {}", e)))?;
+
+    if original.to_string() != reparsed.to_string() {
+        return Err(FormatError::new("formatting changed the meaning of the program").into());
+    }
+
+    Ok(formatted)
+}
+
+/// Returns true if the source is already in canonical form.
+pub fn is_formatted(src: &str) -> Result<bool> {
+    Ok(format_source(src)? == src)
+}
+
+fn tokenize(src: &str) -> Result<Vec<(Token, Range<usize>)>, FormatError> {
+    let mut tokens = vec![];
+    for (tok, span) in lex(src).spanned() {
+        match tok {
+            Ok(tok) => tokens.push((tok, span)),
+            Err(err) => {
+                let line = src[..span.start].matches('\n').count() + 1;
+                let e = format!("{} on line {}", err.message(&src[span]), line);
+                return Err(FormatError::new(&e));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Tokens after which a '-' or '!' is a binary operator / end of an operand
+fn ends_operand(tok: &Token) -> bool {
+    matches!(
+        tok,
+        Token::Ident(_)
+            | Token::Integer(_)
+            | Token::Float(_)
+            | Token::Bool(_)
+            | Token::String(_)
+            | Token::CloseParen
+            | Token::CloseBrace
+    )
+}
+
+fn is_binop(tok: &Token) -> bool {
+    matches!(
+        tok,
+        Token::Plus
+            | Token::Minus
+            | Token::Star
+            | Token::Slash
+            | Token::Percent
+            | Token::Lt
+            | Token::Gt
+            | Token::Le
+            | Token::Ge
+            | Token::LogEq
+            | Token::LogAnd
+            | Token::LogOr
+    )
+}
+
+/// Tokens that may follow a closing brace on the same line
+fn continues_after_brace(tok: &Token) -> bool {
+    is_binop(tok)
+        || matches!(
+            tok,
+            Token::Else | Token::Semi | Token::Comma | Token::CloseParen | Token::Dot
+        )
+}
+
+struct Printer<'src> {
+    src: &'src str,
+    tokens: &'src [(Token, Range<usize>)],
+    out: String,
+    indent: usize,
+    line_start: bool,
+    // true if the last emitted token was a prefix operator, e.g - in -2
+    prev_unary: bool,
+}
+
+impl<'src> Printer<'src> {
+    fn new(src: &'src str, tokens: &'src [(Token, Range<usize>)]) -> Printer<'src> {
+        Printer {
+            src,
+            tokens,
+            out: String::new(),
+            indent: 0,
+            line_start: true,
+            prev_unary: false,
+        }
+    }
+
+    fn print(mut self) -> String {
+        for idx in 0..self.tokens.len() {
+            let gap_start = if idx == 0 {
+                0
+            } else {
+                self.tokens[idx - 1].1.end
+            };
+            let gap = &self.src[gap_start..self.tokens[idx].1.start];
+            let trivia = Trivia::from_gap(gap, idx > 0);
+            self.print_trivia(idx, &trivia);
+            self.print_token(idx);
+        }
+
+        // Comments after the last token
+        let gap_start = self.tokens.last().map(|(_, span)| span.end).unwrap_or(0);
+        let trivia = Trivia::from_gap(&self.src[gap_start..], !self.tokens.is_empty());
+        self.print_trivia(self.tokens.len(), &trivia);
+
+        let trimmed = self.out.trim_end();
+        if trimmed.is_empty() {
+            String::new()
+        } else {
+            format!("{}\n", trimmed)
+        }
+    }
+
+    fn prev_tok(&self, idx: usize) -> Option<&'src Token> {
+        if idx == 0 {
+            None
+        } else {
+            self.tokens.get(idx - 1).map(|(tok, _)| tok)
+        }
+    }
+
+    fn next_tok(&self, idx: usize) -> Option<&'src Token> {
+        self.tokens.get(idx + 1).map(|(tok, _)| tok)
+    }
+
+    /// True if we are in the middle of a statement, so a new line needs a continuation indent
+    fn mid_stmt(&self, idx: usize) -> bool {
+        !matches!(
+            self.prev_tok(idx),
+            None | Some(Token::Semi) | Some(Token::OpenBrace) | Some(Token::CloseBrace)
+        )
+    }
+
+    fn after_open_brace(&self) -> bool {
+        self.out.trim_end_matches([' ', '\n']).ends_with('{')
+    }
+
+    fn newline(&mut self) {
+        let trimmed = self.out.trim_end_matches(' ').len();
+        self.out.truncate(trimmed);
+        self.out.push('\n');
+        self.line_start = true;
+    }
+
+    fn blank_line(&mut self) {
+        if !self.out.is_empty() && !self.out.ends_with("\n\n") && !self.after_open_brace() {
+            self.out.push('\n');
+        }
+    }
+
+    fn write(&mut self, text: &str, space_before: bool, extra_indent: bool) {
+        if self.line_start {
+            let depth = self.indent + usize::from(extra_indent);
+            self.out.push_str(&INDENT.repeat(depth));
+            self.line_start = false;
+        } else if space_before {
+            self.out.push(' ');
+        }
+        self.out.push_str(text);
+    }
+
+    fn print_trivia(&mut self, idx: usize, trivia: &Trivia) {
+        let closing = matches!(
+            self.tokens.get(idx).map(|(tok, _)| tok),
+            Some(Token::CloseBrace)
+        );
+        // Comments before } belong to the inside of the block, nothing continues at end of input
+        let extra_indent = self.mid_stmt(idx) && !closing && idx < self.tokens.len();
+
+        if let Some(comment) = &trivia.trailing {
+            // The previous token may have ended the line already: the comment stays on that line
+            if self.line_start && self.out.ends_with('\n') {
+                self.out.pop();
+                self.line_start = false;
+            }
+            self.write(comment, true, extra_indent);
+            self.newline();
+        }
+
+        let mut pending_blank = false;
+        for item in trivia.items.iter() {
+            match item {
+                TriviaItem::Blank => pending_blank = true,
+                TriviaItem::Comment(comment) => {
+                    if !self.line_start {
+                        self.newline();
+                    }
+                    if pending_blank {
+                        self.blank_line();
+                        pending_blank = false;
+                    }
+                    self.write(comment, false, extra_indent);
+                    self.newline();
+                }
+            }
+        }
+
+        // Keep at most one blank line between statements, never at the start/end of a block
+        if pending_blank && self.line_start && !closing && idx < self.tokens.len() {
+            self.blank_line();
+        }
+    }
+
+    fn print_token(&mut self, idx: usize) {
+        let (tok, span) = &self.tokens[idx];
+        let text = &self.src[span.clone()];
+        let prev = self.prev_tok(idx);
+        let next = self.next_tok(idx);
+
+        if tok.eq(&Token::CloseBrace) {
+            self.indent = self.indent.saturating_sub(1);
+            if !self.line_start && !matches!(prev, Some(Token::OpenBrace)) {
+                self.newline();
+            }
+        }
+
+        let space_before = self.space_before(prev, tok);
+        let extra_indent = self.mid_stmt(idx) && !tok.eq(&Token::CloseBrace);
+        self.write(text, space_before, extra_indent);
+
+        self.prev_unary =
+            matches!(tok, Token::Minus | Token::Bang) && !prev.map(ends_operand).unwrap_or(false);
+
+        match tok {
+            Token::OpenBrace => {
+                self.indent += 1;
+                // Empty block stays on one line: {}
+                let next_is_close = matches!(next, Some(Token::CloseBrace));
+                let gap =
+                    &self.src[span.end..self.tokens.get(idx + 1).map_or(span.end, |t| t.1.start)];
+                if !next_is_close || gap.contains("//") {
+                    self.newline();
+                }
+            }
+            Token::Semi => self.newline(),
+            Token::CloseBrace if !next.map(continues_after_brace).unwrap_or(false) => {
+                self.newline()
+            }
+            _ => (),
+        }
+    }
+
+    fn space_before(&self, prev: Option<&Token>, tok: &Token) -> bool {
+        let prev = match prev {
+            Some(prev) => prev,
+            None => return false,
+        };
+
+        if self.prev_unary {
+            return false;
+        }
+
+        match (prev, tok) {
+            (_, Token::Semi | Token::Comma | Token::CloseParen | Token::Colon | Token::Dot) => {
+                false
+            }
+            (Token::OpenParen | Token::Dot, _) => false,
+            // ranges and annotations: 0..n, @parallel
+            (_, Token::DotDot) | (Token::DotDot | Token::IntegerDotDot(_) | Token::At, _) => false,
+            // empty block
+            (Token::OpenBrace, Token::CloseBrace) => false,
+            // fn call, fn type annotation
+            (Token::Ident(_) | Token::CloseParen | Token::Fn, Token::OpenParen) => false,
+            _ => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_fmt(inp: &str, expected: &str) {
+        let res = format_source(inp).expect("Should format");
+        assert_eq!(res, expected);
+        // formatting is idempotent
+        assert_eq!(format_source(&res).expect("Should format again"), res);
+    }
+
+    #[test]
+    fn test_fmt_spacing() {
+        test_fmt("let x:int=2+3*4;", "let x: int = 2 + 3 * 4;\n");
+        test_fmt("let x = -2 - -3;", "let x = -2 - -3;\n");
+        test_fmt("let b = !(x<3)&&y||!z;", "let b = !(x < 3) && y || !z;\n");
+        test_fmt("x>=3||x<=1", "x >= 3 || x <= 1\n");
+        test_fmt("f( 2,3 ,g(4) )", "f(2, 3, g(4))\n");
+        test_fmt("x=x+1;y", "x = x + 1;\ny\n");
+        test_fmt(
+            "@ parallel loop i in 0 .. n+1 {f(i);}",
+            "@parallel loop i in 0..n + 1 {\n    f(i);\n}\n",
+        );
+        test_fmt("", "");
+    }
+
+    #[test]
+    fn test_fmt_literals_kept() {
+        test_fmt(r#"println("a  b");"#, "println(\"a  b\");\n");
+        test_fmt("let f = 2.50;", "let f = 2.50;\n");
+    }
+
+    #[test]
+    fn test_fmt_blocks() {
+        test_fmt(
+            "fn foo(x:int,y:int)->int{x+y} foo(2,5)",
+            "fn foo(x: int, y: int) -> int {\n    x + y\n}\nfoo(2, 5)\n",
+        );
+        test_fmt(
+            "let x : int = if y { 5; 2 } else { 3 };",
+            "let x: int = if y {\n    5;\n    2\n} else {\n    3\n};\n",
+        );
+        test_fmt(
+            "loop x < 3 { if x == 2 { break; } x = x + 1; }",
+            "loop x < 3 {\n    if x == 2 {\n        break;\n    }\n    x = x + 1;\n}\n",
+        );
+        test_fmt("{ fn garbage() {} }", "{\n    fn garbage() {}\n}\n");
+        test_fmt(
+            "let f : fn(int) -> fn(int) -> int = g;",
+            "let f: fn(int) -> fn(int) -> int = g;\n",
+        );
+    }
+
+    #[test]
+    fn test_fmt_blank_lines() {
+        test_fmt(
+            "\n\nlet x = 2;\n\n\n\nlet y = 3;\n\n",
+            "let x = 2;\n\nlet y = 3;\n",
+        );
+        // no blank lines at start or end of block
+        test_fmt("loop {\n\n  2;\n\n}", "loop {\n    2;\n}\n");
+    }
+
+    #[test]
+    fn test_fmt_comments() {
+        test_fmt(
+            "// header\n\nlet x = 2; // trailing\n  // own line\nx",
+            "// header\n\nlet x = 2; // trailing\n// own line\nx\n",
+        );
+        test_fmt(
+            "fn f() {\n// inside\nlet x = 2;\n   // before close\n}\n// end",
+            "fn f() {\n    // inside\n    let x = 2;\n    // before close\n}\n// end\n",
+        );
+        test_fmt("{ // only a comment\n}", "{ // only a comment\n}\n");
+        test_fmt("x\n\n  // end", "x\n\n// end\n");
+        test_fmt("let x = 2 + // why\n3;", "let x = 2 + // why\n    3;\n");
+    }
+
+    #[test]
+    fn test_fmt_concurrency() {
+        test_fmt(
+            "let t = spawn f(1); wait sem; post sem; yield; join t",
+            "let t = spawn f(1);\nwait sem;\npost sem;\nyield;\njoin t\n",
+        );
+    }
+
+    #[test]
+    fn test_fmt_errs() {
+        let err = format_source("let x = ;").expect_err("Should err");
+        assert!(err.to_string().contains("[ParseError]"));
+
+        let err = format_source("let x = 2 ` 3;").expect_err("Should err");
+        assert_eq!(
+            err.to_string(),
+            "[FormatError]: Unrecognised token '`' on line 1"
+        );
+    }
+
+    #[test]
+    fn test_fmt_examples_idempotent() {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/../../example");
+        let mut dirs = vec![std::path::PathBuf::from(dir)];
+        let mut files = vec![];
+        // example/project is a program split across files
+        while let Some(dir) = dirs.pop() {
+            for entry in std::fs::read_dir(dir).expect("example dir exists") {
+                let path = entry.expect("entry").path();
+                if path.is_dir() {
+                    dirs.push(path);
+                } else {
+                    files.push(path);
+                }
+            }
+        }
+
+        for path in files {
+            let src = std::fs::read_to_string(&path).expect("readable");
+            // some examples are intentionally ill-typed but all of them parse
+            let once = format_source(&src).expect("example should format");
+            assert_eq!(format_source(&once).expect("should format"), once);
+        }
+    }
+}
@@ -0,0 +1,5 @@
+use anyhow::Result;
+
+fn main() -> Result<()> {
+    rstfmt::cli::main("rstfmt", std::env::args_os())
+}
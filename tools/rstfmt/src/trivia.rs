@@ -0,0 +1,88 @@
+/// Something between two tokens that the formatter has to keep
+#[derive(Debug, PartialEq)]
+pub enum TriviaItem {
+    Comment(String),
+    Blank,
+}
+
+/// Comments and blank lines found in the whitespace between two tokens
+#[derive(Debug, PartialEq, Default)]
+pub struct Trivia {
+    /// Comment on the same line as the previous token e.g let x = 2; // here
+    pub trailing: Option<String>,
+    /// Comments on their own line and blank lines, in source order
+    pub items: Vec<TriviaItem>,
+}
+
+impl Trivia {
+    /// Build from the text between two tokens. The lexer skips whitespace and comments, so the
+    /// gap only ever contains those. `after_token` is false for the gap at the start of the file.
+    pub fn from_gap(gap: &str, after_token: bool) -> Trivia {
+        let mut trivia = Trivia::default();
+        let lines: Vec<&str> = gap.split('\n').collect();
+
+        // The last line is the one the next token is on (or end of input), so it's only whitespace
+        let full_lines = &lines[..lines.len() - 1];
+
+        for (idx, line) in full_lines.iter().enumerate() {
+            let line = line.trim();
+            if idx == 0 && after_token {
+                if !line.is_empty() {
+                    trivia.trailing.replace(line.to_string());
+                }
+                continue;
+            }
+
+            if line.is_empty() {
+                // collapse consecutive blank lines
+                if !matches!(trivia.items.last(), Some(TriviaItem::Blank)) {
+                    trivia.items.push(TriviaItem::Blank);
+                }
+            } else {
+                trivia.items.push(TriviaItem::Comment(line.to_string()));
+            }
+        }
+
+        // Comment at the very end of the input without a newline
+        let last = lines[lines.len() - 1].trim();
+        if !last.is_empty() {
+            if lines.len() == 1 && after_token {
+                trivia.trailing.replace(last.to_string());
+            } else {
+                trivia.items.push(TriviaItem::Comment(last.to_string()));
+            }
+        }
+
+        trivia
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trivia_from_gap() {
+        assert_eq!(Trivia::from_gap(" ", true), Trivia::default());
+
+        let t = Trivia::from_gap(" // hi\n\n\n  // there\n   ", true);
+        assert_eq!(t.trailing, Some("// hi".to_string()));
+        assert_eq!(
+            t.items,
+            vec![
+                TriviaItem::Blank,
+                TriviaItem::Comment("// there".to_string())
+            ]
+        );
+
+        // start of file: first line is not a trailing comment
+        let t = Trivia::from_gap("// hi\n", false);
+        assert_eq!(t.trailing, None);
+        assert_eq!(t.items, vec![TriviaItem::Comment("// hi".to_string())]);
+
+        // end of file without newline
+        let t = Trivia::from_gap(" // end", true);
+        assert_eq!(t.trailing, Some("// end".to_string()));
+        assert!(t.items.is_empty());
+    }
+}
@@ -0,0 +1,90 @@
+use std::ffi::OsString;
+
+use anyhow::Result;
+use clap::{Parser, Subcommand};
+
+#[derive(Parser, Debug)]
+#[command(name = "rustscript")]
+#[command(version = "0.1.0")]
+#[command(about = "Build, run, check and format RustScript programs", long_about = None)]
+struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+/// Each subcommand passes its arguments on to the tool it wraps, so it takes the same options.
+/// Run a subcommand with --help to see them.
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Compile a .rst file to a .o2 file, like oxidate
+    #[command(disable_help_flag = true, disable_version_flag = true)]
+    Build {
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<OsString>,
+    },
+
+    /// Run a .rst file, compiling it in memory, or a compiled .o2 file, like ignite
+    #[command(disable_help_flag = true, disable_version_flag = true)]
+    Run {
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<OsString>,
+    },
+
+    /// Start the REPL, like ignite --repl
+    #[command(disable_help_flag = true, disable_version_flag = true)]
+    Repl {
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<OsString>,
+    },
+
+    /// Parse and type check a .rst file and print its diagnostics, like oxidate check
+    #[command(disable_help_flag = true, disable_version_flag = true)]
+    Check {
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<OsString>,
+    },
+
+    /// Format .rst files, like rstfmt
+    #[command(disable_help_flag = true, disable_version_flag = true)]
+    Fmt {
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<OsString>,
+    },
+}
+
+/// The command line of the wrapped tool: its program name, then prefix, then args
+fn tool_args(name: &str, prefix: &[&str], args: Vec<OsString>) -> Vec<OsString> {
+    std::iter::once(name)
+        .chain(prefix.iter().copied())
+        .map(OsString::from)
+        .chain(args)
+        .collect()
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+
+    match args.command {
+        Command::Build { args } => {
+            let name = "rustscript build";
+            compiler::cli::main(name, tool_args(name, &[], args))
+        }
+        Command::Run { args } => {
+            let name = "rustscript run";
+            ignite::cli::main(name, tool_args(name, &[], args))
+        }
+        Command::Repl { args } => {
+            let name = "rustscript repl";
+            ignite::cli::main(name, tool_args(name, &["--repl"], args))
+        }
+        Command::Check { args } => {
+            // oxidate's check subcommand, so usage reads rustscript check
+            let name = "rustscript";
+            compiler::cli::main(name, tool_args(name, &["check"], args))
+        }
+        Command::Fmt { args } => {
+            let name = "rustscript fmt";
+            rstfmt::cli::main(name, tool_args(name, &[], args))
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use anyhow::Result;
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const RUSTSCRIPT_BINARY: &str = "rustscript";
+
+/// rustscript, caching the .rst files it runs in the tests' own directory rather than the user's cache
+fn rustscript() -> Result<Command> {
+    let mut cmd = Command::cargo_bin(RUSTSCRIPT_BINARY)?;
+    cmd.env(
+        "RUSTSCRIPT_CACHE_DIR",
+        Path::new(env!("CARGO_TARGET_TMPDIR")).join("rustscript-cache"),
+    );
+    Ok(cmd)
+}
+
+/// A file in a directory of its own for the test named name, with contents src
+fn temp_file(name: &str, file: &str, src: &str) -> Result<PathBuf> {
+    let dir = std::env::temp_dir().join(format!("rustscript-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(file);
+    std::fs::write(&path, src)?;
+    Ok(path)
+}
+
+#[test]
+fn help_lists_subcommands() -> Result<()> {
+    let mut cmd = rustscript()?;
+    cmd.arg("--help");
+    let mut assert = cmd.assert().success();
+    for sub in ["build", "run", "repl", "check", "fmt"] {
+        assert = assert.stdout(predicate::str::contains(format!("  {}", sub)));
+    }
+
+    // options are the wrapped tool's, shown under the subcommand's name
+    let mut cmd = rustscript()?;
+    cmd.args(["build", "--help"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Usage: rustscript build"))
+        .stdout(predicate::str::contains("--no-typecheck"));
+
+    Ok(())
+}
+
+#[test]
+fn build_then_run() -> Result<()> {
+    let src = temp_file("build", "prog.rst", "let x = 20; println(x + 22);")?;
+    let out = src.with_extension("");
+
+    let mut cmd = rustscript()?;
+    cmd.arg("build").arg(&src).arg("-o").arg(&out);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Compiled successfully"));
+
+    let mut cmd = rustscript()?;
+    cmd.arg("run").arg(out.with_extension("o2"));
+    cmd.assert().success().stdout(predicate::eq("42\n"));
+
+    std::fs::remove_dir_all(src.parent().expect("File is in a directory"))?;
+
+    Ok(())
+}
+
+#[test]
+fn run_rst() -> Result<()> {
+    let exp = "before spawn func\nafter spawn func\ninside func\n500\n";
+
+    let mut cmd = rustscript()?;
+    cmd.args([
+        "run",
+        "../../example/simple-join.rst",
+        "--no-cache",
+        "--echo-result",
+    ]);
+    cmd.assert().success().stdout(predicate::eq(exp));
+
+    Ok(())
+}
+
+#[test]
+fn check() -> Result<()> {
+    let mut cmd = rustscript()?;
+    cmd.args(["check", "../../example/simple-join.rst"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("No errors found"));
+
+    let src = temp_file("check", "bad.rst", "let x : int = true;")?;
+    let mut cmd = rustscript()?;
+    cmd.arg("check").arg(&src).arg("--json");
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains(r#""success":false"#));
+
+    std::fs::remove_dir_all(src.parent().expect("File is in a directory"))?;
+
+    Ok(())
+}
+
+#[test]
+fn fmt() -> Result<()> {
+    let src = temp_file("fmt", "messy.rst", "let x=1;\n")?;
+
+    let mut cmd = rustscript()?;
+    cmd.arg("fmt").arg("--check").arg(&src);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Not formatted"));
+
+    let mut cmd = rustscript()?;
+    cmd.arg("fmt").arg("-w").arg(&src);
+    cmd.assert().success();
+    assert_eq!(std::fs::read_to_string(&src)?, "let x = 1;\n");
+
+    std::fs::remove_dir_all(src.parent().expect("File is in a directory"))?;
+
+    Ok(())
+}
+
+#[test]
+fn unknown_subcommand() -> Result<()> {
+    let mut cmd = rustscript()?;
+    cmd.arg("compile");
+    cmd.assert().failure().stderr(predicate::str::contains(
+        "unrecognized subcommand 'compile'",
+    ));
+
+    Ok(())
+}
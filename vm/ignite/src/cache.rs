@@ -0,0 +1,403 @@
+//! Cache of compiled .rst files, so running an unchanged file skips parsing, type checking and
+//! compiling.
+//!
+//! Each file is compiled to a .o2 file in the cache directory named after a hash of everything the
+//! result depends on: the file's path and source, how it is compiled, and the ignite binary
+//! itself, so rebuilding the compiler invalidates the cache. The entry starts with all of that in
+//! full, and is only used if it matches, so two keys with the same hash never share a program.
+//! REPL lines aren't cached, as each is compiled after the lines before it.
+//!
+//! The directory is RUSTSCRIPT_CACHE_DIR, or rustscript in $XDG_CACHE_HOME or ~/.cache. Programs
+//! are run from it, so it is only used if it belongs to the current user and no one else can write
+//! to it, and a new one is only accessible to its owner. Only the MAX_ENTRIES most recently written
+//! programs are kept.
+
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use bytecode::{read_o2, write_o2, O2File};
+use log::debug;
+use parser::edition::Edition;
+
+const CACHE_DIR_VAR: &str = "RUSTSCRIPT_CACHE_DIR";
+/// Number of programs kept in the cache, writing another removes the oldest
+const MAX_ENTRIES: usize = 256;
+
+pub struct CompileCache {
+    dir: PathBuf,
+    max_entries: usize,
+}
+
+/// What a cached program was compiled from
+#[derive(Clone, Copy)]
+pub struct CacheKey<'a> {
+    pub file: &'a str,
+    pub src: &'a str,
+    pub type_check: bool,
+    pub inline: bool,
+    pub fold_constants: bool,
+    pub edition: Edition,
+}
+
+impl CompileCache {
+    /// The cache in the user's cache directory, if it can be used safely
+    pub fn new() -> Option<CompileCache> {
+        let Some(dir) = cache_dir() else {
+            debug!("No cache directory, set {} or HOME", CACHE_DIR_VAR);
+            return None;
+        };
+        CompileCache::open(dir, MAX_ENTRIES)
+    }
+
+    /// The cache in dir, which is created if it doesn't exist. None if someone other than the
+    /// current user could write to it.
+    fn open(dir: PathBuf, max_entries: usize) -> Option<CompileCache> {
+        if let Err(err) = create_private_dir(&dir) {
+            debug!("Not caching in {}: {}", dir.display(), err);
+            return None;
+        }
+        Some(CompileCache { dir, max_entries })
+    }
+
+    /// The program compiled from key, if it was cached
+    pub fn get(&self, key: &CacheKey) -> Option<O2File> {
+        let path = self.path(key);
+        let o2 = File::open(&path).ok().and_then(|mut f| {
+            let stored = read_key(&mut f).ok()?;
+            (stored == key.bytes()).then_some(())?;
+            read_o2(&mut f).ok()
+        });
+        match &o2 {
+            Some(_) => debug!("Using {} cached at {}", key.file, path.display()),
+            None => debug!("{} is not cached", key.file),
+        }
+        o2
+    }
+
+    /// Cache the program compiled from key. The cache is only an optimization, so failing to write
+    /// it is not an error.
+    pub fn put(&self, key: &CacheKey, o2: &O2File) {
+        let path = self.path(key);
+        // written to a file of its own and renamed, so another run never reads half a program
+        let tmp = path.with_extension(format!("o2.{}.tmp", std::process::id()));
+        let res = File::create(&tmp)
+            .map_err(anyhow::Error::from)
+            .and_then(|mut f| {
+                write_key(&key.bytes(), &mut f)?;
+                write_o2(o2, &mut f)
+            })
+            .and_then(|_| Ok(std::fs::rename(&tmp, &path)?));
+
+        match res {
+            Ok(()) => debug!("Cached {} at {}", key.file, path.display()),
+            Err(err) => {
+                let _ = std::fs::remove_file(&tmp);
+                debug!("Could not cache {}: {}", key.file, err);
+            }
+        }
+        self.evict();
+    }
+
+    /// Remove the oldest programs until at most max_entries are left
+    fn evict(&self) {
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return;
+        };
+        let mut cached: Vec<_> = entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "o2"))
+            .filter_map(|entry| {
+                let modified = entry.metadata().and_then(|meta| meta.modified()).ok()?;
+                Some((modified, entry.path()))
+            })
+            .collect();
+        if cached.len() <= self.max_entries {
+            return;
+        }
+
+        cached.sort();
+        let evicted = cached.len() - self.max_entries;
+        for (_, path) in cached.into_iter().take(evicted) {
+            match std::fs::remove_file(&path) {
+                Ok(()) => debug!("Evicted {} from the cache", path.display()),
+                Err(err) => debug!("Could not evict {}: {}", path.display(), err),
+            }
+        }
+    }
+
+    fn path(&self, key: &CacheKey) -> PathBuf {
+        let mut hash = Fnv1a::new();
+        hash.write(&key.bytes());
+        self.dir.join(format!("{:016x}.o2", hash.0))
+    }
+}
+
+impl CacheKey<'_> {
+    /// Everything the program depends on, as stored in its entry
+    fn bytes(&self) -> Vec<u8> {
+        let file = self.file.as_bytes();
+        let edition = self.edition.to_string();
+        [
+            &(file.len() as u64).to_le_bytes(),
+            file,
+            &[
+                self.type_check as u8,
+                self.inline as u8,
+                self.fold_constants as u8,
+            ],
+            edition.as_bytes(),
+            &[0],
+            &binary_stamp(),
+            self.src.as_bytes(),
+        ]
+        .concat()
+    }
+}
+
+/// Write the key an entry was compiled from, before its program
+fn write_key<W: Write>(key: &[u8], writer: &mut W) -> std::io::Result<()> {
+    writer.write_all(&(key.len() as u64).to_le_bytes())?;
+    writer.write_all(key)
+}
+
+/// Read the key an entry was compiled from, leaving reader at its program
+fn read_key<R: Read>(reader: &mut R) -> std::io::Result<Vec<u8>> {
+    let mut len = [0; 8];
+    reader.read_exact(&mut len)?;
+    let len = u64::from_le_bytes(len);
+
+    // read as far as there is data, rather than trusting the length to allocate
+    let mut key = vec![];
+    reader.take(len).read_to_end(&mut key)?;
+    if key.len() as u64 != len {
+        return Err(std::io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(key)
+}
+
+/// RUSTSCRIPT_CACHE_DIR, or rustscript in $XDG_CACHE_HOME or ~/.cache
+fn cache_dir() -> Option<PathBuf> {
+    let var = |name| std::env::var_os(name).filter(|val| !val.is_empty());
+    if let Some(dir) = var(CACHE_DIR_VAR) {
+        return Some(PathBuf::from(dir));
+    }
+
+    // relative paths are ignored, as the XDG spec says
+    let xdg = var("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute());
+    let home = || var("HOME").map(|home| PathBuf::from(home).join(".cache"));
+    Some(xdg.or_else(home)?.join("rustscript"))
+}
+
+/// Create dir, and any missing parents, only accessible to the current user. Fails if dir already
+/// exists but isn't a directory of the current user's that only they can write to.
+#[cfg(unix)]
+fn create_private_dir(dir: &Path) -> std::io::Result<()> {
+    use std::io::{Error, ErrorKind};
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+
+    std::fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir)?;
+
+    // not following a symlink, which could point anywhere
+    let meta = std::fs::symlink_metadata(dir)?;
+    // SAFETY: getuid has no preconditions and can't fail
+    let uid = unsafe { libc::getuid() };
+    if !meta.is_dir() {
+        Err(Error::other("not a directory"))
+    } else if meta.uid() != uid {
+        Err(Error::new(
+            ErrorKind::PermissionDenied,
+            "owned by another user",
+        ))
+    } else if meta.mode() & 0o022 != 0 {
+        Err(Error::new(
+            ErrorKind::PermissionDenied,
+            "writable by other users",
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(not(unix))]
+fn create_private_dir(dir: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(dir)
+}
+
+/// Size and modification time of the running binary, which change whenever it is rebuilt
+fn binary_stamp() -> Vec<u8> {
+    let meta = std::env::current_exe().and_then(std::fs::metadata);
+    let Ok(meta) = meta else {
+        return vec![];
+    };
+
+    let modified = meta
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+        .map_or(0, |since| since.as_nanos());
+
+    [meta.len().to_le_bytes().as_slice(), &modified.to_le_bytes()].concat()
+}
+
+/// 64 bit FNV-1a. Unlike DefaultHasher its output is the same in every build, which the names of
+/// cached files rely on.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Fnv1a {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytecode::{ByteCode, O2Header};
+
+    use super::*;
+
+    #[test]
+    fn test_fnv1a() {
+        let hash = |s: &str| {
+            let mut h = Fnv1a::new();
+            h.write(s.as_bytes());
+            h.0
+        };
+        // reference values
+        assert_eq!(hash(""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(hash("a"), 0xaf63_dc4c_8601_ec8c);
+    }
+
+    #[test]
+    fn test_compile_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = CompileCache::open(dir.path().join("cache"), MAX_ENTRIES).unwrap();
+        let key = CacheKey {
+            file: "main.rst",
+            src: "1 + 2",
+            type_check: true,
+            inline: false,
+            fold_constants: false,
+            edition: Edition::default(),
+        };
+        let o2 = O2File {
+            header: Some(O2Header { typechecked: true }),
+            bytecode: vec![ByteCode::ldc(3), ByteCode::DONE],
+            source_map: None,
+        };
+
+        assert_eq!(cache.get(&key), None);
+        cache.put(&key, &o2);
+        assert_eq!(cache.get(&key), Some(o2));
+
+        // anything the program depends on changing is a miss
+        let changed = [
+            CacheKey {
+                src: "1 + 3",
+                ..key
+            },
+            CacheKey {
+                type_check: false,
+                ..key
+            },
+            CacheKey {
+                inline: true,
+                ..key
+            },
+            CacheKey {
+                fold_constants: true,
+                ..key
+            },
+            CacheKey {
+                file: "other.rst",
+                ..key
+            },
+            CacheKey {
+                edition: Edition::E2027,
+                ..key
+            },
+        ];
+        for key in changed.iter() {
+            assert_eq!(cache.get(key), None);
+        }
+
+        // an entry for another key with the same hash is a miss
+        let other = CacheKey {
+            src: "1 + 4",
+            ..key
+        };
+        std::fs::copy(cache.path(&key), cache.path(&other)).unwrap();
+        assert_eq!(cache.get(&other), None);
+
+        // a corrupt entry is a miss
+        std::fs::write(cache.path(&key), b"garbage").unwrap();
+        assert_eq!(cache.get(&key), None);
+    }
+
+    #[test]
+    fn test_compile_cache_evict() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = CompileCache::open(dir.path().to_path_buf(), 2).unwrap();
+        let o2 = O2File {
+            header: None,
+            bytecode: vec![ByteCode::DONE],
+            source_map: None,
+        };
+        let srcs = ["1", "2", "3"];
+        let keys: Vec<_> = srcs
+            .iter()
+            .map(|src| CacheKey {
+                file: "main.rst",
+                src,
+                type_check: true,
+                inline: false,
+                fold_constants: false,
+                edition: Edition::default(),
+            })
+            .collect();
+
+        for key in keys.iter() {
+            cache.put(key, &o2);
+            // entries are ordered by modification time, make sure it differs
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+
+        // only the newest are kept
+        assert_eq!(cache.get(&keys[0]), None);
+        assert_eq!(cache.get(&keys[1]), Some(o2.clone()));
+        assert_eq!(cache.get(&keys[2]), Some(o2));
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_compile_cache_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let cache_dir = dir.path().join("a").join("cache");
+        assert!(CompileCache::open(cache_dir.clone(), MAX_ENTRIES).is_some());
+        let mode = std::fs::metadata(&cache_dir).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o700);
+
+        // a directory others can plant programs in isn't used
+        std::fs::set_permissions(&cache_dir, std::fs::Permissions::from_mode(0o777)).unwrap();
+        assert!(CompileCache::open(cache_dir, MAX_ENTRIES).is_none());
+
+        let file = dir.path().join("file");
+        std::fs::write(&file, "").unwrap();
+        assert!(CompileCache::open(file, MAX_ENTRIES).is_none());
+    }
+}
@@ -0,0 +1,358 @@
+use std::collections::VecDeque;
+
+use anyhow::Result;
+use bytecode::{type_of, FnType, FrameType, Queue, StackFrame, Value, W};
+
+use crate::{extend_environment, Runtime, VmError};
+
+/// A builtin that called a user function and is waiting for it to return, like map calling its f.
+///
+/// The function is called like CALL does: a call frame is pushed and the thread jumps to the
+/// function, so it runs as part of the thread and can be preempted, block and spawn like any other
+/// code. The builtin's state is kept on the thread instead of the Rust stack, and reset resumes the
+/// builtin when the function's frame is popped. The return address of the frame is the instruction
+/// after the builtin's CALL, so the thread carries on from there once the builtin has finished.
+#[derive(Debug, Clone)]
+pub struct Callback {
+    /// The length of the runtime stack with the called function's frame on it. The function has
+    /// returned once the stack is shorter.
+    pub depth: usize,
+    pub builtin: PendingBuiltin,
+}
+
+/// What a builtin that calls functions still has to do.
+#[derive(Debug, Clone)]
+pub enum PendingBuiltin {
+    /// Values of the input queue left to map and the results so far
+    Map {
+        f: Value,
+        rest: VecDeque<Value>,
+        out: Vec<Value>,
+    },
+    /// Values left to test, the value being tested and the values kept so far
+    Filter {
+        pred: Value,
+        rest: VecDeque<Value>,
+        current: Option<Value>,
+        out: Vec<Value>,
+    },
+    /// Values left to fold into the accumulator, which is the result of the last call
+    Reduce { f: Value, rest: VecDeque<Value> },
+}
+
+/// The next thing a pending builtin does
+enum Step {
+    Call(Value, Vec<Value>),
+    Done(Value),
+}
+
+impl PendingBuiltin {
+    /// Take the result of the last call, None before the first call, and decide what to do next.
+    fn step(&mut self, result: Option<Value>) -> Result<Step> {
+        let step = match self {
+            PendingBuiltin::Map { f, rest, out } => {
+                out.extend(result);
+                match rest.pop_front() {
+                    Some(val) => Step::Call(f.clone(), vec![val]),
+                    None => Step::Done(queue_of(std::mem::take(out))),
+                }
+            }
+            PendingBuiltin::Filter {
+                pred,
+                rest,
+                current,
+                out,
+            } => {
+                if let Some(result) = result {
+                    let keep: bool = result.try_into()?;
+                    if let (true, Some(val)) = (keep, current.take()) {
+                        out.push(val);
+                    }
+                }
+
+                match rest.pop_front() {
+                    Some(val) => {
+                        *current = Some(val.clone());
+                        Step::Call(pred.clone(), vec![val])
+                    }
+                    None => Step::Done(queue_of(std::mem::take(out))),
+                }
+            }
+            PendingBuiltin::Reduce { f, rest } => {
+                let acc = result.ok_or(VmError::OperandStackUnderflow)?;
+                match rest.pop_front() {
+                    Some(val) => Step::Call(f.clone(), vec![acc, val]),
+                    None => Step::Done(acc),
+                }
+            }
+        };
+
+        Ok(step)
+    }
+
+    /// The values the builtin holds on to, which the garbage collector must treat as live.
+    pub fn values(&self) -> Vec<&Value> {
+        match self {
+            PendingBuiltin::Map { f, rest, out } => {
+                std::iter::once(f).chain(rest).chain(out).collect()
+            }
+            PendingBuiltin::Filter {
+                pred,
+                rest,
+                current,
+                out,
+            } => std::iter::once(pred)
+                .chain(rest)
+                .chain(current)
+                .chain(out)
+                .collect(),
+            PendingBuiltin::Reduce { f, rest } => std::iter::once(f).chain(rest).collect(),
+        }
+    }
+
+    /// Name of the builtin, for errors
+    fn sym(&self) -> &'static str {
+        match self {
+            PendingBuiltin::Map { .. } => bytecode::builtin::MAP_SYM,
+            PendingBuiltin::Filter { .. } => bytecode::builtin::FILTER_SYM,
+            PendingBuiltin::Reduce { .. } => bytecode::builtin::REDUCE_SYM,
+        }
+    }
+}
+
+fn queue_of(vals: Vec<Value>) -> Value {
+    let q = Queue::new();
+    q.borrow_mut().values.extend(vals);
+    q.into()
+}
+
+/// Start a builtin that calls functions. The first call is made straight away, or the builtin's
+/// result is pushed if it has nothing to call.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime, with the pc after the builtin's CALL.
+///
+/// * `builtin` - The builtin's state before its first call.
+///
+/// * `init` - The result to start from, the initial accumulator for reduce and None otherwise.
+///
+/// # Errors
+///
+/// If a value to call is not a user function or takes the wrong number of arguments.
+pub fn start_callback(
+    rt: &mut Runtime,
+    mut builtin: PendingBuiltin,
+    init: Option<Value>,
+) -> Result<()> {
+    let (f, args) = match builtin.step(init)? {
+        Step::Done(val) => {
+            rt.current_thread.operand_stack.push(val);
+            return Ok(());
+        }
+        Step::Call(f, args) => (f, args),
+    };
+
+    let Value::Closure {
+        fn_type,
+        sym,
+        prms,
+        addr,
+        env,
+    } = f
+    else {
+        return Err(VmError::BadType {
+            expected: "Closure".to_string(),
+            found: type_of(&f).to_string(),
+        }
+        .into());
+    };
+
+    // A builtin would run to completion inside this call, or rewind the pc with retry_call, so only
+    // user functions can be called back.
+    if let FnType::Builtin = fn_type {
+        return Err(VmError::BuiltinCallback {
+            builtin: builtin.sym().to_string(),
+            callback: sym,
+        }
+        .into());
+    }
+
+    if prms.len() != args.len() {
+        return Err(VmError::ArityParamsMismatch {
+            arity: args.len(),
+            params: prms.len(),
+        }
+        .into());
+    }
+
+    let frame = StackFrame {
+        frame_type: FrameType::CallFrame,
+        env: W(rt.current_thread.env.clone()),
+        address: Some(rt.current_thread.pc),
+        scope: None,
+    };
+
+    rt.current_thread.runtime_stack.push(frame);
+    let depth = rt.current_thread.runtime_stack.len();
+    rt.current_thread
+        .callbacks
+        .push(Callback { depth, builtin });
+    extend_environment(rt, env.0, prms, args)?;
+    rt.current_thread.pc = addr;
+
+    Ok(())
+}
+
+/// Resume the builtin waiting on the function that just returned, whose result is on top of the
+/// operand stack. Called by reset after popping a call frame.
+///
+/// # Errors
+///
+/// If there is no result on the operand stack, or the next call fails like in start_callback.
+pub fn resume_callback(rt: &mut Runtime) -> Result<()> {
+    let callback = rt
+        .current_thread
+        .callbacks
+        .pop()
+        .ok_or(VmError::RuntimeStackUnderflow)?;
+    let result = rt
+        .current_thread
+        .operand_stack
+        .pop()
+        .ok_or(VmError::OperandStackUnderflow)?;
+
+    start_callback(rt, callback.builtin, Some(result))
+}
+
+/// Whether the function the innermost pending builtin called has returned
+#[inline]
+pub fn callback_returned(rt: &Runtime) -> bool {
+    rt.current_thread
+        .callbacks
+        .last()
+        .is_some_and(|cb| rt.current_thread.runtime_stack.len() < cb.depth)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use anyhow::Result;
+    use bytecode::Value;
+    use compiler::compiler::compile_from_string;
+
+    use crate::{run, Runtime};
+
+    fn run_src(src: &str, rt_setup: impl FnOnce(&mut Runtime)) -> Result<Value> {
+        let mut rt = Runtime::new(compile_from_string(src, true)?);
+        rt_setup(&mut rt);
+        let rt = run(rt)?;
+        assert!(rt.current_thread.callbacks.is_empty());
+        Ok(rt
+            .current_thread
+            .operand_stack
+            .last()
+            .cloned()
+            .unwrap_or(Value::Unit))
+    }
+
+    #[test]
+    fn test_map_filter_reduce() -> Result<()> {
+        let src = r"
+        fn sq(x: int) -> int { x * x }
+        fn big(x: int) -> bool { x > 5 }
+        fn add(acc: int, x: int) -> int { acc + x }
+        let q = queue_create();
+        queue_push(q, 1);
+        queue_push(q, 2);
+        queue_push(q, 3);
+        let sum = reduce(filter(map(q, sq), big), 0, add);
+        // q is unchanged
+        reduce(q, sum * 100, add)
+        ";
+        assert_eq!(run_src(src, |_| ())?, Value::Int(906));
+
+        // reduce of an empty queue is init, and nothing is called
+        let src = "fn f(acc: int, x: int) -> int { x } reduce(queue_create(), 7, f)";
+        assert_eq!(run_src(src, |_| ())?, Value::Int(7));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_callback_nested() -> Result<()> {
+        // a callback calls map itself and returns early from inside a loop
+        let src = r"
+        fn inc(x: int) -> int { x + 1 }
+        fn first_inc(x: int) -> int {
+            let q = queue_create();
+            queue_push(q, x);
+            let r = map(q, inc);
+            loop {
+                return queue_pop(r);
+            }
+            0
+        }
+        fn add(acc: int, x: int) -> int { acc + first_inc(x) }
+        let q = queue_create();
+        queue_push(q, 10);
+        queue_push(q, 20);
+        reduce(map(q, first_inc), 0, add)
+        ";
+        assert_eq!(run_src(src, |_| ())?, Value::Int(34));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_callback_threads_and_gc() -> Result<()> {
+        // the callback is a closure only the builtin holds on to, and it blocks on another thread
+        // while being preempted every instruction and collected before every instruction
+        let src = r"
+        let done = queue_create();
+        fn push(x: int) { queue_push(done, x); }
+        fn adder(n: int) -> fn(int) -> int {
+            fn add(x: int) -> int {
+                let t = spawn push(x);
+                join t;
+                x + n + queue_pop(done)
+            }
+            add
+        }
+        fn add(acc: int, x: int) -> int { acc + x }
+        let q = queue_create();
+        queue_push(q, 1);
+        queue_push(q, 2);
+        reduce(map(q, adder(10)), 0, add)
+        ";
+        let res = run_src(src, |rt| {
+            rt.set_time_quantum(Duration::ZERO);
+            rt.set_gc_stress_mode();
+            rt.set_paranoid_gc_mode();
+        })?;
+        assert_eq!(res, Value::Int(26));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_callback_errs() {
+        // the type checker rejects these, the VM checks them too when it is skipped
+        let src = "let q = queue_create(); queue_push(q, 1); map(q, abs)";
+        let Err(err) = run(Runtime::new(compile_from_string(src, false).unwrap())) else {
+            panic!("Should err");
+        };
+        assert_eq!(
+            err.to_string(),
+            "map can't call the builtin abs, wrap it in a fn"
+        );
+
+        let src =
+            "fn f(x: int) -> int { x } let q = queue_create(); queue_push(q, 1); filter(q, f)";
+        let Err(err) = run(Runtime::new(compile_from_string(src, false).unwrap())) else {
+            panic!("Should err");
+        };
+        assert_eq!(err.to_string(), "Type mismatch, expected Bool, found 1");
+    }
+}
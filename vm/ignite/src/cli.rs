@@ -0,0 +1,454 @@
+//! The ignite command line, also run by rustscript run and rustscript repl.
+
+use std::ffi::OsString;
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::{Error, Result};
+use bytecode::{builtin, read_o2, ByteCode, O2File, O2Header, SourceMap};
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
+use compiler::pipeline::{compile, Options};
+use diagnostics::{Diagnostic, Stage, UNCHECKED_PROGRAM};
+use log::info;
+use parser::edition::Edition;
+
+use crate::cache::{CacheKey, CompileCache};
+use crate::logger::{init_logger, LogLevel};
+use crate::repl::{ignite_repl, REPL_MAX_STACK_DEPTH};
+use crate::runtime::*;
+use crate::VmError;
+
+#[derive(Parser, Debug)]
+#[command(name = "Ignite")]
+#[command(version = "0.1.0")]
+#[command(about = "Virtual Machine for RustScript", long_about = None)]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// File name of the program to run: a .o2 file, or a .rst file which is compiled in memory first.
+    file: Option<String>,
+
+    /// If true, launch in REPL mode. False by default.
+    #[arg(long, short)]
+    repl: bool,
+
+    /// Set custom time quantum for the VM in milliseconds.
+    /// Default is 100ms.
+    #[arg(short, long, global = true)]
+    quantum: Option<u64>,
+
+    /// Set custom garbage collection interval for the VM in milliseconds. The collector only runs
+    /// on the interval if environments were created since it last ran.
+    /// Default is 1000ms.
+    #[arg(short, long, global = true)]
+    gc_interval: Option<u64>,
+
+    /// Garbage collect once this many environments were created since the last collection, however
+    /// recently it ran. Default is 10000.
+    #[arg(long, global = true, value_name = "N")]
+    gc_alloc_threshold: Option<usize>,
+
+    /// Garbage collect before every instruction. Very slow, for finding garbage collection bugs.
+    #[arg(long, global = true)]
+    gc_stress: bool,
+
+    /// Check the environment graph of every thread after each garbage collection, failing with an
+    /// error if a live thread can reach a reclaimed or unregistered environment.
+    #[arg(long, global = true)]
+    paranoid_gc: bool,
+
+    /// Keep at most this many finished threads waiting to be joined, reaping the oldest beyond that.
+    /// Joining a reaped thread is an error. By default finished threads are kept until joined.
+    #[arg(long, global = true, value_name = "N")]
+    max_zombies: Option<usize>,
+
+    /// Stop the program with a timeout error if it runs for longer than this, e.g. 5s, 500ms or 2m.
+    /// A number without a unit is in seconds.
+    #[arg(long, global = true, value_name = "DURATION", value_parser = parse_duration)]
+    timeout: Option<Duration>,
+
+    /// Stop the program with a timeout error if it executes more than N instructions.
+    #[arg(long, global = true, value_name = "N")]
+    max_instr: Option<u64>,
+
+    /// Stop the program with an error if it builds a string longer than N bytes.
+    #[arg(long, global = true, value_name = "N")]
+    max_string_len: Option<usize>,
+
+    /// Stop the program with an error if a thread has more than N values on its operand stack.
+    #[arg(long, global = true, value_name = "N")]
+    max_stack: Option<usize>,
+
+    /// Stop the program with an error if more than N environments are live at once, e.g. from
+    /// runaway recursion.
+    #[arg(long, global = true, value_name = "N")]
+    max_envs: Option<usize>,
+
+    /// Stop the program with a stack overflow error if a thread nests more than N calls and scopes,
+    /// e.g. from runaway recursion. The REPL stops at 10000 unless this is given.
+    #[arg(long, global = true, value_name = "N")]
+    max_depth: Option<usize>,
+
+    /// Print floats with DIGITS digits after the decimal point instead of the shortest form that
+    /// reads back as the same float. Programs can change it with set_float_precision.
+    #[arg(long, global = true, value_name = "DIGITS")]
+    #[arg(value_parser = clap::value_parser!(i64).range(0..=builtin::MAX_FIXED_DIGITS))]
+    float_precision: Option<i64>,
+
+    /// What happens to threads still running when the main thread finishes: kill them, or wait for
+    /// every thread that was not detached.
+    #[arg(long, global = true, value_enum, default_value_t = ExitPolicy::Kill)]
+    on_main_exit: ExitPolicy,
+
+    /// What happens when a thread other than the main thread hits a runtime error: end the program,
+    /// or end only that thread and return its error to the thread that joins it.
+    #[arg(long, global = true, value_enum, default_value_t = ThreadErrorPolicy::FailFast)]
+    on_thread_error: ThreadErrorPolicy,
+
+    /// Let threads spin in a loop waiting for another thread. By default a thread found busy waiting
+    /// yields to the other threads, and a warning suggests wait and post instead.
+    #[arg(long, global = true)]
+    allow_busy_wait: bool,
+
+    /// Print the scheduler's counters to stderr when the program finishes: context switches, the
+    /// average ready queue wait and each thread's run time, to find threads that are starved.
+    #[arg(long, global = true)]
+    sched_stats: bool,
+
+    /// Print the result of a program, the value of its last expression, after it finishes, as the
+    /// REPL does for each line. Off by default, so a program's output is only what it prints.
+    #[arg(long, global = true, conflicts_with = "no_echo_result")]
+    echo_result: bool,
+
+    /// Don't print the result of each line in the REPL
+    #[arg(long, global = true)]
+    no_echo_result: bool,
+
+    /// Record every preemption, input read and join timeout to FILE, so the run can be reproduced
+    /// exactly with --replay.
+    #[arg(long, global = true, value_name = "FILE", conflicts_with = "replay")]
+    record: Option<String>,
+
+    /// Re-run the program following a log written by --record instead of the clock and stdin.
+    /// Fails if the program does something the log does not match.
+    #[arg(long, global = true, value_name = "FILE")]
+    replay: Option<String>,
+
+    /// Log what the VM is doing to stderr: trace logs every instruction, debug scheduling and
+    /// garbage collection, info how the program started and finished. Off by default.
+    #[arg(long, global = true, value_enum, value_name = "LEVEL")]
+    log_level: Option<LogLevel>,
+
+    /// Same as --log-level trace
+    #[arg(short, long, global = true)]
+    debug: bool,
+
+    /// If present, does not type check in REPL or when running a .rst file. Ignored if only running bytecode.
+    #[arg(short, global = true)]
+    notype: bool,
+
+    /// Inline calls of small functions when compiling a .rst file. Ignored if only running bytecode.
+    #[arg(long, global = true)]
+    inline: bool,
+
+    /// Load builtin constants like PI as literals when compiling a .rst file, wherever the program
+    /// doesn't declare or assign the name itself. Ignored if only running bytecode.
+    #[arg(long, global = true)]
+    fold_constants: bool,
+
+    /// Edition of the language .rst files are written in: 2026, or 2027 which also makes while,
+    /// for, in, struct, enum, match and continue keywords
+    #[arg(long, global = true, default_value_t = Edition::default())]
+    edition: Edition,
+
+    /// Always compile .rst files, instead of reusing the compiled program cached from the last run
+    /// of an unchanged file. The cache is kept in RUSTSCRIPT_CACHE_DIR, or rustscript in
+    /// $XDG_CACHE_HOME or ~/.cache.
+    #[arg(long, global = true)]
+    no_cache: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Compile a .rst file in memory and run it, without writing a .o2 file
+    Run {
+        /// File containing RustScript code. Must have extension .rst
+        file: String,
+    },
+}
+
+/// Run the VM with command line args, the first of which is the program name. Usage and help show
+/// bin_name as the program, so other binaries can run it as one of their subcommands.
+pub fn main<I, T>(bin_name: &str, args: I) -> Result<()>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    let matches = Args::command()
+        .bin_name(bin_name.to_owned())
+        .get_matches_from(args);
+    let args = Args::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+
+    let log_level = args.debug.then_some(LogLevel::Trace).or(args.log_level);
+    if let Some(level) = log_level {
+        init_logger(level);
+    }
+
+    if let Some(Command::Run { file }) = &args.command {
+        let (bytecode_vec, source_map) = compile_file(file, &args)?;
+        return run_program(bytecode_vec, Some(source_map), &args);
+    }
+
+    let file_provided = args.file.is_some();
+
+    if args.repl {
+        // TODO: if file provided, run the file and pass generated context to REPL
+        ignite_repl(
+            !args.notype,
+            args.float_precision.map(|digits| digits as usize),
+            args.edition,
+            !args.no_echo_result,
+            args.max_depth.unwrap_or(REPL_MAX_STACK_DEPTH),
+        )?;
+        return Ok(()); // REPL done: exit
+    } else if !args.repl && !file_provided {
+        return Err(Error::msg("File should be provided if not launching REPL."));
+    }
+
+    let file = args.file.clone().expect("File was provided");
+
+    // Check if the file exists
+    if !Path::new(&file).exists() {
+        return Err(VmError::FileDoesNotExist(file).into());
+    }
+
+    // Source files are compiled and run directly, so scripts can start with #!/usr/bin/env ignite
+    if Path::new(&file).extension().is_some_and(|ext| ext == "rst") {
+        let (bytecode_vec, source_map) = compile_file(&file, &args)?;
+        return run_program(bytecode_vec, Some(source_map), &args);
+    }
+
+    // check file extension
+    if Path::new(&file).extension().is_none_or(|ext| ext != "o2") {
+        return Err(VmError::NotO2File(file).into());
+    }
+
+    // Deserialize the program
+    let o2 = read_o2(&mut std::fs::File::open(&file)?)?;
+
+    if o2.header.is_some_and(|header| !header.typechecked) {
+        let msg = format!(
+            "{} was compiled without type checking, type errors will only be caught when they happen",
+            file
+        );
+        eprintln!(
+            "{}",
+            Diagnostic::warning(Stage::Runtime, UNCHECKED_PROGRAM, &msg)
+        );
+    }
+
+    run_program(o2.bytecode, o2.source_map, &args)
+}
+
+/// Parse a duration for --timeout: a number followed by ms, s or m. Without a unit, seconds.
+fn parse_duration(arg: &str) -> Result<Duration, String> {
+    let arg = arg.trim();
+    let split = arg
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(arg.len());
+    let (num, unit) = arg.split_at(split);
+
+    let num: f64 = num
+        .parse()
+        .map_err(|_| format!("invalid duration '{}', expected e.g. 5s or 500ms", arg))?;
+
+    let secs = match unit {
+        "ms" => num / 1000.0,
+        "" | "s" => num,
+        "m" => num * 60.0,
+        _ => {
+            return Err(format!(
+                "unknown duration unit '{}', expected ms, s or m",
+                unit
+            ))
+        }
+    };
+
+    Duration::try_from_secs_f64(secs).map_err(|err| err.to_string())
+}
+
+/// Compile a .rst file to bytecode in memory, with its source map, or reuse the program cached from
+/// the last run of the same file unless --no-cache is given.
+fn compile_file(file: &str, args: &Args) -> Result<(Vec<ByteCode>, SourceMap)> {
+    let path = Path::new(file);
+
+    if !path.exists() {
+        return Err(VmError::FileDoesNotExist(file.to_string()).into());
+    }
+
+    if path.extension().is_none_or(|ext| ext != "rst") {
+        return Err(VmError::NotRstFile(file.to_string()).into());
+    }
+
+    let code = std::fs::read_to_string(path)?;
+    let type_check = !args.notype;
+
+    let key = CacheKey {
+        file,
+        src: &code,
+        type_check,
+        inline: args.inline,
+        fold_constants: args.fold_constants,
+        edition: args.edition,
+    };
+    let cache = (!args.no_cache).then(CompileCache::new).flatten();
+    if let Some(o2) = cache.as_ref().and_then(|cache| cache.get(&key)) {
+        let source_map = o2
+            .source_map
+            .unwrap_or_else(|| SourceMap::new(file, vec![]));
+        return Ok((o2.bytecode, source_map));
+    }
+
+    let options = Options {
+        typecheck: type_check,
+        inline: args.inline,
+        fold_constants: args.fold_constants,
+        edition: args.edition,
+        ..Options::default()
+    };
+    let compiled = compile(&code, &options).map_err(|err| {
+        Error::msg(format!(
+            "\n{}",
+            diagnostics::render(&compiler::compiler::diagnostics(&err))
+        ))
+    })?;
+
+    let source_map = SourceMap::new(file, compiled.lines);
+
+    if let Some(cache) = cache {
+        let o2 = O2File {
+            header: Some(O2Header {
+                typechecked: type_check,
+            }),
+            bytecode: compiled.bytecode,
+            source_map: Some(source_map),
+        };
+        cache.put(&key, &o2);
+        return Ok((o2.bytecode, o2.source_map.expect("Source map was set")));
+    }
+
+    Ok((compiled.bytecode, source_map))
+}
+
+fn run_program(
+    bytecode_vec: Vec<ByteCode>,
+    source_map: Option<SourceMap>,
+    args: &Args,
+) -> Result<()> {
+    let mut rt = Runtime::new(bytecode_vec);
+
+    if let Some(source_map) = source_map {
+        rt.set_source_map(source_map);
+    }
+
+    if let Some(quantum) = args.quantum {
+        rt.set_time_quantum(Duration::from_millis(quantum));
+    }
+
+    if let Some(gc_interval) = args.gc_interval {
+        rt.set_gc_interval(Duration::from_millis(gc_interval));
+    }
+
+    if let Some(gc_alloc_threshold) = args.gc_alloc_threshold {
+        rt.set_gc_alloc_threshold(gc_alloc_threshold);
+    }
+
+    if args.gc_stress {
+        rt.set_gc_stress_mode();
+    }
+
+    if args.paranoid_gc {
+        rt.set_paranoid_gc_mode();
+    }
+
+    if let Some(max_zombies) = args.max_zombies {
+        rt.set_max_zombies(max_zombies);
+    }
+
+    if let Some(timeout) = args.timeout {
+        rt.set_timeout(timeout);
+    }
+
+    if let Some(max_instr) = args.max_instr {
+        rt.set_max_instr(max_instr);
+    }
+
+    if let Some(max_string_len) = args.max_string_len {
+        rt.set_max_string_len(max_string_len);
+    }
+
+    if let Some(max_stack) = args.max_stack {
+        rt.set_max_operand_stack(max_stack);
+    }
+
+    if let Some(max_envs) = args.max_envs {
+        rt.set_max_envs(max_envs);
+    }
+
+    if let Some(max_depth) = args.max_depth {
+        rt.set_max_stack_depth(max_depth);
+    }
+
+    if let Some(float_precision) = args.float_precision {
+        rt.set_float_precision(float_precision as usize);
+    }
+
+    rt.set_exit_policy(args.on_main_exit);
+    rt.set_thread_error_policy(args.on_thread_error);
+    rt.set_echo_result(args.echo_result);
+
+    if args.allow_busy_wait {
+        rt.allow_busy_wait();
+    }
+
+    if let Some(file) = &args.record {
+        rt.set_replay_mode(ReplayMode::record(file)?);
+    }
+
+    if let Some(file) = &args.replay {
+        rt.set_replay_mode(ReplayMode::replay(file)?);
+    }
+
+    info!(
+        "Running {} instructions, time quantum {:?}",
+        rt.instrs.len(),
+        rt.time_quantum
+    );
+
+    let mut rt = run(rt).inspect_err(|_| {
+        // Make sure output printed before the error, e.g. by print without a newline, is not lost
+        let _ = std::io::stdout().flush();
+    })?;
+
+    rt.replay.finish()?;
+
+    info!(
+        "Finished after {} instructions on {} threads",
+        rt.instr_count, rt.thread_count
+    );
+
+    if args.sched_stats {
+        eprintln!("{}", rt.sched_report());
+    }
+
+    // exit(code) was called: the result of the program is not printed
+    if let Some(code) = rt.exit_code {
+        std::process::exit(code);
+    }
+
+    rt.print_result();
+
+    Ok(())
+}
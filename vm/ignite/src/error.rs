@@ -1,3 +1,4 @@
+use diagnostics::{Diagnostic, Stage, RUNTIME_ERROR};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -11,6 +12,9 @@ pub enum VmError {
     #[error("File is not a .o2 file: {0}")]
     NotO2File(String),
 
+    #[error("File is not a .rst file: {0}")]
+    NotRstFile(String),
+
     #[error("Unbounded name: {0}")]
     UnboundedName(String),
 
@@ -20,6 +24,9 @@ pub enum VmError {
     #[error("Runtime stack underflow")]
     RuntimeStackUnderflow,
 
+    #[error("Thread scope underflow")]
+    ThreadScopeUnderflow,
+
     #[error("No threads in ready queue")]
     NoThreadsInReadyQueue,
 
@@ -29,6 +36,12 @@ pub enum VmError {
     #[error("Bad type: expected {expected}, found {found}")]
     BadType { expected: String, found: String },
 
+    #[error("Division by zero")]
+    DivisionByZero,
+
+    #[error("Integer overflow: {0}")]
+    IntegerOverflow(String),
+
     #[error("Illegal argument: {0}")]
     IllegalArgument(String),
 
@@ -38,6 +51,13 @@ pub enum VmError {
     #[error("Type mismatch: expected {expected}, found {found}")]
     TypeMismatch { expected: String, found: String },
 
+    #[error("Can't compare {lhs} {op} {rhs}: ints and floats are never converted implicitly. Convert one side with 'as float' or 'as int' to compare them")]
+    IntFloatComparison {
+        lhs: String,
+        op: String,
+        rhs: String,
+    },
+
     #[error("Arity and params mismatch: arity {arity}, found {params} params")]
     ArityParamsMismatch { arity: usize, params: usize },
 
@@ -47,6 +67,78 @@ pub enum VmError {
     #[error("Environment access after drop")]
     EnvironmentDroppedError,
 
+    #[error("Environment reclaimed while still in use by thread {thread_id} at pc {pc}")]
+    EnvironmentReclaimed { thread_id: i64, pc: usize },
+
+    #[error(
+        "Environment used by thread {thread_id} at pc {pc} is not in the environment registry"
+    )]
+    EnvironmentNotRegistered { thread_id: i64, pc: usize },
+
+    #[error("Thread {0} finished and was reaped before being joined")]
+    ThreadReaped(i64),
+
+    #[error("Thread {0} was detached and can't be joined")]
+    ThreadDetached(i64),
+
+    #[error("Thread {0} can't join itself")]
+    SelfJoin(i64),
+
+    #[error("Thread {0} was already joined")]
+    ThreadAlreadyJoined(i64),
+
+    #[error("Thread {0} not found or already joined")]
+    ThreadNotFound(i64),
+
+    #[error("Thread {0} failed: {1}")]
+    ThreadFailed(i64, String),
+
+    #[error("Expected a thread ID from spawn or current_tid, found {0}")]
+    NotAThreadId(String),
+
+    #[error("Deadlock: thread {0} is waiting on an empty queue and no other thread can run")]
+    EmptyQueueDeadlock(i64),
+
+    #[error("Deadlock: thread {0} is waiting on a full queue and no other thread can run")]
+    FullQueueDeadlock(i64),
+
+    #[error("Deadlock: thread {0} is waiting in a select none of whose queues can be used and no other thread can run")]
+    SelectDeadlock(i64),
+
+    #[error("Deadlock: thread {waiter} is waiting on a semaphore thread {owner} acquired and never posted before it ended")]
+    OrphanedSemaphore { waiter: i64, owner: i64 },
+
+    #[error("Deadlock: thread {0} is joining thread {1}, which is waiting on a semaphore no other thread can post")]
+    JoinDeadlock(i64, i64),
+
+    #[error("Replay diverged: {0}")]
+    ReplayDiverged(String),
+
+    #[error("Timeout exceeded: {0}")]
+    TimeoutExceeded(String),
+
+    #[error("Resource limit exceeded: {0}")]
+    ResourceLimitExceeded(String),
+
+    #[error("Stack overflow: thread {thread_id} nested more than {max_depth} calls and scopes deep, likely from runaway recursion")]
+    StackOverflow { thread_id: i64, max_depth: usize },
+
+    #[error("{builtin} can't call the builtin {callback}, wrap it in a fn")]
+    BuiltinCallback { builtin: String, callback: String },
+
     #[error("Unknown builtin: {sym}")]
     UnknownBuiltin { sym: String },
+
+    /// A runtime error of a program with a source map, with the line it happened on
+    #[error("{err} at {file}:{line}")]
+    AtSourceLine {
+        err: anyhow::Error,
+        file: String,
+        line: usize,
+    },
+}
+
+/// Diagnostic for an error returned by the VM, a VmError or an error from the bytecode crate
+pub fn runtime_diagnostic(err: &anyhow::Error) -> Diagnostic {
+    Diagnostic::error(Stage::Runtime, RUNTIME_ERROR, &err.to_string())
 }
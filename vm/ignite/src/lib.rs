@@ -0,0 +1,15 @@
+pub use crate::callback::*;
+pub use crate::error::*;
+pub use crate::thread::*;
+
+use runtime::*;
+
+mod cache;
+mod callback;
+pub mod cli;
+mod error;
+mod logger;
+mod micro_code;
+mod repl;
+mod runtime;
+mod thread;
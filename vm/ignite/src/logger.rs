@@ -0,0 +1,84 @@
+use std::io::Write;
+
+use clap::ValueEnum;
+use log::{LevelFilter, Log, Metadata, Record};
+
+/// How much the VM says about what it is doing, set with --log-level. Each level includes the ones
+/// below it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogLevel {
+    /// Every instruction with the stacks and environment of the thread running it
+    Trace,
+    /// Scheduling, blocking and garbage collection
+    Debug,
+    /// How the program started and finished
+    Info,
+}
+
+impl From<LogLevel> for LevelFilter {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Trace => LevelFilter::Trace,
+            LogLevel::Debug => LevelFilter::Debug,
+            LogLevel::Info => LevelFilter::Info,
+        }
+    }
+}
+
+/// Writes log records to stderr, one per line, so they never mix with the program's own output on
+/// stdout.
+struct StderrLogger;
+
+static LOGGER: StderrLogger = StderrLogger;
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            let _ = writeln!(std::io::stderr().lock(), "{}", format_record(record));
+        }
+    }
+
+    fn flush(&self) {
+        let _ = std::io::stderr().flush();
+    }
+}
+
+/// [LEVEL module] message, e.g. [DEBUG ignite::runtime::gc] Mark begin
+fn format_record(record: &Record) -> String {
+    format!("[{} {}] {}", record.level(), record.target(), record.args())
+}
+
+/// Log at level and below to stderr. Nothing is logged until this is called.
+pub fn init_logger(level: LogLevel) {
+    // Only fails if a logger was already set, which then keeps logging
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(level.into());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use log::Level;
+
+    use super::*;
+
+    #[test]
+    fn test_format_record() {
+        let record = Record::builder()
+            .level(Level::Debug)
+            .target("ignite::runtime::gc")
+            .args(format_args!("Mark begin"))
+            .build();
+        assert_eq!(
+            format_record(&record),
+            "[DEBUG ignite::runtime::gc] Mark begin"
+        );
+
+        assert_eq!(LevelFilter::from(LogLevel::Info), LevelFilter::Info);
+        assert!(LevelFilter::from(LogLevel::Trace) > LevelFilter::from(LogLevel::Debug));
+    }
+}
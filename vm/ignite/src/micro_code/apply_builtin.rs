@@ -1,27 +1,63 @@
+use std::collections::VecDeque;
+
 use anyhow::Result;
-use bytecode::{builtin, Value};
+use bytecode::{builtin, Queue, Value};
 
-use crate::{Runtime, VmError};
+use crate::{
+    micro_code::{join_timeout, thread_id, yield_},
+    start_callback, PendingBuiltin, Runtime, VmError,
+};
 
+/// Apply a builtin to its arguments, pushing its result. Builtins with no result push Unit, so a
+/// builtin called through another name (e.g let p = println; p(2)) behaves like a direct call.
 #[inline]
-pub fn apply_builtin(mut rt: Runtime, sym: &str, args: Vec<Value>) -> Result<Runtime> {
+pub fn apply_builtin(rt: &mut Runtime, sym: &str, args: Vec<Value>) -> Result<()> {
     match sym {
         builtin::READ_LINE_SYM => {
-            let input = builtin::read_line_impl()?;
+            let input = rt.read_input(builtin::read_line_impl)?;
+            rt.current_thread.operand_stack.push(Value::String(input));
+        }
+        builtin::READ_ALL_SYM => {
+            let input = rt.read_input(builtin::read_all_impl)?;
             rt.current_thread.operand_stack.push(Value::String(input));
         }
+        builtin::EOF_SYM => {
+            let eof = rt.eof()?;
+            rt.current_thread.operand_stack.push(Value::Bool(eof));
+        }
         builtin::PRINT_SYM => {
             for arg in args {
-                builtin::print_impl(&arg);
+                builtin::print_impl(&arg, rt.float_precision);
             }
+            rt.current_thread.operand_stack.push(Value::Unit);
         }
         builtin::PRINTLN_SYM => {
-            for arg in args[..args.len() - 1].iter() {
-                builtin::print_impl(arg);
+            let (last, rest) = args.split_last().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+            for arg in rest.iter() {
+                builtin::print_impl(arg, rt.float_precision);
             }
-            if let Some(arg) = args.last() {
-                builtin::println_impl(arg);
+            builtin::println_impl(last, rt.float_precision);
+            rt.current_thread.operand_stack.push(Value::Unit);
+        }
+        builtin::EPRINT_SYM => {
+            for arg in args {
+                builtin::eprint_impl(&arg, rt.float_precision);
+            }
+            rt.current_thread.operand_stack.push(Value::Unit);
+        }
+        builtin::EPRINTLN_SYM => {
+            let (last, rest) = args.split_last().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+            for arg in rest.iter() {
+                builtin::eprint_impl(arg, rt.float_precision);
             }
+            builtin::eprintln_impl(last, rt.float_precision);
+            rt.current_thread.operand_stack.push(Value::Unit);
         }
         builtin::STRING_LEN_SYM => {
             let s = args.first().ok_or(VmError::InsufficientArguments {
@@ -125,6 +161,156 @@ pub fn apply_builtin(mut rt: Runtime, sym: &str, args: Vec<Value>) -> Result<Run
             let pow = builtin::pow_impl(x, y)?;
             rt.current_thread.operand_stack.push(pow);
         }
+        builtin::FLOOR_SYM => {
+            let x = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+
+            let floor = builtin::floor_impl(x)?;
+            rt.current_thread.operand_stack.push(floor);
+        }
+        builtin::CEIL_SYM => {
+            let x = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+
+            let ceil = builtin::ceil_impl(x)?;
+            rt.current_thread.operand_stack.push(ceil);
+        }
+        builtin::ROUND_SYM => {
+            let x = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+
+            let round = builtin::round_impl(x)?;
+            rt.current_thread.operand_stack.push(round);
+        }
+        builtin::TRUNC_SYM => {
+            let x = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+
+            let trunc = builtin::trunc_impl(x)?;
+            rt.current_thread.operand_stack.push(trunc);
+        }
+        builtin::EXP_SYM => {
+            let x = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+
+            let exp = builtin::exp_impl(x)?;
+            rt.current_thread.operand_stack.push(exp);
+        }
+        builtin::LOG2_SYM => {
+            let x = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+
+            let log2 = builtin::log2_impl(x)?;
+            rt.current_thread.operand_stack.push(log2);
+        }
+        builtin::LOG10_SYM => {
+            let x = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+
+            let log10 = builtin::log10_impl(x)?;
+            rt.current_thread.operand_stack.push(log10);
+        }
+        builtin::ATAN2_SYM => {
+            let y = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+            let x = args.get(1).ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+
+            let atan2 = builtin::atan2_impl(y, x)?;
+            rt.current_thread.operand_stack.push(atan2);
+        }
+        builtin::HYPOT_SYM => {
+            let x = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+            let y = args.get(1).ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+
+            let hypot = builtin::hypot_impl(x, y)?;
+            rt.current_thread.operand_stack.push(hypot);
+        }
+        builtin::CLAMP_SYM => {
+            let x = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 3,
+                got: args.len(),
+            })?;
+            let lo = args.get(1).ok_or(VmError::InsufficientArguments {
+                expected: 3,
+                got: args.len(),
+            })?;
+            let hi = args.get(2).ok_or(VmError::InsufficientArguments {
+                expected: 3,
+                got: args.len(),
+            })?;
+
+            let clamp = builtin::clamp_impl(x, lo, hi)?;
+            rt.current_thread.operand_stack.push(clamp);
+        }
+        builtin::IS_NAN_SYM => {
+            let x = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+
+            let is_nan = builtin::is_nan_impl(x)?;
+            rt.current_thread.operand_stack.push(is_nan);
+        }
+        builtin::IS_FINITE_SYM => {
+            let x = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+
+            let is_finite = builtin::is_finite_impl(x)?;
+            rt.current_thread.operand_stack.push(is_finite);
+        }
+        builtin::DIV_SYM => {
+            let a = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+            let b = args.get(1).ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+
+            let div = builtin::div_impl(a, b)?;
+            rt.current_thread.operand_stack.push(div);
+        }
+        builtin::FDIV_SYM => {
+            let a = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+            let b = args.get(1).ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+
+            let fdiv = builtin::fdiv_impl(a, b)?;
+            rt.current_thread.operand_stack.push(fdiv);
+        }
         builtin::ITOA_SYM => {
             let x = args.first().ok_or(VmError::InsufficientArguments {
                 expected: 1,
@@ -143,6 +329,50 @@ pub fn apply_builtin(mut rt: Runtime, sym: &str, args: Vec<Value>) -> Result<Run
             let atoi = builtin::atoi_impl(s)?;
             rt.current_thread.operand_stack.push(atoi);
         }
+        builtin::PARSE_INT_SYM => {
+            let s = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+
+            let n = builtin::parse_int_impl(s)?;
+            rt.current_thread.parse_failed = n.is_none();
+            rt.current_thread
+                .operand_stack
+                .push(n.unwrap_or(Value::Int(0)));
+        }
+        builtin::PARSE_INT_RADIX_SYM => {
+            let s = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+            let base = args.get(1).ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+
+            let n = builtin::parse_int_radix_impl(s, base)?;
+            rt.current_thread.parse_failed = n.is_none();
+            rt.current_thread
+                .operand_stack
+                .push(n.unwrap_or(Value::Int(0)));
+        }
+        builtin::PARSE_FLOAT_SYM => {
+            let s = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+
+            let x = builtin::parse_float_impl(s)?;
+            rt.current_thread.parse_failed = x.is_none();
+            rt.current_thread
+                .operand_stack
+                .push(x.unwrap_or(Value::Float(0.0)));
+        }
+        builtin::PARSE_FAILED_SYM => {
+            let failed = rt.current_thread.parse_failed;
+            rt.current_thread.operand_stack.push(Value::Bool(failed));
+        }
         builtin::FLOAT_TO_INT_SYM => {
             let x = args.first().ok_or(VmError::InsufficientArguments {
                 expected: 1,
@@ -161,8 +391,106 @@ pub fn apply_builtin(mut rt: Runtime, sym: &str, args: Vec<Value>) -> Result<Run
             let int_to_float = builtin::int_to_float_impl(x)?;
             rt.current_thread.operand_stack.push(int_to_float);
         }
+        builtin::CLONE_SYM => {
+            let v = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+
+            rt.current_thread.operand_stack.push(builtin::clone_impl(v));
+        }
+        builtin::JSON_STRINGIFY_SYM => {
+            let v = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+
+            let json = builtin::json_stringify_impl(v)?;
+            rt.current_thread.operand_stack.push(json);
+        }
+        builtin::JSON_GET_SYM => {
+            let s = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+            let path = args.get(1).ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+
+            let v = builtin::json_get_impl(s, path)?;
+            rt.current_thread.parse_failed = v.is_none();
+            rt.current_thread
+                .operand_stack
+                .push(v.unwrap_or(Value::String(String::new())));
+        }
+        builtin::TYPE_OF_SYM => {
+            let v = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+
+            rt.current_thread
+                .operand_stack
+                .push(builtin::type_of_impl(v));
+        }
+        builtin::VARS_SYM => {
+            let vars = builtin::vars_impl(&rt.current_thread.env);
+            rt.current_thread.operand_stack.push(vars);
+        }
+        // the expression text is only there when dbg is called by name
+        builtin::DBG_SYM => {
+            let v = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+
+            // pc is past the CALL
+            let at = rt.source_location(rt.current_thread.pc - 1);
+            let v = builtin::dbg_impl(v, args.get(1), at.as_deref())?;
+            rt.current_thread.operand_stack.push(v);
+        }
+        builtin::TO_FIXED_SYM => {
+            let f = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+            let digits = args.get(1).ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+
+            let fixed = builtin::to_fixed_impl(f, digits)?;
+            rt.current_thread.operand_stack.push(fixed);
+        }
+        builtin::SET_FLOAT_PRECISION_SYM => {
+            let digits = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+
+            rt.float_precision = builtin::set_float_precision_impl(digits)?;
+            rt.current_thread.operand_stack.push(Value::Unit);
+        }
+        builtin::FORMAT_INT_SYM => {
+            let n = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+            let base = args.get(1).ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+
+            let formatted = builtin::format_int_impl(n, base)?;
+            rt.current_thread.operand_stack.push(formatted);
+        }
         builtin::SEM_CREATE_SYM => {
-            let sem = builtin::sem_create_impl();
+            // the initial count is optional
+            let default = Value::Int(builtin::SEM_CREATE_DEFAULT_COUNT);
+            let count = args.first().unwrap_or(&default);
+
+            let sem = builtin::sem_create_impl(count)?;
             rt.current_thread.operand_stack.push(sem);
         }
         builtin::SEM_SET_SYM => {
@@ -176,6 +504,322 @@ pub fn apply_builtin(mut rt: Runtime, sym: &str, args: Vec<Value>) -> Result<Run
             })?;
 
             builtin::sem_set_impl(sem, val)?;
+            rt.current_thread.operand_stack.push(Value::Unit);
+        }
+        builtin::SEM_VALUE_SYM => {
+            let sem = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+
+            let count = builtin::sem_value_impl(sem)?;
+            rt.current_thread.operand_stack.push(count);
+        }
+        builtin::QUEUE_CREATE_SYM => {
+            let q = builtin::queue_create_impl();
+            rt.current_thread.operand_stack.push(q);
+        }
+        builtin::QUEUE_CREATE_BOUNDED_SYM => {
+            let capacity = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+
+            let q = builtin::queue_create_bounded_impl(capacity)?;
+            rt.current_thread.operand_stack.push(q);
+        }
+        builtin::QUEUE_PUSH_SYM => {
+            let q = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+            let v = args.get(1).ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+
+            if builtin::queue_push_impl(q, v)? {
+                rt.current_thread.operand_stack.push(Value::Unit);
+            } else if rt.ready_queue.is_empty() {
+                // No other thread can run to pop from the queue
+                return Err(VmError::FullQueueDeadlock(rt.current_thread.thread_id).into());
+            } else {
+                return retry_call(rt, builtin::queue_push(), args);
+            }
+        }
+        builtin::QUEUE_TRY_PUSH_SYM => {
+            let q = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+            let v = args.get(1).ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+
+            let pushed = builtin::queue_try_push_impl(q, v)?;
+            rt.current_thread.operand_stack.push(pushed);
+        }
+        builtin::QUEUE_POP_SYM => {
+            let q = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+
+            match builtin::queue_pop_impl(q)? {
+                Some(v) => rt.current_thread.operand_stack.push(v),
+                None if rt.ready_queue.is_empty() => {
+                    // No other thread can run to push to the queue
+                    return Err(VmError::EmptyQueueDeadlock(rt.current_thread.thread_id).into());
+                }
+                None => return retry_call(rt, builtin::queue_pop(), args),
+            }
+        }
+        builtin::QUEUE_TRY_POP_SYM => {
+            let q = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+            let default = args.get(1).ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+
+            let v = builtin::queue_try_pop_impl(q, default)?;
+            rt.current_thread.operand_stack.push(v);
+        }
+        builtin::MAP_SYM => {
+            let q = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+            let f = args.get(1).ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+
+            let pending = PendingBuiltin::Map {
+                f: f.clone(),
+                rest: queue_values(q)?,
+                out: vec![],
+            };
+            return start_callback(rt, pending, None);
+        }
+        builtin::FILTER_SYM => {
+            let q = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+            let pred = args.get(1).ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+
+            let pending = PendingBuiltin::Filter {
+                pred: pred.clone(),
+                rest: queue_values(q)?,
+                current: None,
+                out: vec![],
+            };
+            return start_callback(rt, pending, None);
+        }
+        builtin::REDUCE_SYM => {
+            let q = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 3,
+                got: args.len(),
+            })?;
+            let init = args.get(1).ok_or(VmError::InsufficientArguments {
+                expected: 3,
+                got: args.len(),
+            })?;
+            let f = args.get(2).ok_or(VmError::InsufficientArguments {
+                expected: 3,
+                got: args.len(),
+            })?;
+
+            let pending = PendingBuiltin::Reduce {
+                f: f.clone(),
+                rest: queue_values(q)?,
+            };
+            return start_callback(rt, pending, Some(init.clone()));
+        }
+        builtin::MATRIX_SYM => {
+            let rows = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 3,
+                got: args.len(),
+            })?;
+            let cols = args.get(1).ok_or(VmError::InsufficientArguments {
+                expected: 3,
+                got: args.len(),
+            })?;
+            let init = args.get(2).ok_or(VmError::InsufficientArguments {
+                expected: 3,
+                got: args.len(),
+            })?;
+
+            let m = builtin::matrix_impl(rows, cols, init)?;
+            rt.current_thread.operand_stack.push(m);
+        }
+        builtin::MAT_GET_SYM => {
+            let m = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 3,
+                got: args.len(),
+            })?;
+            let i = args.get(1).ok_or(VmError::InsufficientArguments {
+                expected: 3,
+                got: args.len(),
+            })?;
+            let j = args.get(2).ok_or(VmError::InsufficientArguments {
+                expected: 3,
+                got: args.len(),
+            })?;
+
+            let x = builtin::mat_get_impl(m, i, j)?;
+            rt.current_thread.operand_stack.push(x);
+        }
+        builtin::MAT_SET_SYM => {
+            let m = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 4,
+                got: args.len(),
+            })?;
+            let i = args.get(1).ok_or(VmError::InsufficientArguments {
+                expected: 4,
+                got: args.len(),
+            })?;
+            let j = args.get(2).ok_or(VmError::InsufficientArguments {
+                expected: 4,
+                got: args.len(),
+            })?;
+            let x = args.get(3).ok_or(VmError::InsufficientArguments {
+                expected: 4,
+                got: args.len(),
+            })?;
+
+            builtin::mat_set_impl(m, i, j, x)?;
+            rt.current_thread.operand_stack.push(Value::Unit);
+        }
+        builtin::MAT_ROWS_SYM => {
+            let m = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+
+            let rows = builtin::mat_rows_impl(m)?;
+            rt.current_thread.operand_stack.push(rows);
+        }
+        builtin::MAT_COLS_SYM => {
+            let m = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+
+            let cols = builtin::mat_cols_impl(m)?;
+            rt.current_thread.operand_stack.push(cols);
+        }
+        builtin::MATMUL_SYM => {
+            let a = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+            let b = args.get(1).ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+
+            let product = builtin::matmul_impl(a, b)?;
+            rt.current_thread.operand_stack.push(product);
+        }
+        builtin::JOIN_TIMEOUT_SYM => {
+            let tid = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+            let ms = args.get(1).ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+
+            let tid = thread_id(tid)?;
+            let ms: i64 = ms.clone().try_into()?;
+            return join_timeout(rt, tid, ms);
+        }
+        builtin::IS_ALIVE_SYM => {
+            let tid = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+
+            let tid = thread_id(tid)?;
+            let alive = rt.is_alive(tid);
+            rt.current_thread.operand_stack.push(Value::Bool(alive));
+        }
+        builtin::DETACH_SYM => {
+            let tid = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+
+            let tid = thread_id(tid)?;
+            rt.detach(tid)?;
+            rt.current_thread.operand_stack.push(Value::Unit);
+        }
+        builtin::CANCEL_SYM => {
+            let tid = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+
+            let tid = thread_id(tid)?;
+            rt.cancel(tid)?;
+            rt.current_thread.operand_stack.push(Value::Unit);
+        }
+        builtin::CURRENT_TID_SYM => {
+            let tid = rt.current_thread.thread_id;
+            rt.current_thread.operand_stack.push(Value::ThreadId(tid));
+        }
+        builtin::THREAD_COUNT_SYM => {
+            let count = rt.live_thread_count() as i64;
+            rt.current_thread.operand_stack.push(Value::Int(count));
+        }
+        builtin::SCHED_STATS_SYM => {
+            let stats = rt.sched_report().to_json();
+            rt.current_thread.operand_stack.push(Value::String(stats));
+        }
+        builtin::EXIT_SYM => {
+            let code = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 1,
+                got: args.len(),
+            })?;
+
+            // Stop every thread: the run loop checks done before fetching the next instruction
+            rt.exit_code = Some(builtin::exit_impl(code)?);
+            rt.done = true;
+            rt.current_thread.operand_stack.push(Value::Unit);
+        }
+        #[cfg(feature = "exec")]
+        builtin::EXEC_SYM => {
+            let cmd = args.first().ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+            let timeout_ms = args.get(1).ok_or(VmError::InsufficientArguments {
+                expected: 2,
+                got: args.len(),
+            })?;
+
+            let out = builtin::exec_impl(cmd, timeout_ms)?;
+            rt.current_thread.exec_output = (out.stdout, out.stderr);
+            rt.current_thread.operand_stack.push(Value::Int(out.code));
+        }
+        #[cfg(feature = "exec")]
+        builtin::EXEC_STDOUT_SYM => {
+            let out = rt.current_thread.exec_output.0.clone();
+            rt.current_thread.operand_stack.push(Value::String(out));
+        }
+        #[cfg(feature = "exec")]
+        builtin::EXEC_STDERR_SYM => {
+            let err = rt.current_thread.exec_output.1.clone();
+            rt.current_thread.operand_stack.push(Value::String(err));
         }
         _ => {
             return Err(VmError::UnknownBuiltin {
@@ -185,14 +829,33 @@ pub fn apply_builtin(mut rt: Runtime, sym: &str, args: Vec<Value>) -> Result<Run
         }
     }
 
-    Ok(rt)
+    Ok(())
+}
+
+/// The values in a queue when a builtin like map is called. Pushes and pops while the builtin runs
+/// don't change what it goes through.
+fn queue_values(q: &Value) -> Result<VecDeque<Value>> {
+    let q: Queue = q.clone().try_into()?;
+    let vals = q.borrow().values.clone();
+    Ok(vals)
+}
+
+/// Put a builtin call that can't complete yet back on the operand stack and yield, so the CALL is
+/// executed again when the thread is next scheduled, like join does.
+pub(crate) fn retry_call(rt: &mut Runtime, closure: Value, args: Vec<Value>) -> Result<()> {
+    rt.current_thread.operand_stack.push(closure);
+    rt.current_thread.operand_stack.extend(args);
+    rt.current_thread.pc -= 1;
+    yield_(rt)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use anyhow::Ok;
-    use bytecode::{builtin::*, type_of, Semaphore};
+    use bytecode::{builtin::*, type_of, Queue, Semaphore};
+
+    use crate::{micro_code::spawn, MAIN_THREAD_ID};
 
     #[test]
     fn test_apply_builtin() -> Result<()> {
@@ -203,17 +866,17 @@ mod tests {
         let sym = PRINT_SYM;
         let args = vec![Value::String(hello_world.clone())];
         println!("Expect to see 'Hello, world!':");
-        rt = apply_builtin(rt, sym, args)?;
+        apply_builtin(&mut rt, sym, args)?;
         println!();
 
         let sym = PRINTLN_SYM;
         let args = vec![Value::String(hello_world.clone())];
         println!("Expect to see 'Hello, world!':");
-        rt = apply_builtin(rt, sym, args)?;
+        apply_builtin(&mut rt, sym, args)?;
 
         let sym = STRING_LEN_SYM;
         let args = vec![Value::String(hello_world.clone())];
-        rt = apply_builtin(rt, sym, args)?;
+        apply_builtin(&mut rt, sym, args)?;
         assert_eq!(
             Value::Int(hello_world.clone().len() as i64),
             rt.current_thread.operand_stack.pop().unwrap()
@@ -222,7 +885,7 @@ mod tests {
         // Conv
         let sym = INT_TO_FLOAT_SYM;
         let args = vec![Value::Int(42)];
-        rt = apply_builtin(rt, sym, args)?;
+        apply_builtin(&mut rt, sym, args)?;
 
         let expected = Value::Float(42.0);
         let actual = rt.current_thread.operand_stack.pop().unwrap();
@@ -230,7 +893,7 @@ mod tests {
 
         let sym = FLOAT_TO_INT_SYM;
         let args = vec![Value::Float(42.0)];
-        rt = apply_builtin(rt, sym, args)?;
+        apply_builtin(&mut rt, sym, args)?;
 
         let expected = Value::Int(42);
         let actual = rt.current_thread.operand_stack.pop().unwrap();
@@ -238,36 +901,91 @@ mod tests {
 
         let sym = ATOI_SYM;
         let args = vec![Value::String("42".to_string())];
-        rt = apply_builtin(rt, sym, args)?;
+        apply_builtin(&mut rt, sym, args)?;
         assert_eq!(
             Value::Int(42),
             rt.current_thread.operand_stack.pop().unwrap()
         );
 
         let args: Vec<Value> = vec![Value::String("forty-two".to_string())];
-        let result = apply_builtin(rt, sym, args);
+        let result = apply_builtin(&mut rt, sym, args);
         assert!(result.is_err());
 
         let mut rt = Runtime::default();
         let sym = ITOA_SYM;
         let args = vec![Value::Int(42)];
-        rt = apply_builtin(rt, sym, args)?;
+        apply_builtin(&mut rt, sym, args)?;
         assert_eq!(
             Value::String("42".to_string()),
             rt.current_thread.operand_stack.pop().unwrap()
         );
 
+        // parse_* return a sentinel on bad input instead of failing, and parse_failed reports it
+        let parse_cases = [
+            (
+                PARSE_INT_SYM,
+                vec![Value::String(" 42\n".into())],
+                Value::Int(42),
+                false,
+            ),
+            (
+                PARSE_INT_SYM,
+                vec![Value::String("4x".into())],
+                Value::Int(0),
+                true,
+            ),
+            (
+                PARSE_INT_RADIX_SYM,
+                vec![Value::String("-FF".into()), Value::Int(16)],
+                Value::Int(-255),
+                false,
+            ),
+            (
+                PARSE_INT_RADIX_SYM,
+                vec![Value::String("2".into()), Value::Int(2)],
+                Value::Int(0),
+                true,
+            ),
+            (
+                PARSE_FLOAT_SYM,
+                vec![Value::String("2.5e1".into())],
+                Value::Float(25.0),
+                false,
+            ),
+            (
+                PARSE_FLOAT_SYM,
+                vec![Value::String("".into())],
+                Value::Float(0.0),
+                true,
+            ),
+        ];
+        for (sym, args, exp, failed) in parse_cases {
+            apply_builtin(&mut rt, sym, args)?;
+            assert_eq!(exp, rt.current_thread.operand_stack.pop().unwrap());
+            apply_builtin(&mut rt, PARSE_FAILED_SYM, vec![])?;
+            assert_eq!(
+                Value::Bool(failed),
+                rt.current_thread.operand_stack.pop().unwrap()
+            );
+        }
+
+        let args = vec![Value::String("1".into()), Value::Int(37)];
+        let result = apply_builtin(&mut rt, PARSE_INT_RADIX_SYM, args);
+        assert!(result.is_err());
+
+        let mut rt = Runtime::default();
+
         // Math
         let sym = MIN_SYM;
         let args = vec![Value::Int(42), Value::Int(24)];
-        rt = apply_builtin(rt, sym, args)?;
+        apply_builtin(&mut rt, sym, args)?;
         assert_eq!(
             Value::Int(24),
             rt.current_thread.operand_stack.pop().unwrap()
         );
 
         let args = vec![Value::Float(42.0), Value::Float(24.0)];
-        rt = apply_builtin(rt, sym, args)?;
+        apply_builtin(&mut rt, sym, args)?;
         assert_eq!(
             Value::Float(24.0),
             rt.current_thread.operand_stack.pop().unwrap()
@@ -275,14 +993,14 @@ mod tests {
 
         let sym = MAX_SYM;
         let args = vec![Value::Int(42), Value::Int(24)];
-        rt = apply_builtin(rt, sym, args)?;
+        apply_builtin(&mut rt, sym, args)?;
         assert_eq!(
             Value::Int(42),
             rt.current_thread.operand_stack.pop().unwrap()
         );
 
         let args = vec![Value::Float(42.0), Value::Float(24.0)];
-        rt = apply_builtin(rt, sym, args)?;
+        apply_builtin(&mut rt, sym, args)?;
         assert_eq!(
             Value::Float(42.0),
             rt.current_thread.operand_stack.pop().unwrap()
@@ -290,14 +1008,14 @@ mod tests {
 
         let sym = ABS_SYM;
         let args = vec![Value::Int(-42)];
-        rt = apply_builtin(rt, sym, args)?;
+        apply_builtin(&mut rt, sym, args)?;
         assert_eq!(
             Value::Int(42),
             rt.current_thread.operand_stack.pop().unwrap()
         );
 
         let args = vec![Value::Float(-42.0)];
-        rt = apply_builtin(rt, sym, args)?;
+        apply_builtin(&mut rt, sym, args)?;
         assert_eq!(
             Value::Float(42.0),
             rt.current_thread.operand_stack.pop().unwrap()
@@ -305,14 +1023,14 @@ mod tests {
 
         let sym = COS_SYM;
         let args = vec![Value::Float(0.0)];
-        rt = apply_builtin(rt, sym, args)?;
+        apply_builtin(&mut rt, sym, args)?;
         assert_eq!(
             Value::Float(0.0_f64.cos()),
             rt.current_thread.operand_stack.pop().unwrap()
         );
 
         let args = vec![Value::Float(std::f64::consts::PI)];
-        rt = apply_builtin(rt, sym, args)?;
+        apply_builtin(&mut rt, sym, args)?;
         assert_eq!(
             Value::Float(std::f64::consts::PI.cos()),
             rt.current_thread.operand_stack.pop().unwrap()
@@ -320,14 +1038,14 @@ mod tests {
 
         let sym = SIN_SYM;
         let args = vec![Value::Float(0.0)];
-        rt = apply_builtin(rt, sym, args)?;
+        apply_builtin(&mut rt, sym, args)?;
         assert_eq!(
             Value::Float(0.0),
             rt.current_thread.operand_stack.pop().unwrap()
         );
 
         let args = vec![Value::Float(std::f64::consts::PI)];
-        rt = apply_builtin(rt, sym, args)?;
+        apply_builtin(&mut rt, sym, args)?;
         assert_eq!(
             Value::Float(std::f64::consts::PI.sin()),
             rt.current_thread.operand_stack.pop().unwrap()
@@ -335,14 +1053,14 @@ mod tests {
 
         let sym = TAN_SYM;
         let args = vec![Value::Float(0.0)];
-        rt = apply_builtin(rt, sym, args)?;
+        apply_builtin(&mut rt, sym, args)?;
         assert_eq!(
             Value::Float(0.0),
             rt.current_thread.operand_stack.pop().unwrap()
         );
 
         let args = vec![Value::Float(std::f64::consts::PI)];
-        rt = apply_builtin(rt, sym, args)?;
+        apply_builtin(&mut rt, sym, args)?;
         assert_eq!(
             Value::Float(std::f64::consts::PI.tan()),
             rt.current_thread.operand_stack.pop().unwrap()
@@ -350,14 +1068,14 @@ mod tests {
 
         let sym = SQRT_SYM;
         let args = vec![Value::Float(42.0)];
-        rt = apply_builtin(rt, sym, args)?;
+        apply_builtin(&mut rt, sym, args)?;
         assert_eq!(
             Value::Float(42.0_f64.sqrt()),
             rt.current_thread.operand_stack.pop().unwrap()
         );
 
         let args = vec![Value::Float(102934.0)];
-        rt = apply_builtin(rt, sym, args)?;
+        apply_builtin(&mut rt, sym, args)?;
         assert_eq!(
             Value::Float(102934.0_f64.sqrt()),
             rt.current_thread.operand_stack.pop().unwrap()
@@ -365,39 +1083,349 @@ mod tests {
 
         let sym = POW_SYM;
         let args = vec![Value::Float(2.0), Value::Float(3.0)];
-        rt = apply_builtin(rt, sym, args)?;
+        apply_builtin(&mut rt, sym, args)?;
         assert_eq!(
             Value::Float(2.0_f64.powf(3.0)),
             rt.current_thread.operand_stack.pop().unwrap()
         );
 
         let args = vec![Value::Float(2.0), Value::Int(3)];
-        let result = apply_builtin(rt, sym, args);
+        let result = apply_builtin(&mut rt, sym, args);
         assert!(result.is_err());
 
         let mut rt = Runtime::default();
         let sym = LOG_SYM;
         let args = vec![Value::Float(42.0)];
-        rt = apply_builtin(rt, sym, args)?;
+        apply_builtin(&mut rt, sym, args)?;
         assert_eq!(
             Value::Float(42.0_f64.log(10.0)),
             rt.current_thread.operand_stack.pop().unwrap()
         );
 
         let sym = SEM_CREATE_SYM;
-        let args = vec![];
-        rt = apply_builtin(rt, sym, args)?;
+        let args = vec![Value::Int(SEM_CREATE_DEFAULT_COUNT)];
+        apply_builtin(&mut rt, sym, args)?;
         assert_eq!(
             type_of(&Value::Semaphore(Semaphore::default())),
             type_of(&rt.current_thread.operand_stack.pop().unwrap())
         );
 
+        // without a count it starts at the default
+        apply_builtin(&mut rt, sym, vec![])?;
+        let sem = rt.current_thread.operand_stack.pop().unwrap();
+        apply_builtin(&mut rt, SEM_VALUE_SYM, vec![sem])?;
+        assert_eq!(
+            Value::Int(SEM_CREATE_DEFAULT_COUNT),
+            rt.current_thread.operand_stack.pop().unwrap()
+        );
+
+        let args = vec![Value::Int(3)];
+        apply_builtin(&mut rt, sym, args)?;
+        let sem = rt.current_thread.operand_stack.pop().unwrap();
+        apply_builtin(&mut rt, SEM_VALUE_SYM, vec![sem])?;
+        assert_eq!(
+            Value::Int(3),
+            rt.current_thread.operand_stack.pop().unwrap()
+        );
+
+        let args = vec![Value::Int(-1)];
+        let result = apply_builtin(&mut rt, sym, args);
+        assert!(result.is_err());
+
+        let mut rt = Runtime::default();
         let sym = SEM_SET_SYM;
         let sem = Semaphore::default();
         let args = vec![sem.clone().into(), Value::Int(42)];
-        _ = apply_builtin(rt, sym, args)?;
-        let sem_guard = sem.lock().unwrap();
-        assert_eq!(42, *sem_guard);
+        apply_builtin(&mut rt, sym, args)?;
+        {
+            let sem_guard = sem.lock().unwrap();
+            assert_eq!(42, *sem_guard);
+        }
+
+        let args = vec![sem.clone().into(), Value::Int(-1)];
+        let result = apply_builtin(&mut rt, sym, args);
+        assert!(result.is_err());
+
+        let mut rt = Runtime::default();
+
+        let q = Queue::new();
+        apply_builtin(
+            &mut rt,
+            QUEUE_PUSH_SYM,
+            vec![q.clone().into(), Value::Int(1)],
+        )?;
+        apply_builtin(
+            &mut rt,
+            QUEUE_PUSH_SYM,
+            vec![q.clone().into(), Value::Int(2)],
+        )?;
+        apply_builtin(&mut rt, QUEUE_POP_SYM, vec![q.clone().into()])?;
+        assert_eq!(
+            Value::Int(1),
+            rt.current_thread.operand_stack.pop().unwrap()
+        );
+        apply_builtin(&mut rt, QUEUE_POP_SYM, vec![q.clone().into()])?;
+        assert_eq!(
+            Value::Int(2),
+            rt.current_thread.operand_stack.pop().unwrap()
+        );
+
+        let result = apply_builtin(&mut rt, QUEUE_PUSH_SYM, vec![q.clone().into(), queue_pop()]);
+        assert!(result.is_err());
+
+        // popping an empty queue puts the call back and yields
+        let mut rt = Runtime::default();
+        spawn(&mut rt, 0, 0)?;
+        rt.current_thread.pc = 1;
+        apply_builtin(&mut rt, QUEUE_POP_SYM, vec![q.clone().into()])?;
+        assert_eq!(rt.current_thread.thread_id, MAIN_THREAD_ID + 1);
+        let waiting = rt.ready_queue.back().unwrap();
+        assert_eq!(waiting.pc, 0);
+        assert_eq!(waiting.operand_stack.last(), Some(&Value::Queue(q)));
+
+        // a bounded queue takes values until it is full
+        let mut rt = Runtime::default();
+        apply_builtin(&mut rt, QUEUE_CREATE_BOUNDED_SYM, vec![Value::Int(1)])?;
+        let q = rt.current_thread.operand_stack.pop().unwrap();
+        apply_builtin(&mut rt, QUEUE_TRY_PUSH_SYM, vec![q.clone(), Value::Int(1)])?;
+        assert_eq!(
+            rt.current_thread.operand_stack.pop(),
+            Some(Value::Bool(true))
+        );
+        apply_builtin(&mut rt, QUEUE_TRY_PUSH_SYM, vec![q.clone(), Value::Int(2)])?;
+        assert_eq!(
+            rt.current_thread.operand_stack.pop(),
+            Some(Value::Bool(false))
+        );
+
+        let err = apply_builtin(&mut rt, QUEUE_PUSH_SYM, vec![q.clone(), Value::Int(2)])
+            .expect_err("Nothing can pop from the queue");
+        assert_eq!(
+            err.to_string(),
+            "Deadlock: thread 1 is waiting on a full queue and no other thread can run"
+        );
+
+        apply_builtin(&mut rt, QUEUE_TRY_POP_SYM, vec![q.clone(), Value::Int(-1)])?;
+        assert_eq!(rt.current_thread.operand_stack.pop(), Some(Value::Int(1)));
+        apply_builtin(&mut rt, QUEUE_TRY_POP_SYM, vec![q.clone(), Value::Int(-1)])?;
+        assert_eq!(rt.current_thread.operand_stack.pop(), Some(Value::Int(-1)));
+
+        let result = apply_builtin(&mut rt, QUEUE_CREATE_BOUNDED_SYM, vec![Value::Int(0)]);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Illegal argument: queue capacity must be at least 1, got 0"
+        );
+
+        // pushing to a full queue puts the call back and yields
+        apply_builtin(&mut rt, QUEUE_PUSH_SYM, vec![q.clone(), Value::Int(1)])?;
+        spawn(&mut rt, 0, 0)?;
+        rt.current_thread.pc = 1;
+        apply_builtin(&mut rt, QUEUE_PUSH_SYM, vec![q.clone(), Value::Int(2)])?;
+        assert_eq!(rt.current_thread.thread_id, MAIN_THREAD_ID + 1);
+        let waiting = rt.ready_queue.back().unwrap();
+        assert_eq!(waiting.pc, 0);
+        assert_eq!(waiting.operand_stack.last(), Some(&Value::Int(2)));
+
+        let mut rt = Runtime::default();
+        let sym = EXIT_SYM;
+        let args = vec![Value::Int(3)];
+        apply_builtin(&mut rt, sym, args)?;
+        assert!(rt.done);
+        assert_eq!(rt.exit_code, Some(3));
+
+        let args = vec![Value::Int(i64::MAX)];
+        let result = apply_builtin(&mut rt, sym, args);
+        assert!(result.is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_builtin_math() -> Result<()> {
+        let mut rt = Runtime::default();
+
+        let cases: [(&str, Vec<Value>, Value); 16] = [
+            (FLOOR_SYM, vec![Value::Float(-2.5)], Value::Float(-3.0)),
+            (CEIL_SYM, vec![Value::Float(-2.5)], Value::Float(-2.0)),
+            (ROUND_SYM, vec![Value::Float(2.5)], Value::Float(3.0)),
+            (ROUND_SYM, vec![Value::Float(-2.5)], Value::Float(-3.0)),
+            (TRUNC_SYM, vec![Value::Float(-2.7)], Value::Float(-2.0)),
+            (EXP_SYM, vec![Value::Float(0.0)], Value::Float(1.0)),
+            (LOG2_SYM, vec![Value::Float(8.0)], Value::Float(3.0)),
+            (LOG10_SYM, vec![Value::Float(1000.0)], Value::Float(3.0)),
+            (
+                LOG10_SYM,
+                vec![Value::Float(0.0)],
+                Value::Float(f64::NEG_INFINITY),
+            ),
+            (
+                FLOOR_SYM,
+                vec![Value::Float(f64::INFINITY)],
+                Value::Float(f64::INFINITY),
+            ),
+            (
+                ATAN2_SYM,
+                vec![Value::Float(1.0), Value::Float(1.0)],
+                Value::Float(std::f64::consts::FRAC_PI_4),
+            ),
+            (
+                HYPOT_SYM,
+                vec![Value::Float(3.0), Value::Float(4.0)],
+                Value::Float(5.0),
+            ),
+            (
+                CLAMP_SYM,
+                vec![Value::Int(5), Value::Int(0), Value::Int(3)],
+                Value::Int(3),
+            ),
+            (
+                CLAMP_SYM,
+                vec![Value::Int(-5), Value::Int(0), Value::Int(3)],
+                Value::Int(0),
+            ),
+            (
+                CLAMP_SYM,
+                vec![Value::Float(1.5), Value::Float(0.0), Value::Float(3.0)],
+                Value::Float(1.5),
+            ),
+            (
+                CLAMP_SYM,
+                vec![
+                    Value::Float(f64::INFINITY),
+                    Value::Float(0.0),
+                    Value::Float(3.0),
+                ],
+                Value::Float(3.0),
+            ),
+        ];
+
+        for (sym, args, exp) in cases {
+            apply_builtin(&mut rt, sym, args)?;
+            assert_eq!(rt.current_thread.operand_stack.pop().unwrap(), exp);
+        }
+
+        // NaN propagates
+        apply_builtin(&mut rt, LOG2_SYM, vec![Value::Float(-1.0)])?;
+        let Value::Float(f) = rt.current_thread.operand_stack.pop().unwrap() else {
+            panic!("Expected float");
+        };
+        assert!(f.is_nan());
+
+        apply_builtin(
+            &mut rt,
+            CLAMP_SYM,
+            vec![Value::Float(f64::NAN), Value::Float(0.0), Value::Float(1.0)],
+        )?;
+        let Value::Float(f) = rt.current_thread.operand_stack.pop().unwrap() else {
+            panic!("Expected float");
+        };
+        assert!(f.is_nan());
+
+        for (a, b, exp) in [
+            (7, 2, 3),
+            (-7, 2, -4),
+            (7, -2, -4),
+            (-7, -2, 3),
+            (6, -3, -2),
+        ] {
+            apply_builtin(&mut rt, DIV_SYM, vec![Value::Int(a), Value::Int(b)])?;
+            assert_eq!(
+                rt.current_thread.operand_stack.pop().unwrap(),
+                Value::Int(exp)
+            );
+        }
+        apply_builtin(&mut rt, FDIV_SYM, vec![Value::Int(4), Value::Int(5)])?;
+        assert_eq!(
+            rt.current_thread.operand_stack.pop().unwrap(),
+            Value::Float(0.8)
+        );
+        let res = apply_builtin(
+            &mut Runtime::default(),
+            DIV_SYM,
+            vec![Value::Int(1), Value::Int(0)],
+        );
+        assert_eq!(res.err().unwrap().to_string(), "Division by zero");
+        let res = apply_builtin(
+            &mut Runtime::default(),
+            DIV_SYM,
+            vec![Value::Int(i64::MIN), Value::Int(-1)],
+        );
+        assert!(res.is_err());
+
+        // Bad bounds and mixed types are errors, not panics
+        let bad = [
+            vec![Value::Int(1), Value::Int(3), Value::Int(0)],
+            vec![Value::Float(1.0), Value::Float(f64::NAN), Value::Float(0.0)],
+            vec![Value::Int(1), Value::Float(0.0), Value::Int(3)],
+        ];
+        for args in bad {
+            assert!(apply_builtin(&mut Runtime::default(), CLAMP_SYM, args).is_err());
+        }
+        assert!(apply_builtin(&mut Runtime::default(), FLOOR_SYM, vec![Value::Int(2)]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_builtin_format() -> Result<()> {
+        let mut rt = Runtime::default();
+
+        let fixed = |rt: &mut Runtime, f: f64, digits: i64| {
+            apply_builtin(rt, TO_FIXED_SYM, vec![Value::Float(f), Value::Int(digits)])
+        };
+
+        fixed(&mut rt, 14.179615384615389, 2)?;
+        assert_eq!(
+            rt.current_thread.operand_stack.pop().unwrap(),
+            Value::String("14.18".into())
+        );
+        fixed(&mut rt, -0.5, 0)?;
+        assert_eq!(
+            rt.current_thread.operand_stack.pop().unwrap(),
+            Value::String("-0".into())
+        );
+        fixed(&mut rt, 2.0, 3)?;
+        assert_eq!(
+            rt.current_thread.operand_stack.pop().unwrap(),
+            Value::String("2.000".into())
+        );
+        assert!(fixed(&mut Runtime::default(), 2.0, -1).is_err());
+        assert!(fixed(&mut Runtime::default(), 2.0, 101).is_err());
+
+        let format = |rt: &mut Runtime, n: i64, base: i64| {
+            apply_builtin(rt, FORMAT_INT_SYM, vec![Value::Int(n), Value::Int(base)])
+        };
+
+        for (n, base, exp) in [
+            (10, 2, "1010"),
+            (0, 2, "0"),
+            (255, 16, "ff"),
+            (-255, 16, "-ff"),
+            (8, 8, "10"),
+            (35, 36, "z"),
+            (i64::MIN, 16, "-8000000000000000"),
+            (i64::MAX, 10, "9223372036854775807"),
+        ] {
+            format(&mut rt, n, base)?;
+            assert_eq!(
+                rt.current_thread.operand_stack.pop().unwrap(),
+                Value::String(exp.into())
+            );
+        }
+        assert!(format(&mut Runtime::default(), 2, 1).is_err());
+        assert!(format(&mut Runtime::default(), 2, 37).is_err());
+
+        let precision = |rt: &mut Runtime, digits: i64| {
+            apply_builtin(rt, SET_FLOAT_PRECISION_SYM, vec![Value::Int(digits)])
+        };
+
+        precision(&mut rt, 3)?;
+        assert_eq!(rt.float_precision, Some(3));
+        assert_eq!(rt.current_thread.operand_stack.pop().unwrap(), Value::Unit);
+        precision(&mut rt, -1)?;
+        assert_eq!(rt.float_precision, None);
+        assert!(precision(&mut Runtime::default(), -2).is_err());
+        assert!(precision(&mut Runtime::default(), 101).is_err());
 
         Ok(())
     }
@@ -8,7 +8,15 @@ use crate::{Runtime, VmError};
 /// operation, and pushes the result back onto the stack.
 /// Note the top of the stack is the right-hand side of the operation.
 /// The second-to-top of the stack is the left-hand side of the operation.
-/// The two values must be of the same type.
+/// The two values must be of the same type. An int is never converted to a float, so comparing
+/// one with a float, e.g. 2 == 2.0, is an error rather than true or false.
+///
+/// Floats follow IEEE 754: arithmetic involving NaN gives NaN, dividing by zero
+/// gives INFINITY, -INFINITY or NaN, and every comparison involving NaN is false,
+/// including NaN == NaN. Use the is_nan builtin to test for NaN.
+///
+/// Strings are ordered lexicographically by Unicode code point, so "Z" < "a" and
+/// "ab" < "b". This is the same as comparing their UTF-8 bytes.
 ///
 /// # Arguments
 ///
@@ -21,7 +29,7 @@ use crate::{Runtime, VmError};
 /// If the stack has fewer than two values or the operation is not supported
 /// for the types of the values on the stack.
 #[inline]
-pub fn binop(mut rt: Runtime, op: BinOp) -> Result<Runtime> {
+pub fn binop(rt: &mut Runtime, op: BinOp) -> Result<()> {
     let rhs_val = rt
         .current_thread
         .operand_stack
@@ -46,17 +54,25 @@ pub fn binop(mut rt: Runtime, op: BinOp) -> Result<Runtime> {
                 }
             };
             rt.current_thread.operand_stack.push(result);
-            Ok(rt)
+            Ok(())
         }
         (Value::Int(lhs), Value::Int(rhs)) => {
             let result = match op {
-                BinOp::Add => Value::Int(lhs + rhs),  // Addition
-                BinOp::Sub => Value::Int(lhs - rhs),  // Subtraction
-                BinOp::Mul => Value::Int(lhs * rhs),  // Multiplication
-                BinOp::Div => Value::Int(lhs / rhs),  // Division
-                BinOp::Mod => Value::Int(lhs % rhs),  // Modulus
-                BinOp::Gt => Value::Bool(lhs > rhs),  // Greater Than
-                BinOp::Lt => Value::Bool(lhs < rhs),  // Less Than
+                // Addition
+                BinOp::Add => checked_int(lhs.checked_add(rhs), lhs, op, rhs)?,
+                // Subtraction
+                BinOp::Sub => checked_int(lhs.checked_sub(rhs), lhs, op, rhs)?,
+                // Multiplication
+                BinOp::Mul => checked_int(lhs.checked_mul(rhs), lhs, op, rhs)?,
+                BinOp::Div | BinOp::Mod if rhs == 0 => return Err(VmError::DivisionByZero.into()),
+                // Division, truncates towards zero
+                BinOp::Div => checked_int(lhs.checked_div(rhs), lhs, op, rhs)?,
+                // Modulus
+                BinOp::Mod => checked_int(lhs.checked_rem(rhs), lhs, op, rhs)?,
+                BinOp::Gt => Value::Bool(lhs > rhs), // Greater Than
+                BinOp::Lt => Value::Bool(lhs < rhs), // Less Than
+                BinOp::Ge => Value::Bool(lhs >= rhs), // Greater Than or Equal
+                BinOp::Le => Value::Bool(lhs <= rhs), // Less Than or Equal
                 BinOp::Eq => Value::Bool(lhs == rhs), // Equality
                 BinOp::And => {
                     return Err(VmError::UnsupportedOperation(
@@ -74,7 +90,7 @@ pub fn binop(mut rt: Runtime, op: BinOp) -> Result<Runtime> {
                 }
             };
             rt.current_thread.operand_stack.push(result);
-            Ok(rt)
+            Ok(())
         }
         (Value::Float(lhs), Value::Float(rhs)) => {
             let result = match op {
@@ -84,6 +100,8 @@ pub fn binop(mut rt: Runtime, op: BinOp) -> Result<Runtime> {
                 BinOp::Div => Value::Float(lhs / rhs), // Division
                 BinOp::Gt => Value::Bool(lhs > rhs),   // Greater Than
                 BinOp::Lt => Value::Bool(lhs < rhs),   // Less Than
+                BinOp::Ge => Value::Bool(lhs >= rhs),  // Greater Than or Equal
+                BinOp::Le => Value::Bool(lhs <= rhs),  // Less Than or Equal
                 BinOp::Eq => Value::Bool(lhs == rhs),  // Equality
                 BinOp::Or => {
                     return Err(VmError::UnsupportedOperation(
@@ -108,7 +126,7 @@ pub fn binop(mut rt: Runtime, op: BinOp) -> Result<Runtime> {
                 }
             };
             rt.current_thread.operand_stack.push(result);
-            Ok(rt)
+            Ok(())
         }
         (Value::Bool(lhs), Value::Bool(rhs)) => {
             let result = match op {
@@ -124,12 +142,20 @@ pub fn binop(mut rt: Runtime, op: BinOp) -> Result<Runtime> {
                 }
             };
             rt.current_thread.operand_stack.push(result);
-            Ok(rt)
+            Ok(())
         }
         (Value::String(lhs), Value::String(rhs)) => {
             let result = match op {
-                BinOp::Add => Value::String(lhs + &rhs),
+                BinOp::Add => {
+                    rt.check_string_len(lhs.len() + rhs.len())?;
+                    Value::String(lhs + &rhs)
+                }
                 BinOp::Eq => Value::Bool(lhs == rhs),
+                // Lexicographic by Unicode code point, which for UTF-8 is the same as by byte
+                BinOp::Gt => Value::Bool(lhs > rhs),
+                BinOp::Lt => Value::Bool(lhs < rhs),
+                BinOp::Ge => Value::Bool(lhs >= rhs),
+                BinOp::Le => Value::Bool(lhs <= rhs),
                 _ => {
                     return Err(VmError::UnsupportedOperation(
                         op.into(),
@@ -139,7 +165,7 @@ pub fn binop(mut rt: Runtime, op: BinOp) -> Result<Runtime> {
                 }
             };
             rt.current_thread.operand_stack.push(result);
-            Ok(rt)
+            Ok(())
         }
         (Value::Semaphore(s1), Value::Semaphore(s2)) => {
             let result = match op {
@@ -153,11 +179,54 @@ pub fn binop(mut rt: Runtime, op: BinOp) -> Result<Runtime> {
                 }
             };
             rt.current_thread.operand_stack.push(result);
-            Ok(rt)
+            Ok(())
+        }
+        (Value::Queue(q1), Value::Queue(q2)) => {
+            let result = match op {
+                BinOp::Eq => Value::Bool(q1 == q2),
+                _ => {
+                    return Err(VmError::UnsupportedOperation(
+                        op.into(),
+                        type_of(&rhs_val).to_string(),
+                    )
+                    .into())
+                }
+            };
+            rt.current_thread.operand_stack.push(result);
+            Ok(())
+        }
+        // matrices are equal if they have the same shape and elements, like a copy made by clone.
+        // Queues and semaphores are only equal to themselves
+        (Value::Matrix(m1), Value::Matrix(m2)) => {
+            let result = match op {
+                BinOp::Eq => Value::Bool(*m1.borrow() == *m2.borrow()),
+                _ => {
+                    return Err(VmError::UnsupportedOperation(
+                        op.into(),
+                        type_of(&rhs_val).to_string(),
+                    )
+                    .into())
+                }
+            };
+            rt.current_thread.operand_stack.push(result);
+            Ok(())
         }
         (Value::Closure { .. }, Value::Closure { .. }) => {
             Err(VmError::UnsupportedOperation(op.into(), type_of(&rhs_val).to_string()).into())
         }
+        (Value::Int(_), Value::Float(_)) | (Value::Float(_), Value::Int(_))
+            if matches!(
+                op,
+                BinOp::Eq | BinOp::Lt | BinOp::Gt | BinOp::Le | BinOp::Ge
+            ) =>
+        {
+            Err(VmError::IntFloatComparison {
+                lhs: lhs_val.to_string(),
+                op: op.into(),
+                rhs: rhs_val.to_string(),
+            }
+            .into())
+        }
         _ => Err(VmError::TypeMismatch {
             expected: type_of(&lhs_val).to_string(),
             found: type_of(&rhs_val).to_string(),
@@ -166,6 +235,13 @@ pub fn binop(mut rt: Runtime, op: BinOp) -> Result<Runtime> {
     }
 }
 
+/// The result of a checked integer operation, or an overflow error if it doesn't fit in an int,
+/// e.g. MAX_INT + 1 or MIN_INT / -1
+fn checked_int(res: Option<i64>, lhs: i64, op: BinOp, rhs: i64) -> Result<Value, VmError> {
+    res.map(Value::Int)
+        .ok_or_else(|| VmError::IntegerOverflow(format!("{} {} {}", lhs, String::from(op), rhs)))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -176,171 +252,300 @@ mod tests {
     #[test]
     fn test_binop() {
         let mut rt = Runtime::new(vec![]);
-        rt = ldc(rt, Value::Int(42)).unwrap();
-        rt = ldc(rt, Value::Int(42)).unwrap();
-        rt = binop(rt, BinOp::Add).unwrap();
+        ldc(&mut rt, Value::Int(42)).unwrap();
+        ldc(&mut rt, Value::Int(42)).unwrap();
+        binop(&mut rt, BinOp::Add).unwrap();
         assert_eq!(
             rt.current_thread.operand_stack.pop().unwrap(),
             Value::Int(84)
         );
 
-        rt = ldc(rt, Value::Int(1)).unwrap();
-        rt = ldc(rt, Value::Int(2)).unwrap();
-        rt = binop(rt, BinOp::Sub).unwrap();
+        ldc(&mut rt, Value::Int(1)).unwrap();
+        ldc(&mut rt, Value::Int(2)).unwrap();
+        binop(&mut rt, BinOp::Sub).unwrap();
         assert_eq!(
             rt.current_thread.operand_stack.pop().unwrap(),
             Value::Int(-1)
         );
 
-        rt = ldc(rt, Value::Int(21)).unwrap();
-        rt = ldc(rt, Value::Int(2)).unwrap();
-        rt = binop(rt, BinOp::Mul).unwrap();
+        ldc(&mut rt, Value::Int(21)).unwrap();
+        ldc(&mut rt, Value::Int(2)).unwrap();
+        binop(&mut rt, BinOp::Mul).unwrap();
         assert_eq!(
             rt.current_thread.operand_stack.pop().unwrap(),
             Value::Int(42)
         );
 
-        rt = ldc(rt, Value::Int(84)).unwrap();
-        rt = ldc(rt, Value::Int(2)).unwrap();
-        rt = binop(rt, BinOp::Div).unwrap();
+        ldc(&mut rt, Value::Int(84)).unwrap();
+        ldc(&mut rt, Value::Int(2)).unwrap();
+        binop(&mut rt, BinOp::Div).unwrap();
         assert_eq!(
             rt.current_thread.operand_stack.pop().unwrap(),
             Value::Int(42)
         );
 
-        rt = ldc(rt, Value::Int(84)).unwrap();
-        rt = ldc(rt, Value::Int(2)).unwrap();
-        rt = binop(rt, BinOp::Mod).unwrap();
+        ldc(&mut rt, Value::Int(84)).unwrap();
+        ldc(&mut rt, Value::Int(2)).unwrap();
+        binop(&mut rt, BinOp::Mod).unwrap();
         assert_eq!(
             rt.current_thread.operand_stack.pop().unwrap(),
             Value::Int(0)
         );
 
-        rt = ldc(rt, Value::Int(84)).unwrap();
-        rt = ldc(rt, Value::Int(42)).unwrap();
-        rt = binop(rt, BinOp::Gt).unwrap();
+        ldc(&mut rt, Value::Int(84)).unwrap();
+        ldc(&mut rt, Value::Int(42)).unwrap();
+        binop(&mut rt, BinOp::Gt).unwrap();
         assert_eq!(
             rt.current_thread.operand_stack.pop().unwrap(),
             Value::Bool(true)
         );
 
-        rt = ldc(rt, Value::Int(84)).unwrap();
-        rt = ldc(rt, Value::Int(42)).unwrap();
-        rt = binop(rt, BinOp::Lt).unwrap();
+        ldc(&mut rt, Value::Int(84)).unwrap();
+        ldc(&mut rt, Value::Int(42)).unwrap();
+        binop(&mut rt, BinOp::Lt).unwrap();
         assert_eq!(
             rt.current_thread.operand_stack.pop().unwrap(),
             Value::Bool(false)
         );
 
-        rt = ldc(rt, Value::Int(84)).unwrap();
-        rt = ldc(rt, Value::Int(42)).unwrap();
-        rt = binop(rt, BinOp::Eq).unwrap();
+        ldc(&mut rt, Value::Int(84)).unwrap();
+        ldc(&mut rt, Value::Int(42)).unwrap();
+        binop(&mut rt, BinOp::Eq).unwrap();
         assert_eq!(
             rt.current_thread.operand_stack.pop().unwrap(),
             Value::Bool(false)
         );
 
-        rt = ldc(rt, Value::Float(42.0)).unwrap();
-        rt = ldc(rt, Value::Int(42)).unwrap();
-        let result = binop(rt, BinOp::Add);
+        ldc(&mut rt, Value::Float(42.0)).unwrap();
+        ldc(&mut rt, Value::Int(42)).unwrap();
+        let result = binop(&mut rt, BinOp::Add);
         assert!(result.is_err());
 
         let mut rt = Runtime::new(vec![]);
-        rt = ldc(rt, Value::Float(42.0)).unwrap();
-        rt = ldc(rt, Value::Float(42.0)).unwrap();
-        rt = binop(rt, BinOp::Add).unwrap();
+        ldc(&mut rt, Value::Float(42.0)).unwrap();
+        ldc(&mut rt, Value::Float(42.0)).unwrap();
+        binop(&mut rt, BinOp::Add).unwrap();
         assert_eq!(
             rt.current_thread.operand_stack.pop().unwrap(),
             Value::Float(84.0)
         );
 
-        rt = ldc(rt, Value::Float(42.0)).unwrap();
-        rt = ldc(rt, Value::Float(42.0)).unwrap();
-        rt = binop(rt, BinOp::Sub).unwrap();
+        ldc(&mut rt, Value::Float(42.0)).unwrap();
+        ldc(&mut rt, Value::Float(42.0)).unwrap();
+        binop(&mut rt, BinOp::Sub).unwrap();
         assert_eq!(
             rt.current_thread.operand_stack.pop().unwrap(),
             Value::Float(0.0)
         );
 
-        rt = ldc(rt, Value::Float(42.0)).unwrap();
-        rt = ldc(rt, Value::Float(42.0)).unwrap();
-        rt = binop(rt, BinOp::Mul).unwrap();
+        ldc(&mut rt, Value::Float(42.0)).unwrap();
+        ldc(&mut rt, Value::Float(42.0)).unwrap();
+        binop(&mut rt, BinOp::Mul).unwrap();
         assert_eq!(
             rt.current_thread.operand_stack.pop().unwrap(),
             Value::Float(1764.0)
         );
 
-        rt = ldc(rt, Value::Float(42.0)).unwrap();
-        rt = ldc(rt, Value::Float(42.0)).unwrap();
-        rt = binop(rt, BinOp::Div).unwrap();
+        ldc(&mut rt, Value::Float(42.0)).unwrap();
+        ldc(&mut rt, Value::Float(42.0)).unwrap();
+        binop(&mut rt, BinOp::Div).unwrap();
         assert_eq!(
             rt.current_thread.operand_stack.pop().unwrap(),
             Value::Float(1.0)
         );
 
-        rt = ldc(rt, Value::Float(42.0)).unwrap();
-        rt = ldc(rt, Value::Float(22.0)).unwrap();
-        rt = binop(rt, BinOp::Gt).unwrap();
+        ldc(&mut rt, Value::Float(42.0)).unwrap();
+        ldc(&mut rt, Value::Float(22.0)).unwrap();
+        binop(&mut rt, BinOp::Gt).unwrap();
         assert_eq!(
             rt.current_thread.operand_stack.pop().unwrap(),
             Value::Bool(true)
         );
 
-        rt = ldc(rt, Value::Float(42.0)).unwrap();
-        rt = ldc(rt, Value::Float(22.0)).unwrap();
-        rt = binop(rt, BinOp::Lt).unwrap();
+        ldc(&mut rt, Value::Float(42.0)).unwrap();
+        ldc(&mut rt, Value::Float(22.0)).unwrap();
+        binop(&mut rt, BinOp::Lt).unwrap();
         assert_eq!(
             rt.current_thread.operand_stack.pop().unwrap(),
             Value::Bool(false)
         );
 
-        rt = ldc(rt, Value::Float(42.0)).unwrap();
-        rt = ldc(rt, Value::Float(22.0)).unwrap();
-        rt = binop(rt, BinOp::Eq).unwrap();
+        ldc(&mut rt, Value::Float(42.0)).unwrap();
+        ldc(&mut rt, Value::Float(22.0)).unwrap();
+        binop(&mut rt, BinOp::Eq).unwrap();
         assert_eq!(
             rt.current_thread.operand_stack.pop().unwrap(),
             Value::Bool(false)
         );
 
-        rt = ldc(rt, Value::Bool(true)).unwrap();
-        rt = ldc(rt, Value::Bool(false)).unwrap();
-        rt = binop(rt, BinOp::And).unwrap();
+        ldc(&mut rt, Value::Bool(true)).unwrap();
+        ldc(&mut rt, Value::Bool(false)).unwrap();
+        binop(&mut rt, BinOp::And).unwrap();
         assert_eq!(
             rt.current_thread.operand_stack.pop().unwrap(),
             Value::Bool(false)
         );
 
-        rt = ldc(rt, Value::Bool(true)).unwrap();
-        rt = ldc(rt, Value::Bool(false)).unwrap();
-        rt = binop(rt, BinOp::Or).unwrap();
+        ldc(&mut rt, Value::Bool(true)).unwrap();
+        ldc(&mut rt, Value::Bool(false)).unwrap();
+        binop(&mut rt, BinOp::Or).unwrap();
         assert_eq!(
             rt.current_thread.operand_stack.pop().unwrap(),
             Value::Bool(true)
         );
 
-        rt = ldc(rt, Value::String("hello".into())).unwrap();
-        rt = ldc(rt, Value::String(" world".into())).unwrap();
-        rt = binop(rt, BinOp::Add).unwrap();
+        ldc(&mut rt, Value::String("hello".into())).unwrap();
+        ldc(&mut rt, Value::String(" world".into())).unwrap();
+        binop(&mut rt, BinOp::Add).unwrap();
         assert_eq!(
             rt.current_thread.operand_stack.pop().unwrap(),
             Value::String("hello world".into())
         );
 
-        rt = ldc(rt, Value::String("hello".into())).unwrap();
-        rt = ldc(rt, Value::String(" world".into())).unwrap();
-        rt = binop(rt, BinOp::Eq).unwrap();
+        ldc(&mut rt, Value::String("hello".into())).unwrap();
+        ldc(&mut rt, Value::String(" world".into())).unwrap();
+        binop(&mut rt, BinOp::Eq).unwrap();
         assert_eq!(
             rt.current_thread.operand_stack.pop().unwrap(),
             Value::Bool(false)
         );
 
         let sem: Value = Semaphore::new(1).into();
-        rt = ldc(rt, sem.clone()).unwrap();
-        rt = ldc(rt, sem).unwrap();
-        rt = binop(rt, BinOp::Eq).unwrap();
+        ldc(&mut rt, sem.clone()).unwrap();
+        ldc(&mut rt, sem).unwrap();
+        binop(&mut rt, BinOp::Eq).unwrap();
         assert_eq!(
             rt.current_thread.operand_stack.pop().unwrap(),
             Value::Bool(true)
         );
     }
+
+    #[test]
+    fn test_binop_cmp() {
+        let cmp = |lhs: Value, rhs: Value, op: BinOp| {
+            let mut rt = Runtime::new(vec![]);
+            ldc(&mut rt, lhs).unwrap();
+            ldc(&mut rt, rhs).unwrap();
+            binop(&mut rt, op).unwrap();
+            rt.current_thread.operand_stack.pop().unwrap()
+        };
+
+        assert_eq!(cmp(2.into(), 2.into(), BinOp::Le), Value::Bool(true));
+        assert_eq!(cmp(2.into(), 3.into(), BinOp::Ge), Value::Bool(false));
+        assert_eq!(cmp(2.5.into(), 2.5.into(), BinOp::Ge), Value::Bool(true));
+
+        // strings by code point: prefixes first, upper case before lower case
+        for (lhs, rhs) in [("ab", "b"), ("a", "ab"), ("Z", "a"), ("", "a"), ("z", "é")] {
+            assert_eq!(cmp(lhs.into(), rhs.into(), BinOp::Lt), Value::Bool(true));
+            assert_eq!(cmp(lhs.into(), rhs.into(), BinOp::Le), Value::Bool(true));
+            assert_eq!(cmp(lhs.into(), rhs.into(), BinOp::Gt), Value::Bool(false));
+            assert_eq!(cmp(rhs.into(), lhs.into(), BinOp::Ge), Value::Bool(true));
+        }
+        assert_eq!(cmp("a".into(), "a".into(), BinOp::Ge), Value::Bool(true));
+        assert_eq!(cmp("a".into(), "a".into(), BinOp::Lt), Value::Bool(false));
+    }
+
+    #[test]
+    fn test_binop_int_div_by_zero() {
+        for op in [BinOp::Div, BinOp::Mod] {
+            let mut rt = Runtime::new(vec![]);
+            ldc(&mut rt, Value::Int(1)).unwrap();
+            ldc(&mut rt, Value::Int(0)).unwrap();
+            let res = binop(&mut rt, op);
+            assert_eq!(res.err().unwrap().to_string(), "Division by zero");
+        }
+    }
+
+    #[test]
+    fn test_binop_int_overflow() {
+        let cases = [
+            (i64::MAX, BinOp::Add, 1, "9223372036854775807 + 1"),
+            (i64::MIN, BinOp::Sub, 1, "-9223372036854775808 - 1"),
+            (i64::MAX, BinOp::Mul, 2, "9223372036854775807 * 2"),
+            (i64::MIN, BinOp::Div, -1, "-9223372036854775808 / -1"),
+            (i64::MIN, BinOp::Mod, -1, "-9223372036854775808 % -1"),
+        ];
+        for (lhs, op, rhs, exp) in cases {
+            let mut rt = Runtime::new(vec![]);
+            ldc(&mut rt, Value::Int(lhs)).unwrap();
+            ldc(&mut rt, Value::Int(rhs)).unwrap();
+            let res = binop(&mut rt, op);
+            assert_eq!(
+                res.err().unwrap().to_string(),
+                format!("Integer overflow: {}", exp)
+            );
+        }
+    }
+
+    #[test]
+    fn test_binop_nan() {
+        let nan_op = |lhs: f64, rhs: f64, op: BinOp| {
+            let mut rt = Runtime::new(vec![]);
+            ldc(&mut rt, Value::Float(lhs)).unwrap();
+            ldc(&mut rt, Value::Float(rhs)).unwrap();
+            binop(&mut rt, op).unwrap();
+            rt.current_thread.operand_stack.pop().unwrap()
+        };
+
+        // comparisons with NaN are false
+        for op in [BinOp::Eq, BinOp::Lt, BinOp::Gt, BinOp::Le, BinOp::Ge] {
+            assert_eq!(nan_op(f64::NAN, f64::NAN, op.clone()), Value::Bool(false));
+            assert_eq!(nan_op(f64::NAN, 1.0, op.clone()), Value::Bool(false));
+            assert_eq!(nan_op(1.0, f64::NAN, op), Value::Bool(false));
+        }
+
+        // arithmetic propagates NaN
+        for op in [BinOp::Add, BinOp::Sub, BinOp::Mul, BinOp::Div] {
+            let Value::Float(res) = nan_op(f64::NAN, 1.0, op) else {
+                panic!("Expected float");
+            };
+            assert!(res.is_nan());
+        }
+
+        // float division by zero does not error
+        assert_eq!(nan_op(1.0, 0.0, BinOp::Div), Value::Float(f64::INFINITY));
+        assert_eq!(
+            nan_op(-1.0, 0.0, BinOp::Div),
+            Value::Float(f64::NEG_INFINITY)
+        );
+        let Value::Float(res) = nan_op(0.0, 0.0, BinOp::Div) else {
+            panic!("Expected float");
+        };
+        assert!(res.is_nan());
+
+        assert_eq!(nan_op(f64::INFINITY, 1.0, BinOp::Gt), Value::Bool(true));
+    }
+
+    #[test]
+    fn test_binop_int_float() {
+        let mixed_op = |lhs: Value, rhs: Value, op: BinOp| {
+            let mut rt = Runtime::new(vec![]);
+            ldc(&mut rt, lhs).unwrap();
+            ldc(&mut rt, rhs).unwrap();
+            binop(&mut rt, op).expect_err("Should err").to_string()
+        };
+
+        assert_eq!(
+            mixed_op(Value::Int(2), Value::Float(2.5), BinOp::Eq),
+            "Can't compare 2 == 2.5: ints and floats are never converted implicitly. Convert one side with 'as float' or 'as int' to compare them"
+        );
+        assert!(mixed_op(Value::Float(2.5), Value::Int(2), BinOp::Lt)
+            .starts_with("Can't compare 2.5 < 2:"));
+
+        // arithmetic on mixed types is a plain type mismatch
+        assert_eq!(
+            mixed_op(Value::Int(2), Value::Float(2.5), BinOp::Add),
+            "Type mismatch: expected Int, found Float"
+        );
+    }
+
+    #[test]
+    fn test_binop_closure() {
+        // the type checker rejects f == g, this is for programs that weren't type checked
+        let mut rt = Runtime::new(vec![]);
+        ldc(&mut rt, bytecode::builtin::println()).unwrap();
+        ldc(&mut rt, bytecode::builtin::println()).unwrap();
+        let err = binop(&mut rt, BinOp::Eq).expect_err("Should err");
+        assert_eq!(err.to_string(), "Unsupported operation == on type Closure");
+    }
 }
@@ -1,5 +1,5 @@
 use anyhow::Result;
-use bytecode::{type_of, FnType, FrameType, StackFrame, Value};
+use bytecode::{type_of, FnType, FrameType, StackFrame, Value, W};
 
 use crate::{extend_environment, Runtime, VmError};
 
@@ -7,13 +7,13 @@ use super::apply_builtin;
 
 /// Call a function with the given number of arguments.
 /// First it pops n values from the operand stack where n is the arity of the function.
-/// The values will be the arguments to the function and they are pushed to a vector and reversed.
+/// The values will be the arguments to the function, in the order they were pushed.
 /// i.e. the last argument is the top value of the operand stack.
 /// Then it pops the closure from the operand stack.
 /// It checks that the closure is a closure and that the arity of the closure matches the number of arguments.
 /// If the closure is a builtin function it applies the builtin function and returns.
-/// Otherwise it creates a new stack frame with the environment of the closure and the address of the closure.
-/// It extends the environment with the parameters and arguments.
+/// Otherwise it creates a new stack frame saving the caller's environment and the return address.
+/// It extends the closure's environment with the parameters and arguments.
 /// It sets the program counter to the address of the closure. Essentially calling the function.
 ///
 /// # Arguments
@@ -26,21 +26,17 @@ use super::apply_builtin;
 ///
 /// If the operand stack does not contain enough values to pop (arity + 1).
 /// If the closure is not of type closure or the arity of the closure does not match the number of arguments.
+/// VmError::StackOverflow if the runtime stack is already as deep as the runtime allows.
 #[inline]
-pub fn call(mut rt: Runtime, arity: usize) -> Result<Runtime> {
-    let mut args = Vec::new();
-    args.reserve_exact(arity);
-
-    for _ in 0..arity {
-        args.push(
-            rt.current_thread
-                .operand_stack
-                .pop()
-                .ok_or(VmError::OperandStackUnderflow)?,
-        );
-    }
-
-    args.reverse();
+pub fn call(rt: &mut Runtime, arity: usize) -> Result<()> {
+    // The arguments are the top arity values, in order, so they come off the stack in one go
+    let split = rt
+        .current_thread
+        .operand_stack
+        .len()
+        .checked_sub(arity)
+        .ok_or(VmError::OperandStackUnderflow)?;
+    let args = rt.current_thread.operand_stack.split_off(split);
 
     let value = rt
         .current_thread
@@ -63,7 +59,9 @@ pub fn call(mut rt: Runtime, arity: usize) -> Result<Runtime> {
         .into());
     };
 
-    if prms.len() != arity {
+    // a builtin checks it got enough arguments itself, since some of its parameters can be left out
+    let too_few = arity < prms.len() && !matches!(fn_type, FnType::Builtin);
+    if arity > prms.len() || too_few {
         return Err(VmError::ArityParamsMismatch {
             arity,
             params: prms.len(),
@@ -75,28 +73,40 @@ pub fn call(mut rt: Runtime, arity: usize) -> Result<Runtime> {
         return apply_builtin(rt, sym.as_str(), args);
     }
 
+    if let Some(max_depth) = rt.max_stack_depth {
+        if rt.current_thread.runtime_stack.len() >= max_depth {
+            return Err(VmError::StackOverflow {
+                thread_id: rt.current_thread.thread_id,
+                max_depth,
+            }
+            .into());
+        }
+    }
+
+    // The frame saves the caller's environment, restored when the function returns
     let frame = StackFrame {
         frame_type: FrameType::CallFrame,
-        env: env.clone(),
+        env: W(rt.current_thread.env.clone()),
         address: Some(rt.current_thread.pc),
+        scope: None,
     };
 
     rt.current_thread.runtime_stack.push(frame);
-    rt = extend_environment(rt, env.0, prms, args)?;
+    extend_environment(rt, env.0, prms, args)?;
     rt.current_thread.pc = addr;
 
-    Ok(rt)
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use bytecode::{ByteCode, FnType};
+    use bytecode::{builtin, ByteCode, FnType};
 
     #[test]
     fn test_call() -> Result<()> {
-        let rt = Runtime::new(vec![ByteCode::CALL(0), ByteCode::DONE]);
-        let result = call(rt, 0);
+        let mut rt = Runtime::new(vec![ByteCode::CALL(0), ByteCode::DONE]);
+        let result = call(&mut rt, 0);
         assert!(result.is_err());
 
         let mut rt = Runtime::new(vec![ByteCode::CALL(0), ByteCode::DONE]);
@@ -108,9 +118,36 @@ mod tests {
             env: Default::default(),
         });
 
-        let rt = call(rt, 0)?;
+        call(&mut rt, 0)?;
         assert_eq!(rt.current_thread.pc, 123);
 
+        // a builtin checks its own arguments, some of which can be left out
+        let mut rt = Runtime::new(vec![ByteCode::CALL(0), ByteCode::DONE]);
+        rt.current_thread.operand_stack.push(builtin::println());
+        let err = call(&mut rt, 0).expect_err("println needs an argument");
+        assert_eq!(
+            err.to_string(),
+            VmError::InsufficientArguments {
+                expected: 1,
+                got: 0
+            }
+            .to_string()
+        );
+
+        let mut rt = Runtime::new(vec![ByteCode::CALL(0), ByteCode::DONE]);
+        rt.current_thread.operand_stack.push(builtin::println());
+        rt.current_thread.operand_stack.push(Value::Int(1));
+        rt.current_thread.operand_stack.push(Value::Int(2));
+        let err = call(&mut rt, 2).expect_err("println takes one argument");
+        assert_eq!(
+            err.to_string(),
+            VmError::ArityParamsMismatch {
+                arity: 2,
+                params: 1
+            }
+            .to_string()
+        );
+
         Ok(())
     }
 }
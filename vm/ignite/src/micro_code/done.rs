@@ -1,8 +1,12 @@
 use anyhow::{Ok, Result};
+use log::debug;
 
-use crate::{Runtime, VmError, MAIN_THREAD_ID};
+use crate::{micro_code::yield_, ExitPolicy, Runtime, VmError, MAIN_THREAD_ID};
 
 /// Set the state of the runtime to done if the current thread is the main thread.
+/// With ExitPolicy::Wait the main thread first yields until every thread that was not detached has
+/// finished, re-executing DONE each time it is scheduled. Threads left blocked with nothing ready to
+/// wake them are not waited for.
 /// Otherwise, set the current thread to zombie and yield to the next ready thread.
 ///
 /// # Arguments
@@ -13,26 +17,40 @@ use crate::{Runtime, VmError, MAIN_THREAD_ID};
 ///
 /// * If the current thread is not the main thread and there are no threads in the ready queue.
 #[inline]
-pub fn done(mut rt: Runtime) -> Result<Runtime> {
+pub fn done(rt: &mut Runtime) -> Result<()> {
     // If the current thread is the main thread, then we are done
     if rt.current_thread.thread_id == MAIN_THREAD_ID {
+        if rt.exit_policy == ExitPolicy::Wait && waiting_for_threads(rt) {
+            rt.current_thread.pc -= 1;
+            return yield_(rt);
+        }
+
         rt.done = true;
-        Ok(rt)
+        Ok(())
     // Otherwise we will set the current thread to zombie and yield
     } else {
-        let current_thread = rt.current_thread;
-        let current_thread_id = current_thread.thread_id;
-        rt.zombie_threads.insert(current_thread_id, current_thread);
-
         let next_ready_thread = rt
             .ready_queue
             .pop_front()
             .ok_or(VmError::NoThreadsInReadyQueue)?;
-        rt.current_thread = next_ready_thread;
-        Ok(rt)
+        let mut current_thread = rt.switch_to(next_ready_thread);
+        debug!("Thread {} finished", current_thread.thread_id);
+        rt.record_finished(&current_thread);
+        rt.orphan_held_sems(&mut current_thread);
+        rt.add_zombie(current_thread);
+        Ok(())
     }
 }
 
+/// Whether a thread that was not detached is still running and can make progress
+fn waiting_for_threads(rt: &Runtime) -> bool {
+    let attached = |tid| !rt.detached_threads.contains(&tid);
+
+    !rt.ready_queue.is_empty()
+        && (rt.ready_queue.iter().any(|t| attached(t.thread_id))
+            || rt.blocked_queue.iter().any(|(t, _)| attached(t.thread_id)))
+}
+
 #[cfg(test)]
 mod tests {
     use crate::micro_code::{spawn, yield_};
@@ -42,7 +60,7 @@ mod tests {
     #[test]
     fn test_done_01() -> Result<()> {
         let mut rt = Runtime::new(vec![]);
-        rt = done(rt)?;
+        done(&mut rt)?;
 
         // The main thread should be done
         assert!(rt.done);
@@ -53,9 +71,9 @@ mod tests {
     #[test]
     fn test_done_02() -> Result<()> {
         let mut rt = Runtime::new(vec![]);
-        rt = spawn(rt, 0)?;
-        rt = yield_(rt)?; // Yield the control to the child thread
-        rt = done(rt)?;
+        spawn(&mut rt, 0, 0)?;
+        yield_(&mut rt)?; // Yield the control to the child thread
+        done(&mut rt)?;
 
         // The main thread should not be done
         assert!(!rt.done);
@@ -67,4 +85,33 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_done_wait_policy() -> Result<()> {
+        let mut rt = Runtime::new(vec![]);
+        rt.set_exit_policy(ExitPolicy::Wait);
+        spawn(&mut rt, 0, 0)?;
+        rt.current_thread.pc = 1; // as if DONE at 0 was fetched
+
+        // main waits for the child
+        done(&mut rt)?;
+        assert!(!rt.done);
+        assert_eq!(rt.current_thread.thread_id, MAIN_THREAD_ID + 1);
+        assert_eq!(rt.ready_queue.back().unwrap().pc, 0);
+
+        // child finishes, main is done next time
+        done(&mut rt)?;
+        done(&mut rt)?;
+        assert!(rt.done);
+
+        // detached threads are not waited for
+        let mut rt = Runtime::new(vec![]);
+        rt.set_exit_policy(ExitPolicy::Wait);
+        spawn(&mut rt, 0, 0)?;
+        rt.detach(MAIN_THREAD_ID + 1)?;
+        done(&mut rt)?;
+        assert!(rt.done);
+
+        Ok(())
+    }
 }
@@ -1,10 +1,11 @@
 use anyhow::Result;
-use bytecode::{FrameType, StackFrame, Symbol, Value, W};
+use bytecode::{weak_clone, Environment, FrameType, StackFrame, Symbol, Value, W};
 
 use crate::{extend_environment, Runtime};
 
 /// Create a new scope in the current environment. The new environment will be a child of the current
 /// environment. All symbols in the new scope will be initialized to `Value::Unitialized`.
+/// The compiler lists each symbol once, but a repeated symbol still only gets one binding.
 ///
 /// # Arguments
 ///
@@ -16,7 +17,7 @@ use crate::{extend_environment, Runtime};
 ///
 /// Infallible.
 #[inline]
-pub fn enter_scope(mut rt: Runtime, syms: Vec<Symbol>) -> Result<Runtime> {
+pub fn enter_scope(rt: &mut Runtime, syms: Vec<Symbol>) -> Result<()> {
     let current_env = rt.current_thread.env.clone();
 
     // Preserve the current environment in a stack frame
@@ -31,9 +32,47 @@ pub fn enter_scope(mut rt: Runtime, syms: Vec<Symbol>) -> Result<Runtime> {
         .collect::<Vec<Value>>();
 
     let current_env = rt.current_thread.env.clone();
-    rt = extend_environment(rt, current_env, syms, uninitialized)?;
+    extend_environment(rt, current_env, syms, uninitialized)?;
 
-    Ok(rt)
+    Ok(())
+}
+
+/// Create a new scope like enter_scope, for a block the compiler found no closure or thread can
+/// capture. The scope's environment is owned by its stack frame rather than registered with the
+/// garbage collector, and is taken from the thread's pool of exited scopes when there is one.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to create a new scope in.
+///
+/// * `syms` - The symbols to add to the new scope.
+///
+/// # Errors
+///
+/// Infallible.
+#[inline]
+pub fn enter_scope_local(rt: &mut Runtime, syms: &[Symbol]) -> Result<()> {
+    let current_env = rt.current_thread.env.clone();
+
+    let scope = rt
+        .current_thread
+        .scope_pool
+        .pop()
+        .unwrap_or_else(Environment::new_wrapped);
+    {
+        let mut env = scope.borrow_mut();
+        env.set_parent(current_env.clone());
+        for sym in syms {
+            env.set(sym.clone(), Value::Unitialized);
+        }
+    }
+
+    rt.current_thread.env = weak_clone(&scope);
+    rt.current_thread
+        .runtime_stack
+        .push(StackFrame::new_local(W(current_env), scope));
+
+    Ok(())
 }
 
 #[cfg(test)]
@@ -59,7 +98,7 @@ mod tests {
             .borrow_mut()
             .set("b", 123);
 
-        rt = enter_scope(rt, vec!["c".to_string(), "d".to_string()]).unwrap();
+        enter_scope(&mut rt, vec!["c".to_string(), "d".to_string()]).unwrap();
 
         assert_eq!(rt.current_thread.runtime_stack.len(), 1);
         assert!(rt
@@ -109,4 +148,17 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_enter_scope_repeated_symbol() -> Result<()> {
+        let mut rt = Runtime::new(vec![]);
+
+        enter_scope(&mut rt, vec!["x".to_string(), "x".to_string()])?;
+
+        let env = rt.current_thread.env.upgrade().unwrap();
+        assert_eq!(env.borrow().env.len(), 1);
+        assert_eq!(env.borrow().get(&"x".to_string())?, Value::Unitialized);
+
+        Ok(())
+    }
 }
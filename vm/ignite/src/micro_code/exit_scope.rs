@@ -1,4 +1,5 @@
 use anyhow::Result;
+use bytecode::W;
 
 use crate::{Runtime, VmError};
 
@@ -12,7 +13,7 @@ use crate::{Runtime, VmError};
 ///
 /// If the runtime stack is empty.
 #[inline]
-pub fn exit_scope(mut rt: Runtime) -> Result<Runtime> {
+pub fn exit_scope(rt: &mut Runtime) -> Result<()> {
     let prev_frame = rt
         .current_thread
         .runtime_stack
@@ -20,11 +21,46 @@ pub fn exit_scope(mut rt: Runtime) -> Result<Runtime> {
         .ok_or(VmError::RuntimeStackUnderflow)?;
 
     rt.current_thread.env = prev_frame.env.0;
-    Ok(rt)
+    Ok(())
+}
+
+/// Exit the current scope like exit_scope, for scopes no closure or other thread can refer to.
+/// A scope entered with ENTERSCOPELOCAL is owned by its frame, and its environment goes back to the
+/// thread's pool for reuse. Otherwise the scope's environment is unregistered, so it is freed now
+/// rather than at the next garbage collection.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to exit the current scope in.
+///
+/// # Errors
+///
+/// If the runtime stack is empty.
+#[inline]
+pub fn exit_scope_local(rt: &mut Runtime) -> Result<()> {
+    let prev_frame = rt
+        .current_thread
+        .runtime_stack
+        .pop()
+        .ok_or(VmError::RuntimeStackUnderflow)?;
+
+    let scope_env = std::mem::replace(&mut rt.current_thread.env, prev_frame.env.0);
+
+    if let Some(scope) = prev_frame.scope {
+        // the thread no longer refers to the scope, so it can be reused
+        drop(scope_env);
+        rt.current_thread.recycle_scope(scope);
+    } else if let Some(env) = scope_env.upgrade() {
+        rt.env_registry.remove(&W(env));
+    }
+
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
+    use std::rc::Weak;
+
     use bytecode::{weak_clone, Environment, FrameType, StackFrame, Value, W};
 
     use super::*;
@@ -55,7 +91,7 @@ mod tests {
             Value::Int(123)
         );
 
-        rt = exit_scope(rt).unwrap();
+        exit_scope(&mut rt).unwrap();
 
         assert_eq!(rt.current_thread.runtime_stack.len(), 0);
         assert_eq!(
@@ -70,4 +106,65 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_exit_scope_local() -> Result<()> {
+        let mut rt = Runtime::new(vec![]);
+        let registered = rt.env_registry.len();
+
+        crate::micro_code::enter_scope(&mut rt, vec!["x".to_string()])?;
+        let scope_env = rt.current_thread.env.clone();
+        assert_eq!(rt.env_registry.len(), registered + 1);
+
+        exit_scope_local(&mut rt)?;
+
+        // freed without garbage collection
+        assert_eq!(rt.env_registry.len(), registered);
+        assert!(scope_env.upgrade().is_none());
+        assert!(rt.current_thread.env.upgrade().is_some());
+        assert_eq!(rt.current_thread.runtime_stack.len(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_exit_scope_local_reuses_scope() -> Result<()> {
+        let mut rt = Runtime::new(vec![]);
+        let registered = rt.env_registry.len();
+        let outer_env = rt.current_thread.env.clone();
+
+        crate::micro_code::enter_scope_local(&mut rt, &["x".to_string()])?;
+        // owned by the frame, not the registry
+        assert_eq!(rt.env_registry.len(), registered);
+        assert!(rt.current_thread.runtime_stack[0].scope.is_some());
+        let scope_ptr = rt.current_thread.env.as_ptr();
+        rt.current_thread
+            .env
+            .upgrade()
+            .unwrap()
+            .borrow_mut()
+            .update("x", 1)?;
+
+        exit_scope_local(&mut rt)?;
+        assert!(Weak::ptr_eq(&rt.current_thread.env, &outer_env));
+        assert_eq!(rt.current_thread.runtime_stack.len(), 0);
+        assert_eq!(rt.current_thread.scope_pool.len(), 1);
+
+        // the next local scope gets the same environment, emptied
+        crate::micro_code::enter_scope_local(&mut rt, &["y".to_string()])?;
+        assert_eq!(rt.current_thread.env.as_ptr(), scope_ptr);
+        let scope = rt.current_thread.env.upgrade().unwrap();
+        assert!(scope.borrow().get(&"x".to_string()).is_err());
+        assert_eq!(scope.borrow().get(&"y".to_string())?, Value::Unitialized);
+        assert!(rt.current_thread.scope_pool.is_empty());
+
+        // a scope something still refers to is dropped with its frame rather than reused
+        let held = rt.current_thread.env.clone();
+        drop(scope);
+        exit_scope_local(&mut rt)?;
+        assert!(rt.current_thread.scope_pool.is_empty());
+        assert!(held.upgrade().is_none());
+
+        Ok(())
+    }
 }
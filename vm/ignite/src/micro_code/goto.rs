@@ -14,9 +14,9 @@ use anyhow::Result;
 ///
 /// Infallible.
 #[inline]
-pub fn goto(mut rt: Runtime, pc: usize) -> Result<Runtime> {
+pub fn goto(rt: &mut Runtime, pc: usize) -> Result<()> {
     rt.current_thread.pc = pc;
-    Ok(rt)
+    Ok(())
 }
 
 #[cfg(test)]
@@ -26,7 +26,7 @@ mod tests {
     #[test]
     fn test_goto() {
         let mut rt = Runtime::new(vec![]);
-        rt = goto(rt, 123).unwrap();
+        goto(&mut rt, 123).unwrap();
         assert_eq!(rt.current_thread.pc, 123);
     }
 }
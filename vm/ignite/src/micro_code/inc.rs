@@ -0,0 +1,59 @@
+use anyhow::Result;
+use bytecode::{BinOp, Symbol, Value};
+
+use crate::{micro_code, Runtime};
+
+/// Fused form of LD(sym); LDC(val); BINOP(Add); ASSIGN(sym), i.e `sym = sym + val`.
+/// Behaves exactly like the unfused sequence but is dispatched as one instruction.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to execute the instruction on.
+///
+/// * `sym` - The symbol to increment.
+///
+/// * `val` - The value to add to the symbol.
+///
+/// # Errors
+///
+/// If the symbol is not found or the addition is not supported for the types involved.
+#[inline]
+pub fn inc(rt: &mut Runtime, sym: &Symbol, val: Value) -> Result<()> {
+    micro_code::ld_ldc_binop(rt, sym, val, BinOp::Add)?;
+    micro_code::assign(rt, sym.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inc() -> Result<()> {
+        let mut rt = Runtime::new(vec![]);
+        rt.current_thread
+            .env
+            .upgrade()
+            .unwrap()
+            .borrow_mut()
+            .set("x", 41);
+
+        inc(&mut rt, &"x".to_string(), Value::Int(1))?;
+
+        assert!(rt.current_thread.operand_stack.is_empty());
+        assert_eq!(
+            rt.current_thread
+                .env
+                .upgrade()
+                .unwrap()
+                .borrow()
+                .get(&"x".to_string())?,
+            Value::Int(42)
+        );
+
+        // same errors as the unfused form
+        let mut rt = Runtime::new(vec![]);
+        assert!(inc(&mut rt, &"y".to_string(), Value::Int(1)).is_err());
+
+        Ok(())
+    }
+}
@@ -14,7 +14,7 @@ use crate::{Runtime, VmError};
 ///
 /// If the stack is empty or the top of the stack is not a boolean.
 #[inline]
-pub fn jof(mut rt: Runtime, pc: usize) -> Result<Runtime> {
+pub fn jof(rt: &mut Runtime, pc: usize) -> Result<()> {
     let cond = rt
         .current_thread
         .operand_stack
@@ -26,7 +26,7 @@ pub fn jof(mut rt: Runtime, pc: usize) -> Result<Runtime> {
         rt.current_thread.pc = pc;
     }
 
-    Ok(rt)
+    Ok(())
 }
 
 #[cfg(test)]
@@ -39,17 +39,17 @@ mod tests {
     #[test]
     fn test_jof() {
         let mut rt = Runtime::new(vec![]);
-        rt = ldc(rt, Value::Bool(false)).unwrap();
-        rt = jof(rt, 123).unwrap();
+        ldc(&mut rt, Value::Bool(false)).unwrap();
+        jof(&mut rt, 123).unwrap();
         assert_eq!(rt.current_thread.pc, 123);
 
         let mut rt = Runtime::new(vec![]);
-        rt = ldc(rt, Value::Bool(true)).unwrap();
-        rt = jof(rt, 42).unwrap();
+        ldc(&mut rt, Value::Bool(true)).unwrap();
+        jof(&mut rt, 42).unwrap();
         assert_eq!(rt.current_thread.pc, 0);
 
-        rt = ldc(rt, Value::Unit).unwrap();
-        let result = jof(rt, 42);
+        ldc(&mut rt, Value::Unit).unwrap();
+        let result = jof(&mut rt, 42);
         assert!(result.is_err());
     }
 }
@@ -1,14 +1,20 @@
+use std::time::{Duration, Instant};
+
 use anyhow::{Ok, Result};
+use bytecode::{builtin, type_of, Value};
+use log::debug;
 
-use crate::{Runtime, VmError};
+use crate::{Runtime, VmError, MAIN_THREAD_ID};
 
-use super::yield_;
+use super::{apply_builtin::retry_call, yield_};
 
 /// Pop the operand stack for the thread ID to join.
 /// If the thread to join is in zombie state, then the current thread will be set to ready and the result
-/// of the zombie thread will be pushed onto the current thread's operand stack. The zombie thread is deallocated.
-/// If the thread to join is not found, then panic.
-/// Otherwise, the current thread will yield.
+/// of the zombie thread will be pushed onto the current thread's operand stack. The zombie thread is recycled.
+/// If the thread was reaped before being joined (see Runtime::max_zombies) or detached, an error is returned.
+/// If the thread failed (see ThreadErrorPolicy::Isolate), its error is returned.
+/// If the thread is still running, the current thread will yield. Otherwise, the thread was
+/// already joined or never existed, and an error is returned instead of waiting forever.
 ///
 /// # Arguments
 ///
@@ -18,25 +24,40 @@ use super::yield_;
 ///
 /// # Errors
 ///
-/// * If the thread with the given ID is not found in the thread state hashmap.
 /// * If the operand stack is empty.
-/// * If the value on the operand stack is not an integer.
+/// * If the value on the operand stack is not a thread ID, e.g. a plain int.
+/// * If the thread failed with an error.
+/// * If the thread was reaped before being joined.
+/// * If the thread was detached.
+/// * If the thread is the current thread.
+/// * If the thread was already joined, or there is no such thread.
+/// * If the thread is blocked on a semaphore and no other thread can run.
 #[inline]
-pub fn join(mut rt: Runtime) -> Result<Runtime> {
-    let tid: i64 = rt
-        .current_thread
-        .operand_stack
-        .pop()
-        .ok_or(VmError::OperandStackUnderflow)?
-        .clone()
-        .try_into()?;
+pub fn join(rt: &mut Runtime) -> Result<()> {
+    let tid = thread_id(
+        &rt.current_thread
+            .operand_stack
+            .pop()
+            .ok_or(VmError::OperandStackUnderflow)?,
+    )?;
+
+    check_joinable(rt, tid)?;
 
     let Some(mut zombie_thread) = rt.zombie_threads.remove(&tid) else {
+        if !rt.is_alive(tid) {
+            return Err(not_found(rt, tid).into());
+        }
+
+        // With no thread ready, the thread to join is blocked and nothing can wake it
+        if rt.ready_queue.is_empty() {
+            return Err(join_deadlock(rt, tid).into());
+        }
+
         // If the thread to join is not found, we need to yield control and try again
         rt.current_thread.pc -= 1; // Decrement the program counter to re-execute the join instruction
-        rt.current_thread.operand_stack.push(tid.into()); // Add the pid back to the operand stack
-        let rt = yield_(rt)?;
-        return Ok(rt);
+        rt.current_thread.operand_stack.push(Value::ThreadId(tid)); // Add the pid back to the operand stack
+        yield_(rt)?;
+        return Ok(());
     };
 
     let result = zombie_thread
@@ -44,20 +65,143 @@ pub fn join(mut rt: Runtime) -> Result<Runtime> {
         .pop()
         .ok_or(VmError::OperandStackUnderflow)?;
 
-    // Deallocate the zombie thread
-    drop(zombie_thread);
+    debug!(
+        "Thread {} joined thread {}",
+        rt.current_thread.thread_id, tid
+    );
+    // Reuse the zombie thread's allocations for future spawns
+    rt.recycle_thread(zombie_thread);
 
     rt.current_thread.operand_stack.push(result);
-    Ok(rt)
+    Ok(())
+}
+
+/// Join a thread like join, but give up after ms milliseconds, for the join_timeout builtin.
+/// Pushes the result of the thread if it finished in time, otherwise ().
+/// While waiting the call is retried each time the current thread is scheduled. The deadline is kept
+/// in the current thread between retries.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to join in.
+///
+/// * `tid` - The ID of the thread to join.
+///
+/// * `ms` - The maximum time to wait in milliseconds.
+///
+/// # Errors
+///
+/// * If the thread failed with an error.
+/// * If the thread was reaped before being joined, or detached.
+/// * If the thread is the current thread.
+/// * If there is no such thread, or it was already joined.
+/// * If ms is negative.
+pub fn join_timeout(rt: &mut Runtime, tid: i64, ms: i64) -> Result<()> {
+    if let Err(err) = check_joinable(rt, tid) {
+        rt.current_thread.join_deadline = None;
+        return Err(err);
+    }
+
+    if let Some(mut zombie_thread) = rt.zombie_threads.remove(&tid) {
+        rt.current_thread.join_deadline = None;
+        let result = zombie_thread
+            .operand_stack
+            .pop()
+            .ok_or(VmError::OperandStackUnderflow)?;
+        rt.recycle_thread(zombie_thread);
+        rt.current_thread.operand_stack.push(result);
+        return Ok(());
+    }
+
+    if !rt.is_alive(tid) {
+        return Err(not_found(rt, tid).into());
+    }
+
+    let timeout = u64::try_from(ms).map_err(|_| {
+        VmError::IllegalArgument(format!("join timeout must not be negative, got {}", ms))
+    })?;
+
+    let deadline = *rt
+        .current_thread
+        .join_deadline
+        .get_or_insert_with(|| Instant::now() + Duration::from_millis(timeout));
+
+    if rt.join_timed_out(deadline)? {
+        rt.current_thread.join_deadline = None;
+        rt.current_thread.operand_stack.push(Value::Unit);
+        return Ok(());
+    }
+
+    retry_call(
+        rt,
+        builtin::join_timeout(),
+        vec![Value::ThreadId(tid), Value::Int(ms)],
+    )
+}
+
+/// The thread ID in value. Plain ints are rejected, so a join can only wait for a thread that
+/// was really spawned.
+pub fn thread_id(value: &Value) -> Result<i64> {
+    match value {
+        Value::ThreadId(tid) => Ok(*tid),
+        _ => Err(VmError::NotAThreadId(format!("{} {}", type_of(value), value)).into()),
+    }
+}
+
+/// The errors of joining tid that don't depend on whether it is still running: it failed, was
+/// reaped or detached, or it is the current thread, which would wait for itself forever.
+/// The error of a failed thread is handed to the joining thread, so it is only returned once.
+fn check_joinable(rt: &mut Runtime, tid: i64) -> Result<()> {
+    if let Some(err) = rt.failed_threads.remove(&tid) {
+        return Err(VmError::ThreadFailed(tid, err).into());
+    }
+
+    if rt.reaped_threads.remove(&tid) {
+        return Err(VmError::ThreadReaped(tid).into());
+    }
+
+    if rt.detached_threads.contains(&tid) {
+        return Err(VmError::ThreadDetached(tid).into());
+    }
+
+    if rt.current_thread.thread_id == tid {
+        return Err(VmError::SelfJoin(tid).into());
+    }
+
+    Ok(())
+}
+
+/// The error for joining tid, which is neither running nor finished and waiting to be joined.
+/// Thread IDs are handed out in order, so a spawned thread in that state was already joined.
+fn not_found(rt: &Runtime, tid: i64) -> VmError {
+    if tid > MAIN_THREAD_ID && tid <= rt.thread_count {
+        VmError::ThreadAlreadyJoined(tid)
+    } else {
+        VmError::ThreadNotFound(tid)
+    }
+}
+
+/// The error for joining tid when it is blocked on a semaphore and no other thread can run to post it.
+pub(super) fn join_deadlock(rt: &Runtime, tid: i64) -> VmError {
+    let owner = rt
+        .blocked_queue
+        .iter()
+        .find(|(thread, _)| thread.thread_id == tid)
+        .and_then(|(_, sem)| rt.orphaned_by(sem));
+
+    match owner {
+        Some(owner) => VmError::OrphanedSemaphore { waiter: tid, owner },
+        None => VmError::JoinDeadlock(rt.current_thread.thread_id, tid),
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use bytecode::Value;
+    use bytecode::{Semaphore, Value};
 
     use crate::{
-        micro_code::{done, spawn},
-        MAIN_THREAD_ID,
+        micro_code::{done, spawn, wait},
+        ThreadErrorPolicy, MAIN_THREAD_ID,
     };
 
     use super::*;
@@ -66,13 +210,13 @@ mod tests {
     fn test_join_01() -> Result<()> {
         let mut rt = Runtime::default();
         rt.current_thread.pc = 1; // prevent u64 subtraction overflow
-        rt = spawn(rt, 0)?;
-        rt = join(rt)?;
+        spawn(&mut rt, 0, 0)?;
+        join(&mut rt)?;
         // Add this point, both threads are in the ready state, so join should yield the current thread
         assert_eq!(rt.current_thread.thread_id, MAIN_THREAD_ID + 1);
 
         // Add the parent thread ID to the operand stack of the child
-        rt = yield_(rt)?;
+        yield_(&mut rt)?;
         assert_eq!(rt.current_thread.thread_id, MAIN_THREAD_ID);
 
         // PID should remain on the operand stack
@@ -85,12 +229,12 @@ mod tests {
     fn test_join_02() -> Result<()> {
         let mut rt = Runtime::default();
         rt.current_thread.pc = 1; // prevent u64 subtraction overflow
-        rt = spawn(rt, 0)?;
-        rt = yield_(rt)?; // Yield the parent thread to make the child thread the current thread
-        rt = done(rt)?; // Set the current thread to zombie state
-        rt = yield_(rt)?; // Yield the child thread to make the parent thread the current thread
+        spawn(&mut rt, 0, 0)?;
+        yield_(&mut rt)?; // Yield the parent thread to make the child thread the current thread
+        done(&mut rt)?; // Set the current thread to zombie state
+        yield_(&mut rt)?; // Yield the child thread to make the parent thread the current thread
 
-        rt = join(rt)?;
+        join(&mut rt)?;
         // Add this point, the thread to join is in zombie state, so the current thread should just continue
         assert_eq!(rt.current_thread.thread_id, MAIN_THREAD_ID);
         // Zombie thread should be deallocated
@@ -106,4 +250,108 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_join_reaped() -> Result<()> {
+        let mut rt = Runtime::default();
+        rt.set_max_zombies(0);
+        rt.current_thread.pc = 1; // prevent u64 subtraction overflow
+        spawn(&mut rt, 0, 0)?;
+        yield_(&mut rt)?;
+        done(&mut rt)?; // child is reaped straight away
+        assert!(rt.zombie_threads.is_empty());
+        assert_eq!(rt.thread_pool.len(), 1);
+
+        let err = join(&mut rt).expect_err("Joining a reaped thread should fail");
+        assert_eq!(
+            err.to_string(),
+            "Thread 2 finished and was reaped before being joined"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_join_failed() -> Result<()> {
+        let mut rt = Runtime::default();
+        rt.set_thread_error_policy(ThreadErrorPolicy::Isolate);
+        rt.current_thread.pc = 1; // prevent u64 subtraction overflow
+        spawn(&mut rt, 0, 0)?;
+        yield_(&mut rt)?;
+        rt.fail_thread(VmError::DivisionByZero.into())?;
+
+        let err = join(&mut rt).expect_err("Joining a failed thread should fail");
+        assert_eq!(err.to_string(), "Thread 2 failed: Division by zero");
+
+        // the error is handed over once
+        rt.current_thread
+            .operand_stack
+            .push(Value::ThreadId(MAIN_THREAD_ID + 1));
+        let err = join(&mut rt).expect_err("Double join should fail");
+        assert_eq!(err.to_string(), "Thread 2 was already joined");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_join_deadlock() -> Result<()> {
+        let mut rt = Runtime::default();
+        rt.current_thread.pc = 1; // prevent u64 subtraction overflow
+        spawn(&mut rt, 0, 0)?;
+        yield_(&mut rt)?;
+        rt.current_thread
+            .operand_stack
+            .push(Value::Semaphore(Semaphore::new(0)));
+        wait(&mut rt)?; // the child blocks, nothing will post
+
+        let err = join(&mut rt).expect_err("Joining a thread nothing can wake should fail");
+        assert_eq!(
+            err.to_string(),
+            "Deadlock: thread 1 is joining thread 2, which is waiting on a semaphore no other thread can post"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_join_invalid() -> Result<()> {
+        let mut rt = Runtime::default();
+        rt.current_thread.pc = 1; // prevent u64 subtraction overflow
+        spawn(&mut rt, 0, 0)?;
+        yield_(&mut rt)?;
+        done(&mut rt)?;
+        yield_(&mut rt)?;
+        join(&mut rt)?;
+        rt.current_thread.operand_stack.pop();
+
+        // joining again fails instead of waiting for a zombie that will never come
+        rt.current_thread
+            .operand_stack
+            .push(Value::ThreadId(MAIN_THREAD_ID + 1));
+        let err = join(&mut rt).expect_err("Double join should fail");
+        assert_eq!(err.to_string(), "Thread 2 was already joined");
+
+        let mut rt = Runtime::default();
+        rt.current_thread.operand_stack.push(Value::ThreadId(7));
+        let err = join(&mut rt).expect_err("Unknown thread should fail");
+        assert_eq!(err.to_string(), "Thread 7 not found or already joined");
+
+        // only thread IDs can be joined
+        let mut rt = Runtime::default();
+        rt.current_thread.operand_stack.push(Value::Int(2));
+        let err = join(&mut rt).expect_err("Joining an int should fail");
+        assert_eq!(
+            err.to_string(),
+            "Expected a thread ID from spawn or current_tid, found Int 2"
+        );
+
+        let mut rt = Runtime::default();
+        rt.current_thread
+            .operand_stack
+            .push(Value::ThreadId(MAIN_THREAD_ID));
+        let err = join(&mut rt).expect_err("Self join should fail");
+        assert_eq!(err.to_string(), "Thread 1 can't join itself");
+
+        Ok(())
+    }
 }
@@ -15,17 +15,17 @@ use crate::{Runtime, VmError};
 ///
 /// If the symbol is not found.
 #[inline]
-pub fn ld(mut rt: Runtime, sym: Symbol) -> Result<Runtime> {
+pub fn ld(rt: &mut Runtime, sym: &Symbol) -> Result<()> {
     let val = rt
         .current_thread
         .env
         .upgrade()
         .ok_or(VmError::EnvironmentDroppedError)?
         .borrow()
-        .get(&sym)?;
+        .get(sym)?;
 
     rt.current_thread.operand_stack.push(val);
-    Ok(rt)
+    Ok(())
 }
 
 #[cfg(test)]
@@ -43,7 +43,7 @@ mod tests {
             .unwrap()
             .borrow_mut()
             .set("x".to_string(), 42);
-        rt = ld(rt, "x".to_string()).unwrap();
+        ld(&mut rt, &"x".to_string()).unwrap();
         assert_eq!(rt.current_thread.operand_stack.pop(), Some(Value::Int(42)));
     }
 
@@ -57,7 +57,7 @@ mod tests {
         let env_weak = weak_clone(&env);
         env.borrow_mut().set_parent(parent_weak);
         rt.current_thread.env = env_weak;
-        rt = ld(rt, "x".to_string()).unwrap();
+        ld(&mut rt, &"x".to_string()).unwrap();
         assert_eq!(rt.current_thread.operand_stack.pop(), Some(Value::Int(42)));
     }
 }
@@ -0,0 +1,56 @@
+use anyhow::Result;
+use bytecode::{BinOp, Symbol, Value};
+
+use crate::{micro_code, Runtime};
+
+/// Fused form of LD(sym); LDC(val); BINOP(op), e.g `x < 10`.
+/// Behaves exactly like the unfused sequence but is dispatched as one instruction.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to execute the instruction on.
+///
+/// * `sym` - The symbol for the left-hand side of the operation.
+///
+/// * `val` - The constant for the right-hand side of the operation.
+///
+/// * `op` - The operation to execute.
+///
+/// # Errors
+///
+/// If the symbol is not found or the operation is not supported for the types involved.
+#[inline]
+pub fn ld_ldc_binop(rt: &mut Runtime, sym: &Symbol, val: Value, op: BinOp) -> Result<()> {
+    micro_code::ld(rt, sym)?;
+    micro_code::ldc(rt, val)?;
+    micro_code::binop(rt, op)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ld_ldc_binop() -> Result<()> {
+        let mut rt = Runtime::new(vec![]);
+        rt.current_thread
+            .env
+            .upgrade()
+            .unwrap()
+            .borrow_mut()
+            .set("x", 5);
+
+        ld_ldc_binop(&mut rt, &"x".to_string(), Value::Int(10), BinOp::Lt)?;
+        assert_eq!(
+            rt.current_thread.operand_stack.pop(),
+            Some(Value::Bool(true))
+        );
+
+        ld_ldc_binop(&mut rt, &"x".to_string(), Value::Int(3), BinOp::Mul)?;
+        assert_eq!(rt.current_thread.operand_stack.pop(), Some(Value::Int(15)));
+
+        assert!(ld_ldc_binop(&mut rt, &"x".to_string(), Value::Int(0), BinOp::Div).is_err());
+
+        Ok(())
+    }
+}
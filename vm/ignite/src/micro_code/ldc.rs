@@ -15,9 +15,9 @@ use crate::Runtime;
 ///
 /// Infallible.
 #[inline]
-pub fn ldc(mut rt: Runtime, val: Value) -> Result<Runtime> {
+pub fn ldc(rt: &mut Runtime, val: Value) -> Result<()> {
     rt.current_thread.operand_stack.push(val);
-    Ok(rt)
+    Ok(())
 }
 
 #[cfg(test)]
@@ -29,28 +29,28 @@ mod tests {
     #[test]
     fn test_ldc() {
         let mut rt = Runtime::new(vec![]);
-        rt = ldc(rt, Value::Unit).unwrap();
+        ldc(&mut rt, Value::Unit).unwrap();
         assert_eq!(rt.current_thread.operand_stack.pop().unwrap(), Value::Unit);
 
-        rt = ldc(rt, Value::Int(42)).unwrap();
+        ldc(&mut rt, Value::Int(42)).unwrap();
         assert_eq!(
             rt.current_thread.operand_stack.pop().unwrap(),
             Value::Int(42)
         );
 
-        rt = ldc(rt, Value::Float(42.0)).unwrap();
+        ldc(&mut rt, Value::Float(42.0)).unwrap();
         assert_eq!(
             rt.current_thread.operand_stack.pop().unwrap(),
             Value::Float(42.0)
         );
 
-        rt = ldc(rt, Value::Bool(true)).unwrap();
+        ldc(&mut rt, Value::Bool(true)).unwrap();
         assert_eq!(
             rt.current_thread.operand_stack.pop().unwrap(),
             Value::Bool(true)
         );
 
-        rt = ldc(rt, Value::String("hello world".into())).unwrap();
+        ldc(&mut rt, Value::String("hello world".into())).unwrap();
         assert_eq!(
             rt.current_thread.operand_stack.pop().unwrap(),
             Value::String("hello world".into())
@@ -17,7 +17,7 @@ use crate::Runtime;
 ///
 /// Infallible.
 #[inline]
-pub fn ldf(mut rt: Runtime, addr: usize, prms: Vec<Symbol>) -> Result<Runtime> {
+pub fn ldf(rt: &mut Runtime, addr: usize, prms: Vec<Symbol>) -> Result<()> {
     let closure = Value::Closure {
         fn_type: FnType::User,
         sym: "Closure".to_string(),
@@ -27,7 +27,7 @@ pub fn ldf(mut rt: Runtime, addr: usize, prms: Vec<Symbol>) -> Result<Runtime> {
     };
 
     rt.current_thread.operand_stack.push(closure);
-    Ok(rt)
+    Ok(())
 }
 
 #[cfg(test)]
@@ -37,7 +37,7 @@ mod tests {
     #[test]
     fn test_ldf() {
         let mut rt = Runtime::new(vec![]);
-        rt = ldf(rt, 0, vec!["x".to_string()]).unwrap();
+        ldf(&mut rt, 0, vec!["x".to_string()]).unwrap();
 
         let closure = rt.current_thread.operand_stack.pop().unwrap();
         assert_ne!(
@@ -3,19 +3,25 @@ pub use assign::assign;
 pub use binop::binop;
 pub use call::call;
 pub use done::done;
-pub use enter_scope::enter_scope;
-pub use exit_scope::exit_scope;
+pub use enter_scope::{enter_scope, enter_scope_local};
+pub use exit_scope::{exit_scope, exit_scope_local};
 pub use goto::goto;
+pub use inc::inc;
 pub use jof::jof;
-pub use join::join;
+pub use join::{join, join_timeout, thread_id};
 pub use ld::ld;
+pub use ld_ldc_binop::ld_ldc_binop;
 pub use ldc::ldc;
 pub use ldf::ldf;
 pub use pop::pop;
 pub use post::post;
 pub use reset::reset;
+pub use select::select;
 pub use sem_create::sem_create;
 pub use spawn::spawn;
+pub use spawn_isolated::spawn_isolated;
+pub use spawn_limited::spawn_limited;
+pub use thread_scope::{enter_thread_scope, join_thread_scope};
 pub use unop::unop;
 pub use wait::wait;
 pub use yield_::yield_; // yield is a reserved keyword in Rust
@@ -28,16 +34,22 @@ mod done;
 mod enter_scope;
 mod exit_scope;
 mod goto;
+mod inc;
 mod jof;
 mod join;
 mod ld;
+mod ld_ldc_binop;
 mod ldc;
 mod ldf;
 mod pop;
 mod post;
 mod reset;
+mod select;
 mod sem_create;
 mod spawn;
+mod spawn_isolated;
+mod spawn_limited;
+mod thread_scope;
 mod unop;
 mod wait;
 mod yield_; // yield is a reserved keyword in Rust
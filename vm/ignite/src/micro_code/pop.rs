@@ -12,12 +12,12 @@ use crate::{Runtime, VmError};
 ///
 /// If the stack is empty.
 #[inline]
-pub fn pop(mut rt: Runtime) -> Result<Runtime> {
+pub fn pop(rt: &mut Runtime) -> Result<()> {
     rt.current_thread
         .operand_stack
         .pop()
         .ok_or(VmError::OperandStackUnderflow)?;
-    Ok(rt)
+    Ok(())
 }
 
 #[cfg(test)]
@@ -30,8 +30,8 @@ mod tests {
     #[test]
     fn test_pop() {
         let mut rt = Runtime::new(vec![]);
-        rt = ldc(rt, Value::Unit).unwrap();
-        rt = pop(rt).unwrap();
+        ldc(&mut rt, Value::Unit).unwrap();
+        pop(&mut rt).unwrap();
         assert_eq!(rt.current_thread.operand_stack.len(), 0);
 
         let vals = vec![
@@ -44,22 +44,22 @@ mod tests {
         let val_len = vals.len();
         let mut rt = Runtime::new(vec![]);
         for val in vals {
-            rt = ldc(rt, val).unwrap();
+            ldc(&mut rt, val).unwrap();
         }
         for _ in 0..val_len {
-            rt = pop(rt).unwrap();
+            pop(&mut rt).unwrap();
         }
         assert_eq!(rt.current_thread.operand_stack.len(), 0);
 
-        rt = ldc(rt, Value::String("remember".into())).unwrap();
-        rt = ldc(rt, Value::Unit).unwrap();
-        rt = pop(rt).unwrap();
+        ldc(&mut rt, Value::String("remember".into())).unwrap();
+        ldc(&mut rt, Value::Unit).unwrap();
+        pop(&mut rt).unwrap();
         assert_eq!(
             rt.current_thread.operand_stack.pop().unwrap(),
             Value::String("remember".into())
         );
 
-        let empty_rt = Runtime::new(vec![]);
-        assert!(pop(empty_rt).is_err());
+        let mut empty_rt = Runtime::new(vec![]);
+        assert!(pop(&mut empty_rt).is_err());
     }
 }
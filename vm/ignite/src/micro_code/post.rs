@@ -1,12 +1,14 @@
 use anyhow::{Ok, Result};
 use bytecode::Semaphore;
+use log::debug;
 
-use crate::{Runtime, VmError};
+use crate::{ready_now, Runtime, VmError};
 
 /// Pops a value off the stack.
 /// The value is expected to be a semaphore.
 /// The semaphore is incremented.
-/// If a thread is blocked on this semaphore, the first blocked thread is moved to the ready queue.
+/// If a thread is blocked on this semaphore, the first blocked thread is moved to the ready queue, and
+/// holds the semaphore.
 /// The current thread continues execution.
 ///
 /// # Arguments
@@ -18,7 +20,7 @@ use crate::{Runtime, VmError};
 /// If the stack is empty.
 /// If the top value on stack is not a semaphore.
 #[inline]
-pub fn post(mut rt: Runtime) -> Result<Runtime> {
+pub fn post(rt: &mut Runtime) -> Result<()> {
     let sem: Semaphore = rt
         .current_thread
         .operand_stack
@@ -26,6 +28,10 @@ pub fn post(mut rt: Runtime) -> Result<Runtime> {
         .ok_or(VmError::OperandStackUnderflow)?
         .try_into()?;
 
+    // The current thread no longer holds the semaphore, and no thread that ended does
+    rt.current_thread.release(&sem);
+    rt.orphaned_sems.retain(|(orphan, _)| orphan != &sem);
+
     let mut sem_guard = sem.lock().unwrap();
     *sem_guard += 1;
 
@@ -36,17 +42,23 @@ pub fn post(mut rt: Runtime) -> Result<Runtime> {
         .position(|(_, blocking_sem)| blocking_sem == &sem)
         .map(|i| rt.blocked_queue.remove(i));
 
-    let Some(Some((blocked_thread, _))) = blocked_thread else {
+    let Some(Some((mut blocked_thread, _))) = blocked_thread else {
         // If no blocked threads are found, nothing needs to be done.
-        return Ok(rt);
+        return Ok(());
     };
 
     *sem_guard -= 1;
     drop(sem_guard); // Unlock the semaphore.
 
-    // Move the blocked thread to the ready queue.
+    debug!(
+        "Thread {} woke thread {} waiting on a semaphore",
+        rt.current_thread.thread_id, blocked_thread.thread_id
+    );
+    // Move the blocked thread to the ready queue, holding the semaphore.
+    blocked_thread.acquire(sem);
+    ready_now(&mut blocked_thread);
     rt.ready_queue.push_back(blocked_thread);
-    Ok(rt)
+    Ok(())
 }
 
 #[cfg(test)]
@@ -64,10 +76,10 @@ mod tests {
         let mut rt = Runtime::default();
         let sem = Semaphore::new(0);
         let current_env = rt.current_thread.env.clone();
-        rt = extend_environment(rt, current_env, vec!["sem"], vec![sem.clone()])?;
-        rt = spawn(rt, 0)?; // spawn a child thread to populate ready queue
-        rt = ld(rt, "sem".into())?;
-        rt = post(rt)?;
+        extend_environment(&mut rt, current_env, vec!["sem"], vec![sem.clone()])?;
+        spawn(&mut rt, 0, 0)?; // spawn a child thread to populate ready queue
+        ld(&mut rt, &"sem".into())?;
+        post(&mut rt)?;
 
         // Since no threads are blocked on the semaphore, the current thread should continue.
         assert_eq!(rt.current_thread.thread_id, MAIN_THREAD_ID);
@@ -82,20 +94,19 @@ mod tests {
         let mut rt = Runtime::default();
         let sem = Semaphore::new(0);
         let current_env = rt.current_thread.env.clone();
-        rt = extend_environment(rt, current_env, vec!["sem"], vec![sem.clone()])?;
-        rt = spawn(rt, 0)?; // spawn a child thread to populate ready queue
-        rt = yield_(rt)?; // yield the current thread to child thread
-        rt = ld(rt, "sem".into())?;
-        rt = wait(rt)?;
-        rt = ld(rt, "sem".into())?;
-        rt = post(rt)?;
-
-        // Child thread should be moved to the ready queue.
+        extend_environment(&mut rt, current_env, vec!["sem"], vec![sem.clone()])?;
+        spawn(&mut rt, 0, 0)?; // spawn a child thread to populate ready queue
+        yield_(&mut rt)?; // yield the current thread to child thread
+        ld(&mut rt, &"sem".into())?;
+        wait(&mut rt)?;
+        ld(&mut rt, &"sem".into())?;
+        post(&mut rt)?;
+
+        // Child thread should be moved to the ready queue, holding the semaphore.
         let child_thread_id = MAIN_THREAD_ID + 1;
-        assert_eq!(
-            rt.ready_queue.pop_front().unwrap().thread_id,
-            child_thread_id
-        );
+        let child = rt.ready_queue.pop_front().unwrap();
+        assert_eq!(child.thread_id, child_thread_id);
+        assert_eq!(child.held_sems, vec![(sem, 1)]);
 
         Ok(())
     }
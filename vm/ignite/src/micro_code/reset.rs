@@ -1,9 +1,10 @@
-use crate::{Runtime, VmError};
+use crate::{callback_returned, resume_callback, Runtime, VmError};
 use anyhow::Result;
 use bytecode::FrameType;
 
 /// Reset the runtime to the last frame of the given type. This will pop all frames up to and including
 /// the last frame of the given type.
+/// If the call frame popped belongs to a function called by a builtin like map, the builtin is resumed.
 ///
 /// # Arguments
 ///
@@ -14,8 +15,9 @@ use bytecode::FrameType;
 /// # Errors
 ///
 /// If the runtime stack underflows. i.e. there are no frames of the given type.
+/// If the function returning was called by a builtin that fails on its result.
 #[inline]
-pub fn reset(mut rt: Runtime, ft: FrameType) -> Result<Runtime> {
+pub fn reset(rt: &mut Runtime, ft: FrameType) -> Result<()> {
     loop {
         let frame = rt
             .current_thread
@@ -35,7 +37,12 @@ pub fn reset(mut rt: Runtime, ft: FrameType) -> Result<Runtime> {
         break;
     }
 
-    Ok(rt)
+    // The function returning may have been called by a builtin, which carries on with its result
+    if ft == FrameType::CallFrame && callback_returned(rt) {
+        return resume_callback(rt);
+    }
+
+    Ok(())
 }
 
 #[cfg(test)]
@@ -65,7 +72,7 @@ mod tests {
 
         assert!(rt.current_thread.runtime_stack.len() == 3);
 
-        rt = reset(rt, FrameType::BlockFrame).unwrap();
+        reset(&mut rt, FrameType::BlockFrame).unwrap();
 
         assert!(rt.current_thread.runtime_stack.len() == 1);
         assert_eq!(
@@ -103,7 +110,7 @@ mod tests {
 
         assert!(rt.current_thread.runtime_stack.len() == 3);
 
-        rt = reset(rt, FrameType::BlockFrame).unwrap();
+        reset(&mut rt, FrameType::BlockFrame).unwrap();
 
         assert!(rt.current_thread.runtime_stack.len() == 1);
         assert_eq!(rt.current_thread.pc, 123);
@@ -0,0 +1,157 @@
+use anyhow::Result;
+use bytecode::{builtin, SelectOp, Value};
+
+use crate::{Runtime, VmError};
+
+use super::yield_;
+
+/// Pops the operands of the queue operations off the stack, the first operation's deepest, and
+/// does the first operation that can be done straight away: a pop from a queue that isn't empty or
+/// a push to a queue that isn't full. Pushes the popped value, or Unit for a push, then the index
+/// of the operation.
+///
+/// If no operation can be done and the select has a default arm, pushes Unit and the number of
+/// operations. Otherwise the operands are put back and the current thread yields, so the
+/// instruction is retried when it is scheduled again, like a queue_pop of an empty queue.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to do the select in.
+///
+/// * `ops` - The operations to choose from, in the order of the select's arms.
+///
+/// * `has_default` - Whether the select has a default arm, so it never waits.
+///
+/// # Errors
+///
+/// If the stack has fewer values than the operations take.
+/// If an operand is not a queue, or a value pushed is a closure.
+/// If no operation can be done, there is no default arm and no other thread can run.
+#[inline]
+pub fn select(rt: &mut Runtime, ops: &[SelectOp], has_default: bool) -> Result<()> {
+    let stack = &mut rt.current_thread.operand_stack;
+    let n_operands: usize = ops.iter().map(SelectOp::operands).sum();
+    let start = stack
+        .len()
+        .checked_sub(n_operands)
+        .ok_or(VmError::OperandStackUnderflow)?;
+    let operands = stack.split_off(start);
+
+    let mut rest = operands.iter();
+    for (idx, op) in ops.iter().enumerate() {
+        let q = rest.next().ok_or(VmError::OperandStackUnderflow)?;
+        let done = match op {
+            SelectOp::Pop => builtin::queue_pop_impl(q)?,
+            SelectOp::Push => {
+                let v = rest.next().ok_or(VmError::OperandStackUnderflow)?;
+                builtin::queue_push_impl(q, v)?.then_some(Value::Unit)
+            }
+        };
+
+        if let Some(val) = done {
+            rt.current_thread.operand_stack.push(val);
+            rt.current_thread.operand_stack.push(Value::Int(idx as i64));
+            return Ok(());
+        }
+    }
+
+    if has_default {
+        rt.current_thread.operand_stack.push(Value::Unit);
+        rt.current_thread
+            .operand_stack
+            .push(Value::Int(ops.len() as i64));
+        return Ok(());
+    }
+
+    // No other thread can run to pop or push
+    if rt.ready_queue.is_empty() {
+        return Err(VmError::SelectDeadlock(rt.current_thread.thread_id).into());
+    }
+
+    rt.current_thread.operand_stack.extend(operands);
+    rt.current_thread.pc -= 1; // Decrement the program counter to re-execute the instruction
+    yield_(rt)
+}
+
+#[cfg(test)]
+mod tests {
+    use bytecode::Queue;
+
+    use crate::{micro_code::spawn, MAIN_THREAD_ID};
+
+    use super::*;
+
+    #[test]
+    fn test_select() -> Result<()> {
+        let mut rt = Runtime::default();
+        let empty = Queue::new();
+        let full = Queue::bounded(1);
+        full.borrow_mut().values.push_back(Value::Int(7));
+        let ops = [SelectOp::Pop, SelectOp::Push, SelectOp::Pop];
+
+        // the first op that can be done is the pop from full
+        rt.current_thread.operand_stack.extend([
+            empty.clone().into(),
+            full.clone().into(),
+            Value::Int(1),
+            full.clone().into(),
+        ]);
+        select(&mut rt, &ops, false)?;
+        assert_eq!(
+            rt.current_thread.operand_stack,
+            vec![Value::Int(7), Value::Int(2)]
+        );
+
+        // now full has room for the push
+        rt.current_thread.operand_stack.clear();
+        rt.current_thread.operand_stack.extend([
+            empty.clone().into(),
+            full.clone().into(),
+            Value::Int(1),
+            empty.clone().into(),
+        ]);
+        select(&mut rt, &ops, false)?;
+        assert_eq!(
+            rt.current_thread.operand_stack,
+            vec![Value::Unit, Value::Int(1)]
+        );
+        assert_eq!(full.borrow().values, vec![Value::Int(1)]);
+
+        // nothing can be done: the default arm
+        rt.current_thread.operand_stack.clear();
+        rt.current_thread.operand_stack.extend([
+            empty.clone().into(),
+            full.clone().into(),
+            Value::Int(2),
+        ]);
+        select(&mut rt, &[SelectOp::Pop, SelectOp::Push], true)?;
+        assert_eq!(
+            rt.current_thread.operand_stack,
+            vec![Value::Unit, Value::Int(2)]
+        );
+
+        // without one, the thread waits with its operands put back
+        rt.current_thread.operand_stack.clear();
+        let err = select(&mut rt, &[SelectOp::Pop], false).expect_err("Stack is empty");
+        assert_eq!(err.to_string(), "Operand stack underflow");
+
+        rt.current_thread.operand_stack.push(empty.clone().into());
+        let err = select(&mut rt, &[SelectOp::Pop], false).expect_err("Nothing else can run");
+        assert_eq!(
+            err.to_string(),
+            "Deadlock: thread 1 is waiting in a select none of whose queues can be used and no other thread can run"
+        );
+
+        let mut rt = Runtime::default();
+        spawn(&mut rt, 0, 0)?;
+        rt.current_thread.pc = 1;
+        rt.current_thread.operand_stack.push(empty.clone().into());
+        select(&mut rt, &[SelectOp::Pop], false)?;
+        assert_eq!(rt.current_thread.thread_id, MAIN_THREAD_ID + 1);
+        let waiting = rt.ready_queue.back().unwrap();
+        assert_eq!(waiting.pc, 0);
+        assert_eq!(waiting.operand_stack.last(), Some(&Value::Queue(empty)));
+
+        Ok(())
+    }
+}
@@ -1,12 +1,20 @@
+use std::rc::Weak;
+
 use anyhow::Result;
+use bytecode::Value;
+use log::debug;
 
-use crate::Runtime;
+use crate::{ready_now, Runtime, VmError};
 
 /// Spawn a child thread that clones the current/parent thread at the time of the spawn.
-/// The child thread is given a unique thread ID.
+/// The child thread is given a unique thread ID, and reuses a pooled thread if there is one.
 /// The child thread is added to the back of the ready queue.
-/// This thread ID is pushed onto the operand stack of the parent thread.
-/// 0 is pushed onto the operand stack of the child thread.
+/// The top moved values of the parent's operand stack, the function the child calls and its
+/// arguments, are moved to the child's operand stack in the same order. They are evaluated by the
+/// parent, so the child starts with the values they had at the spawn.
+/// This thread ID is pushed onto the operand stack of the parent thread, and added to the parent's
+/// innermost thread scope if it is in one.
+/// 0 is pushed onto the operand stack of the child thread, above the moved values.
 /// The child thread starts execution at the given address.
 /// The parent thread continues execution.
 ///
@@ -14,35 +22,81 @@ use crate::Runtime;
 ///
 /// * `rt` - The runtime to spawn a new thread in.
 ///
+/// * `addr` - The address of the instruction for the child to execute.
+///
+/// * `moved` - The number of values to move from the parent's operand stack to the child's.
+///
 /// # Errors
 ///
-/// Infallible.
+/// If the parent's operand stack has fewer than moved values.
 #[inline]
-pub fn spawn(mut rt: Runtime, addr: usize) -> Result<Runtime> {
+pub fn spawn(rt: &mut Runtime, addr: usize, moved: usize) -> Result<()> {
+    let stack = &mut rt.current_thread.operand_stack;
+    let split = stack
+        .len()
+        .checked_sub(moved)
+        .ok_or(VmError::OperandStackUnderflow)?;
+    let args = stack.split_off(split);
+
     rt.thread_count += 1;
 
     let child_thread_id = rt.thread_count;
-    let mut child_thread = rt.current_thread.spawn_child(child_thread_id, addr);
+    let env = Weak::clone(&rt.current_thread.env);
+    let mut child_thread = rt.new_thread(child_thread_id, env, addr);
 
+    child_thread.operand_stack.extend(args);
     // 0 is pushed onto the operand stack of the child thread.
     child_thread.operand_stack.push(0.into());
     // The child thread ID is pushed onto the operand stack of the parent thread.
-    rt.current_thread.operand_stack.push(child_thread_id.into());
+    rt.current_thread
+        .operand_stack
+        .push(Value::ThreadId(child_thread_id));
+    if let Some(scope) = rt.current_thread.thread_scopes.last_mut() {
+        scope.push(child_thread_id);
+    }
 
+    debug!(
+        "Thread {} spawned thread {}",
+        rt.current_thread.thread_id, child_thread_id
+    );
+    ready_now(&mut child_thread);
     rt.ready_queue.push_back(child_thread);
-    Ok(rt)
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
+    use bytecode::Value;
+
     use super::*;
 
     #[test]
     fn test_spawn() -> Result<()> {
-        let rt = Runtime::new(vec![]);
-        let rt = spawn(rt, 0)?;
+        let mut rt = Runtime::new(vec![]);
+        spawn(&mut rt, 0, 0)?;
         assert_eq!(rt.thread_count, 2);
         assert_eq!(rt.ready_queue.len(), 1);
         Ok(())
     }
+
+    #[test]
+    fn test_spawn_moves_args() -> Result<()> {
+        let mut rt = Runtime::new(vec![]);
+        rt.current_thread
+            .operand_stack
+            .extend([Value::Int(1), Value::Int(2), Value::Int(3)]);
+
+        spawn(&mut rt, 0, 2)?;
+        assert_eq!(
+            rt.current_thread.operand_stack,
+            vec![Value::Int(1), Value::ThreadId(2)]
+        );
+        assert_eq!(
+            rt.ready_queue.back().unwrap().operand_stack,
+            vec![Value::Int(2), Value::Int(3), Value::Int(0)]
+        );
+
+        assert!(spawn(&mut rt, 0, 3).is_err());
+        Ok(())
+    }
 }
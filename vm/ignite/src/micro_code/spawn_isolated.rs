@@ -0,0 +1,71 @@
+use anyhow::Result;
+
+use crate::{fork_environment, micro_code::spawn, Runtime};
+
+/// Spawn a child thread like SPAWN, but give the child a copy of the parent's environment chain.
+/// Variables the child assigns to, including through functions it calls, are not seen by the
+/// parent or any other thread, and the child does not see later writes by the parent. Functions
+/// moved to the child, like the one it calls, run on the copy too.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to spawn a new thread in.
+///
+/// * `addr` - The address of the instruction for the child to execute.
+///
+/// * `moved` - The number of values to move from the parent's operand stack to the child's.
+///
+/// # Errors
+///
+/// If an environment in the parent's chain has been dropped, or like SPAWN.
+#[inline]
+pub fn spawn_isolated(rt: &mut Runtime, addr: usize, moved: usize) -> Result<()> {
+    spawn(rt, addr, moved)?;
+
+    let child = rt
+        .ready_queue
+        .back_mut()
+        .expect("Child thread was just added to the ready queue");
+    let mut stack = std::mem::take(&mut child.operand_stack);
+
+    let env = rt.current_thread.env.clone();
+    let forked = fork_environment(rt, &env, &mut stack)?;
+
+    let child = rt
+        .ready_queue
+        .back_mut()
+        .expect("Child thread was just added to the ready queue");
+    child.env = forked;
+    child.operand_stack = stack;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use bytecode::{weak_clone, Environment, Value};
+
+    use super::*;
+
+    #[test]
+    fn test_spawn_isolated() -> Result<()> {
+        let mut rt = Runtime::new(vec![]);
+        let env = Environment::new_wrapped();
+        env.borrow_mut().set_parent(rt.current_thread.env.clone());
+        env.borrow_mut().set("x", 1);
+        rt.current_thread.env = weak_clone(&env);
+
+        spawn_isolated(&mut rt, 0, 0)?;
+        assert_eq!(rt.thread_count, 2);
+
+        let child_env = rt.ready_queue.back().unwrap().env.upgrade().unwrap();
+        assert!(!Rc::ptr_eq(&child_env, &env));
+
+        child_env.borrow_mut().update("x", 2)?;
+        assert_eq!(env.borrow().get(&"x".to_string())?, Value::Int(1));
+
+        Ok(())
+    }
+}
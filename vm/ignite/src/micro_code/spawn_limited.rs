@@ -0,0 +1,92 @@
+use anyhow::Result;
+
+use crate::{micro_code::spawn, Runtime, VmError};
+
+/// Spawn a child thread like SPAWN, but limit the number of instructions the child may execute.
+/// The budget is popped from the parent's operand stack before the values moved to the child. A child that runs out of budget is
+/// terminated, and joining it gives an error message instead of its result.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to spawn a new thread in.
+///
+/// * `addr` - The address of the instruction for the child to execute.
+///
+/// * `moved` - The number of values to move from the parent's operand stack to the child's.
+///
+/// # Errors
+///
+/// If the operand stack is empty, the budget is not a non-negative integer, or like SPAWN.
+#[inline]
+pub fn spawn_limited(rt: &mut Runtime, addr: usize, moved: usize) -> Result<()> {
+    let budget: i64 = rt
+        .current_thread
+        .operand_stack
+        .pop()
+        .ok_or(VmError::OperandStackUnderflow)?
+        .try_into()?;
+
+    let budget = u64::try_from(budget).map_err(|_| {
+        VmError::IllegalArgument(format!(
+            "instruction budget must not be negative, got {}",
+            budget
+        ))
+    })?;
+
+    spawn(rt, addr, moved)?;
+    rt.ready_queue
+        .back_mut()
+        .expect("Child thread was just added to the ready queue")
+        .instr_budget = Some(budget);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use bytecode::{ByteCode, Value};
+
+    use super::*;
+    use crate::{micro_code::ldc, run};
+
+    #[test]
+    fn test_spawn_limited() -> Result<()> {
+        let mut rt = Runtime::new(vec![]);
+        ldc(&mut rt, Value::Int(10))?;
+        spawn_limited(&mut rt, 0, 0)?;
+
+        assert_eq!(rt.thread_count, 2);
+        assert!(rt.current_thread.operand_stack.len() == 1); // child tid
+        assert_eq!(rt.ready_queue.back().unwrap().instr_budget, Some(10));
+
+        let mut rt = Runtime::new(vec![]);
+        ldc(&mut rt, Value::Int(-1))?;
+        assert!(spawn_limited(&mut rt, 0, 0).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_spawn_limited_runaway() -> Result<()> {
+        // child loops forever, parent joins it
+        let instrs = vec![
+            ByteCode::ldc(5),
+            ByteCode::SPAWNLIMITED(4, 0),
+            ByteCode::JOIN,
+            ByteCode::DONE,
+            ByteCode::POP, // 4: child
+            ByteCode::GOTO(5),
+        ];
+
+        let rt = run(Runtime::new(instrs))?;
+        let result = rt.current_thread.operand_stack.last().cloned();
+        assert_eq!(
+            result,
+            Some(Value::String(
+                "thread 2 exceeded its instruction budget of 5".to_string()
+            ))
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,203 @@
+use anyhow::Result;
+use bytecode::ThreadID;
+use log::debug;
+
+use crate::{Runtime, VmError};
+
+use super::{join::join_deadlock, yield_};
+
+/// Start a thread scope in the current thread. Threads it spawns from now on, directly or in the
+/// functions it calls, are added to the scope and joined by the matching join_thread_scope.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to start the thread scope in.
+#[inline]
+pub fn enter_thread_scope(rt: &mut Runtime) -> Result<()> {
+    rt.current_thread.thread_scopes.push(vec![]);
+    Ok(())
+}
+
+/// Join every thread spawned in the current thread's innermost thread scope, then end the scope.
+/// The results of the threads are discarded. Threads that were already joined, reaped or detached
+/// are not waited for.
+/// While a thread of the scope is still running, the current thread yields and the instruction is
+/// retried when it is scheduled again.
+///
+/// # Arguments
+///
+/// * `rt` - The runtime to join the thread scope in.
+///
+/// # Errors
+///
+/// * If the current thread is not in a thread scope.
+/// * If a thread of the scope failed, with the error of the first one spawned. The scope ends and
+///   its threads still running are cancelled.
+/// * If a thread of the scope is blocked on a semaphore and no other thread can run.
+#[inline]
+pub fn join_thread_scope(rt: &mut Runtime) -> Result<()> {
+    let scope = rt
+        .current_thread
+        .thread_scopes
+        .last()
+        .ok_or(VmError::ThreadScopeUnderflow)?;
+
+    if let Some(&tid) = scope.iter().find(|tid| rt.failed_threads.contains_key(tid)) {
+        let scope = rt.current_thread.thread_scopes.pop().unwrap_or_default();
+        cancel_scope(rt, &scope)?;
+        let err = rt
+            .failed_threads
+            .remove(&tid)
+            .expect("Thread ID was taken from failed threads");
+        return Err(VmError::ThreadFailed(tid, err).into());
+    }
+
+    while let Some(&tid) = rt
+        .current_thread
+        .thread_scopes
+        .last()
+        .and_then(|scope| scope.last())
+    {
+        if let Some(zombie_thread) = rt.zombie_threads.remove(&tid) {
+            debug!(
+                "Thread {} joined thread {} at the end of its scope",
+                rt.current_thread.thread_id, tid
+            );
+            rt.recycle_thread(zombie_thread);
+        } else if rt.is_alive(tid) && !rt.detached_threads.contains(&tid) {
+            // With no thread ready, the thread to join is blocked and nothing can wake it
+            if rt.ready_queue.is_empty() {
+                return Err(join_deadlock(rt, tid).into());
+            }
+
+            rt.current_thread.pc -= 1; // Decrement the program counter to re-execute the instruction
+            return yield_(rt);
+        } else {
+            // Joined, reaped or detached before the end of the scope
+            rt.reaped_threads.remove(&tid);
+        }
+
+        if let Some(scope) = rt.current_thread.thread_scopes.last_mut() {
+            scope.pop();
+        }
+    }
+
+    rt.current_thread.thread_scopes.pop();
+    Ok(())
+}
+
+/// Cancel the threads of a scope that are still running and detach them, so they end without being
+/// joined. Those that finished are recycled.
+fn cancel_scope(rt: &mut Runtime, scope: &[ThreadID]) -> Result<()> {
+    for &tid in scope {
+        if let Some(zombie_thread) = rt.zombie_threads.remove(&tid) {
+            rt.recycle_thread(zombie_thread);
+        } else if rt.is_alive(tid) && !rt.detached_threads.contains(&tid) {
+            rt.cancel(tid)?;
+            rt.detach(tid)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use bytecode::{Semaphore, Value};
+
+    use crate::{
+        micro_code::{done, join, spawn, wait},
+        ThreadErrorPolicy, MAIN_THREAD_ID,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_join_thread_scope() -> Result<()> {
+        let mut rt = Runtime::default();
+        rt.current_thread.pc = 1; // prevent u64 subtraction overflow
+        spawn(&mut rt, 0, 0)?; // not in the scope
+        enter_thread_scope(&mut rt)?;
+        spawn(&mut rt, 0, 0)?;
+        spawn(&mut rt, 0, 0)?;
+        assert_eq!(rt.current_thread.thread_scopes, vec![vec![3, 4]]);
+
+        // both threads of the scope are running, so the current thread waits for them
+        join_thread_scope(&mut rt)?;
+        assert_eq!(rt.current_thread.thread_id, MAIN_THREAD_ID + 1);
+        done(&mut rt)?;
+        assert_eq!(rt.current_thread.thread_id, MAIN_THREAD_ID + 2);
+        done(&mut rt)?;
+        assert_eq!(rt.current_thread.thread_id, MAIN_THREAD_ID + 3);
+        done(&mut rt)?;
+        assert_eq!(rt.current_thread.thread_id, MAIN_THREAD_ID);
+
+        join_thread_scope(&mut rt)?;
+        assert!(rt.current_thread.thread_scopes.is_empty());
+        // only the thread spawned outside the scope is left to join
+        let zombies: Vec<_> = rt.zombie_threads.keys().copied().collect();
+        assert_eq!(zombies, vec![MAIN_THREAD_ID + 1]);
+
+        // threads joined in the scope are skipped
+        enter_thread_scope(&mut rt)?;
+        spawn(&mut rt, 0, 0)?;
+        yield_(&mut rt)?;
+        done(&mut rt)?;
+        join(&mut rt)?;
+        join_thread_scope(&mut rt)?;
+        assert!(rt.current_thread.thread_scopes.is_empty());
+
+        assert!(matches!(
+            join_thread_scope(&mut rt)
+                .expect_err("Should have no scope")
+                .downcast_ref::<VmError>(),
+            Some(VmError::ThreadScopeUnderflow)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_join_thread_scope_errors() -> Result<()> {
+        let mut rt = Runtime::default();
+        rt.set_thread_error_policy(ThreadErrorPolicy::Isolate);
+        rt.current_thread.pc = 1; // prevent u64 subtraction overflow
+        enter_thread_scope(&mut rt)?;
+        spawn(&mut rt, 0, 0)?;
+        yield_(&mut rt)?;
+        rt.fail_thread(VmError::DivisionByZero.into())?;
+
+        let err = join_thread_scope(&mut rt).expect_err("A thread of the scope failed");
+        assert_eq!(err.to_string(), "Thread 2 failed: Division by zero");
+        assert!(rt.current_thread.thread_scopes.is_empty());
+
+        enter_thread_scope(&mut rt)?;
+        spawn(&mut rt, 0, 0)?;
+        yield_(&mut rt)?;
+        rt.current_thread
+            .operand_stack
+            .push(Value::Semaphore(Semaphore::new(0)));
+        wait(&mut rt)?; // the child blocks, nothing will post
+
+        let err = join_thread_scope(&mut rt).expect_err("Nothing can wake the thread");
+        assert_eq!(
+            err.to_string(),
+            "Deadlock: thread 1 is joining thread 3, which is waiting on a semaphore no other thread can post"
+        );
+
+        // the other threads of a failed scope are cancelled, and not left to join
+        enter_thread_scope(&mut rt)?;
+        spawn(&mut rt, 0, 0)?;
+        spawn(&mut rt, 0, 0)?;
+        yield_(&mut rt)?;
+        rt.fail_thread(VmError::DivisionByZero.into())?;
+        assert_eq!(rt.current_thread.thread_id, MAIN_THREAD_ID + 4);
+        yield_(&mut rt)?;
+
+        let err = join_thread_scope(&mut rt).expect_err("A thread of the scope failed");
+        assert_eq!(err.to_string(), "Thread 4 failed: Division by zero");
+        assert!(rt.ready_queue[0].cancelled);
+        assert!(rt.detached_threads.contains(&(MAIN_THREAD_ID + 4)));
+
+        Ok(())
+    }
+}
@@ -17,28 +17,44 @@ use bytecode::{type_of, UnOp, Value};
 /// If the stack is empty or the operation is not supported for
 /// the type of the value on the stack.
 #[inline]
-pub fn unop(mut rt: Runtime, op: UnOp) -> Result<Runtime> {
+pub fn unop(rt: &mut Runtime, op: UnOp) -> Result<()> {
     let val = rt
         .current_thread
         .operand_stack
         .pop()
         .ok_or(VmError::OperandStackUnderflow)?;
 
+    if matches!(op, UnOp::ToInt | UnOp::ToFloat | UnOp::ToBool) {
+        let result = cast(&op, &val)
+            .ok_or_else(|| VmError::UnsupportedOperation(op.into(), type_of(&val).into()))?;
+        rt.current_thread.operand_stack.push(result);
+        return Ok(());
+    }
+
     match val {
         Value::Unit => Err(VmError::UnsupportedOperation(op.into(), type_of(&val).into()).into()),
         Value::Int(i) => {
             let result = match op {
-                UnOp::Neg => Value::Int(-i), // Negation
+                // Negation
+                UnOp::Neg => Value::Int(
+                    i.checked_neg()
+                        .ok_or_else(|| VmError::IntegerOverflow(format!("-({})", i)))?,
+                ),
                 UnOp::Not => Value::Int(!i), // Bitwise Not
+                _ => {
+                    return Err(
+                        VmError::UnsupportedOperation(op.into(), type_of(&val).into()).into(),
+                    )
+                }
             };
             rt.current_thread.operand_stack.push(result);
-            Ok(rt)
+            Ok(())
         }
         Value::Float(f) => {
             if let UnOp::Neg = op {
                 let result = Value::Float(-f); // Negation
                 rt.current_thread.operand_stack.push(result);
-                Ok(rt)
+                Ok(())
             } else {
                 Err(VmError::UnsupportedOperation(op.into(), type_of(&val).into()).into())
             }
@@ -47,7 +63,7 @@ pub fn unop(mut rt: Runtime, op: UnOp) -> Result<Runtime> {
             if let UnOp::Not = op {
                 let result = Value::Bool(!b); // Logical Not
                 rt.current_thread.operand_stack.push(result);
-                Ok(rt)
+                Ok(())
             } else {
                 Err(VmError::UnsupportedOperation(op.into(), type_of(&val).into()).into())
             }
@@ -58,15 +74,44 @@ pub fn unop(mut rt: Runtime, op: UnOp) -> Result<Runtime> {
         Value::Unitialized => {
             Err(VmError::UnsupportedOperation(op.into(), type_of(&val).into()).into())
         }
+        Value::ThreadId(_) => {
+            Err(VmError::UnsupportedOperation(op.into(), type_of(&val).into()).into())
+        }
         Value::Semaphore(_) => {
             Err(VmError::UnsupportedOperation(op.into(), type_of(&val).into()).into())
         }
+        Value::Queue(_) => {
+            Err(VmError::UnsupportedOperation(op.into(), type_of(&val).into()).into())
+        }
+        Value::Matrix(_) => {
+            Err(VmError::UnsupportedOperation(op.into(), type_of(&val).into()).into())
+        }
         Value::Closure { .. } => {
             Err(VmError::UnsupportedOperation(op.into(), type_of(&val).into()).into())
         }
+        Value::Cancelled => {
+            Err(VmError::UnsupportedOperation(op.into(), type_of(&val).into()).into())
+        }
     }
 }
 
+/// Result of a cast, or None if the value can't be cast to that type. Float to int uses Rust's as
+/// semantics: truncation towards zero, saturating at the bounds and NaN to 0.
+fn cast(op: &UnOp, val: &Value) -> Option<Value> {
+    let res = match (op, val) {
+        (UnOp::ToInt, Value::Int(i)) => Value::Int(*i),
+        (UnOp::ToInt, Value::Float(f)) => Value::Int(*f as i64),
+        (UnOp::ToInt, Value::Bool(b)) => Value::Int(*b as i64),
+        (UnOp::ToInt, Value::ThreadId(tid)) => Value::Int(*tid),
+        (UnOp::ToFloat, Value::Int(i)) => Value::Float(*i as f64),
+        (UnOp::ToFloat, Value::Float(f)) => Value::Float(*f),
+        (UnOp::ToBool, Value::Bool(b)) => Value::Bool(*b),
+        _ => return None,
+    };
+
+    Some(res)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -76,50 +121,90 @@ mod tests {
     #[test]
     fn test_unop() {
         let mut rt = Runtime::new(vec![]);
-        rt = ldc(rt, Value::Int(42)).unwrap();
-        rt = unop(rt, UnOp::Neg).unwrap();
+        ldc(&mut rt, Value::Int(42)).unwrap();
+        unop(&mut rt, UnOp::Neg).unwrap();
         assert_eq!(
             rt.current_thread.operand_stack.pop().unwrap(),
             Value::Int(-42)
         );
 
-        rt = ldc(rt, Value::Float(42.0)).unwrap();
-        rt = unop(rt, UnOp::Neg).unwrap();
+        ldc(&mut rt, Value::Float(42.0)).unwrap();
+        unop(&mut rt, UnOp::Neg).unwrap();
         assert_eq!(
             rt.current_thread.operand_stack.pop().unwrap(),
             Value::Float(-42.0)
         );
 
-        rt = ldc(rt, Value::Bool(true)).unwrap();
-        rt = unop(rt, UnOp::Not).unwrap();
+        ldc(&mut rt, Value::Bool(true)).unwrap();
+        unop(&mut rt, UnOp::Not).unwrap();
         assert_eq!(
             rt.current_thread.operand_stack.pop().unwrap(),
             Value::Bool(false)
         );
 
-        rt = ldc(rt, Value::Bool(false)).unwrap();
-        rt = unop(rt, UnOp::Not).unwrap();
+        ldc(&mut rt, Value::Bool(false)).unwrap();
+        unop(&mut rt, UnOp::Not).unwrap();
         assert_eq!(
             rt.current_thread.operand_stack.pop().unwrap(),
             Value::Bool(true)
         );
 
-        rt = ldc(rt, Value::Unit).unwrap();
-        let result = unop(rt, UnOp::Not);
+        ldc(&mut rt, Value::Unit).unwrap();
+        let result = unop(&mut rt, UnOp::Not);
         assert!(result.is_err());
 
         let mut rt = Runtime::new(vec![]);
-        rt = ldc(rt, Value::String("hello world".into())).unwrap();
-        let result = unop(rt, UnOp::Not);
+        ldc(&mut rt, Value::String("hello world".into())).unwrap();
+        let result = unop(&mut rt, UnOp::Not);
         assert!(result.is_err());
 
         let mut rt = Runtime::new(vec![]);
-        rt = ldc(rt, Value::Int(42)).unwrap();
-        rt = unop(rt, UnOp::Not).unwrap();
-        rt = unop(rt, UnOp::Neg).unwrap();
+        ldc(&mut rt, Value::Int(42)).unwrap();
+        unop(&mut rt, UnOp::Not).unwrap();
+        unop(&mut rt, UnOp::Neg).unwrap();
         assert_eq!(
             rt.current_thread.operand_stack.pop().unwrap(),
             Value::Int(43)
         );
     }
+
+    #[test]
+    fn test_unop_neg_overflow() {
+        let mut rt = Runtime::new(vec![]);
+        ldc(&mut rt, Value::Int(i64::MIN)).unwrap();
+        let result = unop(&mut rt, UnOp::Neg);
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "Integer overflow: -(-9223372036854775808)"
+        );
+    }
+
+    #[test]
+    fn test_unop_cast() {
+        let cases = [
+            (Value::Float(2.9), UnOp::ToInt, Value::Int(2)),
+            (Value::Float(-2.9), UnOp::ToInt, Value::Int(-2)),
+            (Value::Float(1e300), UnOp::ToInt, Value::Int(i64::MAX)),
+            (Value::Float(f64::NAN), UnOp::ToInt, Value::Int(0)),
+            (Value::Bool(true), UnOp::ToInt, Value::Int(1)),
+            (Value::ThreadId(2), UnOp::ToInt, Value::Int(2)),
+            (Value::Int(3), UnOp::ToFloat, Value::Float(3.0)),
+            (Value::Bool(false), UnOp::ToBool, Value::Bool(false)),
+        ];
+
+        for (val, op, exp) in cases {
+            let mut rt = Runtime::new(vec![]);
+            ldc(&mut rt, val).unwrap();
+            unop(&mut rt, op).unwrap();
+            assert_eq!(rt.current_thread.operand_stack.pop().unwrap(), exp);
+        }
+
+        let mut rt = Runtime::new(vec![]);
+        ldc(&mut rt, Value::Bool(true)).unwrap();
+        assert!(unop(&mut rt, UnOp::ToFloat).is_err());
+
+        let mut rt = Runtime::new(vec![]);
+        ldc(&mut rt, Value::String("1".into())).unwrap();
+        assert!(unop(&mut rt, UnOp::ToInt).is_err());
+    }
 }
@@ -1,5 +1,6 @@
 use anyhow::{Ok, Result};
 use bytecode::Semaphore;
+use log::debug;
 
 use crate::{Runtime, VmError};
 
@@ -10,7 +11,7 @@ use crate::{Runtime, VmError};
 ///   - The next ready thread is popped from the ready queue and set as the current thread.
 ///
 /// If the semaphore is greater than 0, the semaphore is decremented.
-/// The current thread continues execution.
+/// The current thread continues execution, holding the semaphore until it posts it.
 ///
 /// # Arguments
 ///
@@ -20,9 +21,10 @@ use crate::{Runtime, VmError};
 ///
 /// If the stack is empty.
 /// If the top value on stack is not a semaphore.
-/// If there are no threads in the ready queue when the current thread is blocked.
+/// If there are no threads in the ready queue when the current thread is blocked. The error names the
+/// thread that ended holding the semaphore, if one did.
 #[inline]
-pub fn wait(mut rt: Runtime) -> Result<Runtime> {
+pub fn wait(rt: &mut Runtime) -> Result<()> {
     let sem: Semaphore = rt
         .current_thread
         .operand_stack
@@ -35,21 +37,24 @@ pub fn wait(mut rt: Runtime) -> Result<Runtime> {
         *sem_guard -= 1;
         drop(sem_guard); //unlock the semaphore
 
-        Ok(rt)
+        rt.current_thread.acquire(sem);
+        Ok(())
     } else {
         drop(sem_guard); //unlock the semaphore
 
         // Move the current thread to the blocked queue and pop the next ready thread.
-        let current_thread = rt.current_thread;
-        rt.blocked_queue.push_back((current_thread, sem.clone()));
-
+        debug!(
+            "Thread {} blocked on a semaphore",
+            rt.current_thread.thread_id
+        );
         let next_ready_thread = rt
             .ready_queue
             .pop_front()
-            .ok_or(VmError::NoThreadsInReadyQueue)?;
+            .ok_or_else(|| rt.sem_deadlock(rt.current_thread.thread_id, &sem))?;
 
-        rt.current_thread = next_ready_thread;
-        Ok(rt)
+        let current_thread = rt.switch_to(next_ready_thread);
+        rt.blocked_queue.push_back((current_thread, sem));
+        Ok(())
     }
 }
 
@@ -68,10 +73,10 @@ mod tests {
         let mut rt = Runtime::default();
         let sem = Semaphore::new(1);
         let current_env = rt.current_thread.env.clone();
-        rt = extend_environment(rt, current_env, vec!["sem"], vec![sem.clone()])?;
-        rt = micro_code::spawn(rt, 0)?; // spawn a child thread to populate ready queue
-        rt = ld(rt, "sem".into())?;
-        rt = wait(rt)?;
+        extend_environment(&mut rt, current_env, vec!["sem"], vec![sem.clone()])?;
+        micro_code::spawn(&mut rt, 0, 0)?; // spawn a child thread to populate ready queue
+        ld(&mut rt, &"sem".into())?;
+        wait(&mut rt)?;
 
         assert_eq!(*sem.lock().unwrap(), 0);
         // Since the semaphore greater than 0, the semaphore should be decremented and the current thread should continue.
@@ -85,10 +90,10 @@ mod tests {
         let mut rt = Runtime::default();
         let sem = Semaphore::new(0);
         let current_env = rt.current_thread.env.clone();
-        rt = extend_environment(rt, current_env, vec!["sem"], vec![sem.clone()])?;
-        rt = micro_code::spawn(rt, 0)?; // spawn a child thread to populate ready queue
-        rt = ld(rt, "sem".into())?;
-        rt = wait(rt)?;
+        extend_environment(&mut rt, current_env, vec!["sem"], vec![sem.clone()])?;
+        micro_code::spawn(&mut rt, 0, 0)?; // spawn a child thread to populate ready queue
+        ld(&mut rt, &"sem".into())?;
+        wait(&mut rt)?;
 
         let child_thread_id = MAIN_THREAD_ID + 1;
         assert_eq!(*sem.lock().unwrap(), 0);
@@ -1,33 +1,26 @@
-use std::time::Instant;
-
 use anyhow::Result;
 
-use crate::{Runtime, VmError};
+use crate::{ready_now, Runtime};
 
 /// Yield the current thread in the runtime.
-/// Push the current thread to the back of the ready queue.
 /// Pop the next ready thread from the front of the ready queue and set it as the current thread.
+/// Push the current thread to the back of the ready queue.
+/// With no other thread ready, the current thread keeps running.
 ///
 /// # Arguments
 ///
 /// * `rt` - The runtime to yield the current thread in.
-///
-/// # Errors
-///
-/// Returns an error if there are no threads in the ready queue.
 #[inline]
-pub fn yield_(mut rt: Runtime) -> Result<Runtime> {
-    let current_thread = rt.current_thread;
+pub fn yield_(rt: &mut Runtime) -> Result<()> {
+    let Some(next_ready_thread) = rt.ready_queue.pop_front() else {
+        rt.account_cpu_time();
+        return Ok(());
+    };
+
+    let mut current_thread = rt.switch_to(next_ready_thread);
+    ready_now(&mut current_thread);
     rt.ready_queue.push_back(current_thread);
-
-    let next_ready_thread = rt
-        .ready_queue
-        .pop_front()
-        .ok_or(VmError::NoThreadsInReadyQueue)?;
-
-    rt.current_thread = next_ready_thread;
-    rt.time = Instant::now(); // Reset the time
-    Ok(rt)
+    Ok(())
 }
 
 #[cfg(test)]
@@ -39,8 +32,8 @@ mod tests {
     #[test]
     fn test_yield() -> Result<()> {
         let mut rt = Runtime::new(vec![]);
-        rt = spawn(rt, 1)?;
-        rt = yield_(rt)?;
+        spawn(&mut rt, 1, 0)?;
+        yield_(&mut rt)?;
 
         assert_eq!(rt.current_thread.thread_id, MAIN_THREAD_ID + 1);
 
@@ -1,13 +1,440 @@
+use std::{cell::RefCell, rc::Weak, time::Instant};
+
+use ::compiler::linker;
+use ::compiler::pipeline::{Incremental, Options};
 use anyhow::Result;
-use bytecode::builtin;
+use bytecode::{builtin, type_of, ByteCode, Environment, Symbol, Value};
 use compiler::compiler;
+use diagnostics::render;
+use lexer::{lex, Token};
+use parser::edition::Edition;
 use rustyline::DefaultEditor;
 
-use crate::{run, Runtime};
+use crate::{
+    extend_environment, run_in_place, runtime_diagnostic, Runtime, Thread, MAIN_THREAD_ID,
+};
+
+/// How deep calls and scopes can nest in the REPL unless --max-depth says otherwise, so runaway
+/// recursion gives an error instead of using up the memory of the session.
+pub const REPL_MAX_STACK_DEPTH: usize = 10_000;
+
+/// A line of REPL input: either a command starting with ':' or code to run
+#[derive(Debug, PartialEq)]
+enum ReplInput<'a> {
+    /// `:set typecheck on` / `:set typecheck off`
+    SetTypeCheck(bool),
+    /// `:save <file>`
+    Save(&'a str),
+    /// `:restore <file>`
+    Restore(&'a str),
+    /// `:env`, `:vars`, `:stack` or `:threads`, to look at the session's VM
+    Inspect(Inspect),
+    /// Code to compile and run, with whether it should be type checked
+    Code { src: &'a str, type_check: bool },
+}
+
+/// What an inspection command shows
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Inspect {
+    /// The environment chain of the main thread, innermost scope first. Between lines that is the
+    /// session's scope, holding the top level names of every line
+    Env,
+    /// The names in scope of the main thread and their types, as vars() lists them. Between lines
+    /// those are the names every line of the session declared
+    Vars,
+    /// The operand stack of the main thread, top first
+    Stack,
+    /// Every thread the scheduler knows about and its state
+    Threads,
+}
+
+/// Parse a trimmed line of REPL input. `type_check` is the current session setting.
+/// A line prefixed with `:notype` is run without type checking regardless of the setting.
+fn parse_input(inp: &str, type_check: bool) -> Result<ReplInput<'_>, String> {
+    if let Some(rest) = inp.strip_prefix(":notype") {
+        return Ok(ReplInput::Code {
+            src: rest.trim(),
+            type_check: false,
+        });
+    }
+
+    if let Some(rest) = inp.strip_prefix(":set") {
+        let opts: Vec<&str> = rest.split_whitespace().collect();
+        return match opts.as_slice() {
+            ["typecheck", "on"] => Ok(ReplInput::SetTypeCheck(true)),
+            ["typecheck", "off"] => Ok(ReplInput::SetTypeCheck(false)),
+            _ => Err(format!(
+                "Unknown setting '{}', expected ':set typecheck on|off'",
+                rest.trim()
+            )),
+        };
+    }
+
+    if let Some(rest) = inp.strip_prefix(":save") {
+        return file_arg(":save", rest).map(ReplInput::Save);
+    }
+
+    if let Some(rest) = inp.strip_prefix(":restore") {
+        return file_arg(":restore", rest).map(ReplInput::Restore);
+    }
+
+    match inp {
+        ":env" => return Ok(ReplInput::Inspect(Inspect::Env)),
+        ":vars" => return Ok(ReplInput::Inspect(Inspect::Vars)),
+        ":stack" => return Ok(ReplInput::Inspect(Inspect::Stack)),
+        ":threads" => return Ok(ReplInput::Inspect(Inspect::Threads)),
+        _ => (),
+    }
+
+    if inp.starts_with(':') {
+        return Err(format!("Unknown command '{}'", inp));
+    }
+
+    Ok(ReplInput::Code {
+        src: inp,
+        type_check,
+    })
+}
+
+/// File name argument of a :save or :restore command
+fn file_arg<'a>(cmd: &str, rest: &'a str) -> Result<&'a str, String> {
+    let file = rest.trim();
+    if file.is_empty() {
+        return Err(format!("Expected a file name after '{}'", cmd));
+    }
+    Ok(file)
+}
+
+/// Join the lines of a REPL session into a single compilable program.
+/// Lines are terminated with ';' where needed so that expressions still parse when followed
+/// by more code. The ';' goes after the line's last token, so before any trailing comment.
+fn session_source(lines: &[String]) -> String {
+    let mut out = String::new();
+
+    for line in lines {
+        let last = lex(line)
+            .spanned()
+            .filter_map(|(tok, span)| tok.ok().map(|tok| (tok, span.end)))
+            .last();
+        match last {
+            Some((tok, end)) if tok != Token::Semi && tok != Token::CloseBrace => {
+                out.push_str(&line[..end]);
+                out.push(';');
+                out.push_str(&line[end..]);
+            }
+            _ => out.push_str(line),
+        }
+        out.push('\n');
+    }
+
+    out
+}
+
+/// A REPL session: one VM that runs every line, with the top level names of all of them declared
+/// in one scope, so a line can use what the lines before it declared. Threads spawned by a line
+/// keep running in the lines after it.
+struct Session {
+    rt: Runtime,
+    /// The scope the lines' top level names are declared in, a child of the global scope
+    scope: Weak<RefCell<Environment>>,
+    /// Type checks each line after the top level of the lines that ran before it
+    compiler: Incremental,
+    /// Lines that ran successfully and declared top level names, written out by :save. Lines
+    /// declaring nothing, like `println(x);` or `x = 2;`, aren't kept.
+    lines: Vec<String>,
+    edition: Edition,
+    /// Whether a line has run, for the inspection commands
+    ran: bool,
+}
+
+impl Session {
+    /// Start a session. Floats are printed with float_precision, which set_float_precision in a line
+    /// changes for the rest of the session. Threads nesting more than max_depth calls and scopes stop
+    /// with a stack overflow error, so runaway recursion ends the line rather than the session.
+    fn new(
+        float_precision: Option<usize>,
+        edition: Edition,
+        echo_result: bool,
+        max_depth: usize,
+    ) -> Result<Session> {
+        let mut rt = Runtime::new(vec![]);
+        rt.float_precision = float_precision;
+        rt.set_echo_result(echo_result);
+        rt.set_max_stack_depth(max_depth);
+
+        let global = rt.current_thread.env.clone();
+        extend_environment(&mut rt, global, Vec::<Symbol>::new(), Vec::<Value>::new())?;
+        let scope = rt.current_thread.env.clone();
+
+        Ok(Session {
+            rt,
+            scope,
+            compiler: Incremental::default(),
+            lines: vec![],
+            edition,
+            ran: false,
+        })
+    }
+
+    /// Compile and run one piece of REPL input after the lines before it, printing the result, if
+    /// echo_result, or any errors. Returns whether it compiled and ran successfully, in which case
+    /// it is added to the session's lines if it declared any top level names.
+    fn run_line(&mut self, src: &str, type_check: bool) -> bool {
+        // lines run as they are entered, main isn't called after them
+        let options = Options {
+            typecheck: type_check,
+            edition: self.edition,
+            call_main: false,
+            ..Options::default()
+        };
+        let base = self.rt.instrs.len();
+        let line = self.compiler.compile(src, &options).and_then(|compiled| {
+            let (syms, instrs) = linker::unscoped("line", &compiled.bytecode, base)?;
+            Ok((compiled, syms, instrs))
+        });
+        let (compiled, syms, instrs) = match line {
+            Ok(line) => line,
+            Err(err) => {
+                eprintln!("{}", render(&compiler::diagnostics(&err)));
+                return false;
+            }
+        };
+
+        let declares = !syms.is_empty();
+        self.start_line(syms, instrs);
+        self.ran = true;
+        if let Err(err) = run_in_place(&mut self.rt) {
+            eprintln!("{}", runtime_diagnostic(&err));
+            self.end_failed_line();
+            return false;
+        }
+
+        if let Some(code) = self.rt.exit_code {
+            std::process::exit(code);
+        }
+        self.rt.print_result();
+
+        // A line run without type checking is only known to the type checker if it would have
+        // passed, so its errors don't fail every line after it
+        if type_check {
+            self.compiler.push(&compiled);
+        } else if let Ok(checked) = self.compiler.compile(
+            src,
+            &Options {
+                typecheck: true,
+                ..options
+            },
+        ) {
+            self.compiler.push(&checked);
+        }
+        if declares {
+            self.lines.push(src.trim().to_string());
+        }
+
+        true
+    }
+
+    /// Add a line's instructions to the end of the program and start the main thread on them, in
+    /// the session's scope with the line's new top level names declared. The instructions of the
+    /// lines before stay, for the functions and threads they created.
+    fn start_line(&mut self, syms: Vec<Symbol>, instrs: Vec<ByteCode>) {
+        // a name declared again keeps its value until the line assigns it, as in one program
+        if let Some(scope) = self.scope.upgrade() {
+            let mut scope = scope.borrow_mut();
+            for sym in syms {
+                if !scope.env.contains_key(&sym) {
+                    scope.set(sym, Value::Unitialized);
+                }
+            }
+        }
+
+        let pc = self.rt.instrs.len();
+        let mut program = self.rt.instrs.to_vec();
+        program.extend(instrs);
+        self.rt.instrs = program.into();
+
+        let main = &mut self.rt.current_thread;
+        main.pc = pc;
+        main.env = self.scope.clone();
+        main.operand_stack.clear();
+        main.runtime_stack.clear();
+        self.rt.done = false;
+        self.rt.time = Instant::now();
+    }
+
+    /// After a line failed, replace the main thread, which may have been anywhere in the line, with
+    /// a new one in the session's scope. If another thread failed the line, it is dropped.
+    fn end_failed_line(&mut self) {
+        let rt = &mut self.rt;
+        let main = if rt.current_thread.thread_id == MAIN_THREAD_ID {
+            None
+        } else if let Some(idx) = rt
+            .ready_queue
+            .iter()
+            .position(|t| t.thread_id == MAIN_THREAD_ID)
+        {
+            rt.ready_queue.remove(idx)
+        } else {
+            rt.blocked_queue
+                .iter()
+                .position(|(t, _)| t.thread_id == MAIN_THREAD_ID)
+                .and_then(|idx| rt.blocked_queue.remove(idx))
+                .map(|(t, _)| t)
+        };
+        if let Some(main) = main {
+            let failed = std::mem::replace(&mut rt.current_thread, main);
+            rt.recycle_thread(failed);
+        }
+
+        let main = rt.new_thread(MAIN_THREAD_ID, self.scope.clone(), rt.instrs.len());
+        let old_main = std::mem::replace(&mut rt.current_thread, main);
+        rt.recycle_thread(old_main);
+    }
+}
+
+/// Format rows as a table with a header, each column as wide as its widest cell
+fn table(header: &[&str], rows: &[Vec<String>]) -> String {
+    let mut widths: Vec<usize> = header.iter().map(|h| h.len()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    let line = |cells: Vec<&str>| {
+        let padded: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        padded.join("  ").trim_end().to_string()
+    };
+
+    let mut out = vec![line(header.to_vec())];
+    out.extend(
+        rows.iter()
+            .map(|row| line(row.iter().map(|c| c.as_str()).collect())),
+    );
+    out.join("\n")
+}
+
+/// What an inspection command prints for the session's runtime
+fn inspect(rt: &Runtime, what: Inspect) -> String {
+    match what {
+        Inspect::Env => inspect_env(rt),
+        Inspect::Vars => inspect_vars(rt),
+        Inspect::Stack => inspect_stack(rt),
+        Inspect::Threads => inspect_threads(rt),
+    }
+}
+
+/// The symbols of each scope of the main thread, innermost (0) first. The global scope only holds
+/// builtins and constants, so it is summarised rather than listed.
+fn inspect_env(rt: &Runtime) -> String {
+    let mut rows = vec![];
+    let mut globals = 0;
+    let mut env: Option<Weak<RefCell<Environment>>> = Some(rt.current_thread.env.clone());
+
+    let mut depth = 0;
+    while let Some(scope) = env.and_then(|env| env.upgrade()) {
+        let scope = scope.borrow();
+        if scope.parent.is_none() {
+            globals = scope.env.len();
+            break;
+        }
+
+        let mut syms: Vec<_> = scope.env.iter().collect();
+        syms.sort_by_key(|(sym, _)| *sym);
+        for (sym, val) in syms {
+            rows.push(vec![
+                depth.to_string(),
+                sym.to_string(),
+                type_of(val).to_string(),
+                val.display(rt.float_precision),
+            ]);
+        }
+
+        env = scope.parent.clone();
+        depth += 1;
+    }
+
+    let mut out = table(&["scope", "name", "type", "value"], &rows);
+    out.push_str(&format!(
+        "\nglobal: {} builtins and constants not shown",
+        globals
+    ));
+    out
+}
+
+/// The names in scope of the main thread and their types. Unlike :env, shadowed names are left out.
+fn inspect_vars(rt: &Runtime) -> String {
+    let rows: Vec<Vec<String>> = builtin::bindings(&rt.current_thread.env)
+        .into_iter()
+        .map(|(sym, ty)| vec![sym, ty.to_string()])
+        .collect();
+
+    table(&["name", "type"], &rows)
+}
 
-pub fn ignite_repl(type_check: bool) -> Result<()> {
+/// The operand stack of the main thread, top first
+fn inspect_stack(rt: &Runtime) -> String {
+    let rows: Vec<Vec<String>> = rt
+        .current_thread
+        .operand_stack
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(idx, val)| {
+            vec![
+                idx.to_string(),
+                type_of(val).to_string(),
+                val.display(rt.float_precision),
+            ]
+        })
+        .collect();
+
+    table(&["#", "type", "value"], &rows)
+}
+
+/// Every thread the scheduler holds, by thread ID
+fn inspect_threads(rt: &Runtime) -> String {
+    let mut threads: Vec<(&Thread, &str)> = vec![(&rt.current_thread, "running")];
+    threads.extend(rt.ready_queue.iter().map(|t| (t, "ready")));
+    threads.extend(rt.blocked_queue.iter().map(|(t, _)| (t, "blocked")));
+    threads.extend(rt.zombie_threads.values().map(|t| (t, "finished")));
+    threads.sort_by_key(|(t, _)| t.thread_id);
+
+    let rows: Vec<Vec<String>> = threads
+        .into_iter()
+        .map(|(t, state)| {
+            vec![
+                t.thread_id.to_string(),
+                state.to_string(),
+                t.pc.to_string(),
+                t.instr_count.to_string(),
+                t.operand_stack.len().to_string(),
+                t.runtime_stack.len().to_string(),
+            ]
+        })
+        .collect();
+
+    table(&["tid", "state", "pc", "instrs", "stack", "frames"], &rows)
+}
+
+pub fn ignite_repl(
+    type_check: bool,
+    float_precision: Option<usize>,
+    edition: Edition,
+    echo_result: bool,
+    max_depth: usize,
+) -> Result<()> {
+    let mut type_check = type_check;
+    let mut session = Session::new(float_precision, edition, echo_result, max_depth)?;
     let mut rl = DefaultEditor::new().unwrap();
     println!("Welcome to the RustScript REPL! Type /exit to exit.");
+    println!("Use ':set typecheck on|off' to toggle type checking, or prefix a line with ':notype' to skip it once.");
+    println!("Use ':save <file>' to write the session's declarations to a .rst file and ':restore <file>' to replay one.");
+    println!("Use ':env', ':vars', ':stack' and ':threads' to look at the session's VM.");
     println!();
 
     loop {
@@ -27,42 +454,300 @@ pub fn ignite_repl(type_check: bool) -> Result<()> {
 
             rl.add_history_entry(inp.clone().trim()).unwrap();
 
-            let compiled = compiler::compile_from_string(&inp, type_check);
-            match compiled {
-                Ok(_) => (),
-                Err(err) => {
-                    println!("{}", err);
-                    continue;
+            match parse_input(&inp, type_check) {
+                Ok(ReplInput::SetTypeCheck(on)) => {
+                    type_check = on;
+                    println!("Type checking {}", if on { "on" } else { "off" });
+                }
+                Ok(ReplInput::Save(file)) => {
+                    match std::fs::write(file, session_source(&session.lines)) {
+                        Ok(_) => println!("Saved {} line(s) to {}", session.lines.len(), file),
+                        Err(err) => eprintln!("Could not save session to {}: {}", file, err),
+                    }
                 }
+                Ok(ReplInput::Restore(file)) => match std::fs::read_to_string(file) {
+                    Ok(src) => {
+                        session.run_line(&src, type_check);
+                    }
+                    Err(err) => eprintln!("Could not restore session from {}: {}", file, err),
+                },
+                Ok(ReplInput::Inspect(what)) => {
+                    if session.ran {
+                        println!("{}", inspect(&session.rt, what));
+                    } else {
+                        eprintln!("Nothing has run yet");
+                    }
+                }
+                Ok(ReplInput::Code { src, type_check }) => {
+                    if src.is_empty() {
+                        continue;
+                    }
+                    session.run_line(src, type_check);
+                }
+                Err(err) => eprintln!("{}", err),
             }
+        }
+    }
 
-            let compiled = compiled.unwrap();
+    Ok(())
+}
 
-            // For now, make a new Runtime for each line
-            // Later: try to introduce global state
-            // dbg!(&compiled);
+#[cfg(test)]
+mod tests {
+    use super::{
+        inspect, parse_input, session_source, Inspect, ReplInput, Session, REPL_MAX_STACK_DEPTH,
+    };
+    use bytecode::Value;
+    use compiler::compiler::compile_from_string;
+    use parser::edition::Edition;
 
-            let mut rt = Runtime::new(compiled);
-            let run_res = run(rt);
+    fn new_session() -> Session {
+        Session::new(None, Edition::default(), false, REPL_MAX_STACK_DEPTH)
+            .expect("Should start a session")
+    }
 
-            match run_res {
-                Ok(_) => (),
-                Err(err) => {
-                    println!("[RuntimeError]: {}", err);
-                    continue;
-                }
-            }
+    /// Run src as the first line of a session
+    fn run_repl(src: &str) -> Session {
+        let mut session = new_session();
+        assert!(session.run_line(src, true), "Should run: {}", src);
+        session
+    }
 
-            rt = run_res.unwrap();
+    /// The value the last line of the session left
+    fn result(session: &Session) -> Option<&Value> {
+        session.rt.current_thread.operand_stack.last()
+    }
 
-            let top = rt.current_thread.operand_stack.last();
-            dbg!(rt.current_thread.operand_stack.len());
+    #[test]
+    fn test_repl_parse_input() {
+        assert_eq!(
+            parse_input("2 + 3", true),
+            Ok(ReplInput::Code {
+                src: "2 + 3",
+                type_check: true
+            })
+        );
+        assert_eq!(
+            parse_input("2 + 3", false),
+            Ok(ReplInput::Code {
+                src: "2 + 3",
+                type_check: false
+            })
+        );
 
-            if let Some(val) = top {
-                builtin::println_impl(val);
-            }
-        }
+        // :notype overrides the session setting for one line
+        assert_eq!(
+            parse_input(":notype 2 + true", true),
+            Ok(ReplInput::Code {
+                src: "2 + true",
+                type_check: false
+            })
+        );
+
+        assert_eq!(
+            parse_input(":set typecheck off", true),
+            Ok(ReplInput::SetTypeCheck(false))
+        );
+        assert_eq!(
+            parse_input(":set  typecheck   on", false),
+            Ok(ReplInput::SetTypeCheck(true))
+        );
+
+        assert!(parse_input(":set typecheck maybe", true)
+            .unwrap_err()
+            .contains("Unknown setting 'typecheck maybe'"));
+        assert_eq!(
+            parse_input(":save session.rst", true),
+            Ok(ReplInput::Save("session.rst"))
+        );
+        assert_eq!(
+            parse_input(":restore  session.rst ", true),
+            Ok(ReplInput::Restore("session.rst"))
+        );
+        assert!(parse_input(":save", true)
+            .unwrap_err()
+            .contains("Expected a file name after ':save'"));
+
+        assert!(parse_input(":foo", true)
+            .unwrap_err()
+            .contains("Unknown command ':foo'"));
+
+        assert_eq!(
+            parse_input(":threads", true),
+            Ok(ReplInput::Inspect(Inspect::Threads))
+        );
+        assert_eq!(
+            parse_input(":vars", true),
+            Ok(ReplInput::Inspect(Inspect::Vars))
+        );
+        assert!(parse_input(":env x", true).is_err());
     }
 
-    Ok(())
+    #[test]
+    fn test_repl_runaway_recursion() {
+        let mut session = run_repl("let x = 2;");
+        assert!(session.run_line("fn f(n: int) -> int { 1 + f(n + 1) }", true));
+
+        // the line fails with a stack overflow, and the session goes on
+        assert!(!session.run_line("f(0)", true));
+        assert!(session.run_line("x", true));
+        assert_eq!(result(&session), Some(&Value::Int(2)));
+        // only the lines declaring names are kept for :save
+        assert_eq!(
+            session.lines,
+            ["let x = 2;", "fn f(n: int) -> int { 1 + f(n + 1) }"]
+        );
+    }
+
+    #[test]
+    fn test_repl_session() {
+        let mut session = new_session();
+        let mut line = |src: &str| {
+            let ok = session.run_line(src, true);
+            (ok, result(&session).cloned())
+        };
+
+        // names, functions and closures of earlier lines
+        assert!(line("let x = 2;").0);
+        assert!(line("fn f(y: int) -> int { x + y }").0);
+        assert!(line("x = 5;").0);
+        assert_eq!(line("f(1)"), (true, Some(Value::Int(6))));
+        assert!(
+            line("fn adder(n: int) -> fn(int) -> int { fn add(m: int) -> int { n + m + x } add }")
+                .0
+        );
+        assert!(line("let g = adder(1);").0);
+        assert_eq!(line("g(3)"), (true, Some(Value::Int(9))));
+        // an earlier line's function with a builtin's name is called as declared
+        assert!(line("fn dbg(y: int) -> int { y * 2 }").0);
+        assert_eq!(line("dbg(3)"), (true, Some(Value::Int(6))));
+
+        // checked against the types of earlier lines
+        assert!(!line("x + true").0);
+        assert!(!line("h()").0);
+        // a name declared again keeps its value until assigned, as in one program
+        assert_eq!(line("let x = x + 1; x"), (true, Some(Value::Int(6))));
+
+        // a line failing at runtime ends, and the session goes on
+        assert!(!line("let z = x / 0;").0);
+        assert_eq!(line("x"), (true, Some(Value::Int(6))));
+
+        // threads spawned in a line can be joined in a later one
+        assert!(line("let t = spawn f(10);").0);
+        assert_eq!(line("join t"), (true, Some(Value::Int(16))));
+
+        // lines run without type checking are checked after if they can be
+        assert!(session.run_line("let w = 3;", false));
+        assert!(session.run_line("w + x", true));
+        assert!(session.run_line("let bad : int = 1.5;", false));
+        assert!(session.run_line("x", true));
+    }
+
+    #[test]
+    fn test_repl_restore() {
+        // a restored file runs as one line, its names stay for the lines after it
+        let saved = session_source(&["let x = 2;".to_string(), "fn f() -> int { x }".to_string()]);
+        let mut session = run_repl(&saved);
+        assert!(session.run_line("f() + x", true));
+        assert_eq!(result(&session), Some(&Value::Int(4)));
+        assert_eq!(
+            inspect(&session.rt, Inspect::Vars),
+            "name  type\nf     Closure\nx     Int"
+        );
+    }
+
+    #[test]
+    fn test_repl_inspect() {
+        // the line's top level names are still in scope, the block's are gone
+        let mut session = run_repl("let x = 2; let s = \"hi\"; { let y = 1.5; } x");
+        let env = inspect(&session.rt, Inspect::Env);
+        let lines: Vec<&str> = env.lines().collect();
+        assert_eq!(lines[0], "scope  name  type    value");
+        assert_eq!(lines[1], "0      s     String  hi");
+        assert_eq!(lines[2], "0      x     Int     2");
+        assert!(lines[3].starts_with("global: "));
+        assert_eq!(lines.len(), 4);
+
+        assert_eq!(
+            inspect(&session.rt, Inspect::Stack),
+            "#  type  value\n0  Int   2"
+        );
+        assert_eq!(
+            inspect(&session.rt, Inspect::Vars),
+            "name  type\ns     String\nx     Int"
+        );
+
+        // the names of every line of the session, not just the last
+        assert!(session.run_line("let b = true; x = 3;", true));
+        assert_eq!(
+            inspect(&session.rt, Inspect::Env)
+                .lines()
+                .collect::<Vec<_>>()[1..4],
+            [
+                "0      b     Bool    true",
+                "0      s     String  hi",
+                "0      x     Int     3"
+            ]
+        );
+        assert_eq!(
+            inspect(&session.rt, Inspect::Vars),
+            "name  type\nb     Bool\ns     String\nx     Int"
+        );
+
+        // a thread blocked on a semaphore and one that finished
+        let src = r"
+        let s = sem_create(0);
+        fn f() { wait s; }
+        fn g() -> float { 0.5 }
+        spawn f();
+        spawn g();
+        yield;
+        ";
+        let session = run_repl(src);
+        let threads = inspect(&session.rt, Inspect::Threads);
+        let states: Vec<Vec<&str>> = threads
+            .lines()
+            .map(|line| line.split_whitespace().take(2).collect())
+            .collect();
+        assert_eq!(
+            states,
+            vec![
+                vec!["tid", "state"],
+                vec!["1", "running"],
+                vec!["2", "blocked"],
+                vec!["3", "finished"],
+            ]
+        );
+
+        // without top level names there is only the global scope
+        let session = run_repl("1 + 1");
+        assert!(
+            inspect(&session.rt, Inspect::Env).starts_with("scope  name  type  value\nglobal: ")
+        );
+    }
+
+    #[test]
+    fn test_repl_session_source() {
+        let lines: Vec<String> = [
+            "let x = 2;",
+            "let y = x + 3",
+            "fn f() -> int { 2 }",
+            "let z = f() // note",
+            "let s = \"}\"; // c",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+
+        let src = session_source(&lines);
+        assert_eq!(
+            src,
+            "let x = 2;\nlet y = x + 3;\nfn f() -> int { 2 }\nlet z = f(); // note\nlet s = \"}\"; // c\n"
+        );
+
+        // saved session should be compilable as is
+        compile_from_string(&src, true).expect("Saved session should compile");
+
+        assert_eq!(session_source(&[]), "");
+    }
 }
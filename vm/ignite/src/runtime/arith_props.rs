@@ -0,0 +1,110 @@
+//! Property tests compiling and running generated arithmetic against a reference evaluator.
+//!
+//! Expressions are printed with only the parentheses precedence and associativity need, so a
+//! precedence bug anywhere between the parser and the VM gives a different value than evaluating
+//! the generated tree directly. Overflow and division by zero must be runtime errors in the VM
+//! exactly when the reference evaluation fails.
+
+use compiler::compiler::compile_from_string;
+use proptest::prelude::*;
+
+use bytecode::Value;
+
+use crate::{run, Runtime};
+
+#[derive(Debug, Clone)]
+enum Arith {
+    Int(i64),
+    Neg(Box<Arith>),
+    Bin(char, Box<Arith>, Box<Arith>),
+}
+
+impl Arith {
+    /// Reference value with the VM's int semantics: division truncates and overflow is an error
+    fn eval(&self) -> Option<i64> {
+        match self {
+            Arith::Int(val) => Some(*val),
+            Arith::Neg(operand) => operand.eval()?.checked_neg(),
+            Arith::Bin(op, lhs, rhs) => {
+                let (lhs, rhs) = (lhs.eval()?, rhs.eval()?);
+                match op {
+                    '+' => lhs.checked_add(rhs),
+                    '-' => lhs.checked_sub(rhs),
+                    '*' => lhs.checked_mul(rhs),
+                    _ => lhs.checked_div(rhs),
+                }
+            }
+        }
+    }
+
+    fn prec(&self) -> u8 {
+        match self {
+            Arith::Bin('+' | '-', _, _) => 1,
+            Arith::Bin(_, _, _) => 2,
+            Arith::Neg(_) => 3,
+            Arith::Int(_) => 4,
+        }
+    }
+
+    /// Source with the fewest parentheses that keep the meaning: binary operators are left associative
+    fn source(&self) -> String {
+        let wrap = |child: &Arith, parens: bool| {
+            if parens {
+                format!("({})", child.source())
+            } else {
+                child.source()
+            }
+        };
+
+        match self {
+            Arith::Int(val) => val.to_string(),
+            Arith::Neg(operand) => format!("-{}", wrap(operand, operand.prec() < 3)),
+            Arith::Bin(op, lhs, rhs) => format!(
+                "{} {} {}",
+                wrap(lhs, lhs.prec() < self.prec()),
+                op,
+                wrap(rhs, rhs.prec() <= self.prec())
+            ),
+        }
+    }
+}
+
+fn arith() -> impl Strategy<Value = Arith> {
+    let leaf = prop_oneof![
+        10 => (0..20i64).prop_map(Arith::Int),
+        1 => Just(Arith::Int(i64::MAX)),
+    ];
+
+    leaf.prop_recursive(5, 32, 2, |inner| {
+        prop_oneof![
+            inner.clone().prop_map(|e| Arith::Neg(Box::new(e))),
+            (
+                prop::sample::select(vec!['+', '-', '*', '/']),
+                inner.clone(),
+                inner
+            )
+                .prop_map(|(op, lhs, rhs)| Arith::Bin(
+                    op,
+                    Box::new(lhs),
+                    Box::new(rhs)
+                )),
+        ]
+    })
+}
+
+proptest! {
+    #[test]
+    fn prop_arith_matches_reference(expr in arith()) {
+        let src = expr.source();
+        let instrs = compile_from_string(&src, true).expect("Should compile");
+        let res = run(Runtime::new(instrs));
+
+        match expr.eval() {
+            Some(exp) => {
+                let rt = res.unwrap_or_else(|err| panic!("'{}' should run: {}", src, err));
+                prop_assert_eq!(rt.current_thread.operand_stack.last(), Some(&Value::Int(exp)), "{}", src);
+            }
+            None => prop_assert!(res.is_err(), "'{}' should fail", src),
+        }
+    }
+}
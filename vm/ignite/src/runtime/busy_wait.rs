@@ -0,0 +1,194 @@
+use bytecode::ByteCode;
+use diagnostics::{Diagnostic, Stage, BUSY_WAIT};
+
+use crate::Runtime;
+
+/// Longest loop, in instructions, that counts as a busy wait. Loops doing real work are longer.
+pub const BUSY_WAIT_MAX_LOOP_LEN: usize = 32;
+/// Number of timer checks in a row a thread must be found in the same loop before it is busy waiting.
+/// Timer checks are TIMER_CHECK_INTERVAL instructions apart.
+pub const BUSY_WAIT_SAMPLES: usize = 64;
+
+/// A loop the current thread was found in at the last timer checks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpinWindow {
+    thread_id: i64,
+    /// Address of the first and last instruction of the loop.
+    start: usize,
+    end: usize,
+    /// Whether the loop only reads, see is_read_only.
+    read_only: bool,
+    /// Number of timer checks in a row the thread was in the loop.
+    samples: usize,
+}
+
+impl Runtime {
+    /// Check whether the current thread is busy waiting: spinning in a short loop that only reads
+    /// variables, like `loop { if flag { break; } }`, which only another thread can end. Called at
+    /// each timer check, so it costs nothing between them.
+    ///
+    /// A busy waiting thread should yield so the thread it is waiting for can run. The first time a
+    /// loop is found busy waiting, a warning suggesting wait and post is printed to stderr.
+    pub fn busy_waiting(&mut self) -> bool {
+        // with no other thread ready, yielding can't help
+        if !self.detect_busy_wait || self.ready_queue.is_empty() {
+            self.spin = None;
+            return false;
+        }
+
+        let thread_id = self.current_thread.thread_id;
+        let pc = self.current_thread.pc;
+        let window = match self.spin {
+            Some(mut window)
+                if window.thread_id == thread_id && (window.start..=window.end).contains(&pc) =>
+            {
+                window.samples += 1;
+                window
+            }
+            _ => match short_loop(&self.instrs, pc) {
+                Some((start, end)) => SpinWindow {
+                    thread_id,
+                    start,
+                    end,
+                    read_only: is_read_only(&self.instrs[start..=end]),
+                    samples: 1,
+                },
+                None => {
+                    self.spin = None;
+                    return false;
+                }
+            },
+        };
+
+        // a loop that does work is remembered too, so it isn't looked at again every timer check
+        if !window.read_only || window.samples < BUSY_WAIT_SAMPLES {
+            self.spin = Some(window);
+            return false;
+        }
+
+        self.spin = None;
+        if self.busy_wait_warned.insert(window.start) {
+            self.warn_busy_wait(thread_id, window.start);
+        }
+        true
+    }
+
+    fn warn_busy_wait(&self, thread_id: i64, pc: usize) {
+        let at = self
+            .source_location(pc)
+            .unwrap_or_else(|| format!("instruction {}", pc));
+        let msg = format!(
+            "Thread {} is busy waiting in the loop at {}, so it yields to other threads each time it is found spinning. Wait on a semaphore the other thread posts instead",
+            thread_id, at
+        );
+        eprintln!("{}", Diagnostic::warning(Stage::Runtime, BUSY_WAIT, &msg));
+    }
+}
+
+/// The innermost loop containing pc, as the addresses of its first and last instruction, if it is
+/// at most BUSY_WAIT_MAX_LOOP_LEN long. A loop ends in a GOTO back to its start.
+fn short_loop(instrs: &[ByteCode], pc: usize) -> Option<(usize, usize)> {
+    let (end, start) = instrs
+        .iter()
+        .enumerate()
+        .skip(pc)
+        .take(BUSY_WAIT_MAX_LOOP_LEN)
+        .find_map(|(addr, instr)| match instr {
+            ByteCode::GOTO(target) if *target <= pc => Some((addr, *target)),
+            _ => None,
+        })?;
+
+    (end - start < BUSY_WAIT_MAX_LOOP_LEN).then_some((start, end))
+}
+
+/// Whether a loop only reads: nothing in it can change a variable, call a function or block, so
+/// unless its condition is already false, only another thread can end it.
+fn is_read_only(body: &[ByteCode]) -> bool {
+    body.iter().all(|instr| {
+        matches!(
+            instr,
+            ByteCode::LD(_)
+                | ByteCode::LDC(_)
+                | ByteCode::LDLDCBINOP(_, _, _)
+                | ByteCode::POP
+                | ByteCode::UNOP(_)
+                | ByteCode::BINOP(_)
+                | ByteCode::JOF(_)
+                | ByteCode::GOTO(_)
+                | ByteCode::ENTERSCOPE(_)
+                | ByteCode::ENTERSCOPELOCAL(_)
+                | ByteCode::EXITSCOPE
+                | ByteCode::EXITSCOPELOCAL
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use bytecode::{BinOp, ByteCode};
+
+    use super::{is_read_only, short_loop, BUSY_WAIT_SAMPLES};
+    use crate::{micro_code, Runtime};
+
+    // loop !flag {}, then the value of flag
+    fn spin_loop() -> Vec<ByteCode> {
+        vec![
+            ByteCode::ld("flag"),
+            ByteCode::UNOP(bytecode::UnOp::Not),
+            ByteCode::JOF(4),
+            ByteCode::GOTO(0),
+            ByteCode::ld("flag"),
+            ByteCode::DONE,
+        ]
+    }
+
+    #[test]
+    fn test_short_loop() {
+        let instrs = spin_loop();
+        assert_eq!(short_loop(&instrs, 0), Some((0, 3)));
+        assert_eq!(short_loop(&instrs, 3), Some((0, 3)));
+        // after the loop
+        assert_eq!(short_loop(&instrs, 4), None);
+        assert!(is_read_only(&instrs[0..=3]));
+
+        // a loop that assigns does work of its own
+        let instrs = vec![
+            ByteCode::ld("i"),
+            ByteCode::ldc(1),
+            ByteCode::BINOP(BinOp::Add),
+            ByteCode::assign("i"),
+            ByteCode::GOTO(0),
+        ];
+        assert_eq!(short_loop(&instrs, 2), Some((0, 4)));
+        assert!(!is_read_only(&instrs));
+    }
+
+    #[test]
+    fn test_busy_waiting() -> Result<()> {
+        let mut rt = Runtime::new(spin_loop());
+        rt.current_thread.pc = 1;
+
+        // alone, there is no one to yield to
+        for _ in 0..BUSY_WAIT_SAMPLES {
+            assert!(!rt.busy_waiting());
+        }
+
+        micro_code::spawn(&mut rt, 0, 0)?;
+        for _ in 1..BUSY_WAIT_SAMPLES {
+            assert!(!rt.busy_waiting());
+        }
+        assert!(rt.busy_waiting());
+        assert!(rt.busy_wait_warned.contains(&0));
+
+        // found again from scratch
+        assert!(!rt.busy_waiting());
+
+        rt.detect_busy_wait = false;
+        for _ in 0..BUSY_WAIT_SAMPLES {
+            assert!(!rt.busy_waiting());
+        }
+
+        Ok(())
+    }
+}
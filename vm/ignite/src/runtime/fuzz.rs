@@ -0,0 +1,288 @@
+//! Instruction-level fuzzing of the VM.
+//!
+//! Generates random but structurally valid programs: every jump and function address is inside the
+//! program and every instruction is well formed, but nothing else is guaranteed. Loads are of a few
+//! variables or of the builtin functions, so calls run builtins with random arguments too. Operands may have
+//! the wrong types, the stacks may underflow and scopes may not be balanced. The VM must reject such
+//! programs with an error rather than panic.
+//!
+//! Each program is generated from a seed, so a failure can be reproduced from the seed it reports.
+//! The number of programs defaults to FUZZ_RUNS and can be raised with the IGNITE_FUZZ_RUNS
+//! environment variable for longer fuzzing sessions.
+
+use std::panic::{self, AssertUnwindSafe};
+use std::time::Duration;
+
+use bytecode::{builtin, BinOp, ByteCode, FrameType, SelectOp, UnOp, Value};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::{run, Runtime};
+
+const FUZZ_RUNS: u64 = 5000;
+const MAX_PROGRAM_LEN: usize = 40;
+
+const SYMS: [&str; 3] = ["x", "y", "z"];
+
+// Builtins loaded so CALL reaches apply_builtin, all but those reading stdin or running commands
+const BUILTINS: [&str; 71] = [
+    builtin::ABS_SYM,
+    builtin::COS_SYM,
+    builtin::SIN_SYM,
+    builtin::TAN_SYM,
+    builtin::LOG_SYM,
+    builtin::POW_SYM,
+    builtin::SQRT_SYM,
+    builtin::MAX_SYM,
+    builtin::MIN_SYM,
+    builtin::FLOOR_SYM,
+    builtin::CEIL_SYM,
+    builtin::ROUND_SYM,
+    builtin::TRUNC_SYM,
+    builtin::EXP_SYM,
+    builtin::LOG2_SYM,
+    builtin::LOG10_SYM,
+    builtin::ATAN2_SYM,
+    builtin::HYPOT_SYM,
+    builtin::CLAMP_SYM,
+    builtin::DIV_SYM,
+    builtin::FDIV_SYM,
+    builtin::IS_NAN_SYM,
+    builtin::IS_FINITE_SYM,
+    builtin::STRING_LEN_SYM,
+    builtin::INT_TO_FLOAT_SYM,
+    builtin::FLOAT_TO_INT_SYM,
+    builtin::ATOI_SYM,
+    builtin::ITOA_SYM,
+    builtin::PARSE_INT_SYM,
+    builtin::PARSE_INT_RADIX_SYM,
+    builtin::PARSE_FLOAT_SYM,
+    builtin::PARSE_FAILED_SYM,
+    builtin::CLONE_SYM,
+    builtin::TYPE_OF_SYM,
+    builtin::VARS_SYM,
+    builtin::DBG_SYM,
+    builtin::JSON_STRINGIFY_SYM,
+    builtin::JSON_GET_SYM,
+    builtin::TO_FIXED_SYM,
+    builtin::FORMAT_INT_SYM,
+    builtin::SET_FLOAT_PRECISION_SYM,
+    builtin::PRINT_SYM,
+    builtin::PRINTLN_SYM,
+    builtin::EPRINT_SYM,
+    builtin::EPRINTLN_SYM,
+    builtin::SEM_CREATE_SYM,
+    builtin::SEM_SET_SYM,
+    builtin::SEM_VALUE_SYM,
+    builtin::QUEUE_CREATE_SYM,
+    builtin::QUEUE_PUSH_SYM,
+    builtin::QUEUE_POP_SYM,
+    builtin::QUEUE_CREATE_BOUNDED_SYM,
+    builtin::QUEUE_TRY_PUSH_SYM,
+    builtin::QUEUE_TRY_POP_SYM,
+    builtin::MAP_SYM,
+    builtin::FILTER_SYM,
+    builtin::REDUCE_SYM,
+    builtin::MATRIX_SYM,
+    builtin::MAT_GET_SYM,
+    builtin::MAT_SET_SYM,
+    builtin::MAT_ROWS_SYM,
+    builtin::MAT_COLS_SYM,
+    builtin::MATMUL_SYM,
+    builtin::JOIN_TIMEOUT_SYM,
+    builtin::IS_ALIVE_SYM,
+    builtin::DETACH_SYM,
+    builtin::CANCEL_SYM,
+    builtin::CURRENT_TID_SYM,
+    builtin::THREAD_COUNT_SYM,
+    builtin::SCHED_STATS_SYM,
+    builtin::EXIT_SYM,
+];
+
+fn gen_sym(rng: &mut StdRng) -> String {
+    SYMS[rng.gen_range(0..SYMS.len())].to_string()
+}
+
+/// A variable, or sometimes a builtin function so calls can reach every builtin
+fn gen_ld_sym(rng: &mut StdRng) -> String {
+    if rng.gen_range(0..3) == 0 {
+        BUILTINS[rng.gen_range(0..BUILTINS.len())].to_string()
+    } else {
+        gen_sym(rng)
+    }
+}
+
+fn gen_syms(rng: &mut StdRng) -> Vec<String> {
+    (0..rng.gen_range(0..=SYMS.len()))
+        .map(|_| gen_sym(rng))
+        .collect()
+}
+
+fn gen_value(rng: &mut StdRng) -> Value {
+    match rng.gen_range(0..7) {
+        0 => Value::Unit,
+        1 => Value::Int(rng.gen_range(-3..=3)),
+        2 => Value::Int(*[i64::MIN, i64::MAX, -1].get(rng.gen_range(0..3)).unwrap()),
+        3 => Value::Float(
+            *[0.0, -1.5, f64::NAN, f64::INFINITY, 1e300]
+                .get(rng.gen_range(0..5))
+                .unwrap(),
+        ),
+        4 => Value::Bool(rng.gen()),
+        5 => Value::String(["", "a", "bc"][rng.gen_range(0..3)].to_string()),
+        _ => Value::Unitialized,
+    }
+}
+
+fn gen_binop(rng: &mut StdRng) -> BinOp {
+    [
+        BinOp::Add,
+        BinOp::Sub,
+        BinOp::Mul,
+        BinOp::Div,
+        BinOp::Mod,
+        BinOp::Gt,
+        BinOp::Lt,
+        BinOp::Eq,
+        BinOp::And,
+        BinOp::Or,
+        BinOp::Ge,
+        BinOp::Le,
+    ][rng.gen_range(0..12)]
+    .clone()
+}
+
+fn gen_unop(rng: &mut StdRng) -> UnOp {
+    [
+        UnOp::Neg,
+        UnOp::Not,
+        UnOp::ToInt,
+        UnOp::ToFloat,
+        UnOp::ToBool,
+    ][rng.gen_range(0..5)]
+    .clone()
+}
+
+fn gen_frame_type(rng: &mut StdRng) -> FrameType {
+    if rng.gen() {
+        FrameType::BlockFrame
+    } else {
+        FrameType::CallFrame
+    }
+}
+
+/// A random instruction for a program of len instructions. Addresses may point one past the end.
+fn gen_instr(rng: &mut StdRng, len: usize) -> ByteCode {
+    let addr = rng.gen_range(0..=len);
+    match rng.gen_range(0..25) {
+        0 => ByteCode::DONE,
+        1 => ByteCode::ASSIGN(gen_sym(rng)),
+        2 | 3 => ByteCode::LD(gen_ld_sym(rng)),
+        4..=6 => ByteCode::LDC(gen_value(rng)),
+        7 => ByteCode::LDF(addr, gen_syms(rng)),
+        8 => ByteCode::POP,
+        9 => ByteCode::UNOP(gen_unop(rng)),
+        10 | 11 => ByteCode::BINOP(gen_binop(rng)),
+        12 => ByteCode::JOF(addr),
+        13 => ByteCode::GOTO(addr),
+        14 => ByteCode::RESET(gen_frame_type(rng)),
+        15 => match rng.gen_range(0..2) {
+            0 => ByteCode::ENTERSCOPE(gen_syms(rng)),
+            _ => ByteCode::ENTERSCOPELOCAL(gen_syms(rng)),
+        },
+        16 => match rng.gen_range(0..2) {
+            0 => ByteCode::EXITSCOPE,
+            _ => ByteCode::EXITSCOPELOCAL,
+        },
+        17 => ByteCode::CALL(rng.gen_range(0..3)),
+        18 => {
+            let moved = rng.gen_range(0..3);
+            match rng.gen_range(0..3) {
+                0 => ByteCode::SPAWN(addr, moved),
+                1 => ByteCode::SPAWNISOLATED(addr, moved),
+                _ => ByteCode::SPAWNLIMITED(addr, moved),
+            }
+        }
+        19 => match rng.gen_range(0..3) {
+            0 => ByteCode::ENTERTHREADSCOPE,
+            1 => ByteCode::JOINTHREADSCOPE,
+            _ => ByteCode::JOIN,
+        },
+        20 => match rng.gen_range(0..2) {
+            0 => ByteCode::YIELD,
+            _ => {
+                let ops = (0..rng.gen_range(0..3))
+                    .map(|_| match rng.gen_range(0..2) {
+                        0 => SelectOp::Pop,
+                        _ => SelectOp::Push,
+                    })
+                    .collect();
+                ByteCode::SELECT(ops, rng.gen())
+            }
+        },
+        21 => ByteCode::SEMCREATE,
+        22 => {
+            if rng.gen() {
+                ByteCode::WAIT
+            } else {
+                ByteCode::POST
+            }
+        }
+        23 => ByteCode::INC(gen_sym(rng), gen_value(rng)),
+        _ => ByteCode::LDLDCBINOP(gen_sym(rng), gen_value(rng), gen_binop(rng)),
+    }
+}
+
+/// A random program, always ending in DONE so running off the end is not the only way out
+pub fn gen_program(seed: u64) -> Vec<ByteCode> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let len = rng.gen_range(1..=MAX_PROGRAM_LEN);
+    let mut instrs: Vec<ByteCode> = (0..len - 1).map(|_| gen_instr(&mut rng, len)).collect();
+    instrs.push(ByteCode::DONE);
+    instrs
+}
+
+/// Run the program with tight resource limits, so loops and runaway recursion end with an error
+fn run_bounded(instrs: Vec<ByteCode>) {
+    let mut rt = Runtime::new(instrs);
+    rt.set_max_instr(5_000);
+    rt.set_timeout(Duration::from_secs(1));
+    rt.set_max_envs(200);
+    rt.set_max_operand_stack(200);
+    rt.set_max_string_len(1_000);
+    // the result does not matter, only that there is one
+    let _ = run(rt);
+}
+
+#[test]
+fn fuzz_vm_never_panics() {
+    let runs = std::env::var("IGNITE_FUZZ_RUNS")
+        .ok()
+        .and_then(|runs| runs.parse().ok())
+        .unwrap_or(FUZZ_RUNS);
+
+    let failures: Vec<(u64, String)> = (0..runs)
+        .filter_map(|seed| {
+            panic::catch_unwind(AssertUnwindSafe(|| run_bounded(gen_program(seed))))
+                .err()
+                .map(|err| {
+                    let msg = err
+                        .downcast_ref::<String>()
+                        .cloned()
+                        .or_else(|| err.downcast_ref::<&str>().map(|s| s.to_string()))
+                        .unwrap_or_default();
+                    (seed, msg)
+                })
+        })
+        .collect();
+
+    for (seed, msg) in failures.iter() {
+        eprintln!("seed {}: {}\n{:?}\n", seed, msg, gen_program(*seed));
+    }
+    assert!(
+        failures.is_empty(),
+        "{} of {} fuzzed programs panicked",
+        failures.len(),
+        runs
+    );
+}
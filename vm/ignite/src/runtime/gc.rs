@@ -1,8 +1,13 @@
-use std::{cell::RefCell, collections::HashMap, rc::Weak};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    rc::{Rc, Weak},
+};
 
 use bytecode::{weak_clone, EnvWeak, Environment, StackFrame, Value, W};
+use log::debug;
 
-use crate::{Runtime, Thread};
+use crate::{Callback, Runtime, Thread, VmError};
 
 /// Runtime methods at runtime.
 impl Runtime {
@@ -17,19 +22,22 @@ impl Runtime {
     ///   - Mark its current environment and the environment of closure values in the current environment,
     ///     and the chain of parent environments.
     ///   - Go through the runtime stack and mark all the environments and environment of closure values in
-    ///     their respective environment, and the chain of parent environments
+    ///     their respective environment, and the chain of parent environments. Local scopes owned by a
+    ///     frame are not in the registry, but what they refer to is marked the same way
     ///   - Go through the operand stack and mark all the environments of closure values, and the chain of parent environments
+    ///   - Mark the environments of closure values held by builtins waiting on a callback, like map's function
+    ///
+    /// Threads in the ready and blocked queues are marked the same way. For zombie threads only the result
+    /// waiting to be joined is marked.
     #[inline]
-    pub fn mark_and_weep(self) -> Self {
-        let marked = mark(&self);
-        sweep(self, marked)
+    pub fn mark_and_weep(&mut self) {
+        let marked = mark(self);
+        sweep(self, marked);
     }
 }
 
 fn mark(rt: &Runtime) -> HashMap<EnvWeak, bool> {
-    if rt.debug {
-        println!("Mark begin")
-    }
+    debug!("Mark begin");
 
     let mut marked = env_hashmap(rt);
 
@@ -46,15 +54,16 @@ fn mark(rt: &Runtime) -> HashMap<EnvWeak, bool> {
         marked = mark_thread(marked, thread);
     }
 
-    // Zombie threads will be ignored
+    // Mark the results of zombie threads, they can be closures. The rest of a zombie is never used again.
+    for thread in rt.zombie_threads.values() {
+        marked = mark_operand_stack(marked, &thread.operand_stack);
+    }
 
     marked
 }
 
-fn sweep(mut rt: Runtime, m: HashMap<EnvWeak, bool>) -> Runtime {
-    if rt.debug {
-        println!("Sweep begin")
-    }
+fn sweep(rt: &mut Runtime, m: HashMap<EnvWeak, bool>) {
+    debug!("Sweep begin");
 
     let registry = rt
         .env_registry
@@ -63,14 +72,11 @@ fn sweep(mut rt: Runtime, m: HashMap<EnvWeak, bool>) -> Runtime {
         .collect();
     rt.env_registry = registry;
 
-    if rt.debug {
-        println!(
-            "Sweep end, {} environments removed",
-            m.len() - rt.env_registry.len()
-        )
-    }
-
-    rt // Any environment that is not marked will be removed from the registry and dropped
+    debug!(
+        "Sweep end, {} environments removed",
+        m.len() - rt.env_registry.len()
+    );
+    // Any environment that is not marked was removed from the registry and dropped
 }
 
 fn env_hashmap(rt: &Runtime) -> HashMap<EnvWeak, bool> {
@@ -85,6 +91,7 @@ fn mark_thread(mut m: HashMap<EnvWeak, bool>, t: &Thread) -> HashMap<EnvWeak, bo
     m = mark_env(m, &t.env);
     m = mark_operand_stack(m, &t.operand_stack);
     m = mark_runtime_stack(m, &t.runtime_stack);
+    m = mark_callbacks(m, &t.callbacks);
     m
 }
 
@@ -92,23 +99,45 @@ fn mark_env(
     mut m: HashMap<EnvWeak, bool>,
     env: &Weak<RefCell<Environment>>,
 ) -> HashMap<EnvWeak, bool> {
-    let is_marked = m
-        .get_mut(&W(env.clone()))
-        .expect("Environment must be in the registry");
+    // Builtin closures have no environment
+    if env.strong_count() == 0 {
+        return m;
+    }
+
+    // An environment missing from the registry is not owned by the runtime, so there is nothing to mark.
+    // --paranoid-gc reports these.
+    let Some(is_marked) = m.get_mut(&W(env.clone())) else {
+        return m;
+    };
 
     match is_marked {
         true => return m, // Already marked
         false => *is_marked = true,
     }
 
-    let env = env
-        .upgrade()
-        .expect("Environment must still be referenced to be marked");
+    let Some(env) = env.upgrade() else {
+        return m;
+    };
 
+    mark_env_contents(m, &env)
+}
+
+/// Mark the parent of env and the environments of closures stored in it
+fn mark_env_contents(
+    mut m: HashMap<EnvWeak, bool>,
+    env: &Rc<RefCell<Environment>>,
+) -> HashMap<EnvWeak, bool> {
     if let Some(parent) = &env.borrow().parent {
         m = mark_env(m, parent);
     }
 
+    // Closures stored in the environment keep their own environments alive
+    for val in env.borrow().env.values() {
+        if let Value::Closure { env, .. } = val {
+            m = mark_env(m, env);
+        }
+    }
+
     m
 }
 
@@ -124,16 +153,125 @@ fn mark_operand_stack(mut m: HashMap<EnvWeak, bool>, os: &[Value]) -> HashMap<En
 fn mark_runtime_stack(mut m: HashMap<EnvWeak, bool>, rs: &[StackFrame]) -> HashMap<EnvWeak, bool> {
     for frame in rs.iter() {
         m = mark_env(m, &frame.env);
+        // A local scope is not in the registry, but what it refers to must be kept
+        if let Some(scope) = &frame.scope {
+            m = mark_env_contents(m, scope);
+        }
+    }
+    m
+}
+
+fn mark_callbacks(mut m: HashMap<EnvWeak, bool>, cbs: &[Callback]) -> HashMap<EnvWeak, bool> {
+    for val in cbs.iter().flat_map(|cb| cb.builtin.values()) {
+        if let Value::Closure { env, .. } = val {
+            m = mark_env(m, env);
+        }
     }
     m
 }
 
+/// Check that every environment reachable from a live thread is still alive and in the registry, or
+/// is a local scope owned by one of the thread's frames.
+/// Used by --paranoid-gc after each collection.
+///
+/// # Errors
+///
+/// * `VmError::EnvironmentReclaimed` - If a reachable environment has been dropped.
+/// * `VmError::EnvironmentNotRegistered` - If a reachable environment is not in the registry or
+///   owned by a frame.
+pub fn validate_env_graph(rt: &Runtime) -> Result<(), VmError> {
+    let mut visited = HashSet::new();
+
+    let threads = std::iter::once(&rt.current_thread)
+        .chain(rt.ready_queue.iter())
+        .chain(rt.blocked_queue.iter().map(|(thread, _)| thread));
+
+    for thread in threads {
+        let mut envs = vec![thread.env.clone()];
+        envs.extend(thread.runtime_stack.iter().map(|frame| frame.env.0.clone()));
+        envs.extend(closure_envs(&thread.operand_stack));
+        for cb in thread.callbacks.iter() {
+            envs.extend(closure_envs(cb.builtin.values()));
+        }
+
+        let scopes: HashSet<*const RefCell<Environment>> = thread
+            .runtime_stack
+            .iter()
+            .filter_map(|frame| frame.scope.as_ref().map(Rc::as_ptr))
+            .collect();
+
+        for env in envs {
+            validate_env(rt, thread, &scopes, &env, &mut visited)?;
+        }
+    }
+
+    for thread in rt.zombie_threads.values() {
+        for env in closure_envs(&thread.operand_stack) {
+            validate_env(rt, thread, &HashSet::new(), &env, &mut visited)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn closure_envs<'a>(vals: impl IntoIterator<Item = &'a Value>) -> Vec<Weak<RefCell<Environment>>> {
+    vals.into_iter()
+        .filter_map(|val| match val {
+            Value::Closure { env, .. } => Some(env.0.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+fn validate_env(
+    rt: &Runtime,
+    thread: &Thread,
+    scopes: &HashSet<*const RefCell<Environment>>,
+    env: &Weak<RefCell<Environment>>,
+    visited: &mut HashSet<*const RefCell<Environment>>,
+) -> Result<(), VmError> {
+    // Builtin closures have no environment
+    if Weak::ptr_eq(env, &Weak::new()) || !visited.insert(env.as_ptr()) {
+        return Ok(());
+    }
+
+    let Some(strong) = env.upgrade() else {
+        return Err(VmError::EnvironmentReclaimed {
+            thread_id: thread.thread_id,
+            pc: thread.pc,
+        });
+    };
+
+    if !scopes.contains(&Rc::as_ptr(&strong)) && !rt.env_registry.contains(&W(strong.clone())) {
+        return Err(VmError::EnvironmentNotRegistered {
+            thread_id: thread.thread_id,
+            pc: thread.pc,
+        });
+    }
+
+    let env = strong.borrow();
+
+    if let Some(parent) = &env.parent {
+        validate_env(rt, thread, scopes, parent, visited)?;
+    }
+
+    for val in env.env.values() {
+        if let Value::Closure { env, .. } = val {
+            validate_env(rt, thread, scopes, &env.0, visited)?;
+        }
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::run;
+    use crate::{extend_environment, run};
 
     use super::*;
 
+    use std::{rc::Rc, time::Duration};
+
     use anyhow::Result;
     use bytecode::*;
 
@@ -155,12 +293,11 @@ mod tests {
             ByteCode::DONE,
         ];
 
-        let mut rt = Runtime::new(instrs);
-        rt.set_debug_mode();
-        let rt = run(rt)?;
+        let rt = Runtime::new(instrs);
+        let mut rt = run(rt)?;
         assert_eq!(rt.env_registry.len(), 3); // Global env, program env, block env
 
-        let rt = rt.mark_and_weep();
+        rt.mark_and_weep();
         assert_eq!(rt.env_registry.len(), 1); // Only the global environment should be left
 
         Ok(())
@@ -229,12 +366,244 @@ mod tests {
             ByteCode::DONE,
         ];
 
+        let rt = Runtime::new(instrs);
+        let mut rt = run(rt)?;
+
+        rt.mark_and_weep();
+        assert_eq!(rt.env_registry.len(), 1); // Only the global environment should be left
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_gc_closure_in_env() -> Result<()> {
+        // let add10 = higher_order(10);
+        // The environment of add10 is only reachable through the closure stored in the program scope
+        let rt = Runtime::new(vec![]);
+        let global = rt.current_thread.env.clone();
+
+        let program = Environment::new_wrapped();
+        program.borrow_mut().set_parent(global);
+
+        let call_env = Environment::new_wrapped();
+        call_env.borrow_mut().set_parent(weak_clone(&program));
+        call_env.borrow_mut().set("x", 10);
+
+        program.borrow_mut().set(
+            "add10",
+            Value::Closure {
+                fn_type: FnType::User,
+                sym: "add10".to_string(),
+                prms: vec!["y".to_string()],
+                addr: 0,
+                env: W(weak_clone(&call_env)),
+            },
+        );
+
+        let mut rt = rt;
+        rt.current_thread.env = weak_clone(&program);
+        rt.env_registry.insert(W(program));
+        rt.env_registry.insert(W(call_env.clone()));
+
+        rt.mark_and_weep();
+        assert_eq!(rt.env_registry.len(), 3);
+        assert!(rt.env_registry.contains(&W(call_env)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_gc_builtin_closure_on_stack() -> Result<()> {
+        // builtins have no environment, marking them should not panic
+        let mut rt = Runtime::new(vec![]);
+        rt.current_thread.operand_stack.push(builtin::println());
+
+        rt.mark_and_weep();
+        assert_eq!(rt.env_registry.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_gc_roots_blocked_and_zombie() -> Result<()> {
+        let rt = Runtime::new(vec![]);
+        let global = rt.current_thread.env.clone();
+
+        let closure_for = |env: &Rc<RefCell<Environment>>| Value::Closure {
+            fn_type: FnType::User,
+            sym: "f".to_string(),
+            prms: vec![],
+            addr: 0,
+            env: W(weak_clone(env)),
+        };
+
+        // environment of a blocked thread
+        let blocked_env = Environment::new_wrapped();
+        blocked_env.borrow_mut().set_parent(global.clone());
+        let mut blocked = Thread::new(2, weak_clone(&blocked_env));
+        blocked.operand_stack.push(Value::Int(1));
+
+        // closure returned by a zombie thread that has not been joined
+        let zombie_result_env = Environment::new_wrapped();
+        zombie_result_env.borrow_mut().set_parent(global.clone());
+        let mut zombie = Thread::new(3, Weak::new());
+        zombie.operand_stack.push(closure_for(&zombie_result_env));
+
+        // garbage
+        let garbage_env = Environment::new_wrapped();
+        garbage_env.borrow_mut().set_parent(global);
+
+        let mut rt = rt;
+        rt.blocked_queue.push_back((blocked, Semaphore::new(0)));
+        rt.add_zombie(zombie);
+        rt.env_registry.insert(W(blocked_env.clone()));
+        rt.env_registry.insert(W(zombie_result_env.clone()));
+        rt.env_registry.insert(W(garbage_env.clone()));
+
+        // survives several collections
+        for _ in 0..3 {
+            rt.mark_and_weep();
+        }
+
+        assert_eq!(rt.env_registry.len(), 3);
+        assert!(rt.env_registry.contains(&W(blocked_env.clone())));
+        assert!(rt.env_registry.contains(&W(zombie_result_env.clone())));
+        assert!(!rt.env_registry.contains(&W(garbage_env.clone())));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_gc_local_scopes_freed_on_exit() -> Result<()> {
+        // no closures or threads: every block's environment is freed when the block exits
+        let src = "let i = 0; loop i < 50 { let x = i; i = x + 1; } i";
+        let instrs = compiler::compiler::compile_from_string(src, true)?;
+        assert!(instrs.contains(&ByteCode::EXITSCOPELOCAL));
+
+        let mut rt = Runtime::new(instrs);
+        rt.set_paranoid_gc_mode();
+        let rt = run(rt)?;
+        assert_eq!(rt.env_registry.len(), 1); // Only the global environment is left
+        assert_eq!(
+            rt.current_thread.operand_stack.last(),
+            Some(&Value::Int(50))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_gc_local_scope_keeps_closures() -> Result<()> {
+        // the block creates no closure, so its scope is local, but the closure it holds must survive
+        let src = r"
+        fn adder(n: int) -> fn(int) -> int {
+            fn add(x: int) -> int { x + n }
+            add
+        }
+        let r = 0;
+        {
+            let f = adder(2);
+            let i = 0;
+            loop i < 3 { i = f(i); }
+            r = i;
+        }
+        r";
+        let instrs = compiler::compiler::compile_from_string(src, true)?;
+        assert!(instrs
+            .iter()
+            .any(|i| matches!(i, ByteCode::ENTERSCOPELOCAL(_))));
+
+        let mut rt = Runtime::new(instrs);
+        rt.set_gc_stress_mode();
+        rt.set_paranoid_gc_mode();
+        let rt = run(rt)?;
+        assert_eq!(rt.current_thread.operand_stack.last(), Some(&Value::Int(4)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_should_garbage_collect() -> Result<()> {
+        let mut rt = Runtime::default();
+        rt.set_gc_interval(Duration::ZERO);
+        rt.set_gc_alloc_threshold(2);
+
+        // nothing was created, so the interval passing is not enough
+        assert!(!rt.should_garbage_collect());
+        let env = rt.current_thread.env.clone();
+        extend_environment(&mut rt, env.clone(), vec!["x"], vec![1])?;
+        assert!(rt.should_garbage_collect());
+
+        // enough environments were created, however recently the collector ran
+        rt.set_gc_interval(Duration::from_secs(3600));
+        rt.garbage_collect()?;
+        assert_eq!(rt.envs_since_gc, 0);
+        extend_environment(&mut rt, env.clone(), vec!["x"], vec![1])?;
+        assert!(!rt.should_garbage_collect());
+        extend_environment(&mut rt, env, vec!["x"], vec![1])?;
+        assert!(rt.should_garbage_collect());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_gc_stress() -> Result<()> {
+        // closures and threads survive a collection before every instruction
+        let instrs = vec![
+            ByteCode::enterscope(vec!["f", "t"]),
+            ByteCode::ldf(4, Vec::<Symbol>::new()),
+            ByteCode::assign("f"),
+            ByteCode::GOTO(7),
+            ByteCode::ld("println"), // PC: 4, f body returns a builtin
+            ByteCode::RESET(FrameType::CallFrame),
+            ByteCode::DONE,
+            ByteCode::SPAWN(11, 0), // PC: 7
+            ByteCode::assign("t"),
+            ByteCode::ld("t"),
+            ByteCode::GOTO(15),
+            ByteCode::POP, // PC: 11, child calls f
+            ByteCode::ld("f"),
+            ByteCode::CALL(0),
+            ByteCode::DONE,
+            ByteCode::JOIN, // PC: 15
+            ByteCode::ldc(42),
+            ByteCode::CALL(1),
+            ByteCode::EXITSCOPE,
+            ByteCode::DONE,
+        ];
+
         let mut rt = Runtime::new(instrs);
-        rt.set_debug_mode();
+        rt.set_gc_stress_mode();
         let rt = run(rt)?;
+        assert!(rt.done);
 
-        let rt = rt.mark_and_weep();
-        assert_eq!(rt.env_registry.len(), 1); // Only the global environment should be left
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_env_graph() -> Result<()> {
+        let mut rt = Runtime::new(vec![]);
+        rt.current_thread.operand_stack.push(builtin::println());
+        validate_env_graph(&rt)?;
+
+        let env = Environment::new_wrapped();
+        env.borrow_mut().set_parent(rt.current_thread.env.clone());
+        rt.current_thread.env = weak_clone(&env);
+        rt.current_thread.pc = 3;
+        assert_eq!(
+            validate_env_graph(&rt).unwrap_err().to_string(),
+            "Environment used by thread 1 at pc 3 is not in the environment registry"
+        );
+
+        drop(env);
+        assert_eq!(
+            validate_env_graph(&rt).unwrap_err().to_string(),
+            "Environment reclaimed while still in use by thread 1 at pc 3"
+        );
+
+        // paranoid mode checks after collecting
+        rt.set_paranoid_gc_mode();
+        assert!(rt.garbage_collect().is_err());
 
         Ok(())
     }
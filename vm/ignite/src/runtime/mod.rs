@@ -1,19 +1,65 @@
 use std::{
     collections::{HashMap, HashSet, VecDeque},
+    rc::Rc,
     time::{Duration, Instant},
 };
 
-use bytecode::{weak_clone, ByteCode, EnvStrong, Environment, Semaphore, ThreadID, W};
+use bytecode::{
+    builtin, weak_clone, ByteCode, EnvStrong, Environment, Semaphore, SourceMap, ThreadID, W,
+};
 
 use crate::Thread;
+pub use busy_wait::*;
+pub use gc::validate_env_graph;
+pub use replay::*;
 pub use run::*;
+pub use sched_stats::*;
 
+#[cfg(test)]
+mod arith_props;
+mod busy_wait;
+#[cfg(test)]
+mod fuzz;
 mod gc;
+mod replay;
 mod run;
+mod sched_stats;
 
 pub const DEFAULT_TIME_QUANTUM: Duration = Duration::from_millis(100);
 pub const DEFAULT_GC_INTERVAL: Duration = Duration::from_secs(1);
+/// Environments created since the last garbage collection that trigger the next one.
+pub const DEFAULT_GC_ALLOC_THRESHOLD: usize = 10_000;
 pub const MAIN_THREAD_ID: i64 = 1;
+/// Maximum number of finished Thread structs kept around for reuse by SPAWN.
+pub const MAX_THREAD_POOL_SIZE: usize = 64;
+/// Maximum number of exited local scope environments a thread keeps around for reuse by ENTERSCOPELOCAL.
+pub const MAX_SCOPE_POOL_SIZE: usize = 32;
+/// Values a new thread's operand stack has room for before it has to grow.
+pub const DEFAULT_OPERAND_STACK_CAPACITY: usize = 32;
+/// Frames a new thread's runtime stack has room for before it has to grow.
+pub const DEFAULT_RUNTIME_STACK_CAPACITY: usize = 16;
+/// Number of instructions executed between checks of the time quantum and garbage collection timers.
+pub const TIMER_CHECK_INTERVAL: usize = 32;
+
+/// What happens to threads that are still running when the main thread finishes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ExitPolicy {
+    /// The program ends immediately, other threads are killed.
+    #[default]
+    Kill,
+    /// The program ends once every thread that was not detached has finished.
+    Wait,
+}
+
+/// What happens when a thread other than the main thread hits a runtime error.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ThreadErrorPolicy {
+    /// The program ends with the error.
+    #[default]
+    FailFast,
+    /// Only the failing thread ends. Its error is kept and returned to the thread that joins it.
+    Isolate,
+}
 
 /// The runtime of the virtual machine.
 /// It contains the instructions to execute, the current thread, and the ready and blocked threads.
@@ -24,18 +70,28 @@ pub const MAIN_THREAD_ID: i64 = 1;
 pub struct Runtime {
     /// If the program is done.
     pub done: bool,
-    /// If the program is in debug mode.
-    pub debug: bool,
+    /// Set when the program calls exit(code), the process should exit with this code.
+    pub exit_code: Option<i32>,
     /// The time the program started, used for calculating the time quantum.
     pub time: Instant,
     /// The maximum amount of time a thread can run before it is preempted.
     pub time_quantum: Duration,
     /// The time the garbage collector was last run.
     pub gc_timer: Instant,
-    /// The interval at which to run the mark and sweep garbage collector.
+    /// The interval at which to run the mark and sweep garbage collector, if environments were
+    /// created since it last ran.
     pub gc_interval: Duration,
-    /// The instructions to execute.
-    pub instrs: Vec<ByteCode>,
+    /// The number of environments created since the last garbage collection that triggers the
+    /// next one, however long ago it ran.
+    pub gc_alloc_threshold: usize,
+    /// The number of environments created since the last garbage collection.
+    pub envs_since_gc: usize,
+    /// If true, garbage collect before every instruction to shake out GC bugs. Very slow.
+    pub gc_stress: bool,
+    /// If true, check the environment graph of every thread after each garbage collection.
+    pub paranoid_gc: bool,
+    /// The instructions to execute, shared so the run loop can borrow them while mutating the runtime.
+    pub instrs: Rc<[ByteCode]>,
     /// The environment registry, holds strong references to environments.
     pub env_registry: HashSet<EnvStrong>,
     /// The number of threads that have been created.
@@ -48,6 +104,63 @@ pub struct Runtime {
     pub blocked_queue: VecDeque<(Thread, Semaphore)>,
     /// The threads that have finished executing, waiting to be joined.
     pub zombie_threads: HashMap<ThreadID, Thread>,
+    /// The maximum number of zombie threads to keep. When exceeded, the oldest zombie is reaped.
+    /// None means zombies are kept until joined.
+    pub max_zombies: Option<usize>,
+    /// The IDs of zombie threads that were reaped before being joined.
+    pub reaped_threads: HashSet<ThreadID>,
+    /// The IDs of threads that were detached. They can't be joined and are not waited for at exit.
+    pub detached_threads: HashSet<ThreadID>,
+    /// What happens to running threads when the main thread finishes.
+    pub exit_policy: ExitPolicy,
+    /// What happens when a thread other than the main thread hits a runtime error.
+    pub thread_error_policy: ThreadErrorPolicy,
+    /// The errors of threads that failed under ThreadErrorPolicy::Isolate, kept until they are joined.
+    pub failed_threads: HashMap<ThreadID, String>,
+    /// Semaphores held by threads that ended without posting them, with the thread that held each.
+    /// A semaphore is forgotten once it is posted.
+    pub orphaned_sems: Vec<(Semaphore, ThreadID)>,
+    /// Recording or replaying of preemptions and inputs, to reproduce a run exactly.
+    pub replay: ReplayMode,
+    /// Finished threads whose allocations can be reused for new threads.
+    pub thread_pool: Vec<Thread>,
+    /// The number of values the operand stack of each new thread is allocated with.
+    pub operand_stack_capacity: usize,
+    /// The number of frames the runtime stack of each new thread is allocated with.
+    pub runtime_stack_capacity: usize,
+    /// The time the runtime was created, used for the timeout.
+    pub start_time: Instant,
+    /// The maximum wall-clock time the program may run for. None means no limit.
+    pub timeout: Option<Duration>,
+    /// The number of instructions executed by all threads.
+    pub instr_count: u64,
+    /// The maximum number of instructions the program may execute. None means no limit.
+    pub max_instr: Option<u64>,
+    /// The maximum length in bytes of a string the program may build. None means no limit.
+    pub max_string_len: Option<usize>,
+    /// The maximum number of values on the operand stack of a thread. None means no limit.
+    pub max_operand_stack: Option<usize>,
+    /// The maximum number of live environments across all threads. None means no limit.
+    pub max_envs: Option<usize>,
+    /// The maximum number of frames on the runtime stack of a thread, which function calls and
+    /// scopes push. None means no limit.
+    pub max_stack_depth: Option<usize>,
+    /// Where in the source each instruction came from, to say which line a runtime error is on.
+    pub source_map: Option<Rc<SourceMap>>,
+    /// Digits after the decimal point when printing floats. None means the shortest representation
+    /// that reads back as the same float.
+    pub float_precision: Option<usize>,
+    /// If true, print_result prints the result of the program. Only the REPL sets it by default, so
+    /// embedders and programs run from files only get output the program prints itself.
+    pub echo_result: bool,
+    /// If true, threads found busy waiting yield to other threads and are warned about.
+    pub detect_busy_wait: bool,
+    /// The loop the current thread was last found in, while looking for busy waiting.
+    pub spin: Option<SpinWindow>,
+    /// Start addresses of the loops already warned about for busy waiting.
+    pub busy_wait_warned: HashSet<usize>,
+    /// Context switches and the stats of finished threads, for sched_stats.
+    pub sched_stats: SchedStats,
 }
 
 /// Constructors for the runtime.
@@ -59,19 +172,54 @@ impl Runtime {
         envs.insert(W(global_env));
 
         Runtime {
-            debug: false,
             done: false,
+            exit_code: None,
             time: Instant::now(),
             time_quantum: DEFAULT_TIME_QUANTUM,
             gc_timer: Instant::now(),
             gc_interval: DEFAULT_GC_INTERVAL,
-            instrs,
+            gc_alloc_threshold: DEFAULT_GC_ALLOC_THRESHOLD,
+            envs_since_gc: 0,
+            gc_stress: false,
+            paranoid_gc: false,
+            instrs: instrs.into(),
             env_registry: envs,
             thread_count: 1,
-            current_thread: Thread::new(MAIN_THREAD_ID, global_env_weak),
+            current_thread: Thread::with_capacity(
+                MAIN_THREAD_ID,
+                global_env_weak,
+                DEFAULT_OPERAND_STACK_CAPACITY,
+                DEFAULT_RUNTIME_STACK_CAPACITY,
+            ),
             ready_queue: VecDeque::new(),
             blocked_queue: VecDeque::new(),
             zombie_threads: HashMap::new(),
+            max_zombies: None,
+            reaped_threads: HashSet::new(),
+            detached_threads: HashSet::new(),
+            exit_policy: ExitPolicy::default(),
+            thread_error_policy: ThreadErrorPolicy::default(),
+            failed_threads: HashMap::new(),
+            orphaned_sems: Vec::new(),
+            replay: ReplayMode::default(),
+            thread_pool: Vec::new(),
+            operand_stack_capacity: DEFAULT_OPERAND_STACK_CAPACITY,
+            runtime_stack_capacity: DEFAULT_RUNTIME_STACK_CAPACITY,
+            start_time: Instant::now(),
+            timeout: None,
+            instr_count: 0,
+            max_instr: None,
+            max_string_len: None,
+            max_operand_stack: None,
+            max_envs: None,
+            max_stack_depth: None,
+            source_map: None,
+            float_precision: None,
+            echo_result: false,
+            detect_busy_wait: true,
+            spin: None,
+            busy_wait_warned: HashSet::new(),
+            sched_stats: SchedStats::default(),
         }
     }
 }
@@ -92,7 +240,104 @@ impl Runtime {
         self.gc_interval = gc_interval;
     }
 
-    pub fn set_debug_mode(&mut self) {
-        self.debug = true;
+    pub fn set_gc_alloc_threshold(&mut self, gc_alloc_threshold: usize) {
+        self.gc_alloc_threshold = gc_alloc_threshold;
+    }
+
+    pub fn set_gc_stress_mode(&mut self) {
+        self.gc_stress = true;
+    }
+
+    pub fn set_paranoid_gc_mode(&mut self) {
+        self.paranoid_gc = true;
+    }
+
+    pub fn set_max_zombies(&mut self, max_zombies: usize) {
+        self.max_zombies = Some(max_zombies);
+    }
+
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = Some(timeout);
+    }
+
+    pub fn set_max_instr(&mut self, max_instr: u64) {
+        self.max_instr = Some(max_instr);
+    }
+
+    pub fn set_max_string_len(&mut self, max_string_len: usize) {
+        self.max_string_len = Some(max_string_len);
+    }
+
+    pub fn set_max_operand_stack(&mut self, max_operand_stack: usize) {
+        self.max_operand_stack = Some(max_operand_stack);
+    }
+
+    pub fn set_max_envs(&mut self, max_envs: usize) {
+        self.max_envs = Some(max_envs);
+    }
+
+    pub fn set_max_stack_depth(&mut self, max_stack_depth: usize) {
+        self.max_stack_depth = Some(max_stack_depth);
+    }
+
+    /// Allocate the operand and runtime stacks of threads with room for this many values and frames,
+    /// so call-heavy or deeply recursive programs don't grow them as they run.
+    /// The current thread's stacks are grown to the new capacity too.
+    pub fn set_stack_capacity(&mut self, operand_stack: usize, runtime_stack: usize) {
+        self.operand_stack_capacity = operand_stack;
+        self.runtime_stack_capacity = runtime_stack;
+        self.current_thread
+            .reserve_stacks(operand_stack, runtime_stack);
+    }
+
+    pub fn set_source_map(&mut self, source_map: SourceMap) {
+        self.source_map = Some(Rc::new(source_map));
+    }
+
+    /// Where the instruction at pc was compiled from: file:line, or the file if the line isn't
+    /// known. None without a source map.
+    pub fn source_location(&self, pc: usize) -> Option<String> {
+        let source_map = self.source_map.as_ref()?;
+        match source_map.line(pc) {
+            Some(line) => Some(format!("{}:{}", source_map.file, line)),
+            None => Some(source_map.file.clone()),
+        }
+    }
+
+    pub fn set_float_precision(&mut self, float_precision: usize) {
+        self.float_precision = Some(float_precision);
+    }
+
+    /// Let threads spin waiting for each other without yielding or being warned about it.
+    pub fn allow_busy_wait(&mut self) {
+        self.detect_busy_wait = false;
+    }
+
+    pub fn set_exit_policy(&mut self, exit_policy: ExitPolicy) {
+        self.exit_policy = exit_policy;
+    }
+
+    pub fn set_thread_error_policy(&mut self, thread_error_policy: ThreadErrorPolicy) {
+        self.thread_error_policy = thread_error_policy;
+    }
+
+    pub fn set_replay_mode(&mut self, replay: ReplayMode) {
+        self.replay = replay;
+    }
+
+    pub fn set_echo_result(&mut self, echo_result: bool) {
+        self.echo_result = echo_result;
+    }
+
+    /// Print the result of the program, the value left on the operand stack of the thread that
+    /// finished last, if echo_result is set and there is one.
+    pub fn print_result(&self) {
+        if !self.echo_result {
+            return;
+        }
+
+        if let Some(val) = self.current_thread.operand_stack.last() {
+            builtin::println_impl(val, self.float_precision);
+        }
     }
 }
@@ -0,0 +1,316 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+    time::Instant,
+};
+
+use anyhow::Result;
+
+use crate::{Runtime, VmError};
+
+const LOG_HEADER: &str = "rustscript-replay 1";
+
+/// A nondeterministic event that decides how a program runs. Everything else the VM does follows from
+/// the program and these events, so replaying them reproduces a run exactly.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplayEvent {
+    /// The current thread was preempted because its time quantum expired, after this many instructions
+    /// had been executed in total.
+    Preempt(u64),
+    /// read_line or read_all returned this input.
+    Input(String),
+    /// eof returned this.
+    Eof(bool),
+    /// A join_timeout call gave up, after this many instructions had been executed in total.
+    JoinTimeout(u64),
+}
+
+impl ReplayEvent {
+    fn to_line(&self) -> String {
+        match self {
+            ReplayEvent::Preempt(n) => format!("preempt {}", n),
+            ReplayEvent::Input(input) => format!("input {}", escape(input)),
+            ReplayEvent::Eof(eof) => format!("eof {}", eof),
+            ReplayEvent::JoinTimeout(n) => format!("join_timeout {}", n),
+        }
+    }
+
+    fn from_line(line: &str) -> Option<ReplayEvent> {
+        let (kind, rest) = line.split_once(' ')?;
+        match kind {
+            "preempt" => rest.parse().ok().map(ReplayEvent::Preempt),
+            "input" => unescape(rest).map(ReplayEvent::Input),
+            "eof" => rest.parse().ok().map(ReplayEvent::Eof),
+            "join_timeout" => rest.parse().ok().map(ReplayEvent::JoinTimeout),
+            _ => None,
+        }
+    }
+}
+
+/// Whether scheduling decisions and inputs are recorded to a log, replayed from one, or neither.
+#[derive(Debug, Default)]
+pub enum ReplayMode {
+    #[default]
+    Off,
+    /// Events are written to the log as they happen, so the log is complete even if the program fails.
+    Record(BufWriter<File>),
+    /// Events still to be replayed, in order.
+    Replay(VecDeque<ReplayEvent>),
+}
+
+impl ReplayMode {
+    /// Start recording to the file at path, replacing it.
+    pub fn record(path: impl AsRef<Path>) -> Result<ReplayMode> {
+        let mut log = BufWriter::new(File::create(path)?);
+        writeln!(log, "{}", LOG_HEADER)?;
+        Ok(ReplayMode::Record(log))
+    }
+
+    /// Load a log written by record.
+    ///
+    /// # Errors
+    ///
+    /// If the file can't be read or is not a replay log.
+    pub fn replay(path: impl AsRef<Path>) -> Result<ReplayMode> {
+        let mut lines = BufReader::new(File::open(path)?).lines();
+
+        if lines.next().transpose()?.as_deref() != Some(LOG_HEADER) {
+            return Err(VmError::ReplayDiverged("not a replay log".to_string()).into());
+        }
+
+        let mut events = VecDeque::new();
+        for line in lines {
+            let line = line?;
+            let event = ReplayEvent::from_line(&line).ok_or_else(|| {
+                VmError::ReplayDiverged(format!("bad line in replay log: {}", line))
+            })?;
+            events.push_back(event);
+        }
+
+        Ok(ReplayMode::Replay(events))
+    }
+
+    pub fn is_replaying(&self) -> bool {
+        matches!(self, ReplayMode::Replay(_))
+    }
+
+    /// Record the event if recording.
+    pub fn log(&mut self, event: ReplayEvent) -> Result<()> {
+        if let ReplayMode::Record(log) = self {
+            writeln!(log, "{}", event.to_line())?;
+        }
+        Ok(())
+    }
+
+    /// When replaying, take the next event if it is the given one.
+    pub fn take_if(&mut self, event: &ReplayEvent) -> bool {
+        match self {
+            ReplayMode::Replay(events) if events.front() == Some(event) => {
+                events.pop_front();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// When replaying, take the next event, which must be a read of stdin.
+    ///
+    /// # Errors
+    ///
+    /// If the next event is not an input or eof.
+    pub fn take_read(&mut self) -> Result<ReplayEvent> {
+        match self {
+            ReplayMode::Replay(events) => match events.pop_front() {
+                Some(event @ (ReplayEvent::Input(_) | ReplayEvent::Eof(_))) => Ok(event),
+                other => Err(VmError::ReplayDiverged(format!(
+                    "program read stdin but the log has {:?}",
+                    other
+                ))
+                .into()),
+            },
+            _ => unreachable!("take_read is only called when replaying"),
+        }
+    }
+
+    /// Flush the log when recording. When replaying, check the whole log was used.
+    ///
+    /// # Errors
+    ///
+    /// If the log can't be written, or replay finished with events left over.
+    pub fn finish(&mut self) -> Result<()> {
+        match self {
+            ReplayMode::Off => Ok(()),
+            ReplayMode::Record(log) => Ok(log.flush()?),
+            ReplayMode::Replay(events) => match events.front() {
+                None => Ok(()),
+                Some(event) => Err(VmError::ReplayDiverged(format!(
+                    "program finished before {:?} in the log",
+                    event
+                ))
+                .into()),
+            },
+        }
+    }
+}
+
+/// Runtime methods for decisions that are recorded or replayed.
+impl Runtime {
+    /// Decide whether to preempt the current thread at a timer check. Follows the log when replaying,
+    /// otherwise preempts if the time quantum has expired and records that when recording.
+    ///
+    /// # Errors
+    ///
+    /// If the log can't be written.
+    pub fn should_preempt(&mut self) -> Result<bool> {
+        let event = ReplayEvent::Preempt(self.instr_count);
+
+        if self.replay.is_replaying() {
+            return Ok(self.replay.take_if(&event));
+        }
+
+        if !self.time_quantum_expired() {
+            return Ok(false);
+        }
+
+        self.replay.log(event)?;
+        Ok(true)
+    }
+
+    /// Read from stdin with read, e.g. read_line_impl, or take the input from the log when replaying.
+    ///
+    /// # Errors
+    ///
+    /// If reading stdin or writing the log fails, or the log has no input next.
+    pub fn read_input(&mut self, read: fn() -> Result<String>) -> Result<String> {
+        if self.replay.is_replaying() {
+            return match self.replay.take_read()? {
+                ReplayEvent::Input(input) => Ok(input),
+                event => Err(VmError::ReplayDiverged(format!(
+                    "program read input but the log has {:?}",
+                    event
+                ))
+                .into()),
+            };
+        }
+
+        let input = read()?;
+        self.replay.log(ReplayEvent::Input(input.clone()))?;
+        Ok(input)
+    }
+
+    /// Check whether stdin is at end of input for the eof builtin, or take the answer from the log
+    /// when replaying.
+    ///
+    /// # Errors
+    ///
+    /// If reading stdin or writing the log fails, or the log has no eof next.
+    pub fn eof(&mut self) -> Result<bool> {
+        if self.replay.is_replaying() {
+            return match self.replay.take_read()? {
+                ReplayEvent::Eof(eof) => Ok(eof),
+                event => Err(VmError::ReplayDiverged(format!(
+                    "program checked for end of input but the log has {:?}",
+                    event
+                ))
+                .into()),
+            };
+        }
+
+        let eof = bytecode::builtin::eof_impl()?;
+        self.replay.log(ReplayEvent::Eof(eof))?;
+        Ok(eof)
+    }
+
+    /// Decide whether a join_timeout call with the given deadline gives up. Follows the log when
+    /// replaying, otherwise gives up if the deadline has passed and records that when recording.
+    ///
+    /// # Errors
+    ///
+    /// If the log can't be written.
+    pub fn join_timed_out(&mut self, deadline: Instant) -> Result<bool> {
+        let event = ReplayEvent::JoinTimeout(self.instr_count);
+
+        if self.replay.is_replaying() {
+            return Ok(self.replay.take_if(&event));
+        }
+
+        if Instant::now() < deadline {
+            return Ok(false);
+        }
+
+        self.replay.log(event)?;
+        Ok(true)
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+fn unescape(s: &str) -> Option<String> {
+    let mut res = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            res.push(c);
+            continue;
+        }
+        match chars.next()? {
+            '\\' => res.push('\\'),
+            'n' => res.push('\n'),
+            'r' => res.push('\r'),
+            _ => return None,
+        }
+    }
+    Some(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_lines() {
+        let events = [
+            ReplayEvent::Preempt(42),
+            ReplayEvent::Input("a\\b\r\n".to_string()),
+            ReplayEvent::Eof(true),
+            ReplayEvent::JoinTimeout(7),
+        ];
+
+        for event in events {
+            let line = event.to_line();
+            assert!(!line.contains('\n'));
+            assert_eq!(ReplayEvent::from_line(&line), Some(event));
+        }
+
+        assert_eq!(ReplayEvent::from_line("yield 3"), None);
+        assert_eq!(ReplayEvent::from_line("input \\x"), None);
+    }
+
+    #[test]
+    fn test_record_replay_file() -> Result<()> {
+        let file = format!("./{}.log", rand::random::<u128>());
+
+        let mut rec = ReplayMode::record(&file)?;
+        rec.log(ReplayEvent::Preempt(3))?;
+        rec.log(ReplayEvent::Input("hi\n".to_string()))?;
+        rec.finish()?;
+
+        let mut rep = ReplayMode::replay(&file)?;
+        std::fs::remove_file(&file)?;
+
+        assert!(rep.is_replaying());
+        assert!(!rep.take_if(&ReplayEvent::Preempt(2)));
+        assert!(rep.take_if(&ReplayEvent::Preempt(3)));
+        assert!(rep.finish().is_err());
+        assert_eq!(rep.take_read()?, ReplayEvent::Input("hi\n".to_string()));
+        assert!(rep.finish().is_ok());
+
+        Ok(())
+    }
+}
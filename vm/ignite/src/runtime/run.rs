@@ -1,14 +1,16 @@
-use std::time::Instant;
+use std::{cell::RefCell, rc::Rc, time::Instant};
 
 use anyhow::Result;
-use bytecode::ByteCode;
+use bytecode::{ByteCode, ByteCodeError, Environment, SourceMap, W};
+use log::{log_enabled, trace, Level};
 
-use crate::{micro_code, Runtime, VmError};
+use crate::{micro_code, validate_env_graph, Runtime, VmError, TIMER_CHECK_INTERVAL};
 
 /// Runtime methods at runtime.
 impl Runtime {
-    /// Fetch the next instruction to execute.
+    /// Fetch the next instruction to execute from instrs, which should be the runtime's instructions.
     /// This will increment the program counter of the current thread.
+    /// The instruction is borrowed rather than cloned so no heap allocation happens per instruction.
     ///
     /// # Returns
     ///
@@ -18,11 +20,9 @@ impl Runtime {
     ///
     /// If the program counter is out of bounds.
     #[inline]
-    pub fn fetch_instr(&mut self) -> Result<ByteCode> {
-        let instr = self
-            .instrs
+    pub fn fetch_instr<'a>(&mut self, instrs: &'a [ByteCode]) -> Result<&'a ByteCode> {
+        let instr = instrs
             .get(self.current_thread.pc)
-            .cloned()
             .ok_or(VmError::PcOutOfBounds(self.current_thread.pc))?;
         self.current_thread.pc += 1;
         Ok(instr)
@@ -34,16 +34,134 @@ impl Runtime {
         self.time.elapsed() >= self.time_quantum
     }
 
+    /// Add the time since the current thread was scheduled to its CPU time, and restart the clock
+    /// for the thread switched to next. Call before switching threads.
+    #[inline]
+    pub fn account_cpu_time(&mut self) {
+        let now = Instant::now();
+        self.current_thread.cpu_time += now - self.time;
+        self.time = now;
+    }
+
+    /// Check the program has not run for longer than its timeout, if it has one.
+    ///
+    /// # Errors
+    ///
+    /// VmError::TimeoutExceeded if it has.
+    #[inline]
+    pub fn check_timeout(&self) -> Result<()> {
+        match self.timeout {
+            Some(timeout) if self.start_time.elapsed() >= timeout => Err(VmError::TimeoutExceeded(
+                format!("program ran for longer than {:?}", timeout),
+            )
+            .into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check the program has not executed more instructions than its limit, if it has one.
+    ///
+    /// # Errors
+    ///
+    /// VmError::TimeoutExceeded if it has.
+    #[inline]
+    pub fn check_max_instr(&self) -> Result<()> {
+        match self.max_instr {
+            Some(max_instr) if self.instr_count > max_instr => Err(VmError::TimeoutExceeded(
+                format!("program executed more than {} instructions", max_instr),
+            )
+            .into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check a string of len bytes is within the maximum string length, if there is one.
+    /// Called before building the string so an oversized string is never allocated.
+    ///
+    /// # Errors
+    ///
+    /// VmError::ResourceLimitExceeded if it is not.
+    #[inline]
+    pub fn check_string_len(&self, len: usize) -> Result<()> {
+        match self.max_string_len {
+            Some(max_string_len) if len > max_string_len => {
+                Err(VmError::ResourceLimitExceeded(format!(
+                    "string of length {} is longer than {} bytes",
+                    len, max_string_len
+                ))
+                .into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Check the operand stack of the current thread and the number of environments are within their
+    /// limits, if they have any. Garbage environments count towards the limit until collected, so the
+    /// garbage collector is run before giving up on the environment limit.
+    ///
+    /// # Errors
+    ///
+    /// VmError::ResourceLimitExceeded if either is over its limit.
+    #[inline]
+    pub fn check_limits(&mut self) -> Result<()> {
+        if let Some(max_operand_stack) = self.max_operand_stack {
+            if self.current_thread.operand_stack.len() > max_operand_stack {
+                return Err(VmError::ResourceLimitExceeded(format!(
+                    "operand stack of thread {} has more than {} values",
+                    self.current_thread.thread_id, max_operand_stack
+                ))
+                .into());
+            }
+        }
+
+        if let Some(max_envs) = self.max_envs {
+            if self.env_registry.len() > max_envs {
+                self.garbage_collect()?;
+            }
+            if self.env_registry.len() > max_envs {
+                return Err(VmError::ResourceLimitExceeded(format!(
+                    "program has more than {} environments",
+                    max_envs
+                ))
+                .into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether to run the garbage collector: once gc_alloc_threshold environments were created since
+    /// it last ran, or once gc_interval has passed if any were. Without new environments the heap
+    /// has not grown, e.g. while every thread is blocked or only computing, so collecting is skipped.
     #[inline]
     pub fn should_garbage_collect(&self) -> bool {
-        self.gc_timer.elapsed() >= self.gc_interval
+        self.envs_since_gc >= self.gc_alloc_threshold
+            || (self.envs_since_gc > 0 && self.gc_timer.elapsed() >= self.gc_interval)
     }
 
+    /// Register a new environment with the garbage collector, counting it towards the next collection.
     #[inline]
-    pub fn garbage_collect(mut self) -> Self {
-        self = self.mark_and_weep();
+    pub fn register_env(&mut self, env: Rc<RefCell<Environment>>) {
+        self.env_registry.insert(W(env));
+        self.envs_since_gc += 1;
+    }
+
+    /// Run the garbage collector and reset the GC timer and allocation count.
+    ///
+    /// # Errors
+    ///
+    /// If paranoid GC is on and a live thread can reach an environment that was reclaimed or is not registered.
+    #[inline]
+    pub fn garbage_collect(&mut self) -> Result<()> {
+        self.mark_and_weep();
         self.gc_timer = Instant::now();
-        self
+        self.envs_since_gc = 0;
+
+        if self.paranoid_gc {
+            validate_env_graph(self)?;
+        }
+
+        Ok(())
     }
 
     /// The program is done if the current thread is the main thread and the current thread is done.
@@ -52,18 +170,26 @@ impl Runtime {
         self.done
     }
 
-    pub fn debug_print(&self) {
+    /// Log the instruction about to run and the state of the thread running it
+    pub fn trace_instr(&self) {
         let thread_id = self.current_thread.thread_id;
         let pc = self.current_thread.pc;
-        let instruction = self.instrs.get(pc).expect("PC out of bounds");
-        println!("Thread: {}, PC: {}, {:?}", thread_id, pc, instruction);
-        println!("Operand Stack: {:?}", self.current_thread.operand_stack);
-        println!("Runtime Stack: {:?}", self.current_thread.runtime_stack);
-        println!(
-            "Environment: {:?}",
-            self.current_thread.env.upgrade().unwrap().borrow()
+        let Some(instruction) = self.instrs.get(pc) else {
+            // fetch_instr reports this
+            return;
+        };
+        trace!("Thread: {}, PC: {}, {:?}", thread_id, pc, instruction);
+        trace!(
+            "Instructions: {}, CPU time: {:?}",
+            self.current_thread.instr_count,
+            self.current_thread.cpu_time
         );
-        println!();
+        trace!("Operand Stack: {:?}", self.current_thread.operand_stack);
+        trace!("Runtime Stack: {:?}", self.current_thread.runtime_stack);
+        match self.current_thread.env.upgrade() {
+            Some(env) => trace!("Environment: {:?}", env.borrow()),
+            None => trace!("Environment: reclaimed"),
+        }
     }
 }
 
@@ -82,30 +208,121 @@ impl Runtime {
 /// If an error occurs during execution.
 #[inline]
 pub fn run(mut rt: Runtime) -> Result<Runtime> {
+    run_in_place(&mut rt)?;
+    Ok(rt)
+}
+
+/// Run the program until it is done, like run, but borrowing the runtime so the caller keeps it
+/// even if the program fails. The REPL runs every line of a session in one runtime this way.
+///
+/// # Errors
+///
+/// If an error occurs during execution.
+#[inline]
+pub fn run_in_place(rt: &mut Runtime) -> Result<()> {
+    let instrs = Rc::clone(&rt.instrs);
+    let source_map = rt.source_map.clone();
+    let mut until_timer_check = 0;
+
     loop {
         if rt.is_done() {
             break;
         }
 
-        if rt.should_garbage_collect() {
-            rt = rt.garbage_collect();
+        if rt.gc_stress {
+            rt.garbage_collect()?;
         }
 
-        if rt.time_quantum_expired() {
-            rt = micro_code::yield_(rt)?;
-            continue;
+        // Reading the clock costs more than most instructions, so only check the timers periodically
+        if until_timer_check == 0 {
+            until_timer_check = TIMER_CHECK_INTERVAL;
+
+            rt.check_timeout()?;
+
+            if rt.should_garbage_collect() {
+                rt.garbage_collect()?;
+            }
+
+            if rt.should_preempt()? {
+                micro_code::yield_(rt)?;
+                continue;
+            }
+
+            if rt.busy_waiting() {
+                micro_code::yield_(rt)?;
+                continue;
+            }
         }
+        until_timer_check -= 1;
 
-        if rt.debug {
-            rt.debug_print();
+        if log_enabled!(Level::Trace) {
+            rt.trace_instr();
         }
 
-        let instr = rt.fetch_instr()?;
+        if rt.budget_exceeded() {
+            rt.terminate_over_budget()?;
+            continue;
+        }
+        if rt.current_thread.cancelled {
+            rt.terminate_cancelled()?;
+            continue;
+        }
+        rt.current_thread.instr_count += 1;
+        rt.instr_count += 1;
+        rt.check_max_instr()?;
+
+        let thread_id = rt.current_thread.thread_id;
+        let pc = rt.current_thread.pc;
+        let instr = rt.fetch_instr(&instrs)?;
+
+        let executed = execute(rt, instr)
+            .map_err(|err| with_env_context(err, thread_id, pc))
+            .map_err(|err| at_source_line(err, source_map.as_deref(), pc));
+        if let Err(err) = executed {
+            rt.fail_thread(err)?;
+            continue;
+        }
+        rt.check_limits()
+            .map_err(|err| at_source_line(err, source_map.as_deref(), pc))?;
+    }
+
+    rt.warn_unjoined_failures();
+    Ok(())
+}
 
-        rt = execute(rt, instr)?;
+/// Replace the context-free environment dropped errors raised by instructions and environments with
+/// VmError::EnvironmentReclaimed, which says which thread and instruction hit it.
+fn with_env_context(err: anyhow::Error, thread_id: i64, pc: usize) -> anyhow::Error {
+    let dropped = matches!(
+        err.downcast_ref::<VmError>(),
+        Some(VmError::EnvironmentDroppedError)
+    ) || matches!(
+        err.downcast_ref::<ByteCodeError>(),
+        Some(ByteCodeError::EnvironmentDroppedError)
+    );
+
+    if dropped {
+        VmError::EnvironmentReclaimed { thread_id, pc }.into()
+    } else {
+        err
     }
+}
 
-    Ok(rt)
+/// Add the line of the instruction at pc to the error, if the program has a source map saying which
+fn at_source_line(err: anyhow::Error, source_map: Option<&SourceMap>, pc: usize) -> anyhow::Error {
+    let Some(source_map) = source_map else {
+        return err;
+    };
+
+    match source_map.line(pc) {
+        Some(line) => VmError::AtSourceLine {
+            err,
+            file: source_map.file.clone(),
+            line,
+        }
+        .into(),
+        None => err,
+    }
 }
 
 /// Execute a single instruction, mutating the runtime.
@@ -116,36 +333,43 @@ pub fn run(mut rt: Runtime) -> Result<Runtime> {
 ///
 /// * `instr` - The instruction to execute.
 ///
-/// # Returns
-///
-/// The runtime after the instruction has been executed.
-///
 /// # Errors
 ///
 /// If an error occurs during execution.
 #[inline]
-pub fn execute(rt: Runtime, instr: ByteCode) -> Result<Runtime> {
+pub fn execute(rt: &mut Runtime, instr: &ByteCode) -> Result<()> {
     match instr {
         ByteCode::DONE => micro_code::done(rt),
-        ByteCode::ASSIGN(sym) => micro_code::assign(rt, sym),
+        ByteCode::ASSIGN(sym) => micro_code::assign(rt, sym.clone()),
         ByteCode::LD(sym) => micro_code::ld(rt, sym),
-        ByteCode::LDC(val) => micro_code::ldc(rt, val),
-        ByteCode::LDF(addr, prms) => micro_code::ldf(rt, addr, prms),
+        ByteCode::LDC(val) => micro_code::ldc(rt, val.clone()),
+        ByteCode::LDF(addr, prms) => micro_code::ldf(rt, *addr, prms.clone()),
         ByteCode::POP => micro_code::pop(rt),
-        ByteCode::UNOP(op) => micro_code::unop(rt, op),
-        ByteCode::BINOP(op) => micro_code::binop(rt, op),
-        ByteCode::JOF(pc) => micro_code::jof(rt, pc),
-        ByteCode::GOTO(pc) => micro_code::goto(rt, pc),
-        ByteCode::RESET(ft) => micro_code::reset(rt, ft),
-        ByteCode::ENTERSCOPE(syms) => micro_code::enter_scope(rt, syms),
+        ByteCode::UNOP(op) => micro_code::unop(rt, op.clone()),
+        ByteCode::BINOP(op) => micro_code::binop(rt, op.clone()),
+        ByteCode::JOF(pc) => micro_code::jof(rt, *pc),
+        ByteCode::GOTO(pc) => micro_code::goto(rt, *pc),
+        ByteCode::RESET(ft) => micro_code::reset(rt, ft.clone()),
+        ByteCode::ENTERSCOPE(syms) => micro_code::enter_scope(rt, syms.clone()),
+        ByteCode::ENTERSCOPELOCAL(syms) => micro_code::enter_scope_local(rt, syms),
         ByteCode::EXITSCOPE => micro_code::exit_scope(rt),
-        ByteCode::CALL(arity) => micro_code::call(rt, arity),
-        ByteCode::SPAWN(addr) => micro_code::spawn(rt, addr),
+        ByteCode::EXITSCOPELOCAL => micro_code::exit_scope_local(rt),
+        ByteCode::CALL(arity) => micro_code::call(rt, *arity),
+        ByteCode::SPAWN(addr, moved) => micro_code::spawn(rt, *addr, *moved),
+        ByteCode::SPAWNISOLATED(addr, moved) => micro_code::spawn_isolated(rt, *addr, *moved),
+        ByteCode::SPAWNLIMITED(addr, moved) => micro_code::spawn_limited(rt, *addr, *moved),
         ByteCode::JOIN => micro_code::join(rt),
+        ByteCode::ENTERTHREADSCOPE => micro_code::enter_thread_scope(rt),
+        ByteCode::JOINTHREADSCOPE => micro_code::join_thread_scope(rt),
+        ByteCode::SELECT(ops, has_default) => micro_code::select(rt, ops, *has_default),
         ByteCode::YIELD => micro_code::yield_(rt),
         ByteCode::SEMCREATE => micro_code::sem_create(rt),
         ByteCode::WAIT => micro_code::wait(rt),
         ByteCode::POST => micro_code::post(rt),
+        ByteCode::INC(sym, val) => micro_code::inc(rt, sym, val.clone()),
+        ByteCode::LDLDCBINOP(sym, val, op) => {
+            micro_code::ld_ldc_binop(rt, sym, val.clone(), op.clone())
+        }
     }
 }
 
@@ -200,6 +424,108 @@ mod tests {
         assert_eq!(rt.current_thread.pc, 3);
     }
 
+    #[test]
+    fn test_env_reclaimed_error() {
+        let mut rt = Runtime::new(vec![ByteCode::ldc(1), ByteCode::ld("x"), ByteCode::DONE]);
+        let env = bytecode::Environment::new_wrapped();
+        rt.current_thread.env = bytecode::weak_clone(&env);
+        drop(env);
+
+        let err = run(rt)
+            .err()
+            .expect("Loading from a dropped environment should fail");
+        assert_eq!(
+            err.to_string(),
+            "Environment reclaimed while still in use by thread 1 at pc 1"
+        );
+    }
+
+    #[test]
+    fn test_max_instr() {
+        // infinite loop
+        let mut rt = Runtime::new(vec![ByteCode::GOTO(0)]);
+        rt.set_max_instr(100);
+
+        let err = run(rt)
+            .err()
+            .expect("Loop should hit the instruction limit");
+        assert_eq!(
+            err.to_string(),
+            "Timeout exceeded: program executed more than 100 instructions"
+        );
+
+        let mut rt = Runtime::new(vec![ByteCode::ldc(1), ByteCode::DONE]);
+        rt.set_max_instr(2);
+        assert!(run(rt).is_ok());
+    }
+
+    #[test]
+    fn test_timeout() {
+        let mut rt = Runtime::new(vec![ByteCode::GOTO(0)]);
+        rt.set_timeout(std::time::Duration::from_millis(10));
+
+        let err = run(rt).err().expect("Loop should time out");
+        assert!(err.to_string().starts_with("Timeout exceeded"));
+
+        // calls itself forever
+        let instrs = compiler::compiler::compile_from_string("fn f() -> int { 1 + f() } f()", true)
+            .expect("Should compile");
+        let mut rt = Runtime::new(instrs);
+        rt.set_max_stack_depth(20);
+        let err = run(rt).err().expect("Stack should overflow");
+        assert_eq!(
+            err.to_string(),
+            "Stack overflow: thread 1 nested more than 20 calls and scopes deep, likely from runaway recursion"
+        );
+    }
+
+    #[test]
+    fn test_resource_limits() {
+        let mut rt = Runtime::new(vec![
+            ByteCode::ldc("ab"),
+            ByteCode::ldc("cd"),
+            ByteCode::BINOP(BinOp::Add),
+            ByteCode::DONE,
+        ]);
+        rt.set_max_string_len(3);
+        let err = run(rt).err().expect("String should be too long");
+        assert_eq!(
+            err.to_string(),
+            "Resource limit exceeded: string of length 4 is longer than 3 bytes"
+        );
+
+        // pushes forever
+        let mut rt = Runtime::new(vec![ByteCode::ldc(1), ByteCode::GOTO(0)]);
+        rt.set_max_operand_stack(50);
+        let err = run(rt).err().expect("Operand stack should overflow");
+        assert_eq!(
+            err.to_string(),
+            "Resource limit exceeded: operand stack of thread 1 has more than 50 values"
+        );
+
+        // nests scopes forever, every environment stays reachable
+        let mut rt = Runtime::new(vec![ByteCode::enterscope(vec!["x"]), ByteCode::GOTO(0)]);
+        rt.set_max_envs(50);
+        let err = run(rt).err().expect("Should run out of environments");
+        assert_eq!(
+            err.to_string(),
+            "Resource limit exceeded: program has more than 50 environments"
+        );
+
+        // unreachable environments are collected rather than counted
+        let mut rt = Runtime::new(vec![
+            ByteCode::enterscope(vec!["x"]),
+            ByteCode::EXITSCOPE,
+            ByteCode::GOTO(0),
+        ]);
+        rt.set_max_envs(5);
+        rt.set_max_instr(1000);
+        let err = run(rt)
+            .err()
+            .expect("Loop should hit the instruction limit");
+        assert!(err.to_string().starts_with("Timeout exceeded"));
+    }
+
     #[test]
     fn test_arithmetic() {
         // 42 + 42
@@ -335,10 +661,7 @@ mod tests {
         let rt = Runtime::new(instrs);
         let rt = run(rt)?;
 
-        assert_eq!(
-            rt.current_thread.operand_stack,
-            vec![Value::Int(std::i64::MAX)]
-        );
+        assert_eq!(rt.current_thread.operand_stack, vec![Value::Int(i64::MAX)]);
 
         Ok(())
     }
@@ -374,7 +697,7 @@ mod tests {
 
     #[test]
     fn test_concurrency_01() -> Result<()> {
-        let instrs = vec![ByteCode::SPAWN(1), ByteCode::DONE];
+        let instrs = vec![ByteCode::SPAWN(1, 0), ByteCode::DONE];
 
         let mut rt = Runtime::new(instrs);
         rt.set_time_quantum(Duration::from_millis(u64::MAX)); // Set the time quantum to infinity
@@ -387,7 +710,7 @@ mod tests {
         // The spawn instruction pushes the child thread ID onto the parent thread's operand stack
         assert_eq!(
             rt.current_thread.operand_stack,
-            vec![Value::Int(MAIN_THREAD_ID + 1)]
+            vec![Value::ThreadId(MAIN_THREAD_ID + 1)]
         );
 
         Ok(())
@@ -400,7 +723,7 @@ mod tests {
         // }
         //
         // spawn simple(123);
-        // join 2
+        // join the child
         let instrs = vec![
             ByteCode::enterscope(vec!["simple"]),
             ByteCode::ldf(3, vec!["n"]),
@@ -408,15 +731,14 @@ mod tests {
             ByteCode::ld("n"),
             ByteCode::RESET(FrameType::CallFrame),
             ByteCode::assign("simple"),
-            ByteCode::SPAWN(8), // Parent operand stack will have child tid 2, child operand stack will have
-            ByteCode::GOTO(13), // Parent jump past CALL and DONE
+            ByteCode::SPAWN(8, 0), // Parent operand stack will have child tid 2, child operand stack will have
+            ByteCode::GOTO(13),    // Parent jump past CALL and DONE
             ByteCode::POP,
             ByteCode::ld("simple"),
             ByteCode::ldc(123),
             ByteCode::CALL(1),
             ByteCode::DONE,
-            ByteCode::ldc(MAIN_THREAD_ID + 1), // Load the child tid onto the stack
-            ByteCode::JOIN,
+            ByteCode::JOIN, // Join the child tid spawn left on the stack
             ByteCode::DONE,
         ];
 
@@ -458,9 +780,9 @@ mod tests {
             ByteCode::ldc(1),
             ByteCode::BINOP(BinOp::Add),
             ByteCode::assign("count"),
-            ByteCode::GOTO(6),   // End of function body
-            ByteCode::SPAWN(13), // Parent operand stack will have child tid 2, child operand stack will have
-            ByteCode::GOTO(17),  // Parent jump past CALL and DONE
+            ByteCode::GOTO(6),      // End of function body
+            ByteCode::SPAWN(13, 0), // Parent operand stack will have child tid 2, child operand stack will have
+            ByteCode::GOTO(17),     // Parent jump past CALL and DONE
             ByteCode::POP,
             ByteCode::ld("infinite_increment"),
             ByteCode::CALL(0),
@@ -561,7 +883,7 @@ mod tests {
             // pc 24
             ByteCode::RESET(FrameType::CallFrame), // End of function
             // pc 25
-            ByteCode::SPAWN(28), // Parent operand stack will have child tid 2, child operand stack will have 0
+            ByteCode::SPAWN(28, 0), // Parent operand stack will have child tid 2, child operand stack will have 0
             // pc 26
             ByteCode::assign("tid_2"), // Parent saves the child tid
             // pc 27
@@ -575,7 +897,7 @@ mod tests {
             // pc 31
             ByteCode::DONE, // Child is done
             // pc 32
-            ByteCode::SPAWN(35), // Parent operand stack will have child tid 3, child operand stack will have 0
+            ByteCode::SPAWN(35, 0), // Parent operand stack will have child tid 3, child operand stack will have 0
             // pc 33
             ByteCode::assign("tid_3"), // Parent saves the child tid
             // pc 34
@@ -589,7 +911,7 @@ mod tests {
             // pc 38
             ByteCode::DONE, // Child is done
             // pc 39
-            ByteCode::SPAWN(42), // Parent operand stack will have child tid 4, child operand stack will have 0
+            ByteCode::SPAWN(42, 0), // Parent operand stack will have child tid 4, child operand stack will have 0
             // pc 40
             ByteCode::assign("tid_4"), // Parent loads the child tid
             // pc 41
@@ -733,7 +1055,7 @@ mod tests {
             // pc 31
             ByteCode::RESET(FrameType::CallFrame), // End of function
             // pc 32
-            ByteCode::SPAWN(35), // Parent operand stack will have child tid 2, child operand stack will have 0
+            ByteCode::SPAWN(35, 0), // Parent operand stack will have child tid 2, child operand stack will have 0
             // pc 33
             ByteCode::assign("tid_2"), // Parent saves the child tid
             // pc 34
@@ -747,7 +1069,7 @@ mod tests {
             // pc 38
             ByteCode::DONE, // Child is done
             // pc 39
-            ByteCode::SPAWN(42), // Parent operand stack will have child tid 3, child operand stack will have 0
+            ByteCode::SPAWN(42, 0), // Parent operand stack will have child tid 3, child operand stack will have 0
             // pc 40
             ByteCode::assign("tid_3"), // Parent saves the child tid
             // pc 41
@@ -761,7 +1083,7 @@ mod tests {
             // pc 45
             ByteCode::DONE, // Child is done
             // pc 46
-            ByteCode::SPAWN(49), // Parent operand stack will have child tid 4, child operand stack will have 0
+            ByteCode::SPAWN(49, 0), // Parent operand stack will have child tid 4, child operand stack will have 0
             // pc 47
             ByteCode::assign("tid_4"), // Parent loads the child tid
             // pc 48
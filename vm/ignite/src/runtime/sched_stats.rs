@@ -0,0 +1,192 @@
+use std::{
+    fmt::Display,
+    time::{Duration, Instant},
+};
+
+use bytecode::ThreadID;
+use serde::Serialize;
+
+use crate::{Runtime, Thread};
+
+/// Counters the scheduler keeps so starving threads can be found, see sched_stats and --sched-stats.
+#[derive(Debug, Default)]
+pub struct SchedStats {
+    /// The number of times the current thread was switched for another.
+    pub context_switches: u64,
+    /// The stats of threads that finished or failed, in the order they did.
+    pub finished: Vec<ThreadStats>,
+}
+
+/// How much of the machine a thread got, and how long it waited for it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ThreadStats {
+    pub thread_id: ThreadID,
+    /// Time the thread was the current thread, in microseconds.
+    pub run_time_us: u64,
+    pub instructions: u64,
+    /// The number of times the thread was scheduled after waiting in the ready queue.
+    pub scheduled: u64,
+    /// Total time the thread waited in the ready queue, in microseconds.
+    pub ready_wait_us: u64,
+    pub finished: bool,
+}
+
+impl ThreadStats {
+    fn of(thread: &Thread, run_time: Duration, finished: bool) -> Self {
+        ThreadStats {
+            thread_id: thread.thread_id,
+            run_time_us: micros(run_time),
+            instructions: thread.instr_count,
+            scheduled: thread.scheduled,
+            ready_wait_us: micros(thread.ready_wait),
+            finished,
+        }
+    }
+}
+
+/// The scheduler stats of the program so far, with every thread it has run.
+#[derive(Debug, Serialize)]
+pub struct SchedReport {
+    pub context_switches: u64,
+    /// Average time a thread waited in the ready queue before it was scheduled, in microseconds.
+    pub avg_ready_wait_us: u64,
+    /// Finished and running threads, by thread ID.
+    pub threads: Vec<ThreadStats>,
+}
+
+impl SchedReport {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("Scheduler stats are plain numbers")
+    }
+}
+
+impl Display for SchedReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Context switches: {}", self.context_switches)?;
+        writeln!(
+            f,
+            "Average ready queue wait: {:?}",
+            Duration::from_micros(self.avg_ready_wait_us)
+        )?;
+        write!(
+            f,
+            "{:>8} {:>12} {:>14} {:>10} {:>12}  state",
+            "thread", "run time", "instructions", "scheduled", "ready wait"
+        )?;
+        for t in self.threads.iter() {
+            write!(
+                f,
+                "\n{:>8} {:>12} {:>14} {:>10} {:>12}  {}",
+                t.thread_id,
+                format!("{:?}", Duration::from_micros(t.run_time_us)),
+                t.instructions,
+                t.scheduled,
+                format!("{:?}", Duration::from_micros(t.ready_wait_us)),
+                if t.finished { "finished" } else { "running" }
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl Runtime {
+    /// Make next, taken from the ready queue, the current thread, and return the thread it replaces.
+    /// The CPU time of the replaced thread and the time next waited in the ready queue are accounted.
+    pub fn switch_to(&mut self, mut next: Thread) -> Thread {
+        self.account_cpu_time();
+        if let Some(ready_since) = next.ready_since.take() {
+            next.ready_wait += self.time.saturating_duration_since(ready_since);
+            next.scheduled += 1;
+        }
+        self.sched_stats.context_switches += 1;
+        std::mem::replace(&mut self.current_thread, next)
+    }
+
+    /// Keep the stats of a thread that finished or failed, before it is recycled.
+    pub fn record_finished(&mut self, thread: &Thread) {
+        let stats = ThreadStats::of(thread, thread.cpu_time, true);
+        self.sched_stats.finished.push(stats);
+    }
+
+    /// The scheduler stats so far. The current thread's run time includes its current time quantum,
+    /// and once the program is done the main thread counts as finished.
+    pub fn sched_report(&self) -> SchedReport {
+        let current = &self.current_thread;
+        let running = std::iter::once(ThreadStats::of(
+            current,
+            current.cpu_time + self.time.elapsed(),
+            self.done,
+        ))
+        .chain(
+            self.ready_queue
+                .iter()
+                .chain(self.blocked_queue.iter().map(|(t, _)| t))
+                .map(|t| ThreadStats::of(t, t.cpu_time, false)),
+        );
+
+        let mut threads: Vec<ThreadStats> = self
+            .sched_stats
+            .finished
+            .iter()
+            .cloned()
+            .chain(running)
+            .collect();
+        threads.sort_by_key(|t| t.thread_id);
+
+        let scheduled: u64 = threads.iter().map(|t| t.scheduled).sum();
+        let ready_wait: u64 = threads.iter().map(|t| t.ready_wait_us).sum();
+
+        SchedReport {
+            context_switches: self.sched_stats.context_switches,
+            avg_ready_wait_us: ready_wait.checked_div(scheduled).unwrap_or_default(),
+            threads,
+        }
+    }
+}
+
+/// Mark a thread as waiting in the ready queue from now
+pub fn ready_now(thread: &mut Thread) {
+    thread.ready_since = Some(Instant::now());
+}
+
+fn micros(d: Duration) -> u64 {
+    u64::try_from(d.as_micros()).unwrap_or(u64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Weak;
+
+    use crate::{micro_code, Runtime, Thread, MAIN_THREAD_ID};
+
+    #[test]
+    fn test_sched_report() -> anyhow::Result<()> {
+        let mut rt = Runtime::default();
+        rt.current_thread.pc = 1; // prevent u64 subtraction overflow
+        micro_code::spawn(&mut rt, 0, 0)?;
+        micro_code::yield_(&mut rt)?;
+        micro_code::yield_(&mut rt)?;
+        assert_eq!(rt.current_thread.thread_id, MAIN_THREAD_ID);
+
+        let mut finished = Thread::new(3, Weak::new());
+        finished.instr_count = 7;
+        rt.record_finished(&finished);
+
+        let report = rt.sched_report();
+        assert_eq!(report.context_switches, 2);
+        let ids: Vec<_> = report.threads.iter().map(|t| t.thread_id).collect();
+        assert_eq!(ids, vec![1, 2, 3]);
+        // each thread waited in the ready queue once
+        assert_eq!(report.threads[0].scheduled, 1);
+        assert_eq!(report.threads[1].scheduled, 1);
+        assert!(!report.threads[1].finished);
+        assert_eq!(report.threads[2].instructions, 7);
+        assert!(report.threads[2].finished);
+
+        let json: serde_json::Value = serde_json::from_str(&report.to_json())?;
+        assert_eq!(json["context_switches"], 2);
+        assert_eq!(json["threads"][2]["instructions"], 7);
+
+        Ok(())
+    }
+}
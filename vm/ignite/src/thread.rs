@@ -1,12 +1,24 @@
-use std::{cell::RefCell, rc::Weak};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    rc::{Rc, Weak},
+    time::{Duration, Instant},
+};
 
 use anyhow::Result;
-use bytecode::{weak_clone, Environment, StackFrame, Symbol, ThreadID, Value, W};
+use bytecode::{weak_clone, Environment, Semaphore, StackFrame, Symbol, ThreadID, Value};
+use diagnostics::{Diagnostic, Stage, LOST_THREAD_ERROR};
+use log::debug;
 
-use crate::{Runtime, VmError};
+use crate::{
+    micro_code, ready_now, Callback, Runtime, ThreadErrorPolicy, VmError, MAIN_THREAD_ID,
+    MAX_SCOPE_POOL_SIZE, MAX_THREAD_POOL_SIZE,
+};
 
 /// A thread of execution.
 /// Each thread has its own environment, operand stack, runtime stack, and program counter.
+/// It also keeps count of the instructions it has executed and the time it has been scheduled for,
+/// so the scheduler can see how much of the machine each thread is using.
 #[derive(Debug, Default, Clone)]
 pub struct Thread {
     pub thread_id: ThreadID,
@@ -14,6 +26,38 @@ pub struct Thread {
     pub operand_stack: Vec<Value>,
     pub runtime_stack: Vec<StackFrame>,
     pub pc: usize,
+    /// The number of instructions the thread has executed.
+    pub instr_count: u64,
+    /// The total time the thread has been the current thread.
+    pub cpu_time: Duration,
+    /// When the thread was last put in the ready queue, if it is waiting there.
+    pub ready_since: Option<Instant>,
+    /// The total time the thread has waited in the ready queue.
+    pub ready_wait: Duration,
+    /// The number of times the thread was scheduled after waiting in the ready queue.
+    pub scheduled: u64,
+    /// The maximum number of instructions the thread may execute, set by spawn_limited.
+    /// None means the thread is not limited.
+    pub instr_budget: Option<u64>,
+    /// When the join_timeout the thread is waiting in gives up. None if it is not in join_timeout.
+    pub join_deadline: Option<Instant>,
+    /// Whether cancel was called on the thread. It ends before executing its next instruction.
+    pub cancelled: bool,
+    /// Whether the last parse_int, parse_int_radix, parse_float or json_get call got invalid input, for
+    /// parse_failed.
+    pub parse_failed: bool,
+    /// What the last exec call printed to stdout and stderr, for exec_stdout and exec_stderr.
+    #[cfg(feature = "exec")]
+    pub exec_output: (String, String),
+    /// Semaphores the thread acquired with wait and has not posted since, with how many times.
+    pub held_sems: Vec<(Semaphore, u64)>,
+    /// Builtins like map waiting for a function they called to return, innermost last.
+    pub callbacks: Vec<Callback>,
+    /// Environments of exited local scopes, emptied, for ENTERSCOPELOCAL to reuse.
+    pub scope_pool: Vec<Rc<RefCell<Environment>>>,
+    /// The threads spawned in each thread scope the thread is in, innermost last, for
+    /// JOINTHREADSCOPE to join.
+    pub thread_scopes: Vec<Vec<ThreadID>>,
 }
 
 impl Thread {
@@ -27,6 +71,71 @@ impl Thread {
         }
     }
 
+    /// Record that the thread acquired sem with wait.
+    pub fn acquire(&mut self, sem: Semaphore) {
+        match self.held_sems.iter_mut().find(|(held, _)| held == &sem) {
+            Some((_, count)) => *count += 1,
+            None => self.held_sems.push((sem, 1)),
+        }
+    }
+
+    /// Record that the thread posted sem, releasing one acquisition of it if it holds any.
+    pub fn release(&mut self, sem: &Semaphore) {
+        let Some(idx) = self.held_sems.iter().position(|(held, _)| held == sem) else {
+            return;
+        };
+        self.held_sems[idx].1 -= 1;
+        if self.held_sems[idx].1 == 0 {
+            self.held_sems.swap_remove(idx);
+        }
+    }
+
+    /// Create a new thread with room on its operand stack and runtime stack for the given number of
+    /// values and frames.
+    pub fn with_capacity(
+        thread_id: i64,
+        env: Weak<RefCell<Environment>>,
+        operand_stack: usize,
+        runtime_stack: usize,
+    ) -> Self {
+        Thread {
+            thread_id,
+            env,
+            operand_stack: Vec::with_capacity(operand_stack),
+            runtime_stack: Vec::with_capacity(runtime_stack),
+            ..Default::default()
+        }
+    }
+
+    /// Make sure the operand stack and runtime stack have room for at least the given number of
+    /// values and frames in total.
+    pub fn reserve_stacks(&mut self, operand_stack: usize, runtime_stack: usize) {
+        let len = self.operand_stack.len();
+        self.operand_stack
+            .reserve(operand_stack.saturating_sub(len));
+        let len = self.runtime_stack.len();
+        self.runtime_stack
+            .reserve(runtime_stack.saturating_sub(len));
+    }
+
+    /// Keep the environment of an exited local scope for the next ENTERSCOPELOCAL, if nothing else
+    /// refers to it and the pool has room. Otherwise it is dropped.
+    pub fn recycle_scope(&mut self, scope: Rc<RefCell<Environment>>) {
+        if Rc::strong_count(&scope) > 1
+            || Rc::weak_count(&scope) > 0
+            || self.scope_pool.len() >= MAX_SCOPE_POOL_SIZE
+        {
+            return;
+        }
+
+        {
+            let mut env = scope.borrow_mut();
+            env.env.clear();
+            env.parent = None;
+        }
+        self.scope_pool.push(scope);
+    }
+
     /// Create a new thread with the same environment as the current thread.
     /// But operand stack and runtime stack are empty.
     pub fn spawn_child(&self, thread_id: i64, pc: usize) -> Self {
@@ -36,17 +145,310 @@ impl Thread {
             operand_stack: Vec::new(),
             runtime_stack: Vec::new(),
             pc,
+            ..Default::default()
+        }
+    }
+}
+
+/// Thread lifecycle methods for the runtime.
+impl Runtime {
+    /// Create a new thread, reusing a pooled Thread struct if there is one.
+    /// A pooled thread keeps the stacks it grew, and either way the thread has room for at least the
+    /// runtime's stack capacity.
+    pub fn new_thread(
+        &mut self,
+        thread_id: ThreadID,
+        env: Weak<RefCell<Environment>>,
+        pc: usize,
+    ) -> Thread {
+        let mut thread = self.thread_pool.pop().unwrap_or_default();
+        thread.reserve_stacks(self.operand_stack_capacity, self.runtime_stack_capacity);
+        thread.thread_id = thread_id;
+        thread.env = env;
+        thread.pc = pc;
+        thread
+    }
+
+    /// Return a thread that will not run again to the pool so its stacks can be reused.
+    pub fn recycle_thread(&mut self, mut thread: Thread) {
+        if self.thread_pool.len() >= MAX_THREAD_POOL_SIZE {
+            return;
+        }
+
+        thread.operand_stack.clear();
+        thread.runtime_stack.clear();
+        thread.env = Weak::new();
+        thread.instr_count = 0;
+        thread.cpu_time = Duration::ZERO;
+        thread.ready_since = None;
+        thread.ready_wait = Duration::ZERO;
+        thread.scheduled = 0;
+        thread.instr_budget = None;
+        thread.join_deadline = None;
+        thread.cancelled = false;
+        thread.parse_failed = false;
+        #[cfg(feature = "exec")]
+        {
+            thread.exec_output = Default::default();
+        }
+        thread.held_sems.clear();
+        thread.callbacks.clear();
+        thread.thread_scopes.clear();
+        self.thread_pool.push(thread);
+    }
+
+    /// Store a finished thread until it is joined. Only its result, the top of the operand stack, is kept.
+    /// If there are more zombies than max_zombies, the oldest zombie (lowest thread ID) is reaped.
+    /// Detached threads are never joined, so they are recycled straight away.
+    pub fn add_zombie(&mut self, mut thread: Thread) {
+        if self.detached_threads.contains(&thread.thread_id) {
+            self.recycle_thread(thread);
+            return;
+        }
+
+        let result = thread.operand_stack.pop();
+        thread.operand_stack.clear();
+        thread.operand_stack.extend(result);
+        thread.runtime_stack.clear();
+
+        self.zombie_threads.insert(thread.thread_id, thread);
+
+        let Some(max_zombies) = self.max_zombies else {
+            return;
+        };
+
+        while self.zombie_threads.len() > max_zombies {
+            let oldest = *self
+                .zombie_threads
+                .keys()
+                .min()
+                .expect("There are more zombies than max_zombies");
+            let reaped = self
+                .zombie_threads
+                .remove(&oldest)
+                .expect("Thread ID was taken from zombie threads");
+
+            self.reaped_threads.insert(oldest);
+            self.recycle_thread(reaped);
+        }
+    }
+
+    /// Check whether the thread is still running: it is the current thread or waiting in the ready or
+    /// blocked queue. Finished threads, joined or not, are not alive.
+    pub fn is_alive(&self, tid: ThreadID) -> bool {
+        self.current_thread.thread_id == tid
+            || self.ready_queue.iter().any(|t| t.thread_id == tid)
+            || self.blocked_queue.iter().any(|(t, _)| t.thread_id == tid)
+    }
+
+    /// Remember the semaphores a thread that ended still held, so a thread left waiting on one of
+    /// them can be told which thread never posted it. Called when a thread finishes or fails.
+    pub fn orphan_held_sems(&mut self, thread: &mut Thread) {
+        for (sem, _) in thread.held_sems.drain(..) {
+            if self.orphaned_by(&sem).is_some() {
+                continue;
+            }
+            debug!(
+                "Thread {} ended without posting a semaphore it acquired",
+                thread.thread_id
+            );
+            self.orphaned_sems.push((sem, thread.thread_id));
+        }
+    }
+
+    /// The thread that ended holding sem, if it has not been posted since.
+    pub fn orphaned_by(&self, sem: &Semaphore) -> Option<ThreadID> {
+        self.orphaned_sems
+            .iter()
+            .find(|(orphan, _)| orphan == sem)
+            .map(|(_, owner)| *owner)
+    }
+
+    /// The error for a thread that can never run again because it waits on sem while no other thread
+    /// can run: whichever thread ended holding sem is to blame, if any.
+    pub fn sem_deadlock(&self, waiter: ThreadID, sem: &Semaphore) -> VmError {
+        match self.orphaned_by(sem) {
+            Some(owner) => VmError::OrphanedSemaphore { waiter, owner },
+            None => VmError::NoThreadsInReadyQueue,
+        }
+    }
+
+    /// Detach a thread: it can't be joined, its result is dropped, and the program does not wait for it
+    /// at exit. A thread that already finished is recycled now, and the error of one that failed is
+    /// printed as a warning.
+    ///
+    /// # Errors
+    ///
+    /// If there is no such thread, or it was already joined or reaped.
+    pub fn detach(&mut self, tid: ThreadID) -> Result<()> {
+        if let Some(err) = self.failed_threads.remove(&tid) {
+            self.detached_threads.insert(tid);
+            warn_thread_failed(tid, &err, "it was detached");
+            return Ok(());
+        }
+
+        if let Some(zombie) = self.zombie_threads.remove(&tid) {
+            self.detached_threads.insert(tid);
+            self.recycle_thread(zombie);
+            return Ok(());
+        }
+
+        if !self.is_alive(tid) || tid == MAIN_THREAD_ID {
+            return Err(VmError::IllegalArgument(format!(
+                "thread {} can't be detached, it does not exist, is the main thread or was already joined",
+                tid
+            ))
+            .into());
+        }
+
+        self.detached_threads.insert(tid);
+        Ok(())
+    }
+
+    /// Cancel a thread: it ends before executing its next instruction, and the thread that joins it
+    /// gets Value::Cancelled instead of its result. A thread blocked on a semaphore is moved to the
+    /// ready queue so it can end. A thread that already finished or failed is left as it is.
+    ///
+    /// # Errors
+    ///
+    /// If tid is the main thread or there is no such thread.
+    pub fn cancel(&mut self, tid: ThreadID) -> Result<()> {
+        if tid <= MAIN_THREAD_ID || tid > self.thread_count {
+            return Err(VmError::IllegalArgument(format!(
+                "thread {} can't be cancelled, it does not exist or is the main thread",
+                tid
+            ))
+            .into());
+        }
+
+        if self.current_thread.thread_id == tid {
+            self.current_thread.cancelled = true;
+        } else if let Some(thread) = self.ready_queue.iter_mut().find(|t| t.thread_id == tid) {
+            thread.cancelled = true;
+        } else if let Some(idx) = self
+            .blocked_queue
+            .iter()
+            .position(|(t, _)| t.thread_id == tid)
+        {
+            let (mut thread, _) = self
+                .blocked_queue
+                .remove(idx)
+                .expect("Index was found in the blocked queue");
+            thread.cancelled = true;
+            ready_now(&mut thread);
+            self.ready_queue.push_back(thread);
+        }
+
+        Ok(())
+    }
+
+    /// End the current thread because it was cancelled. The thread finishes as if it ran DONE, with
+    /// Value::Cancelled as its result.
+    ///
+    /// # Errors
+    ///
+    /// If there are no threads in the ready queue to switch to.
+    pub fn terminate_cancelled(&mut self) -> Result<()> {
+        debug!("Thread {} was cancelled", self.current_thread.thread_id);
+        self.current_thread.operand_stack.clear();
+        self.current_thread.operand_stack.push(Value::Cancelled);
+        micro_code::done(self)
+    }
+
+    /// The number of live threads: the current thread and the threads in the ready and blocked queues.
+    pub fn live_thread_count(&self) -> usize {
+        1 + self.ready_queue.len() + self.blocked_queue.len()
+    }
+
+    /// Check whether the current thread has used up its instruction budget.
+    #[inline]
+    pub fn budget_exceeded(&self) -> bool {
+        self.current_thread
+            .instr_budget
+            .is_some_and(|budget| self.current_thread.instr_count >= budget)
+    }
+
+    /// Terminate the current thread because it used up its instruction budget.
+    /// The thread finishes as if it ran DONE, with an error message as its result so the joining thread sees it.
+    ///
+    /// # Errors
+    ///
+    /// If there are no threads in the ready queue to switch to.
+    pub fn terminate_over_budget(&mut self) -> Result<()> {
+        let message = format!(
+            "thread {} exceeded its instruction budget of {}",
+            self.current_thread.thread_id,
+            self.current_thread.instr_budget.unwrap_or_default()
+        );
+
+        self.current_thread.operand_stack.clear();
+        self.current_thread
+            .operand_stack
+            .push(Value::String(message));
+        micro_code::done(self)
+    }
+
+    /// Handle a runtime error of the current thread. Under ThreadErrorPolicy::Isolate a thread other
+    /// than the main thread ends with the error, which is kept for the thread that joins it, and the
+    /// next ready thread runs. A detached thread is never joined, so its error is printed as a warning.
+    ///
+    /// # Errors
+    ///
+    /// The error itself under ThreadErrorPolicy::FailFast, in the main thread, or if there are no
+    /// threads in the ready queue to switch to.
+    pub fn fail_thread(&mut self, err: anyhow::Error) -> Result<()> {
+        if self.thread_error_policy == ThreadErrorPolicy::FailFast
+            || self.current_thread.thread_id == MAIN_THREAD_ID
+        {
+            return Err(err);
         }
+
+        let Some(next_ready_thread) = self.ready_queue.pop_front() else {
+            return Err(err);
+        };
+
+        let mut failed_thread = self.switch_to(next_ready_thread);
+        let tid = failed_thread.thread_id;
+        debug!("Thread {} failed: {}", tid, err);
+        self.record_finished(&failed_thread);
+        self.orphan_held_sems(&mut failed_thread);
+        self.recycle_thread(failed_thread);
+
+        if self.detached_threads.contains(&tid) {
+            warn_thread_failed(tid, &err.to_string(), "it was detached");
+        } else {
+            self.failed_threads.insert(tid, err.to_string());
+        }
+
+        Ok(())
     }
+
+    /// Warn about the threads that failed under ThreadErrorPolicy::Isolate and were never joined, so
+    /// their errors are not lost. Called when the program is done.
+    pub fn warn_unjoined_failures(&self) {
+        let mut failed: Vec<_> = self.failed_threads.iter().collect();
+        failed.sort();
+        for (tid, err) in failed {
+            warn_thread_failed(*tid, err, "it was never joined");
+        }
+    }
+}
+
+fn warn_thread_failed(tid: ThreadID, err: &str, why: &str) {
+    let msg = format!("Thread {} failed and {}: {}", tid, why, err);
+    eprintln!(
+        "{}",
+        Diagnostic::warning(Stage::Runtime, LOST_THREAD_ERROR, &msg)
+    );
 }
 
 #[inline]
 pub fn extend_environment<S, V>(
-    mut rt: Runtime,
+    rt: &mut Runtime,
     env: Weak<RefCell<Environment>>,
     syms: Vec<S>,
     vals: Vec<V>,
-) -> Result<Runtime>
+) -> Result<()>
 where
     S: Into<Symbol>,
     V: Into<Value>,
@@ -61,20 +463,329 @@ where
     let new_env = Environment::new_wrapped();
     new_env.borrow_mut().set_parent(env);
 
-    for (sym, val) in syms.into_iter().zip(vals.into_iter()) {
+    for (sym, val) in syms.into_iter().zip(vals) {
         new_env.borrow_mut().set(sym, val);
     }
 
     rt.current_thread.env = weak_clone(&new_env);
-    rt.env_registry.insert(W(new_env));
+    rt.register_env(new_env);
 
-    Ok(rt)
+    Ok(())
+}
+
+/// Copy the chain of environments starting at env, so a thread spawned with `spawn isolated` sees a
+/// snapshot of its parent's variables and its writes are not visible to other threads.
+/// The root (global) environment only holds builtins and constants and is shared, not copied.
+/// Closures stored in the copied environments, or in values, are pointed at the copies, so
+/// function bodies also run on the snapshot.
+///
+/// # Returns
+///
+/// The copy of env. The copies are registered with the runtime.
+///
+/// # Errors
+///
+/// If an environment in the chain has been dropped.
+pub fn fork_environment(
+    rt: &mut Runtime,
+    env: &Weak<RefCell<Environment>>,
+    values: &mut [Value],
+) -> Result<Weak<RefCell<Environment>>> {
+    // Chain from env up to, but not including, the root
+    let mut chain: Vec<Rc<RefCell<Environment>>> = vec![];
+    let mut current = env.upgrade().ok_or(VmError::EnvironmentDroppedError)?;
+
+    loop {
+        let parent = match &current.borrow().parent {
+            Some(parent) => parent.upgrade().ok_or(VmError::EnvironmentDroppedError)?,
+            None => break,
+        };
+        chain.push(current);
+        current = parent;
+    }
+
+    let root = weak_clone(&current);
+
+    // Copy from the outermost environment inwards so each copy can point to its parent's copy
+    let mut copies: HashMap<*const RefCell<Environment>, Weak<RefCell<Environment>>> =
+        HashMap::new();
+    let mut forked = root;
+
+    for original in chain.iter().rev() {
+        let copy = Environment::new_wrapped();
+        copy.borrow_mut().env = original.borrow().env.clone();
+        copy.borrow_mut().set_parent(forked);
+
+        forked = weak_clone(&copy);
+        copies.insert(Rc::as_ptr(original), forked.clone());
+        rt.register_env(copy);
+    }
+
+    let repoint = |val: &mut Value| {
+        if let Value::Closure { env, .. } = val {
+            if let Some(env_copy) = copies.get(&env.0.as_ptr()) {
+                env.0 = env_copy.clone();
+            }
+        }
+    };
+
+    for copy in copies.values() {
+        let copy = copy.upgrade().ok_or(VmError::EnvironmentDroppedError)?;
+        copy.borrow_mut().env.values_mut().for_each(repoint);
+    }
+    values.iter_mut().for_each(repoint);
+
+    Ok(forked)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use bytecode::Value;
+    use crate::{DEFAULT_OPERAND_STACK_CAPACITY, DEFAULT_RUNTIME_STACK_CAPACITY};
+    use bytecode::{Value, W};
+
+    #[test]
+    fn test_thread_pool() {
+        let mut rt = Runtime::default();
+
+        let mut t = rt.new_thread(2, Weak::new(), 5);
+        assert_eq!(t.thread_id, 2);
+        assert_eq!(t.pc, 5);
+
+        t.operand_stack.reserve(100);
+        t.operand_stack.push(Value::Int(1));
+        rt.recycle_thread(t);
+        assert_eq!(rt.thread_pool.len(), 1);
+
+        // reused thread is clean but keeps its allocation
+        let t = rt.new_thread(3, Weak::new(), 7);
+        assert_eq!(t.thread_id, 3);
+        assert_eq!(t.pc, 7);
+        assert!(t.operand_stack.is_empty());
+        assert!(t.operand_stack.capacity() >= 100);
+        assert!(rt.thread_pool.is_empty());
+
+        // pool is bounded
+        for i in 0..MAX_THREAD_POOL_SIZE + 10 {
+            rt.recycle_thread(Thread::new(i as ThreadID, Weak::new()));
+        }
+        assert_eq!(rt.thread_pool.len(), MAX_THREAD_POOL_SIZE);
+    }
+
+    #[test]
+    fn test_stack_capacity() {
+        let mut rt = Runtime::default();
+        assert!(rt.current_thread.operand_stack.capacity() >= DEFAULT_OPERAND_STACK_CAPACITY);
+        assert!(rt.current_thread.runtime_stack.capacity() >= DEFAULT_RUNTIME_STACK_CAPACITY);
+
+        rt.set_stack_capacity(200, 50);
+        assert!(rt.current_thread.operand_stack.capacity() >= 200);
+        assert!(rt.current_thread.runtime_stack.capacity() >= 50);
+
+        // new and pooled threads both get the capacity
+        let t = rt.new_thread(2, Weak::new(), 0);
+        assert!(t.operand_stack.capacity() >= 200);
+        assert!(t.runtime_stack.capacity() >= 50);
+
+        rt.recycle_thread(Thread::new(3, Weak::new()));
+        let t = rt.new_thread(3, Weak::new(), 0);
+        assert!(t.operand_stack.capacity() >= 200);
+        assert!(t.runtime_stack.capacity() >= 50);
+    }
+
+    #[test]
+    fn test_add_zombie() {
+        let mut rt = Runtime::default();
+
+        // only the result is kept
+        let mut t = Thread::new(2, Weak::new());
+        t.operand_stack = vec![Value::Int(1), Value::Int(2)];
+        rt.add_zombie(t);
+        assert_eq!(rt.zombie_threads[&2].operand_stack, vec![Value::Int(2)]);
+
+        // no limit by default
+        for i in 3..20 {
+            rt.add_zombie(Thread::new(i, Weak::new()));
+        }
+        assert_eq!(rt.zombie_threads.len(), 18);
+        assert!(rt.reaped_threads.is_empty());
+
+        // oldest zombies are reaped past the limit
+        rt.set_max_zombies(2);
+        rt.add_zombie(Thread::new(20, Weak::new()));
+        assert_eq!(rt.zombie_threads.len(), 2);
+        assert!(rt.zombie_threads.contains_key(&19));
+        assert!(rt.zombie_threads.contains_key(&20));
+        assert_eq!(rt.reaped_threads.len(), 17);
+        assert!(rt.reaped_threads.contains(&2));
+    }
+
+    #[test]
+    fn test_detach() {
+        let mut rt = Runtime::default();
+        rt.ready_queue.push_back(Thread::new(2, Weak::new()));
+        rt.add_zombie(Thread::new(3, Weak::new()));
+
+        // running thread: recycled instead of becoming a zombie when it finishes
+        rt.detach(2).unwrap();
+        let t = rt.ready_queue.pop_front().unwrap();
+        rt.add_zombie(t);
+        assert!(!rt.zombie_threads.contains_key(&2));
+
+        // finished thread: recycled now
+        rt.detach(3).unwrap();
+        assert!(rt.zombie_threads.is_empty());
+        assert_eq!(rt.thread_pool.len(), 2);
+
+        assert!(rt.detach(MAIN_THREAD_ID).is_err());
+        assert!(rt.detach(42).is_err());
+    }
+
+    #[test]
+    fn test_cancel() {
+        let mut rt = Runtime {
+            thread_count: 4,
+            ..Default::default()
+        };
+        rt.ready_queue.push_back(Thread::new(2, Weak::new()));
+        rt.blocked_queue
+            .push_back((Thread::new(3, Weak::new()), Semaphore::new(0)));
+        rt.add_zombie(Thread::new(4, Weak::new()));
+
+        rt.cancel(2).unwrap();
+        assert!(rt.ready_queue[0].cancelled);
+
+        // blocked thread: woken so it can end
+        rt.cancel(3).unwrap();
+        assert!(rt.blocked_queue.is_empty());
+        assert_eq!(rt.ready_queue[1].thread_id, 3);
+        assert!(rt.ready_queue[1].cancelled);
+
+        // finished thread: left as it is
+        rt.cancel(4).unwrap();
+        assert!(rt.zombie_threads.contains_key(&4));
+
+        assert!(rt.cancel(MAIN_THREAD_ID).is_err());
+        assert!(rt.cancel(5).is_err());
+
+        // the current thread is cancelled by the run loop
+        let next = rt.ready_queue.pop_front().unwrap();
+        rt.switch_to(next);
+        rt.terminate_cancelled().unwrap();
+        assert_eq!(rt.zombie_threads[&2].operand_stack, vec![Value::Cancelled]);
+    }
+
+    #[test]
+    fn test_orphan_held_sems() {
+        let mut rt = Runtime::default();
+        let (lock, other) = (Semaphore::new(0), Semaphore::new(0));
+
+        let mut t = Thread::new(2, Weak::new());
+        t.acquire(lock.clone());
+        t.acquire(lock.clone());
+        t.acquire(other.clone());
+        t.release(&lock);
+        t.release(&other);
+        assert_eq!(t.held_sems, vec![(lock.clone(), 1)]);
+
+        rt.orphan_held_sems(&mut t);
+        assert!(t.held_sems.is_empty());
+        assert_eq!(rt.orphaned_by(&lock), Some(2));
+        assert_eq!(rt.orphaned_by(&other), None);
+        assert_eq!(
+            rt.sem_deadlock(MAIN_THREAD_ID, &lock).to_string(),
+            "Deadlock: thread 1 is waiting on a semaphore thread 2 acquired and never posted before it ended"
+        );
+        assert_eq!(
+            rt.sem_deadlock(MAIN_THREAD_ID, &other).to_string(),
+            "No threads in ready queue"
+        );
+    }
+
+    #[test]
+    fn test_fail_thread() {
+        let mut rt = Runtime::default();
+        rt.ready_queue.push_back(Thread::new(2, Weak::new()));
+        micro_code::yield_(&mut rt).unwrap();
+
+        // fail fast by default
+        assert!(rt.fail_thread(VmError::DivisionByZero.into()).is_err());
+
+        rt.set_thread_error_policy(ThreadErrorPolicy::Isolate);
+        rt.fail_thread(VmError::DivisionByZero.into()).unwrap();
+        assert_eq!(rt.current_thread.thread_id, MAIN_THREAD_ID);
+        assert!(!rt.is_alive(2));
+        assert_eq!(rt.failed_threads[&2], "Division by zero");
+
+        // the main thread's errors always end the program
+        assert!(rt.fail_thread(VmError::DivisionByZero.into()).is_err());
+
+        // a detached thread's error is not kept
+        rt.detach(2).unwrap();
+        assert!(rt.failed_threads.is_empty());
+    }
+
+    #[test]
+    fn test_fork_environment() -> Result<()> {
+        let mut rt = Runtime::default();
+        let global = rt.current_thread.env.upgrade().unwrap();
+
+        let outer = Environment::new_wrapped();
+        outer.borrow_mut().set_parent(weak_clone(&global));
+        outer.borrow_mut().set("count", 1);
+        outer.borrow_mut().set(
+            "f",
+            Value::Closure {
+                fn_type: bytecode::FnType::User,
+                sym: "f".to_string(),
+                prms: vec![],
+                addr: 0,
+                env: W(weak_clone(&outer)),
+            },
+        );
+
+        let inner = Environment::new_wrapped();
+        inner.borrow_mut().set_parent(weak_clone(&outer));
+        inner.borrow_mut().set("x", 2);
+
+        let registered = rt.env_registry.len();
+        let mut moved = [outer.borrow().get(&"f".to_string())?, Value::Int(3)];
+        let forked = fork_environment(&mut rt, &weak_clone(&inner), &mut moved)?;
+        let forked = forked.upgrade().unwrap();
+
+        // inner and outer were copied, the global environment is shared
+        assert_eq!(rt.env_registry.len(), registered + 2);
+        assert!(!Rc::ptr_eq(&forked, &inner));
+        let forked_outer = forked.borrow().parent.clone().unwrap().upgrade().unwrap();
+        assert!(!Rc::ptr_eq(&forked_outer, &outer));
+        let forked_global = forked_outer
+            .borrow()
+            .parent
+            .clone()
+            .unwrap()
+            .upgrade()
+            .unwrap();
+        assert!(Rc::ptr_eq(&forked_global, &global));
+
+        // writes to the copy are not seen by the original
+        assert_eq!(forked.borrow().get(&"count".to_string())?, Value::Int(1));
+        forked.borrow_mut().update("count", 5)?;
+        assert_eq!(outer.borrow().get(&"count".to_string())?, Value::Int(1));
+        assert_eq!(forked.borrow().get(&"count".to_string())?, Value::Int(5));
+
+        // closures point at the copied environment
+        let Value::Closure { env, .. } = forked.borrow().get(&"f".to_string())? else {
+            panic!("f should be a closure");
+        };
+        assert!(Rc::ptr_eq(&env.0.upgrade().unwrap(), &forked_outer));
+        // and so do closures in the values given
+        let [Value::Closure { env, .. }, Value::Int(3)] = &moved else {
+            panic!("f should be a closure");
+        };
+        assert!(Rc::ptr_eq(&env.0.upgrade().unwrap(), &forked_outer));
+
+        Ok(())
+    }
 
     #[test]
     fn test_extend_environment_err() -> Result<()> {
@@ -97,7 +808,7 @@ mod tests {
 
         let empty: Vec<String> = Vec::new();
         let current_env = rt.current_thread.env.clone();
-        let result = extend_environment(rt, current_env, vec!["c", "d"], empty);
+        let result = extend_environment(&mut rt, current_env, vec!["c", "d"], empty);
         assert!(result.is_err());
 
         Ok(())
@@ -123,8 +834,8 @@ mod tests {
             .set("b", 123);
 
         let current_env = rt.current_thread.env.clone();
-        let rt = extend_environment(
-            rt,
+        extend_environment(
+            &mut rt,
             current_env,
             vec!["c", "d"],
             vec![Value::Float(12.3), Value::Bool(true)],
@@ -2,24 +2,36 @@ use anyhow::Result;
 use assert_cmd::prelude::*;
 use compiler::compiler::compile_from_string;
 use predicates::prelude::*;
+use std::path::Path;
 use std::process::Command;
 
 const IGNITE_BINARY: &str = "ignite";
 const OXIDATE_BINARY: &str = "oxidate";
 
-// Have to use random file name because tests run in parallel
-// With fixed filename we get errors due to race conditions
+/// ignite, caching the .rst files it runs in the tests' own directory rather than the user's cache
+fn ignite() -> Result<Command> {
+    let mut cmd = Command::cargo_bin(IGNITE_BINARY)?;
+    cmd.env(
+        "RUSTSCRIPT_CACHE_DIR",
+        Path::new(env!("CARGO_TARGET_TMPDIR")).join("rustscript-cache"),
+    );
+    Ok(cmd)
+}
+
+// The compiled file goes in a temporary directory of its own, since tests run in parallel, and is
+// removed with the directory even if the test fails. exp is what the program prints followed by its
+// result, which ignite only prints with --echo-result.
 fn test_pass(inp: &str, exp: &str) -> Result<()> {
-    let file_num = rand::random::<u128>().to_string();
-    let file_name = format!("./{file_num}.o2");
+    let dir = tempfile::tempdir()?;
+    let file_name = dir.path().join("test.o2");
 
-    let mut cmd = Command::cargo_bin(IGNITE_BINARY)?;
+    let mut cmd = ignite()?;
     let comp = compile_from_string(inp, true)?;
 
     let mut file = std::fs::File::create(file_name.clone())?;
     bytecode::write_bytecode(&comp, &mut file)?;
 
-    cmd.arg(file_name.clone());
+    cmd.arg(file_name.clone()).arg("--echo-result");
     let exp = if exp.is_empty() {
         String::from("")
     } else {
@@ -27,24 +39,47 @@ fn test_pass(inp: &str, exp: &str) -> Result<()> {
     };
     cmd.assert().success().stdout(predicate::eq(exp));
 
-    std::fs::remove_file(file_name)?;
+    Ok(())
+}
+
+// Expect the program to exit with the given code, stdout and stderr (stderr checked with contains)
+fn test_exit(inp: &str, code: i32, exp_out: &str, exp_err: &str) -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let file_name = dir.path().join("test.o2");
+
+    let mut cmd = ignite()?;
+    let comp = compile_from_string(inp, true)?;
+
+    let mut file = std::fs::File::create(file_name.clone())?;
+    bytecode::write_bytecode(&comp, &mut file)?;
+
+    cmd.arg(file_name.clone());
+    let res = cmd.assert();
+
+    res.code(code)
+        .stdout(predicate::eq(exp_out))
+        .stderr(predicate::str::contains(exp_err));
 
     Ok(())
 }
 
-// Test files in example/
+// Test files in example/, echoing their result like test_pass
 // file_name is expected to be prefix before .rst
 fn test_file(file_name: &str, exp: &str) -> Result<()> {
     let file_name_rst = format!("../../example/{file_name}.rst");
+    let dir = tempfile::tempdir()?;
+    let out = dir.path().join(file_name);
 
     let mut cmd = Command::cargo_bin(OXIDATE_BINARY)?;
-    cmd.arg(file_name_rst.clone()).assert().success();
-
-    dbg!(format!("{file_name}.o2"));
+    cmd.arg(file_name_rst.clone())
+        .arg("-o")
+        .arg(&out)
+        .assert()
+        .success();
 
-    let file_name_o2 = format!("{file_name}.o2");
+    let file_name_o2 = out.with_extension("o2");
 
-    let mut cmd_vm = Command::cargo_bin(IGNITE_BINARY)?;
+    let mut cmd_vm = ignite()?;
 
     let exp = if exp.is_empty() {
         String::from("")
@@ -54,10 +89,10 @@ fn test_file(file_name: &str, exp: &str) -> Result<()> {
 
     cmd_vm
         .arg(file_name_o2.clone())
+        .arg("--echo-result")
         .assert()
         .success()
         .stdout(predicate::eq(exp));
-    std::fs::remove_file(format!("./{file_name}.o2"))?;
 
     Ok(())
 }
@@ -70,6 +105,50 @@ fn test_e2e_example_folder() -> Result<()> {
     test_file("loop-03", "55")?;
     test_file("loop-04", "20")?;
     test_file("type-01", "33")?;
+    test_file(
+        "matmul-threaded",
+        "[[3.0, 8.0, 13.0], [6.0, 14.0, 22.0], [9.0, 20.0, 31.0]]\ntrue",
+    )?;
+    Ok(())
+}
+
+// The concurrent examples must print the same however their threads are interleaved, so besides the
+// default time quantum they also run preempted after every instruction and every millisecond
+#[test]
+fn test_e2e_concurrent_examples() -> Result<()> {
+    let examples = [
+        ("dining-philosophers", "15"),
+        ("producer-consumer", "55"),
+        ("bounded-buffer", "210"),
+        ("parallel-sum", "500500"),
+        ("parallel-loop", "285.0"),
+        ("thread-scope", "500500"),
+        ("select", "5050"),
+        ("barrier-phases", "9\ntrue"),
+        (
+            "concurrency-04",
+            "Spawning 3 threads\nJoining 3 threads\n3000",
+        ),
+    ];
+
+    for (file, exp) in examples {
+        test_file(file, exp)?;
+
+        for quantum in ["0", "1"] {
+            let mut cmd = ignite()?;
+            cmd.arg("run")
+                .arg(format!("../../example/{file}.rst"))
+                .arg("--quantum")
+                .arg(quantum)
+                .arg("--timeout")
+                .arg("30s")
+                .arg("--echo-result");
+            cmd.assert()
+                .success()
+                .stdout(predicate::eq(format!("{exp}\n")));
+        }
+    }
+
     Ok(())
 }
 
@@ -92,17 +171,25 @@ fn test_e2e_simple() -> Result<()> {
     test_pass("2+2*3", "8")?;
     test_pass("(2+2)*3", "12")?;
     test_pass("2*3+2", "8")?;
-    test_pass("2-3+4/5*6-8+9", "0")?; // because 4/5 = 0 not float
+    test_pass("2-3+4/5*6-8+9", "0")?; // because 4/5 = 0 not float, use fdiv(4, 5) for 0.8
     test_pass("(2*3+(4-(6*5)))*(10-(20)*(3+2))", "1800")?;
     test_pass(
         "5.67 * 8.91 / 2.34 + 6.78 - 9.87 - 4.32",
         "14.179615384615389",
     )?;
+    test_pass("let x = 3; +x * +2 - +-1", "7")?;
 
     // bool ops
     test_pass("!true && false", "false")?;
     test_pass("false == (3 > 5)", "true")?;
     test_pass("false == (3 < 5)", "false")?;
+    test_pass("3 <= 3 && 3 >= 3 && !(2 >= 3)", "true")?;
+
+    // strings are compared by code point
+    test_pass(
+        r#"println("apple" < "banana"); println("Zebra" < "apple"); "abc" >= "ab""#,
+        "true\ntrue\ntrue",
+    )?;
     test_pass("(true || false) && false", "false")?;
     test_pass("true || false && false", "true")?; // true || (false && false) - && has higher prec
 
@@ -231,6 +318,30 @@ fn test_e2e_if_else() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_e2e_if_braceless() -> Result<()> {
+    test_pass(
+        r"
+    fn sign(x: int) -> int {
+        if (x > 0) 1 else if (x < 0) -1 else 0
+    }
+    let i = -3;
+    let total = 0;
+    loop {
+        if (i > 3) break;
+        total = total + if (i == 0) 100 else sign(i) * 10;
+        i = i + 1;
+    }
+    total
+    ",
+        "100",
+    )?;
+
+    test_pass("let x = 1; if (x > 0) x = x + 5; x", "6")?;
+
+    Ok(())
+}
+
 #[test]
 fn test_e2e_lexical_scope() -> Result<()> {
     let t = r"
@@ -613,3 +724,1217 @@ fn test_e2e_fn_decl() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_e2e_exit() -> Result<()> {
+    // last value is not printed after exit
+    test_exit("println(1); exit(0); println(2); 3", 0, "1\n", "")?;
+    test_exit("exit(42);", 42, "", "")?;
+
+    // exit from inside fn and loop
+    let t = r"
+    fn check(x: int) {
+        if x > 2 {
+            println(x);
+            exit(3);
+        }
+    }
+
+    let i = 0;
+    loop {
+        check(i);
+        i = i + 1;
+    }
+    ";
+    test_exit(t, 3, "3\n", "")?;
+
+    // exit from a spawned thread stops the whole program
+    let t = r"
+    fn f() {
+        exit(7);
+    }
+    let t = spawn f();
+    join t;
+    println(1);
+    ";
+    test_exit(t, 7, "", "")?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_runtime_err_exit_code() -> Result<()> {
+    // runtime errors go to stderr with a nonzero exit code
+    test_exit(r#"println(1); atoi("abc")"#, 1, "1\n", "invalid digit")?;
+    Ok(())
+}
+
+#[test]
+fn test_e2e_stderr_print() -> Result<()> {
+    let t = r#"
+    eprint("err ");
+    println("out");
+    eprintln(2);
+    let x : () = eprintln("done");
+    x
+    "#;
+    test_exit(t, 0, "out\n", "err 2\ndone\n")?;
+    Ok(())
+}
+
+#[test]
+fn test_e2e_numeric_formatting() -> Result<()> {
+    test_pass("to_fixed(14.179615384615389, 3)", "14.180")?;
+    test_pass(
+        "println(format_int(10, 2)); format_int(-255, 16)",
+        "1010\n-ff",
+    )?;
+
+    // shortest form that reads back the same by default, for print, println and the result
+    test_pass(
+        "print(0.1 + 0.2); println(2.0); eprintln(1.5); 1.0 / 3.0",
+        "0.300000000000000042.0\n0.3333333333333333",
+    )?;
+    test_pass(
+        "set_float_precision(2); println(2.0 / 3.0); print(1.0); set_float_precision(-1); 2.5",
+        "0.67\n1.002.5",
+    )?;
+    test_exit(
+        "set_float_precision(101)",
+        1,
+        "",
+        "set_float_precision digits must be -1 or between 0 and 100, got 101",
+    )?;
+    Ok(())
+}
+
+#[test]
+fn test_e2e_math_builtins() -> Result<()> {
+    test_pass("floor(2.7) + ceil(0.2) + round(1.5) + trunc(-1.9)", "4.0")?;
+    test_pass("hypot(3.0, 4.0)", "5.0")?;
+    test_pass("log2(1024.0)", "10.0")?;
+    test_pass("clamp(15, 0, 10)", "10")?;
+    test_exit(
+        "clamp(1, 2, 0)",
+        1,
+        "",
+        "clamp bounds must satisfy lo <= hi, got Int 2 and Int 0",
+    )?;
+    test_exit(
+        "clamp(1.0, 2.5, 0.5)",
+        1,
+        "",
+        "clamp bounds must satisfy lo <= hi, got Float 2.5 and Float 0.5",
+    )?;
+    test_exit(
+        "clamp(1.0, 2.0, 0.0)",
+        1,
+        "",
+        "clamp bounds must satisfy lo <= hi, got Float 2.0 and Float 0.0",
+    )?;
+    Ok(())
+}
+
+#[test]
+fn test_e2e_nan_infinity() -> Result<()> {
+    test_pass("NAN == NAN", "false")?;
+    test_pass(
+        "is_nan(NAN) && !is_finite(INFINITY) && is_finite(2.0)",
+        "true",
+    )?;
+    test_pass("println(0.0 / 0.0); 1.0 / 0.0", "NaN\ninf")?;
+    test_pass("let x : float = PI; x > 3.14", "true")?;
+    Ok(())
+}
+
+#[test]
+fn test_e2e_int_division() -> Result<()> {
+    test_pass("-7 / 2", "-3")?;
+    test_pass("div(-7, 2)", "-4")?;
+    test_pass("fdiv(4, 5)", "0.8")?;
+    test_exit("println(1); 1 / 0", 1, "1\n", "Division by zero")?;
+    Ok(())
+}
+
+#[test]
+fn test_e2e_clone() -> Result<()> {
+    test_pass(r#"let s = "abc"; let t = clone(s); t == s"#, "true")?;
+    test_pass("clone(2) + clone(3)", "5")?;
+
+    // a matrix copy is equal to the original until one of them changes
+    let t = r"
+    let m = matrix(2, 2, 1.0);
+    let copy = clone(m);
+    println(m == copy);
+    mat_set(copy, 0, 1, 3.0);
+    println(m == copy);
+    m == matrix(2, 2, 1.0)
+    ";
+    test_pass(t, "true\nfalse\ntrue")?;
+    test_pass("matrix(2, 1, 0.0) == matrix(1, 2, 0.0)", "false")?;
+
+    // queues and semaphores are shared, so clone gives the same one
+    let t = r"
+    let q = queue_create();
+    let alias = clone(q);
+    queue_push(alias, 1);
+    println(q == alias);
+    queue_pop(q)
+    ";
+    test_pass(t, "true\n1")?;
+    test_pass(
+        "let s = sem_create(); clone(s) == s && !(sem_create() == s)",
+        "true",
+    )?;
+    Ok(())
+}
+
+#[test]
+fn test_e2e_type_of_dbg() -> Result<()> {
+    test_pass(
+        "println(type_of(2)); println(type_of(2.5)); type_of(\"s\")",
+        "Int\nFloat\nString",
+    )?;
+    test_exit(
+        r#"let x = 2; let y = dbg(x * 3) + 1; dbg("hi"); println(y)"#,
+        0,
+        "7\n",
+        "[dbg] (x*3) = 6\n[dbg] hi = \"hi\"\n",
+    )?;
+    // called through another name, dbg has no expression text to print
+    test_exit("let d = dbg; d(3); println(4)", 0, "4\n", "[dbg] 3\n")?;
+    // functions the program declares are called as they are, even with a builtin's name
+    test_pass("fn dbg(x: int) -> int { x * 2 } println(dbg(3));", "6")?;
+    test_pass("fn sem_create() -> int { 5 } sem_create()", "5")?;
+    test_pass(
+        "fn spawn_limited(x: int, y: int) -> int { x + y } spawn_limited(1, 2)",
+        "3",
+    )?;
+
+    // with a source map, dbg says where it was called
+    let dir = tempfile::tempdir()?;
+    std::fs::write(
+        dir.path().join("test.rst"),
+        "let x = 2;\nlet y = dbg(x + 1);\nlet d = dbg;\nd(y);",
+    )?;
+    ignite()?
+        .current_dir(&dir)
+        .args(["run", "test.rst"])
+        .assert()
+        .success()
+        .stderr(predicate::eq(
+            "[dbg test.rst:2] (x+1) = 3\n[dbg test.rst:4] 3\n",
+        ));
+    Ok(())
+}
+
+#[test]
+fn test_e2e_json() -> Result<()> {
+    let t = r#"
+    let m = matrix(2, 2, 0.5);
+    mat_set(m, 1, 0, 2.0);
+    let doc = json_stringify(m);
+    println(doc);
+    println(parse_float(json_get(doc, "1.0")) * 2.0);
+    let missing = json_get(doc, "2");
+    println(parse_failed());
+    println(json_stringify(type_of(1)));
+    json_stringify(true)
+    "#;
+    test_pass(t, "[[0.5,0.5],[2.0,0.5]]\n4.0\ntrue\n\"Int\"\ntrue")?;
+
+    test_exit(
+        "json_stringify(0.0 / 0.0)",
+        1,
+        "",
+        "json_stringify can't encode NaN",
+    )?;
+    Ok(())
+}
+
+#[cfg(all(feature = "exec", unix))]
+#[test]
+fn test_e2e_exec() -> Result<()> {
+    let t = r#"
+    let code = exec("echo hello; echo oops >&2; exit 2", 1000);
+    print(exec_stdout());
+    print(exec_stderr());
+    println(code);
+    exec("sleep 5", 50)
+    "#;
+    test_pass(t, "hello\noops\n2\n-1")?;
+    Ok(())
+}
+
+#[test]
+fn test_e2e_vars() -> Result<()> {
+    // the function body's locals first, then its parameters, then the scope it was declared in
+    let t = r"
+    let x = 2;
+    let s = 1.5;
+    fn f(s: int) -> str {
+        let y = true;
+        vars()
+    }
+    println(f(3));
+    ";
+    test_pass(t, "y: Bool\ns: Int\nf: Closure\nx: Int")?;
+    // a shadowed name is listed once, with the inner binding's type
+    test_pass("let x = 1; { let x = true; vars() }", "x: Bool")?;
+    Ok(())
+}
+
+#[test]
+fn test_e2e_spawn_isolated() -> Result<()> {
+    // writes by an isolated thread, directly or through a function, stay in its own copy
+    let t = r"
+    let count = 0;
+
+    fn incr(n: int) -> int {
+        let i = 0;
+        loop i < n {
+            count = count + 1;
+            i = i + 1;
+        }
+        count
+    }
+
+    let t1 = spawn isolated incr(10);
+    let t2 = spawn isolated incr(5);
+    let r1 = join t1;
+    let r2 = join t2;
+    println(r1);
+    println(r2);
+    count
+    ";
+    test_pass(t, "10\n5\n0")?;
+
+    // without isolated, the threads share count
+    let t = r"
+    let count = 0;
+
+    fn incr(n: int) -> int {
+        let i = 0;
+        loop i < n {
+            count = count + 1;
+            i = i + 1;
+        }
+        count
+    }
+
+    let t1 = spawn incr(10);
+    let t2 = spawn incr(5);
+    join t1;
+    join t2;
+    count
+    ";
+    test_pass(t, "15")?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_spawn_args_in_parent() -> Result<()> {
+    // the arguments are evaluated by the parent at the spawn, before it goes on
+    let t = r#"
+    fn arg() -> int {
+        println("arg");
+        5
+    }
+    fn show(x: int) -> int {
+        println(x);
+        x
+    }
+
+    let x = 1;
+    let t1 = spawn show(x);
+    let t2 = spawn show(arg());
+    x = 2;
+    println("parent");
+    join t1;
+    join t2;
+    x
+    "#;
+    test_pass(t, "arg\nparent\n1\n5\n2")?;
+
+    // so does the function: reassigning it after the spawn doesn't change what the child calls
+    let t = r"
+    fn one() -> int { 1 }
+    fn two() -> int { 2 }
+    let f = one;
+    let t = spawn f();
+    f = two;
+    join t
+    ";
+    test_pass(t, "1")?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_spawn_limited() -> Result<()> {
+    // a runaway thread is terminated, its join gives the error and the rest of the program goes on
+    let t = r"
+    fn spin() {
+        loop {}
+    }
+
+    fn work() -> int {
+        let i = 0;
+        loop i < 10 {
+            i = i + 1;
+        }
+        i
+    }
+
+    let t1 = spawn_limited(spin, 1000);
+    let t2 = spawn_limited(work, 1000);
+    let r1 = join t1;
+    let r2 = join t2;
+    println(r1);
+    r2
+    ";
+    test_pass(t, "thread 2 exceeded its instruction budget of 1000\n10")?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_sem_create_initial_value() -> Result<()> {
+    // the child posts to a semaphore created at 0, so the parent waits for it
+    let t = r"
+    let done = sem_create(0);
+    println(sem_value(done));
+
+    fn worker() {
+        println(1);
+        post done;
+    }
+
+    spawn worker();
+    wait done;
+    println(2);
+
+    let s = sem_create(5);
+    sem_set(s, 2);
+    sem_value(s)
+    ";
+    test_pass(t, "0\n1\n2\n2")?;
+
+    // wait and post take any expression that evaluates to a semaphore
+    let t = r"
+    let first = sem_create(1);
+    let second = sem_create(0);
+    fn pick(one: bool) -> sem {
+        if one { first } else { second }
+    }
+    wait pick(true);
+    post pick(false);
+    wait pick(false);
+    sem_value(first) + sem_value(second)
+    ";
+    test_pass(t, "0")?;
+
+    // the count is optional however sem_create is called
+    test_pass(
+        "let c = sem_create; println(c()); println(c(3));",
+        "semaphore\nsemaphore",
+    )?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_orphaned_sem() -> Result<()> {
+    // a thread that ends holding a lock is named when another thread waits for it forever
+    let t = r"
+    let lock = sem_create(1);
+    fn forgets() {
+        wait lock;
+    }
+    fn polite() {
+        wait lock;
+        post lock;
+    }
+    let a = spawn forgets();
+    let b = spawn polite();
+    join a;
+    join b;
+    ";
+    test_exit(
+        t,
+        1,
+        "",
+        "Deadlock: thread 3 is waiting on a semaphore thread 2 acquired and never posted before it ended",
+    )?;
+
+    // main waits itself
+    test_exit(
+        "let lock = sem_create(1); fn f() { wait lock; } let t = spawn f(); join t; wait lock;",
+        1,
+        "",
+        "Deadlock: thread 1 is waiting on a semaphore thread 2 acquired and never posted before it ended",
+    )?;
+
+    // once posted by another thread, the semaphore is no longer orphaned
+    let t = r"
+    let lock = sem_create(1);
+    fn forgets() {
+        wait lock;
+    }
+    let a = spawn forgets();
+    join a;
+    post lock;
+    wait lock;
+    println(1);
+    ";
+    test_pass(t, "1")?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_queue() -> Result<()> {
+    // items come out in the order they were pushed, across threads
+    let t = r"
+    let q = queue_create();
+
+    fn consume(n: int) {
+        let i = 0;
+        loop i < n {
+            println(queue_pop(q));
+            i = i + 1;
+        }
+    }
+
+    let t = spawn consume(3);
+    queue_push(q, 1);
+    queue_push(q, 2);
+    yield;
+    queue_push(q, 3);
+    join t;
+    ";
+    test_pass(t, "1\n2\n3")?;
+
+    // popping an empty queue that nothing can fill is a deadlock
+    test_exit(
+        "let q = queue_create(); queue_pop(q)",
+        1,
+        "",
+        "Deadlock: thread 1 is waiting on an empty queue",
+    )?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_map_filter_reduce() -> Result<()> {
+    // the functions are called in queue order, reduce prints each value and sums them
+    let t = r#"
+    fn double(x: int) -> int { x * 2 }
+    fn odd(x: int) -> bool { !(x / 2 * 2 == x) }
+    fn show(acc: int, x: int) -> int { print(x); print(" "); acc + x }
+    let q = queue_create();
+    let i = 0;
+    loop i < 5 {
+        queue_push(q, i);
+        i = i + 1;
+    }
+    println(reduce(map(q, double), 0, show));
+    reduce(filter(q, odd), 0, show)
+    "#;
+    test_pass(t, "0 2 4 6 8 20\n1 3 4")?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_matrix() -> Result<()> {
+    // a matrix is shared like a queue, clone copies it
+    let t = r"
+    let m = matrix(2, 2, 1.0);
+    let alias = m;
+    let copy = clone(m);
+    mat_set(alias, 1, 0, 0.5);
+    println(m);
+    println(copy);
+    set_float_precision(2);
+    println(matmul(m, matrix(2, 1, 2.0)));
+    mat_rows(m) + mat_cols(copy)
+    ";
+    test_pass(
+        t,
+        "[[1.0, 1.0], [0.5, 1.0]]\n[[1.0, 1.0], [1.0, 1.0]]\n[[4.00], [3.00]]\n4",
+    )?;
+
+    test_exit(
+        "mat_get(matrix(2, 2, 0.0), 2, 0)",
+        1,
+        "",
+        "index (2, 0) is out of bounds for a 2x2 matrix",
+    )?;
+    test_exit(
+        "matmul(matrix(2, 3, 0.0), matrix(2, 3, 0.0))",
+        1,
+        "",
+        "can't multiply a 2x3 matrix by a 2x3 matrix",
+    )?;
+    test_exit(
+        "matrix(-1, 2, 0.0)",
+        1,
+        "",
+        "matrix dimensions must not be negative, got -1x2",
+    )?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_join_timeout_is_alive() -> Result<()> {
+    // a supervisor gives up on a thread that never finishes, and joins one that does
+    let t = r"
+    fn spin() {
+        loop {}
+    }
+
+    fn work() -> int {
+        42
+    }
+
+    let slow = spawn spin();
+    let fast = spawn work();
+
+    println(is_alive(slow));
+    println(join_timeout(slow, 50));
+    println(is_alive(slow));
+
+    println(join_timeout(fast, 1000));
+    println(is_alive(fast));
+    ";
+    test_pass(t, "true\n()\ntrue\n42\nfalse")?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_detach() -> Result<()> {
+    let t = r"
+    fn f() -> int {
+        1
+    }
+
+    let t = spawn f();
+    detach(t);
+    join t
+    ";
+    test_exit(t, 1, "", "Thread 2 was detached and can't be joined")?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_cancel() -> Result<()> {
+    // a spinning thread and one blocked on a semaphore nothing will post both end when cancelled
+    let t = r"
+    fn spin() {
+        loop {}
+    }
+
+    let lock : sem = sem_create(0);
+    fn waiter() -> int {
+        wait lock;
+        1
+    }
+
+    fn work() -> int {
+        42
+    }
+
+    let s = spawn spin();
+    let b = spawn waiter();
+    let w = spawn work();
+    yield;
+
+    cancel(s);
+    cancel(b);
+    let r = join s;
+    println(r);
+    let r = join b;
+    println(r);
+
+    // a thread that already finished keeps its result
+    cancel(w);
+    let r = join w;
+    println(r);
+    ";
+    test_pass(t, "cancelled\ncancelled\n42")?;
+
+    let t = r"
+    cancel(current_tid());
+    ";
+    test_exit(
+        t,
+        1,
+        "",
+        "thread 1 can't be cancelled, it does not exist or is the main thread",
+    )?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_select() -> Result<()> {
+    // with a default arm a select never waits
+    let t = r"
+    let q = queue_create_bounded(1);
+    fn poll() {
+        select {
+            let x = queue_pop(q) => { println(x); }
+            default => { println(-1); }
+        }
+    }
+
+    poll();
+    queue_push(q, 5);
+    poll();
+
+    // a full queue can't be pushed to
+    queue_push(q, 6);
+    select {
+        queue_push(q, 7) => { println(7); }
+        default => { println(queue_try_push(q, 8)); }
+    }
+    println(queue_try_pop(q, 0));
+    println(queue_try_pop(q, 0));
+    ";
+    test_pass(
+        t,
+        "-1
+5
+false
+6
+0",
+    )?;
+
+    // break leaves the loop a select is in
+    let t = r"
+    let q = queue_create();
+    queue_push(q, 1);
+    queue_push(q, 2);
+    let n = 0;
+    loop {
+        select {
+            let x = queue_pop(q) => { n = n + x; }
+            default => { break; }
+        }
+    }
+    n
+    ";
+    test_pass(t, "3")?;
+
+    let t = r"
+    let q = queue_create();
+    select {
+        queue_pop(q) => { println(1); }
+    }
+    ";
+    test_exit(
+        t,
+        1,
+        "",
+        "Deadlock: thread 1 is waiting in a select none of whose queues can be used and no other thread can run",
+    )?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_join_invalid() -> Result<()> {
+    let t = r"
+    fn f() -> int {
+        1
+    }
+
+    let t = spawn f();
+    let r = join t;
+    println(r);
+    join t
+    ";
+    test_exit(t, 1, "1\n", "Thread 2 was already joined")?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_call_from_block() -> Result<()> {
+    // returning restores the caller's block, not the function's
+    let t = r"
+    fn f() -> int {
+        1
+    }
+    let z = 5;
+    let r = {
+        let y = f();
+        y + 1
+    };
+    {
+        let w = f();
+        println(w + z);
+    }
+    r
+    ";
+    test_pass(t, "6\n2")?;
+
+    let t = r"
+    fn fact(n: int) -> int {
+        if n < 2 {
+            1
+        } else {
+            let m = fact(n - 1);
+            n * m
+        }
+    }
+    fact(5)
+    ";
+    test_pass(t, "120")?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_return_from_loop() -> Result<()> {
+    let t = r"
+    fn first_multiple(n: int, of: int) -> int {
+        loop {
+            if (n / of) * of == n {
+                return n;
+            }
+            n = n + 1;
+        }
+    }
+    first_multiple(10, 7)
+    ";
+    test_pass(t, "14")?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_let_same_name() -> Result<()> {
+    // a later let of the same name in a block assigns the same variable
+    let t = r"
+    let x = 1;
+    fn get() -> int {
+        x
+    }
+    let a = get();
+    let x = 2;
+    println(a);
+    println(get());
+    let x = x * 10;
+    x
+    ";
+    test_pass(t, "1\n2\n20")?;
+
+    // in a nested block it is a new variable
+    let t = r"
+    let x = 1;
+    {
+        let x = 2;
+        println(x);
+    }
+    x
+    ";
+    test_pass(t, "2\n1")?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_thread_introspection() -> Result<()> {
+    let t = r"
+    fn f() {
+        println(thread_count());
+        println(current_tid());
+    }
+
+    println(current_tid());
+    println(thread_count());
+    let t = spawn f();
+    join t;
+    thread_count()
+    ";
+    test_pass(t, "1\n1\n2\n2\n1")?;
+
+    Ok(())
+}
+
+#[test]
+fn test_oxidate_json() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let file_name = "test.rst";
+    let oxidate_json = || -> Result<_> {
+        Ok(Command::cargo_bin(OXIDATE_BINARY)?
+            .current_dir(&dir)
+            .arg(file_name)
+            .arg("--json")
+            .assert())
+    };
+
+    std::fs::write(dir.path().join(file_name), "x; y;")?;
+    let failed = oxidate_json()?;
+
+    std::fs::write(dir.path().join(file_name), "1 + 2")?;
+    let passed = oxidate_json()?;
+
+    std::fs::write(dir.path().join(file_name), "let x = 1; let x = 2; x")?;
+    let warned = oxidate_json()?;
+
+    // one line per diagnostic, then the summary
+    failed.failure().stdout(predicate::eq(
+        r#"{"code":"E0201","message":"Identifier 'x' not declared","notes":[],"severity":"error","span":null,"stage":"type","type":"diagnostic"}
+{"code":"E0201","message":"Identifier 'y' not declared","notes":[],"severity":"error","span":null,"stage":"type","type":"diagnostic"}
+{"errors":2,"output":null,"success":false,"type":"summary","warnings":0}
+"#,
+    ));
+    passed.success().stdout(predicate::eq(
+        "{\"errors\":0,\"output\":\"test.o2\",\"success\":true,\"type\":\"summary\",\"warnings\":0}\n",
+    ));
+    // warnings don't fail compilation
+    warned.success().stdout(predicate::eq(format!(
+        "{}\n{{\"errors\":0,\"output\":\"test.o2\",\"success\":true,\"type\":\"summary\",\"warnings\":1}}\n",
+        r#"{"code":"W0201","message":"'x' shadows an earlier binding of 'x' in the same block","notes":[],"severity":"warning","span":null,"stage":"type","type":"diagnostic"}"#
+    )));
+
+    Ok(())
+}
+
+#[test]
+fn test_oxidate_typecheck_flags() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let (file_name, o2_name) = ("test.rst", "test.o2");
+
+    std::fs::write(
+        dir.path().join(file_name),
+        "let x = 1; let x = 2; println(x)",
+    )?;
+    let denied = Command::cargo_bin(OXIDATE_BINARY)?
+        .current_dir(&dir)
+        .arg(file_name)
+        .arg("--deny-warnings")
+        .assert();
+    let denied_written = dir.path().join(o2_name).exists();
+
+    Command::cargo_bin(OXIDATE_BINARY)?
+        .current_dir(&dir)
+        .arg(file_name)
+        .arg("--no-typecheck")
+        .assert()
+        .success();
+    let unchecked = ignite()?.current_dir(&dir).arg(o2_name).assert();
+
+    Command::cargo_bin(OXIDATE_BINARY)?
+        .current_dir(&dir)
+        .arg(file_name)
+        .assert()
+        .success();
+    let checked = ignite()?.current_dir(&dir).arg(o2_name).assert();
+
+    denied.failure().stderr(predicate::str::contains(
+        "Compilation failed: 1 warning(s) denied by --deny-warnings",
+    ));
+    assert!(!denied_written);
+    unchecked
+        .success()
+        .stdout(predicate::eq("2\n"))
+        .stderr(predicate::str::contains(format!(
+            "[RuntimeWarning]: {o2_name} was compiled without type checking"
+        )));
+    checked.success().stderr(predicate::str::is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_oxidate_check() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let file_name = "test.rst";
+    let oxidate_check = |args: &[&str]| -> Result<_> {
+        Ok(Command::cargo_bin(OXIDATE_BINARY)?
+            .current_dir(&dir)
+            .args(["check", file_name])
+            .args(args)
+            .assert())
+    };
+
+    std::fs::write(dir.path().join(file_name), "x;\nlet z : int = true;")?;
+    let failed = oxidate_check(&[])?;
+    let failed_json = oxidate_check(&["--json"])?;
+
+    std::fs::write(dir.path().join(file_name), "let x = 1; let x = 2; x")?;
+    let warned = oxidate_check(&[])?;
+    let denied = oxidate_check(&["--deny-warnings"])?;
+    let written = dir.path().join("test.o2").exists();
+
+    // programs the compiler would reject fail the check too
+    std::fs::write(dir.path().join(file_name), "loop { scope { break; } }")?;
+    let scope_break = oxidate_check(&[])?;
+    std::fs::write(dir.path().join(file_name), "fn f() { scope { return; } }")?;
+    let scope_return = oxidate_check(&[])?;
+
+    // every error is printed, not just the first
+    failed
+        .failure()
+        .stderr(predicate::str::contains(
+            "[TypeError]: Identifier 'x' not declared\n[TypeError]: 'z' has declared type int but assigned type bool",
+        ));
+    failed_json.failure().stdout(predicate::str::ends_with(
+        "{\"errors\":2,\"output\":null,\"success\":false,\"type\":\"summary\",\"warnings\":0}\n",
+    ));
+    warned
+        .success()
+        .stdout(predicate::eq(format!("No errors found in {file_name}\n")))
+        .stderr(predicate::str::contains("[TypeWarning]: 'x' shadows"));
+    denied.failure().stderr(predicate::str::contains(
+        "Check failed: 1 warning(s) denied by --deny-warnings",
+    ));
+    // nothing is compiled
+    assert!(!written);
+    scope_break.failure().stderr(predicate::str::contains(
+        "[TypeError]: Can't break out of a scope block: it must run to its end to join the threads spawned in it",
+    ));
+    scope_return.failure().stderr(predicate::str::contains(
+        "[TypeError]: Can't return from a scope block: it must run to its end to join the threads spawned in it",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_oxidate_link() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let (lib, main, out) = ("lib", "main", "prog");
+
+    std::fs::write(
+        dir.path().join(format!("{lib}.rst")),
+        "fn f(x: int) -> int { x + 1 }\nlet base = 10;",
+    )?;
+    // uses names from the other file, so it can't be type checked on its own
+    std::fs::write(
+        dir.path().join(format!("{main}.rst")),
+        "println(f(base));\nprintln(f(41))",
+    )?;
+
+    Command::cargo_bin(OXIDATE_BINARY)?
+        .current_dir(&dir)
+        .arg(format!("{lib}.rst"))
+        .assert()
+        .success();
+    Command::cargo_bin(OXIDATE_BINARY)?
+        .current_dir(&dir)
+        .arg(format!("{main}.rst"))
+        .arg("-n")
+        .assert()
+        .success();
+    let linked = Command::cargo_bin(OXIDATE_BINARY)?
+        .current_dir(&dir)
+        .arg("link")
+        .arg(format!("{lib}.o2"))
+        .arg(format!("{main}.o2"))
+        .arg("-o")
+        .arg(out)
+        .assert();
+    let run = ignite()?
+        .current_dir(&dir)
+        .arg(format!("{out}.o2"))
+        .assert();
+
+    linked
+        .success()
+        .stdout(predicate::eq(format!("Linked successfully to {out}.o2\n")));
+    run.success().stdout(predicate::eq("11\n42\n"));
+
+    Ok(())
+}
+
+#[test]
+fn test_oxidate_link_project() -> Result<()> {
+    let project = "../../example/project";
+    let dir = tempfile::tempdir()?;
+    let out = dir.path().join("project");
+    let out_o2 = out.with_extension("o2");
+    let files = ["config.rst", "shapes.rst", "main.rst"].map(|file| format!("{project}/{file}"));
+
+    // .rst files are compiled by link itself, type checked with the files before them
+    let linked = Command::cargo_bin(OXIDATE_BINARY)?
+        .arg("link")
+        .args(&files)
+        .arg("-o")
+        .arg(&out)
+        .assert();
+    let run = ignite()?.arg(&out_o2).assert();
+    std::fs::remove_file(&out_o2)?;
+
+    linked.success();
+    run.success().stdout(predicate::eq("12\n140\n120\n"));
+
+    // out of order, shapes.rst uses a variable no file before it declares
+    let failed = Command::cargo_bin(OXIDATE_BINARY)?
+        .arg("link")
+        .args([&files[1], &files[0], &files[2]])
+        .arg("-o")
+        .arg(&out)
+        .assert();
+    let written = out_o2.exists();
+
+    failed.failure().stderr(predicate::str::contains(format!(
+        "In {}:\n[TypeError]: Identifier 'scale' not declared",
+        files[1]
+    )));
+    assert!(!written);
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_fn_values() -> Result<()> {
+    let t = r"
+    fn adder(n: int) -> fn(int) -> int {
+        fn add(x: int) -> int {
+            x + n
+        }
+        add
+    }
+    fn twice(h: fn(int) -> int, v: int) -> int {
+        h(h(v))
+    }
+    let a : fn(int) -> int = adder(10);
+    let g = twice;
+    println(a(5));
+    g(a, 1)
+    ";
+    test_pass(t, "15\n21")?;
+
+    // builtins with no result still produce unit when called through another name
+    let t = r"
+    let p = println;
+    p(2);
+    let x = p(3);
+    x
+    ";
+    test_pass(t, "2\n3\n()")?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_call_expr() -> Result<()> {
+    let t = r"
+    fn inc(x: int) -> int {
+        x + 1
+    }
+    fn dec(x: int) -> int {
+        x - 1
+    }
+    fn pick(up: bool) -> fn(int) -> int {
+        if up {
+            inc
+        } else {
+            dec
+        }
+    }
+    println(pick(true)(10));
+    let down = false;
+    (if down { inc } else { dec })(10) + pick(false)(1)
+    ";
+    test_pass(t, "11\n9")?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_named_default_args() -> Result<()> {
+    let t = r#"
+    fn greet(name: str, greeting: str = "hello", times: int = 1) {
+        let i = 0;
+        loop i < times {
+            print(greeting);
+            print(" ");
+            println(name);
+            i = i + 1;
+        }
+    }
+    greet("Ann");
+    greet(name: "Bob", greeting: "hi");
+    greet("Cy", times: 2);
+    let t = spawn greet(greeting: "yo", name: "Di");
+    join t;
+    "#;
+    test_pass(t, "hello Ann\nhi Bob\nhello Cy\nhello Cy\nyo Di")?;
+
+    // arguments are evaluated in the order written, whatever the parameter order
+    let t = r"
+    fn side(x: int) -> int {
+        println(x);
+        x
+    }
+    fn sub(a: int, b: int) -> int {
+        a - b
+    }
+    sub(b: side(1), a: side(2))
+    ";
+    test_pass(t, "1\n2\n1")?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_cast() -> Result<()> {
+    let t = r"
+    let total = 7;
+    let count = 2;
+    println(total as float / count as float);
+    println(-2.7 as int);
+    println(100000000000000000000.0 as int);
+    true as int + 1
+    ";
+    test_pass(t, "3.5\n-2\n9223372036854775807\n2")?;
+
+    Ok(())
+}
+
+#[test]
+fn test_e2e_fn_main() -> Result<()> {
+    // main runs after the rest of the top level, even code after it
+    let t = r#"
+    let limit = 20;
+
+    fn helper(x: int) -> int {
+        x + 22
+    }
+
+    fn main() -> int {
+        println("in main");
+        helper(limit)
+    }
+
+    println("top level");
+    "#;
+    test_pass(t, "top level\nin main\n42")?;
+
+    // main can spawn and join threads like any other function
+    let t = r"
+    fn work(n: int) -> int {
+        n * 2
+    }
+
+    fn main() {
+        let t = spawn work(21);
+        let r = join t;
+        println(r);
+    }
+    ";
+    test_pass(t, "42")?;
+
+    Ok(())
+}
@@ -2,13 +2,24 @@ use anyhow::Result;
 use assert_cmd::prelude::*;
 use bytecode::ByteCode;
 use predicates::prelude::*;
+use std::path::Path;
 use std::process::Command;
 
 const IGNITE_BINARY: &str = "ignite";
 
+/// ignite, caching the .rst files it runs in the tests' own directory rather than the user's cache
+fn ignite() -> Result<Command> {
+    let mut cmd = Command::cargo_bin(IGNITE_BINARY)?;
+    cmd.env(
+        "RUSTSCRIPT_CACHE_DIR",
+        Path::new(env!("CARGO_TARGET_TMPDIR")).join("rustscript-cache"),
+    );
+    Ok(cmd)
+}
+
 #[test]
 fn file_doesnt_exist() -> Result<()> {
-    let mut cmd = Command::cargo_bin(IGNITE_BINARY)?;
+    let mut cmd = ignite()?;
 
     cmd.arg("test/file/doesnt/exist");
     cmd.assert()
@@ -20,7 +31,7 @@ fn file_doesnt_exist() -> Result<()> {
 
 #[test]
 fn file_not_o2() -> Result<()> {
-    let mut cmd = Command::cargo_bin(IGNITE_BINARY)?;
+    let mut cmd = ignite()?;
 
     cmd.arg("Cargo.toml");
     cmd.assert()
@@ -32,7 +43,7 @@ fn file_not_o2() -> Result<()> {
 
 #[test]
 fn run_simple_program() -> Result<()> {
-    let mut cmd = Command::cargo_bin(IGNITE_BINARY)?;
+    let mut cmd = ignite()?;
 
     let bytecode = vec![
         ByteCode::ldc(42),
@@ -52,3 +63,895 @@ fn run_simple_program() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn run_rst_file() -> Result<()> {
+    let mut cmd = ignite()?;
+
+    cmd.arg("run")
+        .arg("../../example/function-01.rst")
+        .arg("--echo-result");
+    cmd.assert().success().stdout(predicate::eq("7\n"));
+
+    // compiled in memory: no bytecode file left behind
+    assert!(!std::path::Path::new("function-01.o2").exists());
+
+    Ok(())
+}
+
+#[test]
+fn run_script() -> Result<()> {
+    let file_name = format!("./{}.rst", rand::random::<u128>());
+    let src = "#!/usr/bin/env ignite\nlet x = 2;\nprintln(x + 3);\n";
+    std::fs::write(&file_name, src)?;
+
+    // no run subcommand needed for source files
+    let mut cmd = ignite()?;
+    cmd.arg(&file_name);
+    let script_run = cmd.assert();
+
+    std::fs::remove_file(&file_name)?;
+
+    script_run.success().stdout(predicate::eq("5\n"));
+
+    Ok(())
+}
+
+#[test]
+fn run_rst_file_errs() -> Result<()> {
+    let mut cmd = ignite()?;
+    cmd.arg("run").arg("Cargo.toml");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("File is not a .rst file"));
+
+    let mut cmd = ignite()?;
+    cmd.arg("run").arg("../../example/assignment-01.rst");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("[TypeError]"));
+
+    // type errors are skipped with -n, same as oxidate
+    let mut cmd = ignite()?;
+    cmd.arg("run")
+        .arg("../../example/type-01.rst")
+        .arg("-n")
+        .arg("--echo-result");
+    cmd.assert().success().stdout(predicate::eq("33\n"));
+
+    Ok(())
+}
+
+#[test]
+fn run_error_source_line() -> Result<()> {
+    let code = r#"
+    fn f(n: int) -> int {
+        10 / n
+    }
+    f(0)
+    "#;
+    let file_name = format!("./{}.rst", rand::random::<u128>());
+    std::fs::write(&file_name, code)?;
+
+    let mut cmd = ignite()?;
+    cmd.arg("run").arg(&file_name);
+    let res = cmd.assert();
+    std::fs::remove_file(&file_name)?;
+
+    res.failure().stderr(predicate::str::contains(format!(
+        "Division by zero at {}:3",
+        file_name
+    )));
+
+    Ok(())
+}
+
+#[test]
+fn run_float_precision() -> Result<()> {
+    let file_name = format!("./{}.rst", rand::random::<u128>());
+    std::fs::write(&file_name, "println(2.0 / 3.0); println(1.0)")?;
+
+    let mut cmd = ignite()?;
+    cmd.arg("run")
+        .arg(&file_name)
+        .arg("--float-precision")
+        .arg("3");
+    let res = cmd.assert();
+
+    let mut cmd = ignite()?;
+    cmd.arg("run")
+        .arg(&file_name)
+        .arg("--float-precision")
+        .arg("101");
+    let too_many = cmd.assert();
+    std::fs::remove_file(&file_name)?;
+
+    res.success().stdout(predicate::eq("0.667\n1.000\n"));
+    too_many.failure();
+
+    Ok(())
+}
+
+#[test]
+fn run_echo_result() -> Result<()> {
+    let file_name = format!("./{}.rst", rand::random::<u128>());
+    std::fs::write(&file_name, "println(\"out\"); 42")?;
+
+    // only the REPL echoes results by default
+    let mut cmd = ignite()?;
+    cmd.args(["run", &file_name, "--no-cache"]);
+    let not_echoed = cmd.assert();
+
+    let mut cmd = ignite()?;
+    cmd.args(["run", &file_name, "--no-cache", "--echo-result"]);
+    let echoed = cmd.assert();
+
+    let mut cmd = ignite()?;
+    cmd.args([&file_name, "--echo-result", "--no-echo-result"]);
+    let both = cmd.assert();
+    std::fs::remove_file(&file_name)?;
+
+    not_echoed.success().stdout(predicate::eq("out\n"));
+    echoed.success().stdout(predicate::eq("out\n42\n"));
+    both.failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+
+    Ok(())
+}
+
+#[test]
+fn run_max_zombies() -> Result<()> {
+    let file_name = format!("./{}.rst", rand::random::<u128>());
+    let src = r"
+    fn f() -> int {
+        1
+    }
+    let t1 = spawn f();
+    let t2 = spawn f();
+    let t3 = spawn f();
+    let r = join t3;
+    println(r);
+    let r1 = join t1;
+    println(r1);
+    ";
+    std::fs::write(&file_name, src)?;
+
+    // unjoined threads are kept by default
+    let mut cmd = ignite()?;
+    cmd.arg("run").arg(&file_name);
+    let default_run = cmd.assert();
+
+    // t1 and t2 are reaped when t3 finishes
+    let mut cmd = ignite()?;
+    cmd.arg("run").arg(&file_name).arg("--max-zombies").arg("1");
+    let limited_run = cmd.assert();
+
+    std::fs::remove_file(&file_name)?;
+
+    default_run.success().stdout(predicate::eq("1\n1\n"));
+    limited_run
+        .failure()
+        .stdout(predicate::eq("1\n"))
+        .stderr(predicate::str::contains(
+            "Thread 2 finished and was reaped before being joined",
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn run_gc_stress() -> Result<()> {
+    // collecting before every instruction should not change the output
+    for (file, exp) in [
+        ("higher-order-fn-01", "9\n"),
+        (
+            "simple-join",
+            "before spawn func\nafter spawn func\ninside func\n500\n",
+        ),
+    ] {
+        let mut cmd = ignite()?;
+        cmd.arg("run")
+            .arg(format!("../../example/{file}.rst"))
+            .arg("--gc-stress")
+            .arg("--echo-result");
+        cmd.assert().success().stdout(predicate::eq(exp));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn run_gc_alloc_threshold() -> Result<()> {
+    // collecting after every new environment should not change the output
+    let mut cmd = ignite()?;
+    cmd.arg("run")
+        .arg("../../example/higher-order-fn-02.rst")
+        .args(["--gc-alloc-threshold", "1"])
+        .arg("--paranoid-gc");
+    cmd.assert().success();
+
+    Ok(())
+}
+
+#[test]
+fn run_paranoid_gc() -> Result<()> {
+    let mut cmd = ignite()?;
+    cmd.arg("run")
+        .arg("../../example/higher-order-fn-02.rst")
+        .arg("--gc-stress")
+        .arg("--paranoid-gc");
+    cmd.assert().success();
+
+    Ok(())
+}
+
+#[test]
+fn run_log_level() -> Result<()> {
+    let exp = "before spawn func\nafter spawn func\ninside func\n500\n";
+
+    // logs go to stderr, the program's output is unchanged
+    let mut cmd = ignite()?;
+    cmd.arg("run")
+        .arg("../../example/simple-join.rst")
+        .arg("--gc-stress")
+        .arg("--echo-result")
+        .args(["--log-level", "debug"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::eq(exp))
+        .stderr(predicate::str::contains(
+            "[INFO ignite::cli] Finished after",
+        ))
+        .stderr(predicate::str::contains("Thread 1 spawned thread 2"))
+        .stderr(predicate::str::contains("Thread 1 joined thread 2"))
+        .stderr(predicate::str::contains(
+            "[DEBUG ignite::runtime::gc] Mark begin",
+        ))
+        .stderr(predicate::str::contains("PC:").not());
+
+    let mut cmd = ignite()?;
+    cmd.arg("run")
+        .arg("../../example/simple-join.rst")
+        .arg("--echo-result")
+        .args(["--log-level", "trace"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::eq(exp))
+        .stderr(predicate::str::contains(
+            "[TRACE ignite::runtime::run] Thread: 1, PC: 0",
+        ));
+
+    // nothing is logged by default
+    let mut cmd = ignite()?;
+    cmd.arg("run").arg("../../example/simple-join.rst");
+    cmd.assert().success().stderr(predicate::str::is_empty());
+
+    let mut cmd = ignite()?;
+    cmd.arg("run")
+        .arg("../../example/simple-join.rst")
+        .args(["--log-level", "loud"]);
+    cmd.assert().failure().stderr(predicate::str::contains(
+        "possible values: trace, debug, info",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn run_compile_cache() -> Result<()> {
+    let file_name = format!("./{}.rst", rand::random::<u128>());
+    let cache_dir = tempfile::tempdir()?;
+    let run = |extra: &[&str]| -> Result<_> {
+        let mut cmd = ignite()?;
+        cmd.env("RUSTSCRIPT_CACHE_DIR", cache_dir.path())
+            .args(["run", &file_name, "--log-level", "debug"])
+            .args(extra);
+        Ok(cmd.assert())
+    };
+
+    std::fs::write(&file_name, "println(1 + 2)")?;
+    let first = run(&[])?;
+    let second = run(&[])?;
+    let uncached = run(&["--no-cache"])?;
+    std::fs::write(&file_name, "println(1 + 3)")?;
+    let changed = run(&[])?;
+
+    std::fs::remove_file(&file_name)?;
+    let cached_files = std::fs::read_dir(cache_dir.path())?.count();
+
+    let not_cached = format!("{file_name} is not cached");
+    first
+        .success()
+        .stdout(predicate::eq("3\n"))
+        .stderr(predicate::str::contains(not_cached.as_str()))
+        .stderr(predicate::str::contains(format!("Cached {file_name} at")));
+    second
+        .success()
+        .stdout(predicate::eq("3\n"))
+        .stderr(predicate::str::contains(format!(
+            "Using {file_name} cached at"
+        )));
+    uncached
+        .success()
+        .stdout(predicate::eq("3\n"))
+        .stderr(predicate::str::contains("cache").not());
+    changed
+        .success()
+        .stdout(predicate::eq("4\n"))
+        .stderr(predicate::str::contains(not_cached.as_str()));
+    assert_eq!(cached_files, 2);
+
+    Ok(())
+}
+
+#[test]
+fn run_edition() -> Result<()> {
+    let file_name = format!("./{}.rst", rand::random::<u128>());
+    std::fs::write(&file_name, "let match = 2; println(match * 3)")?;
+
+    let mut cmd = ignite()?;
+    cmd.args(["run", &file_name, "--no-cache"]);
+    let default_run = cmd.assert();
+
+    let mut cmd = ignite()?;
+    cmd.args(["run", &file_name, "--no-cache", "--edition", "2027"]);
+    let next_run = cmd.assert();
+
+    std::fs::remove_file(&file_name)?;
+
+    default_run.success().stdout(predicate::eq("6\n"));
+    next_run.failure().stderr(predicate::str::contains(
+        "'match' is a keyword in edition 2027 and can't be used as a name",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn run_inline() -> Result<()> {
+    // closures, shadowing, functions called from loops and threads: inlining changes none of it
+    let src = r"
+    let k = 10;
+    fn scale(x: int) -> int { x * k }
+    fn adder(n: int) -> fn(int) -> int {
+        fn add(x: int) -> int { x + n }
+        add
+    }
+    let add2 = adder(2);
+    fn twice(x: int) -> int { add2(add2(x)) }
+    let total = 0;
+    fn bump(x: int) { total = total + x; }
+    let i = 0;
+    loop i < 3 {
+        let k = 100;
+        bump(scale(i));
+        i = i + 1;
+    }
+    fn sq(x: int) -> int { x * x }
+    let t = spawn sq(4);
+    let r = join t;
+    let x = 1;
+    println(sq(x + 1));
+    println(twice(k));
+    println(total);
+    println(r);
+    ";
+    let file_name = format!("./{}.rst", rand::random::<u128>());
+    std::fs::write(&file_name, src)?;
+
+    let mut cmd = ignite()?;
+    cmd.args(["run", &file_name, "--no-cache"]);
+    let plain = cmd.assert();
+
+    let mut cmd = ignite()?;
+    cmd.args(["run", &file_name, "--no-cache", "--inline"]);
+    let inlined = cmd.assert();
+
+    std::fs::remove_file(&file_name)?;
+
+    let exp = "4\n14\n30\n16\n";
+    plain.success().stdout(predicate::eq(exp));
+    inlined.success().stdout(predicate::eq(exp));
+
+    Ok(())
+}
+
+#[test]
+fn run_fold_constants() -> Result<()> {
+    // constants used as is, in a loop, and shadowed in a function: folding changes none of it
+    let src = r"
+    fn area(r: float) -> float { PI * r * r }
+    fn shadow(PI: int) -> int { PI + 1 }
+    let n = 0;
+    let x = 0.0;
+    loop n < 3 {
+        x = x + area(1.0) + E;
+        n = n + 1;
+    }
+    println(x);
+    println(shadow(2));
+    println(MAX_INT);
+    ";
+    let file_name = format!("./{}.rst", rand::random::<u128>());
+    std::fs::write(&file_name, src)?;
+
+    let mut cmd = ignite()?;
+    cmd.args(["run", &file_name, "--no-cache"]);
+    let plain = cmd.assert();
+
+    let mut cmd = ignite()?;
+    cmd.args(["run", &file_name, "--no-cache", "--fold-constants"]);
+    let folded = cmd.assert();
+
+    std::fs::remove_file(&file_name)?;
+
+    let exp = "17.579623446146513\n3\n9223372036854775807\n";
+    plain.success().stdout(predicate::eq(exp));
+    folded.success().stdout(predicate::eq(exp));
+
+    Ok(())
+}
+
+#[test]
+fn run_timeout() -> Result<()> {
+    let file_name = format!("./{}.rst", rand::random::<u128>());
+    let src = r"
+    print(1);
+    loop {}
+    ";
+    std::fs::write(&file_name, src)?;
+
+    let mut cmd = ignite()?;
+    cmd.arg("run").arg(&file_name).arg("--timeout").arg("200ms");
+    let timeout_run = cmd.assert();
+
+    let mut cmd = ignite()?;
+    cmd.arg("run")
+        .arg(&file_name)
+        .arg("--max-instr")
+        .arg("1000");
+    let max_instr_run = cmd.assert();
+
+    let mut cmd = ignite()?;
+    cmd.arg("run").arg(&file_name).arg("--timeout").arg("5h");
+    let bad_unit_run = cmd.assert();
+
+    std::fs::remove_file(&file_name)?;
+
+    // output printed before the limit is hit is not lost
+    timeout_run
+        .failure()
+        .stdout(predicate::eq("1"))
+        .stderr(predicate::str::contains(
+            "Timeout exceeded: program ran for longer than 200ms",
+        ));
+    max_instr_run
+        .failure()
+        .stdout(predicate::eq("1"))
+        .stderr(predicate::str::contains(
+            "Timeout exceeded: program executed more than 1000 instructions",
+        ));
+    bad_unit_run
+        .failure()
+        .stderr(predicate::str::contains("unknown duration unit 'h'"));
+
+    Ok(())
+}
+
+#[test]
+fn run_resource_limits() -> Result<()> {
+    let file_name = format!("./{}.rst", rand::random::<u128>());
+    let src = r"
+    fn f(n: int) -> int {
+        f(n + 1)
+    }
+    f(0)
+    ";
+    std::fs::write(&file_name, src)?;
+
+    let mut cmd = ignite()?;
+    cmd.arg("run").arg(&file_name).arg("--max-envs").arg("1000");
+    let recursion_run = cmd.assert();
+
+    std::fs::remove_file(&file_name)?;
+
+    recursion_run.failure().stderr(predicate::str::contains(
+        "Resource limit exceeded: program has more than 1000 environments",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn run_on_main_exit() -> Result<()> {
+    let file_name = format!("./{}.rst", rand::random::<u128>());
+    let src = r#"
+    fn worker(name: str) {
+        yield;
+        println(name);
+    }
+
+    let t1 = spawn worker("attached");
+    let t2 = spawn worker("detached");
+    detach(t2);
+    println("main done");
+    "#;
+    std::fs::write(&file_name, src)?;
+
+    // other threads are killed by default
+    let mut cmd = ignite()?;
+    cmd.arg("run").arg(&file_name);
+    let kill_run = cmd.assert();
+
+    let mut cmd = ignite()?;
+    cmd.arg("run")
+        .arg(&file_name)
+        .arg("--on-main-exit")
+        .arg("wait");
+    let wait_run = cmd.assert();
+
+    std::fs::remove_file(&file_name)?;
+
+    kill_run.success().stdout(predicate::eq("main done\n"));
+    wait_run
+        .success()
+        .stdout(predicate::str::contains("main done\n"))
+        .stdout(predicate::str::contains("attached\n"));
+
+    Ok(())
+}
+
+#[test]
+fn run_on_thread_error() -> Result<()> {
+    let file_name = format!("./{}.rst", rand::random::<u128>());
+    let src = r#"
+    fn divide(x: int, y: int) -> int {
+        x / y
+    }
+
+    let ok = spawn divide(10, 2);
+    let failed = spawn divide(1, 0);
+    let unjoined = spawn divide(2, 0);
+    let result = join ok;
+    println(result);
+    println("main done");
+    "#;
+    std::fs::write(&file_name, src)?;
+
+    // by default the failing thread ends the program
+    let mut cmd = ignite()?;
+    cmd.args(["run", &file_name, "--no-cache"]);
+    let fail_fast_run = cmd.assert();
+
+    let mut cmd = ignite()?;
+    cmd.args([
+        "run",
+        &file_name,
+        "--no-cache",
+        "--on-thread-error",
+        "isolate",
+    ]);
+    let isolate_run = cmd.assert();
+
+    // joining the failed thread fails the program with the thread's error
+    std::fs::write(&file_name, src.replace("join ok", "join failed"))?;
+    let mut cmd = ignite()?;
+    cmd.args([
+        "run",
+        &file_name,
+        "--no-cache",
+        "--on-thread-error",
+        "isolate",
+    ]);
+    let join_failed_run = cmd.assert();
+
+    std::fs::remove_file(&file_name)?;
+
+    fail_fast_run
+        .failure()
+        .stderr(predicate::str::contains("Division by zero"));
+    isolate_run
+        .success()
+        .stdout(predicate::eq("5\nmain done\n"))
+        .stderr(predicate::str::contains(format!(
+            "Thread 3 failed and it was never joined: Division by zero at {}:3",
+            file_name
+        )))
+        .stderr(predicate::str::contains(
+            "Thread 4 failed and it was never joined",
+        ));
+    join_failed_run
+        .failure()
+        .stderr(predicate::str::contains(format!(
+            "Thread 3 failed: Division by zero at {}:3",
+            file_name
+        )));
+
+    Ok(())
+}
+
+#[test]
+fn run_sched_stats() -> Result<()> {
+    let file_name = format!("./{}.rst", rand::random::<u128>());
+    let src = r#"
+    fn work() {
+        yield;
+    }
+
+    let t = spawn work();
+    join t;
+    let stats = sched_stats();
+    println(json_get(stats, "threads.1.thread_id"));
+    println(json_get(stats, "threads.1.finished"));
+    "#;
+    std::fs::write(&file_name, src)?;
+
+    let mut cmd = ignite()?;
+    cmd.args(["run", &file_name, "--no-cache", "--sched-stats"]);
+    let assert = cmd.assert();
+
+    std::fs::remove_file(&file_name)?;
+
+    assert
+        .success()
+        .stdout(predicate::eq("2\ntrue\n"))
+        .stderr(predicate::str::contains("Context switches: "))
+        .stderr(predicate::str::contains("Average ready queue wait: "))
+        .stderr(predicate::str::contains("finished"));
+
+    Ok(())
+}
+
+#[test]
+fn run_record_replay() -> Result<()> {
+    let file_name = format!("./{}.rst", rand::random::<u128>());
+    let log_name = format!("./{}.log", rand::random::<u128>());
+    let src = r#"
+    fn worker(name: str) {
+        let i = 0;
+        loop i < 300 {
+            print(name);
+            i = i + 1;
+        }
+    }
+
+    let t1 = spawn worker("a");
+    let t2 = spawn worker("b");
+    let line = read_line();
+    join t1;
+    join t2;
+    println("");
+    println(line);
+    "#;
+    std::fs::write(&file_name, src)?;
+
+    let mut cmd = assert_cmd::Command::from_std(ignite()?);
+    cmd.arg("run")
+        .arg(&file_name)
+        .arg("-q")
+        .arg("1")
+        .arg("--record")
+        .arg(&log_name)
+        .write_stdin("hello\n");
+    let recorded = cmd.output()?;
+
+    // stdin is not read and the quantum does not matter when replaying
+    let mut cmd = ignite()?;
+    cmd.arg("run")
+        .arg(&file_name)
+        .arg("-q")
+        .arg("1000")
+        .arg("--replay")
+        .arg(&log_name);
+    let replayed = cmd.assert();
+
+    // a different program does not match the log
+    std::fs::write(&file_name, "println(1);")?;
+    let mut cmd = ignite()?;
+    cmd.arg("run")
+        .arg(&file_name)
+        .arg("--replay")
+        .arg(&log_name);
+    let diverged = cmd.assert();
+
+    std::fs::remove_file(&file_name)?;
+    std::fs::remove_file(&log_name)?;
+
+    assert!(recorded.status.success());
+    assert!(String::from_utf8(recorded.stdout.clone())?.contains("\nhello\n"));
+    replayed.success().stdout(recorded.stdout);
+    diverged
+        .failure()
+        .stderr(predicate::str::contains("Replay diverged"));
+
+    Ok(())
+}
+
+#[test]
+fn run_stdin_filters() -> Result<()> {
+    let file_name = format!("./{}.rst", rand::random::<u128>());
+
+    let src = r"
+    let count = 0;
+    let chars = 0;
+    loop !eof() {
+        chars = chars + string_len(read_line());
+        count = count + 1;
+    }
+    println(count);
+    println(chars);
+    ";
+    std::fs::write(&file_name, src)?;
+    let mut cmd = assert_cmd::Command::from_std(ignite()?);
+    cmd.arg(&file_name).write_stdin("a\nbb\nccc");
+    let lines_run = cmd.assert();
+
+    let src = r"
+    let first = read_line();
+    println(first);
+    print(read_all());
+    println(eof());
+    ";
+    std::fs::write(&file_name, src)?;
+    let mut cmd = assert_cmd::Command::from_std(ignite()?);
+    cmd.arg(&file_name).write_stdin("1\n2\n3\n");
+    let all_run = cmd.assert();
+
+    // empty lines and the last line without a newline are told apart from the end of input
+    let src = r"
+    loop !eof() {
+        println(string_len(read_line()));
+    }
+    println(string_len(read_line()));
+    ";
+    std::fs::write(&file_name, src)?;
+    let mut cmd = assert_cmd::Command::from_std(ignite()?);
+    cmd.arg(&file_name).write_stdin("ab\r\n\n\nc");
+    let empty_run = cmd.assert();
+
+    std::fs::remove_file(&file_name)?;
+
+    // line endings are not part of the line
+    lines_run.success().stdout(predicate::eq("3\n6\n"));
+    empty_run.success().stdout(predicate::eq("2\n0\n0\n1\n0\n"));
+    all_run.success().stdout(predicate::eq("1\n2\n3\ntrue\n"));
+
+    Ok(())
+}
+
+#[test]
+fn run_validate_input() -> Result<()> {
+    let file_name = format!("./{}.rst", rand::random::<u128>());
+
+    // invalid lines are skipped instead of stopping the VM
+    let src = r#"
+    let sum = 0;
+    let bad = 0;
+    loop !eof() {
+        let n = parse_int(read_line());
+        if parse_failed() {
+            bad = bad + 1;
+        } else {
+            sum = sum + n;
+        }
+    }
+    println(sum);
+    println(bad);
+    "#;
+    std::fs::write(&file_name, src)?;
+    let mut cmd = assert_cmd::Command::from_std(ignite()?);
+    cmd.arg(&file_name).write_stdin("1\ntwo\n 3 \n\n0x4\n");
+    let run = cmd.assert();
+
+    std::fs::remove_file(&file_name)?;
+
+    run.success().stdout(predicate::eq("4\n3\n"));
+
+    Ok(())
+}
+
+#[test]
+fn run_json_config() -> Result<()> {
+    let file_name = format!("./{}.rst", rand::random::<u128>());
+
+    let src = r#"
+    let config = read_all();
+    println(json_get(config, "name"));
+    let port = parse_int(json_get(config, "servers.1.port"));
+    println(port + 1);
+    let missing = json_get(config, "servers.2.port");
+    println(parse_failed());
+    println(json_get(config, "servers.0"));
+    "#;
+    std::fs::write(&file_name, src)?;
+    let mut cmd = assert_cmd::Command::from_std(ignite()?);
+    cmd.arg(&file_name)
+        .write_stdin(r#"{"name": "web", "servers": [{"port": 80}, {"port": 8080, "tls": true}]}"#);
+    let run = cmd.assert();
+
+    std::fs::remove_file(&file_name)?;
+
+    run.success()
+        .stdout(predicate::eq("web\n8081\ntrue\n{\"port\":80}\n"));
+
+    Ok(())
+}
+
+#[test]
+fn run_busy_wait() -> Result<()> {
+    let file_name = format!("./{}.rst", rand::random::<u128>());
+
+    // main spins until the worker is done, instead of waiting on a semaphore
+    let src = r"
+    let done = false;
+    let total = 0;
+    fn work() {
+        let i = 0;
+        loop i < 20000 {
+            total = total + i;
+            i = i + 1;
+        }
+        done = true;
+    }
+    let t = spawn work();
+    loop !done {}
+    join t;
+    println(total);
+    ";
+    std::fs::write(&file_name, src)?;
+
+    let mut cmd = ignite()?;
+    cmd.args(["run", &file_name, "--no-cache"]);
+    let warned = cmd.assert();
+
+    let mut cmd = ignite()?;
+    cmd.args(["run", &file_name, "--no-cache", "--allow-busy-wait"]);
+    let allowed = cmd.assert();
+
+    std::fs::remove_file(&file_name)?;
+
+    // warned about once, however often it is found spinning
+    warned
+        .success()
+        .stdout(predicate::eq("199990000\n"))
+        .stderr(predicate::str::contains("Thread 1 is busy waiting in the loop at").count(1))
+        .stderr(predicate::str::contains(format!("{}:13", file_name)));
+    allowed
+        .success()
+        .stdout(predicate::eq("199990000\n"))
+        .stderr(predicate::str::is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn repl_session() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let saved = dir.path().join("session.rst");
+    let saved = saved.to_string_lossy();
+
+    // each line can use what the lines before it declared
+    let mut cmd = assert_cmd::Command::from_std(ignite()?);
+    cmd.arg("--repl").write_stdin(format!(
+        "let x = 2;\nfn f() -> int {{ x * 10 }}\nf() + x\n:vars\n:save {saved}\n/exit\n"
+    ));
+    cmd.assert().success().stdout(predicate::str::contains(
+        "\n22\nname  type\nf     Closure\nx     Int\nSaved 2 line(s)",
+    ));
+    // only the declarations are saved
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("session.rst"))?,
+        "let x = 2;\nfn f() -> int { x * 10 }\n"
+    );
+
+    // a restored session's names stay for the lines after it
+    let mut cmd = assert_cmd::Command::from_std(ignite()?);
+    cmd.arg("--repl")
+        .write_stdin(format!(":restore {saved}\nx + 1\n/exit\n"));
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("\n3\nSee you again!"))
+        .stderr(predicate::str::is_empty());
+
+    Ok(())
+}